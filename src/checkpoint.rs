@@ -0,0 +1,248 @@
+//! Checkpoints of long conversions with `--rows-per-file` (`--checkpoint`):
+//! every time an output file is finished, the finished files and the number
+//! of addresses of every input written to them are saved as JSON. With
+//! `--resume` the conversion continues after the last checkpoint: inputs
+//! that were written completely are not parsed again, the addresses already
+//! written of the input that was being read are skipped and the output files
+//! are numbered on from the last finished one.
+
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+
+/// Addresses of an input (a file, or a file inside a ZIP archive) written to
+/// the finished output files.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct InputProgress {
+    pub path: PathBuf,
+    pub zip_entry: Option<String>,
+    pub rows: usize,
+    /// All addresses of the input were written.
+    pub complete: bool,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Checkpoint {
+    /// `--output-path` and `--rows-per-file` of the run, which must not
+    /// change when it is resumed.
+    pub output_path: PathBuf,
+    pub rows_per_file: usize,
+    /// Hash of the other options changing the written addresses (filters,
+    /// added columns and so on), which must not change either.
+    #[serde(default)]
+    pub options_hash: u64,
+    /// Output files finished so far.
+    pub output_files: Vec<PathBuf>,
+    /// Inputs in the order they were read.
+    pub inputs: Vec<InputProgress>,
+}
+
+impl Checkpoint {
+    /// Reads a checkpoint, `None` if the file does not exist.
+    pub fn read(path: &Path) -> anyhow::Result<Option<Self>> {
+        let content = match std::fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => {
+                return Err(e)
+                    .with_context(|| format!("Could not read checkpoint `{}`.", path.display()));
+            }
+        };
+        serde_json::from_str(&content)
+            .map(Some)
+            .with_context(|| format!("Could not parse checkpoint `{}`.", path.display()))
+    }
+
+    /// Writes the checkpoint to a temporary file next to `path` and renames
+    /// it, so that `path` always holds a whole checkpoint.
+    pub fn write(&self, path: &Path) -> anyhow::Result<()> {
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        let mut file = tempfile::NamedTempFile::new_in(dir)
+            .with_context(|| format!("Could not write checkpoint `{}`.", path.display()))?;
+        serde_json::to_writer_pretty(&mut file, self)
+            .with_context(|| format!("Could not write checkpoint `{}`.", path.display()))?;
+        file.persist(path)
+            .with_context(|| format!("Could not write checkpoint `{}`.", path.display()))?;
+        Ok(())
+    }
+
+    /// Progress of the input `path` (`zip_entry` inside it).
+    pub fn input(&self, path: &Path, zip_entry: Option<&str>) -> Option<&InputProgress> {
+        self.inputs
+            .iter()
+            .find(|input| input.path == path && input.zip_entry.as_deref() == zip_entry)
+    }
+}
+
+/// Checkpoint of a running conversion, shared by the loop over the inputs
+/// (which starts and completes inputs) and the output writer (which counts
+/// the written rows and saves the checkpoint when a file is finished).
+#[derive(Clone)]
+pub struct Checkpointer {
+    path: PathBuf,
+    state: Arc<Mutex<State>>,
+}
+
+struct State {
+    checkpoint: Checkpoint,
+    /// Checkpoint the run continues from.
+    resumed: Option<Checkpoint>,
+    /// Rows of the current input still to be skipped because they were
+    /// written before the run was resumed.
+    skip_rows: usize,
+}
+
+impl Checkpointer {
+    /// `resumed` is the checkpoint the run continues from.
+    pub fn new(
+        path: PathBuf,
+        output_path: PathBuf,
+        rows_per_file: usize,
+        options_hash: u64,
+        resumed: Option<Checkpoint>,
+    ) -> Self {
+        let checkpoint = Checkpoint {
+            output_path,
+            rows_per_file,
+            options_hash,
+            output_files: resumed
+                .as_ref()
+                .map_or_else(Vec::new, |c| c.output_files.clone()),
+            inputs: Vec::new(),
+        };
+        Self {
+            path,
+            state: Arc::new(Mutex::new(State {
+                checkpoint,
+                resumed,
+                skip_rows: 0,
+            })),
+        }
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().expect("Checkpoint lock poisoned.")
+    }
+
+    /// Output files finished before the run was resumed.
+    pub fn output_files(&self) -> Vec<PathBuf> {
+        self.state().checkpoint.output_files.clone()
+    }
+
+    /// Starts reading an input. Returns its number of rows if it was written
+    /// completely before the run was resumed, then it is not to be read
+    /// again. Otherwise the rows of it written before are skipped by
+    /// `take_skipped_rows`.
+    pub fn start_input(&self, path: &Path, zip_entry: Option<&str>) -> Option<usize> {
+        let mut state = self.state();
+        let resumed = state
+            .resumed
+            .as_ref()
+            .and_then(|c| c.input(path, zip_entry))
+            .cloned();
+        let (rows, complete) = resumed.map_or((0, false), |input| (input.rows, input.complete));
+        state.checkpoint.inputs.push(InputProgress {
+            path: path.to_path_buf(),
+            zip_entry: zip_entry.map(str::to_string),
+            rows,
+            complete,
+        });
+        state.skip_rows = if complete { 0 } else { rows };
+        complete.then_some(rows)
+    }
+
+    /// Marks the current input as read completely.
+    pub fn complete_input(&self) {
+        let mut state = self.state();
+        if let Some(input) = state.checkpoint.inputs.last_mut() {
+            input.complete = true;
+        }
+        state.skip_rows = 0;
+    }
+
+    /// Number of the next `rows` of the current input to skip, which is
+    /// taken off the rows to be skipped.
+    pub fn take_skipped_rows(&self, rows: usize) -> usize {
+        let mut state = self.state();
+        let skipped = state.skip_rows.min(rows);
+        state.skip_rows -= skipped;
+        skipped
+    }
+
+    /// Counts `rows` of the current input written to the output.
+    pub fn add_rows(&self, rows: usize) {
+        if let Some(input) = self.state().checkpoint.inputs.last_mut() {
+            input.rows += rows;
+        }
+    }
+
+    /// Saves the checkpoint after `file` was finished. All rows counted so
+    /// far must be in the finished files.
+    pub fn file_finished(&self, file: &Path) -> anyhow::Result<()> {
+        let mut state = self.state();
+        state.checkpoint.output_files.push(file.to_path_buf());
+        state.checkpoint.write(&self.path)
+    }
+}
+
+#[test]
+fn test_checkpoint_round_trip() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("checkpoint.json");
+    assert_eq!(Checkpoint::read(&path).unwrap(), None);
+
+    let checkpointer = Checkpointer::new(path.clone(), PathBuf::from("out.csv"), 2, 0, None);
+    assert_eq!(checkpointer.start_input(Path::new("a.xml"), None), None);
+    checkpointer.add_rows(2);
+    checkpointer.complete_input();
+    assert_eq!(
+        checkpointer.start_input(Path::new("b.zip"), Some("b.gml")),
+        None
+    );
+    assert_eq!(checkpointer.take_skipped_rows(2), 0);
+    checkpointer.add_rows(3);
+    checkpointer
+        .file_finished(Path::new("out-00001.csv"))
+        .unwrap();
+
+    let checkpoint = Checkpoint::read(&path).unwrap().unwrap();
+    assert_eq!(
+        checkpoint.output_files,
+        vec![PathBuf::from("out-00001.csv")]
+    );
+    assert_eq!(
+        checkpoint.input(Path::new("a.xml"), None),
+        Some(&InputProgress {
+            path: PathBuf::from("a.xml"),
+            zip_entry: None,
+            rows: 2,
+            complete: true,
+        })
+    );
+    let b = checkpoint.input(Path::new("b.zip"), Some("b.gml")).unwrap();
+    assert_eq!((b.rows, b.complete), (3, false));
+    assert_eq!(checkpoint.input(Path::new("b.zip"), None), None);
+
+    // resumed: `a.xml` is not read again, the rows of `b.gml` are skipped
+    let resumed = Checkpointer::new(
+        path.clone(),
+        PathBuf::from("out.csv"),
+        2,
+        0,
+        Some(checkpoint),
+    );
+    assert_eq!(resumed.output_files(), vec![PathBuf::from("out-00001.csv")]);
+    assert_eq!(resumed.start_input(Path::new("a.xml"), None), Some(2));
+    assert_eq!(resumed.start_input(Path::new("b.zip"), Some("b.gml")), None);
+    assert_eq!(resumed.take_skipped_rows(2), 2);
+    assert_eq!(resumed.take_skipped_rows(2), 1);
+    assert_eq!(resumed.take_skipped_rows(2), 0);
+
+    std::fs::write(&path, "{").unwrap();
+    assert!(Checkpoint::read(&path).is_err());
+}
@@ -1,64 +1,207 @@
+use std::collections::{BTreeMap, HashMap};
 use std::fs::File;
 #[cfg(feature = "download")]
 use std::io::Seek;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
 use anyhow::Context;
+use chrono::NaiveDate;
+use chrono::format::{Item, StrftimeItems};
 use clap::ArgAction;
+use clap::builder::{PossibleValue, PossibleValuesParser, TypedValueParser};
+#[cfg(feature = "geoparquet")]
+use geoparquet::writer::GeoParquetWriterEncoding;
 use glob::glob;
-use parquet::basic::BrotliLevel;
-use parquet::basic::Compression;
-use parquet::basic::ZstdLevel;
+#[cfg(feature = "geoparquet")]
 use parquet::file::properties::WriterVersion;
 #[cfg(feature = "download")]
 use tempfile::NamedTempFile;
-use zip::ZipArchive;
 
 use prg_convert::CRS;
 use prg_convert::FileType;
+use prg_convert::JoinAudit;
 use prg_convert::OutputFormat;
+use prg_convert::ParquetCompression;
 use prg_convert::SchemaVersion;
+use prg_convert::StreetTypes;
+use prg_convert::TimestampTimezone;
+use prg_convert::common::MAX_COORD_PRECISION;
+use prg_convert::common::NameCase;
+use prg_convert::common::RecordFilter;
+use prg_convert::common::StreetFilter;
+use prg_convert::common::UntrimmedColumns;
+use prg_convert::common::Verbosity;
+use prg_convert::common::estimated_bytes_per_row;
+use prg_convert::common::missing_projections;
+use prg_convert::convert::{
+    ConvertOptions, DEFAULT_BATCH_SIZE, DEFAULT_COORD_PRECISION, DEFAULT_CSV_DATE_FORMAT,
+    DEFAULT_WORKERS_PER_FILE, FILE_NUMBER_PLACEHOLDER, FileRecord, default_csv_timestamp_format,
+};
+use prg_convert::progress;
+use prg_convert::split_zip::is_split_volume;
+use prg_convert::terc;
 
-pub const DEFAULT_BATCH_SIZE: usize = 100_000;
+use crate::expected_counts::read_expected_counts;
 
 #[derive(Clone, Copy, clap::ValueEnum)]
-pub enum OutputFormatArg {
-    Csv,
-    Geoparquet,
+pub enum ParquetVersionArg {
+    #[value(name = "v1")]
+    V1,
+    #[value(name = "v2")]
+    V2,
 }
 
 #[derive(Clone, Copy, clap::ValueEnum)]
-pub enum SchemaVersionArg {
-    #[value(name = "2012")]
-    V2012,
-    #[value(name = "2021")]
-    V2021,
+pub enum GeometryEncodingArg {
+    Wkb,
+    Native,
 }
 
 #[derive(Clone, Copy, clap::ValueEnum)]
-pub enum ParquetCompressionArg {
-    Zstd,
-    Snappy,
-    Brotli,
+pub enum RowGroupPerArg {
+    Voivodeship,
 }
 
 #[derive(Clone, Copy, clap::ValueEnum)]
-pub enum ParquetVersionArg {
-    #[value(name = "v1")]
-    V1,
-    #[value(name = "v2")]
-    V2,
+pub enum SpatialSortArg {
+    Hilbert,
+}
+
+#[derive(Clone, Copy, Default, clap::ValueEnum)]
+pub enum ListFormatArg {
+    #[default]
+    Text,
+    Json,
 }
 
 #[derive(Clone, Copy, clap::ValueEnum)]
-pub enum CrsEpsgArg {
-    #[value(name = "2180")]
-    Epsg2180,
-    #[value(name = "4326")]
-    Epsg4326,
+pub enum NameCaseArg {
+    Preserve,
+    Lower,
+    Title,
+}
+
+/// Parser for options converted with the library's `TryFrom<&str>`
+/// implementations. `names` are listed as possible values in help; anything
+/// else `TryFrom` accepts (e.g. the displayed `EPSG:2180`) is accepted too.
+fn lib_enum_parser<T>(names: &'static [&'static str]) -> LibEnumParser<T> {
+    LibEnumParser {
+        names,
+        value: std::marker::PhantomData,
+    }
+}
+
+#[derive(Clone)]
+struct LibEnumParser<T> {
+    names: &'static [&'static str],
+    value: std::marker::PhantomData<fn() -> T>,
+}
+
+impl<T> TypedValueParser for LibEnumParser<T>
+where
+    T: for<'a> TryFrom<&'a str, Error = anyhow::Error> + Clone + Send + Sync + 'static,
+{
+    type Value = T;
+
+    fn parse_ref(
+        &self,
+        cmd: &clap::Command,
+        arg: Option<&clap::Arg>,
+        value: &std::ffi::OsStr,
+    ) -> Result<T, clap::Error> {
+        if let Some(parsed) = value.to_str().and_then(|v| T::try_from(v).ok()) {
+            return Ok(parsed);
+        }
+        // the usual "invalid value" error listing the possible values
+        PossibleValuesParser::new(self.names.iter().copied())
+            .try_map(|name| T::try_from(name.as_str()))
+            .parse_ref(cmd, arg, value)
+    }
+
+    fn possible_values(&self) -> Option<Box<dyn Iterator<Item = PossibleValue> + '_>> {
+        Some(Box::new(
+            self.names.iter().map(|name| PossibleValue::new(*name)),
+        ))
+    }
+}
+
+/// Environment variables read for options not given on the command line
+/// (command line > environment > default), with the options they set.
+pub const ENV_FALLBACKS: [(&str, &str); 11] = [
+    ("PRG_BATCH_SIZE", "batch-size"),
+    ("PRG_MAX_MEMORY", "max-memory"),
+    ("PRG_SCHEMA_VERSION", "schema-version"),
+    ("PRG_TERYT_PATH", "teryt-path"),
+    ("PRG_COMPRESSION", "parquet-compression"),
+    ("PRG_COMPRESSION_LEVEL", "compression-level"),
+    ("PRG_ROW_GROUP_SIZE", "parquet-row-group-size"),
+    ("PRG_CRS_EPSG", "crs-epsg"),
+    ("PRG_TIMESTAMP_TZ", "timestamp-tz"),
+    ("PRG_MAX_ERRORS", "max-errors"),
+    ("PRG_WORKERS_PER_FILE", "workers-per-file"),
+];
+
+/// Command line options of the `ConvertOptions` fields (and library
+/// functions) named in backticks in errors of the library, see
+/// `with_option_flags`.
+const LIBRARY_OPTION_FLAGS: [(&str, &str); 10] = [
+    ("column_renames", "rename-columns"),
+    ("parquet_bloom_filter_columns", "parquet-bloom-filter"),
+    ("output_schema", "print-schema"),
+    ("max_errors", "max-errors"),
+    ("rows_per_file", "rows-per-file"),
+    ("grid_size", "grid"),
+    ("validate_only", "validate-only"),
+    ("spatial_sort", "spatial-sort"),
+    ("tail", "tail"),
+    ("compare_with", "compare"),
+];
+
+/// Adds the command line options of the `ConvertOptions` fields named in
+/// `error` (e.g. `--rename-columns` for `column_renames`) to an error of
+/// `convert` or `output_schema`.
+pub fn with_option_flags(error: impl Into<anyhow::Error>) -> anyhow::Error {
+    let error = error.into();
+    let message = format!("{:#}", error);
+    let flags: Vec<String> = LIBRARY_OPTION_FLAGS
+        .iter()
+        .filter(|(name, _)| message.contains(&format!("`{}`", name)))
+        .map(|(_, flag)| format!("--{}", flag))
+        .collect();
+    if flags.is_empty() {
+        return error;
+    }
+    error.context(format!("Conversion failed, see {}.", flags.join(", ")))
+}
+
+/// Help text listing `ENV_FALLBACKS`, shown after the options.
+fn env_fallbacks_help() -> String {
+    fn join(items: Vec<String>) -> String {
+        match items.split_last() {
+            Some((last, rest)) if !rest.is_empty() => format!("{} and {}", rest.join(", "), last),
+            _ => items.concat(),
+        }
+    }
+    format!(
+        "Options not given on the command line are read from environment variables if they are set: {} (for {}). Command line options take precedence over environment variables, which take precedence over defaults.",
+        join(
+            ENV_FALLBACKS
+                .iter()
+                .map(|(var, _)| var.to_string())
+                .collect()
+        ),
+        join(
+            ENV_FALLBACKS
+                .iter()
+                .map(|(_, option)| format!("--{}", option))
+                .collect()
+        ),
+    )
 }
 
 #[derive(clap::Parser)]
+#[command(after_help = env_fallbacks_help())]
 pub struct RawArgs {
     #[arg(
         long = "input-paths",
@@ -67,23 +210,59 @@ pub struct RawArgs {
         num_args = 1..,
     )]
     input_paths: Vec<String>,
+    #[arg(
+        long = "input-list",
+        help = "(Optional) Text file with input paths or glob patterns, one per line, read in addition to --input-paths. Blank lines and lines starting with `#` are skipped. Relative paths are resolved against the current directory."
+    )]
+    input_list: Option<std::path::PathBuf>,
     #[arg(long = "download-data", num_args = 0..=1, default_missing_value = "", help = "Download PRG address data from the official GUGiK URL instead of providing --input-paths. Optionally provide a file path to save the downloaded file to (e.g. --download-data /tmp/prg.zip). If no path is given, a temporary file is used. URL: https://integracja.gugik.gov.pl/PRG/pobierz.php?adresy_zbiorcze_gml")]
     download_data: Option<String>,
-    #[arg(long = "output-path", help = "Output file path.")]
-    output_path: std::path::PathBuf,
+    #[arg(
+        long = "output-path",
+        required_unless_present_any = ["print_schema", "validate_only", "transform_only", "list_zip"],
+        help = "Output file path."
+    )]
+    output_path: Option<std::path::PathBuf>,
+    #[arg(
+        long = "rows-per-file",
+        help = "(Optional) Split the output into files of at most this many addresses, numbered from 1. The letter `n` in curly braces in `output-path` is replaced with the number, otherwise the number is added before the extension (`output.csv` -> `output-00001.csv`, `output-00002.csv`, ...). Every CSV file has a header and every parquet file its own metadata."
+    )]
+    rows_per_file: Option<usize>,
+    #[arg(
+        long = "checkpoint",
+        help = "(Optional) With --rows-per-file, save the progress to this JSON file every time an output file is finished, so that an interrupted run can be continued with --resume."
+    )]
+    checkpoint: Option<std::path::PathBuf>,
+    #[arg(
+        long = "resume",
+        action = ArgAction::SetTrue,
+        help = "(Optional) Continue after the progress saved to the --checkpoint file: inputs written completely are not read again and the numbered output files continue after the last finished one. Starts from the beginning if the file does not exist."
+    )]
+    resume: Option<bool>,
     #[arg(
         long = "output-format",
         ignore_case = true,
-        help = "Output file format."
+        value_parser = lib_enum_parser::<OutputFormat>(OutputFormat::NAMES),
+        required_unless_present_any = ["validate_only", "transform_only", "list_zip"],
+        help = "Output file format: `csv`, `geoparquet`, `parquet` (plain parquet with the same columns as CSV, without geometry and GeoParquet metadata) `gml` (GML feature collection with the points in EPSG:2180) or `pbf` (length-delimited protobuf `Address` messages, see `proto/address.proto`; needs the `protobuf` feature)."
+    )]
+    output_format: Option<OutputFormat>,
+    #[arg(
+        long = "schema-version",
+        value_parser = lib_enum_parser::<SchemaVersion>(&SchemaVersion::NAMES),
+        help = "(Optional) Schema version. If not provided it is detected from the first input file (for ZIP files: from the first XML/GML file inside). Required with --download-data."
     )]
-    output_format: OutputFormatArg,
-    #[arg(long = "schema-version", help = "Schema version.")]
-    schema_version: SchemaVersionArg,
+    schema_version: Option<SchemaVersion>,
     #[arg(
         long = "teryt-path",
-        help = "Path of XML file with TERYT dictionary unpacked from archive downloaded from: https://eteryt.stat.gov.pl/eTeryt/rejestr_teryt/udostepnianie_danych/baza_teryt/uzytkownicy_indywidualni/pobieranie/pliki_pelne.aspx?contrast=default (TERC, podstawowa). Required for --schema-version 2021."
+        help = "Path of XML file with TERYT dictionary unpacked from archive downloaded from: https://eteryt.stat.gov.pl/eTeryt/rejestr_teryt/udostepnianie_danych/baza_teryt/uzytkownicy_indywidualni/pobieranie/pliki_pelne.aspx?contrast=default (TERC, podstawowa). The downloaded ZIP archive and the CSV version of the catalog (`WOJ;POW;GMI;RODZ;NAZWA;NAZWA_DOD;STAN_NA`) are also accepted. A directory is also accepted, then the TERC file in it with the newest catalog date (or date in the file name) is used, see --teryt-date. A file inside a ZIP archive can be given as `archive.zip::TERC_Urzedowy_2025-11-18.xml`. Required for --schema-version 2021, unless an input ZIP file contains a TERC file (`TERC*.xml` or `TERC*.csv`), which is then used."
     )]
     teryt_path: Option<std::path::PathBuf>,
+    #[arg(
+        long = "teryt-date",
+        help = "(Optional) Date of the TERC catalog to use (YYYY-MM-DD): with a directory as --teryt-path the file of that date is used instead of the newest, with a file it is an error if the file is of another date."
+    )]
+    teryt_date: Option<NaiveDate>,
     #[arg(long = "download-teryt", action = ArgAction::SetTrue, help = "Download TERYT dictionary file from official API. (Requires authentication info, see: https://api.stat.gov.pl/Home/TerytApi , relevant flags: teryt-api-username, teryt-api-password)")]
     teryt_download: Option<bool>,
     #[arg(
@@ -101,12 +280,18 @@ pub struct RawArgs {
         help = format!("(Optional) How many rows are kept in memory before writing to output (default: {}).", DEFAULT_BATCH_SIZE),
     )]
     batch_size: Option<usize>,
+    #[arg(
+        long = "max-memory",
+        help = "(Optional) Approximate memory budget in MB for one batch. Caps the batch size based on an estimate of per-row size. Ignored if `batch-size` is provided."
+    )]
+    max_memory: Option<usize>,
     #[arg(
         long = "parquet-compression",
         ignore_case = true,
+        value_parser = lib_enum_parser::<ParquetCompression>(&ParquetCompression::NAMES),
         help = "(Optional) Compression to use when writing parquet file (default: zstd)."
     )]
-    parquet_compression: Option<ParquetCompressionArg>,
+    parquet_compression: Option<ParquetCompression>,
     #[arg(
         long = "compression-level",
         help = "(Optional) What level of compression to use when writing parquet file (if compression algorithm supports it)."
@@ -117,6 +302,60 @@ pub struct RawArgs {
         help = "(Optional) What's the max row group size when writing parquet file (default: same as batch-size)."
     )]
     parquet_row_group_size: Option<usize>,
+    #[arg(
+        long = "parquet-data-page-size",
+        help = "(Optional) Size in bytes after which a parquet data page is closed (default: 1 MiB). Checked after every written batch, so pages can be larger. GeoParquet and parquet only."
+    )]
+    parquet_data_page_size: Option<usize>,
+    #[arg(
+        long = "parquet-dict-page-size",
+        help = "(Optional) Size in bytes of the dictionary page of a column after which the column falls back to plain encoding (default: 1 MiB). GeoParquet and parquet only."
+    )]
+    parquet_dict_page_size: Option<usize>,
+    #[arg(
+        long = "flush-every",
+        help = "(Optional) Close the current parquet row group after at least this many rows were written since the last flush, independently of `batch-size` and `parquet-row-group-size` (useful for consumers reading the file while it's written). GeoParquet and parquet only."
+    )]
+    flush_every: Option<usize>,
+    #[arg(
+        long = "row-group-per",
+        help = "(Optional) Close the current parquet row group whenever `teryt_wojewodztwo` changes, so that every row group holds a single voivodeship (better predicate pushdown on the voivodeship columns). Only helps when the input is grouped by voivodeship, like the national file; otherwise a warning is printed and row groups are written as without it from the first voivodeship that comes back. Combines with `parquet-row-group-size` and `flush-every`. GeoParquet and parquet only."
+    )]
+    row_group_per: Option<RowGroupPerArg>,
+    #[arg(
+        long = "parquet-bloom-filter",
+        value_delimiter = ',',
+        help = "(Optional) Comma-separated string columns written with a bloom filter, to speed up looking up single values (e.g. `uuid` for `lokalny_id`, or `uuid,ulica`). Names are output column names (see --print-schema). GeoParquet and parquet only."
+    )]
+    parquet_bloom_filter: Vec<String>,
+    #[arg(
+        long = "csv-delimiter",
+        help = "(Optional) Field delimiter of the CSV output, a single ASCII character (default: `,`). CSV only."
+    )]
+    csv_delimiter: Option<char>,
+    #[arg(long = "decimal-comma", action = ArgAction::SetTrue, help = "(Optional) Write coordinates with a decimal comma (`12,345`) instead of a dot, as expected by spreadsheets in Polish locale. Requires a `csv-delimiter` other than `,` (e.g. `;`). CSV only.")]
+    decimal_comma: Option<bool>,
+    #[arg(
+        long = "null-string",
+        help = "(Optional) Text written for missing values (e.g. `\\N`), so they can be told apart from empty strings (default: empty). It cannot contain the delimiter, a quote or a line break. CSV only."
+    )]
+    null_string: Option<String>,
+    #[arg(
+        long = "date-format",
+        help = "(Optional) strftime pattern of date columns (default: `%Y-%m-%d`). CSV only."
+    )]
+    date_format: Option<String>,
+    #[arg(
+        long = "timestamp-format",
+        help = "(Optional) strftime pattern of timestamp columns, which are in the timezone of --timestamp-tz (default: `%Y-%m-%dT%H:%M:%S%.fZ` for UTC, `%Y-%m-%dT%H:%M:%S%.f%:z` otherwise, RFC 3339). CSV only."
+    )]
+    timestamp_format: Option<String>,
+    #[arg(
+        long = "timestamp-tz",
+        value_parser = lib_enum_parser::<TimestampTimezone>(&TimestampTimezone::NAMES),
+        help = "(Optional) Timezone of timestamp columns: `utc` (default) or `warsaw` (Europe/Warsaw wall-clock time). The instants are the same, only the timezone stored with the columns (and their rendering in CSV) changes."
+    )]
+    timestamp_tz: Option<TimestampTimezone>,
     #[arg(
         long = "parquet-version",
         ignore_case = true,
@@ -125,103 +364,289 @@ pub struct RawArgs {
     parquet_version: Option<ParquetVersionArg>,
     #[arg(
         long = "crs-epsg",
-        help = "(Optional) EPSG code of Coordinate Reference System for geometry data written to geoparquet (default: 2180). Does not affect CSV format which includes coordinates in both."
+        value_parser = lib_enum_parser::<CRS>(&CRS::NAMES),
+        help = "(Optional) EPSG code of Coordinate Reference System for geometry data written to geoparquet (default: 2180). Does not affect CSV format which includes coordinates in both, nor GML which is always in EPSG:2180."
+    )]
+    crs_epsg: Option<CRS>,
+    #[arg(
+        long = "epsg-input",
+        value_parser = lib_enum_parser::<CRS>(&CRS::NAMES),
+        help = "(Optional) EPSG code of the coordinates in `gml:pos` of the input (default: 2180), assumed for points without `srsName`; points with another `srsName` are skipped. With 4326 the coordinates are read as longitude/latitude in degrees (in the order of the schema's EPSG:2180 coordinates) and written as is, the EPSG:2180 columns are reprojected from them."
+    )]
+    epsg_input: Option<CRS>,
+    #[arg(
+        long = "coord-precision",
+        help = "(Optional) Number of decimals (0-15) longitude and latitude are rounded to, also in the geometry of GeoParquet in EPSG:4326 (default: 6, about 0.1 m). The EPSG:2180 coordinates are not rounded."
+    )]
+    coord_precision: Option<u32>,
+    #[arg(
+        long = "geometry-encoding",
+        ignore_case = true,
+        help = "(Optional) Encoding of the geometry column written to geoparquet: `wkb` (GeoParquet 1.0, readable by most tools) or `native` (GeoArrow point encoding, GeoParquet 1.1) (default: wkb)."
+    )]
+    geometry_encoding: Option<GeometryEncodingArg>,
+    #[arg(long = "points-only-schema", action = ArgAction::SetTrue, help = "(Optional) Write a compact geoparquet for geocoders with only the columns `lokalny_id`, `adres` (the address as a single string: `miejscowosc, ulica numer, kod_pocztowy`, e.g. `Rzepin, Inwalidów Wojennych 1A, 69-110`, or `Żubrów 21A, 69-200` without a street) and `geometry`. Columns added by other options are not written.")]
+    points_only_schema: Option<bool>,
+    #[arg(
+        long = "errors-to",
+        help = "(Optional) Path of a JSON-lines file to which addresses that could not be parsed are written (byte offset, uuid if known, error message). Such addresses are skipped and the conversion continues. If not provided errors are printed as warnings."
+    )]
+    errors_to: Option<std::path::PathBuf>,
+    #[arg(
+        long = "join-audit",
+        help = "(Optional) Path of a JSON-lines file to which problems of joining addresses with the TERC catalog and the city dictionary are written: municipalities missing from TERC (`municipality_not_in_terc`) and cities with an unknown `prgad:rodzaj` code (`unknown_city_kind`), one line per municipality or city with its number of addresses. Addresses in missing municipalities are skipped (see --errors-to) either way. The addresses are still written (use --validate-only to only write the audit). Schema 2021 only."
+    )]
+    join_audit: Option<std::path::PathBuf>,
+    #[arg(
+        long = "max-errors",
+        help = "(Optional) Stop with an error once more than N addresses could not be parsed, e.g. 0 to fail on the first one. If not provided all such addresses are skipped."
+    )]
+    max_errors: Option<usize>,
+    #[arg(
+        long = "start-date",
+        help = "(Optional) Only output addresses with `wazny_od_lub_data_nadania` (2012: waznyOd, 2021: dataNadania) on or after this date (YYYY-MM-DD). Addresses without the date are skipped when a date filter is used."
+    )]
+    start_date: Option<NaiveDate>,
+    #[arg(
+        long = "end-date",
+        help = "(Optional) Only output addresses with `wazny_od_lub_data_nadania` on or before this date (YYYY-MM-DD). Addresses without the date are skipped when a date filter is used."
+    )]
+    end_date: Option<NaiveDate>,
+    #[arg(
+        long = "sample-rate",
+        help = "(Optional) Only output roughly this fraction of addresses (between 0 and 1, e.g. 0.01 for 1%). Addresses are chosen by a hash of their uuid, so the same seed always selects the same addresses."
+    )]
+    sample_rate: Option<f64>,
+    #[arg(
+        long = "sample-seed",
+        help = "(Optional) Seed selecting which addresses are sampled with `sample-rate` (default: 0)."
+    )]
+    sample_seed: Option<u64>,
+    #[arg(
+        long = "include-zip-entry",
+        help = "(Optional) Glob pattern of file names inside ZIP archives that should be parsed regardless of their extension. Can be used multiple times."
+    )]
+    include_zip_entry: Vec<String>,
+    #[arg(
+        long = "exclude-zip-entry",
+        help = "(Optional) Glob pattern of file names inside ZIP archives that should not be parsed (e.g. `*metadane*`). Takes precedence over `include-zip-entry`. Can be used multiple times."
+    )]
+    exclude_zip_entry: Vec<String>,
+    #[arg(long = "skip-bad-entries", action = ArgAction::SetTrue, help = "(Optional) When a file inside a ZIP archive cannot be read or decompressed, print a warning and continue with the remaining files instead of stopping. Addresses read from the file before the failure are kept. Skipped files are listed in the summary.")]
+    skip_bad_entries: Option<bool>,
+    #[arg(
+        long = "manifest",
+        help = "(Optional) Path of a JSON file to which a summary of the conversion is written after the run (inputs, schema, output options, row counts, duration, program version, TERC catalog date)."
+    )]
+    manifest: Option<std::path::PathBuf>,
+    #[arg(
+        long = "expect-counts",
+        help = "(Optional) Path of a CSV file with the expected number of addresses per voivodeship, one `teryt_wojewodztwo,count` pair per line (e.g. `02,123456`, a header line is allowed). After the run the addresses written to the output are counted per voivodeship and the program exits with an error if any listed count differs by more than `expect-counts-tolerance`."
+    )]
+    expect_counts: Option<std::path::PathBuf>,
+    #[arg(
+        long = "expect-counts-tolerance",
+        help = "(Optional) Allowed difference from the counts in `expect-counts`, in percent of the expected count (default: 0)."
+    )]
+    expect_counts_tolerance: Option<f64>,
+    #[arg(
+        long = "compare",
+        help = "(Optional) Older PRG snapshot (path(s) or glob patterns, like --input-paths) to compare the input with. Addresses are matched by `lokalny_id` and only the differences are written, with column `zmiana`: `added`, `removed` (the old address) or `modified` (the new address, when any column other than the identifiers and version metadata differs). Both snapshots are read with the same options.",
+        value_delimiter = ' ',
+        num_args = 1..,
+    )]
+    compare: Vec<String>,
+    #[arg(long = "normalize-names", action = ArgAction::SetTrue, help = "(Optional) Add columns `miejscowosc_norm` and `ulica_norm` with city and street names lowercased, without Polish diacritics and with collapsed whitespace (for fuzzy matching).")]
+    normalize_names: Option<bool>,
+    #[arg(long = "split-house-number", action = ArgAction::SetTrue, help = "(Optional) Add columns `numer` (leading integer of `numer_porzadkowy`, e.g. `12` of `12A`) and `numer_suffix` (the rest, e.g. `A`, `/3`, `-14`) for sorting and joins. Numbers not starting with a digit are all suffix. `numer_porzadkowy` is kept.")]
+    split_house_number: Option<bool>,
+    #[arg(long = "split-valid-from", action = ArgAction::SetTrue, help = "(Optional) Add columns `wazny_od` and `data_nadania` with the value of `wazny_od_lub_data_nadania` in the one matching the source field (`prg-ad:waznyOd` in schema 2012, `prgad:dataNadania` in schema 2021), the other is null. Useful when combining outputs of both schemas. `wazny_od_lub_data_nadania` is kept.")]
+    split_valid_from: Option<bool>,
+    #[arg(long = "add-full-address", action = ArgAction::SetTrue, help = "(Optional) Add column `pelny_adres` with the address as a single line, e.g. `ul. Podgórna 12A, 67-415 Konotop, gmina Kolsko, powiat nowosolski, woj. lubuskie`. Addresses without a street start with the city (`Żubrów 21A, ...`), street names starting with a street type are not prefixed with `ul.` and missing parts are left out.")]
+    add_full_address: Option<bool>,
+    #[arg(long = "add-source-column", action = ArgAction::SetTrue, help = "(Optional) Add column `zrodlo` with the name of the input file every address was read from (for ZIP archives followed by the name of the file inside, e.g. `archive.zip/file.xml`).")]
+    add_source_column: Option<bool>,
+    #[arg(
+        long = "grid",
+        value_parser = clap::value_parser!(u32).range(1..),
+        help = "(Optional) Add column `grid_id` with the cell of a grid of this many meters in EPSG:2180 the address lies in, as `x_y` of its lower left corner (e.g. `--grid 1000` gives `500000_300000`), for publishing aggregated counts instead of exact points. Null for addresses without coordinates (leave them out with --drop-null-geometry). The exact coordinates are still written as well."
+    )]
+    grid: Option<u32>,
+    #[arg(long = "parallel-parse", action = ArgAction::SetTrue, help = "(Optional, experimental) Parse each uncompressed XML/GML input file on all available CPU cores by splitting it into parts at address boundaries. Files inside ZIP archives are still parsed on one thread.")]
+    parallel_parse: Option<bool>,
+    #[arg(
+        long = "rename-columns",
+        value_delimiter = ',',
+        help = "(Optional) Rename output columns, given as comma-separated `old=new` pairs (e.g. `przestrzen_nazw=namespace,ulica=street`). Old names must be columns of the output (see --print-schema); the GeoParquet geometry column can be renamed too."
+    )]
+    rename_columns: Vec<String>,
+    #[arg(
+        long = "id-prefix",
+        help = "(Optional) Prefix of the `prg-ad:komponent` links before the id of the referenced administrative unit, city or street (e.g. `http://geoportal.gov.pl/PZGIK/dane/`). By default anything up to the last `/` or `#` of the link is ignored. Schema 2012 only."
+    )]
+    id_prefix: Option<String>,
+    #[arg(long = "status-raw", action = ArgAction::SetTrue, help = "(Optional) Write `prg-ad:status` values as they are in the input. By default known statuses are normalized to one of: `istniejacy`, `prognozowany`, `w budowie`, `nieistniejacy` (unknown ones are written as is with a warning). Schema 2012 only.")]
+    status_raw: Option<bool>,
+    #[arg(long = "skip-dictionary", action = ArgAction::SetTrue, help = "(Optional) Do not read the input twice to build the dictionary of administrative units, cities and streets the addresses refer to (`prg-ad:komponent`). Names are then only taken from the addresses themselves and the TERYT id columns (`teryt_*`) are empty, which is enough for e.g. structural checks and about twice as fast. Schema 2012 only.")]
+    skip_dictionary: Option<bool>,
+    #[arg(
+        long = "street-type",
+        value_delimiter = ',',
+        help = "(Optional) Street types written before street names, given as comma-separated `code=type` pairs where code is the `prgad:rodzaj` code (e.g. `3=pl.,11=os.`); an empty type writes none. Overrides the default full names (e.g. `plac`) and --abbreviate-street-types. Names that already start with the type are left as they are. Schema 2021 only."
+    )]
+    street_type: Vec<String>,
+    #[arg(long = "abbreviate-street-types", action = ArgAction::SetTrue, help = "(Optional) Write common street types abbreviated (`pl.`, `os.`, `al.`, `bulw.`, `wyb.`) instead of in full. Schema 2021 only.")]
+    abbreviate_street_types: Option<bool>,
+    #[arg(long = "verbose-unknown-tags", action = ArgAction::SetTrue, help = "(Optional) Print the unknown tags found inside addresses (with their number of occurrences) after every batch. By default they are printed once at the end of every input file.")]
+    verbose_unknown_tags: Option<bool>,
+    #[arg(long = "benchmark", action = ArgAction::SetTrue, help = "(Optional) Print the time spent in every phase of the conversion at the end: building dictionaries, reading TERC, XML parsing, reprojection, building arrow batches and writing (with encoding and compression), e.g. to find out whether compression or reprojection is the bottleneck. Times of phases running on several threads are summed.")]
+    benchmark: Option<bool>,
+    #[arg(long = "fail-on-unknown-tag", action = ArgAction::SetTrue, help = "(Optional) Stop with an error (naming the tag and the byte offset of the address) at the first unknown tag inside an address, instead of ignoring it and printing the unknown tags at the end. Useful to notice changes of the schema.")]
+    fail_on_unknown_tag: Option<bool>,
+    #[arg(long = "normalize-whitespace", action = ArgAction::SetTrue, help = "(Optional) Collapse runs of whitespace (including non-breaking spaces) to a single space in the names of administrative units, cities and streets, house numbers, postcodes and statuses. By default they are written as in the input, only trimmed.")]
+    normalize_whitespace: Option<bool>,
+    #[arg(
+        long = "no-trim",
+        value_delimiter = ',',
+        help = "(Optional) Comma-separated columns written with the leading and trailing whitespace of the input instead of trimmed, for byte-for-byte comparisons with the source: `numer_porzadkowy`, `kod_pocztowy`, `status` (schema 2012, only statuses written as is, see --status-raw) and `id_budynku`. Cannot be combined with --normalize-whitespace."
+    )]
+    no_trim: Vec<String>,
+    #[arg(
+        long = "name-case",
+        help = "(Optional) Case of the names of voivodeships, counties, municipalities and cities (`wojewodztwo`, `powiat`, `gmina`, `miejscowosc`): `preserve` (default, as in the input: voivodeships lowercase, other names mostly capitalized), `lower` or `title` (every word capitalized, except prepositions inside a name, e.g. `Kostrzyn nad Odrą`)."
+    )]
+    name_case: Option<NameCaseArg>,
+    #[arg(long = "quiet", action = ArgAction::SetTrue, conflicts_with = "verbose", help = "(Optional) Print only warnings and errors, without the parameters, progress and summary.")]
+    quiet: Option<bool>,
+    #[arg(long = "verbose", action = ArgAction::SetTrue, help = "(Optional) Print also the unknown tags found inside addresses after every batch (like --verbose-unknown-tags). Without --quiet or --verbose the parameters, progress and summary are printed.")]
+    verbose: Option<bool>,
+    #[arg(
+        long = "tail",
+        help = "(Optional) Only output the last N addresses of the input (e.g. to check the end of a possibly truncated download). All addresses are still parsed; up to N rows are kept in memory."
+    )]
+    tail: Option<usize>,
+    #[arg(
+        long = "spatial-sort",
+        help = "(Optional) Sort the output by the position of every address on a Hilbert curve over EPSG:2180 coordinates, so that nearby addresses are stored together (better spatial locality, e.g. smaller bounding boxes of GeoParquet row groups). Addresses without coordinates are written last. All addresses are kept in memory until the end of the input, see `spatial-sort-buffer`."
+    )]
+    spatial_sort: Option<SpatialSortArg>,
+    #[arg(
+        long = "spatial-sort-buffer",
+        help = "(Optional) With `spatial-sort`, sort and write the addresses in runs of at least N rows (whole batches) instead of all at once, keeping only about N rows in memory. Addresses are then only sorted within every run."
+    )]
+    spatial_sort_buffer: Option<usize>,
+    #[arg(long = "require-street", action = ArgAction::SetTrue, conflicts_with = "no_street", help = "(Optional) Only output addresses with a street (`ulica`).")]
+    require_street: Option<bool>,
+    #[arg(long = "no-street", action = ArgAction::SetTrue, help = "(Optional) Only output addresses without a street (`ulica`), e.g. rural addresses identified by city and house number.")]
+    no_street: Option<bool>,
+    #[arg(long = "drop-null-geometry", action = ArgAction::SetTrue, help = "(Optional) Do not output addresses without coordinates (`gml:pos` missing or not parseable). The number of dropped addresses is printed in the summary.")]
+    drop_null_geometry: Option<bool>,
+    #[arg(long = "mmap", action = ArgAction::SetTrue, conflicts_with = "parallel_parse", help = "(Optional) Read uncompressed XML/GML input files through a memory map instead of a buffered reader. Files inside ZIP archives are read as usual.")]
+    mmap: Option<bool>,
+    #[arg(
+        long = "workers-per-file",
+        value_parser = clap::value_parser!(u8).range(1..=2),
+        help = format!("(Optional) Threads used per input file: with 2 batches are encoded, compressed and written to the output on a separate thread while the next ones are parsed, with 1 both happen on the parsing thread (default: {}).", DEFAULT_WORKERS_PER_FILE),
+    )]
+    workers_per_file: Option<u8>,
+    #[arg(long = "validate-only", action = ArgAction::SetTrue, help = "(Optional) Only check that the input parses: read all addresses without writing output and print a report (number of addresses, component references not found in dictionaries, unknown tags). Exits with an error if any address could not be parsed. `output-path` and `output-format` are not required.")]
+    validate_only: Option<bool>,
+    #[arg(long = "print-schema", action = ArgAction::SetTrue, help = "(Optional) Print the arrow schema of the output (for the chosen output format, schema version, CRS and extra columns) as JSON and exit without processing any input.")]
+    print_schema: Option<bool>,
+    #[arg(
+        long = "transform-only",
+        hide = true,
+        help = "(Advanced) Benchmark the reprojection: read up to this many `gml:pos` coordinates from the input, transform them from EPSG:2180 to EPSG:4326 point by point and in one call, print the throughput and exit without writing output."
+    )]
+    transform_only: Option<usize>,
+    #[arg(
+        long = "serve",
+        exclusive = true,
+        help = "(Optional) Instead of converting, listen on this address (e.g. `127.0.0.1:8080`) and convert files on request, one at a time: `POST /convert` with a JSON array of the other options as the body (e.g. `[\"--input-paths\", \"data.zip\", \"--output-format\", \"csv\"]`, without `--output-path`) responds with the converted file and its summary as JSON in the `X-Prg-Convert-Report` header. There is no authentication, requests can read and write any file the program can. Requires the `http` feature."
     )]
-    crs_epsg: Option<CrsEpsgArg>,
+    serve: Option<String>,
+    #[arg(
+        long = "self-test",
+        action = ArgAction::SetTrue,
+        exclusive = true,
+        help = "(Optional) Instead of converting, parse the sample files and TERC catalog built into the program, check the results against known values and exit with an error if any check failed."
+    )]
+    self_test: Option<bool>,
+    #[arg(
+        long = "list-zip",
+        value_delimiter = ' ',
+        num_args = 1..,
+        conflicts_with_all = ["input_paths", "input_list", "download_data"],
+        help = "(Optional) Instead of converting, print the entries of these ZIP archives (glob patterns are accepted) and exit without parsing anything: index, name, compressed and uncompressed size in bytes and whether the entry would be parsed with the given --schema-version (detected from the first archive if not given), --include-zip-entry and --exclude-zip-entry."
+    )]
+    list_zip: Vec<String>,
+    #[arg(
+        long = "format",
+        help = "(Optional) Format of --list-zip: `text` (default), a tab-separated table with a header, or `json`, an array of objects with the same fields."
+    )]
+    format: Option<ListFormatArg>,
 }
 
-pub struct CompressedFile {
-    pub index: usize,
-    pub name: String,
-    pub compressed_size: u64,
-    pub uncompressed_size: u64,
-    pub to_be_parsed: bool,
+/// Overrides of the extension-based decision which files inside a ZIP
+/// archive are parsed (`CompressedFile::to_be_parsed`).
+#[derive(Default)]
+pub struct ZipEntryFilter {
+    include: Vec<glob::Pattern>,
+    exclude: Vec<glob::Pattern>,
 }
 
-pub struct FileRecord {
-    pub file_type: FileType,
-    pub path: PathBuf,
-    pub size_in_bytes: u64,
-    pub compressed_files: Option<Vec<CompressedFile>>, // only for FileType::ZIP
-    pub decompressed_size: Option<u128>,               // only for FileType::ZIP
+impl ZipEntryFilter {
+    pub fn new(include: &[String], exclude: &[String]) -> anyhow::Result<Self> {
+        let compile = |patterns: &[String]| -> anyhow::Result<Vec<glob::Pattern>> {
+            patterns
+                .iter()
+                .map(|p| {
+                    glob::Pattern::new(p)
+                        .with_context(|| format!("Failed to parse glob pattern: `{}`", p))
+                })
+                .collect()
+        };
+        Ok(Self {
+            include: compile(include)?,
+            exclude: compile(exclude)?,
+        })
+    }
+
+    /// Entries matching an exclude pattern are skipped, entries matching an
+    /// include pattern are parsed, others keep `default`.
+    fn to_be_parsed(&self, name: &str, default: bool) -> bool {
+        if self.exclude.iter().any(|p| p.matches(name)) {
+            false
+        } else if self.include.iter().any(|p| p.matches(name)) {
+            true
+        } else {
+            default
+        }
+    }
 }
 
 pub(crate) fn parse_input_paths(
     input_paths: &Vec<String>,
     schema_version: &SchemaVersion,
+    zip_entry_filter: &ZipEntryFilter,
+    skip_bad_entries: bool,
 ) -> anyhow::Result<Vec<FileRecord>> {
     let mut paths: Vec<FileRecord> = Vec::new();
     for raw_path in input_paths {
         let globbed_paths = glob(&raw_path)
             .with_context(|| format!("Failed to parse glob pattern: `{}`", &raw_path))?;
         for potential_path in globbed_paths {
-            let path = potential_path?;
-            let file_metadata = std::fs::metadata(&path).with_context(|| {
-                format!("could not get metadata for file `{}`", &path.display())
-            })?;
-            if file_metadata.is_dir() {
-                anyhow::bail!(
-                    "input path `{}` is a directory, expected a file",
-                    &path.display()
-                );
+            let potential_path = potential_path?;
+            if is_split_volume(&potential_path) {
+                // read together with the `.zip` volume
+                continue;
             }
-            let file_type = match path
-                .extension()
-                .expect("Could not read file extension.")
-                .to_string_lossy()
-                .to_lowercase()
-                .as_str()
-            {
-                "zip" => FileType::ZIP,
-                "xml" | "gml" => FileType::XML,
-                _ => {
-                    anyhow::bail!("File extension not one of: zip, xml, gml.")
-                }
-            };
-            let mut compressed_files = None;
-            let mut decompressed_size = None;
-            if let FileType::ZIP = file_type {
-                let mut cf: Vec<CompressedFile> = Vec::new();
-                let f = File::open(&path)
-                    .with_context(|| format!("Failed to open ZIP file: `{}`.", &path.display()))?;
-                let mut archive = ZipArchive::new(f).with_context(|| {
-                    format!("Failed to decompress ZIP file: `{}`.", &path.display())
-                })?;
-                decompressed_size = archive.decompressed_size();
-                for idx in 0..archive.len() {
-                    let entry = archive
-                        .by_index(idx)
-                        .with_context(|| "Could not access file inside ZIP archive")?;
-                    let name = entry
-                        .enclosed_name()
-                        .with_context(|| "Could not read file name inside ZIP archive.")?;
-                    // for now we'll determine if the file inside zip should be processed based on extension
-                    let file_extension = &name
-                        .extension()
-                        .expect("Could not read file extension.")
-                        .to_string_lossy()
-                        .to_lowercase();
-                    let to_be_parsed = match schema_version {
-                        SchemaVersion::Model2012 => file_extension == "xml",
-                        SchemaVersion::Model2021 => file_extension == "gml",
-                    };
-                    cf.push(CompressedFile {
-                        index: idx,
-                        name: name.to_string_lossy().to_string(),
-                        compressed_size: entry.compressed_size(),
-                        uncompressed_size: entry.size(),
-                        to_be_parsed: to_be_parsed,
-                    });
-                }
-                compressed_files = Some(cf);
+            let mut record =
+                FileRecord::from_path(potential_path, schema_version, skip_bad_entries)?;
+            for entry in record.compressed_files.iter_mut().flatten() {
+                entry.to_be_parsed = zip_entry_filter.to_be_parsed(&entry.name, entry.to_be_parsed);
             }
-            paths.push(FileRecord {
-                file_type: file_type,
-                path: path,
-                size_in_bytes: file_metadata.len(),
-                compressed_files: compressed_files,
-                decompressed_size,
-            });
+            paths.push(record);
         }
     }
     if paths.is_empty() {
@@ -230,6 +655,76 @@ pub(crate) fn parse_input_paths(
     Ok(paths)
 }
 
+/// Row of `--list-zip`: an entry of a ZIP archive.
+#[derive(serde::Serialize)]
+struct ZipEntryListing<'a> {
+    path: &'a Path,
+    index: usize,
+    name: &'a str,
+    compressed_size: u64,
+    uncompressed_size: u64,
+    to_be_parsed: bool,
+}
+
+/// Reads paths or glob patterns from an `--input-list` file: one per line,
+/// trimmed, skipping blank lines and `#` comments.
+fn read_input_list(path: &Path) -> anyhow::Result<Vec<String>> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read input list file: `{}`.", path.display()))?;
+    Ok(content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect())
+}
+
+/// TERYT path (`archive.zip::entry`) of the TERC file inside the input ZIP
+/// archives, used for schema 2021 when `--teryt-path` is not given. It is an
+/// error if there is more than one.
+fn find_terc_in_inputs(inputs: &[FileRecord]) -> anyhow::Result<Option<PathBuf>> {
+    let found: Vec<PathBuf> = inputs
+        .iter()
+        .flat_map(|file| {
+            file.compressed_files
+                .iter()
+                .flatten()
+                .filter(|entry| terc::is_terc_entry_name(&entry.name))
+                .map(|entry| terc::zip_entry_path(file.read_path(), &entry.name))
+        })
+        .collect();
+    match found.as_slice() {
+        [] => Ok(None),
+        [teryt_path] => Ok(Some(teryt_path.clone())),
+        _ => anyhow::bail!(
+            "Found more than one TERC file inside the input ZIP files ({}), choose one with --teryt-path.",
+            found
+                .iter()
+                .map(|path| format!("`{}`", path.display()))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    }
+}
+
+/// Detects the schema version from the first file matching `input_paths`.
+fn detect_schema_version(input_paths: &Vec<String>) -> anyhow::Result<SchemaVersion> {
+    for raw_path in input_paths {
+        let globbed_paths = glob(raw_path)
+            .with_context(|| format!("Failed to parse glob pattern: `{}`", &raw_path))?;
+        if let Some(path) = globbed_paths.flatten().find(|p| p.is_file()) {
+            let schema_version = SchemaVersion::detect_from_path(&path)?;
+            progress!(
+                "Detected schema version {} from file `{}`.",
+                schema_version,
+                path.display()
+            );
+            return Ok(schema_version);
+        }
+    }
+    anyhow::bail!("Could not read input files. Do the files exist? Are the paths correct?")
+}
+
 pub const PRG_DOWNLOAD_URL: &str =
     "https://integracja.gugik.gov.pl/PRG/pobierz.php?adresy_zbiorcze_gml";
 
@@ -241,7 +736,7 @@ pub fn download_prg_data(
         .timeout(std::time::Duration::from_secs(3600))
         .build()
         .with_context(|| "Failed to build HTTP client.")?;
-    println!("Sending download request to: {}", PRG_DOWNLOAD_URL);
+    progress!("Sending download request to: {}", PRG_DOWNLOAD_URL);
     let mut response = client
         .get(PRG_DOWNLOAD_URL)
         .send()
@@ -250,22 +745,22 @@ pub fn download_prg_data(
         anyhow::bail!("Download request failed with status: {}", response.status());
     }
     if let Some(path) = save_path {
-        println!("Download started, saving to: {}", path.display());
+        progress!("Download started, saving to: {}", path.display());
         let mut file = File::create(path)
             .with_context(|| format!("Failed to create file: {}", path.display()))?;
         std::io::copy(&mut response, &mut file)
             .with_context(|| format!("Failed to stream download to: {}", path.display()))?;
-        println!("Download complete.");
+        progress!("Download complete.");
         Ok(None)
     } else {
         let mut temp_file = tempfile::Builder::new()
             .suffix(".zip")
             .tempfile()
             .with_context(|| "Failed to create temporary file for download.")?;
-        println!("Download started, saving to temporary file...");
+        progress!("Download started, saving to temporary file...");
         std::io::copy(&mut response, &mut temp_file)
             .with_context(|| "Failed to stream download to temporary file.")?;
-        println!("Download complete.");
+        progress!("Download complete.");
         temp_file
             .seek(std::io::SeekFrom::Start(0))
             .with_context(|| "Failed to seek to start of temporary file after download.")?;
@@ -283,14 +778,169 @@ pub struct ParsedArgs {
     pub teryt_api_username: Option<String>,
     pub teryt_api_password: Option<String>,
     pub teryt_path: Option<std::path::PathBuf>,
+    pub teryt_date: Option<NaiveDate>,
     pub batch_size: usize,
+    pub max_memory: Option<usize>,
     pub schema_version: SchemaVersion,
     pub output_format: OutputFormat,
+    #[cfg(feature = "geoparquet")]
     pub compression_level: Option<i32>,
+    #[cfg(feature = "geoparquet")]
     pub parquet_compression: parquet::basic::Compression,
     pub parquet_row_group_size: usize,
+    pub parquet_data_page_size: Option<usize>,
+    pub parquet_dictionary_page_size: Option<usize>,
+    pub flush_every: Option<usize>,
+    pub row_group_per_voivodeship: bool,
+    pub parquet_bloom_filter_columns: Vec<String>,
+    pub csv_delimiter: u8,
+    pub decimal_comma: bool,
+    pub csv_null_string: String,
+    pub csv_date_format: String,
+    pub csv_timestamp_format: String,
+    pub timestamp_timezone: TimestampTimezone,
+    #[cfg(feature = "geoparquet")]
     pub parquet_version: parquet::file::properties::WriterVersion,
     pub crs: CRS,
+    #[cfg(feature = "geoparquet")]
+    pub geometry_encoding: GeoParquetWriterEncoding,
+    #[cfg(feature = "geoparquet")]
+    pub points_only_schema: bool,
+    pub errors_to: Option<PathBuf>,
+    pub join_audit: Option<PathBuf>,
+    pub max_errors: Option<usize>,
+    pub start_date: Option<NaiveDate>,
+    pub end_date: Option<NaiveDate>,
+    pub sample_rate: Option<f64>,
+    pub sample_seed: u64,
+    pub street_filter: StreetFilter,
+    pub drop_null_geometry: bool,
+    pub tail: Option<usize>,
+    pub spatial_sort: bool,
+    pub spatial_sort_buffer: Option<usize>,
+    pub id_prefix: Option<String>,
+    pub status_raw: bool,
+    pub skip_dictionary: bool,
+    pub input_crs: CRS,
+    pub coord_precision: u32,
+    pub street_types: Option<Arc<StreetTypes>>,
+    pub verbose_unknown_tags: bool,
+    pub benchmark: bool,
+    pub fail_on_unknown_tag: bool,
+    pub normalize_whitespace: bool,
+    pub untrimmed_columns: UntrimmedColumns,
+    pub name_case: NameCase,
+    /// Old to new output column names.
+    pub column_renames: HashMap<String, String>,
+    pub zip_entry_filter: ZipEntryFilter,
+    pub skip_bad_entries: bool,
+    pub manifest_path: Option<PathBuf>,
+    /// Expected addresses per voivodeship (`--expect-counts`).
+    pub expected_counts: Option<BTreeMap<String, usize>>,
+    pub expect_counts_tolerance: f64,
+    /// Paths or patterns of the old snapshot (`--compare`).
+    pub compare_paths: Vec<String>,
+    pub compare_with: Vec<FileRecord>,
+    pub normalize_names: bool,
+    pub split_house_number: bool,
+    pub split_valid_from: bool,
+    pub add_full_address: bool,
+    pub add_source_column: bool,
+    pub grid_size: Option<u32>,
+    pub parallel_parse: bool,
+    pub mmap: bool,
+    pub workers_per_file: usize,
+    pub rows_per_file: Option<usize>,
+    pub checkpoint: Option<PathBuf>,
+    pub resume: bool,
+    pub validate_only: bool,
+    /// Number of coordinates read by `--transform-only`.
+    pub transform_only: Option<usize>,
+    pub print_schema: bool,
+}
+
+impl ParsedArgs {
+    /// Options of the conversion of `inputs` (the parsed or downloaded input files).
+    pub fn convert_options(&self, inputs: Vec<FileRecord>) -> ConvertOptions {
+        ConvertOptions {
+            inputs,
+            output_path: self.output_path.clone(),
+            schema_version: self.schema_version,
+            output_format: self.output_format,
+            crs: self.crs,
+            teryt_path: self.teryt_path.clone(),
+            teryt_date: self.teryt_date.map(|d| d.to_string()),
+            download_teryt: self.download_teryt,
+            teryt_api_username: self.teryt_api_username.clone(),
+            teryt_api_password: self.teryt_api_password.clone(),
+            batch_size: self.batch_size,
+            #[cfg(feature = "geoparquet")]
+            parquet_compression: self.parquet_compression,
+            parquet_row_group_size: self.parquet_row_group_size,
+            parquet_data_page_size: self.parquet_data_page_size,
+            parquet_dictionary_page_size: self.parquet_dictionary_page_size,
+            #[cfg(feature = "geoparquet")]
+            parquet_version: self.parquet_version,
+            #[cfg(feature = "geoparquet")]
+            geometry_encoding: self.geometry_encoding,
+            #[cfg(feature = "geoparquet")]
+            points_only_schema: self.points_only_schema,
+            flush_every: self.flush_every,
+            row_group_per_voivodeship: self.row_group_per_voivodeship,
+            parquet_bloom_filter_columns: self.parquet_bloom_filter_columns.clone(),
+            csv_delimiter: self.csv_delimiter,
+            decimal_comma: self.decimal_comma,
+            csv_null_string: self.csv_null_string.clone(),
+            csv_date_format: self.csv_date_format.clone(),
+            csv_timestamp_format: self.csv_timestamp_format.clone(),
+            timestamp_timezone: self.timestamp_timezone,
+            errors_to: self.errors_to.clone(),
+            max_errors: self.max_errors,
+            filter: RecordFilter {
+                start_date: self.start_date,
+                end_date: self.end_date,
+                sample_rate: self.sample_rate,
+                sample_seed: self.sample_seed,
+                street: self.street_filter,
+                drop_null_geometry: self.drop_null_geometry,
+                ..Default::default()
+            },
+            tail: self.tail,
+            spatial_sort: self.spatial_sort,
+            spatial_sort_buffer: self.spatial_sort_buffer,
+            id_prefix: self.id_prefix.clone(),
+            status_raw: self.status_raw,
+            skip_dictionary: self.skip_dictionary,
+            input_crs: self.input_crs,
+            coord_precision: Some(self.coord_precision),
+            street_types: self.street_types.clone(),
+            verbose_unknown_tags: self.verbose_unknown_tags,
+            benchmark: self.benchmark,
+            fail_on_unknown_tag: self.fail_on_unknown_tag,
+            normalize_whitespace: self.normalize_whitespace,
+            untrimmed_columns: self.untrimmed_columns,
+            name_case: self.name_case,
+            column_renames: self.column_renames.clone(),
+            normalize_names: self.normalize_names,
+            split_house_number: self.split_house_number,
+            split_valid_from: self.split_valid_from,
+            add_full_address: self.add_full_address,
+            add_source_column: self.add_source_column,
+            grid_size: self.grid_size,
+            parallel_parse: self.parallel_parse,
+            mmap: self.mmap,
+            workers_per_file: self.workers_per_file,
+            rows_per_file: self.rows_per_file,
+            checkpoint_path: self.checkpoint.clone(),
+            resume: self.resume,
+            validate_only: self.validate_only,
+            skip_bad_entries: self.skip_bad_entries,
+            interrupt: None,
+            join_audit: self.join_audit.clone().map(JoinAudit::to_file),
+            count_voivodeships: self.expected_counts.is_some(),
+            compare_with: self.compare_with.clone(),
+        }
+    }
 }
 
 pub fn print_parsed_args(parsed_args: &ParsedArgs) {
@@ -349,8 +999,27 @@ pub fn print_parsed_args(parsed_args: &ParsedArgs) {
             };
         }
     }
-    println!("  Output file: {}", parsed_args.output_path.display());
-    println!("  Output file format: {}", parsed_args.output_format);
+    if let Some(max_points) = parsed_args.transform_only {
+        println!(
+            "  Output: none (reprojection benchmark of up to {} points)",
+            max_points
+        );
+    } else if parsed_args.validate_only {
+        println!("  Output: none (validation only)");
+    } else {
+        println!("  Output file: {}", parsed_args.output_path.display());
+        println!("  Output file format: {}", parsed_args.output_format);
+        if let Some(rows_per_file) = parsed_args.rows_per_file {
+            println!("  Addresses per file: {}", rows_per_file);
+        }
+        if let Some(checkpoint) = &parsed_args.checkpoint {
+            println!(
+                "  Checkpoint: {}{}",
+                checkpoint.display(),
+                if parsed_args.resume { " (resume)" } else { "" }
+            );
+        }
+    }
     println!("  Schema version: {}", parsed_args.schema_version);
     match parsed_args.schema_version {
         SchemaVersion::Model2012 => {}
@@ -366,11 +1035,44 @@ pub fn print_parsed_args(parsed_args: &ParsedArgs) {
                     "  TERYT file: {}",
                     &parsed_args.teryt_path.as_ref().unwrap().display()
                 );
+                if let Some(date) = parsed_args.teryt_date {
+                    println!("  TERYT date: {}", date);
+                }
             }
         }
     }
     println!("  Batch size: {}", parsed_args.batch_size);
-    if let OutputFormat::GeoParquet = parsed_args.output_format {
+    if let Some(max_memory) = parsed_args.max_memory {
+        println!(
+            "  Max memory: {} MB (~{} bytes per row)",
+            max_memory,
+            estimated_bytes_per_row()
+        );
+    }
+    if parsed_args.output_format == OutputFormat::CSV {
+        if parsed_args.csv_delimiter != b',' {
+            println!("  CSV delimiter: {}", parsed_args.csv_delimiter as char);
+        }
+        if parsed_args.decimal_comma {
+            println!("  Decimal comma: yes");
+        }
+        if !parsed_args.csv_null_string.is_empty() {
+            println!("  Null string: {}", parsed_args.csv_null_string);
+        }
+        if parsed_args.csv_date_format != DEFAULT_CSV_DATE_FORMAT {
+            println!("  Date format: {}", parsed_args.csv_date_format);
+        }
+        if parsed_args.csv_timestamp_format
+            != default_csv_timestamp_format(parsed_args.timestamp_timezone)
+        {
+            println!("  Timestamp format: {}", parsed_args.csv_timestamp_format);
+        }
+    }
+    if parsed_args.timestamp_timezone != TimestampTimezone::Utc {
+        println!("  Timestamp timezone: {}", parsed_args.timestamp_timezone);
+    }
+    #[cfg(feature = "geoparquet")]
+    if parsed_args.output_format.is_parquet() {
         println!("  Parquet compression: {}", parsed_args.parquet_compression);
         if parsed_args.compression_level.is_some() {
             println!(
@@ -382,6 +1084,24 @@ pub fn print_parsed_args(parsed_args: &ParsedArgs) {
             "  Parquet max row group size: {}",
             parsed_args.parquet_row_group_size
         );
+        if let Some(size) = parsed_args.parquet_data_page_size {
+            println!("  Parquet data page size: {} bytes", size);
+        }
+        if let Some(size) = parsed_args.parquet_dictionary_page_size {
+            println!("  Parquet dictionary page size: {} bytes", size);
+        }
+        if let Some(flush_every) = parsed_args.flush_every {
+            println!("  Flush row group every: {} rows", flush_every);
+        }
+        if parsed_args.row_group_per_voivodeship {
+            println!("  Row group per voivodeship: yes");
+        }
+        if !parsed_args.parquet_bloom_filter_columns.is_empty() {
+            println!(
+                "  Bloom filter columns: {}",
+                parsed_args.parquet_bloom_filter_columns.join(", ")
+            );
+        }
         match parsed_args.parquet_version {
             WriterVersion::PARQUET_1_0 => {
                 println!("  Parquet file format version: v1")
@@ -390,32 +1110,432 @@ pub fn print_parsed_args(parsed_args: &ParsedArgs) {
                 println!("  Parquet file format version: v2")
             }
         };
+    }
+    #[cfg(feature = "geoparquet")]
+    if let OutputFormat::GeoParquet = parsed_args.output_format {
         println!("  CRS: {}", parsed_args.crs);
+        match parsed_args.geometry_encoding {
+            GeoParquetWriterEncoding::WKB => println!("  Geometry encoding: wkb"),
+            GeoParquetWriterEncoding::GeoArrow => println!("  Geometry encoding: native"),
+        };
+        if parsed_args.points_only_schema {
+            println!("  Points only schema: yes");
+        }
     };
+    if let Some(path) = &parsed_args.errors_to {
+        println!("  Errors file: {}", path.display());
+    }
+    if let Some(path) = &parsed_args.join_audit {
+        println!("  Join audit file: {}", path.display());
+    }
+    if let Some(max_errors) = parsed_args.max_errors {
+        println!("  Max errors: {}", max_errors);
+    }
+    if let Some(date) = parsed_args.start_date {
+        println!("  Start date (wazny_od_lub_data_nadania): {}", date);
+    }
+    if let Some(date) = parsed_args.end_date {
+        println!("  End date (wazny_od_lub_data_nadania): {}", date);
+    }
+    if let Some(rate) = parsed_args.sample_rate {
+        println!(
+            "  Sample rate: {} (seed: {})",
+            rate, parsed_args.sample_seed
+        );
+    }
+    if !parsed_args.column_renames.is_empty() {
+        let mut renames: Vec<String> = parsed_args
+            .column_renames
+            .iter()
+            .map(|(old, new)| format!("{}={}", old, new))
+            .collect();
+        renames.sort();
+        println!("  Renamed columns: {}", renames.join(", "));
+    }
+    if let Some(prefix) = &parsed_args.id_prefix {
+        println!("  Component id prefix: {}", prefix);
+    }
+    if parsed_args.skip_bad_entries {
+        println!("  Skip bad ZIP entries: yes");
+    }
+    if parsed_args.status_raw {
+        println!("  Raw status values: yes");
+    }
+    if parsed_args.skip_dictionary {
+        println!("  Skip dictionary: yes");
+    }
+    if parsed_args.input_crs != CRS::Epsg2180 {
+        println!("  Input CRS: {}", parsed_args.input_crs);
+    }
+    println!(
+        "  Longitude/latitude decimals: {}",
+        parsed_args.coord_precision
+    );
+    if parsed_args.street_types.is_some() {
+        println!("  Custom street types: yes");
+    }
+    if parsed_args.verbose_unknown_tags {
+        println!("  Unknown tags after every batch: yes");
+    }
+    if parsed_args.fail_on_unknown_tag {
+        println!("  Fail on unknown tags: yes");
+    }
+    if parsed_args.benchmark {
+        println!("  Benchmark: yes");
+    }
+    if parsed_args.normalize_whitespace {
+        println!("  Normalize whitespace: yes");
+    }
+    if parsed_args.untrimmed_columns != UntrimmedColumns::default() {
+        println!(
+            "  Untrimmed columns: {}",
+            parsed_args.untrimmed_columns.names().join(", ")
+        );
+    }
+    if parsed_args.name_case != NameCase::Preserve {
+        println!("  Name case: {}", parsed_args.name_case);
+    }
+    if let Some(tail) = parsed_args.tail {
+        println!("  Only last addresses: {}", tail);
+    }
+    if parsed_args.spatial_sort {
+        match parsed_args.spatial_sort_buffer {
+            Some(rows) => println!("  Spatial sort: hilbert (in runs of {} rows)", rows),
+            None => println!("  Spatial sort: hilbert"),
+        }
+    }
+    if parsed_args.street_filter != StreetFilter::Any {
+        println!("  Only addresses: {}", parsed_args.street_filter);
+    }
+    if parsed_args.drop_null_geometry {
+        println!("  Drop addresses without coordinates: yes");
+    }
+    if let Some(path) = &parsed_args.manifest_path {
+        println!("  Manifest file: {}", path.display());
+    }
+    if let Some(expected_counts) = &parsed_args.expected_counts {
+        println!(
+            "  Expected counts: {} voivodeships (tolerance: {}%)",
+            expected_counts.len(),
+            parsed_args.expect_counts_tolerance
+        );
+    }
+    if !parsed_args.compare_paths.is_empty() {
+        println!("  Compare with:");
+        for file in &parsed_args.compare_with {
+            println!("    - {}", file.path.display());
+        }
+    }
+    if parsed_args.normalize_names {
+        println!("  Normalized name columns: yes");
+    }
+    if parsed_args.split_house_number {
+        println!("  House number columns: yes");
+    }
+    if parsed_args.split_valid_from {
+        println!("  Valid from columns: yes");
+    }
+    if parsed_args.add_full_address {
+        println!("  Full address column: yes");
+    }
+    if parsed_args.add_source_column {
+        println!("  Source column: yes");
+    }
+    if let Some(size) = parsed_args.grid_size {
+        println!("  Grid column: {} m", size);
+    }
+    if parsed_args.parallel_parse {
+        println!("  Parallel parsing (experimental): yes");
+    }
+    if parsed_args.mmap {
+        println!("  Memory-mapped input: yes");
+    }
+    if parsed_args.workers_per_file != DEFAULT_WORKERS_PER_FILE {
+        println!("  Workers per file: {}", parsed_args.workers_per_file);
+    }
     println!("----------------------------------------");
 }
 
-impl TryFrom<RawArgs> for ParsedArgs {
-    type Error = anyhow::Error;
+/// Parses `old=new` pairs of `--rename-columns`. Whether the old names exist
+/// is checked against the output schema.
+fn parse_column_renames(pairs: &[String]) -> anyhow::Result<HashMap<String, String>> {
+    let mut renames = HashMap::new();
+    for pair in pairs {
+        let Some((old, new)) = pair.split_once('=') else {
+            anyhow::bail!("--rename-columns expects `old=new` pairs, got `{}`.", pair);
+        };
+        let (old, new) = (old.trim(), new.trim());
+        if old.is_empty() || new.is_empty() {
+            anyhow::bail!("--rename-columns expects `old=new` pairs, got `{}`.", pair);
+        }
+        if renames.insert(old.to_string(), new.to_string()).is_some() {
+            anyhow::bail!("Column `{}` is renamed more than once.", old);
+        }
+    }
+    Ok(renames)
+}
 
-    fn try_from(value: RawArgs) -> anyhow::Result<ParsedArgs> {
-        let batch_size = value.batch_size.unwrap_or(DEFAULT_BATCH_SIZE);
-        let download_data = value.download_data.is_some();
-        let download_data_path = value
+/// Builds the street types of `--street-type` and `--abbreviate-street-types`,
+/// `None` when both are missing.
+fn parse_street_types(pairs: &[String], abbreviate: bool) -> anyhow::Result<Option<StreetTypes>> {
+    if pairs.is_empty() && !abbreviate {
+        return Ok(None);
+    }
+    let mut street_types = if abbreviate {
+        StreetTypes::abbreviated()
+    } else {
+        StreetTypes::default()
+    };
+    for pair in pairs {
+        let Some((code, name)) = pair.split_once('=') else {
+            anyhow::bail!("--street-type expects `code=type` pairs, got `{}`.", pair);
+        };
+        street_types
+            .set(code.trim(), name.trim())
+            .context("Invalid --street-type.")?;
+    }
+    Ok(Some(street_types))
+}
+
+/// Rejects strftime patterns with unknown specifiers, which would otherwise
+/// fail only when the first batch is written.
+fn check_strftime_pattern(option: &str, pattern: &str) -> anyhow::Result<()> {
+    if StrftimeItems::new(pattern).any(|item| matches!(item, Item::Error)) {
+        anyhow::bail!(
+            "--{} is not a valid strftime pattern: `{}`.",
+            option,
+            pattern
+        );
+    }
+    Ok(())
+}
+
+impl RawArgs {
+    /// `--quiet`: warnings and errors, `--verbose`: also unknown tags after
+    /// every batch, neither: also parameters, progress and summary. With
+    /// `--list-zip` progress is not printed, so that scripts can read the
+    /// listing.
+    pub fn verbosity(&self) -> Verbosity {
+        if self.quiet.unwrap_or(false) || !self.list_zip.is_empty() {
+            Verbosity::Quiet
+        } else if self.verbose.unwrap_or(false) {
+            Verbosity::Verbose
+        } else {
+            Verbosity::Normal
+        }
+    }
+
+    /// Address to listen on with `--serve`.
+    pub fn serve_address(&self) -> Option<&str> {
+        self.serve.as_deref()
+    }
+
+    /// Whether `--self-test` was given.
+    pub fn self_test(&self) -> bool {
+        self.self_test.unwrap_or(false)
+    }
+
+    /// Entries of the `--list-zip` archives formatted as `--format`, `None`
+    /// without `--list-zip`.
+    pub fn list_zip(&self) -> anyhow::Result<Option<String>> {
+        if self.list_zip.is_empty() {
+            return Ok(None);
+        }
+        let schema_version = match self.schema_version {
+            Some(schema_version) => schema_version,
+            None => detect_schema_version(&self.list_zip)?,
+        };
+        let zip_entry_filter =
+            ZipEntryFilter::new(&self.include_zip_entry, &self.exclude_zip_entry)?;
+        let records = parse_input_paths(
+            &self.list_zip,
+            &schema_version,
+            &zip_entry_filter,
+            self.skip_bad_entries.unwrap_or(false),
+        )?;
+        let mut entries = Vec::new();
+        for record in &records {
+            let compressed_files = record
+                .compressed_files
+                .as_ref()
+                .with_context(|| format!("`{}` is not a ZIP archive.", record.path.display()))?;
+            entries.extend(compressed_files.iter().map(|file| ZipEntryListing {
+                path: &record.path,
+                index: file.index,
+                name: &file.name,
+                compressed_size: file.compressed_size,
+                uncompressed_size: file.uncompressed_size,
+                to_be_parsed: file.to_be_parsed,
+            }));
+        }
+        Ok(Some(match self.format.unwrap_or_default() {
+            ListFormatArg::Text => {
+                let mut table =
+                    "path\tindex\tname\tcompressed_size\tuncompressed_size\tto_be_parsed\n"
+                        .to_string();
+                for entry in &entries {
+                    table.push_str(&format!(
+                        "{}\t{}\t{}\t{}\t{}\t{}\n",
+                        entry.path.display(),
+                        entry.index,
+                        entry.name,
+                        entry.compressed_size,
+                        entry.uncompressed_size,
+                        entry.to_be_parsed
+                    ));
+                }
+                table
+            }
+            ListFormatArg::Json => {
+                serde_json::to_string_pretty(&entries)
+                    .context("Could not serialize ZIP entries.")?
+                    + "\n"
+            }
+        }))
+    }
+
+    /// Fills the options of `ENV_FALLBACKS` that were not given on the
+    /// command line from the environment variables returned by `env` (unset
+    /// or empty variables are ignored). Values are parsed like on the command
+    /// line, an invalid one is an error naming the variable. A batch size or
+    /// memory budget from the environment is only used if neither
+    /// `--batch-size` nor `--max-memory` was given, and a TERYT path only
+    /// without `--download-teryt`. Called before `ParsedArgs::try_from`, which
+    /// does not read the environment, with `std::env::var`.
+    pub fn with_env_fallbacks(
+        mut self,
+        env: impl Fn(&str) -> Option<String>,
+    ) -> anyhow::Result<Self> {
+        fn fallback<T>(
+            env: &impl Fn(&str) -> Option<String>,
+            name: &str,
+            parse: impl FnOnce(&str) -> anyhow::Result<T>,
+        ) -> anyhow::Result<Option<T>> {
+            let Some(value) = env(name).filter(|v| !v.trim().is_empty()) else {
+                return Ok(None);
+            };
+            let option = ENV_FALLBACKS
+                .iter()
+                .find(|(var, _)| *var == name)
+                .map_or("", |(_, option)| option);
+            parse(value.trim()).map(Some).with_context(|| {
+                format!(
+                    "Invalid value `{}` of environment variable {} (--{}).",
+                    value, name, option
+                )
+            })
+        }
+        // the same conversions as the command line parsers (`FromStr` of the
+        // library enums is their `TryFrom<&str>`)
+        fn parse<T>(value: &str) -> anyhow::Result<T>
+        where
+            T: std::str::FromStr,
+            T::Err: Into<anyhow::Error>,
+        {
+            value.parse::<T>().map_err(Into::into)
+        }
+
+        if self.batch_size.is_none() && self.max_memory.is_none() {
+            self.batch_size = fallback(&env, "PRG_BATCH_SIZE", parse)?;
+            if self.batch_size.is_none() {
+                self.max_memory = fallback(&env, "PRG_MAX_MEMORY", parse)?;
+            }
+        }
+        if self.schema_version.is_none() {
+            self.schema_version = fallback(&env, "PRG_SCHEMA_VERSION", parse)?;
+        }
+        if self.teryt_path.is_none() && !self.teryt_download.unwrap_or(false) {
+            self.teryt_path = fallback(&env, "PRG_TERYT_PATH", |v| Ok(PathBuf::from(v)))?;
+        }
+        if self.parquet_compression.is_none() {
+            self.parquet_compression = fallback(&env, "PRG_COMPRESSION", parse)?;
+        }
+        if self.compression_level.is_none() {
+            self.compression_level = fallback(&env, "PRG_COMPRESSION_LEVEL", parse)?;
+        }
+        if self.parquet_row_group_size.is_none() {
+            self.parquet_row_group_size = fallback(&env, "PRG_ROW_GROUP_SIZE", parse)?;
+        }
+        if self.crs_epsg.is_none() {
+            self.crs_epsg = fallback(&env, "PRG_CRS_EPSG", parse)?;
+        }
+        if self.timestamp_tz.is_none() {
+            self.timestamp_tz = fallback(&env, "PRG_TIMESTAMP_TZ", parse)?;
+        }
+        if self.max_errors.is_none() {
+            self.max_errors = fallback(&env, "PRG_MAX_ERRORS", parse)?;
+        }
+        if self.workers_per_file.is_none() {
+            self.workers_per_file = fallback(&env, "PRG_WORKERS_PER_FILE", |v| {
+                let workers = parse::<u8>(v)?;
+                if !(1..=2).contains(&workers) {
+                    anyhow::bail!("{} is not in 1..=2", workers);
+                }
+                Ok(workers)
+            })?;
+        }
+        Ok(self)
+    }
+}
+
+impl TryFrom<RawArgs> for ParsedArgs {
+    type Error = anyhow::Error;
+
+    fn try_from(value: RawArgs) -> anyhow::Result<ParsedArgs> {
+        let max_memory = if value.batch_size.is_some() {
+            None
+        } else {
+            value.max_memory
+        };
+        let batch_size = match (value.batch_size, max_memory) {
+            (Some(batch_size), _) => batch_size,
+            (None, Some(max_memory)) => {
+                let budget_rows = max_memory * 1024 * 1024 / estimated_bytes_per_row();
+                if budget_rows == 0 {
+                    anyhow::bail!(
+                        "--max-memory {} MB is too small to fit a single row.",
+                        max_memory
+                    );
+                }
+                budget_rows.min(DEFAULT_BATCH_SIZE)
+            }
+            (None, None) => DEFAULT_BATCH_SIZE,
+        };
+        let download_data = value.download_data.is_some();
+        let download_data_path = value
             .download_data
             .as_deref()
             .filter(|s| !s.is_empty())
             .map(PathBuf::from);
-        let has_input_paths = !value.input_paths.is_empty();
+        let print_schema = value.print_schema.unwrap_or(false);
+        let mut input_paths = value.input_paths;
+        if let Some(input_list) = &value.input_list {
+            input_paths.extend(read_input_list(input_list)?);
+        }
+        let has_input_paths = !input_paths.is_empty() || value.input_list.is_some();
         if has_input_paths && download_data {
-            anyhow::bail!("Provide either --input-paths or --download-data, but not both.");
+            anyhow::bail!(
+                "Provide either --input-paths/--input-list or --download-data, but not both."
+            );
         }
-        if !has_input_paths && !download_data {
-            anyhow::bail!("Either --input-paths or --download-data must be provided.");
+        if !has_input_paths && !download_data && !print_schema {
+            anyhow::bail!(
+                "Either --input-paths, --input-list or --download-data must be provided."
+            );
         }
+        let schema_version = match value.schema_version {
+            Some(schema_version) => schema_version,
+            None if download_data => anyhow::bail!(
+                "--schema-version is required with --download-data because the downloaded archive contains files in both schemas."
+            ),
+            None if !has_input_paths => anyhow::bail!(
+                "--schema-version is required with --print-schema when no --input-paths are provided."
+            ),
+            None => detect_schema_version(&input_paths)?,
+        };
         let download_teryt_flag = {
             let mut flag = value.teryt_download.unwrap_or(false);
-            if matches!(value.schema_version, SchemaVersionArg::V2012) && flag {
+            if schema_version == SchemaVersion::Model2012 && flag {
                 println!(
                     "Warning: teryt-download was set to true but schema was set to 2012 which is not compatible. teryt-download will be treated as false."
                 );
@@ -423,14 +1543,56 @@ impl TryFrom<RawArgs> for ParsedArgs {
             }
             flag
         };
-        if matches!(value.schema_version, SchemaVersionArg::V2021)
-            && value.teryt_path.is_none()
-            && !download_teryt_flag
+        let id_prefix = match schema_version {
+            SchemaVersion::Model2012 => value.id_prefix,
+            SchemaVersion::Model2021 => {
+                if value.id_prefix.is_some() {
+                    println!("Warning: id-prefix only applies to schema 2012 and will be ignored.");
+                }
+                None
+            }
+        };
+        let status_raw = value.status_raw.unwrap_or(false);
+        if status_raw && schema_version == SchemaVersion::Model2021 {
+            println!("Warning: status-raw only applies to schema 2012 and will be ignored.");
+        }
+        let untrimmed_columns =
+            UntrimmedColumns::from_names(&value.no_trim).context("Invalid --no-trim.")?;
+        if untrimmed_columns != UntrimmedColumns::default()
+            && value.normalize_whitespace.unwrap_or(false)
         {
-            anyhow::bail!(
-                "Chosen schema 2021 but provided neither teryt file path nor teryt-download flag. PRG schema 2021 does not contain names of administrative units so they need to be read from external source."
-            )
+            anyhow::bail!("--no-trim cannot be combined with --normalize-whitespace.");
+        }
+        let skip_dictionary = value.skip_dictionary.unwrap_or(false);
+        if skip_dictionary && schema_version == SchemaVersion::Model2021 {
+            println!("Warning: skip-dictionary only applies to schema 2012 and will be ignored.");
         }
+        let street_types = parse_street_types(
+            &value.street_type,
+            value.abbreviate_street_types.unwrap_or(false),
+        )?;
+        let join_audit = match schema_version {
+            SchemaVersion::Model2021 => value.join_audit,
+            SchemaVersion::Model2012 => {
+                if value.join_audit.is_some() {
+                    println!(
+                        "Warning: join-audit only applies to schema 2021 and will be ignored."
+                    );
+                }
+                None
+            }
+        };
+        let street_types = match schema_version {
+            SchemaVersion::Model2021 => street_types.map(Arc::new),
+            SchemaVersion::Model2012 => {
+                if street_types.is_some() {
+                    println!(
+                        "Warning: street-type and abbreviate-street-types only apply to schema 2021 and will be ignored."
+                    );
+                }
+                None
+            }
+        };
         let teryt_api_username = value
             .teryt_api_username
             .unwrap_or(std::env::var("TERYT_API_USERNAME").unwrap_or_default());
@@ -442,48 +1604,294 @@ impl TryFrom<RawArgs> for ParsedArgs {
                 "When teryt-download flag is used then either the env variables need to be set or credentials needs to be provided via parameters."
             )
         }
-        let schema_version = match value.schema_version {
-            SchemaVersionArg::V2012 => SchemaVersion::Model2012,
-            SchemaVersionArg::V2021 => SchemaVersion::Model2021,
+        let teryt_date = match value.teryt_date {
+            Some(_) if download_teryt_flag => {
+                println!(
+                    "Warning: teryt-date only applies to --teryt-path, the newest catalog is downloaded."
+                );
+                None
+            }
+            teryt_date => teryt_date,
+        };
+        let output_format = value.output_format.unwrap_or(OutputFormat::CSV);
+        #[cfg(feature = "geoparquet")]
+        let compression = value
+            .parquet_compression
+            .unwrap_or(ParquetCompression::Zstd);
+        #[cfg(feature = "geoparquet")]
+        let compression_level = compression
+            .default_level()
+            .map(|default| value.compression_level.unwrap_or(default));
+        #[cfg(feature = "geoparquet")]
+        let parquet_compression = compression.to_parquet(compression_level)?;
+        let parquet_row_group_size = value.parquet_row_group_size.unwrap_or(batch_size);
+        if value.transform_only == Some(0) {
+            anyhow::bail!("--transform-only must be greater than 0.");
+        }
+        if value.rows_per_file == Some(0) {
+            anyhow::bail!("--rows-per-file must be greater than 0.");
+        }
+        if value.rows_per_file.is_none()
+            && value
+                .output_path
+                .as_ref()
+                .is_some_and(|p| p.to_string_lossy().contains(FILE_NUMBER_PLACEHOLDER))
+        {
+            anyhow::bail!(
+                "--output-path contains `{}`, which is only replaced with --rows-per-file.",
+                FILE_NUMBER_PLACEHOLDER
+            );
+        }
+        if value.checkpoint.is_some() && value.rows_per_file.is_none() {
+            anyhow::bail!("--checkpoint requires --rows-per-file.");
+        }
+        let resume = value.resume.unwrap_or(false);
+        if resume && value.checkpoint.is_none() {
+            anyhow::bail!("--resume requires --checkpoint.");
+        }
+        if value.format.is_some() {
+            anyhow::bail!("--format requires --list-zip.");
+        }
+        if value.flush_every == Some(0) {
+            anyhow::bail!("--flush-every must be greater than 0.");
+        }
+        let flush_every = if output_format.is_parquet() {
+            value.flush_every
+        } else {
+            if value.flush_every.is_some() {
+                println!(
+                    "Warning: flush-every only applies to parquet output and will be ignored."
+                );
+            }
+            None
         };
-        let output_format = match value.output_format {
-            OutputFormatArg::Csv => OutputFormat::CSV,
-            OutputFormatArg::Geoparquet => OutputFormat::GeoParquet,
+        let row_group_per_voivodeship = match value.row_group_per {
+            Some(RowGroupPerArg::Voivodeship) if output_format.is_parquet() => true,
+            Some(_) => {
+                println!(
+                    "Warning: row-group-per only applies to parquet output and will be ignored."
+                );
+                false
+            }
+            None => false,
         };
-        let compression_level = match value.parquet_compression {
-            None | Some(ParquetCompressionArg::Zstd) => Some(value.compression_level.unwrap_or(11)),
-            Some(ParquetCompressionArg::Brotli) => Some(value.compression_level.unwrap_or(6)),
-            Some(ParquetCompressionArg::Snappy) => None,
+        let parquet_bloom_filter_columns = if output_format.is_parquet() {
+            value.parquet_bloom_filter
+        } else {
+            if !value.parquet_bloom_filter.is_empty() {
+                println!(
+                    "Warning: parquet-bloom-filter only applies to parquet output and will be ignored."
+                );
+            }
+            Vec::new()
         };
-        let parquet_compression = match value.parquet_compression {
-            None | Some(ParquetCompressionArg::Zstd) => {
-                Compression::ZSTD(ZstdLevel::try_new(compression_level.unwrap())?)
+        if value.parquet_data_page_size == Some(0) {
+            anyhow::bail!("--parquet-data-page-size must be greater than 0.");
+        }
+        if value.parquet_dict_page_size == Some(0) {
+            anyhow::bail!("--parquet-dict-page-size must be greater than 0.");
+        }
+        let (parquet_data_page_size, parquet_dictionary_page_size) = if output_format.is_parquet() {
+            (value.parquet_data_page_size, value.parquet_dict_page_size)
+        } else {
+            if value.parquet_data_page_size.is_some() || value.parquet_dict_page_size.is_some() {
+                println!(
+                    "Warning: parquet-data-page-size and parquet-dict-page-size only apply to parquet output and will be ignored."
+                );
             }
-            Some(ParquetCompressionArg::Snappy) => Compression::SNAPPY,
-            Some(ParquetCompressionArg::Brotli) => Compression::BROTLI(BrotliLevel::try_new(
-                compression_level.unwrap().cast_unsigned(),
-            )?),
+            (None, None)
         };
-        let parquet_row_group_size = value.parquet_row_group_size.unwrap_or(batch_size);
+        let csv_delimiter = value.csv_delimiter.unwrap_or(',');
+        if !csv_delimiter.is_ascii() || matches!(csv_delimiter, '"' | '\n' | '\r') {
+            anyhow::bail!(
+                "--csv-delimiter must be a single ASCII character other than a quote or a line break, got `{}`.",
+                csv_delimiter
+            );
+        }
+        let decimal_comma = value.decimal_comma.unwrap_or(false);
+        if decimal_comma && csv_delimiter == ',' {
+            anyhow::bail!(
+                "--decimal-comma cannot be used with `,` as the CSV delimiter, choose another one with --csv-delimiter (e.g. `;`)."
+            );
+        }
+        let csv_null_string = value.null_string.unwrap_or_default();
+        if csv_null_string.contains([csv_delimiter, '"', '\n', '\r']) {
+            anyhow::bail!(
+                "--null-string cannot contain the CSV delimiter, a quote or a line break, got `{}`.",
+                csv_null_string
+            );
+        }
+        let csv_date_format = value
+            .date_format
+            .unwrap_or_else(|| DEFAULT_CSV_DATE_FORMAT.to_string());
+        check_strftime_pattern("date-format", &csv_date_format)?;
+        let timestamp_timezone = value.timestamp_tz.unwrap_or_default();
+        let csv_timestamp_format = value
+            .timestamp_format
+            .unwrap_or_else(|| default_csv_timestamp_format(timestamp_timezone).to_string());
+        check_strftime_pattern("timestamp-format", &csv_timestamp_format)?;
+        if output_format != OutputFormat::CSV
+            && (value.csv_delimiter.is_some()
+                || decimal_comma
+                || !csv_null_string.is_empty()
+                || csv_date_format != DEFAULT_CSV_DATE_FORMAT
+                || csv_timestamp_format != default_csv_timestamp_format(timestamp_timezone))
+        {
+            println!(
+                "Warning: csv-delimiter, decimal-comma, null-string, date-format and timestamp-format only apply to csv output and will be ignored."
+            );
+        }
+        #[cfg(feature = "geoparquet")]
         let parquet_version = match value.parquet_version {
             None | Some(ParquetVersionArg::V2) => WriterVersion::PARQUET_2_0,
             Some(ParquetVersionArg::V1) => WriterVersion::PARQUET_1_0,
         };
-        let crs = match value.crs_epsg {
-            None | Some(CrsEpsgArg::Epsg2180) => CRS::Epsg2180,
-            Some(CrsEpsgArg::Epsg4326) => CRS::Epsg4326,
+        let crs = value.crs_epsg.unwrap_or(CRS::Epsg2180);
+        let coord_precision = value.coord_precision.unwrap_or(DEFAULT_COORD_PRECISION);
+        if coord_precision > MAX_COORD_PRECISION {
+            anyhow::bail!(
+                "--coord-precision must be between 0 and {}.",
+                MAX_COORD_PRECISION
+            );
+        }
+        // coordinates are read in EPSG:2180 and longitude/latitude are always
+        // derived, so check all projections now instead of at the first address
+        let crs_problems = missing_projections(&[2180, 4326, crs.epsg_code()]);
+        if !crs_problems.is_empty() {
+            anyhow::bail!("Invalid CRS options:\n  - {}", crs_problems.join("\n  - "));
+        }
+        #[cfg(feature = "geoparquet")]
+        let geometry_encoding = match value.geometry_encoding {
+            None | Some(GeometryEncodingArg::Wkb) => GeoParquetWriterEncoding::WKB,
+            Some(GeometryEncodingArg::Native) => GeoParquetWriterEncoding::GeoArrow,
+        };
+        #[cfg(feature = "geoparquet")]
+        let points_only_schema = match value.points_only_schema {
+            Some(true) if output_format == OutputFormat::GeoParquet => true,
+            Some(true) => {
+                println!(
+                    "Warning: points-only-schema only applies to geoparquet output and will be ignored."
+                );
+                false
+            }
+            _ => false,
+        };
+        #[cfg(feature = "protobuf")]
+        if output_format == OutputFormat::Protobuf
+            && (!value.rename_columns.is_empty()
+                || value.normalize_names.unwrap_or(false)
+                || value.split_house_number.unwrap_or(false)
+                || value.split_valid_from.unwrap_or(false)
+                || value.add_full_address.unwrap_or(false)
+                || value.add_source_column.unwrap_or(false)
+                || value.grid.is_some()
+                || !value.compare.is_empty())
+        {
+            println!(
+                "Warning: pbf output has the fixed fields of `proto/address.proto`, rename-columns and columns added by other options will be ignored."
+            );
+        }
+        if let (Some(start), Some(end)) = (value.start_date, value.end_date)
+            && start > end
+        {
+            anyhow::bail!("--start-date ({}) is after --end-date ({}).", start, end);
+        }
+        if let Some(rate) = value.sample_rate
+            && !(rate > 0.0 && rate <= 1.0)
+        {
+            anyhow::bail!(
+                "--sample-rate must be greater than 0 and at most 1, got {}.",
+                rate
+            );
+        }
+        if value.sample_seed.is_some() && value.sample_rate.is_none() {
+            anyhow::bail!("--sample-seed requires --sample-rate.");
+        }
+        if value.tail == Some(0) {
+            anyhow::bail!("--tail must be greater than 0.");
+        }
+        if value.spatial_sort_buffer == Some(0) {
+            anyhow::bail!("--spatial-sort-buffer must be greater than 0.");
+        }
+        if value.spatial_sort_buffer.is_some() && value.spatial_sort.is_none() {
+            anyhow::bail!("--spatial-sort-buffer requires --spatial-sort.");
+        }
+        if let Some(tolerance) = value.expect_counts_tolerance
+            && !(tolerance >= 0.0 && tolerance.is_finite())
+        {
+            anyhow::bail!(
+                "--expect-counts-tolerance must be at least 0, got {}.",
+                tolerance
+            );
+        }
+        if value.expect_counts_tolerance.is_some() && value.expect_counts.is_none() {
+            anyhow::bail!("--expect-counts-tolerance requires --expect-counts.");
+        }
+        let expected_counts = value
+            .expect_counts
+            .as_deref()
+            .map(read_expected_counts)
+            .transpose()?;
+        let street_filter = match (
+            value.require_street.unwrap_or(false),
+            value.no_street.unwrap_or(false),
+        ) {
+            (true, true) => {
+                anyhow::bail!("--require-street and --no-street cannot be used together.")
+            }
+            (true, false) => StreetFilter::WithStreet,
+            (false, true) => StreetFilter::WithoutStreet,
+            (false, false) => StreetFilter::Any,
+        };
+        let skip_bad_entries = value.skip_bad_entries.unwrap_or(false);
+        let zip_entry_filter =
+            ZipEntryFilter::new(&value.include_zip_entry, &value.exclude_zip_entry)?;
+        let parsed_paths = if download_data || print_schema {
+            vec![]
+        } else {
+            parse_input_paths(
+                &input_paths,
+                &schema_version,
+                &zip_entry_filter,
+                skip_bad_entries,
+            )?
+        };
+        let needs_teryt = schema_version == SchemaVersion::Model2021
+            && !download_teryt_flag
+            && !print_schema
+            && value.transform_only.is_none();
+        let teryt_path = match value.teryt_path {
+            None if needs_teryt => match find_terc_in_inputs(&parsed_paths)? {
+                Some(teryt_path) => {
+                    progress!(
+                        "Using TERC file `{}` found in the input.",
+                        teryt_path.display()
+                    );
+                    Some(teryt_path)
+                }
+                None => anyhow::bail!(
+                    "Chosen schema 2021 but provided neither teryt file path nor teryt-download flag, and there is no TERC file (`TERC*.xml` or `TERC*.csv`) inside the input ZIP files. PRG schema 2021 does not contain names of administrative units so they need to be read from external source."
+                ),
+            },
+            teryt_path => teryt_path,
         };
-        let parsed_paths = if download_data {
+        let compare_with = if value.compare.is_empty() || print_schema {
             vec![]
         } else {
-            parse_input_paths(&value.input_paths, &schema_version)?
+            parse_input_paths(
+                &value.compare,
+                &schema_version,
+                &zip_entry_filter,
+                skip_bad_entries,
+            )
+            .context("Invalid --compare.")?
         };
         Ok(ParsedArgs {
-            input_paths: value.input_paths,
+            input_paths,
             parsed_paths: parsed_paths,
             download_data,
             download_data_path,
-            output_path: value.output_path,
+            output_path: value.output_path.unwrap_or_default(),
             download_teryt: download_teryt_flag,
             teryt_api_username: if teryt_api_username.is_empty() {
                 None
@@ -495,15 +1903,89 @@ impl TryFrom<RawArgs> for ParsedArgs {
             } else {
                 Some(teryt_api_password)
             },
-            teryt_path: value.teryt_path,
+            teryt_path,
+            teryt_date,
             batch_size: batch_size,
+            max_memory,
             schema_version: schema_version,
             output_format: output_format,
+            #[cfg(feature = "geoparquet")]
             compression_level: compression_level,
+            #[cfg(feature = "geoparquet")]
             parquet_compression: parquet_compression,
             parquet_row_group_size: parquet_row_group_size,
+            parquet_data_page_size,
+            parquet_dictionary_page_size,
+            flush_every,
+            row_group_per_voivodeship,
+            parquet_bloom_filter_columns,
+            csv_delimiter: csv_delimiter as u8,
+            decimal_comma,
+            csv_null_string,
+            csv_date_format,
+            csv_timestamp_format,
+            timestamp_timezone,
+            #[cfg(feature = "geoparquet")]
             parquet_version: parquet_version,
             crs: crs,
+            #[cfg(feature = "geoparquet")]
+            geometry_encoding,
+            #[cfg(feature = "geoparquet")]
+            points_only_schema,
+            errors_to: value.errors_to,
+            join_audit,
+            max_errors: value.max_errors,
+            start_date: value.start_date,
+            end_date: value.end_date,
+            sample_rate: value.sample_rate,
+            sample_seed: value.sample_seed.unwrap_or(0),
+            street_filter,
+            drop_null_geometry: value.drop_null_geometry.unwrap_or(false),
+            tail: value.tail,
+            spatial_sort: matches!(value.spatial_sort, Some(SpatialSortArg::Hilbert)),
+            spatial_sort_buffer: value.spatial_sort_buffer,
+            id_prefix,
+            status_raw,
+            skip_dictionary,
+            input_crs: value.epsg_input.unwrap_or_default(),
+            coord_precision,
+            street_types,
+            verbose_unknown_tags: value.verbose_unknown_tags.unwrap_or(false)
+                || value.verbose.unwrap_or(false),
+            benchmark: value.benchmark.unwrap_or(false),
+            fail_on_unknown_tag: value.fail_on_unknown_tag.unwrap_or(false),
+            normalize_whitespace: value.normalize_whitespace.unwrap_or(false),
+            untrimmed_columns,
+            name_case: match value.name_case {
+                None | Some(NameCaseArg::Preserve) => NameCase::Preserve,
+                Some(NameCaseArg::Lower) => NameCase::Lower,
+                Some(NameCaseArg::Title) => NameCase::Title,
+            },
+            column_renames: parse_column_renames(&value.rename_columns)?,
+            zip_entry_filter,
+            skip_bad_entries,
+            manifest_path: value.manifest,
+            expected_counts,
+            expect_counts_tolerance: value.expect_counts_tolerance.unwrap_or(0.0),
+            compare_paths: value.compare,
+            compare_with,
+            normalize_names: value.normalize_names.unwrap_or(false),
+            split_house_number: value.split_house_number.unwrap_or(false),
+            split_valid_from: value.split_valid_from.unwrap_or(false),
+            add_full_address: value.add_full_address.unwrap_or(false),
+            add_source_column: value.add_source_column.unwrap_or(false),
+            grid_size: value.grid,
+            parallel_parse: value.parallel_parse.unwrap_or(false),
+            mmap: value.mmap.unwrap_or(false),
+            workers_per_file: value
+                .workers_per_file
+                .map_or(DEFAULT_WORKERS_PER_FILE, usize::from),
+            rows_per_file: value.rows_per_file,
+            checkpoint: value.checkpoint,
+            resume,
+            validate_only: value.validate_only.unwrap_or(false),
+            transform_only: value.transform_only,
+            print_schema,
         })
     }
 }
@@ -512,24 +1994,96 @@ impl TryFrom<RawArgs> for ParsedArgs {
 mod tests {
     use super::*;
     use clap::Parser;
+    use std::io::Write;
 
     fn make_base_raw_args() -> RawArgs {
         RawArgs {
             input_paths: vec!["fixtures/sample_model2012.xml".to_string()],
+            input_list: None,
             download_data: None,
-            output_path: PathBuf::from("/tmp/test_output.csv"),
-            output_format: OutputFormatArg::Csv,
-            schema_version: SchemaVersionArg::V2012,
+            output_path: Some(PathBuf::from("/tmp/test_output.csv")),
+            output_format: Some(OutputFormat::CSV),
+            schema_version: Some(SchemaVersion::Model2012),
             teryt_path: None,
+            teryt_date: None,
             teryt_download: None,
             teryt_api_username: None,
             teryt_api_password: None,
             batch_size: None,
+            max_memory: None,
             parquet_compression: None,
             compression_level: None,
             parquet_row_group_size: None,
+            parquet_data_page_size: None,
+            parquet_dict_page_size: None,
+            flush_every: None,
+            row_group_per: None,
+            parquet_bloom_filter: vec![],
+            csv_delimiter: None,
+            decimal_comma: None,
+            null_string: None,
+            date_format: None,
+            timestamp_format: None,
+            timestamp_tz: None,
             parquet_version: None,
             crs_epsg: None,
+            geometry_encoding: None,
+            points_only_schema: None,
+            errors_to: None,
+            join_audit: None,
+            max_errors: None,
+            start_date: None,
+            end_date: None,
+            sample_rate: None,
+            sample_seed: None,
+            tail: None,
+            spatial_sort: None,
+            spatial_sort_buffer: None,
+            id_prefix: None,
+            status_raw: None,
+            skip_dictionary: None,
+            epsg_input: None,
+            coord_precision: None,
+            street_type: vec![],
+            abbreviate_street_types: None,
+            verbose_unknown_tags: None,
+            benchmark: None,
+            fail_on_unknown_tag: None,
+            normalize_whitespace: None,
+            no_trim: vec![],
+            name_case: None,
+            quiet: None,
+            verbose: None,
+            rename_columns: vec![],
+            require_street: None,
+            no_street: None,
+            drop_null_geometry: None,
+            include_zip_entry: vec![],
+            exclude_zip_entry: vec![],
+            skip_bad_entries: None,
+            manifest: None,
+            expect_counts: None,
+            expect_counts_tolerance: None,
+            compare: vec![],
+            normalize_names: None,
+            split_house_number: None,
+            split_valid_from: None,
+            add_full_address: None,
+            add_source_column: None,
+            grid: None,
+            parallel_parse: None,
+            mmap: None,
+            workers_per_file: None,
+            rows_per_file: None,
+            checkpoint: None,
+            resume: None,
+            validate_only: None,
+            transform_only: None,
+            serve: None,
+            self_test: None,
+            list_zip: vec![],
+            format: None,
+            print_schema: None,
         }
     }
 
@@ -538,6 +2092,8 @@ mod tests {
         let result = parse_input_paths(
             &vec!["fixtures/sample_model2012.xml".to_string()],
             &prg_convert::SchemaVersion::Model2012,
+            &ZipEntryFilter::default(),
+            false,
         );
         let records = result.expect("Expected Ok result");
         assert_eq!(records.len(), 1);
@@ -550,6 +2106,8 @@ mod tests {
         let result = parse_input_paths(
             &vec!["fixtures/PRG-punkty_adresowe.zip".to_string()],
             &prg_convert::SchemaVersion::Model2012,
+            &ZipEntryFilter::default(),
+            false,
         );
         let records = result.expect("Expected Ok result");
         assert_eq!(records.len(), 1);
@@ -577,6 +2135,8 @@ mod tests {
         let result = parse_input_paths(
             &vec!["fixtures/PRG-punkty_adresowe.zip".to_string()],
             &prg_convert::SchemaVersion::Model2021,
+            &ZipEntryFilter::default(),
+            false,
         );
         let records = result.expect("Expected Ok result");
         assert_eq!(records.len(), 1);
@@ -598,11 +2158,50 @@ mod tests {
         assert!(xml_files.iter().all(|f| !f.to_be_parsed));
     }
 
+    #[test]
+    fn test_parse_input_paths_zip_entry_filter() {
+        let names_to_parse = |include: &[&str], exclude: &[&str]| -> Vec<String> {
+            let to_strings = |v: &[&str]| v.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+            let filter = ZipEntryFilter::new(&to_strings(include), &to_strings(exclude)).unwrap();
+            let records = parse_input_paths(
+                &vec!["fixtures/PRG-punkty_adresowe.zip".to_string()],
+                &prg_convert::SchemaVersion::Model2021,
+                &filter,
+                false,
+            )
+            .expect("Expected Ok result");
+            records[0]
+                .compressed_files
+                .as_ref()
+                .unwrap()
+                .iter()
+                .filter(|f| f.to_be_parsed)
+                .map(|f| f.name.clone())
+                .collect()
+        };
+        assert_eq!(
+            names_to_parse(&[], &[]),
+            vec!["NOWE_07.11.2025_12.05.59_08_lubuskie.gml"]
+        );
+        assert!(names_to_parse(&[], &["NOWE_*"]).is_empty());
+        assert_eq!(
+            names_to_parse(&["*.xml"], &[]),
+            vec![
+                "06.11.2025_20_06_55__08_lubuskie.xml",
+                "NOWE_07.11.2025_12.05.59_08_lubuskie.gml"
+            ]
+        );
+        assert!(names_to_parse(&["*.gml"], &["*.gml"]).is_empty());
+        assert!(ZipEntryFilter::new(&["[".to_string()], &[]).is_err());
+    }
+
     #[test]
     fn test_parse_input_paths_empty_glob_result() {
         let result = parse_input_paths(
             &vec!["fixtures/nonexistent_*.xml".to_string()],
             &prg_convert::SchemaVersion::Model2012,
+            &ZipEntryFilter::default(),
+            false,
         );
         assert!(result.is_err());
         let err_str = format!("{}", result.err().unwrap());
@@ -612,7 +2211,7 @@ mod tests {
     #[test]
     fn test_try_into_schema_2021_missing_teryt() {
         let args = RawArgs {
-            schema_version: SchemaVersionArg::V2021,
+            schema_version: Some(SchemaVersion::Model2021),
             teryt_path: None,
             teryt_download: Some(false),
             ..make_base_raw_args()
@@ -664,7 +2263,7 @@ mod tests {
             std::env::remove_var("TERYT_API_PASSWORD");
         }
         let args = RawArgs {
-            schema_version: SchemaVersionArg::V2021,
+            schema_version: Some(SchemaVersion::Model2021),
             teryt_path: None,
             teryt_download: Some(true),
             ..make_base_raw_args()
@@ -728,6 +2327,103 @@ mod tests {
 
     // --- happy path tests ---
 
+    #[test]
+    fn test_try_into_input_list() {
+        let mut input_list = NamedTempFile::new().unwrap();
+        writeln!(
+            input_list,
+            "# regional files\n\n  fixtures/sample_model2012.xm?  \n#fixtures/missing.xml"
+        )
+        .unwrap();
+        let args = RawArgs {
+            input_paths: vec!["fixtures/PRG-punkty_adresowe.zip".to_string()],
+            input_list: Some(input_list.path().to_path_buf()),
+            ..make_base_raw_args()
+        };
+        let parsed: ParsedArgs = args.try_into().expect("Expected Ok result");
+        assert_eq!(
+            parsed.input_paths,
+            vec![
+                "fixtures/PRG-punkty_adresowe.zip",
+                "fixtures/sample_model2012.xm?"
+            ]
+        );
+        assert_eq!(parsed.parsed_paths.len(), 2);
+        assert_eq!(
+            parsed.parsed_paths[1].path,
+            PathBuf::from("fixtures/sample_model2012.xml")
+        );
+    }
+
+    #[test]
+    fn test_try_into_missing_input_list() {
+        let args = RawArgs {
+            input_paths: vec![],
+            input_list: Some(PathBuf::from("fixtures/missing_list.txt")),
+            ..make_base_raw_args()
+        };
+        let result: anyhow::Result<ParsedArgs> = args.try_into();
+        let err_str = format!("{}", result.err().unwrap());
+        assert!(
+            err_str.contains("missing_list.txt"),
+            "Error message was: {}",
+            err_str
+        );
+    }
+
+    #[test]
+    fn test_try_into_expect_counts() {
+        let args = RawArgs {
+            expect_counts_tolerance: Some(1.0),
+            ..make_base_raw_args()
+        };
+        let result: anyhow::Result<ParsedArgs> = args.try_into();
+        assert!(result.is_err());
+
+        let args = RawArgs {
+            expect_counts: Some(PathBuf::from("fixtures/missing_counts.csv")),
+            ..make_base_raw_args()
+        };
+        let result: anyhow::Result<ParsedArgs> = args.try_into();
+        assert!(result.is_err());
+
+        let mut counts_file = NamedTempFile::new().unwrap();
+        writeln!(counts_file, "08,2").unwrap();
+        let args = RawArgs {
+            expect_counts: Some(counts_file.path().to_path_buf()),
+            expect_counts_tolerance: Some(-1.0),
+            ..make_base_raw_args()
+        };
+        let result: anyhow::Result<ParsedArgs> = args.try_into();
+        assert!(result.is_err());
+
+        let args = RawArgs {
+            expect_counts: Some(counts_file.path().to_path_buf()),
+            ..make_base_raw_args()
+        };
+        let parsed: ParsedArgs = args.try_into().expect("Expected Ok result");
+        assert_eq!(parsed.expected_counts.unwrap()["08"], 2);
+        assert_eq!(parsed.expect_counts_tolerance, 0.0);
+    }
+
+    #[test]
+    fn test_try_into_compare() {
+        let args = RawArgs {
+            compare: vec!["fixtures/sample_model2012*.xml".to_string()],
+            ..make_base_raw_args()
+        };
+        let parsed: ParsedArgs = args.try_into().expect("Expected Ok result");
+        assert!(!parsed.compare_with.is_empty());
+        assert!(!parsed.convert_options(vec![]).compare_with.is_empty());
+
+        let args = RawArgs {
+            compare: vec!["fixtures/missing_snapshot.xml".to_string()],
+            ..make_base_raw_args()
+        };
+        let result: anyhow::Result<ParsedArgs> = args.try_into();
+        assert!(result.is_err());
+    }
+
     #[test]
     fn test_try_into_valid_model2012() {
         let args = make_base_raw_args();
@@ -744,7 +2440,7 @@ mod tests {
     #[test]
     fn test_try_into_valid_model2021_with_teryt_path() {
         let args = RawArgs {
-            schema_version: SchemaVersionArg::V2021,
+            schema_version: Some(SchemaVersion::Model2021),
             teryt_path: Some(PathBuf::from("fixtures/TERC_Urzedowy_2025-11-18.xml")),
             ..make_base_raw_args()
         };
@@ -758,7 +2454,898 @@ mod tests {
         );
     }
 
-    // --- invalid parquet/crs options ---
+    #[test]
+    fn test_try_into_teryt_date() {
+        let args = RawArgs {
+            schema_version: Some(SchemaVersion::Model2021),
+            teryt_path: Some(PathBuf::from("fixtures")),
+            teryt_date: NaiveDate::from_ymd_opt(2025, 1, 1),
+            ..make_base_raw_args()
+        };
+        let parsed: ParsedArgs = args.try_into().unwrap();
+        assert_eq!(parsed.teryt_date, NaiveDate::from_ymd_opt(2025, 1, 1));
+        assert_eq!(
+            parsed.convert_options(vec![]).teryt_date.as_deref(),
+            Some("2025-01-01")
+        );
+
+        // the newest catalog is downloaded
+        let args = RawArgs {
+            schema_version: Some(SchemaVersion::Model2021),
+            teryt_download: Some(true),
+            teryt_api_username: Some("user".to_string()),
+            teryt_api_password: Some("password".to_string()),
+            teryt_date: NaiveDate::from_ymd_opt(2025, 1, 1),
+            ..make_base_raw_args()
+        };
+        let parsed: ParsedArgs = args.try_into().unwrap();
+        assert_eq!(parsed.teryt_date, None);
+    }
+
+    #[test]
+    fn test_try_into_detects_schema_version() {
+        let args = RawArgs {
+            schema_version: None,
+            ..make_base_raw_args()
+        };
+        let parsed: ParsedArgs = args.try_into().expect("Expected Ok result");
+        assert_eq!(parsed.schema_version, SchemaVersion::Model2012);
+
+        let args = RawArgs {
+            input_paths: vec!["fixtures/sample_model2021.xml".to_string()],
+            schema_version: None,
+            teryt_path: Some(PathBuf::from("fixtures/TERC_Urzedowy_2025-11-18.xml")),
+            ..make_base_raw_args()
+        };
+        let parsed: ParsedArgs = args.try_into().expect("Expected Ok result");
+        assert_eq!(parsed.schema_version, SchemaVersion::Model2021);
+    }
+
+    #[test]
+    fn test_try_into_detected_2021_still_requires_teryt() {
+        let args = RawArgs {
+            input_paths: vec!["fixtures/sample_model2021.xml".to_string()],
+            schema_version: None,
+            ..make_base_raw_args()
+        };
+        let result: anyhow::Result<ParsedArgs> = args.try_into();
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_try_into_download_data_requires_schema_version() {
+        let args = RawArgs {
+            input_paths: vec![],
+            download_data: Some(String::new()),
+            schema_version: None,
+            ..make_base_raw_args()
+        };
+        let result: anyhow::Result<ParsedArgs> = args.try_into();
+        let err_str = format!("{}", result.err().unwrap());
+        assert!(err_str.contains("--schema-version"));
+    }
+
+    #[test]
+    fn test_parse_date_filters() {
+        let result = RawArgs::try_parse_from([
+            "prg_convert",
+            "--input-paths",
+            "fixtures/sample_model2012.xml",
+            "--output-path",
+            "/tmp/out.csv",
+            "--output-format",
+            "csv",
+            "--start-date",
+            "2020-01-01",
+            "--end-date",
+            "2020-12-31",
+        ]);
+        let parsed: ParsedArgs = result.unwrap().try_into().unwrap();
+        assert_eq!(parsed.start_date, NaiveDate::from_ymd_opt(2020, 1, 1));
+        assert_eq!(parsed.end_date, NaiveDate::from_ymd_opt(2020, 12, 31));
+    }
+
+    #[test]
+    fn test_try_into_rejects_start_date_after_end_date() {
+        let args = RawArgs {
+            start_date: NaiveDate::from_ymd_opt(2021, 1, 1),
+            end_date: NaiveDate::from_ymd_opt(2020, 1, 1),
+            ..make_base_raw_args()
+        };
+        let result: anyhow::Result<ParsedArgs> = args.try_into();
+        assert!(result.is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "geoparquet")]
+    fn test_parse_print_schema_without_input_or_output() {
+        let result = RawArgs::try_parse_from([
+            "prg_convert",
+            "--print-schema",
+            "--output-format",
+            "geoparquet",
+            "--schema-version",
+            "2021",
+        ]);
+        let parsed: ParsedArgs = result.unwrap().try_into().unwrap();
+        assert!(parsed.print_schema);
+        assert!(parsed.parsed_paths.is_empty());
+    }
+
+    #[test]
+    fn test_try_into_print_schema_requires_schema_version_without_input() {
+        let args = RawArgs {
+            input_paths: vec![],
+            schema_version: None,
+            print_schema: Some(true),
+            ..make_base_raw_args()
+        };
+        let result: anyhow::Result<ParsedArgs> = args.try_into();
+        let err_str = format!("{}", result.err().unwrap());
+        assert!(err_str.contains("--schema-version"));
+    }
+
+    #[test]
+    fn test_parse_validate_only_without_output() {
+        let result = RawArgs::try_parse_from([
+            "prg_convert",
+            "--validate-only",
+            "--input-paths",
+            "fixtures/sample_model2012.xml",
+        ]);
+        let parsed: ParsedArgs = result.unwrap().try_into().unwrap();
+        assert!(parsed.validate_only);
+        assert_eq!(parsed.schema_version, SchemaVersion::Model2012);
+    }
+
+    #[test]
+    fn test_parse_transform_only_without_output() {
+        let parse = |value: &str| {
+            RawArgs::try_parse_from([
+                "prg_convert",
+                "--input-paths",
+                "fixtures/sample_model2021.xml",
+                "--transform-only",
+                value,
+            ])
+        };
+        // no output and no TERYT file needed for schema 2021
+        let parsed: ParsedArgs = parse("1000").unwrap().try_into().unwrap();
+        assert_eq!(parsed.transform_only, Some(1000));
+        assert_eq!(parsed.schema_version, SchemaVersion::Model2021);
+        let result: anyhow::Result<ParsedArgs> = parse("0").unwrap().try_into();
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_parse_requires_output_path_without_print_schema() {
+        let result = RawArgs::try_parse_from([
+            "prg_convert",
+            "--input-paths",
+            "fixtures/sample_model2012.xml",
+            "--output-format",
+            "csv",
+        ]);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_parse_mmap_conflicts_with_parallel_parse() {
+        let result = RawArgs::try_parse_from([
+            "prg_convert",
+            "--validate-only",
+            "--input-paths",
+            "fixtures/sample_model2012.xml",
+            "--mmap",
+            "--parallel-parse",
+        ]);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_parse_verbosity() {
+        let parse = |flags: &[&str]| {
+            let mut args = vec![
+                "prg_convert",
+                "--validate-only",
+                "--input-paths",
+                "fixtures/sample_model2012.xml",
+            ];
+            args.extend_from_slice(flags);
+            RawArgs::try_parse_from(args)
+        };
+        assert_eq!(parse(&[]).unwrap().verbosity(), Verbosity::Normal);
+        assert_eq!(parse(&["--quiet"]).unwrap().verbosity(), Verbosity::Quiet);
+        let verbose = parse(&["--verbose"]).unwrap();
+        assert_eq!(verbose.verbosity(), Verbosity::Verbose);
+        let parsed: ParsedArgs = verbose.try_into().unwrap();
+        assert!(parsed.verbose_unknown_tags);
+        assert!(parse(&["--quiet", "--verbose"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_workers_per_file() {
+        let parse = |value: &str| {
+            RawArgs::try_parse_from([
+                "prg_convert",
+                "--validate-only",
+                "--input-paths",
+                "fixtures/sample_model2012.xml",
+                "--workers-per-file",
+                value,
+            ])
+        };
+        let parsed: ParsedArgs = parse("1").unwrap().try_into().unwrap();
+        assert_eq!(parsed.workers_per_file, 1);
+        assert!(parse("0").is_err());
+        assert!(parse("3").is_err());
+        let parsed: ParsedArgs = make_base_raw_args().try_into().unwrap();
+        assert_eq!(parsed.workers_per_file, DEFAULT_WORKERS_PER_FILE);
+    }
+
+    #[test]
+    fn test_parse_street_types() {
+        assert_eq!(parse_street_types(&[], false).unwrap(), None);
+        assert_eq!(
+            parse_street_types(&[], true).unwrap(),
+            Some(StreetTypes::abbreviated())
+        );
+        let mut expected = StreetTypes::default();
+        expected.set("3", "pl.").unwrap();
+        expected.set("1", "").unwrap();
+        assert_eq!(
+            parse_street_types(&["3=pl.".to_string(), "1=".to_string()], false).unwrap(),
+            Some(expected)
+        );
+        assert!(parse_street_types(&["3".to_string()], false).is_err());
+        assert!(parse_street_types(&["99=x".to_string()], false).is_err());
+        // ignored for schema 2012
+        let parsed: ParsedArgs = RawArgs {
+            street_type: vec!["3=pl.".to_string()],
+            ..make_base_raw_args()
+        }
+        .try_into()
+        .unwrap();
+        assert!(parsed.street_types.is_none());
+    }
+
+    #[test]
+    fn test_parse_max_errors() {
+        let parsed: ParsedArgs = RawArgs::try_parse_from([
+            "prg_convert",
+            "--validate-only",
+            "--input-paths",
+            "fixtures/sample_model2012.xml",
+            "--max-errors",
+            "0",
+        ])
+        .unwrap()
+        .try_into()
+        .unwrap();
+        assert_eq!(parsed.max_errors, Some(0));
+        assert_eq!(parsed.convert_options(Vec::new()).max_errors, Some(0));
+        let parsed: ParsedArgs = make_base_raw_args().try_into().unwrap();
+        assert_eq!(parsed.max_errors, None);
+    }
+
+    #[test]
+    fn test_parse_street_filter() {
+        let parse = |flags: &[&str]| {
+            let mut args = vec![
+                "prg_convert",
+                "--validate-only",
+                "--input-paths",
+                "fixtures/sample_model2012.xml",
+            ];
+            args.extend_from_slice(flags);
+            RawArgs::try_parse_from(args)
+        };
+        let street_filter = |flags: &[&str]| {
+            let parsed: ParsedArgs = parse(flags).unwrap().try_into().unwrap();
+            parsed.street_filter
+        };
+        assert_eq!(street_filter(&[]), StreetFilter::Any);
+        assert_eq!(
+            street_filter(&["--require-street"]),
+            StreetFilter::WithStreet
+        );
+        assert_eq!(street_filter(&["--no-street"]), StreetFilter::WithoutStreet);
+        assert!(parse(&["--require-street", "--no-street"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_drop_null_geometry() {
+        let parse = |flags: &[&str]| {
+            let mut args = vec![
+                "prg_convert",
+                "--validate-only",
+                "--input-paths",
+                "fixtures/sample_model2012.xml",
+            ];
+            args.extend_from_slice(flags);
+            let parsed: ParsedArgs = RawArgs::try_parse_from(args).unwrap().try_into().unwrap();
+            parsed
+        };
+        assert!(!parse(&[]).drop_null_geometry);
+        let parsed = parse(&["--drop-null-geometry"]);
+        assert!(parsed.drop_null_geometry);
+        assert!(parsed.convert_options(vec![]).filter.drop_null_geometry);
+    }
+
+    #[test]
+    fn test_parse_column_renames() {
+        let result = RawArgs::try_parse_from([
+            "prg_convert",
+            "--validate-only",
+            "--input-paths",
+            "fixtures/sample_model2012.xml",
+            "--rename-columns",
+            "przestrzen_nazw=namespace, ulica=street",
+        ]);
+        let parsed: ParsedArgs = result.unwrap().try_into().unwrap();
+        assert_eq!(parsed.column_renames.len(), 2);
+        assert_eq!(parsed.column_renames["ulica"], "street");
+
+        assert!(parse_column_renames(&["ulica".to_string()]).is_err());
+        assert!(parse_column_renames(&["ulica=".to_string()]).is_err());
+        assert!(parse_column_renames(&["ulica=a".to_string(), "ulica=b".to_string()]).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "geoparquet")]
+    fn test_try_into_flush_every() {
+        let args = RawArgs {
+            output_format: Some(OutputFormat::GeoParquet),
+            flush_every: Some(1000),
+            ..make_base_raw_args()
+        };
+        let parsed: ParsedArgs = args.try_into().unwrap();
+        assert_eq!(parsed.flush_every, Some(1000));
+
+        // ignored for CSV
+        let args = RawArgs {
+            flush_every: Some(1000),
+            ..make_base_raw_args()
+        };
+        let parsed: ParsedArgs = args.try_into().unwrap();
+        assert_eq!(parsed.flush_every, None);
+
+        let args = RawArgs {
+            output_format: Some(OutputFormat::GeoParquet),
+            flush_every: Some(0),
+            ..make_base_raw_args()
+        };
+        let result: anyhow::Result<ParsedArgs> = args.try_into();
+        assert!(result.is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "geoparquet")]
+    fn test_try_into_row_group_per() {
+        let args = RawArgs {
+            output_format: Some(OutputFormat::GeoParquet),
+            row_group_per: Some(RowGroupPerArg::Voivodeship),
+            ..make_base_raw_args()
+        };
+        let parsed: ParsedArgs = args.try_into().unwrap();
+        assert!(parsed.row_group_per_voivodeship);
+
+        // ignored for CSV
+        let args = RawArgs {
+            row_group_per: Some(RowGroupPerArg::Voivodeship),
+            ..make_base_raw_args()
+        };
+        let parsed: ParsedArgs = args.try_into().unwrap();
+        assert!(!parsed.row_group_per_voivodeship);
+    }
+
+    #[test]
+    fn test_try_into_name_case() {
+        let parsed: ParsedArgs = make_base_raw_args().try_into().unwrap();
+        assert_eq!(parsed.name_case, NameCase::Preserve);
+        let args = RawArgs {
+            name_case: Some(NameCaseArg::Title),
+            ..make_base_raw_args()
+        };
+        let parsed: ParsedArgs = args.try_into().unwrap();
+        assert_eq!(parsed.name_case, NameCase::Title);
+        let options = parsed.convert_options(Vec::new());
+        assert_eq!(options.name_case, NameCase::Title);
+    }
+
+    #[test]
+    fn test_try_into_spatial_sort() {
+        let args = RawArgs {
+            spatial_sort: Some(SpatialSortArg::Hilbert),
+            spatial_sort_buffer: Some(1000),
+            ..make_base_raw_args()
+        };
+        let parsed: ParsedArgs = args.try_into().unwrap();
+        assert!(parsed.spatial_sort);
+        assert_eq!(parsed.spatial_sort_buffer, Some(1000));
+        let options = parsed.convert_options(Vec::new());
+        assert!(options.spatial_sort);
+
+        let args = RawArgs {
+            spatial_sort_buffer: Some(1000),
+            ..make_base_raw_args()
+        };
+        let parsed: anyhow::Result<ParsedArgs> = args.try_into();
+        assert!(parsed.is_err());
+        let args = RawArgs {
+            spatial_sort: Some(SpatialSortArg::Hilbert),
+            spatial_sort_buffer: Some(0),
+            ..make_base_raw_args()
+        };
+        let parsed: anyhow::Result<ParsedArgs> = args.try_into();
+        assert!(parsed.is_err());
+    }
+
+    #[test]
+    fn test_try_into_skip_dictionary() {
+        let args = RawArgs {
+            skip_dictionary: Some(true),
+            ..make_base_raw_args()
+        };
+        let parsed: ParsedArgs = args.try_into().unwrap();
+        assert!(parsed.skip_dictionary);
+        let options = parsed.convert_options(Vec::new());
+        assert!(options.skip_dictionary);
+    }
+
+    #[test]
+    fn test_try_into_checkpoint() {
+        let args = RawArgs {
+            rows_per_file: Some(10),
+            checkpoint: Some(PathBuf::from("checkpoint.json")),
+            resume: Some(true),
+            ..make_base_raw_args()
+        };
+        let parsed: ParsedArgs = args.try_into().unwrap();
+        assert!(parsed.resume);
+        let options = parsed.convert_options(Vec::new());
+        assert_eq!(
+            options.checkpoint_path,
+            Some(PathBuf::from("checkpoint.json"))
+        );
+        assert!(options.resume);
+
+        let args = RawArgs {
+            checkpoint: Some(PathBuf::from("checkpoint.json")),
+            ..make_base_raw_args()
+        };
+        assert!(ParsedArgs::try_from(args).is_err());
+        let args = RawArgs {
+            rows_per_file: Some(10),
+            resume: Some(true),
+            ..make_base_raw_args()
+        };
+        assert!(ParsedArgs::try_from(args).is_err());
+    }
+
+    #[test]
+    fn test_parse_epsg_input() {
+        let args = RawArgs::try_parse_from([
+            "prg_convert",
+            "--output-path",
+            "/tmp/o.csv",
+            "--output-format",
+            "csv",
+            "--epsg-input",
+            "EPSG:4326",
+        ])
+        .unwrap();
+        let args = RawArgs {
+            epsg_input: args.epsg_input,
+            ..make_base_raw_args()
+        };
+        let parsed: ParsedArgs = args.try_into().unwrap();
+        assert_eq!(parsed.input_crs, CRS::Epsg4326);
+        assert_eq!(parsed.convert_options(Vec::new()).input_crs, CRS::Epsg4326);
+        let parsed: ParsedArgs = make_base_raw_args().try_into().unwrap();
+        assert_eq!(parsed.input_crs, CRS::Epsg2180);
+    }
+
+    #[test]
+    fn test_try_into_coord_precision() {
+        let parsed: ParsedArgs = make_base_raw_args().try_into().unwrap();
+        assert_eq!(parsed.coord_precision, 6);
+        assert_eq!(parsed.convert_options(Vec::new()).coord_precision, Some(6));
+        for (precision, ok) in [(0, true), (15, true), (16, false)] {
+            let args = RawArgs {
+                coord_precision: Some(precision),
+                ..make_base_raw_args()
+            };
+            assert_eq!(ParsedArgs::try_from(args).is_ok(), ok, "{}", precision);
+        }
+    }
+
+    #[test]
+    fn test_parse_grid() {
+        let args = RawArgs {
+            grid: Some(100),
+            ..make_base_raw_args()
+        };
+        let parsed: ParsedArgs = args.try_into().unwrap();
+        assert_eq!(parsed.grid_size, Some(100));
+        assert_eq!(parsed.convert_options(vec![]).grid_size, Some(100));
+        let error = RawArgs::try_parse_from(["prg_convert", "--grid", "0"]).err();
+        assert_eq!(
+            error.map(|e| e.kind()),
+            Some(clap::error::ErrorKind::ValueValidation)
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "geoparquet")]
+    fn test_try_into_points_only_schema() {
+        let args = RawArgs {
+            output_format: Some(OutputFormat::GeoParquet),
+            points_only_schema: Some(true),
+            ..make_base_raw_args()
+        };
+        let parsed: ParsedArgs = args.try_into().unwrap();
+        assert!(parsed.points_only_schema);
+        assert!(parsed.convert_options(vec![]).points_only_schema);
+
+        // ignored for plain parquet
+        let args = RawArgs {
+            output_format: Some(OutputFormat::Parquet),
+            points_only_schema: Some(true),
+            ..make_base_raw_args()
+        };
+        let parsed: ParsedArgs = args.try_into().unwrap();
+        assert!(!parsed.points_only_schema);
+    }
+
+    #[test]
+    #[cfg(feature = "geoparquet")]
+    fn test_try_into_parquet_bloom_filter() {
+        let args = RawArgs::try_parse_from([
+            "prg_convert",
+            "--input-paths",
+            "fixtures/sample_model2012.xml",
+            "--output-path",
+            "/tmp/o.parquet",
+            "--output-format",
+            "parquet",
+            "--parquet-bloom-filter",
+            "uuid,ulica",
+        ])
+        .unwrap();
+        let parsed: ParsedArgs = args.try_into().unwrap();
+        assert_eq!(parsed.parquet_bloom_filter_columns, vec!["uuid", "ulica"]);
+
+        // ignored for CSV
+        let args = RawArgs {
+            parquet_bloom_filter: vec!["uuid".to_string()],
+            ..make_base_raw_args()
+        };
+        let parsed: ParsedArgs = args.try_into().unwrap();
+        assert!(parsed.parquet_bloom_filter_columns.is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "geoparquet")]
+    fn test_try_into_parquet_page_sizes() {
+        let args = RawArgs::try_parse_from([
+            "prg_convert",
+            "--input-paths",
+            "fixtures/sample_model2012.xml",
+            "--output-path",
+            "/tmp/o.parquet",
+            "--output-format",
+            "parquet",
+            "--parquet-data-page-size",
+            "65536",
+            "--parquet-dict-page-size",
+            "4096",
+        ])
+        .unwrap();
+        let parsed: ParsedArgs = args.try_into().unwrap();
+        assert_eq!(parsed.parquet_data_page_size, Some(65536));
+        assert_eq!(parsed.parquet_dictionary_page_size, Some(4096));
+
+        // ignored for CSV
+        let args = RawArgs {
+            parquet_data_page_size: Some(65536),
+            ..make_base_raw_args()
+        };
+        let parsed: ParsedArgs = args.try_into().unwrap();
+        assert_eq!(parsed.parquet_data_page_size, None);
+
+        for args in [
+            RawArgs {
+                parquet_data_page_size: Some(0),
+                ..make_base_raw_args()
+            },
+            RawArgs {
+                parquet_dict_page_size: Some(0),
+                ..make_base_raw_args()
+            },
+        ] {
+            let err = ParsedArgs::try_from(args).err().unwrap();
+            assert!(
+                err.to_string().contains("must be greater than 0"),
+                "{}",
+                err
+            );
+        }
+    }
+
+    #[test]
+    fn test_list_zip() {
+        let args = RawArgs::try_parse_from([
+            "prg_convert",
+            "--list-zip",
+            "fixtures/PRG-punkty_adresowe.zip",
+        ])
+        .unwrap();
+        assert_eq!(args.verbosity(), Verbosity::Quiet);
+        let listing = args.list_zip().unwrap().unwrap();
+        let lines: Vec<&str> = listing.lines().collect();
+        assert_eq!(
+            lines,
+            vec![
+                "path\tindex\tname\tcompressed_size\tuncompressed_size\tto_be_parsed",
+                "fixtures/PRG-punkty_adresowe.zip\t0\t06.11.2025_20_06_55__08_lubuskie.xml\t2138\t15398\ttrue",
+                "fixtures/PRG-punkty_adresowe.zip\t1\tNOWE_07.11.2025_12.05.59_08_lubuskie.gml\t1694\t9199\tfalse",
+                "fixtures/PRG-punkty_adresowe.zip\t2\tTabela_konwersja_EMUiA.pdf\t475772\t542535\tfalse",
+            ]
+        );
+
+        // the entries parsed with the schema version and filters
+        let args = RawArgs::try_parse_from([
+            "prg_convert",
+            "--list-zip",
+            "fixtures/PRG-punkty_adresowe.zip",
+            "--schema-version",
+            "2021",
+            "--include-zip-entry",
+            "*.pdf",
+            "--format",
+            "json",
+        ])
+        .unwrap();
+        let listing: serde_json::Value =
+            serde_json::from_str(&args.list_zip().unwrap().unwrap()).unwrap();
+        let to_be_parsed: Vec<bool> = listing
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|entry| entry["to_be_parsed"].as_bool().unwrap())
+            .collect();
+        assert_eq!(to_be_parsed, vec![false, true, true]);
+        assert_eq!(
+            listing[1]["name"],
+            "NOWE_07.11.2025_12.05.59_08_lubuskie.gml"
+        );
+        assert_eq!(listing[1]["uncompressed_size"], 9199);
+
+        let args =
+            RawArgs::try_parse_from(["prg_convert", "--list-zip", "fixtures/sample_model2012.xml"])
+                .unwrap();
+        assert!(args.list_zip().is_err());
+        assert!(make_base_raw_args().list_zip().unwrap().is_none());
+        // `--format` only applies to `--list-zip`
+        let args = RawArgs {
+            format: Some(ListFormatArg::Json),
+            ..make_base_raw_args()
+        };
+        assert!(ParsedArgs::try_from(args).is_err());
+    }
+
+    #[test]
+    fn test_try_into_no_trim() {
+        let args = RawArgs::try_parse_from([
+            "prg_convert",
+            "--input-paths",
+            "fixtures/sample_model2012.xml",
+            "--output-path",
+            "/tmp/o.csv",
+            "--output-format",
+            "csv",
+            "--no-trim",
+            "numer_porzadkowy,status",
+        ])
+        .unwrap();
+        let parsed: ParsedArgs = args.try_into().unwrap();
+        assert_eq!(
+            parsed.untrimmed_columns.names(),
+            vec!["numer_porzadkowy", "status"]
+        );
+
+        let args = RawArgs {
+            no_trim: vec!["ulica".to_string()],
+            ..make_base_raw_args()
+        };
+        let err = ParsedArgs::try_from(args).err().unwrap();
+        assert!(
+            format!("{:#}", err).contains("Unknown column `ulica`"),
+            "{:#}",
+            err
+        );
+
+        let args = RawArgs {
+            no_trim: vec!["numer_porzadkowy".to_string()],
+            normalize_whitespace: Some(true),
+            ..make_base_raw_args()
+        };
+        assert!(ParsedArgs::try_from(args).is_err());
+    }
+
+    #[test]
+    fn test_try_into_null_string() {
+        let parsed: ParsedArgs = make_base_raw_args().try_into().unwrap();
+        assert_eq!(parsed.csv_null_string, "");
+
+        let args = RawArgs {
+            null_string: Some("\\N".to_string()),
+            ..make_base_raw_args()
+        };
+        let parsed: ParsedArgs = args.try_into().unwrap();
+        assert_eq!(parsed.csv_null_string, "\\N");
+
+        for null_string in ["a,b", "\"\"", "NULL\n"] {
+            let args = RawArgs {
+                null_string: Some(null_string.to_string()),
+                ..make_base_raw_args()
+            };
+            let result: anyhow::Result<ParsedArgs> = args.try_into();
+            assert!(result.is_err(), "{}", null_string);
+        }
+    }
+
+    #[test]
+    fn test_try_into_decimal_comma() {
+        let args = RawArgs {
+            csv_delimiter: Some(';'),
+            decimal_comma: Some(true),
+            ..make_base_raw_args()
+        };
+        let parsed: ParsedArgs = args.try_into().unwrap();
+        assert_eq!(parsed.csv_delimiter, b';');
+        assert!(parsed.decimal_comma);
+
+        // the default delimiter collides with the decimal separator
+        let args = RawArgs {
+            decimal_comma: Some(true),
+            ..make_base_raw_args()
+        };
+        let result: anyhow::Result<ParsedArgs> = args.try_into();
+        assert!(result.is_err());
+
+        let args = RawArgs {
+            csv_delimiter: Some('ś'),
+            ..make_base_raw_args()
+        };
+        let result: anyhow::Result<ParsedArgs> = args.try_into();
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_try_into_date_formats() {
+        let parsed: ParsedArgs = make_base_raw_args().try_into().unwrap();
+        assert_eq!(parsed.csv_date_format, DEFAULT_CSV_DATE_FORMAT);
+        assert_eq!(
+            parsed.csv_timestamp_format,
+            default_csv_timestamp_format(TimestampTimezone::Utc)
+        );
+
+        let args = RawArgs {
+            date_format: Some("%d.%m.%Y".to_string()),
+            timestamp_format: Some("%Y-%m-%d %H:%M:%S".to_string()),
+            ..make_base_raw_args()
+        };
+        let parsed: ParsedArgs = args.try_into().unwrap();
+        assert_eq!(parsed.csv_date_format, "%d.%m.%Y");
+        assert_eq!(parsed.csv_timestamp_format, "%Y-%m-%d %H:%M:%S");
+
+        let args = RawArgs {
+            date_format: Some("%Y-%Q".to_string()),
+            ..make_base_raw_args()
+        };
+        let result: anyhow::Result<ParsedArgs> = args.try_into();
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_try_into_timestamp_tz() {
+        let parsed: ParsedArgs = make_base_raw_args().try_into().unwrap();
+        assert_eq!(parsed.timestamp_timezone, TimestampTimezone::Utc);
+
+        let args = RawArgs {
+            timestamp_tz: Some(TimestampTimezone::Warsaw),
+            ..make_base_raw_args()
+        };
+        let parsed: ParsedArgs = args.try_into().unwrap();
+        assert_eq!(parsed.timestamp_timezone, TimestampTimezone::Warsaw);
+        assert_eq!(parsed.csv_timestamp_format, "%Y-%m-%dT%H:%M:%S%.f%:z");
+
+        let args = RawArgs {
+            timestamp_tz: Some(TimestampTimezone::Warsaw),
+            timestamp_format: Some("%Y-%m-%d %H:%M".to_string()),
+            ..make_base_raw_args()
+        };
+        let parsed: ParsedArgs = args.try_into().unwrap();
+        assert_eq!(parsed.csv_timestamp_format, "%Y-%m-%d %H:%M");
+    }
+
+    #[test]
+    fn test_try_into_max_memory_caps_batch_size() {
+        let args = RawArgs {
+            max_memory: Some(16),
+            ..make_base_raw_args()
+        };
+        let parsed: ParsedArgs = args.try_into().unwrap();
+        assert_eq!(
+            parsed.batch_size,
+            16 * 1024 * 1024 / estimated_bytes_per_row()
+        );
+        assert!(parsed.batch_size < DEFAULT_BATCH_SIZE);
+    }
+
+    #[test]
+    fn test_try_into_max_memory_does_not_raise_default_batch_size() {
+        let args = RawArgs {
+            max_memory: Some(1_000_000),
+            ..make_base_raw_args()
+        };
+        let parsed: ParsedArgs = args.try_into().unwrap();
+        assert_eq!(parsed.batch_size, DEFAULT_BATCH_SIZE);
+    }
+
+    #[test]
+    fn test_try_into_batch_size_overrides_max_memory() {
+        let args = RawArgs {
+            batch_size: Some(500_000),
+            max_memory: Some(16),
+            ..make_base_raw_args()
+        };
+        let parsed: ParsedArgs = args.try_into().unwrap();
+        assert_eq!(parsed.batch_size, 500_000);
+        assert!(parsed.max_memory.is_none());
+    }
+
+    #[test]
+    fn test_try_into_rejects_zero_max_memory() {
+        let args = RawArgs {
+            max_memory: Some(0),
+            ..make_base_raw_args()
+        };
+        let result: anyhow::Result<ParsedArgs> = args.try_into();
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_try_into_sample_rate() {
+        let args = RawArgs {
+            sample_rate: Some(0.01),
+            sample_seed: Some(42),
+            ..make_base_raw_args()
+        };
+        let parsed: ParsedArgs = args.try_into().unwrap();
+        assert_eq!(parsed.sample_rate, Some(0.01));
+        assert_eq!(parsed.sample_seed, 42);
+
+        for rate in [0.0, 1.5, -0.1, f64::NAN] {
+            let args = RawArgs {
+                sample_rate: Some(rate),
+                ..make_base_raw_args()
+            };
+            let result: anyhow::Result<ParsedArgs> = args.try_into();
+            assert!(result.is_err(), "rate {} should be rejected", rate);
+        }
+
+        let args = RawArgs {
+            sample_seed: Some(42),
+            ..make_base_raw_args()
+        };
+        let result: anyhow::Result<ParsedArgs> = args.try_into();
+        assert!(result.is_err());
+    }
+
+    // --- invalid parquet/crs options ---
 
     #[test]
     fn test_parse_rejects_invalid_crs_epsg() {
@@ -776,12 +3363,145 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_with_option_flags() {
+        let error = with_option_flags(
+            anyhow::anyhow!("Unknown column `x`.").context("Invalid `column_renames`."),
+        );
+        assert_eq!(
+            format!("{:#}", error),
+            "Conversion failed, see --rename-columns.: Invalid `column_renames`.: Unknown column `x`."
+        );
+        let error = with_option_flags(anyhow::anyhow!("Unknown column `ulica`."));
+        assert_eq!(format!("{:#}", error), "Unknown column `ulica`.");
+    }
+
+    #[test]
+    fn test_env_fallbacks_help() {
+        let help = env_fallbacks_help();
+        for (var, option) in ENV_FALLBACKS {
+            assert!(help.contains(var), "{}", var);
+            assert!(help.contains(&format!("--{}", option)), "{}", option);
+        }
+        assert!(help.contains("PRG_MAX_ERRORS and PRG_WORKERS_PER_FILE (for --batch-size, "));
+    }
+
+    #[test]
+    fn test_env_fallbacks() {
+        let env = |vars: &'static [(&'static str, &'static str)]| {
+            move |name: &str| {
+                vars.iter()
+                    .find(|(var, _)| *var == name)
+                    .map(|(_, value)| value.to_string())
+            }
+        };
+        let vars = &[
+            ("PRG_BATCH_SIZE", "123"),
+            ("PRG_COMPRESSION", "brotli"),
+            ("PRG_CRS_EPSG", "EPSG:4326"),
+            ("PRG_MAX_ERRORS", " "),
+        ];
+
+        // environment only
+        let parsed: ParsedArgs = make_base_raw_args()
+            .with_env_fallbacks(env(vars))
+            .unwrap()
+            .try_into()
+            .unwrap();
+        assert_eq!(parsed.batch_size, 123);
+        assert_eq!(parsed.crs, CRS::Epsg4326);
+        assert_eq!(parsed.max_errors, None);
+
+        // the command line overrides the environment
+        let args = RawArgs {
+            batch_size: Some(7),
+            parquet_compression: Some(ParquetCompression::Zstd),
+            ..make_base_raw_args()
+        }
+        .with_env_fallbacks(env(vars))
+        .unwrap();
+        assert_eq!(args.batch_size, Some(7));
+        assert_eq!(args.parquet_compression, Some(ParquetCompression::Zstd));
+        assert_eq!(args.crs_epsg, Some(CRS::Epsg4326));
+        // --max-memory also overrides a batch size from the environment
+        let args = RawArgs {
+            max_memory: Some(64),
+            ..make_base_raw_args()
+        }
+        .with_env_fallbacks(env(vars))
+        .unwrap();
+        assert_eq!(args.batch_size, None);
+
+        // invalid values are reported with the variable
+        for vars in [
+            &[("PRG_BATCH_SIZE", "many")][..],
+            &[("PRG_COMPRESSION", "lz4")],
+            &[("PRG_WORKERS_PER_FILE", "3")],
+        ] {
+            let err = make_base_raw_args()
+                .with_env_fallbacks(env(vars))
+                .err()
+                .unwrap();
+            assert!(format!("{:#}", err).contains(vars[0].0), "{:#}", err);
+        }
+    }
+
+    #[test]
+    fn test_parse_serve() {
+        let args = RawArgs::try_parse_from(["prg_convert", "--serve", "127.0.0.1:8080"]).unwrap();
+        assert_eq!(args.serve_address(), Some("127.0.0.1:8080"));
+        assert!(
+            RawArgs::try_parse_from([
+                "prg_convert",
+                "--serve",
+                "127.0.0.1:8080",
+                "--output-path",
+                "/tmp/o.csv",
+            ])
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_parse_self_test() {
+        let args = RawArgs::try_parse_from(["prg_convert", "--self-test"]).unwrap();
+        assert!(args.self_test());
+        assert!(!make_base_raw_args().self_test());
+        assert!(
+            RawArgs::try_parse_from(["prg_convert", "--self-test", "--schema-version", "2012"])
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_parse_accepts_displayed_values() {
+        let result = RawArgs::try_parse_from([
+            "prg_convert",
+            "--output-path",
+            "/tmp/o.parquet",
+            "--schema-version",
+            &SchemaVersion::Model2012.to_string(),
+            "--output-format",
+            &OutputFormat::CSV.to_string(),
+            "--crs-epsg",
+            &CRS::Epsg4326.to_string(),
+            "--timestamp-tz",
+            &TimestampTimezone::Warsaw.to_string(),
+            "--parquet-compression",
+            &ParquetCompression::Brotli.to_string(),
+        ])
+        .unwrap();
+        assert_eq!(result.crs_epsg, Some(CRS::Epsg4326));
+        assert_eq!(result.timestamp_tz, Some(TimestampTimezone::Warsaw));
+        assert_eq!(result.parquet_compression, Some(ParquetCompression::Brotli));
+    }
+
     // --- download-teryt with schema 2012 warning ---
 
     #[test]
     fn test_try_into_download_teryt_with_schema_2012_is_downgraded() {
         let args = RawArgs {
-            schema_version: SchemaVersionArg::V2012,
+            schema_version: Some(SchemaVersion::Model2012),
             teryt_download: Some(true),
             ..make_base_raw_args()
         };
@@ -797,6 +3517,8 @@ mod tests {
         let result = parse_input_paths(
             &vec!["fixtures".to_string()],
             &prg_convert::SchemaVersion::Model2012,
+            &ZipEntryFilter::default(),
+            false,
         );
         assert!(result.is_err());
         let err_str = format!("{}", result.err().unwrap());
@@ -812,6 +3534,8 @@ mod tests {
         let result = parse_input_paths(
             &vec!["Cargo.toml".to_string()],
             &prg_convert::SchemaVersion::Model2012,
+            &ZipEntryFilter::default(),
+            false,
         );
         assert!(result.is_err());
         let err_str = format!("{}", result.err().unwrap());
@@ -882,6 +3606,22 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_parse_rejects_invalid_geometry_encoding() {
+        let result = RawArgs::try_parse_from([
+            "prg_convert",
+            "--output-path",
+            "/tmp/o.parquet",
+            "--schema-version",
+            "2012",
+            "--output-format",
+            "geoparquet",
+            "--geometry-encoding",
+            "wkt",
+        ]);
+        assert!(result.is_err());
+    }
+
     #[test]
     fn test_parse_accepts_uppercase_output_format() {
         // ignore_case = true keeps the old case-insensitive behaviour
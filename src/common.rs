@@ -1,35 +1,106 @@
 use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::BufWriter;
+use std::io::Write;
+use std::path::Path;
 use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU8, AtomicUsize, Ordering};
 
 use anyhow::Context;
+use arrow::array::Array;
 use arrow::array::ArrayBuilder;
+use arrow::array::ArrayRef;
+use arrow::array::AsArray;
+use arrow::array::BooleanArray;
 use arrow::array::Date32Builder;
+use arrow::array::Float64Array;
 use arrow::array::Float64Builder;
+use arrow::array::Int32Array;
 use arrow::array::RecordBatch;
+use arrow::array::StringArray;
 use arrow::array::StringBuilder;
 use arrow::array::TimestampMillisecondBuilder;
+use arrow::array::UInt32Array;
+use arrow::array::new_null_array;
+use arrow::compute::cast;
+use arrow::compute::concat_batches;
+use arrow::compute::filter;
+use arrow::compute::{SortOptions, sort_to_indices, take_record_batch};
 use arrow::datatypes::DataType;
 use arrow::datatypes::Field;
+use arrow::datatypes::Float64Type;
 use arrow::datatypes::Schema;
+use arrow::datatypes::SchemaRef;
 use arrow::datatypes::TimeUnit;
+use arrow::error::ArrowError;
+use arrow::util::display::ArrayFormatter;
+use arrow::util::display::FormatOptions;
 use chrono::Duration;
 use chrono::MappedLocalTime;
 use chrono::NaiveDate;
 use chrono::NaiveDateTime;
 use chrono::TimeZone;
 use chrono_tz::Europe::Warsaw;
-#[cfg(feature = "cli")]
+#[cfg(feature = "geoparquet")]
 use geoarrow::datatypes::Crs;
-#[cfg(feature = "cli")]
+#[cfg(feature = "geoparquet")]
 use geoarrow::datatypes::PointType;
+#[cfg(feature = "geoparquet")]
+use geoarrow::datatypes::{CoordType, Dimension, Metadata};
 use std::sync::LazyLock;
 
 use proj4rs::Proj;
+use serde::Serialize;
 
+use crate::CRS;
 use crate::CoordOrder;
+use crate::PrgError;
+use crate::SchemaVersion;
+use crate::timings::{self, Phase};
 
 pub const EPOCH_DATE: NaiveDate = NaiveDate::from_ymd_opt(1970, 1, 1).unwrap();
 
+/// How much is printed while converting: `Quiet` (`--quiet`) prints only
+/// warnings and errors, `Normal` (default) adds the parameters, progress and
+/// summary, `Verbose` (`--verbose`) adds the unknown tags after every batch.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Verbosity {
+    Quiet,
+    #[default]
+    Normal,
+    Verbose,
+}
+
+static VERBOSITY: AtomicU8 = AtomicU8::new(Verbosity::Normal as u8);
+
+/// Sets the verbosity of the whole process.
+pub fn set_verbosity(verbosity: Verbosity) {
+    VERBOSITY.store(verbosity as u8, Ordering::Relaxed);
+}
+
+pub fn verbosity() -> Verbosity {
+    match VERBOSITY.load(Ordering::Relaxed) {
+        0 => Verbosity::Quiet,
+        1 => Verbosity::Normal,
+        _ => Verbosity::Verbose,
+    }
+}
+
+/// `println!` of progress and summary lines, silenced by `--quiet`.
+#[macro_export]
+macro_rules! progress {
+    ($($arg:tt)*) => {
+        if $crate::common::verbosity() >= $crate::common::Verbosity::Normal {
+            println!($($arg)*);
+        }
+    };
+}
+
 pub static SCHEMA_CSV: LazyLock<Arc<Schema>> = LazyLock::new(|| {
     Arc::new(Schema::new(vec![
         Field::new("przestrzen_nazw", DataType::Utf8, false),
@@ -44,6 +115,8 @@ pub static SCHEMA_CSV: LazyLock<Arc<Schema>> = LazyLock::new(|| {
             DataType::Timestamp(TimeUnit::Millisecond, Some(Arc::from("UTC"))),
             true,
         ),
+        // `prg-ad:waznyOd` in schema 2012, `prgad:dataNadania` in schema 2021
+        // (see `append_valid_from_columns`)
         Field::new("wazny_od_lub_data_nadania", DataType::Date32, true),
         Field::new("wazny_do", DataType::Date32, true),
         Field::new("teryt_wojewodztwo", DataType::Utf8, true),
@@ -57,27 +130,45 @@ pub static SCHEMA_CSV: LazyLock<Arc<Schema>> = LazyLock::new(|| {
         Field::new("czesc_miejscowosci", DataType::Utf8, true),
         Field::new("teryt_ulica", DataType::Utf8, true),
         Field::new("ulica", DataType::Utf8, true),
-        Field::new("numer_porzadkowy", DataType::Utf8, false),
+        Field::new("numer_porzadkowy", DataType::Utf8, true),
         Field::new("kod_pocztowy", DataType::Utf8, true),
         Field::new("status", DataType::Utf8, true),
+        Field::new("id_budynku", DataType::Utf8, true),
         Field::new("x_epsg_2180", DataType::Float64, true),
         Field::new("y_epsg_2180", DataType::Float64, true),
         Field::new("dlugosc_geograficzna", DataType::Float64, true),
         Field::new("szerokosc_geograficzna", DataType::Float64, true),
     ]))
 });
-#[cfg(feature = "cli")]
+#[cfg(feature = "geoparquet")]
 pub static CRS_2180: LazyLock<Crs> = LazyLock::new(|| {
     Crs::from_projjson(serde_json::from_str(include_str!("crs/epsg2180.json")).unwrap())
 });
-#[cfg(feature = "cli")]
+#[cfg(feature = "geoparquet")]
 pub static CRS_4326: LazyLock<Crs> = LazyLock::new(|| {
     Crs::from_projjson(serde_json::from_str(include_str!("crs/epsg4326.json")).unwrap())
 });
 pub static EPSG_2180: LazyLock<Proj> = LazyLock::new(|| Proj::from_epsg_code(2180).unwrap());
 pub static EPSG_4326: LazyLock<Proj> = LazyLock::new(|| Proj::from_epsg_code(4326).unwrap());
 
-#[cfg(feature = "cli")]
+/// Describes each of `epsg_codes` for which no projection definition is
+/// available, so that a conversion can be rejected before the first
+/// coordinate is transformed.
+pub fn missing_projections(epsg_codes: &[u16]) -> Vec<String> {
+    let mut codes = epsg_codes.to_vec();
+    codes.sort_unstable();
+    codes.dedup();
+    codes
+        .into_iter()
+        .filter_map(|code| {
+            Proj::from_epsg_code(code)
+                .err()
+                .map(|e| format!("no projection definition for EPSG:{} ({})", code, e))
+        })
+        .collect()
+}
+
+#[cfg(feature = "geoparquet")]
 pub fn get_geoparquet_schema(geoarrow_geom_type: PointType) -> Arc<Schema> {
     Arc::new(Schema::new(vec![
         Field::new("przestrzen_nazw", DataType::Utf8, false),
@@ -105,15 +196,943 @@ pub fn get_geoparquet_schema(geoarrow_geom_type: PointType) -> Arc<Schema> {
         Field::new("czesc_miejscowosci", DataType::Utf8, true),
         Field::new("teryt_ulica", DataType::Utf8, true),
         Field::new("ulica", DataType::Utf8, true),
-        Field::new("numer_porzadkowy", DataType::Utf8, false),
+        Field::new("numer_porzadkowy", DataType::Utf8, true),
         Field::new("kod_pocztowy", DataType::Utf8, true),
         Field::new("status", DataType::Utf8, true),
+        Field::new("id_budynku", DataType::Utf8, true),
         Field::new("dlugosc_geograficzna", DataType::Float64, true),
         Field::new("szerokosc_geograficzna", DataType::Float64, true),
         geoarrow_geom_type.to_field("geometry", true),
     ]))
 }
 
+/// Point type of the `geometry` column written in `crs` (2D, separated
+/// coordinates, with the PROJJSON definition of `crs`).
+#[cfg(feature = "geoparquet")]
+pub fn point_type_for(crs: &CRS) -> PointType {
+    let geoarrow_crs = match crs {
+        CRS::Epsg2180 => CRS_2180.clone(),
+        CRS::Epsg4326 => CRS_4326.clone(),
+    };
+    PointType::new(Dimension::XY, Arc::new(Metadata::new(geoarrow_crs, None)))
+        .with_coord_type(CoordType::Separated)
+}
+
+/// Minimal GeoParquet schema for geocoders: the id, the address as a single
+/// string (`ADDRESS_FIELD`) and the point.
+#[cfg(feature = "geoparquet")]
+pub fn get_points_only_geoparquet_schema(geoarrow_geom_type: PointType) -> Arc<Schema> {
+    Arc::new(Schema::new(vec![
+        Field::new("lokalny_id", DataType::Utf8, false),
+        ADDRESS_FIELD.clone(),
+        geoarrow_geom_type.to_field("geometry", true),
+    ]))
+}
+
+/// GeoParquet schema (see `get_geoparquet_schema`) with the geometry in `crs`.
+#[cfg(feature = "geoparquet")]
+pub fn geoparquet_schema_for(crs: &CRS) -> Arc<Schema> {
+    get_geoparquet_schema(point_type_for(crs))
+}
+
+/// Fixed number of digits of TERYT identifiers, per canonical column.
+pub const TERYT_ID_WIDTHS: [(&str, usize); 5] = [
+    ("teryt_wojewodztwo", 2),
+    ("teryt_powiat", 4),
+    ("teryt_gmina", 7),
+    ("teryt_miejscowosc", 7),
+    ("teryt_ulica", 5),
+];
+
+/// Checks that TERYT ids in `batch` are strings of digits of the expected
+/// width (see `TERYT_ID_WIDTHS`), e.g. to catch ids that lost a leading zero
+/// upstream. Returns one message per column with anomalies; columns missing
+/// from the batch are skipped.
+pub fn check_teryt_id_widths(batch: &RecordBatch) -> Vec<String> {
+    let mut warnings = Vec::new();
+    for (name, width) in TERYT_ID_WIDTHS {
+        let Some(column) = batch.column_by_name(name) else {
+            continue;
+        };
+        let Some(column) = column.as_string_opt::<i32>() else {
+            continue;
+        };
+        let mut anomalies = column
+            .iter()
+            .flatten()
+            .filter(|v| v.len() != width || !v.bytes().all(|b| b.is_ascii_digit()));
+        if let Some(example) = anomalies.next() {
+            warnings.push(format!(
+                "{} value(s) in column `{}` are not {}-digit TERYT ids (e.g. `{}`).",
+                anomalies.count() + 1,
+                name,
+                width,
+                example
+            ));
+        }
+    }
+    warnings
+}
+
+/// Case of the administrative unit and city names (`--name-case`).
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum NameCase {
+    /// Names as in PRG and TERC.
+    #[default]
+    Preserve,
+    Lower,
+    /// Every word capitalized, except Polish prepositions and conjunctions
+    /// inside a name (`Kostrzyn nad Odrą`), see `title_case`.
+    Title,
+}
+
+impl std::fmt::Display for NameCase {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            NameCase::Preserve => write!(f, "preserve"),
+            NameCase::Lower => write!(f, "lower"),
+            NameCase::Title => write!(f, "title"),
+        }
+    }
+}
+
+/// Columns with names changed by `set_name_case`.
+pub const NAME_CASE_COLUMNS: [&str; 4] = ["wojewodztwo", "powiat", "gmina", "miejscowosc"];
+
+/// Words kept lowercase by `title_case` when they are not the first word of
+/// a name, as in `Nowe Miasto nad Pilicą`.
+const LOWERCASE_WORDS: [&str; 12] = [
+    "nad", "pod", "przy", "na", "w", "we", "z", "ze", "i", "u", "koło", "k.",
+];
+
+/// Title-cases a name: the first letter of every word and of every part of
+/// a hyphenated word is uppercased and the rest lowercased, with Unicode case
+/// mapping of Polish diacritics (`ŁÓDŹ` to `Łódź`). Words in
+/// `LOWERCASE_WORDS` other than the first are lowercased.
+pub fn title_case(name: &str) -> String {
+    let mut title = String::with_capacity(name.len());
+    let mut first_word = true;
+    for (i, word) in name.split(' ').enumerate() {
+        if i > 0 {
+            title.push(' ');
+        }
+        if word.is_empty() {
+            continue;
+        }
+        let lower = word.to_lowercase();
+        if !first_word && LOWERCASE_WORDS.contains(&lower.as_str()) {
+            title.push_str(&lower);
+        } else {
+            for (j, part) in lower.split('-').enumerate() {
+                if j > 0 {
+                    title.push('-');
+                }
+                let mut chars = part.chars();
+                if let Some(c) = chars.next() {
+                    title.extend(c.to_uppercase());
+                    title.push_str(chars.as_str());
+                }
+            }
+        }
+        first_word = false;
+    }
+    title
+}
+
+/// Returns `batch` with the `NAME_CASE_COLUMNS` in `name_case`; the batch
+/// is returned unchanged with `NameCase::Preserve`.
+pub fn set_name_case(batch: &RecordBatch, name_case: NameCase) -> Result<RecordBatch, ArrowError> {
+    let change: fn(&str) -> String = match name_case {
+        NameCase::Preserve => return Ok(batch.clone()),
+        NameCase::Lower => str::to_lowercase,
+        NameCase::Title => title_case,
+    };
+    let mut columns = batch.columns().to_vec();
+    for name in NAME_CASE_COLUMNS {
+        let index = batch.schema().index_of(name)?;
+        let column = columns[index]
+            .as_string_opt::<i32>()
+            .ok_or_else(|| ArrowError::SchemaError(format!("Column `{}` is not Utf8.", name)))?;
+        let changed: StringArray = column.iter().map(|v| v.map(change)).collect();
+        columns[index] = Arc::new(changed);
+    }
+    RecordBatch::try_new(batch.schema(), columns)
+}
+
+/// Columns appended by `append_normalized_name_columns`: normalized
+/// variants of `miejscowosc` and `ulica`.
+pub static NORMALIZED_NAME_FIELDS: LazyLock<[Field; 2]> = LazyLock::new(|| {
+    [
+        Field::new("miejscowosc_norm", DataType::Utf8, false),
+        Field::new("ulica_norm", DataType::Utf8, true),
+    ]
+});
+
+/// Normalizes a name for fuzzy matching: Polish diacritics are replaced with
+/// their ASCII base letters, the name is lowercased and runs of whitespace
+/// are collapsed to a single space.
+pub fn normalize_name(name: &str) -> String {
+    let mut normalized = String::with_capacity(name.len());
+    for word in name.split_whitespace() {
+        if !normalized.is_empty() {
+            normalized.push(' ');
+        }
+        for c in word.chars().flat_map(char::to_lowercase) {
+            normalized.push(match c {
+                'ą' => 'a',
+                'ć' => 'c',
+                'ę' => 'e',
+                'ł' => 'l',
+                'ń' => 'n',
+                'ó' => 'o',
+                'ś' => 's',
+                'ź' | 'ż' => 'z',
+                _ => c,
+            });
+        }
+    }
+    normalized
+}
+
+/// Collapses runs of whitespace (including non-breaking spaces) to a single
+/// ASCII space and trims the ends. Returns `text` unchanged if it has none
+/// to collapse.
+pub fn normalize_whitespace(text: &str) -> Cow<'_, str> {
+    let mut previous_space = true;
+    let normalized = text.chars().all(|c| {
+        let ok = c == ' ' && !previous_space || !c.is_whitespace();
+        previous_space = c == ' ';
+        ok
+    }) && !previous_space;
+    if normalized || text.is_empty() {
+        return Cow::Borrowed(text);
+    }
+    Cow::Owned(text.split_whitespace().collect::<Vec<_>>().join(" "))
+}
+
+/// Returns `batch` with `NORMALIZED_NAME_FIELDS` appended, computed from the
+/// `miejscowosc` and `ulica` columns. The original columns are not changed.
+pub fn append_normalized_name_columns(batch: &RecordBatch) -> Result<RecordBatch, ArrowError> {
+    let normalize_column = |name: &str| -> Result<ArrayRef, ArrowError> {
+        let column = batch
+            .column_by_name(name)
+            .ok_or_else(|| ArrowError::SchemaError(format!("Missing column `{}`.", name)))?
+            .as_string_opt::<i32>()
+            .ok_or_else(|| ArrowError::SchemaError(format!("Column `{}` is not Utf8.", name)))?;
+        let normalized: StringArray = column.iter().map(|v| v.map(normalize_name)).collect();
+        Ok(Arc::new(normalized))
+    };
+    let mut fields = batch.schema().fields().to_vec();
+    fields.extend(NORMALIZED_NAME_FIELDS.iter().cloned().map(Arc::new));
+    let mut columns = batch.columns().to_vec();
+    columns.push(normalize_column("miejscowosc")?);
+    columns.push(normalize_column("ulica")?);
+    RecordBatch::try_new(Arc::new(Schema::new(fields)), columns)
+}
+
+/// Columns appended by `append_house_number_columns`: leading integer of
+/// `numer_porzadkowy` and the rest of it.
+pub static HOUSE_NUMBER_FIELDS: LazyLock<[Field; 2]> = LazyLock::new(|| {
+    [
+        Field::new("numer", DataType::Int32, true),
+        Field::new("numer_suffix", DataType::Utf8, true),
+    ]
+});
+
+/// Splits a house number into its leading integer and the rest with
+/// surrounding whitespace removed: `12A` into `12` and `A`, `15/3` into `15`
+/// and `/3`, `12-14` into `12` and `-14`, `007` into `7` and nothing. A number
+/// not starting with a digit (or with one too large) is all suffix; an empty
+/// one gives nothing.
+pub fn split_house_number(number: &str) -> (Option<i32>, Option<&str>) {
+    let number = number.trim();
+    let digits = number.len()
+        - number
+            .trim_start_matches(|c: char| c.is_ascii_digit())
+            .len();
+    let Ok(integer) = number[..digits].parse::<i32>() else {
+        return (None, Some(number).filter(|s| !s.is_empty()));
+    };
+    let suffix = number[digits..].trim_start();
+    (Some(integer), Some(suffix).filter(|s| !s.is_empty()))
+}
+
+/// Returns `batch` with `HOUSE_NUMBER_FIELDS` appended, split from the
+/// `numer_porzadkowy` column by `split_house_number`. The original column is
+/// not changed.
+pub fn append_house_number_columns(batch: &RecordBatch) -> Result<RecordBatch, ArrowError> {
+    let numbers = batch
+        .column_by_name("numer_porzadkowy")
+        .ok_or_else(|| ArrowError::SchemaError("Missing column `numer_porzadkowy`.".to_string()))?
+        .as_string_opt::<i32>()
+        .ok_or_else(|| {
+            ArrowError::SchemaError("Column `numer_porzadkowy` is not Utf8.".to_string())
+        })?;
+    let (integers, suffixes): (Vec<Option<i32>>, Vec<Option<&str>>) = numbers
+        .iter()
+        .map(|v| v.map_or((None, None), split_house_number))
+        .unzip();
+    let mut fields = batch.schema().fields().to_vec();
+    fields.extend(HOUSE_NUMBER_FIELDS.iter().cloned().map(Arc::new));
+    let mut columns = batch.columns().to_vec();
+    columns.push(Arc::new(Int32Array::from(integers)));
+    columns.push(Arc::new(StringArray::from(suffixes)));
+    RecordBatch::try_new(Arc::new(Schema::new(fields)), columns)
+}
+
+/// Columns appended by `append_valid_from_columns`.
+pub static VALID_FROM_FIELDS: LazyLock<[Field; 2]> = LazyLock::new(|| {
+    [
+        Field::new("wazny_od", DataType::Date32, true),
+        Field::new("data_nadania", DataType::Date32, true),
+    ]
+});
+
+/// Returns `batch` with `VALID_FROM_FIELDS` appended.
+/// `wazny_od_lub_data_nadania` holds `prg-ad:waznyOd` (start of validity) in
+/// schema 2012 and `prgad:dataNadania` (date the number was assigned) in
+/// schema 2021. It is copied to the column of the field it was read from,
+/// and the other column is null. The original column is not changed.
+pub fn append_valid_from_columns(
+    batch: &RecordBatch,
+    schema_version: SchemaVersion,
+) -> Result<RecordBatch, ArrowError> {
+    let dates = batch
+        .column_by_name("wazny_od_lub_data_nadania")
+        .ok_or_else(|| {
+            ArrowError::SchemaError("Missing column `wazny_od_lub_data_nadania`.".to_string())
+        })?;
+    let nulls = new_null_array(&DataType::Date32, batch.num_rows());
+    let (valid_from, assigned) = match schema_version {
+        SchemaVersion::Model2012 => (dates.clone(), nulls),
+        SchemaVersion::Model2021 => (nulls, dates.clone()),
+    };
+    let mut fields = batch.schema().fields().to_vec();
+    fields.extend(VALID_FROM_FIELDS.iter().cloned().map(Arc::new));
+    let mut columns = batch.columns().to_vec();
+    columns.push(valid_from);
+    columns.push(assigned);
+    RecordBatch::try_new(Arc::new(Schema::new(fields)), columns)
+}
+
+/// Column appended by `append_source_column`: name of the input file the
+/// address was read from.
+pub static SOURCE_FIELD: LazyLock<Field> =
+    LazyLock::new(|| Field::new("zrodlo", DataType::Utf8, false));
+
+/// Returns `batch` with `SOURCE_FIELD` appended, holding `source` in every row.
+pub fn append_source_column(batch: &RecordBatch, source: &str) -> Result<RecordBatch, ArrowError> {
+    let mut fields = batch.schema().fields().to_vec();
+    fields.push(Arc::new(SOURCE_FIELD.clone()));
+    let mut columns = batch.columns().to_vec();
+    columns.push(Arc::new(StringArray::from(vec![source; batch.num_rows()])));
+    RecordBatch::try_new(Arc::new(Schema::new(fields)), columns)
+}
+
+/// Column appended by `append_address_column`: the address as a single
+/// string, see `full_address`.
+pub static ADDRESS_FIELD: LazyLock<Field> =
+    LazyLock::new(|| Field::new("adres", DataType::Utf8, false));
+
+/// The address as a single string, `miejscowosc, ulica numer, kod`, e.g.
+/// `Rzepin, Inwalidów Wojennych 1A, 69-110`. Without a street the number
+/// follows the city (`Żubrów 21A, 69-200`), missing parts are left out.
+pub fn full_address(
+    city: &str,
+    street: Option<&str>,
+    number: Option<&str>,
+    postcode: Option<&str>,
+) -> String {
+    let mut address = city.to_string();
+    if let Some(street) = street.filter(|s| !s.is_empty()) {
+        address.push_str(", ");
+        address.push_str(street);
+    }
+    if let Some(number) = number.filter(|s| !s.is_empty()) {
+        address.push(' ');
+        address.push_str(number);
+    }
+    if let Some(postcode) = postcode.filter(|s| !s.is_empty()) {
+        address.push_str(", ");
+        address.push_str(postcode);
+    }
+    address
+}
+
+/// Returns `batch` with `ADDRESS_FIELD` appended, the `full_address` of the
+/// `miejscowosc`, `ulica`, `numer_porzadkowy` and `kod_pocztowy` columns.
+pub fn append_address_column(batch: &RecordBatch) -> Result<RecordBatch, ArrowError> {
+    let column = |name: &str| {
+        batch
+            .column_by_name(name)
+            .ok_or_else(|| ArrowError::SchemaError(format!("Missing column `{}`.", name)))?
+            .as_string_opt::<i32>()
+            .ok_or_else(|| ArrowError::SchemaError(format!("Column `{}` is not Utf8.", name)))
+    };
+    let (cities, streets) = (column("miejscowosc")?, column("ulica")?);
+    let (numbers, postcodes) = (column("numer_porzadkowy")?, column("kod_pocztowy")?);
+    let addresses: StringArray = cities
+        .iter()
+        .zip(streets.iter())
+        .zip(numbers.iter())
+        .zip(postcodes.iter())
+        .map(|(((city, street), number), postcode)| {
+            Some(full_address(
+                city.unwrap_or_default(),
+                street,
+                number,
+                postcode,
+            ))
+        })
+        .collect();
+    let mut fields = batch.schema().fields().to_vec();
+    fields.push(Arc::new(ADDRESS_FIELD.clone()));
+    let mut columns = batch.columns().to_vec();
+    columns.push(Arc::new(addresses));
+    RecordBatch::try_new(Arc::new(Schema::new(fields)), columns)
+}
+
+/// Column appended by `append_full_address_column`: the postal address with
+/// the administrative units, see `full_postal_address`.
+pub static FULL_ADDRESS_FIELD: LazyLock<Field> =
+    LazyLock::new(|| Field::new("pelny_adres", DataType::Utf8, false));
+
+/// Street types street names can start with (lowercase), which are then not
+/// prefixed with `ul.`.
+const STREET_TYPE_PREFIXES: [&str; 20] = [
+    "ul.",
+    "ulica",
+    "al.",
+    "aleja",
+    "pl.",
+    "plac",
+    "os.",
+    "osiedle",
+    "rondo",
+    "skwer",
+    "bulw.",
+    "bulwar",
+    "park",
+    "rynek",
+    "szosa",
+    "droga",
+    "ogród",
+    "wyspa",
+    "wyb.",
+    "wybrzeże",
+];
+
+/// The address as a single line, e.g. `ul. Podgórna 12A, 67-415 Konotop,
+/// gmina Kolsko, powiat nowosolski, woj. lubuskie`. Without a street the
+/// city comes first (`Żubrów 21A, 69-200 Żubrów, ...`); street names that
+/// start with a street type (`plac`, `al.`, ...) are not prefixed with
+/// `ul.`. Missing parts are left out.
+pub fn full_postal_address(
+    street: Option<&str>,
+    number: Option<&str>,
+    postcode: Option<&str>,
+    city: &str,
+    municipality: Option<&str>,
+    county: Option<&str>,
+    voivodeship: Option<&str>,
+) -> String {
+    fn present(value: Option<&str>) -> Option<&str> {
+        value.filter(|s| !s.is_empty())
+    }
+    let mut first = match present(street) {
+        Some(street) => {
+            let first_word = street.split_whitespace().next().unwrap_or_default();
+            if STREET_TYPE_PREFIXES.contains(&first_word.to_lowercase().as_str()) {
+                street.to_string()
+            } else {
+                format!("ul. {}", street)
+            }
+        }
+        None => city.to_string(),
+    };
+    if let Some(number) = present(number) {
+        if !first.is_empty() {
+            first.push(' ');
+        }
+        first.push_str(number);
+    }
+    let mut parts = vec![first];
+    match present(postcode) {
+        Some(postcode) if city.is_empty() => parts.push(postcode.to_string()),
+        Some(postcode) => parts.push(format!("{} {}", postcode, city)),
+        // the city is already the first part of addresses without a street
+        None if present(street).is_some() => parts.push(city.to_string()),
+        None => {}
+    }
+    for (prefix, unit) in [
+        ("gmina", municipality),
+        ("powiat", county),
+        ("woj.", voivodeship),
+    ] {
+        if let Some(unit) = present(unit) {
+            parts.push(format!("{} {}", prefix, unit));
+        }
+    }
+    parts.retain(|part| !part.is_empty());
+    parts.join(", ")
+}
+
+/// Returns `batch` with `FULL_ADDRESS_FIELD` appended, the
+/// `full_postal_address` of the name, number and postcode columns.
+pub fn append_full_address_column(batch: &RecordBatch) -> Result<RecordBatch, ArrowError> {
+    let column = |name: &str| {
+        batch
+            .column_by_name(name)
+            .ok_or_else(|| ArrowError::SchemaError(format!("Missing column `{}`.", name)))?
+            .as_string_opt::<i32>()
+            .ok_or_else(|| ArrowError::SchemaError(format!("Column `{}` is not Utf8.", name)))
+    };
+    let (streets, numbers) = (column("ulica")?, column("numer_porzadkowy")?);
+    let (postcodes, cities) = (column("kod_pocztowy")?, column("miejscowosc")?);
+    let (municipalities, counties) = (column("gmina")?, column("powiat")?);
+    let voivodeships = column("wojewodztwo")?;
+    fn value(array: &StringArray, row: usize) -> Option<&str> {
+        array.is_valid(row).then(|| array.value(row))
+    }
+    let addresses: StringArray = (0..batch.num_rows())
+        .map(|row| {
+            Some(full_postal_address(
+                value(streets, row),
+                value(numbers, row),
+                value(postcodes, row),
+                value(cities, row).unwrap_or_default(),
+                value(municipalities, row),
+                value(counties, row),
+                value(voivodeships, row),
+            ))
+        })
+        .collect();
+    let mut fields = batch.schema().fields().to_vec();
+    fields.push(Arc::new(FULL_ADDRESS_FIELD.clone()));
+    let mut columns = batch.columns().to_vec();
+    columns.push(Arc::new(addresses));
+    RecordBatch::try_new(Arc::new(Schema::new(fields)), columns)
+}
+
+/// Column appended by `append_grid_column`: id of the grid cell of the
+/// address, null without coordinates.
+pub static GRID_FIELD: LazyLock<Field> =
+    LazyLock::new(|| Field::new("grid_id", DataType::Utf8, true));
+
+/// Id of the cell of a grid of `size` meters in EPSG:2180 with the point,
+/// `x_y` of its lower left corner, e.g. `500000_300000`.
+pub fn grid_cell_id(x2180: f64, y2180: f64, size: u32) -> String {
+    let size = f64::from(size);
+    let snap = |value: f64| ((value / size).floor() * size) as i64;
+    format!("{}_{}", snap(x2180), snap(y2180))
+}
+
+/// Returns `batch` with `GRID_FIELD` appended, the `grid_cell_id` of
+/// `x_epsg_2180`/`y_epsg_2180` in a grid of `size` meters.
+pub fn append_grid_column(batch: &RecordBatch, size: u32) -> Result<RecordBatch, ArrowError> {
+    let coordinate = |name: &str| {
+        batch
+            .column_by_name(name)
+            .ok_or_else(|| ArrowError::SchemaError(format!("Missing column `{}`.", name)))?
+            .as_primitive_opt::<Float64Type>()
+            .ok_or_else(|| ArrowError::SchemaError(format!("Column `{}` is not Float64.", name)))
+    };
+    let (xs, ys) = (coordinate("x_epsg_2180")?, coordinate("y_epsg_2180")?);
+    let ids: StringArray = xs
+        .iter()
+        .zip(ys.iter())
+        .map(|(x, y)| Some(grid_cell_id(x?, y?, size)))
+        .collect();
+    let mut fields = batch.schema().fields().to_vec();
+    fields.push(Arc::new(GRID_FIELD.clone()));
+    let mut columns = batch.columns().to_vec();
+    columns.push(Arc::new(ids));
+    RecordBatch::try_new(Arc::new(Schema::new(fields)), columns)
+}
+
+/// Order of the Hilbert curve of `hilbert_index`: the extent is divided into
+/// 2^16 x 2^16 cells (about 12 m).
+const HILBERT_ORDER: u32 = 16;
+/// Square in EPSG:2180 covering Poland, mapped onto the cells of the Hilbert
+/// curve. Points outside of it are moved to its edge.
+const HILBERT_EXTENT_2180: (f64, f64) = (100_000.0, 900_000.0);
+
+/// Position of the point on a Hilbert curve over `HILBERT_EXTENT_2180`. Close
+/// indices are close in space, so rows sorted by it are grouped spatially.
+pub fn hilbert_index(x2180: f64, y2180: f64) -> u32 {
+    let side = 1u32 << HILBERT_ORDER;
+    let (min, max) = HILBERT_EXTENT_2180;
+    let cell = |value: f64| {
+        let scaled = (value - min) / (max - min) * f64::from(side);
+        (scaled.max(0.0) as u32).min(side - 1)
+    };
+    let (mut x, mut y) = (cell(x2180), cell(y2180));
+    let mut index = 0u32;
+    let mut s = side / 2;
+    while s > 0 {
+        let rx = u32::from(x & s > 0);
+        let ry = u32::from(y & s > 0);
+        index += s * s * ((3 * rx) ^ ry);
+        // rotate the quadrant so the curve continues in the next one
+        if ry == 0 {
+            if rx == 1 {
+                x = side - 1 - x;
+                y = side - 1 - y;
+            }
+            std::mem::swap(&mut x, &mut y);
+        }
+        s /= 2;
+    }
+    index
+}
+
+/// Rows of `batches` in one batch, sorted by the `hilbert_index` of
+/// `x_epsg_2180`/`y_epsg_2180`. Rows without coordinates are last.
+pub fn sort_by_hilbert_index(
+    schema: &SchemaRef,
+    batches: &[RecordBatch],
+) -> Result<RecordBatch, ArrowError> {
+    let batch = concat_batches(schema, batches)?;
+    let coordinate = |name: &str| {
+        batch
+            .column_by_name(name)
+            .ok_or_else(|| ArrowError::SchemaError(format!("Missing column `{}`.", name)))?
+            .as_primitive_opt::<Float64Type>()
+            .ok_or_else(|| ArrowError::SchemaError(format!("Column `{}` is not Float64.", name)))
+    };
+    let (xs, ys) = (coordinate("x_epsg_2180")?, coordinate("y_epsg_2180")?);
+    let indices: UInt32Array = xs
+        .iter()
+        .zip(ys.iter())
+        .map(|(x, y)| Some(hilbert_index(x?, y?)))
+        .collect();
+    let order = sort_to_indices(
+        &indices,
+        Some(SortOptions {
+            descending: false,
+            nulls_first: false,
+        }),
+        None,
+    )?;
+    take_record_batch(&batch, &order)
+}
+
+/// Largest number of decimals of `round_coordinates`, about the precision of
+/// a f64 longitude.
+pub const MAX_COORD_PRECISION: u32 = 15;
+
+/// Returns `batch` with `dlugosc_geograficzna` and `szerokosc_geograficzna`
+/// rounded to `decimals` decimal places (6 is about 0.1 m). The EPSG:2180
+/// columns are not changed.
+pub fn round_coordinates(batch: &RecordBatch, decimals: u32) -> Result<RecordBatch, ArrowError> {
+    let scale = 10f64.powi(decimals.min(MAX_COORD_PRECISION) as i32);
+    let mut columns = batch.columns().to_vec();
+    for name in ["dlugosc_geograficzna", "szerokosc_geograficzna"] {
+        let index = batch.schema().index_of(name)?;
+        let column = columns[index]
+            .as_primitive_opt::<Float64Type>()
+            .ok_or_else(|| ArrowError::SchemaError(format!("Column `{}` is not Float64.", name)))?;
+        let rounded: Float64Array = column.unary(|v| (v * scale).round() / scale);
+        columns[index] = Arc::new(rounded);
+    }
+    RecordBatch::try_new(batch.schema(), columns)
+}
+
+/// Returns `batch` with every Float64 column replaced by a Utf8 column of the
+/// same name holding the value as the CSV writer would format it, but with a
+/// decimal comma (`12,345`). Nulls stay null.
+pub fn format_floats_with_decimal_comma(batch: &RecordBatch) -> Result<RecordBatch, ArrowError> {
+    let mut fields = Vec::with_capacity(batch.num_columns());
+    let mut columns = Vec::with_capacity(batch.num_columns());
+    for (field, column) in batch.schema().fields().iter().zip(batch.columns()) {
+        if field.data_type() != &DataType::Float64 {
+            fields.push(field.clone());
+            columns.push(column.clone());
+            continue;
+        }
+        let formatter = ArrayFormatter::try_new(column.as_ref(), &FormatOptions::default())?;
+        let formatted: StringArray = (0..column.len())
+            .map(|i| {
+                column
+                    .is_valid(i)
+                    .then(|| formatter.value(i).to_string().replace('.', ","))
+            })
+            .collect();
+        fields.push(Arc::new(Field::new(
+            field.name(),
+            DataType::Utf8,
+            field.is_nullable(),
+        )));
+        columns.push(Arc::new(formatted) as ArrayRef);
+    }
+    RecordBatch::try_new(Arc::new(Schema::new(fields)), columns)
+}
+
+/// Returns `schema` with fields renamed according to `renames` (old name to
+/// new name), keeping their types and metadata. Fails if a name in `renames`
+/// is not a field of `schema` or if two fields would end up with the same name.
+pub fn rename_fields(schema: &Schema, renames: &HashMap<String, String>) -> anyhow::Result<Schema> {
+    let mut unknown: Vec<&str> = renames
+        .keys()
+        .filter(|name| schema.field_with_name(name).is_err())
+        .map(String::as_str)
+        .collect();
+    if !unknown.is_empty() {
+        unknown.sort_unstable();
+        let available: Vec<&str> = schema.fields().iter().map(|f| f.name().as_str()).collect();
+        anyhow::bail!(
+            "Unknown column(s) to rename: {}. Available columns: {}.",
+            unknown.join(", "),
+            available.join(", ")
+        );
+    }
+    let fields: Vec<Field> = schema
+        .fields()
+        .iter()
+        .map(|field| match renames.get(field.name()) {
+            Some(new_name) => field.as_ref().clone().with_name(new_name),
+            None => field.as_ref().clone(),
+        })
+        .collect();
+    let mut names = HashSet::new();
+    if let Some(duplicate) = fields.iter().find(|f| !names.insert(f.name())) {
+        anyhow::bail!(
+            "Renaming columns would create two columns named `{}`.",
+            duplicate.name()
+        );
+    }
+    Ok(Schema::new_with_metadata(fields, schema.metadata().clone()))
+}
+
+/// Returns `batch` with columns renamed as by [`rename_fields`]. The data is not copied.
+pub fn rename_columns(
+    batch: &RecordBatch,
+    renames: &HashMap<String, String>,
+) -> anyhow::Result<RecordBatch> {
+    let schema = rename_fields(&batch.schema(), renames)?;
+    Ok(RecordBatch::try_new(
+        Arc::new(schema),
+        batch.columns().to_vec(),
+    )?)
+}
+
+/// `schema` with the timezone of its timestamp fields set to `timezone` (an
+/// IANA name, e.g. `Europe/Warsaw`).
+pub fn with_timestamp_timezone(schema: &Schema, timezone: &str) -> Schema {
+    let fields: Vec<Field> = schema
+        .fields()
+        .iter()
+        .map(|field| match field.data_type() {
+            DataType::Timestamp(unit, _) => field
+                .as_ref()
+                .clone()
+                .with_data_type(DataType::Timestamp(*unit, Some(Arc::from(timezone)))),
+            _ => field.as_ref().clone(),
+        })
+        .collect();
+    Schema::new_with_metadata(fields, schema.metadata().clone())
+}
+
+/// `batch` with the timezone of its timestamp columns set to `timezone`. Only
+/// the timezone attached to the columns changes, not the stored instants.
+pub fn set_timestamp_timezone(
+    batch: &RecordBatch,
+    timezone: &str,
+) -> Result<RecordBatch, ArrowError> {
+    let schema = Arc::new(with_timestamp_timezone(&batch.schema(), timezone));
+    let columns = batch
+        .columns()
+        .iter()
+        .zip(schema.fields())
+        .map(|(column, field)| cast(column, field.data_type()))
+        .collect::<Result<_, _>>()?;
+    RecordBatch::try_new(schema, columns)
+}
+
+/// Keeps the last `capacity` rows of the batches pushed to it.
+///
+/// Older rows are dropped as new batches arrive, so memory use is bounded by
+/// `capacity` rows (plus the rest of the oldest batch, which a slice keeps alive).
+pub struct TailBuffer {
+    capacity: usize,
+    batches: VecDeque<RecordBatch>,
+    num_rows: usize,
+}
+
+impl TailBuffer {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            batches: VecDeque::new(),
+            num_rows: 0,
+        }
+    }
+
+    pub fn push(&mut self, batch: RecordBatch) {
+        self.num_rows += batch.num_rows();
+        self.batches.push_back(batch);
+        while self.num_rows > self.capacity {
+            let excess = self.num_rows - self.capacity;
+            let oldest = self.batches.front_mut().unwrap();
+            if oldest.num_rows() <= excess {
+                self.num_rows -= oldest.num_rows();
+                self.batches.pop_front();
+            } else {
+                *oldest = oldest.slice(excess, oldest.num_rows() - excess);
+                self.num_rows -= excess;
+            }
+        }
+    }
+
+    /// Number of rows currently kept.
+    pub fn num_rows(&self) -> usize {
+        self.num_rows
+    }
+
+    /// The kept rows in the order they were pushed.
+    pub fn into_batches(self) -> Vec<RecordBatch> {
+        self.batches.into()
+    }
+}
+
+// Bytes of string values `CanonicalBuilders::with_capacity` reserves per row
+// of a column.
+const ID_NAMESPACE_BYTES: usize = 12;
+const UUID_BYTES: usize = 36;
+/// Names of the administrative units, city, city part and street.
+const NAME_BYTES: usize = 12;
+const HOUSE_NUMBER_BYTES: usize = 6;
+const POSTCODE_BYTES: usize = 6;
+const STATUS_BYTES: usize = 10;
+/// Mostly empty.
+const BUILDING_ID_BYTES: usize = 0;
+/// TERYT ids of the voivodeship, county and municipality.
+const UNIT_TERYT_ID_BYTES: usize = 54;
+const CITY_TERYT_ID_BYTES: usize = 62;
+const STREET_TERYT_ID_BYTES: usize = 91;
+
+/// Sum of the string value capacities `CanonicalBuilders::with_capacity`
+/// reserves per row.
+const STRING_BYTES_PER_ROW: usize = ID_NAMESPACE_BYTES
+    + UUID_BYTES
+    + 6 * NAME_BYTES
+    + HOUSE_NUMBER_BYTES
+    + POSTCODE_BYTES
+    + STATUS_BYTES
+    + BUILDING_ID_BYTES
+    + 3 * UNIT_TERYT_ID_BYTES
+    + CITY_TERYT_ID_BYTES
+    + STREET_TERYT_ID_BYTES;
+/// Number of string columns in `SCHEMA_CSV` (each has an `i32` offset per row).
+const STRING_COLUMNS: usize = 17;
+/// Timestamps, dates, EPSG:2180 coordinates and the derived lon/lat.
+const FIXED_BYTES_PER_ROW: usize = 2 * 8 + 2 * 4 + 2 * 8 + 2 * 8;
+
+/// Rough estimate of how many bytes one row of a batch takes in memory.
+///
+/// Based on the capacities the column builders reserve, doubled to account
+/// for the finished batch being copied/encoded by the writer. This is a
+/// heuristic used to pick a batch size for `--max-memory`, not a hard limit.
+pub fn estimated_bytes_per_row() -> usize {
+    2 * (STRING_BYTES_PER_ROW + STRING_COLUMNS * 4 + FIXED_BYTES_PER_ROW)
+}
+
+/// Checks that `names` are the names of the leading fields of `schema`, in
+/// order. `RecordBatch::try_new` only checks the types of positional columns,
+/// so two swapped columns of the same type would be silently mislabelled.
+pub(crate) fn check_column_order<'a>(
+    schema: &Schema,
+    names: impl IntoIterator<Item = &'a str>,
+) -> Result<(), String> {
+    for (i, name) in names.into_iter().enumerate() {
+        match schema.fields().get(i) {
+            Some(field) if field.name() == name => {}
+            Some(field) => {
+                return Err(format!(
+                    "column {} is `{}` but the schema has `{}` there",
+                    i,
+                    name,
+                    field.name()
+                ));
+            }
+            None => return Err(format!("column {} `{}` is not in the schema", i, name)),
+        }
+    }
+    Ok(())
+}
+
+/// Text columns of `SCHEMA_CSV` passed through `normalize_whitespace` when
+/// `CanonicalBuilders::normalize_whitespace` is set.
+const WHITESPACE_NORMALIZED_COLUMNS: [&str; 9] = [
+    "wojewodztwo",
+    "powiat",
+    "gmina",
+    "miejscowosc",
+    "czesc_miejscowosci",
+    "ulica",
+    "numer_porzadkowy",
+    "kod_pocztowy",
+    "status",
+];
+
+/// Columns that can be written without trimming (`UntrimmedColumns`): text
+/// read from the address itself rather than from the dictionaries.
+pub const UNTRIMMED_COLUMN_NAMES: [&str; 4] =
+    ["numer_porzadkowy", "kod_pocztowy", "status", "id_budynku"];
+
+/// Columns whose text is written with its leading and trailing whitespace,
+/// as in the input; all others are trimmed. `status` (schema 2012) keeps it
+/// only where it is written as in the input (`with_status_raw` and unknown
+/// statuses), known statuses are still recognized after trimming.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct UntrimmedColumns {
+    pub house_number: bool,
+    pub postcode: bool,
+    pub status: bool,
+    pub building_id: bool,
+}
+
+impl UntrimmedColumns {
+    /// Columns named in `names`, see `UNTRIMMED_COLUMN_NAMES`.
+    pub fn from_names<S: AsRef<str>>(names: &[S]) -> anyhow::Result<Self> {
+        let mut columns = Self::default();
+        for name in names {
+            match name.as_ref() {
+                "numer_porzadkowy" => columns.house_number = true,
+                "kod_pocztowy" => columns.postcode = true,
+                "status" => columns.status = true,
+                "id_budynku" => columns.building_id = true,
+                other => anyhow::bail!(
+                    "Unknown column `{}`, expected one of: {}.",
+                    other,
+                    UNTRIMMED_COLUMN_NAMES.join(", ")
+                ),
+            }
+        }
+        Ok(columns)
+    }
+
+    /// Names of the columns, in the order of `UNTRIMMED_COLUMN_NAMES`.
+    pub fn names(&self) -> Vec<&'static str> {
+        let flags = [
+            self.house_number,
+            self.postcode,
+            self.status,
+            self.building_id,
+        ];
+        UNTRIMMED_COLUMN_NAMES
+            .into_iter()
+            .zip(flags)
+            .filter_map(|(name, untrimmed)| untrimmed.then_some(name))
+            .collect()
+    }
+}
+
+/// `text` as read if `untrimmed`, trimmed otherwise.
+pub(crate) fn trim_unless(untrimmed: bool, text: &str) -> &str {
+    if untrimmed { text } else { text.trim() }
+}
+
 /// Owns the arrow column builders for one canonical (`SCHEMA_CSV`-shaped)
 /// batch. Shared by both schema parsers so the column set, order, and
 /// null-padding are defined in one place, next to `SCHEMA_CSV`.
@@ -133,8 +1152,11 @@ pub(crate) struct CanonicalBuilders {
     pub(crate) house_number: StringBuilder,
     pub(crate) postcode: StringBuilder,
     pub(crate) status: StringBuilder,
+    pub(crate) building_id: StringBuilder,
     pub(crate) x_epsg_2180: Float64Builder,
     pub(crate) y_epsg_2180: Float64Builder,
+    /// Longitude/latitude read from geographic input (see `input_crs`),
+    /// empty otherwise.
     pub(crate) longitude: Float64Builder,
     pub(crate) latitude: Float64Builder,
     pub(crate) voivodeship_teryt_id: StringBuilder,
@@ -142,79 +1164,189 @@ pub(crate) struct CanonicalBuilders {
     pub(crate) municipality_teryt_id: StringBuilder,
     pub(crate) city_teryt_id: StringBuilder,
     pub(crate) street_teryt_id: StringBuilder,
+    keep: Vec<bool>,
+    /// Derive longitude/latitude from the EPSG:2180 columns; when `false`
+    /// they are left null.
+    pub(crate) reproject: bool,
+    /// CRS of the input coordinates, see `parse_gml_pos`.
+    pub(crate) input_crs: CRS,
+    /// Collapse whitespace in the names and other text columns, see
+    /// `WHITESPACE_NORMALIZED_COLUMNS`.
+    pub(crate) normalize_whitespace: bool,
+    /// Columns written without trimming.
+    pub(crate) untrimmed: UntrimmedColumns,
 }
 
 impl CanonicalBuilders {
     pub(crate) fn with_capacity(batch_size: usize) -> Self {
+        let strings =
+            |bytes_per_row| StringBuilder::with_capacity(batch_size, bytes_per_row * batch_size);
         Self {
-            id_namespace: StringBuilder::with_capacity(batch_size, 12 * batch_size),
-            uuid: StringBuilder::with_capacity(batch_size, 36 * batch_size),
+            id_namespace: strings(ID_NAMESPACE_BYTES),
+            uuid: strings(UUID_BYTES),
             version: TimestampMillisecondBuilder::with_capacity(batch_size)
                 .with_timezone(Arc::from("UTC")),
             lifecycle_start_date: TimestampMillisecondBuilder::with_capacity(batch_size)
                 .with_timezone(Arc::from("UTC")),
             valid_since_date: Date32Builder::with_capacity(batch_size),
             valid_to_date: Date32Builder::with_capacity(batch_size),
-            voivodeship: StringBuilder::with_capacity(batch_size, 12 * batch_size),
-            county: StringBuilder::with_capacity(batch_size, 12 * batch_size),
-            municipality: StringBuilder::with_capacity(batch_size, 12 * batch_size),
-            city: StringBuilder::with_capacity(batch_size, 12 * batch_size),
-            city_part: StringBuilder::with_capacity(batch_size, 12 * batch_size),
-            street: StringBuilder::with_capacity(batch_size, 12 * batch_size),
-            house_number: StringBuilder::with_capacity(batch_size, 6 * batch_size),
-            postcode: StringBuilder::with_capacity(batch_size, 6 * batch_size),
-            status: StringBuilder::with_capacity(batch_size, 10 * batch_size),
+            voivodeship: strings(NAME_BYTES),
+            county: strings(NAME_BYTES),
+            municipality: strings(NAME_BYTES),
+            city: strings(NAME_BYTES),
+            city_part: strings(NAME_BYTES),
+            street: strings(NAME_BYTES),
+            house_number: strings(HOUSE_NUMBER_BYTES),
+            postcode: strings(POSTCODE_BYTES),
+            status: strings(STATUS_BYTES),
+            building_id: strings(BUILDING_ID_BYTES),
             x_epsg_2180: Float64Builder::with_capacity(batch_size),
             y_epsg_2180: Float64Builder::with_capacity(batch_size),
-            longitude: Float64Builder::with_capacity(batch_size),
-            latitude: Float64Builder::with_capacity(batch_size),
-            voivodeship_teryt_id: StringBuilder::with_capacity(batch_size, 54 * batch_size),
-            county_teryt_id: StringBuilder::with_capacity(batch_size, 54 * batch_size),
-            municipality_teryt_id: StringBuilder::with_capacity(batch_size, 54 * batch_size),
-            city_teryt_id: StringBuilder::with_capacity(batch_size, 62 * batch_size),
-            street_teryt_id: StringBuilder::with_capacity(batch_size, 91 * batch_size),
+            longitude: Float64Builder::new(),
+            latitude: Float64Builder::new(),
+            voivodeship_teryt_id: strings(UNIT_TERYT_ID_BYTES),
+            county_teryt_id: strings(UNIT_TERYT_ID_BYTES),
+            municipality_teryt_id: strings(UNIT_TERYT_ID_BYTES),
+            city_teryt_id: strings(CITY_TERYT_ID_BYTES),
+            street_teryt_id: strings(STREET_TERYT_ID_BYTES),
+            keep: Vec::with_capacity(batch_size),
+            reproject: true,
+            input_crs: CRS::Epsg2180,
+            normalize_whitespace: false,
+            untrimmed: UntrimmedColumns::default(),
         }
     }
 
     /// Finish all builders into a batch matching `SCHEMA_CSV`'s column order.
-    pub(crate) fn build_record_batch(&mut self) -> RecordBatch {
-        RecordBatch::try_new(
-            SCHEMA_CSV.clone(),
-            vec![
-                Arc::new(self.id_namespace.finish()),
-                Arc::new(self.uuid.finish()),
-                Arc::new(self.version.finish()),
+    /// Rows marked as not kept by `end_row` are filtered out here.
+    /// Longitude/latitude are not buffered per address; they are derived here
+    /// from the EPSG:2180 columns with a single reprojection call per batch
+    /// (or left null without `reproject`), unless they were read from
+    /// geographic input.
+    pub(crate) fn build_record_batch(&mut self) -> Result<RecordBatch, ArrowError> {
+        let _timer = timings::start(Phase::ArrowBuilding);
+        let keep = BooleanArray::from(std::mem::take(&mut self.keep));
+        let drop_rows = keep.false_count() > 0;
+        let named_columns: [(&str, ArrayRef); 23] = [
+            ("przestrzen_nazw", Arc::new(self.id_namespace.finish())),
+            ("lokalny_id", Arc::new(self.uuid.finish())),
+            ("wersja_id", Arc::new(self.version.finish())),
+            (
+                "poczatek_wersji_obiektu",
                 Arc::new(self.lifecycle_start_date.finish()),
+            ),
+            (
+                "wazny_od_lub_data_nadania",
                 Arc::new(self.valid_since_date.finish()),
-                Arc::new(self.valid_to_date.finish()),
+            ),
+            ("wazny_do", Arc::new(self.valid_to_date.finish())),
+            (
+                "teryt_wojewodztwo",
                 Arc::new(self.voivodeship_teryt_id.finish()),
-                Arc::new(self.voivodeship.finish()),
-                Arc::new(self.county_teryt_id.finish()),
-                Arc::new(self.county.finish()),
-                Arc::new(self.municipality_teryt_id.finish()),
-                Arc::new(self.municipality.finish()),
-                Arc::new(self.city_teryt_id.finish()),
-                Arc::new(self.city.finish()),
-                Arc::new(self.city_part.finish()),
-                Arc::new(self.street_teryt_id.finish()),
-                Arc::new(self.street.finish()),
-                Arc::new(self.house_number.finish()),
-                Arc::new(self.postcode.finish()),
-                Arc::new(self.status.finish()),
-                Arc::new(self.x_epsg_2180.finish()),
-                Arc::new(self.y_epsg_2180.finish()),
-                Arc::new(self.longitude.finish()),
-                Arc::new(self.latitude.finish()),
-            ],
-        )
-        .expect("Failed to create RecordBatch")
+            ),
+            ("wojewodztwo", Arc::new(self.voivodeship.finish())),
+            ("teryt_powiat", Arc::new(self.county_teryt_id.finish())),
+            ("powiat", Arc::new(self.county.finish())),
+            ("teryt_gmina", Arc::new(self.municipality_teryt_id.finish())),
+            ("gmina", Arc::new(self.municipality.finish())),
+            ("teryt_miejscowosc", Arc::new(self.city_teryt_id.finish())),
+            ("miejscowosc", Arc::new(self.city.finish())),
+            ("czesc_miejscowosci", Arc::new(self.city_part.finish())),
+            ("teryt_ulica", Arc::new(self.street_teryt_id.finish())),
+            ("ulica", Arc::new(self.street.finish())),
+            ("numer_porzadkowy", Arc::new(self.house_number.finish())),
+            ("kod_pocztowy", Arc::new(self.postcode.finish())),
+            ("status", Arc::new(self.status.finish())),
+            ("id_budynku", Arc::new(self.building_id.finish())),
+            ("x_epsg_2180", Arc::new(self.x_epsg_2180.finish())),
+            ("y_epsg_2180", Arc::new(self.y_epsg_2180.finish())),
+        ];
+        debug_assert_eq!(
+            check_column_order(&SCHEMA_CSV, named_columns.iter().map(|(name, _)| *name)),
+            Ok(())
+        );
+        let mut columns: Vec<ArrayRef> = named_columns.into_iter().map(|(_, c)| c).collect();
+        if drop_rows {
+            columns = columns
+                .iter()
+                .map(|c| filter(c, &keep))
+                .collect::<Result<_, _>>()?;
+        }
+        if self.normalize_whitespace {
+            for name in WHITESPACE_NORMALIZED_COLUMNS {
+                let index = SCHEMA_CSV.index_of(name)?;
+                let column = columns[index].as_string::<i32>();
+                if column
+                    .iter()
+                    .flatten()
+                    .any(|value| matches!(normalize_whitespace(value), Cow::Owned(_)))
+                {
+                    let normalized: StringArray =
+                        column.iter().map(|v| v.map(normalize_whitespace)).collect();
+                    columns[index] = Arc::new(normalized);
+                }
+            }
+        }
+        let (longitude, latitude) = (self.longitude.finish(), self.latitude.finish());
+        if self.reproject && self.input_crs.proj().is_latlong() {
+            for column in [longitude, latitude] {
+                columns.push(if drop_rows {
+                    filter(&column, &keep)?
+                } else {
+                    Arc::new(column)
+                });
+            }
+        } else if self.reproject {
+            let _timer = timings::start(Phase::Reprojection);
+            let (longitude, latitude) = reproject_to_4326(
+                columns[21].as_primitive::<Float64Type>(),
+                columns[22].as_primitive::<Float64Type>(),
+                columns[SCHEMA_CSV.index_of("lokalny_id")?].as_string::<i32>(),
+            )
+            .map_err(|e| ArrowError::ComputeError(format!("{:#}", e)))?;
+            columns.push(Arc::new(longitude));
+            columns.push(Arc::new(latitude));
+        } else {
+            let num_rows = columns[21].len();
+            columns.push(new_null_array(&DataType::Float64, num_rows));
+            columns.push(new_null_array(&DataType::Float64, num_rows));
+        }
+        let batch = RecordBatch::try_new(SCHEMA_CSV.clone(), columns)?;
+        for warning in check_teryt_id_widths(&batch) {
+            println!("Warning: {}", warning);
+        }
+        Ok(batch)
+    }
+
+    /// Builds the last, possibly partial, batch once the input has ended.
+    /// Returns `None` if no rows are left to output, so that both models end
+    /// their batch sequence the same way regardless of the batch size.
+    pub(crate) fn build_final_record_batch(&mut self) -> Option<Result<RecordBatch, ArrowError>> {
+        match self.build_record_batch() {
+            Ok(record_batch) if record_batch.num_rows() == 0 => None,
+            result => Some(result),
+        }
+    }
+
+    /// Number of records finished with `end_row` since the last batch was built.
+    pub(crate) fn row_count(&self) -> usize {
+        self.keep.len()
     }
 
-    /// Called at the end of each parsed address record: `uuid` is appended for
-    /// every record, so any other column that fell one row behind it gets a
-    /// null, keeping all builders the same length.
-    pub(crate) fn pad_short_columns(&mut self) {
-        let buffer_length = self.uuid.len();
+    /// Called at the end of each parsed address record. Any column that did not
+    /// receive a value for this record gets a null, keeping all builders the
+    /// same length. Records with `keep == false` (e.g. ones that failed to
+    /// parse) are dropped when the batch is built.
+    pub(crate) fn end_row(&mut self, keep: bool) {
+        self.keep.push(keep);
+        self.pad_short_columns();
+    }
+
+    fn pad_short_columns(&mut self) {
+        let buffer_length = self.keep.len();
+        if self.uuid.len() < buffer_length {
+            self.uuid.append_null();
+        }
         if self.id_namespace.len() < buffer_length {
             self.id_namespace.append_null();
         }
@@ -257,11 +1389,8 @@ impl CanonicalBuilders {
         if self.status.len() < buffer_length {
             self.status.append_null();
         }
-        if self.longitude.len() < buffer_length {
-            self.longitude.append_null();
-        }
-        if self.latitude.len() < buffer_length {
-            self.latitude.append_null();
+        if self.building_id.len() < buffer_length {
+            self.building_id.append_null();
         }
         if self.voivodeship_teryt_id.len() < buffer_length {
             self.voivodeship_teryt_id.append_null();
@@ -284,6 +1413,24 @@ impl CanonicalBuilders {
         if self.y_epsg_2180.len() < buffer_length {
             self.y_epsg_2180.append_null();
         }
+        if self.input_crs.proj().is_latlong() {
+            if self.longitude.len() < buffer_length {
+                self.longitude.append_null();
+            }
+            if self.latitude.len() < buffer_length {
+                self.latitude.append_null();
+            }
+        }
+    }
+
+    /// Appends the coordinates of the first `gml:pos` of an address.
+    pub(crate) fn append_point(&mut self, coords: &PointCoords) {
+        self.x_epsg_2180.append_value(coords.x2180);
+        self.y_epsg_2180.append_value(coords.y2180);
+        if let Some((lon, lat)) = coords.lon_lat {
+            self.longitude.append_value(lon);
+            self.latitude.append_value(lat);
+        }
     }
 }
 
@@ -291,13 +1438,390 @@ pub fn get_attribute<'a>(
     event_start: &'a quick_xml::events::BytesStart<'_>,
     attribute: &'a [u8],
 ) -> Cow<'a, str> {
-    event_start
-        .attributes()
-        .find(|a| a.as_ref().expect("Could not parse attribute.").key.as_ref() == attribute)
-        .expect("Could not find attribute.")
-        .expect("Could not parse attribute.")
-        .decode_and_unescape_value(event_start.decoder())
-        .expect("Could not decode attribute value.")
+    try_get_attribute(event_start, attribute).unwrap_or_else(|e| panic!("{:#}", e))
+}
+
+/// Like `get_attribute`, but returns an error instead of panicking when the
+/// attribute is missing or cannot be decoded.
+pub fn try_get_attribute<'a>(
+    event_start: &'a quick_xml::events::BytesStart<'_>,
+    attribute: &'a [u8],
+) -> anyhow::Result<Cow<'a, str>> {
+    for a in event_start.attributes() {
+        let a = a.context("Could not parse attribute.")?;
+        if a.key.as_ref() == attribute {
+            return a
+                .decode_and_unescape_value(event_start.decoder())
+                .context("Could not decode attribute value.");
+        }
+    }
+    anyhow::bail!(
+        "Could not find attribute `{}`.",
+        String::from_utf8_lossy(attribute)
+    )
+}
+
+/// Per-address bookkeeping used by the parsers to decide whether a record is
+/// kept and, if not, what to report about it.
+#[derive(Default)]
+pub(crate) struct RecordState {
+    /// Byte offset of the address start tag in the (decompressed) XML stream.
+    pub(crate) offset: u64,
+    pub(crate) uuid: Option<String>,
+    pub(crate) error: Option<anyhow::Error>,
+    /// `wazny_od_lub_data_nadania` in days since the epoch.
+    pub(crate) valid_since: Option<i32>,
+    /// Whether a street name was set for the address.
+    pub(crate) has_street: bool,
+    /// Whether a `gml:pos` was already read for the address.
+    has_position: bool,
+    /// Whether valid coordinates were read from the `gml:pos` of the address.
+    pub(crate) has_coordinates: bool,
+    /// Recoverable problems; the record is kept and these are printed once it is finished.
+    warnings: Vec<String>,
+}
+
+impl RecordState {
+    pub(crate) fn new(offset: u64) -> Self {
+        Self {
+            offset,
+            ..Default::default()
+        }
+    }
+
+    /// Remember the first error found in the record; later ones are usually a
+    /// consequence of the first and are dropped.
+    pub(crate) fn fail(&mut self, error: anyhow::Error) {
+        if self.error.is_none() {
+            self.error = Some(error);
+        }
+    }
+
+    /// Remember a problem that does not prevent the record from being written.
+    pub(crate) fn warn(&mut self, warning: String) {
+        self.warnings.push(warning);
+    }
+
+    /// Returns `true` for the first `gml:pos` of the record. Later ones are
+    /// ignored with a warning, so the coordinate columns get exactly one value
+    /// per record.
+    pub(crate) fn first_position(&mut self) -> bool {
+        if self.has_position {
+            self.warn("more than one `gml:pos`, only the first one is used.".to_string());
+            return false;
+        }
+        self.has_position = true;
+        true
+    }
+
+    /// Decides at the end of the record whether it goes to the output.
+    /// Records with errors are reported to `errors` and dropped; records
+    /// rejected by `filter` are dropped silently. Warnings are printed for
+    /// records that are kept. Fails only if the error cannot be reported.
+    pub(crate) fn finish(&self, filter: &RecordFilter, errors: &ErrorSink) -> anyhow::Result<bool> {
+        match &self.error {
+            Some(err) => {
+                errors.report(self, err)?;
+                Ok(false)
+            }
+            None => {
+                let keep = filter.accepts(self);
+                if keep {
+                    for warning in &self.warnings {
+                        println!(
+                            "Warning: address at byte offset {} (uuid: {}): {}",
+                            self.offset,
+                            self.uuid.as_deref().unwrap_or("unknown"),
+                            warning
+                        );
+                    }
+                }
+                Ok(keep)
+            }
+        }
+    }
+}
+
+/// Deterministic 64-bit hash (FNV-1a followed by a splitmix64 finalizer) of
+/// `seed` and `value`. Unlike `std::hash::DefaultHasher` it is stable across
+/// Rust releases and platforms, so a sample is reproducible for a given seed.
+pub fn sample_hash(seed: u64, value: &str) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325 ^ seed;
+    for byte in value.bytes() {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash ^= hash >> 30;
+    hash = hash.wrapping_mul(0xbf58476d1ce4e5b9);
+    hash ^= hash >> 27;
+    hash = hash.wrapping_mul(0x94d049bb133111eb);
+    hash ^ (hash >> 31)
+}
+
+/// Filter on whether an address has a street (`ulica`).
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum StreetFilter {
+    #[default]
+    Any,
+    /// Only addresses with a street.
+    WithStreet,
+    /// Only addresses without a street (mostly rural addresses).
+    WithoutStreet,
+}
+
+impl std::fmt::Display for StreetFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            StreetFilter::Any => write!(f, "any"),
+            StreetFilter::WithStreet => write!(f, "with street"),
+            StreetFilter::WithoutStreet => write!(f, "without street"),
+        }
+    }
+}
+
+/// Record-level filters applied by the parsers when an address is finished.
+/// The default keeps every address.
+#[derive(Clone, Default)]
+pub struct RecordFilter {
+    /// Inclusive lower bound on `wazny_od_lub_data_nadania`.
+    pub start_date: Option<NaiveDate>,
+    /// Inclusive upper bound on `wazny_od_lub_data_nadania`.
+    pub end_date: Option<NaiveDate>,
+    /// Fraction of addresses to keep (`0.0..=1.0`), chosen by `sample_hash` of the uuid.
+    pub sample_rate: Option<f64>,
+    /// Seed mixed into `sample_hash`; different seeds select different samples.
+    pub sample_seed: u64,
+    pub street: StreetFilter,
+    /// Drop addresses without coordinates (`gml:pos` missing or not parseable).
+    pub drop_null_geometry: bool,
+    /// Number of addresses dropped by `drop_null_geometry`, shared by all
+    /// clones of the filter.
+    pub null_geometry_dropped: Arc<AtomicUsize>,
+}
+
+impl RecordFilter {
+    fn accepts(&self, record: &RecordState) -> bool {
+        if self.start_date.is_some() || self.end_date.is_some() {
+            let Some(days) = record.valid_since else {
+                return false;
+            };
+            let date = EPOCH_DATE + Duration::days(days as i64);
+            if self.start_date.is_some_and(|start| date < start)
+                || self.end_date.is_some_and(|end| date > end)
+            {
+                return false;
+            }
+        }
+        let street_accepted = match self.street {
+            StreetFilter::Any => true,
+            StreetFilter::WithStreet => record.has_street,
+            StreetFilter::WithoutStreet => !record.has_street,
+        };
+        if !street_accepted {
+            return false;
+        }
+        if let Some(rate) = self.sample_rate {
+            let Some(uuid) = record.uuid.as_deref() else {
+                return false;
+            };
+            if (sample_hash(self.sample_seed, uuid) as f64) >= rate * (u64::MAX as f64) {
+                return false;
+            }
+        }
+        // checked last so that only addresses otherwise kept are counted
+        if self.drop_null_geometry && !record.has_coordinates {
+            self.null_geometry_dropped.fetch_add(1, Ordering::Relaxed);
+            return false;
+        }
+        true
+    }
+
+    /// Number of addresses dropped so far because they had no coordinates.
+    pub fn null_geometry_dropped(&self) -> usize {
+        self.null_geometry_dropped.load(Ordering::Relaxed)
+    }
+}
+
+#[derive(Serialize)]
+struct ErrorRecord<'a> {
+    offset: u64,
+    uuid: Option<&'a str>,
+    message: String,
+}
+
+struct ErrorSinkInner {
+    writer: Option<BufWriter<File>>,
+    count: usize,
+}
+
+/// Collects addresses that were skipped because they could not be parsed.
+/// Cloning is cheap and every clone reports to the same destination, so a
+/// single sink can be shared by all parsers of a run.
+///
+/// Without a file, errors are printed as warnings. With a file, each error is
+/// written as one JSON object per line with `offset`, `uuid` and `message` keys.
+#[derive(Clone)]
+pub struct ErrorSink {
+    inner: Arc<Mutex<ErrorSinkInner>>,
+}
+
+impl Default for ErrorSink {
+    fn default() -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(ErrorSinkInner {
+                writer: None,
+                count: 0,
+            })),
+        }
+    }
+}
+
+impl ErrorSink {
+    pub fn to_file(path: &Path) -> anyhow::Result<Self> {
+        let file = File::create(path).with_context(|| {
+            format!("Could not create errors file `{}`.", path.to_string_lossy())
+        })?;
+        Ok(Self {
+            inner: Arc::new(Mutex::new(ErrorSinkInner {
+                writer: Some(BufWriter::new(file)),
+                count: 0,
+            })),
+        })
+    }
+
+    pub(crate) fn report(&self, record: &RecordState, error: &anyhow::Error) -> anyhow::Result<()> {
+        let mut inner = self.inner.lock().unwrap();
+        inner.count += 1;
+        match inner.writer.as_mut() {
+            None => {
+                println!(
+                    "Warning: skipping address at byte offset {} (uuid: {}): {:#}",
+                    record.offset,
+                    record.uuid.as_deref().unwrap_or("unknown"),
+                    error
+                );
+            }
+            Some(writer) => {
+                let mut line = serde_json::to_string(&ErrorRecord {
+                    offset: record.offset,
+                    uuid: record.uuid.as_deref(),
+                    message: format!("{:#}", error),
+                })
+                .context("Could not serialize error record.")?;
+                line.push('\n');
+                writer
+                    .write_all(line.as_bytes())
+                    .context("Could not write to errors file.")?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Number of addresses reported so far.
+    pub fn count(&self) -> usize {
+        self.inner.lock().unwrap().count
+    }
+
+    pub fn flush(&self) -> anyhow::Result<()> {
+        if let Some(writer) = self.inner.lock().unwrap().writer.as_mut() {
+            writer.flush().context("Could not write errors file.")?;
+        }
+        Ok(())
+    }
+}
+
+/// Counts collected by the parsers, reported by `--validate-only`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ValidationCounts {
+    pub addresses: usize,
+    /// References from addresses to administrative units, cities and streets.
+    pub components: usize,
+    /// References not found in the file's dictionaries, and TERYT codes not
+    /// found in the TERC dictionary (schema 2021).
+    pub unresolved_references: usize,
+    /// Tags inside addresses the parser does not know, by name.
+    pub unknown_tags: BTreeMap<String, usize>,
+}
+
+impl ValidationCounts {
+    pub(crate) fn unknown_tag(&mut self, name: &[u8]) {
+        let name = String::from_utf8_lossy(name);
+        match self.unknown_tags.get_mut(name.as_ref()) {
+            Some(count) => *count += 1,
+            None => {
+                self.unknown_tags.insert(name.into_owned(), 1);
+            }
+        }
+    }
+
+    /// Prints one warning listing the unknown tags counted so far, then
+    /// forgets them.
+    pub(crate) fn warn_about_unknown_tags(&mut self) {
+        if let Some(summary) = unknown_tags_summary(&std::mem::take(&mut self.unknown_tags)) {
+            println!("Warning: {}", summary);
+        }
+    }
+
+    fn merge(&mut self, other: ValidationCounts) {
+        self.addresses += other.addresses;
+        self.components += other.components;
+        self.unresolved_references += other.unresolved_references;
+        for (name, count) in other.unknown_tags {
+            *self.unknown_tags.entry(name).or_default() += count;
+        }
+    }
+}
+
+/// Sums `ValidationCounts` of all parsers of a run. Cloning is cheap and every
+/// clone adds to the same counts. Parsers with a report collect unknown tags
+/// instead of printing them.
+#[derive(Clone, Default)]
+pub struct ValidationReport {
+    inner: Arc<Mutex<ValidationCounts>>,
+}
+
+impl ValidationReport {
+    pub(crate) fn add(&self, counts: ValidationCounts) {
+        self.inner.lock().unwrap().merge(counts);
+    }
+
+    pub fn counts(&self) -> ValidationCounts {
+        self.inner.lock().unwrap().clone()
+    }
+
+    pub(crate) fn warn_about_unknown_tags(&self) {
+        self.inner.lock().unwrap().warn_about_unknown_tags();
+    }
+}
+
+/// `None` without unknown tags, otherwise a message naming them with their
+/// number of occurrences.
+fn unknown_tags_summary(unknown_tags: &BTreeMap<String, usize>) -> Option<String> {
+    if unknown_tags.is_empty() {
+        return None;
+    }
+    let tags: Vec<String> = unknown_tags
+        .iter()
+        .map(|(name, count)| format!("`{}` ({})", name, count))
+        .collect();
+    Some(format!(
+        "ignored unknown tags inside addresses (occurrences): {}.",
+        tags.join(", ")
+    ))
+}
+
+/// Parses an RFC 3339 datetime into milliseconds since the epoch (UTC).
+pub fn parse_datetime_millis(value: &str) -> anyhow::Result<i64> {
+    let dt = chrono::DateTime::parse_from_rfc3339(value)
+        .with_context(|| format!("Failed to parse datetime `{}`", value))?;
+    Ok(dt.to_utc().timestamp() * 1000)
+}
+
+/// Parses a `YYYY-MM-DD` date into days since the epoch (arrow `Date32`).
+pub fn parse_date_days(value: &str) -> anyhow::Result<i32> {
+    let date = NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .with_context(|| format!("Failed to parse date `{}`", value))?;
+    Ok(date.signed_duration_since(EPOCH_DATE).num_days() as i32)
 }
 
 pub fn str_append_value_or_null(builder: &mut StringBuilder, value: &str) {
@@ -320,15 +1844,22 @@ pub fn option_append_value_or_null(builder: &mut StringBuilder, value: Option<St
 }
 
 pub struct PointCoords {
-    pub x4326: f64,
-    pub y4326: f64,
     pub x2180: f64,
     pub y2180: f64,
+    /// Longitude/latitude when the input is geographic, then they are not
+    /// reprojected from the EPSG:2180 coordinates.
+    pub lon_lat: Option<(f64, f64)>,
 }
 
+/// Parse the text of a `gml:pos` element in the CRS `source` into EPSG:2180
+/// coordinates. Reprojection of EPSG:2180 input to EPSG:4326 is deferred to
+/// `reproject_to_4326` so it can run once per batch instead of once per
+/// address; input in another CRS is reprojected to EPSG:2180 here, geographic
+/// input is in degrees and is kept as `PointCoords::lon_lat`.
 pub fn parse_gml_pos(
     text_trimmed: &str,
     coordinate_order: CoordOrder,
+    source: &Proj,
 ) -> anyhow::Result<Option<PointCoords>> {
     let coords: Vec<&str> = text_trimmed.split_whitespace().collect();
     if coords.len() == 2 {
@@ -336,37 +1867,137 @@ pub fn parse_gml_pos(
             CoordOrder::XY => (coords[0], coords[1]),
             CoordOrder::YX => (coords[1], coords[0]),
         };
-        let y2180 = y
+        let y = y
             .parse::<f64>()
-            .with_context(|| format!("Could not parse y2180 out of: `{}`", text_trimmed))?;
-        let x2180 = x
+            .with_context(|| format!("Could not parse y out of: `{}`", text_trimmed))?;
+        let x = x
             .parse::<f64>()
-            .with_context(|| format!("Could not parse x2180 out of: `{}`", text_trimmed))?;
-        if x2180.is_nan() || y2180.is_nan() {
+            .with_context(|| format!("Could not parse x out of: `{}`", text_trimmed))?;
+        if x.is_nan() || y.is_nan() {
             Ok(None)
+        } else if std::ptr::eq(source, &*EPSG_2180) {
+            Ok(Some(PointCoords {
+                x2180: x,
+                y2180: y,
+                lon_lat: None,
+            }))
         } else {
-            let mut p = (x2180.clone(), y2180.clone());
-            proj4rs::transform::transform(&EPSG_2180, &EPSG_4326, &mut p).with_context(|| {
-                format!(
-                    "Failed to transform coordinates `{:?}` from EPSG:2180 to EPSG:4326",
-                    p
-                )
+            let mut point = if source.is_latlong() {
+                (x.to_radians(), y.to_radians())
+            } else {
+                (x, y)
+            };
+            proj4rs::transform::transform(source, &EPSG_2180, &mut point).with_context(|| {
+                format!("Could not transform point `{}` to EPSG:2180", text_trimmed)
             })?;
-            let lon = p.0.to_degrees();
-            let lat = p.1.to_degrees();
             Ok(Some(PointCoords {
-                x4326: lon,
-                y4326: lat,
-                x2180: x2180,
-                y2180: y2180,
+                x2180: point.0,
+                y2180: point.1,
+                lon_lat: source.is_latlong().then_some((x, y)),
             }))
         }
+    } else {
+        anyhow::bail!("Could not parse coordinates in gml:pos: `{}`", text_trimmed);
+    }
+}
+
+/// Checks the `srsName` of a `gml:Point`, e.g. `urn:ogc:def:crs:EPSG::2180`
+/// or `EPSG:2180`. Coordinates are always read in `input_crs`, so points in
+/// any other CRS are rejected instead of being written with wrong coordinates.
+pub(crate) fn check_srs_name(srs_name: &str, input_crs: CRS) -> anyhow::Result<()> {
+    let code = srs_name.rsplit([':', '/']).next().unwrap_or_default();
+    if srs_name.to_uppercase().contains("EPSG") && code == input_crs.epsg_code().to_string() {
+        Ok(())
     } else {
         anyhow::bail!(
-            "Warning: could not parse coordinates in gml:pos: `{}`.",
-            text_trimmed
-        );
+            "Point is in an unsupported CRS `{}`, only {} is supported.",
+            srs_name,
+            input_crs
+        )
+    }
+}
+
+/// Transform EPSG:2180 `(x, y)` pairs in place into EPSG:4326 `(lon, lat)`
+/// degrees using a single `proj4rs` call over the whole slice.
+pub fn transform_2180_to_4326(points: &mut [(f64, f64)]) -> Result<(), PrgError> {
+    proj4rs::transform::transform(&EPSG_2180, &EPSG_4326, points)
+        .with_context(|| {
+            format!(
+                "Failed to transform {} coordinates from EPSG:2180 to EPSG:4326",
+                points.len()
+            )
+        })
+        .map_err(PrgError::Reprojection)?;
+    for p in points.iter_mut() {
+        *p = (p.0.to_degrees(), p.1.to_degrees());
+    }
+    Ok(())
+}
+
+/// Transforms `points` like `transform_2180_to_4326`. If the single call
+/// over the whole slice fails (`proj4rs` stops at the first point it cannot
+/// transform), the points are retried one by one and the ones that still fail
+/// are `None`, with a warning naming the address of `ids` at the same index.
+fn transform_points_or_null(points: &[(f64, f64)], ids: &[&str]) -> Vec<Option<(f64, f64)>> {
+    let mut transformed = points.to_vec();
+    if transform_2180_to_4326(&mut transformed).is_ok() {
+        return transformed.into_iter().map(Some).collect();
+    }
+    points
+        .iter()
+        .zip(ids)
+        .map(|(&(x, y), id)| {
+            let mut point = (x, y);
+            match transform_2180_to_4326(std::slice::from_mut(&mut point)) {
+                Ok(()) => Some(point),
+                Err(e) => {
+                    println!(
+                        "Warning: could not transform point `{} {}` of address `{}` to EPSG:4326, its longitude/latitude will be null: {:#}",
+                        x, y, id, e
+                    );
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+/// Build longitude/latitude columns from the EPSG:2180 columns of a batch.
+/// Rows with a null input coordinate, or one that cannot be transformed
+/// (with a warning naming its `lokalny_id` from `ids`), are null in the output.
+pub(crate) fn reproject_to_4326(
+    xs: &Float64Array,
+    ys: &Float64Array,
+    ids: &StringArray,
+) -> anyhow::Result<(Float64Array, Float64Array)> {
+    let (points, point_ids): (Vec<(f64, f64)>, Vec<&str>) = xs
+        .iter()
+        .zip(ys.iter())
+        .zip(ids.iter())
+        .filter_map(|((x, y), id)| Some(((x?, y?), id.unwrap_or_default())))
+        .unzip();
+    let mut transformed = transform_points_or_null(&points, &point_ids).into_iter();
+    let mut longitude = Float64Builder::with_capacity(xs.len());
+    let mut latitude = Float64Builder::with_capacity(xs.len());
+    for (x, y) in xs.iter().zip(ys.iter()) {
+        // one transformed point was produced for each non-null input pair, in order
+        let point = if x.is_some() && y.is_some() {
+            transformed.next().expect("Reprojected point missing.")
+        } else {
+            None
+        };
+        match point {
+            Some((lon, lat)) => {
+                longitude.append_value(lon);
+                latitude.append_value(lat);
+            }
+            None => {
+                longitude.append_null();
+                latitude.append_null();
+            }
+        }
     }
+    Ok((longitude.finish(), latitude.finish()))
 }
 
 /// Interpret a naive datetime as `Europe/Warsaw` wall-clock time and return the
@@ -473,50 +2104,928 @@ fn test_get_attribute_returns_value() {
     }
 }
 
+#[test]
+fn test_try_get_attribute_missing_returns_error() {
+    let xml = r#"<root attr="hello"/>"#;
+    let mut reader = quick_xml::Reader::from_str(xml);
+    reader.config_mut().expand_empty_elements = true;
+    let mut buf = Vec::new();
+    loop {
+        if let quick_xml::events::Event::Start(e) = reader.read_event_into(&mut buf).unwrap() {
+            assert_eq!(try_get_attribute(&e, b"attr").unwrap(), Cow::from("hello"));
+            let err = try_get_attribute(&e, b"xlink:href").unwrap_err();
+            assert!(err.to_string().contains("xlink:href"));
+            break;
+        }
+    }
+}
+
+#[test]
+fn test_check_srs_name() {
+    for srs_name in [
+        "urn:ogc:def:crs:EPSG::2180",
+        "EPSG:2180",
+        "http://www.opengis.net/def/crs/EPSG/0/2180",
+        "urn:ogc:def:crs:EPSG:6.9:2180",
+    ] {
+        assert!(
+            check_srs_name(srs_name, CRS::Epsg2180).is_ok(),
+            "{}",
+            srs_name
+        );
+    }
+    for srs_name in ["urn:ogc:def:crs:EPSG::4326", "EPSG:21800", "2180", ""] {
+        assert!(
+            check_srs_name(srs_name, CRS::Epsg2180).is_err(),
+            "{}",
+            srs_name
+        );
+    }
+    assert!(check_srs_name("urn:ogc:def:crs:EPSG::4326", CRS::Epsg4326).is_ok());
+    assert!(check_srs_name("EPSG:2180", CRS::Epsg4326).is_err());
+}
+
 #[test]
 fn test_parse_gml_pos_empty() {
     let gml_pos = "";
-    let coords = parse_gml_pos(gml_pos, CoordOrder::XY);
+    let coords = parse_gml_pos(gml_pos, CoordOrder::XY, &EPSG_2180);
     assert!(coords.is_err());
 }
 
 #[test]
 fn test_parse_gml_pos_1() {
     let gml_pos = "0.0";
-    let coords = parse_gml_pos(gml_pos, CoordOrder::XY);
+    let coords = parse_gml_pos(gml_pos, CoordOrder::XY, &EPSG_2180);
     assert!(coords.is_err());
 }
 
 #[test]
 fn test_parse_gml_pos_3() {
     let gml_pos = "0.0 1.1 2.2";
-    let coords = parse_gml_pos(gml_pos, CoordOrder::XY);
+    let coords = parse_gml_pos(gml_pos, CoordOrder::XY, &EPSG_2180);
     assert!(coords.is_err());
 }
 
 #[test]
 fn test_parse_gml_pos_nan() {
     let gml_pos = "NaN NaN";
-    let coords = parse_gml_pos(gml_pos, CoordOrder::XY).expect("NaN should have been parsed.");
+    let coords =
+        parse_gml_pos(gml_pos, CoordOrder::XY, &EPSG_2180).expect("NaN should have been parsed.");
     assert!(coords.is_none());
 }
 
 #[test]
 fn test_parse_gml_pos_xy() {
     let gml_pos = "216691.39 505645.69";
-    let coords = parse_gml_pos(gml_pos, CoordOrder::XY).unwrap().unwrap();
+    let coords = parse_gml_pos(gml_pos, CoordOrder::XY, &EPSG_2180)
+        .unwrap()
+        .unwrap();
     assert!((coords.x2180 - 216691.39).abs() <= 0.01);
     assert!((coords.y2180 - 505645.69).abs() <= 0.01);
-    assert!((coords.x4326 - 14.8391033).abs() <= 0.000001);
-    assert!((coords.y4326 - 52.343422).abs() <= 0.000001);
 }
 
 #[test]
 fn test_parse_gml_pos_yx() {
     let gml_pos = "505645.69 216691.39";
-    let coords = parse_gml_pos(gml_pos, CoordOrder::YX).unwrap().unwrap();
+    let coords = parse_gml_pos(gml_pos, CoordOrder::YX, &EPSG_2180)
+        .unwrap()
+        .unwrap();
     assert!((coords.x2180 - 216691.39).abs() <= 0.01);
     assert!((coords.y2180 - 505645.69).abs() <= 0.01);
-    assert!((coords.x4326 - 14.8391033).abs() <= 0.000001);
-    assert!((coords.y4326 - 52.343422).abs() <= 0.000001);
+    assert_eq!(coords.lon_lat, None);
+}
+
+#[test]
+fn test_parse_gml_pos_4326() {
+    // the point of `test_parse_gml_pos_xy` as longitude/latitude
+    let gml_pos = "14.8391033 52.343422";
+    let coords = parse_gml_pos(gml_pos, CoordOrder::XY, &EPSG_4326)
+        .unwrap()
+        .unwrap();
+    assert_eq!(coords.lon_lat, Some((14.8391033, 52.343422)));
+    assert!((coords.x2180 - 216691.39).abs() <= 0.1);
+    assert!((coords.y2180 - 505645.69).abs() <= 0.1);
+    let coords = parse_gml_pos("52.343422 14.8391033", CoordOrder::YX, &EPSG_4326)
+        .unwrap()
+        .unwrap();
+    assert_eq!(coords.lon_lat, Some((14.8391033, 52.343422)));
+}
+
+#[test]
+fn test_transform_2180_to_4326() {
+    let mut points = [(216691.39, 505645.69), (287772.37, 456005.140000001)];
+    transform_2180_to_4326(&mut points).unwrap();
+    assert!((points[0].0 - 14.8391033).abs() <= 0.000001);
+    assert!((points[0].1 - 52.343422).abs() <= 0.000001);
+    assert!((points[1].0 - 15.9121240).abs() <= 0.000001);
+    assert!((points[1].1 - 51.9297753).abs() <= 0.000001);
+    assert!(matches!(
+        transform_2180_to_4326(&mut [(1e10, 1e10)]),
+        Err(PrgError::Reprojection(_))
+    ));
+}
+
+#[test]
+fn test_reproject_to_4326_keeps_nulls_aligned() {
+    let xs = Float64Array::from(vec![Some(216691.39), None, Some(287772.37)]);
+    let ys = Float64Array::from(vec![Some(505645.69), None, Some(456005.140000001)]);
+    let ids = StringArray::from(vec!["a", "b", "c"]);
+    let (lon, lat) = reproject_to_4326(&xs, &ys, &ids).unwrap();
+    use arrow::array::Array;
+    assert_eq!(lon.null_count(), 1);
+    assert!(lat.is_null(1));
+    assert!((lon.value(0) - 14.8391033).abs() <= 0.000001);
+    assert!((lat.value(2) - 51.9297753).abs() <= 0.000001);
+}
+
+#[test]
+fn test_reproject_to_4326_failed_point_is_null() {
+    // proj4rs cannot compute the inverse projection of the second point
+    let xs = Float64Array::from(vec![Some(216691.39), Some(1e10), Some(287772.37)]);
+    let ys = Float64Array::from(vec![Some(505645.69), Some(1e10), Some(456005.140000001)]);
+    let ids = StringArray::from(vec!["a", "b", "c"]);
+    let (lon, lat) = reproject_to_4326(&xs, &ys, &ids).unwrap();
+    use arrow::array::Array;
+    assert_eq!(lon.null_count(), 1);
+    assert!(lon.is_null(1));
+    assert!(lat.is_null(1));
+    assert!((lon.value(0) - 14.8391033).abs() <= 0.000001);
+    assert!((lat.value(2) - 51.9297753).abs() <= 0.000001);
+}
+
+#[test]
+fn test_check_teryt_id_widths() {
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("teryt_wojewodztwo", DataType::Utf8, true),
+        Field::new("teryt_gmina", DataType::Utf8, true),
+    ]));
+    let batch = RecordBatch::try_new(
+        schema,
+        vec![
+            Arc::new(StringArray::from(vec![Some("08"), None, Some("02")])),
+            Arc::new(StringArray::from(vec![
+                Some("804032"),
+                Some("0804032"),
+                Some("08O4032"),
+            ])),
+        ],
+    )
+    .unwrap();
+    let warnings = check_teryt_id_widths(&batch);
+    assert_eq!(warnings.len(), 1);
+    assert!(warnings[0].starts_with("2 value(s) in column `teryt_gmina`"));
+    assert!(warnings[0].contains("`804032`"));
+}
+
+#[test]
+fn test_record_filter_date_window() {
+    let day = |y, m, d| {
+        NaiveDate::from_ymd_opt(y, m, d)
+            .unwrap()
+            .signed_duration_since(EPOCH_DATE)
+            .num_days() as i32
+    };
+    let record = |valid_since| RecordState {
+        valid_since,
+        ..Default::default()
+    };
+    let no_filter = RecordFilter::default();
+    assert!(no_filter.accepts(&record(None)));
+
+    let filter = RecordFilter {
+        start_date: NaiveDate::from_ymd_opt(2020, 1, 1),
+        end_date: NaiveDate::from_ymd_opt(2020, 12, 31),
+        ..Default::default()
+    };
+    assert!(filter.accepts(&record(Some(day(2020, 1, 1)))));
+    assert!(filter.accepts(&record(Some(day(2020, 12, 31)))));
+    assert!(!filter.accepts(&record(Some(day(2019, 12, 31)))));
+    assert!(!filter.accepts(&record(Some(day(2021, 1, 1)))));
+    assert!(!filter.accepts(&record(None)));
+}
+
+#[test]
+fn test_normalize_name() {
+    assert_eq!(normalize_name("Świętokrzyska"), "swietokrzyska");
+    assert_eq!(normalize_name("  Plac   Kasztanowy "), "plac kasztanowy");
+    assert_eq!(normalize_name("ŁÓDŹ Żółć"), "lodz zolc");
+}
+
+#[test]
+fn test_title_case() {
+    assert_eq!(title_case("Góra Kalwaria"), "Góra Kalwaria");
+    assert_eq!(title_case("GÓRA KALWARIA"), "Góra Kalwaria");
+    assert_eq!(title_case("góra kalwaria"), "Góra Kalwaria");
+    assert_eq!(title_case("ŁÓDŹ"), "Łódź");
+    assert_eq!(title_case("dolnośląskie"), "Dolnośląskie");
+    assert_eq!(title_case("KĘDZIERZYN-KOŹLE"), "Kędzierzyn-Koźle");
+    assert_eq!(title_case("KOSTRZYN NAD ODRĄ"), "Kostrzyn nad Odrą");
+    assert_eq!(
+        title_case("Nowe Miasto Nad Pilicą"),
+        "Nowe Miasto nad Pilicą"
+    );
+    // only words after the first are kept lowercase
+    assert_eq!(title_case("nad wisłą"), "Nad Wisłą");
+    assert_eq!(title_case(""), "");
+}
+
+#[test]
+fn test_set_name_case() {
+    let names = ["dolnośląskie", "bolesławiecki", "Góra Kalwaria", "ŻAGAŃ"];
+    let schema = Arc::new(Schema::new(
+        NAME_CASE_COLUMNS
+            .iter()
+            .map(|name| Field::new(*name, DataType::Utf8, false))
+            .collect::<Vec<_>>(),
+    ));
+    let batch = RecordBatch::try_new(
+        schema,
+        names
+            .iter()
+            .map(|name| Arc::new(StringArray::from(vec![*name])) as ArrayRef)
+            .collect(),
+    )
+    .unwrap();
+    let values = |batch: &RecordBatch| -> Vec<String> {
+        batch
+            .columns()
+            .iter()
+            .map(|c| c.as_string::<i32>().value(0).to_string())
+            .collect()
+    };
+    assert_eq!(
+        values(&set_name_case(&batch, NameCase::Preserve).unwrap()),
+        names
+    );
+    assert_eq!(
+        values(&set_name_case(&batch, NameCase::Lower).unwrap()),
+        ["dolnośląskie", "bolesławiecki", "góra kalwaria", "żagań"]
+    );
+    assert_eq!(
+        values(&set_name_case(&batch, NameCase::Title).unwrap()),
+        ["Dolnośląskie", "Bolesławiecki", "Góra Kalwaria", "Żagań"]
+    );
+}
+
+#[test]
+fn test_normalize_whitespace() {
+    assert!(matches!(
+        normalize_whitespace("Plac Kasztanowy"),
+        Cow::Borrowed("Plac Kasztanowy")
+    ));
+    assert!(matches!(normalize_whitespace(""), Cow::Borrowed("")));
+    assert_eq!(normalize_whitespace("Plac  Kasztanowy"), "Plac Kasztanowy");
+    assert_eq!(
+        normalize_whitespace("Plac\u{a0}Kasztanowy"),
+        "Plac Kasztanowy"
+    );
+    assert_eq!(normalize_whitespace(" 12 \u{a0}A\t"), "12 A");
+    assert_eq!(normalize_whitespace("\u{a0}"), "");
+}
+
+#[test]
+fn test_split_house_number() {
+    assert_eq!(split_house_number("12"), (Some(12), None));
+    assert_eq!(split_house_number("007"), (Some(7), None));
+    assert_eq!(split_house_number("12A"), (Some(12), Some("A")));
+    assert_eq!(split_house_number("15/3"), (Some(15), Some("/3")));
+    assert_eq!(split_house_number("7 bis"), (Some(7), Some("bis")));
+    assert_eq!(split_house_number("12-14"), (Some(12), Some("-14")));
+    assert_eq!(split_house_number(" 3 "), (Some(3), None));
+    assert_eq!(split_house_number("bn"), (None, Some("bn")));
+    assert_eq!(
+        split_house_number("99999999999"),
+        (None, Some("99999999999"))
+    );
+    assert_eq!(split_house_number(""), (None, None));
+    assert_eq!(split_house_number("  "), (None, None));
+}
+
+#[test]
+fn test_append_house_number_columns() {
+    let schema = Arc::new(Schema::new(vec![Field::new(
+        "numer_porzadkowy",
+        DataType::Utf8,
+        true,
+    )]));
+    let batch = RecordBatch::try_new(
+        schema,
+        vec![Arc::new(StringArray::from(vec![
+            Some("12A"),
+            None,
+            Some("5"),
+        ]))],
+    )
+    .unwrap();
+    let batch = append_house_number_columns(&batch).unwrap();
+    assert_eq!(batch.num_columns(), 3);
+    assert_eq!(batch.schema().field(1), &HOUSE_NUMBER_FIELDS[0]);
+    let integers = batch
+        .column(1)
+        .as_primitive::<arrow::datatypes::Int32Type>();
+    assert_eq!(
+        integers.iter().collect::<Vec<_>>(),
+        [Some(12), None, Some(5)]
+    );
+    let suffixes = batch.column(2).as_string::<i32>();
+    assert_eq!(suffixes.iter().collect::<Vec<_>>(), [Some("A"), None, None]);
+}
+
+#[test]
+fn test_append_valid_from_columns() {
+    use arrow::array::Date32Array;
+
+    let schema = Arc::new(Schema::new(vec![Field::new(
+        "wazny_od_lub_data_nadania",
+        DataType::Date32,
+        true,
+    )]));
+    let batch = RecordBatch::try_new(
+        schema,
+        vec![Arc::new(Date32Array::from(vec![Some(19244), None]))],
+    )
+    .unwrap();
+    for (schema_version, filled) in [(SchemaVersion::Model2012, 1), (SchemaVersion::Model2021, 2)] {
+        let appended = append_valid_from_columns(&batch, schema_version).unwrap();
+        assert_eq!(appended.num_columns(), 3);
+        assert_eq!(appended.schema().field(1), &VALID_FROM_FIELDS[0]);
+        assert_eq!(appended.schema().field(2), &VALID_FROM_FIELDS[1]);
+        assert_eq!(appended.column(filled), batch.column(0));
+        assert_eq!(appended.column(3 - filled).null_count(), 2);
+    }
+}
+
+#[test]
+fn test_append_source_column() {
+    let schema = Arc::new(Schema::new(vec![Field::new(
+        "miejscowosc",
+        DataType::Utf8,
+        false,
+    )]));
+    let batch = RecordBatch::try_new(
+        schema,
+        vec![Arc::new(StringArray::from(vec!["Kielce", "Żubrów"]))],
+    )
+    .unwrap();
+    let batch = append_source_column(&batch, "a.zip/b.xml").unwrap();
+    assert_eq!(batch.schema().field(1), &*SOURCE_FIELD);
+    let source = batch.column_by_name("zrodlo").unwrap().as_string::<i32>();
+    assert_eq!(source.value(0), "a.zip/b.xml");
+    assert_eq!(source.value(1), "a.zip/b.xml");
+}
+
+#[test]
+fn test_full_address() {
+    assert_eq!(
+        full_address(
+            "Rzepin",
+            Some("Inwalidów Wojennych"),
+            Some("1A"),
+            Some("69-110")
+        ),
+        "Rzepin, Inwalidów Wojennych 1A, 69-110"
+    );
+    assert_eq!(
+        full_address("Żubrów", None, Some("21A"), Some("69-200")),
+        "Żubrów 21A, 69-200"
+    );
+    assert_eq!(
+        full_address("Konotop", Some("Podgórna"), None, None),
+        "Konotop, Podgórna"
+    );
+    assert_eq!(
+        full_address("Konotop", Some(""), Some("2"), Some("")),
+        "Konotop 2"
+    );
+}
+
+#[test]
+fn test_append_address_column() {
+    let path = std::path::PathBuf::from("fixtures/sample_model2012.xml");
+    let mut parser = crate::get_address_parser_2012_uncompressed(&path, &10).unwrap();
+    let batch = append_address_column(&parser.next().unwrap().unwrap()).unwrap();
+    assert_eq!(
+        batch.schema().fields().last().unwrap().as_ref(),
+        &*ADDRESS_FIELD
+    );
+    let addresses = batch.column_by_name("adres").unwrap().as_string::<i32>();
+    assert_eq!(addresses.value(0), "Konotop, Podgórna 2, 67-416");
+    assert_eq!(addresses.value(1), "Konotop, Podgórna 1, 67-416");
+}
+
+#[test]
+fn test_full_postal_address() {
+    let units = (Some("Kolsko"), Some("nowosolski"), Some("lubuskie"));
+    let address = |street, number, postcode, city| {
+        full_postal_address(street, number, postcode, city, units.0, units.1, units.2)
+    };
+    // urban
+    assert_eq!(
+        address(Some("Podgórna"), Some("12A"), Some("67-415"), "Konotop"),
+        "ul. Podgórna 12A, 67-415 Konotop, gmina Kolsko, powiat nowosolski, woj. lubuskie"
+    );
+    assert_eq!(
+        address(Some("plac Wolności"), Some("1"), Some("67-415"), "Konotop"),
+        "plac Wolności 1, 67-415 Konotop, gmina Kolsko, powiat nowosolski, woj. lubuskie"
+    );
+    // rural
+    assert_eq!(
+        address(None, Some("21A"), Some("67-415"), "Żubrów"),
+        "Żubrów 21A, 67-415 Żubrów, gmina Kolsko, powiat nowosolski, woj. lubuskie"
+    );
+    // missing postcode
+    assert_eq!(
+        address(Some("Podgórna"), Some("2"), None, "Konotop"),
+        "ul. Podgórna 2, Konotop, gmina Kolsko, powiat nowosolski, woj. lubuskie"
+    );
+    assert_eq!(
+        address(Some(""), Some("2"), Some(""), "Konotop"),
+        "Konotop 2, gmina Kolsko, powiat nowosolski, woj. lubuskie"
+    );
+    // missing number and administrative units
+    assert_eq!(
+        full_postal_address(
+            Some("Podgórna"),
+            None,
+            None,
+            "Konotop",
+            None,
+            Some(""),
+            None
+        ),
+        "ul. Podgórna, Konotop"
+    );
+}
+
+#[test]
+fn test_append_full_address_column() {
+    let path = std::path::PathBuf::from("fixtures/sample_model2012.xml");
+    let mut parser = crate::get_address_parser_2012_uncompressed(&path, &10).unwrap();
+    let batch = append_full_address_column(&parser.next().unwrap().unwrap()).unwrap();
+    assert_eq!(
+        batch.schema().fields().last().unwrap().as_ref(),
+        &*FULL_ADDRESS_FIELD
+    );
+    let addresses = batch
+        .column_by_name("pelny_adres")
+        .unwrap()
+        .as_string::<i32>();
+    assert_eq!(
+        addresses.value(0),
+        "ul. Podgórna 2, 67-416 Konotop, gmina Kolsko, powiat nowosolski, woj. lubuskie"
+    );
+}
+
+#[test]
+fn test_grid_cell_id() {
+    assert_eq!(grid_cell_id(287772.37, 456005.14, 100), "287700_456000");
+    assert_eq!(grid_cell_id(287772.37, 456005.14, 1000), "287000_456000");
+    assert_eq!(grid_cell_id(500000.0, 300000.0, 1000), "500000_300000");
+    assert_eq!(grid_cell_id(500999.99, 300099.99, 100), "500900_300000");
+}
+
+#[test]
+fn test_append_grid_column() {
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("x_epsg_2180", DataType::Float64, true),
+        Field::new("y_epsg_2180", DataType::Float64, true),
+    ]));
+    let batch = RecordBatch::try_new(
+        schema,
+        vec![
+            Arc::new(Float64Array::from(vec![Some(216691.39), None])),
+            Arc::new(Float64Array::from(vec![Some(505645.69), None])),
+        ],
+    )
+    .unwrap();
+    let batch = append_grid_column(&batch, 1000).unwrap();
+    use arrow::array::Array;
+    assert_eq!(batch.schema().field(2), &*GRID_FIELD);
+    let grid = batch.column_by_name("grid_id").unwrap().as_string::<i32>();
+    assert_eq!(grid.value(0), "216000_505000");
+    assert!(grid.is_null(1));
+}
+
+#[test]
+fn test_hilbert_index() {
+    let (min, max) = HILBERT_EXTENT_2180;
+    // the curve starts in the lower left corner and ends in the lower right one
+    assert_eq!(hilbert_index(min, min), 0);
+    assert_eq!(hilbert_index(max, min), u32::MAX);
+    assert_eq!(hilbert_index(min - 1000.0, min - 1000.0), 0);
+    // the first quarter of the curve is the lower left quadrant
+    let middle = (min + max) / 2.0;
+    assert!(hilbert_index(middle - 1.0, middle - 1.0) < 1 << 30);
+    assert!(hilbert_index(middle + 1.0, middle + 1.0) >= 1 << 30);
+}
+
+#[test]
+fn test_sort_by_hilbert_index() {
+    use arrow::array::Array;
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("x_epsg_2180", DataType::Float64, true),
+        Field::new("y_epsg_2180", DataType::Float64, true),
+    ]));
+    let batch = |xs: Vec<Option<f64>>, ys: Vec<Option<f64>>| {
+        RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Float64Array::from(xs)),
+                Arc::new(Float64Array::from(ys)),
+            ],
+        )
+        .unwrap()
+    };
+    let batches = [
+        batch(
+            vec![Some(700000.0), None, Some(216691.39)],
+            vec![Some(300000.0), None, Some(505645.69)],
+        ),
+        batch(
+            vec![Some(216000.0), Some(450000.0), Some(810000.0)],
+            vec![Some(505000.0), Some(650000.0), Some(180000.0)],
+        ),
+    ];
+    let sorted = sort_by_hilbert_index(&schema, &batches).unwrap();
+    assert_eq!(sorted.num_rows(), 6);
+    let xs = sorted.column(0).as_primitive::<Float64Type>();
+    let ys = sorted.column(1).as_primitive::<Float64Type>();
+    let indices: Vec<u32> = (0..5)
+        .map(|i| hilbert_index(xs.value(i), ys.value(i)))
+        .collect();
+    assert!(indices.is_sorted(), "{:?}", indices);
+    // the two close points are next to each other, nulls are last
+    assert_eq!(xs.value(0).floor(), 216000.0);
+    assert_eq!(xs.value(1).floor(), 216691.0);
+    assert!(xs.is_null(5));
+}
+
+#[test]
+fn test_append_normalized_name_columns() {
+    use arrow::array::Array;
+
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("miejscowosc", DataType::Utf8, false),
+        Field::new("ulica", DataType::Utf8, true),
+    ]));
+    let batch = RecordBatch::try_new(
+        schema,
+        vec![
+            Arc::new(StringArray::from(vec!["Kielce", "Żubrów"])),
+            Arc::new(StringArray::from(vec![Some("Świętokrzyska"), None])),
+        ],
+    )
+    .unwrap();
+    let batch = append_normalized_name_columns(&batch).unwrap();
+    assert_eq!(batch.num_columns(), 4);
+    let city = batch
+        .column_by_name("miejscowosc_norm")
+        .unwrap()
+        .as_string::<i32>();
+    assert_eq!(city.value(1), "zubrow");
+    let street = batch
+        .column_by_name("ulica_norm")
+        .unwrap()
+        .as_string::<i32>();
+    assert_eq!(street.value(0), "swietokrzyska");
+    assert!(street.is_null(1));
+    assert_eq!(
+        batch
+            .column_by_name("ulica")
+            .unwrap()
+            .as_string::<i32>()
+            .value(0),
+        "Świętokrzyska"
+    );
+}
+
+#[test]
+fn test_format_floats_with_decimal_comma() {
+    use arrow::array::Array;
+
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("ulica", DataType::Utf8, true),
+        Field::new("x_epsg_2180", DataType::Float64, true),
+    ]));
+    let batch = RecordBatch::try_new(
+        schema,
+        vec![
+            Arc::new(StringArray::from(vec![Some("1.5"), None, Some("a")])),
+            Arc::new(Float64Array::from(vec![Some(12.345), None, Some(600000.0)])),
+        ],
+    )
+    .unwrap();
+    let batch = format_floats_with_decimal_comma(&batch).unwrap();
+    let x = batch
+        .column_by_name("x_epsg_2180")
+        .unwrap()
+        .as_string::<i32>();
+    assert_eq!(x.value(0), "12,345");
+    assert!(x.is_null(1));
+    assert_eq!(x.value(2), "600000,0");
+    // other columns are not changed
+    let street = batch.column_by_name("ulica").unwrap().as_string::<i32>();
+    assert_eq!(street.value(0), "1.5");
+}
+
+#[test]
+fn test_tail_buffer() {
+    let batch = |values: Vec<i32>| {
+        let schema = Arc::new(Schema::new(vec![Field::new("n", DataType::Int32, false)]));
+        RecordBatch::try_new(
+            schema,
+            vec![Arc::new(arrow::array::Int32Array::from(values)) as ArrayRef],
+        )
+        .unwrap()
+    };
+    let values = |tail: TailBuffer| -> Vec<i32> {
+        tail.into_batches()
+            .iter()
+            .flat_map(|b| {
+                b.column(0)
+                    .as_primitive::<arrow::datatypes::Int32Type>()
+                    .values()
+                    .to_vec()
+            })
+            .collect()
+    };
+
+    let mut tail = TailBuffer::new(3);
+    tail.push(batch(vec![1, 2]));
+    assert_eq!(tail.num_rows(), 2);
+    tail.push(batch(vec![3, 4]));
+    tail.push(batch(vec![]));
+    assert_eq!(tail.num_rows(), 3);
+    assert_eq!(values(tail), vec![2, 3, 4]);
+
+    let mut tail = TailBuffer::new(2);
+    tail.push(batch(vec![1, 2, 3]));
+    tail.push(batch(vec![4, 5, 6, 7]));
+    assert_eq!(values(tail), vec![6, 7]);
+}
+
+#[test]
+fn test_record_filter_sample_rate() {
+    let record = |uuid: &str| RecordState {
+        uuid: Some(uuid.to_string()),
+        ..Default::default()
+    };
+    let uuids: Vec<String> = (0..10_000).map(|i| format!("uuid-{}", i)).collect();
+    let sample = |rate, seed| {
+        let filter = RecordFilter {
+            sample_rate: Some(rate),
+            sample_seed: seed,
+            ..Default::default()
+        };
+        uuids
+            .iter()
+            .filter(|uuid| filter.accepts(&record(uuid)))
+            .cloned()
+            .collect::<Vec<_>>()
+    };
+    let one_percent = sample(0.01, 42);
+    assert!((50..150).contains(&one_percent.len()));
+    assert_eq!(one_percent, sample(0.01, 42));
+    assert_ne!(one_percent, sample(0.01, 7));
+    assert_eq!(sample(1.0, 42).len(), uuids.len());
+    assert!(sample(0.0, 42).is_empty());
+    // addresses without uuid cannot be sampled deterministically
+    let filter = RecordFilter {
+        sample_rate: Some(1.0),
+        ..Default::default()
+    };
+    assert!(!filter.accepts(&RecordState::default()));
+}
+
+#[test]
+fn test_record_filter_street() {
+    let record = |has_street| RecordState {
+        has_street,
+        ..Default::default()
+    };
+    let filter = |street| RecordFilter {
+        street,
+        ..Default::default()
+    };
+    assert!(filter(StreetFilter::Any).accepts(&record(true)));
+    assert!(filter(StreetFilter::Any).accepts(&record(false)));
+    assert!(filter(StreetFilter::WithStreet).accepts(&record(true)));
+    assert!(!filter(StreetFilter::WithStreet).accepts(&record(false)));
+    assert!(!filter(StreetFilter::WithoutStreet).accepts(&record(true)));
+    assert!(filter(StreetFilter::WithoutStreet).accepts(&record(false)));
+}
+
+#[test]
+fn test_record_filter_null_geometry() {
+    let record = |has_coordinates| RecordState {
+        has_coordinates,
+        ..Default::default()
+    };
+    assert!(RecordFilter::default().accepts(&record(false)));
+    let filter = RecordFilter {
+        drop_null_geometry: true,
+        ..Default::default()
+    };
+    assert!(filter.accepts(&record(true)));
+    assert!(!filter.clone().accepts(&record(false)));
+    assert!(!filter.accepts(&record(false)));
+    assert_eq!(filter.null_geometry_dropped(), 2);
+    // addresses rejected by another filter are not counted
+    let filter = RecordFilter {
+        drop_null_geometry: true,
+        street: StreetFilter::WithStreet,
+        ..Default::default()
+    };
+    assert!(!filter.accepts(&record(false)));
+    assert_eq!(filter.null_geometry_dropped(), 0);
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_error_sink_write_error() {
+    // writes to `/dev/full` fail once the buffer is flushed
+    let errors = ErrorSink::to_file(Path::new("/dev/full")).unwrap();
+    let mut record = RecordState::new(0);
+    record.fail(anyhow::anyhow!("invalid address"));
+    let result: anyhow::Result<Vec<bool>> = (0..10_000)
+        .map(|_| record.finish(&RecordFilter::default(), &errors))
+        .collect();
+    let err = result.unwrap_err();
+    assert!(err.to_string().contains("errors file"), "{}", err);
+}
+
+#[test]
+fn test_rename_fields() {
+    let renames = |pairs: &[(&str, &str)]| -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(old, new)| (old.to_string(), new.to_string()))
+            .collect()
+    };
+    let schema = rename_fields(
+        &SCHEMA_CSV,
+        &renames(&[("przestrzen_nazw", "namespace"), ("ulica", "street")]),
+    )
+    .unwrap();
+    assert_eq!(schema.fields().len(), SCHEMA_CSV.fields().len());
+    assert_eq!(schema.field(0).name(), "namespace");
+    assert_eq!(schema.field(0).data_type(), SCHEMA_CSV.field(0).data_type());
+    assert!(schema.field_with_name("street").is_ok());
+    assert!(schema.field_with_name("ulica").is_err());
+
+    let err = rename_fields(&SCHEMA_CSV, &renames(&[("street", "ulica")])).unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("Unknown column(s) to rename: street.")
+    );
+    assert!(rename_fields(&SCHEMA_CSV, &renames(&[("ulica", "miejscowosc")])).is_err());
+}
+
+#[test]
+fn test_missing_projections() {
+    assert!(missing_projections(&[2180, 4326, 4326]).is_empty());
+    let missing = missing_projections(&[2180, 1, 1]);
+    assert_eq!(missing.len(), 1);
+    assert!(missing[0].contains("EPSG:1"));
+}
+
+#[test]
+fn test_crs_statics_are_initialized_once() {
+    // a `const` LazyLock would be inlined and re-initialized at every use site
+    assert!(std::ptr::eq(&*EPSG_2180, &*EPSG_2180));
+    assert!(std::ptr::eq(&*EPSG_4326, &*EPSG_4326));
+    #[cfg(feature = "geoparquet")]
+    {
+        assert!(std::ptr::eq(&*CRS_2180, &*CRS_2180));
+        assert!(std::ptr::eq(&*CRS_4326, &*CRS_4326));
+    }
+}
+
+#[cfg(feature = "geoparquet")]
+#[test]
+fn test_geoparquet_schema_for() {
+    assert_eq!(point_type_for(&CRS::Epsg2180).metadata().crs(), &*CRS_2180);
+    assert_eq!(point_type_for(&CRS::Epsg4326).metadata().crs(), &*CRS_4326);
+    let schema = geoparquet_schema_for(&CRS::Epsg4326);
+    let geometry = schema.field_with_name("geometry").unwrap();
+    assert_eq!(geometry.extension_type_name(), Some("geoarrow.point"));
+    assert_eq!(
+        geometry.data_type(),
+        point_type_for(&CRS::Epsg4326)
+            .to_field("geometry", true)
+            .data_type()
+    );
+}
+
+#[test]
+fn test_check_column_order() {
+    let names = ["przestrzen_nazw", "lokalny_id", "wersja_id"];
+    assert_eq!(check_column_order(&SCHEMA_CSV, names), Ok(()));
+    let swapped = ["lokalny_id", "przestrzen_nazw"];
+    assert_eq!(
+        check_column_order(&SCHEMA_CSV, swapped),
+        Err("column 0 is `lokalny_id` but the schema has `przestrzen_nazw` there".to_string())
+    );
+    let too_many = SCHEMA_CSV
+        .fields()
+        .iter()
+        .map(|f| f.name().as_str())
+        .chain(["extra"]);
+    assert!(check_column_order(&SCHEMA_CSV, too_many).is_err());
+}
+
+/// Asserts that every column of row `row` of `batch`, in schema order, has
+/// the name and displayed value of the matching entry of `expected`. Empty
+/// strings stand for nulls.
+#[cfg(test)]
+pub(crate) fn assert_row_values(batch: &RecordBatch, row: usize, expected: &[(&str, &str)]) {
+    use arrow::util::display::array_value_to_string;
+
+    let schema = batch.schema();
+    let names: Vec<&str> = schema.fields().iter().map(|f| f.name().as_str()).collect();
+    let expected_names: Vec<&str> = expected.iter().map(|(name, _)| *name).collect();
+    assert_eq!(names, expected_names);
+    for (column, (name, value)) in batch.columns().iter().zip(expected) {
+        assert_eq!(
+            array_value_to_string(column, row).unwrap(),
+            *value,
+            "column `{}`",
+            name
+        );
+    }
+}
+
+#[test]
+fn test_unknown_tags_summary() {
+    let mut counts = ValidationCounts::default();
+    assert_eq!(unknown_tags_summary(&counts.unknown_tags), None);
+    counts.unknown_tag(b"prg-ad:nowy");
+    counts.unknown_tag(b"prg-ad:inny");
+    counts.unknown_tag(b"prg-ad:nowy");
+    assert_eq!(
+        unknown_tags_summary(&counts.unknown_tags).unwrap(),
+        "ignored unknown tags inside addresses (occurrences): `prg-ad:inny` (1), `prg-ad:nowy` (2)."
+    );
+    counts.warn_about_unknown_tags();
+    assert!(counts.unknown_tags.is_empty());
+}
+
+#[test]
+fn test_set_timestamp_timezone() {
+    use arrow::array::TimestampMillisecondArray;
+    use arrow::datatypes::TimestampMillisecondType;
+
+    let schema = Arc::new(Schema::new(vec![
+        Field::new(
+            "wersja_id",
+            DataType::Timestamp(TimeUnit::Millisecond, Some(Arc::from("UTC"))),
+            false,
+        ),
+        Field::new("lokalny_id", DataType::Utf8, false),
+    ]));
+    let batch = RecordBatch::try_new(
+        schema,
+        vec![
+            Arc::new(TimestampMillisecondArray::from(vec![1_662_740_296_000]).with_timezone("UTC")),
+            Arc::new(StringArray::from(vec!["a"])),
+        ],
+    )
+    .unwrap();
+    let warsaw = set_timestamp_timezone(&batch, "Europe/Warsaw").unwrap();
+    let expected_type =
+        DataType::Timestamp(TimeUnit::Millisecond, Some(Arc::from("Europe/Warsaw")));
+    assert_eq!(warsaw.schema().field(0).data_type(), &expected_type);
+    assert_eq!(warsaw.column(0).data_type(), &expected_type);
+    assert_eq!(
+        warsaw
+            .column(0)
+            .as_primitive::<TimestampMillisecondType>()
+            .value(0),
+        1_662_740_296_000
+    );
+    assert_eq!(warsaw.column(1), batch.column(1));
+}
+
+#[test]
+fn test_round_coordinates() {
+    let path = std::path::PathBuf::from("fixtures/sample_model2012.xml");
+    let mut parser = crate::get_address_parser_2012_uncompressed(&path, &10).unwrap();
+    let batch = parser.next().unwrap().unwrap();
+    let float = |batch: &RecordBatch, name: &str| {
+        batch
+            .column_by_name(name)
+            .unwrap()
+            .as_primitive::<Float64Type>()
+            .value(0)
+    };
+    assert_eq!(float(&batch, "dlugosc_geograficzna"), 15.9121240698886);
+
+    let rounded = round_coordinates(&batch, 6).unwrap();
+    assert_eq!(float(&rounded, "dlugosc_geograficzna"), 15.912124);
+    assert_eq!(float(&rounded, "szerokosc_geograficzna"), 51.929775);
+    let rounded = round_coordinates(&batch, 2).unwrap();
+    assert_eq!(float(&rounded, "dlugosc_geograficzna"), 15.91);
+    assert_eq!(float(&rounded, "szerokosc_geograficzna"), 51.93);
+    // EPSG:2180 coordinates are kept
+    assert_eq!(float(&rounded, "x_epsg_2180"), float(&batch, "x_epsg_2180"));
 }
@@ -0,0 +1,320 @@
+//! Differences between two snapshots of addresses (`--compare`). Addresses are
+//! matched by `lokalny_id` and compared on the columns describing them, so a
+//! new version of an address with the same values is not a change.
+
+use std::collections::HashMap;
+use std::sync::{Arc, LazyLock};
+
+use arrow::array::{ArrayRef, AsArray, RecordBatch, StringArray, UInt32Array};
+use arrow::compute::take_record_batch;
+use arrow::datatypes::{DataType, Field, Schema};
+use arrow::error::ArrowError;
+use arrow::row::{RowConverter, Rows, SortField};
+
+use crate::common::SCHEMA_CSV;
+
+/// Column appended to the differences: `added`, `removed` or `modified`.
+pub static CHANGE_FIELD: LazyLock<Field> =
+    LazyLock::new(|| Field::new("zmiana", DataType::Utf8, false));
+
+/// Columns that are not compared: identifiers and version metadata.
+const IGNORED_COLUMNS: [&str; 4] = [
+    "przestrzen_nazw",
+    "lokalny_id",
+    "wersja_id",
+    "poczatek_wersji_obiektu",
+];
+
+/// Number of addresses by kind of change.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ChangeCounts {
+    pub added: usize,
+    pub removed: usize,
+    pub modified: usize,
+    pub unchanged: usize,
+}
+
+/// Addresses of the old snapshot, indexed by `lokalny_id`. Addresses of the
+/// new snapshot are passed to `diff`; the old ones never passed are removed.
+pub struct Snapshot {
+    batches: Vec<RecordBatch>,
+    /// Compared columns of `batches`, in the row format.
+    rows: Vec<Rows>,
+    converter: RowConverter,
+    /// Batch and row of every `lokalny_id`.
+    index: HashMap<String, (usize, usize)>,
+    /// Rows of `batches` whose `lokalny_id` was found in the new snapshot.
+    /// Rows shadowed by a later address with the same `lokalny_id` are
+    /// marked from the start, so they are never removed.
+    seen: Vec<Vec<bool>>,
+    counts: ChangeCounts,
+}
+
+/// Fields of the canonical columns that are compared.
+fn compared_fields() -> Vec<&'static Field> {
+    SCHEMA_CSV
+        .fields()
+        .iter()
+        .map(|f| f.as_ref())
+        .filter(|f| !IGNORED_COLUMNS.contains(&f.name().as_str()))
+        .collect()
+}
+
+fn compared_columns(batch: &RecordBatch) -> Result<Vec<ArrayRef>, ArrowError> {
+    compared_fields()
+        .iter()
+        .map(|f| {
+            batch.column_by_name(f.name()).cloned().ok_or_else(|| {
+                ArrowError::SchemaError(format!("Batch is missing column `{}`.", f.name()))
+            })
+        })
+        .collect()
+}
+
+fn ids(batch: &RecordBatch) -> Result<&StringArray, ArrowError> {
+    batch
+        .column_by_name("lokalny_id")
+        .map(|c| c.as_string::<i32>())
+        .ok_or_else(|| ArrowError::SchemaError("Batch is missing column `lokalny_id`.".into()))
+}
+
+/// Returns the rows `indices` of `batch` with `CHANGE_FIELD` appended.
+fn take_with_change(
+    batch: &RecordBatch,
+    indices: Vec<u32>,
+    changes: Vec<&str>,
+) -> Result<RecordBatch, ArrowError> {
+    let taken = take_record_batch(batch, &UInt32Array::from(indices))?;
+    let mut fields = taken.schema().fields().to_vec();
+    fields.push(Arc::new(CHANGE_FIELD.clone()));
+    let mut columns = taken.columns().to_vec();
+    columns.push(Arc::new(StringArray::from(changes)));
+    RecordBatch::try_new(Arc::new(Schema::new(fields)), columns)
+}
+
+impl Snapshot {
+    /// Indexes the addresses of the old snapshot. When a `lokalny_id` occurs
+    /// more than once, the last address is compared, the earlier ones are
+    /// neither compared nor removed, and a warning is printed.
+    pub fn new(batches: Vec<RecordBatch>) -> Result<Self, ArrowError> {
+        let converter = RowConverter::new(
+            compared_fields()
+                .iter()
+                .map(|f| SortField::new(f.data_type().clone()))
+                .collect(),
+        )?;
+        let mut rows = Vec::with_capacity(batches.len());
+        let mut index = HashMap::new();
+        let mut seen: Vec<Vec<bool>> = batches.iter().map(|b| vec![false; b.num_rows()]).collect();
+        let mut duplicates = 0;
+        for (batch_index, batch) in batches.iter().enumerate() {
+            rows.push(converter.convert_columns(&compared_columns(batch)?)?);
+            for (row_index, id) in ids(batch)?.iter().enumerate() {
+                let id = id.unwrap_or_default().to_string();
+                if let Some((old_batch, old_row)) = index.insert(id, (batch_index, row_index)) {
+                    seen[old_batch][old_row] = true;
+                    duplicates += 1;
+                }
+            }
+        }
+        if duplicates > 0 {
+            println!(
+                "Warning: {} addresses of the compared snapshot have a `lokalny_id` of an earlier address, only the last one is compared.",
+                duplicates
+            );
+        }
+        Ok(Self {
+            batches,
+            rows,
+            converter,
+            index,
+            seen,
+            counts: ChangeCounts::default(),
+        })
+    }
+
+    /// Returns the addresses of `batch` (new snapshot) that are added or
+    /// modified, with `CHANGE_FIELD` appended.
+    pub fn diff(&mut self, batch: &RecordBatch) -> Result<RecordBatch, ArrowError> {
+        let rows = self.converter.convert_columns(&compared_columns(batch)?)?;
+        let mut indices = Vec::new();
+        let mut changes = Vec::new();
+        for (row_index, id) in ids(batch)?.iter().enumerate() {
+            let change = match self.index.get(id.unwrap_or_default()) {
+                None => {
+                    self.counts.added += 1;
+                    "added"
+                }
+                Some(&(old_batch, old_row)) => {
+                    self.seen[old_batch][old_row] = true;
+                    if self.rows[old_batch].row(old_row) == rows.row(row_index) {
+                        self.counts.unchanged += 1;
+                        continue;
+                    }
+                    self.counts.modified += 1;
+                    "modified"
+                }
+            };
+            indices.push(row_index as u32);
+            changes.push(change);
+        }
+        take_with_change(batch, indices, changes)
+    }
+
+    /// Addresses of the old snapshot not found by `diff`, with `CHANGE_FIELD`
+    /// appended.
+    pub fn removed(&self) -> Result<Vec<RecordBatch>, ArrowError> {
+        let mut removed = Vec::new();
+        for (batch, seen) in self.batches.iter().zip(&self.seen) {
+            let indices: Vec<u32> = (0..batch.num_rows() as u32)
+                .filter(|&i| !seen[i as usize])
+                .collect();
+            if !indices.is_empty() {
+                let changes = vec!["removed"; indices.len()];
+                removed.push(take_with_change(batch, indices, changes)?);
+            }
+        }
+        Ok(removed)
+    }
+
+    /// Counts of the addresses passed to `diff` so far; addresses of the old
+    /// snapshot not passed yet are counted as removed.
+    pub fn counts(&self) -> ChangeCounts {
+        ChangeCounts {
+            removed: self.seen.iter().flatten().filter(|seen| !**seen).count(),
+            ..self.counts.clone()
+        }
+    }
+}
+
+/// The two addresses of the 2012 sample.
+#[cfg(test)]
+fn sample_batch() -> RecordBatch {
+    let path = std::path::PathBuf::from("fixtures/sample_model2012.xml");
+    let mut parser = crate::get_address_parser_2012_uncompressed(&path, &10).unwrap();
+    parser.next().unwrap().unwrap()
+}
+
+/// `batch` with every `numer_porzadkowy` replaced by `number`.
+#[cfg(test)]
+fn with_number(batch: &RecordBatch, number: &str) -> RecordBatch {
+    let index = batch.schema().index_of("numer_porzadkowy").unwrap();
+    let mut columns = batch.columns().to_vec();
+    columns[index] = Arc::new(StringArray::from(vec![number; batch.num_rows()]));
+    RecordBatch::try_new(batch.schema(), columns).unwrap()
+}
+
+#[test]
+fn test_snapshot_diff() {
+    let old = sample_batch();
+    let mut snapshot = Snapshot::new(vec![old.clone()]).unwrap();
+    // first address unchanged, second modified
+    assert_eq!(snapshot.diff(&old.slice(0, 1)).unwrap().num_rows(), 0);
+    let modified = snapshot.diff(&with_number(&old.slice(1, 1), "99")).unwrap();
+    assert_eq!(modified.num_rows(), 1);
+    assert_eq!(modified.schema().fields().last().unwrap().name(), "zmiana");
+    assert_eq!(
+        modified
+            .column_by_name("zmiana")
+            .unwrap()
+            .as_string::<i32>()
+            .value(0),
+        "modified"
+    );
+    assert_eq!(
+        modified
+            .column_by_name("numer_porzadkowy")
+            .unwrap()
+            .as_string::<i32>()
+            .value(0),
+        "99"
+    );
+    assert!(snapshot.removed().unwrap().is_empty());
+    assert_eq!(
+        snapshot.counts(),
+        ChangeCounts {
+            added: 0,
+            removed: 0,
+            modified: 1,
+            unchanged: 1
+        }
+    );
+}
+
+#[test]
+fn test_snapshot_added_and_removed() {
+    let batch = sample_batch();
+    let mut snapshot = Snapshot::new(vec![batch.slice(0, 1)]).unwrap();
+    let added = snapshot.diff(&batch.slice(1, 1)).unwrap();
+    assert_eq!(
+        added
+            .column_by_name("zmiana")
+            .unwrap()
+            .as_string::<i32>()
+            .value(0),
+        "added"
+    );
+    let removed = snapshot.removed().unwrap();
+    assert_eq!(removed.len(), 1);
+    assert_eq!(removed[0].num_rows(), 1);
+    assert_eq!(
+        removed[0].column_by_name("lokalny_id").unwrap(),
+        batch.slice(0, 1).column_by_name("lokalny_id").unwrap()
+    );
+    assert_eq!(
+        removed[0]
+            .column_by_name("zmiana")
+            .unwrap()
+            .as_string::<i32>()
+            .value(0),
+        "removed"
+    );
+    assert_eq!(snapshot.counts().added, 1);
+    assert_eq!(snapshot.counts().removed, 1);
+}
+
+#[test]
+fn test_snapshot_duplicated_id() {
+    let batch = sample_batch();
+    let first = batch.slice(0, 1);
+    // the old snapshot has the first address twice, the second time modified
+    let mut snapshot = Snapshot::new(vec![
+        with_number(&first, "99"),
+        first.clone(),
+        batch.slice(1, 1),
+    ])
+    .unwrap();
+    assert_eq!(snapshot.diff(&batch).unwrap().num_rows(), 0);
+    assert!(snapshot.removed().unwrap().is_empty());
+    assert_eq!(
+        snapshot.counts(),
+        ChangeCounts {
+            added: 0,
+            removed: 0,
+            modified: 0,
+            unchanged: 2
+        }
+    );
+    // only the last of the duplicates is removed when the id is gone
+    let mut snapshot = Snapshot::new(vec![
+        with_number(&first, "99"),
+        first.clone(),
+        batch.slice(1, 1),
+    ])
+    .unwrap();
+    assert_eq!(snapshot.diff(&batch.slice(1, 1)).unwrap().num_rows(), 0);
+    let removed = snapshot.removed().unwrap();
+    assert_eq!(removed.len(), 1);
+    assert_eq!(removed[0].num_rows(), 1);
+    assert_eq!(
+        removed[0]
+            .column_by_name("numer_porzadkowy")
+            .unwrap()
+            .as_string::<i32>(),
+        first
+            .column_by_name("numer_porzadkowy")
+            .unwrap()
+            .as_string::<i32>()
+    );
+    assert_eq!(snapshot.counts().removed, 1);
+}
@@ -0,0 +1,3241 @@
+//! Whole conversion of input files to one output file, as done by the command
+//! line program, for use without going through the command line arguments.
+
+#[cfg(feature = "geoparquet")]
+use std::collections::HashSet;
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{SyncSender, sync_channel};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use anyhow::Context;
+#[cfg(feature = "geoparquet")]
+use arrow::array::{Array, ArrayRef, Float64Array};
+use arrow::array::{AsArray, RecordBatch, RecordBatchReader};
+use arrow::csv::writer::WriterBuilder;
+#[cfg(feature = "geoparquet")]
+use arrow::datatypes::DataType;
+use arrow::datatypes::{Schema, SchemaRef};
+#[cfg(feature = "geoparquet")]
+use geoarrow::array::{GeoArrowArray, PointBuilder};
+#[cfg(feature = "geoparquet")]
+use geoarrow::datatypes::PointType;
+#[cfg(feature = "geoparquet")]
+use geoparquet::writer::{
+    GeoParquetRecordBatchEncoder, GeoParquetWriterEncoding, GeoParquetWriterOptionsBuilder,
+};
+#[cfg(feature = "geoparquet")]
+use parquet::arrow::arrow_writer::ArrowWriter;
+#[cfg(feature = "geoparquet")]
+use parquet::basic::Compression;
+#[cfg(feature = "geoparquet")]
+use parquet::file::metadata::KeyValue;
+#[cfg(feature = "geoparquet")]
+use parquet::file::properties::{WriterProperties, WriterVersion};
+#[cfg(feature = "geoparquet")]
+use parquet::schema::types::ColumnPath;
+use tempfile::TempPath;
+use zip::ZipArchive;
+
+#[cfg(feature = "geoparquet")]
+use crate::ParquetCompression;
+use crate::checkpoint::{Checkpoint, Checkpointer};
+use crate::common::{
+    ErrorSink, FULL_ADDRESS_FIELD, GRID_FIELD, HOUSE_NUMBER_FIELDS, NORMALIZED_NAME_FIELDS,
+    NameCase, RecordFilter, SCHEMA_CSV, SOURCE_FIELD, TailBuffer, UntrimmedColumns,
+    VALID_FROM_FIELDS, ValidationCounts, ValidationReport, append_full_address_column,
+    append_grid_column, append_house_number_columns, append_normalized_name_columns,
+    append_source_column, append_valid_from_columns, format_floats_with_decimal_comma,
+    rename_columns, rename_fields, round_coordinates, sample_hash, set_name_case,
+    set_timestamp_timezone, sort_by_hilbert_index, with_timestamp_timezone,
+};
+#[cfg(feature = "geoparquet")]
+use crate::common::{
+    append_address_column, get_geoparquet_schema, get_points_only_geoparquet_schema, point_type_for,
+};
+use crate::compare::{CHANGE_FIELD, ChangeCounts, Snapshot};
+use crate::gml::GmlWriter;
+use crate::progress;
+#[cfg(feature = "protobuf")]
+use crate::protobuf::ProtobufWriter;
+use crate::split_zip;
+use crate::terc::Terc;
+use crate::timings::{self, Phase};
+use crate::{
+    CRS, FileType, JoinAudit, OutputFormat, PrgError, SchemaVersion, StreetTypes,
+    TimestampTimezone, get_address_parser_2021_mmap, get_address_parser_2021_parallel,
+    get_address_parser_2021_uncompressed, get_address_parser_2021_zip, get_teryt_mapping, map_file,
+    parallel, parallel_parser_2012, parser_2012_from_bytes, parser_2012_uncompressed,
+    parser_2012_zip,
+};
+
+pub const DEFAULT_BATCH_SIZE: usize = 100_000;
+/// Threads per input file: one parsing, one encoding and writing the output.
+pub const DEFAULT_WORKERS_PER_FILE: usize = 2;
+/// Batches parsed ahead of the writer thread, on top of the one it is writing.
+const WRITER_QUEUE_BATCHES: usize = 1;
+/// Decimals of longitude and latitude written by the CLI, about 0.1 m.
+pub const DEFAULT_COORD_PRECISION: u32 = 6;
+/// strftime pattern of `Date32` columns in CSV output.
+pub const DEFAULT_CSV_DATE_FORMAT: &str = "%Y-%m-%d";
+/// strftime pattern of timestamp columns (in UTC) in CSV output, RFC 3339
+/// with fractional seconds only when they are not zero.
+pub const DEFAULT_CSV_TIMESTAMP_FORMAT: &str = "%Y-%m-%dT%H:%M:%S%.fZ";
+/// strftime pattern of timestamp columns in another timezone than UTC in CSV
+/// output, RFC 3339 with the UTC offset.
+pub const DEFAULT_CSV_LOCAL_TIMESTAMP_FORMAT: &str = "%Y-%m-%dT%H:%M:%S%.f%:z";
+
+/// Default strftime pattern of timestamp columns in CSV output for `timezone`.
+pub fn default_csv_timestamp_format(timezone: TimestampTimezone) -> &'static str {
+    match timezone {
+        TimestampTimezone::Utc => DEFAULT_CSV_TIMESTAMP_FORMAT,
+        TimestampTimezone::Warsaw => DEFAULT_CSV_LOCAL_TIMESTAMP_FORMAT,
+    }
+}
+
+#[derive(Clone)]
+pub struct CompressedFile {
+    pub index: usize,
+    pub name: String,
+    pub compressed_size: u64,
+    pub uncompressed_size: u64,
+    pub to_be_parsed: bool,
+}
+
+/// File inside a ZIP archive that was skipped because it could not be read.
+#[derive(Clone, Debug)]
+pub struct SkippedEntry {
+    pub path: PathBuf,
+    pub entry: String,
+    pub error: String,
+}
+
+#[derive(Clone)]
+pub struct FileRecord {
+    pub file_type: FileType,
+    pub path: PathBuf,
+    pub size_in_bytes: u64,
+    pub compressed_files: Option<Vec<CompressedFile>>, // only for FileType::ZIP
+    pub decompressed_size: Option<u128>,               // only for FileType::ZIP
+    /// Entries that could not be listed, only with `skip_bad_entries`.
+    pub skipped_entries: Vec<SkippedEntry>,
+    /// Volumes of a split ZIP archive joined into one temporary archive,
+    /// removed when the last clone of the record is dropped.
+    joined_archive: Option<Arc<TempPath>>,
+}
+
+impl FileRecord {
+    /// Reads the type (from the extension) and size of an input file. For ZIP
+    /// archives the entries are listed and the ones with the extension used by
+    /// `schema_version` (`.xml` for 2012, `.gml` for 2021) are marked to be parsed.
+    /// All marked entries are parsed, in the order they are stored in the archive.
+    /// With `skip_bad_entries` entries that cannot be read are skipped with a
+    /// warning instead of returning an error.
+    pub fn from_path(
+        path: PathBuf,
+        schema_version: &SchemaVersion,
+        skip_bad_entries: bool,
+    ) -> anyhow::Result<Self> {
+        let file_metadata = std::fs::metadata(&path)
+            .with_context(|| format!("could not get metadata for file `{}`", &path.display()))?;
+        if file_metadata.is_dir() {
+            anyhow::bail!(
+                "input path `{}` is a directory, expected a file",
+                &path.display()
+            );
+        }
+        let file_type = match path
+            .extension()
+            .expect("Could not read file extension.")
+            .to_string_lossy()
+            .to_lowercase()
+            .as_str()
+        {
+            "zip" => FileType::ZIP,
+            "xml" | "gml" => FileType::XML,
+            _ => {
+                anyhow::bail!("File extension not one of: zip, xml, gml.")
+            }
+        };
+        let mut compressed_files = None;
+        let mut decompressed_size = None;
+        let mut skipped_entries = Vec::new();
+        let mut size_in_bytes = file_metadata.len();
+        let mut joined_archive = None;
+        if let FileType::ZIP = file_type {
+            joined_archive = split_zip::join_split_archive(&path)?.map(Arc::new);
+            let read_path = joined_archive.as_deref().map_or(path.as_path(), |p| p);
+            size_in_bytes = std::fs::metadata(read_path)?.len();
+            let mut cf: Vec<CompressedFile> = Vec::new();
+            let f = File::open(read_path)
+                .with_context(|| format!("Failed to open ZIP file: `{}`.", &path.display()))?;
+            let mut archive = ZipArchive::new(f).with_context(|| {
+                format!("Failed to decompress ZIP file: `{}`.", &path.display())
+            })?;
+            decompressed_size = archive.decompressed_size();
+            for idx in 0..archive.len() {
+                let name_hint = archive.name_for_index(idx).unwrap_or("?").to_string();
+                let entry = match archive.by_index(idx) {
+                    Ok(entry) => entry,
+                    Err(err) if skip_bad_entries => {
+                        println!(
+                            "Warning: skipping file `{}` inside ZIP archive `{}`: {}.",
+                            name_hint,
+                            path.display(),
+                            err
+                        );
+                        skipped_entries.push(SkippedEntry {
+                            path: path.clone(),
+                            entry: name_hint,
+                            error: err.to_string(),
+                        });
+                        continue;
+                    }
+                    Err(err) => {
+                        return Err(err).context("Could not access file inside ZIP archive");
+                    }
+                };
+                let name = entry
+                    .enclosed_name()
+                    .with_context(|| "Could not read file name inside ZIP archive.")?;
+                // for now we'll determine if the file inside zip should be processed based on extension
+                let file_extension = &name
+                    .extension()
+                    .expect("Could not read file extension.")
+                    .to_string_lossy()
+                    .to_lowercase();
+                let to_be_parsed = match schema_version {
+                    SchemaVersion::Model2012 => file_extension == "xml",
+                    SchemaVersion::Model2021 => file_extension == "gml",
+                };
+                cf.push(CompressedFile {
+                    index: idx,
+                    name: name.to_string_lossy().to_string(),
+                    compressed_size: entry.compressed_size(),
+                    uncompressed_size: entry.size(),
+                    to_be_parsed,
+                });
+            }
+            compressed_files = Some(cf);
+        }
+        Ok(FileRecord {
+            file_type,
+            path,
+            size_in_bytes,
+            compressed_files,
+            decompressed_size,
+            skipped_entries,
+            joined_archive,
+        })
+    }
+
+    /// Path of the file to read: `path`, or the joined archive if `path` is
+    /// the last volume of a split ZIP archive.
+    pub fn read_path(&self) -> &Path {
+        self.joined_archive.as_deref().map_or(&self.path, |p| p)
+    }
+}
+
+/// Settings of `convert`, the library counterpart of the command line arguments.
+#[derive(Clone)]
+pub struct ConvertOptions {
+    pub inputs: Vec<FileRecord>,
+    pub output_path: PathBuf,
+    pub schema_version: SchemaVersion,
+    pub output_format: OutputFormat,
+    pub crs: CRS,
+    /// TERC catalog file, or directory of them of which the newest is used
+    /// (schema 2021 only, unless `download_teryt` is set).
+    pub teryt_path: Option<PathBuf>,
+    /// Required date (`YYYY-MM-DD`) of the TERC catalog of `teryt_path`.
+    pub teryt_date: Option<String>,
+    pub download_teryt: bool,
+    pub teryt_api_username: Option<String>,
+    pub teryt_api_password: Option<String>,
+    pub batch_size: usize,
+    #[cfg(feature = "geoparquet")]
+    pub parquet_compression: Compression,
+    pub parquet_row_group_size: usize,
+    /// Size in bytes after which a parquet data page is closed (default of
+    /// the parquet writer if `None`).
+    pub parquet_data_page_size: Option<usize>,
+    /// Size in bytes of the parquet dictionary page after which a column
+    /// falls back to plain encoding (default of the parquet writer if `None`).
+    pub parquet_dictionary_page_size: Option<usize>,
+    #[cfg(feature = "geoparquet")]
+    pub parquet_version: WriterVersion,
+    #[cfg(feature = "geoparquet")]
+    pub geometry_encoding: GeoParquetWriterEncoding,
+    /// Write only `lokalny_id`, `adres` (the address as a single string) and
+    /// `geometry` to GeoParquet, see `get_points_only_geoparquet_schema`.
+    /// Derived columns are not written.
+    #[cfg(feature = "geoparquet")]
+    pub points_only_schema: bool,
+    /// Close the parquet row group after this many rows.
+    pub flush_every: Option<usize>,
+    /// Close the parquet row group whenever `teryt_wojewodztwo` changes, so
+    /// that row groups hold one voivodeship each when the input is grouped
+    /// by voivodeship.
+    pub row_group_per_voivodeship: bool,
+    /// String columns of parquet outputs written with a bloom filter, by
+    /// output name; `uuid` stands for `lokalny_id`.
+    pub parquet_bloom_filter_columns: Vec<String>,
+    pub csv_delimiter: u8,
+    /// Format floats with a decimal comma in CSV output.
+    pub decimal_comma: bool,
+    /// Text written for nulls in CSV output; empty strings are written as
+    /// empty cells either way.
+    pub csv_null_string: String,
+    /// strftime pattern of date columns in CSV output.
+    pub csv_date_format: String,
+    /// strftime pattern of timestamp columns in CSV output, see
+    /// `default_csv_timestamp_format` when changing `timestamp_timezone`.
+    pub csv_timestamp_format: String,
+    /// Timezone attached to the timestamp columns.
+    pub timestamp_timezone: TimestampTimezone,
+    /// Write addresses that could not be parsed to this file as JSON lines
+    /// instead of printing them as warnings.
+    pub errors_to: Option<PathBuf>,
+    /// Stop with an error once more than this many addresses could not be
+    /// parsed. Without a limit all of them are skipped.
+    pub max_errors: Option<usize>,
+    pub filter: RecordFilter,
+    /// Write only the last N addresses.
+    pub tail: Option<usize>,
+    /// Write the addresses sorted by the Hilbert curve index of their
+    /// EPSG:2180 coordinates (see `hilbert_index`), so that close addresses
+    /// are close in the output. All addresses are kept in memory until the
+    /// end, or at most `spatial_sort_buffer` of them.
+    pub spatial_sort: bool,
+    /// Sort and write the addresses in runs of this many rows (rounded up to
+    /// whole batches) instead of all at once, bounding memory use at the cost
+    /// of sorting only within every run.
+    pub spatial_sort_buffer: Option<usize>,
+    /// Prefix of `prg-ad:komponent` links stripped before the `gml:id` (schema
+    /// 2012 only), see `AddressParser2012::with_id_prefix`.
+    pub id_prefix: Option<String>,
+    /// Write `prg-ad:status` as is instead of its canonical value (schema 2012 only).
+    pub status_raw: bool,
+    /// Do not build the dictionaries of components, so names are only read
+    /// from the addresses and the TERYT id columns are null (schema 2012 only).
+    pub skip_dictionary: bool,
+    /// CRS of the input coordinates (`--epsg-input`), see `parse_gml_pos`.
+    pub input_crs: CRS,
+    /// Street types written in street names instead of the default full
+    /// names (schema 2021 only).
+    pub street_types: Option<Arc<StreetTypes>>,
+    /// Print unknown tags inside addresses after every batch instead of once
+    /// at the end of every file.
+    pub verbose_unknown_tags: bool,
+    /// Stop with an error at the first unknown tag inside an address.
+    pub fail_on_unknown_tag: bool,
+    /// Collapse whitespace in names and other text columns.
+    pub normalize_whitespace: bool,
+    /// Text columns written without trimming leading and trailing whitespace.
+    pub untrimmed_columns: UntrimmedColumns,
+    /// Case of the administrative unit and city names.
+    pub name_case: NameCase,
+    /// Round longitude and latitude (and GeoParquet geometry in EPSG:4326)
+    /// to this many decimals, see `round_coordinates`; full precision if
+    /// `None`.
+    pub coord_precision: Option<u32>,
+    /// Old to new output column names.
+    pub column_renames: HashMap<String, String>,
+    pub normalize_names: bool,
+    /// Add columns `numer` and `numer_suffix` split from `numer_porzadkowy`.
+    pub split_house_number: bool,
+    /// Add columns `wazny_od` and `data_nadania` telling which source field
+    /// `wazny_od_lub_data_nadania` was read from.
+    pub split_valid_from: bool,
+    /// Add column `pelny_adres` with the address as a single line, see
+    /// `common::full_postal_address`.
+    pub add_full_address: bool,
+    /// Add column `zrodlo` with the name of the input file (and of the file
+    /// inside the ZIP archive) of every address.
+    pub add_source_column: bool,
+    /// Add column `grid_id` with the cell of a grid of this many meters in
+    /// EPSG:2180 of every address. Must be greater than 0.
+    pub grid_size: Option<u32>,
+    pub parallel_parse: bool,
+    pub mmap: bool,
+    /// With 2 (default), batches are encoded, compressed and written on a
+    /// separate thread, overlapping with parsing; with 1 on the parse thread.
+    pub workers_per_file: usize,
+    /// Write the output to numbered files of at most this many addresses,
+    /// see `rollover_path`.
+    pub rows_per_file: Option<usize>,
+    /// Save the progress with `rows_per_file` to this file every time an
+    /// output file is finished, see [`crate::checkpoint`].
+    pub checkpoint_path: Option<PathBuf>,
+    /// Continue after the progress saved to `checkpoint_path`.
+    pub resume: bool,
+    /// Read all addresses and collect `ConvertReport::validation` without
+    /// writing the output file. Coordinates are not reprojected and derived
+    /// columns are not added.
+    pub validate_only: bool,
+    /// Skip files inside ZIP archives that fail to be read or decompressed
+    /// (listed in `ConvertReport::skipped_entries`) instead of returning an error.
+    pub skip_bad_entries: bool,
+    /// Stop reading input after the current batch once this flag is set (e.g.
+    /// from a Ctrl-C handler); the output written so far is still finalized.
+    pub interrupt: Option<Arc<AtomicBool>>,
+    /// Collect the municipalities missing from TERC and the cities with an
+    /// unknown kind (schema 2021 only) and write them to its file at the end.
+    pub join_audit: Option<JoinAudit>,
+    /// Count addresses per voivodeship (`ConvertReport::voivodeship_counts`).
+    pub count_voivodeships: bool,
+    /// Old snapshot compared with `inputs`: when not empty, only the added,
+    /// removed and modified addresses are written, with column `zmiana`.
+    pub compare_with: Vec<FileRecord>,
+    /// Measure the time of the phases of the conversion
+    /// (`ConvertReport::phase_durations`). Timings are global for the whole
+    /// process (see [`crate::timings`]): conversions running at the same time
+    /// with `benchmark` mix up their times.
+    pub benchmark: bool,
+}
+
+impl ConvertOptions {
+    /// Options with the same defaults as the command line program: CSV output,
+    /// EPSG:2180 and zstd compressed GeoParquet.
+    pub fn new(
+        inputs: Vec<FileRecord>,
+        output_path: PathBuf,
+        schema_version: SchemaVersion,
+    ) -> Self {
+        Self {
+            inputs,
+            output_path,
+            schema_version,
+            output_format: OutputFormat::CSV,
+            crs: CRS::Epsg2180,
+            teryt_path: None,
+            teryt_date: None,
+            download_teryt: false,
+            teryt_api_username: None,
+            teryt_api_password: None,
+            batch_size: DEFAULT_BATCH_SIZE,
+            #[cfg(feature = "geoparquet")]
+            parquet_compression: ParquetCompression::Zstd
+                .to_parquet(None)
+                .expect("Default zstd level is valid."),
+            parquet_row_group_size: DEFAULT_BATCH_SIZE,
+            parquet_data_page_size: None,
+            parquet_dictionary_page_size: None,
+            #[cfg(feature = "geoparquet")]
+            parquet_version: WriterVersion::PARQUET_2_0,
+            #[cfg(feature = "geoparquet")]
+            geometry_encoding: GeoParquetWriterEncoding::WKB,
+            #[cfg(feature = "geoparquet")]
+            points_only_schema: false,
+            flush_every: None,
+            row_group_per_voivodeship: false,
+            parquet_bloom_filter_columns: Vec::new(),
+            csv_delimiter: b',',
+            decimal_comma: false,
+            csv_null_string: String::new(),
+            csv_date_format: DEFAULT_CSV_DATE_FORMAT.to_string(),
+            csv_timestamp_format: DEFAULT_CSV_TIMESTAMP_FORMAT.to_string(),
+            timestamp_timezone: TimestampTimezone::Utc,
+            errors_to: None,
+            max_errors: None,
+            filter: RecordFilter::default(),
+            tail: None,
+            spatial_sort: false,
+            spatial_sort_buffer: None,
+            id_prefix: None,
+            status_raw: false,
+            skip_dictionary: false,
+            input_crs: CRS::Epsg2180,
+            street_types: None,
+            verbose_unknown_tags: false,
+            fail_on_unknown_tag: false,
+            normalize_whitespace: false,
+            untrimmed_columns: UntrimmedColumns::default(),
+            coord_precision: None,
+            name_case: NameCase::Preserve,
+            column_renames: HashMap::new(),
+            normalize_names: false,
+            split_house_number: false,
+            split_valid_from: false,
+            add_full_address: false,
+            add_source_column: false,
+            grid_size: None,
+            parallel_parse: false,
+            mmap: false,
+            workers_per_file: DEFAULT_WORKERS_PER_FILE,
+            rows_per_file: None,
+            checkpoint_path: None,
+            resume: false,
+            validate_only: false,
+            skip_bad_entries: false,
+            interrupt: None,
+            join_audit: None,
+            count_voivodeships: false,
+            compare_with: Vec::new(),
+            benchmark: false,
+        }
+    }
+
+    fn is_interrupted(&self) -> bool {
+        self.interrupt
+            .as_ref()
+            .is_some_and(|flag| flag.load(Ordering::SeqCst))
+    }
+}
+
+/// Addresses read from one input file (or one parsed file inside a ZIP archive).
+#[derive(Clone, Debug)]
+pub struct FileReport {
+    pub path: PathBuf,
+    /// Name of the file inside the ZIP archive.
+    pub zip_entry: Option<String>,
+    pub rows: usize,
+}
+
+/// Summary of a finished `convert`.
+#[derive(Clone, Debug)]
+pub struct ConvertReport {
+    /// Addresses read (after filtering), also when only the tail was written.
+    pub total_rows: usize,
+    /// Addresses skipped because they could not be parsed.
+    pub skipped_rows: usize,
+    /// Addresses dropped because they had no coordinates, with
+    /// `RecordFilter::drop_null_geometry`.
+    pub null_geometry_rows: usize,
+    pub duration: Duration,
+    pub files: Vec<FileReport>,
+    /// Date of the TERC catalog used (schema 2021 only).
+    pub terc_catalog_date: Option<String>,
+    /// File the TERC catalog was read from (schema 2021 without
+    /// `download_teryt`).
+    pub terc_path: Option<PathBuf>,
+    /// Counts collected with `ConvertOptions::validate_only`.
+    pub validation: Option<ValidationCounts>,
+    /// Files inside ZIP archives skipped with `ConvertOptions::skip_bad_entries`,
+    /// including the ones skipped when listing the inputs.
+    pub skipped_entries: Vec<SkippedEntry>,
+    /// Reading was stopped by `ConvertOptions::interrupt`, the output has
+    /// only the addresses read until then.
+    pub interrupted: bool,
+    /// Addresses written per `teryt_wojewodztwo` (addresses without it are
+    /// not counted), collected with `ConvertOptions::count_voivodeships`.
+    /// With `ConvertOptions::tail` and `ConvertOptions::compare_with` only the
+    /// written addresses are counted.
+    pub voivodeship_counts: Option<BTreeMap<String, usize>>,
+    /// Differences from `ConvertOptions::compare_with`, if it is used.
+    pub changes: Option<ChangeCounts>,
+    /// Files written: `output_path`, the numbered files with
+    /// `ConvertOptions::rows_per_file`, none with `validate_only`.
+    pub output_files: Vec<PathBuf>,
+    /// Records written to the file of `ConvertOptions::join_audit`.
+    pub join_audit_records: Option<usize>,
+    /// Time of every phase, with `ConvertOptions::benchmark`, see
+    /// `timings::phase_durations`.
+    pub phase_durations: Option<Vec<(Phase, Duration)>>,
+}
+
+/// State of `ConvertOptions::row_group_per_voivodeship`.
+#[cfg(feature = "geoparquet")]
+#[derive(Default)]
+struct VoivodeshipRowGroups {
+    /// `teryt_wojewodztwo` of the current row group, `None` before the first row.
+    current: Option<Option<String>>,
+    /// Voivodeships of the closed row groups.
+    closed: HashSet<Option<String>>,
+    /// Set when a voivodeship came back after its row group was closed: the
+    /// input is not grouped and row groups are no longer aligned.
+    disabled: bool,
+}
+
+#[cfg(feature = "geoparquet")]
+impl VoivodeshipRowGroups {
+    /// Splits `batch` at the changes of `teryt_wojewodztwo`. Returns the parts
+    /// with whether the row group is to be closed before writing them.
+    fn split(&mut self, batch: &RecordBatch) -> anyhow::Result<Vec<(bool, RecordBatch)>> {
+        if self.disabled {
+            return Ok(vec![(false, batch.clone())]);
+        }
+        let voivodeships = batch
+            .column_by_name("teryt_wojewodztwo")
+            .context("Batch is missing column `teryt_wojewodztwo`.")?
+            .as_string::<i32>();
+        let mut parts = Vec::new();
+        let mut start = 0;
+        let mut close = false;
+        for (row, voivodeship) in voivodeships.iter().enumerate() {
+            let voivodeship = voivodeship.map(str::to_string);
+            match &self.current {
+                Some(current) if *current == voivodeship => continue,
+                None => {}
+                Some(_) if self.closed.contains(&voivodeship) => {
+                    println!(
+                        "Warning: input is not grouped by voivodeship (`{}` came back after its row group was closed), row groups are no longer aligned to voivodeships.",
+                        voivodeship.as_deref().unwrap_or("null")
+                    );
+                    self.disabled = true;
+                    break;
+                }
+                Some(_) => {
+                    if row > start {
+                        parts.push((close, batch.slice(start, row - start)));
+                        start = row;
+                    }
+                    close = true;
+                    self.closed.extend(self.current.take());
+                }
+            }
+            self.current = Some(voivodeship);
+        }
+        if start < batch.num_rows() {
+            parts.push((close, batch.slice(start, batch.num_rows() - start)));
+        }
+        Ok(parts)
+    }
+}
+
+/// Parts of `batch` to write with whether to close the row group before each,
+/// see `VoivodeshipRowGroups::split`.
+#[cfg(feature = "geoparquet")]
+fn row_group_parts(
+    voivodeship_row_groups: &mut Option<VoivodeshipRowGroups>,
+    batch: &RecordBatch,
+) -> anyhow::Result<Vec<(bool, RecordBatch)>> {
+    match voivodeship_row_groups {
+        Some(row_groups) => row_groups.split(batch),
+        None => Ok(vec![(false, batch.clone())]),
+    }
+}
+
+enum OutputWriter {
+    /// Batches are dropped (`--validate-only`).
+    Discard,
+    /// Batches are kept in memory (old snapshot of `--compare`).
+    Collect { batches: Vec<RecordBatch> },
+    /// Only the differences from the old snapshot are written to `inner`,
+    /// the removed addresses when finished (`--compare`).
+    Compare {
+        snapshot: Box<Snapshot>,
+        inner: Box<OutputWriter>,
+    },
+    /// Only the last rows are kept and written to `inner` when finished (`--tail`).
+    Tail {
+        buffer: TailBuffer,
+        inner: Box<OutputWriter>,
+    },
+    /// Rows are kept and written to `inner` sorted by their Hilbert index
+    /// when finished, or once there are `buffer_rows` of them (`--spatial-sort`).
+    SpatialSort {
+        batches: Vec<RecordBatch>,
+        num_rows: usize,
+        buffer_rows: Option<usize>,
+        inner: Box<OutputWriter>,
+    },
+    /// Batches are written to an output writer on another thread
+    /// (`workers_per_file` 2). `handle` returns its result once the channel
+    /// is closed, or earlier on an error.
+    Threaded {
+        sender: SyncSender<RecordBatch>,
+        handle: Option<JoinHandle<anyhow::Result<()>>>,
+    },
+    /// Batches are written to numbered files of at most `rows_per_file` rows
+    /// (`--rows-per-file`), see `rollover_path`.
+    Rollover {
+        rows_per_file: usize,
+        rows_in_file: usize,
+        /// Writer of the last file of `files`.
+        current: Box<OutputWriter>,
+        files: Vec<PathBuf>,
+        /// `--output-path`, the file number is added to it.
+        path_template: PathBuf,
+        /// Creates the writer of the next file.
+        create: Box<CreateWriter>,
+        /// Skips the rows written before the run was resumed and saves the
+        /// progress when a file is finished (`--checkpoint`).
+        checkpointer: Option<Checkpointer>,
+    },
+    /// Addresses are counted per voivodeship and written to `inner`. Wrapped
+    /// by `Tail` and `Compare`, so only the written addresses are counted,
+    /// some of them when finished.
+    CountVoivodeships {
+        counts: Arc<Mutex<BTreeMap<String, usize>>>,
+        inner: Box<OutputWriter>,
+    },
+    Csv {
+        writer: arrow::csv::writer::Writer<File>,
+        /// Format floats with a decimal comma (`--decimal-comma`).
+        decimal_comma: bool,
+        /// Old to new column names (`--rename-columns`).
+        renames: HashMap<String, String>,
+    },
+    #[cfg(feature = "geoparquet")]
+    GeoParquet {
+        writer: ArrowWriter<File>,
+        encoder: GeoParquetRecordBatchEncoder,
+        crs: CRS,
+        geom_type: PointType,
+        geoparquet_schema: Arc<Schema>,
+        /// `geoparquet_schema` with renamed columns, passed to the encoder.
+        output_schema: SchemaRef,
+        /// Close the row group after this many rows (`--flush-every`).
+        flush_every: Option<usize>,
+        rows_since_flush: usize,
+        /// `--row-group-per voivodeship`.
+        voivodeship_row_groups: Option<VoivodeshipRowGroups>,
+    },
+    Gml {
+        writer: GmlWriter<BufWriter<File>>,
+        /// Old to new column names (`--rename-columns`).
+        renames: HashMap<String, String>,
+    },
+    #[cfg(feature = "protobuf")]
+    Protobuf {
+        writer: ProtobufWriter<BufWriter<File>>,
+    },
+    /// Plain parquet with the same columns as CSV.
+    #[cfg(feature = "geoparquet")]
+    Parquet {
+        writer: ArrowWriter<File>,
+        /// Old to new column names (`--rename-columns`).
+        renames: HashMap<String, String>,
+        /// Close the row group after this many rows (`--flush-every`).
+        flush_every: Option<usize>,
+        rows_since_flush: usize,
+        /// `--row-group-per voivodeship`.
+        voivodeship_row_groups: Option<VoivodeshipRowGroups>,
+    },
+}
+
+impl OutputWriter {
+    /// `terc_catalog_date` is written to the key-value metadata of parquet
+    /// outputs, see [`provenance_metadata`]. Numbered files continue after the
+    /// files finished before a resumed run of `checkpointer`.
+    fn new(
+        options: &ConvertOptions,
+        snapshot: Option<Snapshot>,
+        terc_catalog_date: Option<&str>,
+        checkpointer: Option<&Checkpointer>,
+    ) -> anyhow::Result<Self> {
+        let mut output_writer = match options.rows_per_file {
+            _ if options.validate_only => OutputWriter::Discard,
+            None => OutputWriter::file(options, &options.output_path, terc_catalog_date)?,
+            Some(rows_per_file) => {
+                let mut files = checkpointer.map_or_else(Vec::new, Checkpointer::output_files);
+                let path = rollover_path(&options.output_path, files.len() + 1);
+                let current = OutputWriter::file(options, &path, terc_catalog_date)?;
+                // the inputs are not needed to create the writers of the next files
+                let file_options = ConvertOptions {
+                    inputs: Vec::new(),
+                    compare_with: Vec::new(),
+                    ..options.clone()
+                };
+                let terc_catalog_date = terc_catalog_date.map(str::to_string);
+                files.push(path);
+                OutputWriter::Rollover {
+                    rows_per_file,
+                    rows_in_file: 0,
+                    current: Box::new(current),
+                    files,
+                    path_template: options.output_path.clone(),
+                    create: Box::new(move |path| {
+                        OutputWriter::file(&file_options, path, terc_catalog_date.as_deref())
+                    }),
+                    checkpointer: checkpointer.cloned(),
+                }
+            }
+        };
+        if options.count_voivodeships {
+            output_writer = OutputWriter::CountVoivodeships {
+                counts: Arc::default(),
+                inner: Box::new(output_writer),
+            };
+        }
+        if options.spatial_sort && !options.validate_only {
+            output_writer = OutputWriter::SpatialSort {
+                batches: Vec::new(),
+                num_rows: 0,
+                buffer_rows: options.spatial_sort_buffer,
+                inner: Box::new(output_writer),
+            };
+        }
+        if let Some(tail) = options.tail {
+            output_writer = OutputWriter::Tail {
+                buffer: TailBuffer::new(tail),
+                inner: Box::new(output_writer),
+            };
+        }
+        if let Some(snapshot) = snapshot {
+            output_writer = OutputWriter::Compare {
+                snapshot: Box::new(snapshot),
+                inner: Box::new(output_writer),
+            };
+        }
+        Ok(output_writer)
+    }
+
+    /// Writer of the output file `path` in `options.output_format`, on its
+    /// own thread with `workers_per_file` 2.
+    fn file(
+        options: &ConvertOptions,
+        path: &Path,
+        terc_catalog_date: Option<&str>,
+    ) -> anyhow::Result<Self> {
+        #[cfg(not(feature = "geoparquet"))]
+        let _ = terc_catalog_date;
+        let output_file = File::create(path).with_context(|| {
+            format!("could not create output file `{}`", &path.to_string_lossy())
+        })?;
+        let output_writer = match &options.output_format {
+            OutputFormat::CSV => OutputWriter::Csv {
+                writer: WriterBuilder::new()
+                    .with_header(true)
+                    .with_delimiter(options.csv_delimiter)
+                    .with_null(options.csv_null_string.clone())
+                    .with_date_format(options.csv_date_format.clone())
+                    .with_timestamp_tz_format(options.csv_timestamp_format.clone())
+                    .build(output_file),
+                decimal_comma: options.decimal_comma,
+                renames: options.column_renames.clone(),
+            },
+            OutputFormat::Gml => OutputWriter::Gml {
+                writer: GmlWriter::new(BufWriter::new(output_file))
+                    .context("Failed to write GML header.")?,
+                renames: options.column_renames.clone(),
+            },
+            #[cfg(feature = "protobuf")]
+            OutputFormat::Protobuf => OutputWriter::Protobuf {
+                writer: ProtobufWriter::new(BufWriter::new(output_file)),
+            },
+            #[cfg(feature = "geoparquet")]
+            OutputFormat::GeoParquet => {
+                let (geom_type, geoparquet_schema, output_schema, encoder) =
+                    geoparquet_encoder(options)?;
+                let properties = parquet_properties(options, &output_schema, terc_catalog_date)?;
+                let writer =
+                    ArrowWriter::try_new(output_file, encoder.target_schema(), Some(properties))
+                        .context("Could not create GeoParquet writer.")?;
+                OutputWriter::GeoParquet {
+                    writer,
+                    encoder,
+                    crs: options.crs,
+                    geom_type,
+                    geoparquet_schema,
+                    output_schema,
+                    flush_every: options.flush_every,
+                    rows_since_flush: 0,
+                    voivodeship_row_groups: options
+                        .row_group_per_voivodeship
+                        .then(VoivodeshipRowGroups::default),
+                }
+            }
+            #[cfg(feature = "geoparquet")]
+            OutputFormat::Parquet => {
+                let schema = output_schema(options)?;
+                let properties = parquet_properties(options, &schema, terc_catalog_date)?;
+                let writer = ArrowWriter::try_new(output_file, schema, Some(properties))
+                    .context("Could not create parquet writer.")?;
+                OutputWriter::Parquet {
+                    writer,
+                    renames: options.column_renames.clone(),
+                    flush_every: options.flush_every,
+                    rows_since_flush: 0,
+                    voivodeship_row_groups: options
+                        .row_group_per_voivodeship
+                        .then(VoivodeshipRowGroups::default),
+                }
+            }
+        };
+        if options.workers_per_file > 1 {
+            return Ok(OutputWriter::threaded(output_writer));
+        }
+        Ok(output_writer)
+    }
+
+    /// Moves `inner` to a new thread writing the batches sent to it.
+    fn threaded(mut inner: OutputWriter) -> Self {
+        let (sender, receiver) = sync_channel::<RecordBatch>(WRITER_QUEUE_BATCHES);
+        let handle = std::thread::spawn(move || {
+            for batch in receiver {
+                inner.write_batch(&batch)?;
+            }
+            inner.finish()?;
+            Ok(())
+        });
+        OutputWriter::Threaded {
+            sender,
+            handle: Some(handle),
+        }
+    }
+
+    /// Counts collected by `CountVoivodeships`, if it is used. They are
+    /// complete once the writer is finished.
+    fn voivodeship_counts(&self) -> Option<Arc<Mutex<BTreeMap<String, usize>>>> {
+        match self {
+            OutputWriter::CountVoivodeships { counts, .. } => Some(Arc::clone(counts)),
+            OutputWriter::Compare { inner, .. }
+            | OutputWriter::Tail { inner, .. }
+            | OutputWriter::SpatialSort { inner, .. } => inner.voivodeship_counts(),
+            _ => None,
+        }
+    }
+
+    /// Counts of `Compare`, if it is used.
+    fn change_counts(&self) -> Option<ChangeCounts> {
+        match self {
+            OutputWriter::Compare { snapshot, .. } => Some(snapshot.counts()),
+            _ => None,
+        }
+    }
+
+    /// Writer of an output file, timed as `Phase::Writing`.
+    fn is_file(&self) -> bool {
+        match self {
+            OutputWriter::Csv { .. } | OutputWriter::Gml { .. } => true,
+            #[cfg(feature = "geoparquet")]
+            OutputWriter::GeoParquet { .. } | OutputWriter::Parquet { .. } => true,
+            #[cfg(feature = "protobuf")]
+            OutputWriter::Protobuf { .. } => true,
+            _ => false,
+        }
+    }
+
+    fn write_batch(&mut self, batch: &RecordBatch) -> anyhow::Result<()> {
+        let _timer = self.is_file().then(|| timings::start(Phase::Writing));
+        match self {
+            OutputWriter::Discard => {}
+            OutputWriter::Collect { batches } => batches.push(batch.clone()),
+            OutputWriter::Compare { snapshot, inner } => {
+                let changed = snapshot
+                    .diff(batch)
+                    .context("Failed to compare batch with the old snapshot.")?;
+                if changed.num_rows() > 0 {
+                    inner.write_batch(&changed)?;
+                }
+            }
+            OutputWriter::Tail { buffer, .. } => buffer.push(batch.clone()),
+            OutputWriter::SpatialSort {
+                batches,
+                num_rows,
+                buffer_rows,
+                inner,
+            } => {
+                *num_rows += batch.num_rows();
+                batches.push(batch.clone());
+                if buffer_rows.is_some_and(|buffer_rows| *num_rows >= buffer_rows) {
+                    write_sorted(std::mem::take(batches), inner)?;
+                    *num_rows = 0;
+                }
+            }
+            OutputWriter::Threaded { sender, handle } => {
+                if sender.send(batch.clone()).is_err() {
+                    // the writer thread only stops early on an error
+                    if let Some(handle) = handle.take() {
+                        join_writer(handle)?;
+                    }
+                    anyhow::bail!("Output writer thread stopped.");
+                }
+            }
+            OutputWriter::Rollover {
+                rows_per_file,
+                rows_in_file,
+                current,
+                files,
+                path_template,
+                create,
+                checkpointer,
+            } => {
+                let mut offset = checkpointer
+                    .as_ref()
+                    .map_or(0, |c| c.take_skipped_rows(batch.num_rows()));
+                while offset < batch.num_rows() {
+                    if *rows_in_file == *rows_per_file {
+                        let path = rollover_path(path_template, files.len() + 1);
+                        std::mem::replace(current, Box::new(create(&path)?)).finish()?;
+                        if let Some(checkpointer) = checkpointer {
+                            checkpointer.file_finished(files.last().unwrap())?;
+                        }
+                        files.push(path);
+                        *rows_in_file = 0;
+                    }
+                    let length = (*rows_per_file - *rows_in_file).min(batch.num_rows() - offset);
+                    current.write_batch(&batch.slice(offset, length))?;
+                    if let Some(checkpointer) = checkpointer {
+                        checkpointer.add_rows(length);
+                    }
+                    offset += length;
+                    *rows_in_file += length;
+                }
+            }
+            OutputWriter::CountVoivodeships { counts, inner } => {
+                let voivodeships = batch
+                    .column_by_name("teryt_wojewodztwo")
+                    .context("Batch is missing column `teryt_wojewodztwo`.")?
+                    .as_string::<i32>();
+                let mut counts = counts.lock().unwrap();
+                for voivodeship in voivodeships.iter().flatten() {
+                    *counts.entry(voivodeship.to_string()).or_default() += 1;
+                }
+                inner.write_batch(batch)?;
+            }
+            OutputWriter::Csv {
+                writer,
+                decimal_comma,
+                renames,
+            } => {
+                let mut batch = batch.clone();
+                if *decimal_comma {
+                    batch = format_floats_with_decimal_comma(&batch)
+                        .context("Failed to format decimal commas.")?;
+                }
+                if !renames.is_empty() {
+                    batch = rename_columns(&batch, renames)?;
+                }
+                writer.write(&batch).context("Failed to write CSV batch.")?;
+            }
+            OutputWriter::Gml { writer, renames } => {
+                let renamed = if renames.is_empty() {
+                    batch.clone()
+                } else {
+                    rename_columns(batch, renames)?
+                };
+                writer
+                    .write(batch, &renamed)
+                    .context("Failed to write GML batch.")?;
+            }
+            #[cfg(feature = "protobuf")]
+            OutputWriter::Protobuf { writer } => {
+                writer
+                    .write(batch)
+                    .context("Failed to write protobuf batch.")?;
+            }
+            #[cfg(feature = "geoparquet")]
+            OutputWriter::GeoParquet {
+                writer,
+                encoder,
+                crs,
+                geom_type,
+                geoparquet_schema,
+                output_schema,
+                flush_every,
+                rows_since_flush,
+                voivodeship_row_groups,
+            } => {
+                for (close_row_group, batch) in row_group_parts(voivodeship_row_groups, batch)? {
+                    if close_row_group {
+                        writer
+                            .flush()
+                            .context("Failed to flush GeoParquet row group.")?;
+                        *rows_since_flush = 0;
+                    }
+                    let geo_batch = canonical_to_geoparquet_batch(
+                        &batch,
+                        crs,
+                        geom_type,
+                        geoparquet_schema,
+                        output_schema,
+                    )?;
+                    let encoded = encoder
+                        .encode_record_batch(&geo_batch)
+                        .context("Failed to encode GeoParquet batch.")?;
+                    writer
+                        .write(&encoded)
+                        .context("Failed to write GeoParquet batch.")?;
+                    if let Some(flush_every) = flush_every {
+                        *rows_since_flush += encoded.num_rows();
+                        if *rows_since_flush >= *flush_every {
+                            writer
+                                .flush()
+                                .context("Failed to flush GeoParquet row group.")?;
+                            *rows_since_flush = 0;
+                        }
+                    }
+                }
+            }
+            #[cfg(feature = "geoparquet")]
+            OutputWriter::Parquet {
+                writer,
+                renames,
+                flush_every,
+                rows_since_flush,
+                voivodeship_row_groups,
+            } => {
+                for (close_row_group, mut batch) in row_group_parts(voivodeship_row_groups, batch)?
+                {
+                    if close_row_group {
+                        writer
+                            .flush()
+                            .context("Failed to flush parquet row group.")?;
+                        *rows_since_flush = 0;
+                    }
+                    if !renames.is_empty() {
+                        batch = rename_columns(&batch, renames)?;
+                    }
+                    writer
+                        .write(&batch)
+                        .context("Failed to write parquet batch.")?;
+                    if let Some(flush_every) = flush_every {
+                        *rows_since_flush += batch.num_rows();
+                        if *rows_since_flush >= *flush_every {
+                            writer
+                                .flush()
+                                .context("Failed to flush parquet row group.")?;
+                            *rows_since_flush = 0;
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Finishes the output. Returns the files written by `Rollover`, none
+    /// without it.
+    fn finish(self) -> anyhow::Result<Vec<PathBuf>> {
+        let _timer = self.is_file().then(|| timings::start(Phase::Writing));
+        match self {
+            OutputWriter::Discard | OutputWriter::Collect { .. } | OutputWriter::Csv { .. } => {
+                Ok(Vec::new())
+            }
+            OutputWriter::Compare {
+                snapshot,
+                mut inner,
+            } => {
+                for batch in snapshot
+                    .removed()
+                    .context("Failed to collect removed addresses.")?
+                {
+                    inner.write_batch(&batch)?;
+                }
+                inner.finish()
+            }
+            OutputWriter::Tail { buffer, mut inner } => {
+                for batch in buffer.into_batches() {
+                    inner.write_batch(&batch)?;
+                }
+                inner.finish()
+            }
+            OutputWriter::SpatialSort {
+                batches, mut inner, ..
+            } => {
+                write_sorted(batches, &mut inner)?;
+                inner.finish()
+            }
+            OutputWriter::Gml { writer, .. } => {
+                writer.finish().context("Failed to finish GML output.")?;
+                Ok(Vec::new())
+            }
+            #[cfg(feature = "protobuf")]
+            OutputWriter::Protobuf { writer } => {
+                writer
+                    .finish()
+                    .context("Failed to finish protobuf output.")?;
+                Ok(Vec::new())
+            }
+            OutputWriter::Rollover {
+                current,
+                mut files,
+                rows_in_file,
+                checkpointer,
+                ..
+            } => {
+                current.finish()?;
+                // a resumed run that wrote no more addresses
+                if rows_in_file == 0 && files.len() > 1 {
+                    let path = files.pop().unwrap();
+                    std::fs::remove_file(&path).with_context(|| {
+                        format!("could not remove empty output file `{}`", path.display())
+                    })?;
+                    return Ok(files);
+                }
+                if let Some(checkpointer) = checkpointer {
+                    checkpointer.file_finished(files.last().unwrap())?;
+                }
+                Ok(files)
+            }
+            OutputWriter::CountVoivodeships { inner, .. } => inner.finish(),
+            OutputWriter::Threaded { sender, handle } => {
+                drop(sender);
+                handle.map_or(Ok(()), join_writer)?;
+                Ok(Vec::new())
+            }
+            #[cfg(feature = "geoparquet")]
+            OutputWriter::GeoParquet {
+                mut writer,
+                encoder,
+                ..
+            } => {
+                let kv_metadata = encoder
+                    .into_keyvalue()
+                    .context("Could not create GeoParquet K/V metadata.")?;
+                writer.append_key_value_metadata(kv_metadata);
+                writer
+                    .finish()
+                    .context("Failed to write GeoParquet metadata.")?;
+                Ok(Vec::new())
+            }
+            #[cfg(feature = "geoparquet")]
+            OutputWriter::Parquet { mut writer, .. } => {
+                writer
+                    .finish()
+                    .context("Failed to write parquet metadata.")?;
+                Ok(Vec::new())
+            }
+        }
+    }
+}
+
+/// Writes the rows of `batches` to `inner` sorted by their Hilbert index, see
+/// `OutputWriter::SpatialSort`.
+fn write_sorted(batches: Vec<RecordBatch>, inner: &mut OutputWriter) -> anyhow::Result<()> {
+    let Some(first) = batches.first() else {
+        return Ok(());
+    };
+    let sorted = sort_by_hilbert_index(&first.schema(), &batches)
+        .context("Failed to sort addresses by their Hilbert index.")?;
+    inner.write_batch(&sorted)
+}
+
+/// Creates the writer of an output file, see `OutputWriter::Rollover`.
+type CreateWriter = dyn Fn(&Path) -> anyhow::Result<OutputWriter> + Send;
+
+/// Placeholder of the file number in the output path with `rows_per_file`.
+pub const FILE_NUMBER_PLACEHOLDER: &str = "{n}";
+
+/// Path of the `number`th output file with `rows_per_file`: the placeholder
+/// `{n}` of `output_path` replaced with the zero-padded number, or the number
+/// added before the extension (`output.csv` -> `output-00001.csv`).
+pub fn rollover_path(output_path: &Path, number: usize) -> PathBuf {
+    let number = format!("{:05}", number);
+    let path = output_path.to_string_lossy();
+    if path.contains(FILE_NUMBER_PLACEHOLDER) {
+        return PathBuf::from(path.replace(FILE_NUMBER_PLACEHOLDER, &number));
+    }
+    let stem = output_path
+        .file_stem()
+        .map_or_else(String::new, |s| s.to_string_lossy().to_string());
+    let file_name = match output_path.extension() {
+        Some(extension) => format!("{}-{}.{}", stem, number, extension.to_string_lossy()),
+        None => format!("{}-{}", stem, number),
+    };
+    output_path.with_file_name(file_name)
+}
+
+/// Waits for the thread of `OutputWriter::Threaded` and returns its result.
+fn join_writer(handle: JoinHandle<anyhow::Result<()>>) -> anyhow::Result<()> {
+    handle
+        .join()
+        .map_err(|_| anyhow::anyhow!("Output writer thread panicked."))?
+}
+
+/// Convert a canonical (SCHEMA_CSV-shaped) batch into a GeoParquet batch:
+/// build a `geometry` point column from the coordinate columns selected by `crs`,
+/// drop `x_epsg_2180`/`y_epsg_2180`, and reorder to match `geoparquet_schema`.
+#[cfg(feature = "geoparquet")]
+fn canonical_to_geoparquet_batch(
+    batch: &RecordBatch,
+    crs: &CRS,
+    geom_type: &PointType,
+    geoparquet_schema: &Arc<Schema>,
+    output_schema: &SchemaRef,
+) -> anyhow::Result<RecordBatch> {
+    let (x_name, y_name) = match crs {
+        CRS::Epsg2180 => ("x_epsg_2180", "y_epsg_2180"),
+        CRS::Epsg4326 => ("dlugosc_geograficzna", "szerokosc_geograficzna"),
+    };
+    let xs = batch
+        .column_by_name(x_name)
+        .context("canonical batch missing x column")?
+        .as_any()
+        .downcast_ref::<Float64Array>()
+        .context("x column is not Float64")?;
+    let ys = batch
+        .column_by_name(y_name)
+        .context("canonical batch missing y column")?
+        .as_any()
+        .downcast_ref::<Float64Array>()
+        .context("y column is not Float64")?;
+    let points: Vec<Option<geo_types::Point>> = (0..batch.num_rows())
+        .map(|i| {
+            if xs.is_null(i) || ys.is_null(i) {
+                None
+            } else {
+                Some(geo_types::point!(x: xs.value(i), y: ys.value(i)))
+            }
+        })
+        .collect();
+    let geometry =
+        PointBuilder::from_nullable_points(points.iter().map(Option::as_ref), geom_type.clone())
+            .finish();
+
+    let mut columns: Vec<ArrayRef> = Vec::with_capacity(geoparquet_schema.fields().len());
+    for field in geoparquet_schema.fields() {
+        if field.name() == "geometry" {
+            columns.push(geometry.to_array_ref());
+        } else {
+            let col = batch
+                .column_by_name(field.name())
+                .with_context(|| format!("canonical batch missing column `{}`", field.name()))?;
+            columns.push(col.clone());
+        }
+    }
+    Ok(RecordBatch::try_new(output_schema.clone(), columns)?)
+}
+
+/// Sets the timezone of timestamp fields and appends the fields of derived
+/// columns requested in `options` to `schema`.
+fn with_derived_fields(schema: &Schema, options: &ConvertOptions) -> SchemaRef {
+    let schema = with_timestamp_timezone(schema, options.timestamp_timezone.tz_name());
+    let mut fields = schema.fields().to_vec();
+    if options.normalize_names {
+        fields.extend(NORMALIZED_NAME_FIELDS.iter().cloned().map(Arc::new));
+    }
+    if options.split_house_number {
+        fields.extend(HOUSE_NUMBER_FIELDS.iter().cloned().map(Arc::new));
+    }
+    if options.split_valid_from {
+        fields.extend(VALID_FROM_FIELDS.iter().cloned().map(Arc::new));
+    }
+    if options.add_full_address {
+        fields.push(Arc::new(FULL_ADDRESS_FIELD.clone()));
+    }
+    if options.add_source_column {
+        fields.push(Arc::new(SOURCE_FIELD.clone()));
+    }
+    if options.grid_size.is_some() {
+        fields.push(Arc::new(GRID_FIELD.clone()));
+    }
+    if !options.compare_with.is_empty() {
+        fields.push(Arc::new(CHANGE_FIELD.clone()));
+    }
+    Arc::new(Schema::new(fields))
+}
+
+/// Properties of the parquet writer set by the parquet options. `schema` is
+/// the output schema, used to check the bloom filter columns.
+#[cfg(feature = "geoparquet")]
+fn parquet_properties(
+    options: &ConvertOptions,
+    schema: &Schema,
+    terc_catalog_date: Option<&str>,
+) -> anyhow::Result<WriterProperties> {
+    let mut builder = WriterProperties::builder()
+        .set_max_row_group_row_count(Some(options.parquet_row_group_size))
+        .set_writer_version(options.parquet_version)
+        .set_compression(options.parquet_compression)
+        .set_key_value_metadata(Some(provenance_metadata(terc_catalog_date)));
+    if let Some(size) = options.parquet_data_page_size {
+        builder = builder.set_data_page_size_limit(size);
+    }
+    if let Some(size) = options.parquet_dictionary_page_size {
+        builder = builder.set_dictionary_page_size_limit(size);
+    }
+    for column in bloom_filter_columns(options, schema)? {
+        builder = builder.set_column_bloom_filter_enabled(ColumnPath::from(column), true);
+    }
+    Ok(builder.build())
+}
+
+/// Output names of `ConvertOptions::parquet_bloom_filter_columns`. `uuid` is
+/// the (possibly renamed) `lokalny_id` unless a column is named so; every
+/// column has to be a string column of `schema`.
+#[cfg(feature = "geoparquet")]
+fn bloom_filter_columns(options: &ConvertOptions, schema: &Schema) -> anyhow::Result<Vec<String>> {
+    options
+        .parquet_bloom_filter_columns
+        .iter()
+        .map(|name| {
+            let name = match name.as_str() {
+                "uuid" if schema.field_with_name("uuid").is_err() => options
+                    .column_renames
+                    .get("lokalny_id")
+                    .map_or("lokalny_id", String::as_str),
+                name => name,
+            };
+            let field = schema.field_with_name(name).map_err(|_| {
+                anyhow::anyhow!(
+                    "Unknown column `{}` in `parquet_bloom_filter_columns`, expected `uuid` or a column of the output (see `output_schema`).",
+                    name
+                )
+            })?;
+            if *field.data_type() != DataType::Utf8 {
+                anyhow::bail!(
+                    "Column `{}` in `parquet_bloom_filter_columns` is not a string column.",
+                    name
+                );
+            }
+            Ok(name.to_string())
+        })
+        .collect()
+}
+
+/// Key-value metadata describing where the parquet file comes from: version
+/// of prg_convert, time of the conversion (UTC) and, for schema 2021, date of
+/// the TERC catalog used for administrative unit names.
+#[cfg(feature = "geoparquet")]
+fn provenance_metadata(terc_catalog_date: Option<&str>) -> Vec<KeyValue> {
+    let mut metadata = vec![
+        KeyValue::new(
+            "prg_convert_version".to_string(),
+            env!("CARGO_PKG_VERSION").to_string(),
+        ),
+        KeyValue::new(
+            "converted_at".to_string(),
+            chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+        ),
+    ];
+    if let Some(date) = terc_catalog_date {
+        metadata.push(KeyValue::new("teryt_date".to_string(), date.to_string()));
+    }
+    metadata
+}
+
+/// Geometry type, schema of the batches converted from the parsed ones, the
+/// same schema with renamed columns, and the encoder used when writing GeoParquet.
+#[cfg(feature = "geoparquet")]
+fn geoparquet_encoder(
+    options: &ConvertOptions,
+) -> anyhow::Result<(
+    PointType,
+    SchemaRef,
+    SchemaRef,
+    GeoParquetRecordBatchEncoder,
+)> {
+    let geom_type = point_type_for(&options.crs);
+    let geoparquet_schema = if options.points_only_schema {
+        get_points_only_geoparquet_schema(geom_type.clone())
+    } else {
+        with_derived_fields(&get_geoparquet_schema(geom_type.clone()), options)
+    };
+    let output_schema = Arc::new(
+        rename_fields(&geoparquet_schema, &options.column_renames)
+            .context("Invalid `column_renames`.")?,
+    );
+    let encoder = GeoParquetRecordBatchEncoder::try_new(
+        &output_schema,
+        &GeoParquetWriterOptionsBuilder::default()
+            .set_generate_covering(true)
+            .set_encoding(options.geometry_encoding)
+            .build(),
+    )
+    .context("Could not create GeoParquet encoder.")?;
+    Ok((geom_type, geoparquet_schema, output_schema, encoder))
+}
+
+/// Schema of the CSV columns with derived and renamed columns, as written to
+/// CSV and plain parquet.
+fn csv_output_schema(options: &ConvertOptions) -> anyhow::Result<SchemaRef> {
+    Ok(Arc::new(
+        rename_fields(
+            &with_derived_fields(&SCHEMA_CSV, options),
+            &options.column_renames,
+        )
+        .context("Invalid `column_renames`.")?,
+    ))
+}
+
+/// Arrow schema of the data written to the output file.
+pub fn output_schema(options: &ConvertOptions) -> anyhow::Result<SchemaRef> {
+    match options.output_format {
+        OutputFormat::CSV | OutputFormat::Gml => csv_output_schema(options),
+        // fixed fields of the `Address` message
+        #[cfg(feature = "protobuf")]
+        OutputFormat::Protobuf => Ok(SCHEMA_CSV.clone()),
+        #[cfg(feature = "geoparquet")]
+        OutputFormat::Parquet => csv_output_schema(options),
+        #[cfg(feature = "geoparquet")]
+        OutputFormat::GeoParquet => {
+            let (_, _, _, encoder) = geoparquet_encoder(options)?;
+            Ok(encoder.target_schema())
+        }
+    }
+}
+
+/// Value of the `zrodlo` column: name of the input file, followed by the name
+/// of the file inside it for ZIP archives (`archive.zip/file.xml`).
+fn source_name(file: &FileRecord, zip_file_index: &Option<usize>) -> String {
+    let file_name = file
+        .path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| file.path.to_string_lossy().to_string());
+    let entry = zip_file_index.and_then(|index| {
+        file.compressed_files
+            .iter()
+            .flatten()
+            .find(|e| e.index == index)
+    });
+    match entry {
+        Some(entry) => format!("{}/{}", file_name, entry.name),
+        None => file_name,
+    }
+}
+
+/// Writes all batches produced by a parser, adding derived columns requested
+/// in `options` (see `with_derived_columns`). Stops after
+/// the current batch when `options` is interrupted. Returns the number of
+/// rows written.
+fn write_batches(
+    mut parser: impl RecordBatchReader,
+    file_path: &Path,
+    source: &str,
+    options: &ConvertOptions,
+    output_writer: &mut OutputWriter,
+    errors: &ErrorSink,
+) -> anyhow::Result<usize> {
+    let mut processed_rows = 0;
+    while let Some(batch) = timings::time(Phase::Parsing, || parser.next()) {
+        let mut batch =
+            batch.with_context(|| format!("Failed to parse file: `{}`.", &file_path.display()))?;
+        check_error_count(options, errors)?;
+        // nothing is written with `validate_only`, so the batch is left as parsed
+        if !options.validate_only {
+            batch = with_derived_columns(batch, source, options)?;
+        }
+        processed_rows += batch.num_rows();
+        progress!("Read batch of {} addresses.", batch.num_rows());
+        output_writer.write_batch(&batch)?;
+        if options.is_interrupted() {
+            break;
+        }
+    }
+    Ok(processed_rows)
+}
+
+/// Changes `batch` as requested in `options` (timezone, case of names,
+/// rounding of coordinates) and adds the derived columns (`source` is the
+/// value of the `zrodlo` column).
+fn with_derived_columns(
+    mut batch: RecordBatch,
+    source: &str,
+    options: &ConvertOptions,
+) -> anyhow::Result<RecordBatch> {
+    if options.timestamp_timezone != TimestampTimezone::Utc {
+        batch = set_timestamp_timezone(&batch, options.timestamp_timezone.tz_name())
+            .context("Failed to set the timezone of timestamp columns.")?;
+    }
+    if options.name_case != NameCase::Preserve {
+        batch = set_name_case(&batch, options.name_case)
+            .context("Failed to change the case of names.")?;
+    }
+    if let Some(decimals) = options.coord_precision {
+        batch =
+            round_coordinates(&batch, decimals).context("Failed to round longitude/latitude.")?;
+    }
+    if options.normalize_names {
+        batch = append_normalized_name_columns(&batch)
+            .context("Failed to add normalized name columns.")?;
+    }
+    if options.split_house_number {
+        batch =
+            append_house_number_columns(&batch).context("Failed to add house number columns.")?;
+    }
+    if options.split_valid_from {
+        batch = append_valid_from_columns(&batch, options.schema_version)
+            .context("Failed to add valid from columns.")?;
+    }
+    if options.add_full_address {
+        batch = append_full_address_column(&batch).context("Failed to add full address column.")?;
+    }
+    if options.add_source_column {
+        batch = append_source_column(&batch, source).context("Failed to add source column.")?;
+    }
+    if let Some(size) = options.grid_size {
+        batch = append_grid_column(&batch, size).context("Failed to add grid column.")?;
+    }
+    #[cfg(feature = "geoparquet")]
+    if options.points_only_schema && options.output_format == OutputFormat::GeoParquet {
+        batch = append_address_column(&batch).context("Failed to add address column.")?;
+    }
+    Ok(batch)
+}
+
+/// Fails when more addresses were skipped than `options.max_errors` allows.
+fn check_error_count(options: &ConvertOptions, errors: &ErrorSink) -> anyhow::Result<()> {
+    if let Some(max_errors) = options.max_errors {
+        let count = errors.count();
+        if count > max_errors {
+            errors.flush()?;
+            anyhow::bail!(
+                "{} addresses could not be parsed, more than the allowed {} (`max_errors`).",
+                count,
+                max_errors
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Whether longitude/latitude are needed. They are columns of every output
+/// format, so the reprojection is only skipped when nothing is written.
+fn needs_lonlat(options: &ConvertOptions) -> bool {
+    !options.validate_only
+}
+
+/// Number of threads used with `--parallel-parse`.
+fn parse_threads() -> usize {
+    std::thread::available_parallelism().map_or(1, |n| n.get())
+}
+
+fn parse_file(
+    file: &FileRecord,
+    options: &ConvertOptions,
+    output_writer: &mut OutputWriter,
+    zip_file_index: &Option<usize>,
+    teryt_mapping: &Option<Arc<HashMap<String, Terc>>>,
+    errors: &ErrorSink,
+    report: Option<&ValidationReport>,
+) -> anyhow::Result<usize> {
+    let file_path = &file.path;
+    let source = source_name(file, zip_file_index);
+    let mut processed_rows = 0;
+    let filter = &options.filter;
+    match (&file.file_type, &options.schema_version) {
+        (FileType::XML, SchemaVersion::Model2012) if options.parallel_parse => {
+            let parser = parallel_parser_2012(
+                file_path,
+                options.batch_size,
+                parse_threads(),
+                parallel::CHUNK_SIZE,
+                options.skip_dictionary,
+            )?
+            .with_error_sink(errors.clone())
+            .with_filter(filter.clone())
+            .with_validation_report(report.cloned())
+            .with_verbose_unknown_tags(options.verbose_unknown_tags)
+            .with_fail_on_unknown_tag(options.fail_on_unknown_tag)
+            .with_normalize_whitespace(options.normalize_whitespace)
+            .with_untrimmed_columns(options.untrimmed_columns)
+            .with_reprojection(needs_lonlat(options))
+            .with_input_crs(options.input_crs)
+            .with_id_prefix(options.id_prefix.clone())
+            .with_status_raw(options.status_raw);
+            processed_rows +=
+                write_batches(parser, file_path, &source, options, output_writer, errors)?;
+        }
+        (FileType::XML, SchemaVersion::Model2012) if options.mmap => {
+            let mmap = map_file(file_path)?;
+            let parser =
+                parser_2012_from_bytes(&mmap, &options.batch_size, options.skip_dictionary)?
+                    .with_error_sink(errors.clone())
+                    .with_filter(filter.clone())
+                    .with_validation_report(report.cloned())
+                    .with_verbose_unknown_tags(options.verbose_unknown_tags)
+                    .with_fail_on_unknown_tag(options.fail_on_unknown_tag)
+                    .with_normalize_whitespace(options.normalize_whitespace)
+                    .with_untrimmed_columns(options.untrimmed_columns)
+                    .with_reprojection(needs_lonlat(options))
+                    .with_input_crs(options.input_crs)
+                    .with_id_prefix(options.id_prefix.clone())
+                    .with_status_raw(options.status_raw);
+            processed_rows +=
+                write_batches(parser, file_path, &source, options, output_writer, errors)?;
+        }
+        (FileType::XML, SchemaVersion::Model2012) => {
+            let parser =
+                parser_2012_uncompressed(file_path, &options.batch_size, options.skip_dictionary)?
+                    .with_error_sink(errors.clone())
+                    .with_filter(filter.clone())
+                    .with_validation_report(report.cloned())
+                    .with_verbose_unknown_tags(options.verbose_unknown_tags)
+                    .with_fail_on_unknown_tag(options.fail_on_unknown_tag)
+                    .with_normalize_whitespace(options.normalize_whitespace)
+                    .with_untrimmed_columns(options.untrimmed_columns)
+                    .with_reprojection(needs_lonlat(options))
+                    .with_input_crs(options.input_crs)
+                    .with_id_prefix(options.id_prefix.clone())
+                    .with_status_raw(options.status_raw);
+            processed_rows +=
+                write_batches(parser, file_path, &source, options, output_writer, errors)?;
+        }
+        (FileType::ZIP, SchemaVersion::Model2012) => {
+            let f = File::open(file.read_path())
+                .with_context(|| format!("Failed to open file: `{}`.", &file_path.display()))?;
+            let mut archive = ZipArchive::new(f).with_context(|| {
+                format!("Failed to decompress ZIP file: `{}`.", &file_path.display())
+            })?;
+            let parser = parser_2012_zip(
+                &mut archive,
+                &options.batch_size,
+                zip_file_index.unwrap(),
+                options.skip_dictionary,
+            )?
+            .with_error_sink(errors.clone())
+            .with_filter(filter.clone())
+            .with_validation_report(report.cloned())
+            .with_verbose_unknown_tags(options.verbose_unknown_tags)
+            .with_fail_on_unknown_tag(options.fail_on_unknown_tag)
+            .with_normalize_whitespace(options.normalize_whitespace)
+            .with_untrimmed_columns(options.untrimmed_columns)
+            .with_reprojection(needs_lonlat(options))
+            .with_input_crs(options.input_crs)
+            .with_id_prefix(options.id_prefix.clone())
+            .with_status_raw(options.status_raw);
+            processed_rows +=
+                write_batches(parser, file_path, &source, options, output_writer, errors)?;
+        }
+        (FileType::XML, SchemaVersion::Model2021) if options.parallel_parse => {
+            let parser = get_address_parser_2021_parallel(
+                file_path,
+                &options.batch_size,
+                teryt_mapping.as_ref().unwrap(),
+                parse_threads(),
+            )?
+            .with_error_sink(errors.clone())
+            .with_filter(filter.clone())
+            .with_validation_report(report.cloned())
+            .with_verbose_unknown_tags(options.verbose_unknown_tags)
+            .with_fail_on_unknown_tag(options.fail_on_unknown_tag)
+            .with_normalize_whitespace(options.normalize_whitespace)
+            .with_untrimmed_columns(options.untrimmed_columns)
+            .with_reprojection(needs_lonlat(options))
+            .with_input_crs(options.input_crs)
+            .with_street_types(options.street_types.clone())
+            .with_join_audit(options.join_audit.clone());
+            processed_rows +=
+                write_batches(parser, file_path, &source, options, output_writer, errors)?;
+        }
+        (FileType::XML, SchemaVersion::Model2021) if options.mmap => {
+            let mmap = map_file(file_path)?;
+            let parser = get_address_parser_2021_mmap(
+                &mmap,
+                &options.batch_size,
+                teryt_mapping.as_ref().unwrap(),
+            )?
+            .with_error_sink(errors.clone())
+            .with_filter(filter.clone())
+            .with_validation_report(report.cloned())
+            .with_verbose_unknown_tags(options.verbose_unknown_tags)
+            .with_fail_on_unknown_tag(options.fail_on_unknown_tag)
+            .with_normalize_whitespace(options.normalize_whitespace)
+            .with_untrimmed_columns(options.untrimmed_columns)
+            .with_reprojection(needs_lonlat(options))
+            .with_input_crs(options.input_crs)
+            .with_street_types(options.street_types.clone())
+            .with_join_audit(options.join_audit.clone());
+            processed_rows +=
+                write_batches(parser, file_path, &source, options, output_writer, errors)?;
+        }
+        (FileType::XML, SchemaVersion::Model2021) => {
+            let parser = get_address_parser_2021_uncompressed(
+                file_path,
+                &options.batch_size,
+                teryt_mapping.as_ref().unwrap(),
+            )?
+            .with_error_sink(errors.clone())
+            .with_filter(filter.clone())
+            .with_validation_report(report.cloned())
+            .with_verbose_unknown_tags(options.verbose_unknown_tags)
+            .with_fail_on_unknown_tag(options.fail_on_unknown_tag)
+            .with_normalize_whitespace(options.normalize_whitespace)
+            .with_untrimmed_columns(options.untrimmed_columns)
+            .with_reprojection(needs_lonlat(options))
+            .with_input_crs(options.input_crs)
+            .with_street_types(options.street_types.clone())
+            .with_join_audit(options.join_audit.clone());
+            processed_rows +=
+                write_batches(parser, file_path, &source, options, output_writer, errors)?;
+        }
+        (FileType::ZIP, SchemaVersion::Model2021) => {
+            let f = File::open(file.read_path())
+                .with_context(|| format!("Failed to open file: `{}`.", &file_path.display()))?;
+            let mut archive = ZipArchive::new(f).with_context(|| {
+                format!("Failed to decompress ZIP file: `{}`.", &file_path.display())
+            })?;
+            let parser = get_address_parser_2021_zip(
+                &mut archive,
+                &options.batch_size,
+                teryt_mapping.as_ref().unwrap(),
+                zip_file_index.unwrap(),
+            )?
+            .with_error_sink(errors.clone())
+            .with_filter(filter.clone())
+            .with_validation_report(report.cloned())
+            .with_verbose_unknown_tags(options.verbose_unknown_tags)
+            .with_fail_on_unknown_tag(options.fail_on_unknown_tag)
+            .with_normalize_whitespace(options.normalize_whitespace)
+            .with_untrimmed_columns(options.untrimmed_columns)
+            .with_reprojection(needs_lonlat(options))
+            .with_input_crs(options.input_crs)
+            .with_street_types(options.street_types.clone())
+            .with_join_audit(options.join_audit.clone());
+            processed_rows +=
+                write_batches(parser, file_path, &source, options, output_writer, errors)?;
+        }
+    }
+    Ok(processed_rows)
+}
+
+/// Parses `inputs` and writes their addresses to `output_writer`. Returns the
+/// reports of the parsed files and the skipped files inside ZIP archives.
+/// Stops after the current batch when `options` is interrupted. Inputs
+/// written completely before a resumed run of `checkpointer` are not parsed.
+fn read_inputs(
+    inputs: &[FileRecord],
+    options: &ConvertOptions,
+    output_writer: &mut OutputWriter,
+    teryt_mapping: &Option<Arc<HashMap<String, Terc>>>,
+    errors: &ErrorSink,
+    report: Option<&ValidationReport>,
+    checkpointer: Option<&Checkpointer>,
+) -> anyhow::Result<(Vec<FileReport>, Vec<SkippedEntry>)> {
+    let mut files = Vec::new();
+    let mut skipped_entries = Vec::new();
+    'files: for (file_counter, file) in inputs.iter().enumerate() {
+        if options.is_interrupted() {
+            break;
+        }
+        progress!(
+            "🪓 Processing file ({}/{})({}): `{}`, size: {:.2}MB.",
+            file_counter + 1,
+            inputs.len(),
+            &file.file_type,
+            &file.path.display(),
+            (file.size_in_bytes as f64 / 1024.0 / 1024.0)
+        );
+        match file.file_type {
+            FileType::XML => {
+                if let Some(rows) = resumed_rows(checkpointer, &file.path, None) {
+                    files.push(FileReport {
+                        path: file.path.clone(),
+                        zip_entry: None,
+                        rows,
+                    });
+                    continue;
+                }
+                progress!("Parsing data...");
+                let rows = parse_file(
+                    file,
+                    options,
+                    output_writer,
+                    &None,
+                    teryt_mapping,
+                    errors,
+                    report,
+                )?;
+                // an interrupted input was not read to the end
+                if let Some(checkpointer) = checkpointer
+                    && !options.is_interrupted()
+                {
+                    checkpointer.complete_input();
+                }
+                files.push(FileReport {
+                    path: file.path.clone(),
+                    zip_entry: None,
+                    rows,
+                });
+            }
+            FileType::ZIP => {
+                skipped_entries.extend(file.skipped_entries.iter().cloned());
+                let files_to_parse = file
+                    .compressed_files
+                    .as_ref()
+                    .unwrap()
+                    .iter()
+                    .filter(|f| f.to_be_parsed);
+                for compressed_file in files_to_parse {
+                    if options.is_interrupted() {
+                        break 'files;
+                    }
+                    let entry = Some(compressed_file.name.as_str());
+                    if let Some(rows) = resumed_rows(checkpointer, &file.path, entry) {
+                        files.push(FileReport {
+                            path: file.path.clone(),
+                            zip_entry: Some(compressed_file.name.clone()),
+                            rows,
+                        });
+                        continue;
+                    }
+                    progress!("Decompressing file: {}", compressed_file.name);
+                    let rows = match parse_file(
+                        file,
+                        options,
+                        output_writer,
+                        &Some(compressed_file.index),
+                        teryt_mapping,
+                        errors,
+                        report,
+                    ) {
+                        Ok(rows) => rows,
+                        Err(err)
+                            if options.skip_bad_entries
+                                && check_error_count(options, errors).is_ok() =>
+                        {
+                            println!(
+                                "Warning: skipping file `{}` inside ZIP archive `{}`: {:#}",
+                                compressed_file.name,
+                                file.path.display(),
+                                err
+                            );
+                            skipped_entries.push(SkippedEntry {
+                                path: file.path.clone(),
+                                entry: compressed_file.name.clone(),
+                                error: format!("{:#}", err),
+                            });
+                            continue;
+                        }
+                        Err(err) => return Err(err),
+                    };
+                    if let Some(checkpointer) = checkpointer
+                        && !options.is_interrupted()
+                    {
+                        checkpointer.complete_input();
+                    }
+                    files.push(FileReport {
+                        path: file.path.clone(),
+                        zip_entry: Some(compressed_file.name.clone()),
+                        rows,
+                    });
+                }
+            }
+        }
+    }
+    Ok((files, skipped_entries))
+}
+
+/// Starts reading the input `path` (`zip_entry` inside it) with
+/// `checkpointer`. Returns its number of rows if it was written completely
+/// before the run was resumed.
+fn resumed_rows(
+    checkpointer: Option<&Checkpointer>,
+    path: &Path,
+    zip_entry: Option<&str>,
+) -> Option<usize> {
+    let rows = checkpointer?.start_input(path, zip_entry)?;
+    progress!(
+        "Skipping `{}`{}, it was written before the run was resumed.",
+        path.display(),
+        zip_entry.map_or_else(String::new, |entry| format!(" ({})", entry))
+    );
+    Some(rows)
+}
+
+/// Rejects a `rows_per_file` or `grid_size` of 0. The CLI checks these too,
+/// naming its flags instead.
+fn check_options(options: &ConvertOptions) -> anyhow::Result<()> {
+    if options.rows_per_file == Some(0) {
+        anyhow::bail!("`rows_per_file` must be greater than 0.");
+    }
+    if options.grid_size == Some(0) {
+        anyhow::bail!("`grid_size` must be greater than 0.");
+    }
+    Ok(())
+}
+
+/// Hash of the options that change the written addresses or how they are
+/// written, other than `output_path` and `rows_per_file`. A checkpoint can
+/// only be resumed with the same hash.
+fn checkpoint_options_hash(options: &ConvertOptions) -> u64 {
+    let filter = &options.filter;
+    let mut column_renames: Vec<_> = options.column_renames.iter().collect();
+    column_renames.sort();
+    #[cfg(feature = "geoparquet")]
+    let points_only_schema = options.points_only_schema;
+    #[cfg(not(feature = "geoparquet"))]
+    let points_only_schema = false;
+    let description = format!(
+        "{:?}",
+        (
+            (
+                options.schema_version,
+                options.output_format,
+                options.crs,
+                &options.teryt_path,
+                &options.teryt_date,
+                options.download_teryt,
+                points_only_schema,
+            ),
+            (
+                options.csv_delimiter,
+                options.decimal_comma,
+                &options.csv_null_string,
+                &options.csv_date_format,
+                &options.csv_timestamp_format,
+                options.timestamp_timezone,
+            ),
+            (
+                filter.start_date,
+                filter.end_date,
+                filter.sample_rate,
+                filter.sample_seed,
+                filter.street,
+                filter.drop_null_geometry,
+            ),
+            (
+                &options.id_prefix,
+                options.status_raw,
+                options.skip_dictionary,
+                options.input_crs,
+                &options.street_types,
+                options.normalize_whitespace,
+                options.untrimmed_columns,
+                options.name_case,
+                options.coord_precision,
+            ),
+            (
+                column_renames,
+                options.normalize_names,
+                options.split_house_number,
+                options.split_valid_from,
+                options.add_full_address,
+                options.add_source_column,
+                options.grid_size,
+            ),
+        )
+    );
+    sample_hash(0, &description)
+}
+
+/// Checkpointer of `options.checkpoint_path`, continuing from the saved
+/// checkpoint with `options.resume`.
+fn checkpointer(options: &ConvertOptions) -> anyhow::Result<Option<Checkpointer>> {
+    let Some(path) = &options.checkpoint_path else {
+        return Ok(None);
+    };
+    let Some(rows_per_file) = options.rows_per_file else {
+        anyhow::bail!("Checkpoints require numbered output files (`rows_per_file`).");
+    };
+    if options.validate_only
+        || options.spatial_sort
+        || options.tail.is_some()
+        || !options.compare_with.is_empty()
+    {
+        anyhow::bail!(
+            "Checkpoints cannot be used with `validate_only`, `spatial_sort`, `tail` or `compare_with`."
+        );
+    }
+    let resumed = if options.resume {
+        Checkpoint::read(path)?
+    } else {
+        None
+    };
+    let options_hash = checkpoint_options_hash(options);
+    match &resumed {
+        Some(checkpoint)
+            if checkpoint.output_path != options.output_path
+                || checkpoint.rows_per_file != rows_per_file =>
+        {
+            anyhow::bail!(
+                "Checkpoint `{}` was saved with output path `{}` and {} rows per file, they cannot change when resuming.",
+                path.display(),
+                checkpoint.output_path.display(),
+                checkpoint.rows_per_file
+            );
+        }
+        Some(checkpoint) if checkpoint.options_hash != options_hash => {
+            anyhow::bail!(
+                "Checkpoint `{}` was saved with other options changing the written addresses (filters, sampling, added columns, formatting), they cannot change when resuming.",
+                path.display()
+            );
+        }
+        Some(checkpoint) => progress!(
+            "Resuming after {} output files from checkpoint `{}`.",
+            checkpoint.output_files.len(),
+            path.display()
+        ),
+        None if options.resume => progress!(
+            "Checkpoint `{}` does not exist, starting from the beginning.",
+            path.display()
+        ),
+        None => {}
+    }
+    Ok(Some(Checkpointer::new(
+        path.clone(),
+        options.output_path.clone(),
+        rows_per_file,
+        options_hash,
+        resumed,
+    )))
+}
+
+/// Parses all `inputs` and writes the addresses to `output_path` (nothing is
+/// written with `validate_only`). Progress is printed to stdout. Errors are
+/// classified into [`PrgError`] variants by their causes.
+pub fn convert(options: ConvertOptions) -> Result<ConvertReport, PrgError> {
+    let start_time = Instant::now();
+    check_options(&options)?;
+    // disabled again (unless enabled before) when the conversion returns
+    let _timings = options.benchmark.then(timings::enable);
+    let errors = match &options.errors_to {
+        Some(path) => ErrorSink::to_file(path)?,
+        None => ErrorSink::default(),
+    };
+    let report = options.validate_only.then(ValidationReport::default);
+
+    let mut terc_catalog_date = None;
+    let mut terc_path = None;
+    let teryt_mapping: Option<Arc<HashMap<String, Terc>>> = match &options.schema_version {
+        SchemaVersion::Model2012 => None,
+        SchemaVersion::Model2021 => {
+            let terc = timings::time(Phase::Terc, || {
+                get_teryt_mapping(
+                    options.download_teryt,
+                    &options.teryt_api_username,
+                    &options.teryt_api_password,
+                    &options.teryt_path,
+                    &options.teryt_date,
+                )
+            })?;
+            terc_catalog_date = Some(terc.catalog_date);
+            terc_path = terc.source_path;
+            Some(Arc::new(terc.mapping))
+        }
+    };
+    let snapshot = if options.compare_with.is_empty() {
+        None
+    } else {
+        progress!("Reading addresses to compare with...");
+        let mut collected = OutputWriter::Collect {
+            batches: Vec::new(),
+        };
+        // only the joins of the inputs are audited
+        let compare_options = ConvertOptions {
+            join_audit: None,
+            ..options.clone()
+        };
+        read_inputs(
+            &options.compare_with,
+            &compare_options,
+            &mut collected,
+            &teryt_mapping,
+            &errors,
+            None,
+            None,
+        )?;
+        let OutputWriter::Collect { batches } = collected else {
+            unreachable!()
+        };
+        Some(Snapshot::new(batches).context("Failed to index addresses to compare with.")?)
+    };
+    let checkpointer = checkpointer(&options)?;
+    let mut output_writer = OutputWriter::new(
+        &options,
+        snapshot,
+        terc_catalog_date.as_deref(),
+        checkpointer.as_ref(),
+    )?;
+    let (files, skipped_entries) = read_inputs(
+        &options.inputs,
+        &options,
+        &mut output_writer,
+        &teryt_mapping,
+        &errors,
+        report.as_ref(),
+        checkpointer.as_ref(),
+    )?;
+    let interrupted = options.is_interrupted();
+    if interrupted {
+        progress!("Reading was interrupted, finalizing output file...");
+    }
+    let voivodeship_counts = output_writer.voivodeship_counts();
+    let changes = output_writer.change_counts();
+    check_error_count(&options, &errors)?;
+    let mut output_files = output_writer.finish()?;
+    let voivodeship_counts =
+        voivodeship_counts.map(|counts| std::mem::take(&mut *counts.lock().unwrap()));
+    if options.rows_per_file.is_none() && !options.validate_only {
+        output_files.push(options.output_path.clone());
+    }
+    errors.flush()?;
+    let join_audit_records = options
+        .join_audit
+        .as_ref()
+        .map(JoinAudit::write)
+        .transpose()?;
+    Ok(ConvertReport {
+        total_rows: files.iter().map(|f| f.rows).sum(),
+        skipped_rows: errors.count(),
+        null_geometry_rows: options.filter.null_geometry_dropped(),
+        duration: start_time.elapsed(),
+        files,
+        terc_catalog_date,
+        terc_path,
+        validation: report.map(|r| r.counts()),
+        skipped_entries,
+        interrupted,
+        voivodeship_counts,
+        changes,
+        output_files,
+        join_audit_records,
+        phase_durations: options.benchmark.then(timings::phase_durations),
+    })
+}
+
+#[test]
+fn test_convert_2012_to_csv() {
+    let output = tempfile::NamedTempFile::new().unwrap();
+    let input = FileRecord::from_path(
+        PathBuf::from("fixtures/sample_model2012.xml"),
+        &SchemaVersion::Model2012,
+        false,
+    )
+    .unwrap();
+    let report = convert(ConvertOptions::new(
+        vec![input],
+        output.path().to_path_buf(),
+        SchemaVersion::Model2012,
+    ))
+    .unwrap();
+    assert_eq!(report.total_rows, 2);
+    assert_eq!(report.skipped_rows, 0);
+    assert_eq!(report.files.len(), 1);
+    assert_eq!(report.files[0].rows, 2);
+    assert!(report.validation.is_none());
+    assert!(report.voivodeship_counts.is_none());
+    let content = std::fs::read_to_string(output.path()).unwrap();
+    assert_eq!(content.lines().count(), 3);
+    assert!(content.starts_with("przestrzen_nazw,"));
+}
+
+#[cfg(feature = "protobuf")]
+#[test]
+fn test_convert_protobuf() {
+    use prost::Message;
+    let output = tempfile::NamedTempFile::new().unwrap();
+    let input = FileRecord::from_path(
+        PathBuf::from("fixtures/sample_model2012.xml"),
+        &SchemaVersion::Model2012,
+        false,
+    )
+    .unwrap();
+    let mut options = ConvertOptions::new(
+        vec![input],
+        output.path().to_path_buf(),
+        SchemaVersion::Model2012,
+    );
+    options.output_format = OutputFormat::Protobuf;
+    // columns added by other options are not written
+    options.grid_size = Some(100);
+    let report = convert(options).unwrap();
+    assert_eq!(report.total_rows, 2);
+    let bytes = std::fs::read(output.path()).unwrap();
+    let mut remaining = bytes.as_slice();
+    let mut ids = Vec::new();
+    while !remaining.is_empty() {
+        let address = crate::protobuf::Address::decode_length_delimited(&mut remaining).unwrap();
+        assert_eq!(address.wojewodztwo, "lubuskie");
+        ids.push(address.lokalny_id);
+    }
+    assert_eq!(
+        ids,
+        vec![
+            "fd9c9319-0a6a-44b4-972a-1e6c4ec0d4ca",
+            "5baa8bef-75ef-4241-a2fe-9d4137845693"
+        ]
+    );
+}
+
+#[test]
+fn test_convert_name_case() {
+    let output = tempfile::NamedTempFile::new().unwrap();
+    let input = FileRecord::from_path(
+        PathBuf::from("fixtures/sample_model2012.xml"),
+        &SchemaVersion::Model2012,
+        false,
+    )
+    .unwrap();
+    let mut options = ConvertOptions::new(
+        vec![input],
+        output.path().to_path_buf(),
+        SchemaVersion::Model2012,
+    );
+    options.name_case = NameCase::Title;
+    convert(options).unwrap();
+    let content = std::fs::read_to_string(output.path()).unwrap();
+    // voivodeship and county are lowercase in the input
+    assert!(
+        content.contains(",Lubuskie,0804,Nowosolski,"),
+        "{}",
+        content
+    );
+    assert!(content.contains(",Kolsko,"), "{}", content);
+    assert!(content.contains(",Konotop,"), "{}", content);
+    assert!(!content.contains(",lubuskie,"), "{}", content);
+}
+
+#[test]
+fn test_convert_max_errors() {
+    let xml = std::fs::read_to_string("fixtures/sample_model2012.xml")
+        .unwrap()
+        .replacen(
+            "2022-09-09</prg-ad:waznyOd>",
+            "2022-13-45</prg-ad:waznyOd>",
+            1,
+        );
+    let input_file = tempfile::Builder::new().suffix(".xml").tempfile().unwrap();
+    std::fs::write(input_file.path(), xml).unwrap();
+    let convert_with_max_errors = |max_errors| {
+        let output = tempfile::NamedTempFile::new().unwrap();
+        let input = FileRecord::from_path(
+            input_file.path().to_path_buf(),
+            &SchemaVersion::Model2012,
+            false,
+        )
+        .unwrap();
+        let mut options = ConvertOptions::new(
+            vec![input],
+            output.path().to_path_buf(),
+            SchemaVersion::Model2012,
+        );
+        options.max_errors = max_errors;
+        convert(options)
+    };
+    let err = convert_with_max_errors(Some(0)).unwrap_err();
+    assert!(format!("{:#}", err).contains("`max_errors`"), "{:#}", err);
+    assert_eq!(convert_with_max_errors(Some(1)).unwrap().skipped_rows, 1);
+    assert_eq!(convert_with_max_errors(None).unwrap().total_rows, 1);
+}
+
+#[test]
+fn test_convert_error_kinds() {
+    let output = tempfile::NamedTempFile::new().unwrap();
+    let convert_file = |path: &Path, schema_version: SchemaVersion, teryt_path: Option<&Path>| {
+        let input = FileRecord::from_path(path.to_path_buf(), &schema_version, false).unwrap();
+        let mut options =
+            ConvertOptions::new(vec![input], output.path().to_path_buf(), schema_version);
+        options.teryt_path = teryt_path.map(Path::to_path_buf);
+        convert(options)
+    };
+    // the file ends inside an address
+    let xml = std::fs::read_to_string("fixtures/sample_model2012.xml").unwrap();
+    let truncated = tempfile::Builder::new().suffix(".xml").tempfile().unwrap();
+    std::fs::write(
+        truncated.path(),
+        &xml[..xml.find("</prg-ad:PRG_PunktAdresowy>").unwrap()],
+    )
+    .unwrap();
+    let result = convert_file(truncated.path(), SchemaVersion::Model2012, None);
+    assert!(
+        matches!(result, Err(PrgError::Xml(_))),
+        "{:?}",
+        result.err()
+    );
+
+    let input = Path::new("fixtures/sample_model2021.xml");
+    let result = convert_file(
+        input,
+        SchemaVersion::Model2021,
+        Some(Path::new("fixtures/definitely_nonexistent.xml")),
+    );
+    assert!(matches!(result, Err(PrgError::Io(_))), "{:?}", result.err());
+    let result = convert_file(input, SchemaVersion::Model2021, Some(truncated.path()));
+    assert!(
+        matches!(result, Err(PrgError::TercParse(_))),
+        "{:?}",
+        result.err()
+    );
+}
+
+#[test]
+fn test_convert_spatial_sort() {
+    let input = FileRecord::from_path(
+        PathBuf::from("fixtures/sample_model2012.xml"),
+        &SchemaVersion::Model2012,
+        false,
+    )
+    .unwrap();
+    let mut options = ConvertOptions::new(vec![input], PathBuf::new(), SchemaVersion::Model2012);
+    options.batch_size = 1;
+    // the batches written by a sorting writer with `buffer_rows`
+    let write_sorted_batches = |buffer_rows| {
+        let mut output_writer = OutputWriter::SpatialSort {
+            batches: Vec::new(),
+            num_rows: 0,
+            buffer_rows,
+            inner: Box::new(OutputWriter::Collect {
+                batches: Vec::new(),
+            }),
+        };
+        let errors = ErrorSink::default();
+        read_inputs(
+            &options.inputs,
+            &options,
+            &mut output_writer,
+            &None,
+            &errors,
+            None,
+            None,
+        )
+        .unwrap();
+        let OutputWriter::SpatialSort {
+            batches, mut inner, ..
+        } = output_writer
+        else {
+            unreachable!()
+        };
+        write_sorted(batches, &mut inner).unwrap();
+        let OutputWriter::Collect { batches } = *inner else {
+            unreachable!()
+        };
+        batches
+    };
+
+    // all rows are sorted at once
+    let batches = write_sorted_batches(None);
+    assert_eq!(batches.len(), 1);
+    let coordinate = |name: &str| {
+        batches[0]
+            .column_by_name(name)
+            .unwrap()
+            .as_primitive::<arrow::datatypes::Float64Type>()
+            .clone()
+    };
+    let (xs, ys) = (coordinate("x_epsg_2180"), coordinate("y_epsg_2180"));
+    let indices: Vec<u32> = (0..xs.len())
+        .map(|i| crate::common::hilbert_index(xs.value(i), ys.value(i)))
+        .collect();
+    assert_eq!(indices.len(), 2);
+    assert!(indices.is_sorted(), "{:?}", indices);
+    // every batch is its own run
+    assert_eq!(write_sorted_batches(Some(1)).len(), 2);
+}
+
+#[test]
+fn test_convert_drop_null_geometry() {
+    let xml = std::fs::read_to_string("fixtures/sample_model2012.xml")
+        .unwrap()
+        .replacen("456005.140000001 287772.37", "NaN NaN", 1);
+    let input_file = tempfile::Builder::new().suffix(".xml").tempfile().unwrap();
+    std::fs::write(input_file.path(), xml).unwrap();
+    let convert_with_filter = |drop_null_geometry| {
+        let output = tempfile::NamedTempFile::new().unwrap();
+        let input = FileRecord::from_path(
+            input_file.path().to_path_buf(),
+            &SchemaVersion::Model2012,
+            false,
+        )
+        .unwrap();
+        let mut options = ConvertOptions::new(
+            vec![input],
+            output.path().to_path_buf(),
+            SchemaVersion::Model2012,
+        );
+        options.filter.drop_null_geometry = drop_null_geometry;
+        let report = convert(options).unwrap();
+        let content = std::fs::read_to_string(output.path()).unwrap();
+        (report, content)
+    };
+    let (report, content) = convert_with_filter(false);
+    assert_eq!(report.total_rows, 2);
+    assert_eq!(report.null_geometry_rows, 0);
+    assert_eq!(content.lines().count(), 3);
+    let (report, content) = convert_with_filter(true);
+    assert_eq!(report.total_rows, 1);
+    assert_eq!(report.null_geometry_rows, 1);
+    assert_eq!(report.skipped_rows, 0);
+    assert_eq!(content.lines().count(), 2);
+    assert!(content.contains("287751.0102"));
+}
+
+#[test]
+fn test_rollover_path() {
+    assert_eq!(
+        rollover_path(Path::new("out/output.csv"), 1),
+        PathBuf::from("out/output-00001.csv")
+    );
+    assert_eq!(
+        rollover_path(Path::new("output"), 12),
+        PathBuf::from("output-00012")
+    );
+    assert_eq!(
+        rollover_path(Path::new("out/{n}/part-{n}.parquet"), 3),
+        PathBuf::from("out/00003/part-00003.parquet")
+    );
+}
+
+#[test]
+fn test_convert_rejects_zero_sizes() {
+    let output_dir = tempfile::tempdir().unwrap();
+    let input = FileRecord::from_path(
+        PathBuf::from("fixtures/sample_model2012.xml"),
+        &SchemaVersion::Model2012,
+        false,
+    )
+    .unwrap();
+    let mut options = ConvertOptions::new(
+        vec![input],
+        output_dir.path().join("output.csv"),
+        SchemaVersion::Model2012,
+    );
+    options.rows_per_file = Some(0);
+    let err = convert(options.clone()).unwrap_err();
+    assert!(err.to_string().contains("`rows_per_file`"), "{}", err);
+    assert_eq!(std::fs::read_dir(output_dir.path()).unwrap().count(), 0);
+    options.rows_per_file = None;
+    options.grid_size = Some(0);
+    let err = convert(options).unwrap_err();
+    assert!(err.to_string().contains("`grid_size`"), "{}", err);
+    assert_eq!(std::fs::read_dir(output_dir.path()).unwrap().count(), 0);
+}
+
+#[test]
+fn test_convert_rows_per_file() {
+    let output_dir = tempfile::tempdir().unwrap();
+    let input = FileRecord::from_path(
+        PathBuf::from("fixtures/sample_model2012.xml"),
+        &SchemaVersion::Model2012,
+        false,
+    )
+    .unwrap();
+    let mut options = ConvertOptions::new(
+        vec![input],
+        output_dir.path().join("output.csv"),
+        SchemaVersion::Model2012,
+    );
+    options.rows_per_file = Some(1);
+    let report = convert(options).unwrap();
+    assert_eq!(
+        report.output_files,
+        vec![
+            output_dir.path().join("output-00001.csv"),
+            output_dir.path().join("output-00002.csv"),
+        ]
+    );
+    for path in &report.output_files {
+        let content = std::fs::read_to_string(path).unwrap();
+        assert_eq!(content.lines().count(), 2);
+        assert!(content.starts_with("przestrzen_nazw,"));
+    }
+    assert!(!output_dir.path().join("output.csv").exists());
+}
+
+#[test]
+fn test_convert_benchmark() {
+    let output = tempfile::NamedTempFile::new().unwrap();
+    let input = FileRecord::from_path(
+        PathBuf::from("fixtures/sample_model2012.xml"),
+        &SchemaVersion::Model2012,
+        false,
+    )
+    .unwrap();
+    let mut options = ConvertOptions::new(
+        vec![input],
+        output.path().to_path_buf(),
+        SchemaVersion::Model2012,
+    );
+    assert_eq!(convert(options.clone()).unwrap().phase_durations, None);
+    options.benchmark = true;
+    let phase_durations = convert(options).unwrap().phase_durations.unwrap();
+    // timings are enabled only for the conversion
+    assert!(!timings::enabled());
+    assert_eq!(
+        phase_durations
+            .iter()
+            .map(|(phase, _)| *phase)
+            .collect::<Vec<_>>(),
+        Phase::ALL
+    );
+    for (phase, duration) in phase_durations {
+        if matches!(
+            phase,
+            Phase::Dictionaries | Phase::Reprojection | Phase::ArrowBuilding | Phase::Writing
+        ) {
+            assert!(!duration.is_zero(), "{}", phase);
+        }
+    }
+}
+
+#[test]
+fn test_convert_checkpoint_resume() {
+    let output_dir = tempfile::tempdir().unwrap();
+    let input_path = PathBuf::from("fixtures/sample_model2012.xml");
+    let input =
+        FileRecord::from_path(input_path.clone(), &SchemaVersion::Model2012, false).unwrap();
+    let checkpoint_path = output_dir.path().join("checkpoint.json");
+    let mut options = ConvertOptions::new(
+        vec![input],
+        output_dir.path().join("output.csv"),
+        SchemaVersion::Model2012,
+    );
+    options.rows_per_file = Some(1);
+    options.checkpoint_path = Some(checkpoint_path.clone());
+    let report = convert(options.clone()).unwrap();
+    let first = output_dir.path().join("output-00001.csv");
+    let second = output_dir.path().join("output-00002.csv");
+    let checkpoint = Checkpoint::read(&checkpoint_path).unwrap().unwrap();
+    assert_eq!(checkpoint.output_files, report.output_files);
+    let input = checkpoint.input(&input_path, None).unwrap();
+    assert_eq!((input.rows, input.complete), (2, true));
+
+    // a run stopped after the first file continues with the second address
+    let expected = std::fs::read_to_string(&second).unwrap();
+    std::fs::remove_file(&second).unwrap();
+    Checkpoint {
+        output_files: vec![first.clone()],
+        inputs: vec![crate::checkpoint::InputProgress {
+            path: input_path.clone(),
+            zip_entry: None,
+            rows: 1,
+            complete: false,
+        }],
+        ..checkpoint
+    }
+    .write(&checkpoint_path)
+    .unwrap();
+    options.resume = true;
+    let report = convert(options.clone()).unwrap();
+    assert_eq!(report.output_files, vec![first, second.clone()]);
+    assert_eq!(report.total_rows, 2);
+    assert_eq!(std::fs::read_to_string(&second).unwrap(), expected);
+
+    // a completed run writes nothing more
+    let report = convert(options.clone()).unwrap();
+    assert_eq!(report.output_files.len(), 2);
+    assert!(!output_dir.path().join("output-00003.csv").exists());
+
+    // the output cannot change when resuming
+    let mut changed = options.clone();
+    changed.rows_per_file = Some(2);
+    assert!(convert(changed).is_err());
+    // neither can the addresses written
+    for change in [
+        |o: &mut ConvertOptions| o.filter.sample_rate = Some(0.5),
+        |o: &mut ConvertOptions| o.add_source_column = true,
+        |o: &mut ConvertOptions| o.name_case = NameCase::Lower,
+    ] {
+        let mut changed = options.clone();
+        change(&mut changed);
+        let err = convert(changed).unwrap_err();
+        assert!(err.to_string().contains("other options"), "{}", err);
+    }
+}
+
+#[test]
+fn test_convert_checkpoint_resume_after_interrupt() {
+    let output_dir = tempfile::tempdir().unwrap();
+    // the two sample addresses repeated, so the input is interrupted while it
+    // is being read
+    let sample = std::fs::read_to_string("fixtures/sample_model2012.xml").unwrap();
+    let lines: Vec<&str> = sample.lines().collect();
+    let xml = [
+        lines[..7].join("\n"),
+        lines[7..93].join("\n").repeat(2000),
+        lines[93..].join("\n"),
+    ]
+    .join("\n");
+    let input_path = output_dir.path().join("input.xml");
+    std::fs::write(&input_path, xml).unwrap();
+    let input =
+        FileRecord::from_path(input_path.clone(), &SchemaVersion::Model2012, false).unwrap();
+    let checkpoint_path = output_dir.path().join("checkpoint.json");
+    let mut options = ConvertOptions::new(
+        vec![input],
+        output_dir.path().join("output.csv"),
+        SchemaVersion::Model2012,
+    );
+    options.batch_size = 10;
+    options.rows_per_file = Some(100);
+    options.checkpoint_path = Some(checkpoint_path.clone());
+
+    // interrupted once the first output file was finished
+    let interrupt = Arc::new(AtomicBool::new(false));
+    options.interrupt = Some(interrupt.clone());
+    let watcher = {
+        let checkpoint_path = checkpoint_path.clone();
+        std::thread::spawn(move || {
+            while !checkpoint_path.exists() {
+                std::thread::sleep(std::time::Duration::from_millis(1));
+            }
+            interrupt.store(true, Ordering::SeqCst);
+        })
+    };
+    let report = convert(options.clone()).unwrap();
+    watcher.join().unwrap();
+    assert!(report.interrupted);
+    assert!(report.total_rows < 4000);
+    let checkpoint = Checkpoint::read(&checkpoint_path).unwrap().unwrap();
+    let input = checkpoint.input(&input_path, None).unwrap();
+    assert_eq!((input.rows, input.complete), (report.total_rows, false));
+
+    options.interrupt = None;
+    options.resume = true;
+    let report = convert(options).unwrap();
+    assert!(!report.interrupted);
+    assert_eq!(report.total_rows, 4000);
+    let written_rows: usize = report
+        .output_files
+        .iter()
+        .map(|file| std::fs::read_to_string(file).unwrap().lines().count() - 1)
+        .sum();
+    assert_eq!(written_rows, 4000);
+}
+
+#[cfg(feature = "geoparquet")]
+#[test]
+fn test_convert_rows_per_file_geoparquet() {
+    let output_dir = tempfile::tempdir().unwrap();
+    let input = FileRecord::from_path(
+        PathBuf::from("fixtures/sample_model2012.xml"),
+        &SchemaVersion::Model2012,
+        false,
+    )
+    .unwrap();
+    let mut options = ConvertOptions::new(
+        vec![input],
+        output_dir.path().join("output-{n}.parquet"),
+        SchemaVersion::Model2012,
+    );
+    options.output_format = OutputFormat::GeoParquet;
+    options.rows_per_file = Some(1);
+    let report = convert(options).unwrap();
+    assert_eq!(
+        report.output_files,
+        vec![
+            output_dir.path().join("output-00001.parquet"),
+            output_dir.path().join("output-00002.parquet"),
+        ]
+    );
+    for path in &report.output_files {
+        // every file was finished with its own footer and `geo` metadata
+        let reader = parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder::try_new(
+            File::open(path).unwrap(),
+        )
+        .unwrap();
+        let metadata = reader.metadata().file_metadata();
+        assert_eq!(metadata.num_rows(), 1);
+        assert!(
+            metadata
+                .key_value_metadata()
+                .is_some_and(|kv| kv.iter().any(|kv| kv.key == "geo"))
+        );
+    }
+}
+
+#[test]
+#[cfg(feature = "geoparquet")]
+fn test_convert_parquet_bloom_filter() {
+    let output = tempfile::Builder::new()
+        .suffix(".parquet")
+        .tempfile()
+        .unwrap();
+    let convert_with_bloom_filter = |format, columns: &[&str]| {
+        let input = FileRecord::from_path(
+            PathBuf::from("fixtures/sample_model2012.xml"),
+            &SchemaVersion::Model2012,
+            false,
+        )
+        .unwrap();
+        let mut options = ConvertOptions::new(
+            vec![input],
+            output.path().to_path_buf(),
+            SchemaVersion::Model2012,
+        );
+        options.output_format = format;
+        options.parquet_bloom_filter_columns = columns.iter().map(|c| c.to_string()).collect();
+        convert(options)
+    };
+    // whether the columns have a bloom filter in the written file
+    let bloom_filters = |names: &[&str]| {
+        let reader = parquet::file::serialized_reader::SerializedFileReader::new(
+            File::open(output.path()).unwrap(),
+        )
+        .unwrap();
+        let metadata = parquet::file::reader::FileReader::metadata(&reader);
+        let row_group = metadata.row_group(0);
+        names
+            .iter()
+            .map(|name| {
+                let column = row_group
+                    .columns()
+                    .iter()
+                    .find(|c| c.column_path().string() == *name)
+                    .unwrap();
+                column.bloom_filter_offset().is_some()
+            })
+            .collect::<Vec<_>>()
+    };
+    for format in [OutputFormat::GeoParquet, OutputFormat::Parquet] {
+        convert_with_bloom_filter(format, &["uuid", "ulica"]).unwrap();
+        assert_eq!(
+            bloom_filters(&["lokalny_id", "ulica", "miejscowosc"]),
+            vec![true, true, false]
+        );
+    }
+    convert_with_bloom_filter(OutputFormat::Parquet, &[]).unwrap();
+    assert_eq!(bloom_filters(&["lokalny_id"]), vec![false]);
+
+    let err = convert_with_bloom_filter(OutputFormat::Parquet, &["nope"]).unwrap_err();
+    assert!(
+        format!("{:#}", err).contains("Unknown column `nope`"),
+        "{:#}",
+        err
+    );
+    let err = convert_with_bloom_filter(OutputFormat::Parquet, &["x_epsg_2180"]).unwrap_err();
+    assert!(
+        format!("{:#}", err).contains("not a string column"),
+        "{:#}",
+        err
+    );
+}
+
+#[test]
+#[cfg(feature = "geoparquet")]
+fn test_convert_parquet_page_sizes() {
+    use parquet::basic::{Encoding, PageType};
+    use parquet::file::reader::{FileReader, SerializedFileReader};
+
+    let output = tempfile::Builder::new()
+        .suffix(".parquet")
+        .tempfile()
+        .unwrap();
+    let convert_with_page_sizes = |data_page_size, dictionary_page_size| {
+        let input = FileRecord::from_path(
+            PathBuf::from("fixtures/sample_model2012.xml"),
+            &SchemaVersion::Model2012,
+            false,
+        )
+        .unwrap();
+        let mut options = ConvertOptions::new(
+            vec![input],
+            output.path().to_path_buf(),
+            SchemaVersion::Model2012,
+        );
+        options.output_format = OutputFormat::Parquet;
+        // page sizes are checked after every written batch
+        options.batch_size = 1;
+        options.parquet_data_page_size = data_page_size;
+        options.parquet_dictionary_page_size = dictionary_page_size;
+        convert(options).unwrap();
+    };
+    // encodings of the data pages of `miejscowosc` in the written file
+    let data_page_encodings = || {
+        let reader = SerializedFileReader::new(File::open(output.path()).unwrap()).unwrap();
+        let row_group = reader.get_row_group(0).unwrap();
+        let column = row_group
+            .metadata()
+            .columns()
+            .iter()
+            .position(|c| c.column_path().string() == "miejscowosc")
+            .unwrap();
+        row_group
+            .get_column_page_reader(column)
+            .unwrap()
+            .map(|page| page.unwrap())
+            .filter(|page| page.page_type() != PageType::DICTIONARY_PAGE)
+            .map(|page| page.encoding())
+            .collect::<Vec<_>>()
+    };
+
+    // both addresses fit in one page by default
+    convert_with_page_sizes(None, None);
+    assert_eq!(data_page_encodings(), vec![Encoding::RLE_DICTIONARY]);
+    convert_with_page_sizes(Some(1), None);
+    assert_eq!(
+        data_page_encodings(),
+        vec![Encoding::RLE_DICTIONARY, Encoding::RLE_DICTIONARY]
+    );
+    // the dictionary is full after the first address
+    convert_with_page_sizes(Some(1), Some(1));
+    let encodings = data_page_encodings();
+    assert_eq!(encodings.len(), 2);
+    assert_eq!(encodings[0], Encoding::RLE_DICTIONARY);
+    assert_ne!(encodings[1], Encoding::RLE_DICTIONARY);
+}
+
+#[test]
+fn test_convert_workers_per_file_same_output() {
+    let convert_with_workers = |workers_per_file| {
+        let output = tempfile::NamedTempFile::new().unwrap();
+        let input = FileRecord::from_path(
+            PathBuf::from("fixtures/sample_model2012.xml"),
+            &SchemaVersion::Model2012,
+            false,
+        )
+        .unwrap();
+        let mut options = ConvertOptions::new(
+            vec![input],
+            output.path().to_path_buf(),
+            SchemaVersion::Model2012,
+        );
+        options.batch_size = 1;
+        options.workers_per_file = workers_per_file;
+        let report = convert(options).unwrap();
+        assert_eq!(report.total_rows, 2);
+        std::fs::read_to_string(output.path()).unwrap()
+    };
+    let inline = convert_with_workers(1);
+    assert_eq!(inline.lines().count(), 3);
+    assert_eq!(convert_with_workers(2), inline);
+}
+
+#[test]
+fn test_csv_null_string() {
+    use arrow::array::StringArray;
+
+    let path = PathBuf::from("fixtures/sample_model2012.xml");
+    let batch = crate::get_address_parser_2012_uncompressed(&path, &10)
+        .unwrap()
+        .next()
+        .unwrap()
+        .unwrap();
+    // first postcode null, second an empty string
+    let index = batch.schema().index_of("kod_pocztowy").unwrap();
+    let mut columns = batch.columns().to_vec();
+    columns[index] = Arc::new(StringArray::from(vec![None, Some("")]));
+    let batch = RecordBatch::try_new(batch.schema(), columns).unwrap();
+
+    let output = tempfile::NamedTempFile::new().unwrap();
+    let mut options = ConvertOptions::new(
+        vec![],
+        output.path().to_path_buf(),
+        SchemaVersion::Model2012,
+    );
+    options.csv_null_string = "\\N".to_string();
+    let mut writer = OutputWriter::new(&options, None, None, None).unwrap();
+    writer.write_batch(&batch).unwrap();
+    writer.finish().unwrap();
+    let content = std::fs::read_to_string(output.path()).unwrap();
+    let postcodes: Vec<&str> = content
+        .lines()
+        .skip(1)
+        .map(|line| line.split(',').nth(index).unwrap())
+        .collect();
+    assert_eq!(postcodes, ["\\N", ""]);
+}
+
+#[test]
+#[cfg(feature = "geoparquet")]
+fn test_row_group_per_voivodeship() {
+    let path = PathBuf::from("fixtures/sample_model2012.xml");
+    let sample = crate::get_address_parser_2012_uncompressed(&path, &10)
+        .unwrap()
+        .next()
+        .unwrap()
+        .unwrap();
+    // batches with the given `teryt_wojewodztwo` of their rows
+    let batch = |voivodeships: &[&str]| {
+        let rows = arrow::array::UInt32Array::from_iter_values(
+            (0..voivodeships.len() as u32).map(|row| row % 2),
+        );
+        let mut columns: Vec<ArrayRef> = sample
+            .columns()
+            .iter()
+            .map(|c| arrow::compute::take(c, &rows, None).unwrap())
+            .collect();
+        let index = sample.schema().index_of("teryt_wojewodztwo").unwrap();
+        columns[index] = Arc::new(arrow::array::StringArray::from(voivodeships.to_vec()));
+        RecordBatch::try_new(sample.schema(), columns).unwrap()
+    };
+    let row_group_sizes = |format, batches: &[&[&str]]| {
+        let output = tempfile::NamedTempFile::new().unwrap();
+        let mut options = ConvertOptions::new(
+            vec![],
+            output.path().to_path_buf(),
+            SchemaVersion::Model2012,
+        );
+        options.output_format = format;
+        options.row_group_per_voivodeship = true;
+        let mut writer = OutputWriter::new(&options, None, None, None).unwrap();
+        for voivodeships in batches {
+            writer.write_batch(&batch(voivodeships)).unwrap();
+        }
+        writer.finish().unwrap();
+        let reader = parquet::file::serialized_reader::SerializedFileReader::new(
+            File::open(output.path()).unwrap(),
+        )
+        .unwrap();
+        parquet::file::reader::FileReader::metadata(&reader)
+            .row_groups()
+            .iter()
+            .map(|row_group| row_group.num_rows())
+            .collect::<Vec<_>>()
+    };
+    for format in [OutputFormat::GeoParquet, OutputFormat::Parquet] {
+        // grouped input: also split inside batches
+        assert_eq!(
+            row_group_sizes(format, &[&["02", "02", "14"], &["14"], &["30", "30"]]),
+            vec![2, 2, 2]
+        );
+        // `02` comes back: aligned until then, one row group from there on
+        assert_eq!(
+            row_group_sizes(format, &[&["02", "14"], &["14", "02", "30"]]),
+            vec![1, 4]
+        );
+    }
+}
+
+#[test]
+fn test_threaded_writer_returns_error_of_inner_writer() {
+    let output = tempfile::NamedTempFile::new().unwrap();
+    let inner = OutputWriter::Csv {
+        writer: WriterBuilder::new().build(output.reopen().unwrap()),
+        decimal_comma: false,
+        renames: HashMap::from([("missing".to_string(), "x".to_string())]),
+    };
+    let mut writer = OutputWriter::threaded(inner);
+    let path = PathBuf::from("fixtures/sample_model2012.xml");
+    let batch = crate::get_address_parser_2012_uncompressed(&path, &10)
+        .unwrap()
+        .next()
+        .unwrap()
+        .unwrap();
+    let result = (0..3)
+        .try_for_each(|_| writer.write_batch(&batch))
+        .and_then(|_| writer.finish());
+    let error = result.unwrap_err();
+    assert!(
+        format!("{:#}", error).contains("Unknown column(s) to rename: missing."),
+        "{:#}",
+        error
+    );
+}
+
+#[test]
+fn test_convert_2021_validate_only() {
+    let input = FileRecord::from_path(
+        PathBuf::from("fixtures/sample_model2021.xml"),
+        &SchemaVersion::Model2021,
+        false,
+    )
+    .unwrap();
+    let mut options = ConvertOptions::new(
+        vec![input],
+        PathBuf::from("not_written.parquet"),
+        SchemaVersion::Model2021,
+    );
+    #[cfg(feature = "geoparquet")]
+    {
+        options.output_format = OutputFormat::GeoParquet;
+    }
+    options.teryt_path = Some(PathBuf::from("fixtures/TERC_Urzedowy_2025-11-18.xml"));
+    options.validate_only = true;
+    options.count_voivodeships = true;
+    let report = convert(options).unwrap();
+    assert_eq!(report.total_rows, 3);
+    assert_eq!(
+        report.voivodeship_counts.unwrap().values().sum::<usize>(),
+        3
+    );
+    assert_eq!(report.validation.unwrap().addresses, 3);
+    assert!(report.terc_catalog_date.is_some());
+    assert!(!Path::new("not_written.parquet").exists());
+}
+
+#[test]
+fn test_convert_count_voivodeships_with_tail() {
+    let output = tempfile::NamedTempFile::new().unwrap();
+    let input = FileRecord::from_path(
+        PathBuf::from("fixtures/sample_model2012.xml"),
+        &SchemaVersion::Model2012,
+        false,
+    )
+    .unwrap();
+    let mut options = ConvertOptions::new(
+        vec![input],
+        output.path().to_path_buf(),
+        SchemaVersion::Model2012,
+    );
+    options.count_voivodeships = true;
+    options.tail = Some(1);
+    let report = convert(options).unwrap();
+    assert_eq!(report.total_rows, 2);
+    // only the written address is counted
+    assert_eq!(
+        report.voivodeship_counts.unwrap(),
+        BTreeMap::from([("08".to_string(), 1)])
+    );
+}
+
+#[cfg(feature = "geoparquet")]
+#[test]
+fn test_convert_2021_geoparquet_provenance_metadata() {
+    let output = tempfile::NamedTempFile::new().unwrap();
+    let input = FileRecord::from_path(
+        PathBuf::from("fixtures/sample_model2021.xml"),
+        &SchemaVersion::Model2021,
+        false,
+    )
+    .unwrap();
+    let mut options = ConvertOptions::new(
+        vec![input],
+        output.path().to_path_buf(),
+        SchemaVersion::Model2021,
+    );
+    options.output_format = OutputFormat::GeoParquet;
+    options.teryt_path = Some(PathBuf::from("fixtures/TERC_Urzedowy_2025-11-18.xml"));
+    let report = convert(options).unwrap();
+    let reader = parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder::try_new(
+        File::open(output.path()).unwrap(),
+    )
+    .unwrap();
+    let metadata: HashMap<&str, Option<&str>> = reader
+        .metadata()
+        .file_metadata()
+        .key_value_metadata()
+        .unwrap()
+        .iter()
+        .map(|kv| (kv.key.as_str(), kv.value.as_deref()))
+        .collect();
+    assert!(metadata.contains_key("geo"));
+    assert_eq!(metadata["teryt_date"], report.terc_catalog_date.as_deref());
+    assert_eq!(
+        metadata["prg_convert_version"],
+        Some(env!("CARGO_PKG_VERSION"))
+    );
+    assert!(chrono::DateTime::parse_from_rfc3339(metadata["converted_at"].unwrap()).is_ok());
+}
+
+#[cfg(feature = "geoparquet")]
+#[test]
+fn test_convert_points_only_schema() {
+    let output = tempfile::Builder::new()
+        .suffix(".parquet")
+        .tempfile()
+        .unwrap();
+    let input = FileRecord::from_path(
+        PathBuf::from("fixtures/sample_model2021.xml"),
+        &SchemaVersion::Model2021,
+        false,
+    )
+    .unwrap();
+    let mut options = ConvertOptions::new(
+        vec![input],
+        output.path().to_path_buf(),
+        SchemaVersion::Model2021,
+    );
+    options.output_format = OutputFormat::GeoParquet;
+    options.teryt_path = Some(PathBuf::from("fixtures/TERC_Urzedowy_2025-11-18.xml"));
+    options.points_only_schema = true;
+    // not written with the points only schema
+    options.add_source_column = true;
+    let schema = output_schema(&options).unwrap();
+    let report = convert(options).unwrap();
+    assert_eq!(report.total_rows, 3);
+    let reader = parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder::try_new(
+        File::open(output.path()).unwrap(),
+    )
+    .unwrap();
+    assert!(
+        reader
+            .metadata()
+            .file_metadata()
+            .key_value_metadata()
+            .unwrap()
+            .iter()
+            .any(|kv| kv.key == "geo")
+    );
+    let batches = reader
+        .build()
+        .unwrap()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    let names: Vec<&str> = batches[0]
+        .schema_ref()
+        .fields()
+        .iter()
+        .map(|f| f.name().as_str())
+        .collect();
+    // and the bounding box covering of GeoParquet 1.1
+    assert_eq!(names, ["lokalny_id", "adres", "geometry", "bbox"]);
+    assert_eq!(schema.as_ref(), batches[0].schema_ref().as_ref());
+    let addresses = batches[0]
+        .column_by_name("adres")
+        .unwrap()
+        .as_string::<i32>();
+    assert_eq!(addresses.value(0), "Żubrów 21A, 69-200");
+    assert_eq!(addresses.value(1), "Rzepin, Inwalidów Wojennych 1A, 69-110");
+}
+
+#[cfg(feature = "geoparquet")]
+#[test]
+fn test_convert_interrupted_finalizes_output() {
+    let output = tempfile::NamedTempFile::new().unwrap();
+    let input = FileRecord::from_path(
+        PathBuf::from("fixtures/sample_model2012.xml"),
+        &SchemaVersion::Model2012,
+        false,
+    )
+    .unwrap();
+    let mut options = ConvertOptions::new(
+        vec![input],
+        output.path().to_path_buf(),
+        SchemaVersion::Model2012,
+    );
+    options.output_format = OutputFormat::GeoParquet;
+    options.interrupt = Some(Arc::new(AtomicBool::new(true)));
+    let report = convert(options).unwrap();
+    assert!(report.interrupted);
+    assert_eq!(report.total_rows, 0);
+    // the footer was written, so the file can be read
+    let reader = parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder::try_new(
+        File::open(output.path()).unwrap(),
+    )
+    .unwrap();
+    assert_eq!(reader.metadata().file_metadata().num_rows(), 0);
+    assert!(
+        reader
+            .metadata()
+            .file_metadata()
+            .key_value_metadata()
+            .is_some_and(|kv| kv.iter().any(|kv| kv.key == "geo"))
+    );
+}
+
+/// First batch of the 2012 and the 2021 sample.
+#[cfg(all(test, feature = "geoparquet"))]
+fn sample_batches() -> Vec<RecordBatch> {
+    let teryt_mapping = Arc::new(
+        crate::terc::get_terc_mapping(&PathBuf::from("fixtures/TERC_Urzedowy_2025-11-18.xml"))
+            .unwrap()
+            .mapping,
+    );
+    let mut parser_2012 = crate::get_address_parser_2012_uncompressed(
+        &PathBuf::from("fixtures/sample_model2012.xml"),
+        &10,
+    )
+    .unwrap();
+    let mut parser_2021 = get_address_parser_2021_uncompressed(
+        &PathBuf::from("fixtures/sample_model2021.xml"),
+        &10,
+        &teryt_mapping,
+    )
+    .unwrap();
+    vec![
+        parser_2012.next().unwrap().unwrap(),
+        parser_2021.next().unwrap().unwrap(),
+    ]
+}
+
+#[cfg(feature = "geoparquet")]
+#[test]
+fn test_canonical_to_geoparquet_batch_columns() {
+    for batch in sample_batches() {
+        for (crs, x_name, y_name) in [
+            (CRS::Epsg2180, "x_epsg_2180", "y_epsg_2180"),
+            (
+                CRS::Epsg4326,
+                "dlugosc_geograficzna",
+                "szerokosc_geograficzna",
+            ),
+        ] {
+            let geom_type = point_type_for(&crs);
+            let schema = get_geoparquet_schema(geom_type.clone());
+            let geo_batch =
+                canonical_to_geoparquet_batch(&batch, &crs, &geom_type, &schema, &schema).unwrap();
+            assert_eq!(geo_batch.schema(), schema);
+            for (field, column) in schema.fields().iter().zip(geo_batch.columns()) {
+                if field.name() == "geometry" {
+                    let points = column.as_struct();
+                    assert_eq!(
+                        points.column_by_name("x").unwrap(),
+                        batch.column_by_name(x_name).unwrap()
+                    );
+                    assert_eq!(
+                        points.column_by_name("y").unwrap(),
+                        batch.column_by_name(y_name).unwrap()
+                    );
+                } else {
+                    assert_eq!(
+                        column,
+                        batch.column_by_name(field.name()).unwrap(),
+                        "column `{}`",
+                        field.name()
+                    );
+                }
+            }
+        }
+    }
+}
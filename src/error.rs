@@ -0,0 +1,114 @@
+//! Typed errors of the public API. Every variant keeps the whole `anyhow`
+//! error with its context (displayed the same way as before), so callers can
+//! match on the kind of failure and still print the full message, and
+//! `PrgError` converts into `anyhow::Error` with `?`.
+
+use arrow::error::ArrowError;
+
+#[derive(Debug, thiserror::Error)]
+pub enum PrgError {
+    /// A file could not be opened, read or written.
+    #[error(transparent)]
+    Io(anyhow::Error),
+    /// The XML/GML of an input is malformed or not what the schema expects.
+    #[error(transparent)]
+    Xml(anyhow::Error),
+    /// Coordinates could not be transformed between coordinate systems.
+    #[error(transparent)]
+    Reprojection(anyhow::Error),
+    /// A reference of an address could not be resolved, e.g. a municipality
+    /// missing from the TERC catalog. Such addresses are skipped and reported
+    /// to the `ErrorSink` with this error.
+    #[error(transparent)]
+    UnresolvedReference(anyhow::Error),
+    /// The TERC catalog could not be read or is not of the requested date.
+    #[error(transparent)]
+    TercParse(anyhow::Error),
+    /// The schema version of an input could not be detected.
+    #[error(transparent)]
+    UnknownSchema(anyhow::Error),
+    #[error(transparent)]
+    Other(anyhow::Error),
+}
+
+impl PrgError {
+    /// The variant of the `PrgError` among the causes of `error`, or `Io` for
+    /// errors caused by an I/O error, otherwise `variant`.
+    pub(crate) fn classify(
+        error: anyhow::Error,
+        variant: fn(anyhow::Error) -> PrgError,
+    ) -> PrgError {
+        match PrgError::from(error) {
+            PrgError::Xml(error) | PrgError::Other(error) => variant(error),
+            classified => classified,
+        }
+    }
+
+    fn variant(&self) -> fn(anyhow::Error) -> PrgError {
+        match self {
+            PrgError::Io(_) => PrgError::Io,
+            PrgError::Xml(_) => PrgError::Xml,
+            PrgError::Reprojection(_) => PrgError::Reprojection,
+            PrgError::UnresolvedReference(_) => PrgError::UnresolvedReference,
+            PrgError::TercParse(_) => PrgError::TercParse,
+            PrgError::UnknownSchema(_) => PrgError::UnknownSchema,
+            PrgError::Other(_) => PrgError::Other,
+        }
+    }
+}
+
+/// Classifies `error` by its causes: a `PrgError` keeps its variant, I/O
+/// errors are `Io`, XML errors (also parse errors of the address parsers) are
+/// `Xml` and anything else is `Other`.
+impl From<anyhow::Error> for PrgError {
+    fn from(error: anyhow::Error) -> Self {
+        // without context the error is returned as is (`downcast` would also
+        // look through context and drop it)
+        if error.chain().next().is_some_and(|e| e.is::<PrgError>()) {
+            return error.downcast().unwrap();
+        }
+        let variant =
+            if let Some(prg_error) = error.chain().find_map(|e| e.downcast_ref::<PrgError>()) {
+                prg_error.variant()
+            } else if error.chain().any(|e| e.is::<std::io::Error>()) {
+                PrgError::Io
+            } else if error.chain().any(|e| {
+                e.is::<quick_xml::Error>()
+                    || e.is::<quick_xml::DeError>()
+                    || matches!(e.downcast_ref(), Some(ArrowError::ParseError(_)))
+            }) {
+                PrgError::Xml
+            } else {
+                PrgError::Other
+            };
+        variant(error)
+    }
+}
+
+#[test]
+fn test_from_anyhow_error() {
+    use anyhow::Context;
+    let io_error = std::fs::File::open("fixtures/does_not_exist.xml")
+        .context("Failed to open file.")
+        .unwrap_err();
+    assert!(matches!(PrgError::from(io_error), PrgError::Io(_)));
+    let xml_error = anyhow::Error::new(ArrowError::ParseError("Unknown tag.".to_string()))
+        .context("Failed to parse file.");
+    assert!(matches!(PrgError::from(xml_error), PrgError::Xml(_)));
+    let other = anyhow::anyhow!("Something else.");
+    assert!(matches!(PrgError::from(other), PrgError::Other(_)));
+
+    // the variant is kept through context, with the whole message
+    let terc_error = anyhow::Error::new(PrgError::TercParse(anyhow::anyhow!("Bad catalog.")))
+        .context("Could not read TERYT.");
+    let error = PrgError::from(terc_error);
+    assert!(matches!(error, PrgError::TercParse(_)));
+    assert_eq!(
+        format!("{:#}", anyhow::Error::new(error)),
+        "Could not read TERYT.: Bad catalog."
+    );
+    assert!(matches!(
+        PrgError::classify(anyhow::anyhow!("Bad catalog."), PrgError::TercParse),
+        PrgError::TercParse(_)
+    ));
+}
@@ -0,0 +1,102 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use anyhow::Context;
+
+/// Reads expected numbers of addresses per voivodeship from a CSV file with
+/// lines `teryt_wojewodztwo,count` (e.g. `02,123456`). An optional header line
+/// is skipped, as are blank lines and lines starting with `#`.
+pub fn read_expected_counts(path: &Path) -> anyhow::Result<BTreeMap<String, usize>> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read expected counts file: `{}`.", path.display()))?;
+    parse_expected_counts(&content)
+        .with_context(|| format!("Invalid expected counts file: `{}`.", path.display()))
+}
+
+fn parse_expected_counts(content: &str) -> anyhow::Result<BTreeMap<String, usize>> {
+    let mut counts = BTreeMap::new();
+    let lines = content
+        .lines()
+        .enumerate()
+        .map(|(index, line)| (index + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'));
+    for (position, (line_number, line)) in lines.enumerate() {
+        let Some((voivodeship, count)) = line.split_once(',') else {
+            anyhow::bail!("Line {line_number}: expected `teryt_wojewodztwo,count`, got `{line}`.");
+        };
+        let voivodeship = voivodeship.trim();
+        let count = match count.trim().parse::<usize>() {
+            Ok(count) => count,
+            // header
+            Err(_) if position == 0 => continue,
+            Err(_) => anyhow::bail!("Line {line_number}: invalid count `{}`.", count.trim()),
+        };
+        if counts.insert(voivodeship.to_string(), count).is_some() {
+            anyhow::bail!("Line {line_number}: voivodeship `{voivodeship}` is listed twice.");
+        }
+    }
+    if counts.is_empty() {
+        anyhow::bail!("No expected counts found.");
+    }
+    Ok(counts)
+}
+
+/// Describes every voivodeship whose count differs from the expected one by
+/// more than `tolerance_percent` percent of the expected count. Voivodeships
+/// that are not expected are not checked.
+pub fn count_mismatches(
+    expected: &BTreeMap<String, usize>,
+    actual: &BTreeMap<String, usize>,
+    tolerance_percent: f64,
+) -> Vec<String> {
+    expected
+        .iter()
+        .filter_map(|(voivodeship, &expected_count)| {
+            let actual_count = actual.get(voivodeship).copied().unwrap_or(0);
+            let difference = actual_count.abs_diff(expected_count);
+            let allowed = expected_count as f64 * tolerance_percent / 100.0;
+            (difference as f64 > allowed).then(|| {
+                format!(
+                    "voivodeship {}: expected {}, got {}",
+                    voivodeship, expected_count, actual_count
+                )
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_expected_counts() {
+        let counts =
+            parse_expected_counts("teryt_wojewodztwo,count\n# lubuskie\n08, 3\n\n02,10\n").unwrap();
+        assert_eq!(counts.len(), 2);
+        assert_eq!(counts["08"], 3);
+        assert_eq!(counts["02"], 10);
+
+        assert!(parse_expected_counts("08,3\n02,ten\n").is_err());
+        assert!(parse_expected_counts("08,3\n08,4\n").is_err());
+        assert!(parse_expected_counts("08 3\n").is_err());
+        assert!(parse_expected_counts("teryt_wojewodztwo,count\n").is_err());
+    }
+
+    #[test]
+    fn test_count_mismatches() {
+        let expected = BTreeMap::from([("02".to_string(), 100), ("08".to_string(), 3)]);
+        let actual = BTreeMap::from([("02".to_string(), 99), ("14".to_string(), 5)]);
+        assert_eq!(
+            count_mismatches(&expected, &actual, 0.0),
+            vec![
+                "voivodeship 02: expected 100, got 99",
+                "voivodeship 08: expected 3, got 0"
+            ]
+        );
+        assert_eq!(
+            count_mismatches(&expected, &actual, 1.0),
+            vec!["voivodeship 08: expected 3, got 0"]
+        );
+    }
+}
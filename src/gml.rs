@@ -0,0 +1,184 @@
+//! GML output (`--output-format gml`): a minimal `gml:FeatureCollection` with
+//! one `gml:featureMember` per address, streamed batch by batch. Every column
+//! other than the coordinates is written as an element of the feature and the
+//! point as `gml:Point`/`gml:pos` in EPSG:2180, in the `y x` order of PRG.
+
+use std::io::Write;
+
+use anyhow::Context;
+use arrow::array::{Array, AsArray, RecordBatch};
+use arrow::datatypes::Float64Type;
+use arrow::util::display::{ArrayFormatter, FormatOptions};
+use quick_xml::Writer;
+use quick_xml::events::{BytesDecl, BytesEnd, BytesStart, BytesText, Event};
+
+pub const GML_NAMESPACE: &str = "http://www.opengis.net/gml/3.2";
+/// Namespace of the feature and attribute elements (prefix `prg`).
+pub const FEATURE_NAMESPACE: &str = "https://github.com/ttomasz/prg_convert";
+pub const SRS_NAME: &str = "urn:ogc:def:crs:EPSG::2180";
+
+/// Coordinate columns, written as the geometry instead of attributes.
+const COORDINATE_COLUMNS: [&str; 4] = [
+    "x_epsg_2180",
+    "y_epsg_2180",
+    "dlugosc_geograficzna",
+    "szerokosc_geograficzna",
+];
+
+pub struct GmlWriter<W: Write> {
+    writer: Writer<W>,
+}
+
+impl<W: Write> GmlWriter<W> {
+    /// Writes the XML declaration and opens the feature collection.
+    pub fn new(inner: W) -> anyhow::Result<Self> {
+        let mut writer = Writer::new_with_indent(inner, b' ', 2);
+        writer.write_event(Event::Decl(BytesDecl::new("1.0", Some("UTF-8"), None)))?;
+        writer.write_event(Event::Start(
+            BytesStart::new("gml:FeatureCollection").with_attributes([
+                ("xmlns:gml", GML_NAMESPACE),
+                ("xmlns:prg", FEATURE_NAMESPACE),
+                ("gml:id", "adresy"),
+            ]),
+        ))?;
+        Ok(Self { writer })
+    }
+
+    /// Writes the addresses of a batch with the canonical columns (columns may
+    /// be renamed in `renamed`, which has the same columns in the same order).
+    /// Addresses without coordinates are written without `prg:geometria`.
+    pub fn write(&mut self, batch: &RecordBatch, renamed: &RecordBatch) -> anyhow::Result<()> {
+        let coordinate = |name: &str| {
+            batch
+                .column_by_name(name)
+                .map(|c| c.as_primitive::<Float64Type>())
+                .with_context(|| format!("Batch is missing column `{}`.", name))
+        };
+        let (xs, ys) = (coordinate("x_epsg_2180")?, coordinate("y_epsg_2180")?);
+        let schema = batch.schema();
+        let options = FormatOptions::default();
+        let attributes = schema
+            .fields()
+            .iter()
+            .zip(renamed.schema().fields())
+            .zip(renamed.columns())
+            .filter(|((field, _), _)| !COORDINATE_COLUMNS.contains(&field.name().as_str()))
+            .map(|((_, renamed_field), column)| {
+                Ok((
+                    format!("prg:{}", renamed_field.name()),
+                    column,
+                    ArrayFormatter::try_new(column.as_ref(), &options)?,
+                ))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let namespaces = batch
+            .column_by_name("przestrzen_nazw")
+            .map(|c| c.as_string::<i32>());
+        let ids = batch
+            .column_by_name("lokalny_id")
+            .context("Batch is missing column `lokalny_id`.")?
+            .as_string::<i32>();
+
+        for row in 0..batch.num_rows() {
+            self.writer
+                .write_event(Event::Start(BytesStart::new("gml:featureMember")))?;
+            let namespace = namespaces
+                .filter(|n| n.is_valid(row))
+                .map_or("PRG", |n| n.value(row));
+            let gml_id = format!("{}_{}", namespace, ids.value(row));
+            self.writer.write_event(Event::Start(
+                BytesStart::new("prg:adres").with_attributes([("gml:id", gml_id.as_str())]),
+            ))?;
+            for (name, column, formatter) in &attributes {
+                if column.is_null(row) {
+                    continue;
+                }
+                self.writer
+                    .create_element(name.as_str())
+                    .write_text_content(BytesText::new(&formatter.value(row).to_string()))?;
+            }
+            if xs.is_valid(row) && ys.is_valid(row) {
+                self.writer
+                    .write_event(Event::Start(BytesStart::new("prg:geometria")))?;
+                self.writer.write_event(Event::Start(
+                    BytesStart::new("gml:Point").with_attributes([
+                        ("gml:id", format!("{}.geometria", gml_id).as_str()),
+                        ("srsName", SRS_NAME),
+                    ]),
+                ))?;
+                self.writer
+                    .create_element("gml:pos")
+                    .write_text_content(BytesText::new(&format!(
+                        "{} {}",
+                        ys.value(row),
+                        xs.value(row)
+                    )))?;
+                self.writer
+                    .write_event(Event::End(BytesEnd::new("gml:Point")))?;
+                self.writer
+                    .write_event(Event::End(BytesEnd::new("prg:geometria")))?;
+            }
+            self.writer
+                .write_event(Event::End(BytesEnd::new("prg:adres")))?;
+            self.writer
+                .write_event(Event::End(BytesEnd::new("gml:featureMember")))?;
+        }
+        Ok(())
+    }
+
+    /// Closes the feature collection and returns the inner writer.
+    pub fn finish(mut self) -> anyhow::Result<W> {
+        self.writer
+            .write_event(Event::End(BytesEnd::new("gml:FeatureCollection")))?;
+        let mut inner = self.writer.into_inner();
+        inner.flush()?;
+        Ok(inner)
+    }
+}
+
+#[test]
+fn test_gml_writer() {
+    use std::sync::Arc;
+    let path = std::path::PathBuf::from("fixtures/sample_model2012.xml");
+    let mut parser = crate::get_address_parser_2012_uncompressed(&path, &10).unwrap();
+    let batch = parser.next().unwrap().unwrap();
+    // second address without coordinates
+    let index = batch.schema().index_of("x_epsg_2180").unwrap();
+    let mut columns = batch.columns().to_vec();
+    columns[index] = Arc::new(arrow::array::Float64Array::from(vec![
+        Some(287772.37),
+        None,
+    ]));
+    let batch = RecordBatch::try_new(batch.schema(), columns).unwrap();
+
+    let mut writer = GmlWriter::new(Vec::new()).unwrap();
+    writer.write(&batch, &batch).unwrap();
+    let gml = String::from_utf8(writer.finish().unwrap()).unwrap();
+    assert!(gml.starts_with("<?xml version=\"1.0\" encoding=\"UTF-8\"?>"));
+    assert!(gml.trim_end().ends_with("</gml:FeatureCollection>"));
+    assert_eq!(gml.matches("<gml:featureMember>").count(), 2);
+    assert_eq!(gml.matches("<gml:pos>").count(), 1);
+    assert!(gml.contains("<gml:pos>456005.140000001 287772.37</gml:pos>"));
+    assert!(
+        gml.contains(r#"<prg:adres gml:id="PL.PZGIK.200_fd9c9319-0a6a-44b4-972a-1e6c4ec0d4ca">"#)
+    );
+    assert!(gml.contains("<prg:lokalny_id>5baa8bef-75ef-4241-a2fe-9d4137845693</prg:lokalny_id>"));
+    assert!(gml.contains("<prg:numer_porzadkowy>2</prg:numer_porzadkowy>"));
+    // coordinates are only written as the geometry, null columns are left out
+    assert!(!gml.contains("prg:x_epsg_2180"));
+    assert!(!gml.contains("prg:dlugosc_geograficzna"));
+    assert!(!gml.contains("prg:wazny_do"));
+
+    // the output can be read back
+    let mut reader = quick_xml::Reader::from_str(&gml);
+    let mut depth = 0;
+    loop {
+        match reader.read_event().unwrap() {
+            Event::Start(_) => depth += 1,
+            Event::End(_) => depth -= 1,
+            Event::Eof => break,
+            _ => (),
+        }
+    }
+    assert_eq!(depth, 0);
+}
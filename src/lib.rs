@@ -1,24 +1,54 @@
 use std::collections::HashMap;
 use std::fs::File;
+use std::io::BufRead;
 use std::io::BufReader;
+use std::io::Cursor;
+use std::io::Read;
+use std::path::Path;
 use std::path::PathBuf;
 use std::sync::Arc;
 
 use anyhow::Context;
+use arrow::array::RecordBatch;
+use arrow::error::ArrowError;
+use memmap2::Mmap;
 use quick_xml::Reader;
 use zip::ZipArchive;
 use zip::read::ZipFile;
 
 pub mod terc;
 use terc::Terc;
+use terc::TercDictionary;
 #[cfg(feature = "download")]
 use terc::download_terc_mapping;
-use terc::get_terc_mapping;
+use terc::get_terc_mapping_of_date;
+#[cfg(feature = "cli")]
+pub mod checkpoint;
 pub mod common;
+pub mod compare;
+#[cfg(feature = "cli")]
+pub mod convert;
+pub mod error;
+pub use error::PrgError;
+pub mod gml;
 mod model2012;
 use model2012::AddressParser2012;
+pub use model2012::{
+    AdditionalInfo, KomponentType, StreetNameFormat, construct_full_name_from_parts,
+};
 mod model2021;
 use model2021::AddressParser2021;
+pub use model2021::{City, JoinAudit, JoinAuditRecord, JoinProblem, Mappings, Street, StreetTypes};
+mod parallel;
+use parallel::ParallelParser;
+#[cfg(feature = "protobuf")]
+pub mod protobuf;
+#[cfg(feature = "cli")]
+pub mod self_test;
+pub mod split_zip;
+pub mod timings;
+#[cfg(feature = "cli")]
+pub mod transform_bench;
 
 #[derive(Clone, Copy)]
 pub enum CoordOrder {
@@ -26,27 +56,138 @@ pub enum CoordOrder {
     YX,
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum OutputFormat {
     CSV,
+    #[cfg(feature = "geoparquet")]
     GeoParquet,
+    /// Plain parquet with the CSV columns, without geometry and `geo` metadata.
+    #[cfg(feature = "geoparquet")]
+    Parquet,
+    /// GML feature collection with the points in EPSG:2180, see [`gml`].
+    Gml,
+    /// Length-delimited protobuf `Address` messages, see [`protobuf`].
+    #[cfg(feature = "protobuf")]
+    Protobuf,
+}
+
+impl OutputFormat {
+    /// Names accepted by `OutputFormat::try_from` (case-insensitive).
+    pub const NAMES: &[&str] = &[
+        "csv",
+        #[cfg(feature = "geoparquet")]
+        "geoparquet",
+        #[cfg(feature = "geoparquet")]
+        "parquet",
+        "gml",
+        #[cfg(feature = "protobuf")]
+        "pbf",
+    ];
+
+    /// Every variant available in this build.
+    pub fn all_variants() -> &'static [OutputFormat] {
+        &[
+            OutputFormat::CSV,
+            #[cfg(feature = "geoparquet")]
+            OutputFormat::GeoParquet,
+            #[cfg(feature = "geoparquet")]
+            OutputFormat::Parquet,
+            OutputFormat::Gml,
+            #[cfg(feature = "protobuf")]
+            OutputFormat::Protobuf,
+        ]
+    }
+
+    /// Whether the output is a parquet file (GeoParquet or plain).
+    pub fn is_parquet(&self) -> bool {
+        match self {
+            OutputFormat::CSV | OutputFormat::Gml => false,
+            #[cfg(feature = "geoparquet")]
+            OutputFormat::GeoParquet | OutputFormat::Parquet => true,
+            #[cfg(feature = "protobuf")]
+            OutputFormat::Protobuf => false,
+        }
+    }
 }
 
 impl std::fmt::Display for OutputFormat {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
             OutputFormat::CSV => write!(f, "csv"),
+            #[cfg(feature = "geoparquet")]
             OutputFormat::GeoParquet => write!(f, "geoparquet"),
+            #[cfg(feature = "geoparquet")]
+            OutputFormat::Parquet => write!(f, "parquet"),
+            OutputFormat::Gml => write!(f, "gml"),
+            #[cfg(feature = "protobuf")]
+            OutputFormat::Protobuf => write!(f, "pbf"),
         }
     }
 }
 
-#[derive(Clone, Copy)]
+impl TryFrom<&str> for OutputFormat {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &str) -> anyhow::Result<Self> {
+        match value.to_lowercase().as_str() {
+            "csv" => Ok(OutputFormat::CSV),
+            #[cfg(feature = "geoparquet")]
+            "geoparquet" => Ok(OutputFormat::GeoParquet),
+            #[cfg(feature = "geoparquet")]
+            "parquet" => Ok(OutputFormat::Parquet),
+            "gml" => Ok(OutputFormat::Gml),
+            #[cfg(feature = "protobuf")]
+            "pbf" | "protobuf" => Ok(OutputFormat::Protobuf),
+            #[cfg(not(feature = "geoparquet"))]
+            "geoparquet" | "parquet" => anyhow::bail!(
+                "Output format `{}` is not available, the program was built without the `geoparquet` feature.",
+                value
+            ),
+            #[cfg(not(feature = "protobuf"))]
+            "pbf" | "protobuf" => anyhow::bail!(
+                "Output format `{}` is not available, the program was built without the `protobuf` feature.",
+                value
+            ),
+            _ => anyhow::bail!(
+                "Unknown output format `{}`, expected one of: {}.",
+                value,
+                OutputFormat::NAMES.join(", ")
+            ),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum FileType {
     XML,
     ZIP,
 }
 
+impl FileType {
+    /// Names accepted by `FileType::try_from` (case-insensitive).
+    pub const NAMES: [&str; 2] = ["xml", "zip"];
+
+    pub fn all_variants() -> &'static [FileType] {
+        &[FileType::XML, FileType::ZIP]
+    }
+}
+
+impl TryFrom<&str> for FileType {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &str) -> anyhow::Result<Self> {
+        match value.to_lowercase().as_str() {
+            "xml" => Ok(FileType::XML),
+            "zip" => Ok(FileType::ZIP),
+            _ => anyhow::bail!(
+                "Unknown file type `{}`, expected one of: {}.",
+                value,
+                FileType::NAMES.join(", ")
+            ),
+        }
+    }
+}
+
 impl std::fmt::Display for FileType {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
@@ -56,11 +197,135 @@ impl std::fmt::Display for FileType {
     }
 }
 
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum SchemaVersion {
     Model2012,
     Model2021,
 }
 
+/// How many bytes from the start of a document are inspected by `SchemaVersion::detect`.
+const SCHEMA_SNIFF_BYTES: u64 = 16 * 1024;
+
+impl SchemaVersion {
+    /// Guesses the schema version by looking at the first few KB of an XML/GML
+    /// document for the namespace prefix or address tag specific to each schema
+    /// (`prg-ad:`/`PRG_PunktAdresowy` for 2012, `prgad:`/`AD_PunktAdresowy` for 2021).
+    pub fn detect<R: Read>(reader: R) -> Result<SchemaVersion, PrgError> {
+        let mut head = Vec::new();
+        reader
+            .take(SCHEMA_SNIFF_BYTES)
+            .read_to_end(&mut head)
+            .context("Could not read input to detect schema version.")?;
+        let contains = |needle: &[u8]| head.windows(needle.len()).any(|w| w == needle);
+        let is_2012 = contains(b"xmlns:prg-ad=") || contains(b"prg-ad:PRG_PunktAdresowy");
+        let is_2021 = contains(b"xmlns:prgad=") || contains(b"prgad:AD_PunktAdresowy");
+        match (is_2012, is_2021) {
+            (true, false) => Ok(SchemaVersion::Model2012),
+            (false, true) => Ok(SchemaVersion::Model2021),
+            (true, true) => Err(PrgError::UnknownSchema(anyhow::anyhow!(
+                "Could not detect schema version: input contains both 2012 and 2021 signatures. Use --schema-version."
+            ))),
+            (false, false) => Err(PrgError::UnknownSchema(anyhow::anyhow!(
+                "Could not detect schema version: neither 2012 (`prg-ad:`) nor 2021 (`prgad:`) namespace found in the first {} KB. Use --schema-version.",
+                SCHEMA_SNIFF_BYTES / 1024
+            ))),
+        }
+    }
+
+    /// Like `detect`, but for a file path. For ZIP archives the first `.xml`
+    /// or `.gml` entry is inspected.
+    pub fn detect_from_path(path: &Path) -> Result<SchemaVersion, PrgError> {
+        let f = File::open(path)
+            .with_context(|| format!("Failed to open file: `{}`.", &path.display()))?;
+        let is_zip = path
+            .extension()
+            .is_some_and(|e| e.to_string_lossy().eq_ignore_ascii_case("zip"));
+        if !is_zip {
+            return SchemaVersion::detect(f)
+                .with_context(|| format!("File: `{}`.", &path.display()))
+                .map_err(PrgError::from);
+        }
+        let is_xml = |name: Option<PathBuf>| {
+            name.is_some_and(|name| {
+                name.extension().is_some_and(|e| {
+                    let e = e.to_string_lossy();
+                    e.eq_ignore_ascii_case("xml") || e.eq_ignore_ascii_case("gml")
+                })
+            })
+        };
+        let detect_entry = |name: &str, entry: &mut dyn std::io::Read| {
+            SchemaVersion::detect(entry)
+                .with_context(|| format!("File: `{}` inside `{}`.", name, &path.display()))
+                .map_err(PrgError::from)
+        };
+        let no_xml = || {
+            PrgError::UnknownSchema(anyhow::anyhow!(
+                "Could not detect schema version: no XML/GML file found inside `{}`.",
+                &path.display()
+            ))
+        };
+        // volumes of a split archive are read in order instead of being joined
+        // (the entries are listed from a joined archive by `FileRecord::from_path`)
+        if let Some(mut volumes) = split_zip::read_split_volumes(path)? {
+            loop {
+                match zip::read::read_zipfile_from_stream(&mut volumes) {
+                    Ok(Some(mut entry)) if is_xml(entry.enclosed_name()) => {
+                        let name = entry.name().to_string();
+                        return detect_entry(&name, &mut entry);
+                    }
+                    Ok(Some(_)) => {}
+                    Ok(None) => return Err(no_xml()),
+                    // e.g. sizes only after the data, which cannot be streamed
+                    Err(_) => break,
+                }
+            }
+        }
+        let joined = split_zip::join_split_archive(path)?;
+        let f = match &joined {
+            Some(joined) => File::open(joined)
+                .with_context(|| format!("Failed to open file: `{}`.", joined.display()))?,
+            None => f,
+        };
+        let mut archive = ZipArchive::new(f)
+            .with_context(|| format!("Failed to decompress ZIP file: `{}`.", &path.display()))?;
+        for idx in 0..archive.len() {
+            let mut entry = archive
+                .by_index(idx)
+                .with_context(|| "Could not access file inside ZIP archive")?;
+            if is_xml(entry.enclosed_name()) {
+                let name = entry.name().to_string();
+                return detect_entry(&name, &mut entry);
+            }
+        }
+        Err(no_xml())
+    }
+}
+
+impl SchemaVersion {
+    /// Names accepted by `SchemaVersion::try_from`.
+    pub const NAMES: [&str; 2] = ["2012", "2021"];
+
+    pub fn all_variants() -> &'static [SchemaVersion] {
+        &[SchemaVersion::Model2012, SchemaVersion::Model2021]
+    }
+}
+
+impl TryFrom<&str> for SchemaVersion {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &str) -> anyhow::Result<Self> {
+        match value {
+            "2012" => Ok(SchemaVersion::Model2012),
+            "2021" => Ok(SchemaVersion::Model2021),
+            _ => anyhow::bail!(
+                "Unknown schema version `{}`, expected one of: {}.",
+                value,
+                SchemaVersion::NAMES.join(", ")
+            ),
+        }
+    }
+}
+
 impl std::fmt::Display for SchemaVersion {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
@@ -70,12 +335,58 @@ impl std::fmt::Display for SchemaVersion {
     }
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
 pub enum CRS {
+    #[default]
     Epsg2180,
     Epsg4326,
 }
 
+impl CRS {
+    /// EPSG codes accepted by `CRS::try_from`, also with an `EPSG:` prefix
+    /// (as they are displayed).
+    pub const NAMES: [&str; 2] = ["2180", "4326"];
+
+    pub fn all_variants() -> &'static [CRS] {
+        &[CRS::Epsg2180, CRS::Epsg4326]
+    }
+
+    pub fn epsg_code(&self) -> u16 {
+        match self {
+            CRS::Epsg2180 => 2180,
+            CRS::Epsg4326 => 4326,
+        }
+    }
+
+    /// Projection used to transform coordinates from or to the CRS.
+    pub fn proj(&self) -> &'static proj4rs::Proj {
+        match self {
+            CRS::Epsg2180 => &common::EPSG_2180,
+            CRS::Epsg4326 => &common::EPSG_4326,
+        }
+    }
+}
+
+impl TryFrom<&str> for CRS {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &str) -> anyhow::Result<Self> {
+        let code = value
+            .get(..5)
+            .filter(|prefix| prefix.eq_ignore_ascii_case("EPSG:"))
+            .map_or(value, |_| &value[5..]);
+        match code {
+            "2180" => Ok(CRS::Epsg2180),
+            "4326" => Ok(CRS::Epsg4326),
+            _ => anyhow::bail!(
+                "Unsupported CRS EPSG code `{}`, expected one of: {}.",
+                value,
+                CRS::NAMES.join(", ")
+            ),
+        }
+    }
+}
+
 impl std::fmt::Display for CRS {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
@@ -85,6 +396,155 @@ impl std::fmt::Display for CRS {
     }
 }
 
+/// Timezone of the timestamp columns of the output. The instants are the
+/// same in both, only the timezone attached to them (and so how they are
+/// rendered in CSV) differs.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum TimestampTimezone {
+    #[default]
+    Utc,
+    /// Europe/Warsaw wall-clock time (CET/CEST).
+    Warsaw,
+}
+
+impl TimestampTimezone {
+    /// Names accepted by `TimestampTimezone::try_from` (case-insensitive),
+    /// besides the IANA names they are displayed as.
+    pub const NAMES: [&str; 2] = ["utc", "warsaw"];
+
+    pub fn all_variants() -> &'static [TimestampTimezone] {
+        &[TimestampTimezone::Utc, TimestampTimezone::Warsaw]
+    }
+
+    /// Timezone of arrow timestamp types (IANA name).
+    pub fn tz_name(&self) -> &'static str {
+        match self {
+            TimestampTimezone::Utc => "UTC",
+            TimestampTimezone::Warsaw => "Europe/Warsaw",
+        }
+    }
+}
+
+impl TryFrom<&str> for TimestampTimezone {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &str) -> anyhow::Result<Self> {
+        match value.to_lowercase().as_str() {
+            "utc" => Ok(TimestampTimezone::Utc),
+            "warsaw" | "europe/warsaw" => Ok(TimestampTimezone::Warsaw),
+            _ => anyhow::bail!(
+                "Unknown timestamp timezone `{}`, expected one of: {}.",
+                value,
+                TimestampTimezone::NAMES.join(", ")
+            ),
+        }
+    }
+}
+
+impl std::fmt::Display for TimestampTimezone {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.tz_name())
+    }
+}
+
+/// Compression algorithm of parquet files.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ParquetCompression {
+    Zstd,
+    Snappy,
+    Brotli,
+}
+
+impl ParquetCompression {
+    /// Names accepted by `ParquetCompression::try_from` (case-insensitive).
+    pub const NAMES: [&str; 3] = ["zstd", "snappy", "brotli"];
+
+    pub fn all_variants() -> &'static [ParquetCompression] {
+        &[
+            ParquetCompression::Zstd,
+            ParquetCompression::Snappy,
+            ParquetCompression::Brotli,
+        ]
+    }
+
+    /// Compression level used when none is given, `None` if the algorithm
+    /// does not have levels.
+    pub fn default_level(&self) -> Option<i32> {
+        match self {
+            ParquetCompression::Zstd => Some(11),
+            ParquetCompression::Snappy => None,
+            ParquetCompression::Brotli => Some(6),
+        }
+    }
+
+    /// Converts to the parquet compression setting, using `level` (or the
+    /// default level) for algorithms that support it.
+    #[cfg(feature = "geoparquet")]
+    pub fn to_parquet(&self, level: Option<i32>) -> anyhow::Result<parquet::basic::Compression> {
+        use parquet::basic::{BrotliLevel, Compression, ZstdLevel};
+        let level = level.or(self.default_level());
+        Ok(match self {
+            ParquetCompression::Zstd => Compression::ZSTD(ZstdLevel::try_new(level.unwrap())?),
+            ParquetCompression::Snappy => Compression::SNAPPY,
+            ParquetCompression::Brotli => {
+                Compression::BROTLI(BrotliLevel::try_new(level.unwrap().cast_unsigned())?)
+            }
+        })
+    }
+}
+
+impl TryFrom<&str> for ParquetCompression {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &str) -> anyhow::Result<Self> {
+        match value.to_lowercase().as_str() {
+            "zstd" => Ok(ParquetCompression::Zstd),
+            "snappy" => Ok(ParquetCompression::Snappy),
+            "brotli" => Ok(ParquetCompression::Brotli),
+            _ => anyhow::bail!(
+                "Unknown parquet compression `{}`, expected one of: {}.",
+                value,
+                ParquetCompression::NAMES.join(", ")
+            ),
+        }
+    }
+}
+
+impl std::fmt::Display for ParquetCompression {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ParquetCompression::Zstd => write!(f, "zstd"),
+            ParquetCompression::Snappy => write!(f, "snappy"),
+            ParquetCompression::Brotli => write!(f, "brotli"),
+        }
+    }
+}
+
+/// `FromStr` of the option enums, the same as their `TryFrom<&str>`. The
+/// `Display` output of every variant is accepted.
+macro_rules! from_str_via_try_from {
+    ($($t:ty),*) => {
+        $(
+            impl std::str::FromStr for $t {
+                type Err = anyhow::Error;
+
+                fn from_str(s: &str) -> anyhow::Result<Self> {
+                    Self::try_from(s)
+                }
+            }
+        )*
+    };
+}
+
+from_str_via_try_from!(
+    OutputFormat,
+    FileType,
+    SchemaVersion,
+    CRS,
+    TimestampTimezone,
+    ParquetCompression
+);
+
 fn get_xml_reader_from_uncompressed_file(
     path: &PathBuf,
 ) -> anyhow::Result<Reader<BufReader<File>>> {
@@ -94,30 +554,112 @@ fn get_xml_reader_from_uncompressed_file(
     Ok(reader)
 }
 
+/// Maps an uncompressed file into memory, see [`get_address_parser_2012_mmap`]
+/// and [`get_address_parser_2021_mmap`].
+/// The file must not be modified while the mapping is in use.
+pub fn map_file(path: &PathBuf) -> Result<Mmap, PrgError> {
+    let file =
+        File::open(path).with_context(|| format!("Failed to open file: `{}`.", path.display()))?;
+    // SAFETY: the mapping is only read and input files are not expected to change during conversion
+    unsafe { Mmap::map(&file) }
+        .with_context(|| format!("Failed to map file into memory: `{}`.", path.display()))
+        .map_err(PrgError::from)
+}
+
+fn get_xml_reader_from_bytes(data: &[u8]) -> Reader<Cursor<&[u8]>> {
+    let mut reader = Reader::from_reader(Cursor::new(data));
+    reader.config_mut().expand_empty_elements = true;
+    reader
+}
+
+/// Dictionaries of the 2012 schema read from `reader`, or empty ones with
+/// `skip_dictionary` (`reader` is then not opened).
+fn dictionaries_2012<R: BufRead>(
+    skip_dictionary: bool,
+    reader: impl FnOnce() -> anyhow::Result<Reader<R>>,
+) -> anyhow::Result<HashMap<String, AdditionalInfo>> {
+    if skip_dictionary {
+        return Ok(HashMap::new());
+    }
+    progress!("Building dictionaries...");
+    model2012::build_dictionaries(reader()?)
+}
+
 pub fn get_address_parser_2012_uncompressed(
     file_path: &PathBuf,
     batch_size: &usize,
-) -> anyhow::Result<AddressParser2012<std::io::BufReader<File>>> {
-    let reader = get_xml_reader_from_uncompressed_file(file_path)?;
-    println!("Building dictionaries...");
-    let dict = model2012::build_dictionaries(reader);
+) -> Result<AddressParser2012<std::io::BufReader<File>>, PrgError> {
+    parser_2012_uncompressed(file_path, batch_size, false)
+}
+
+/// With `skip_dictionary` the dictionary pass is skipped, see
+/// `AddressParser2012::with_skip_dictionary`.
+pub(crate) fn parser_2012_uncompressed(
+    file_path: &PathBuf,
+    batch_size: &usize,
+    skip_dictionary: bool,
+) -> Result<AddressParser2012<std::io::BufReader<File>>, PrgError> {
+    let dict = dictionaries_2012(skip_dictionary, || {
+        get_xml_reader_from_uncompressed_file(file_path)
+    })?;
     let reader = get_xml_reader_from_uncompressed_file(file_path)?;
-    Ok(AddressParser2012::new(reader, *batch_size, dict))
+    Ok(AddressParser2012::new(reader, *batch_size, dict).with_skip_dictionary(skip_dictionary))
+}
+
+/// Parses XML that is already in memory. Both passes (dictionaries and addresses) read the same bytes.
+pub fn get_address_parser_2012_from_bytes<'a>(
+    bytes: &'a [u8],
+    batch_size: &usize,
+) -> Result<AddressParser2012<Cursor<&'a [u8]>>, PrgError> {
+    parser_2012_from_bytes(bytes, batch_size, false)
+}
+
+/// With `skip_dictionary` the dictionary pass is skipped, see
+/// `AddressParser2012::with_skip_dictionary`.
+pub(crate) fn parser_2012_from_bytes<'a>(
+    bytes: &'a [u8],
+    batch_size: &usize,
+    skip_dictionary: bool,
+) -> Result<AddressParser2012<Cursor<&'a [u8]>>, PrgError> {
+    let dict = dictionaries_2012(skip_dictionary, || Ok(get_xml_reader_from_bytes(bytes)))?;
+    let reader = get_xml_reader_from_bytes(bytes);
+    Ok(AddressParser2012::new(reader, *batch_size, dict).with_skip_dictionary(skip_dictionary))
+}
+
+/// Reads the file from a memory map (see [`map_file`]) instead of through a buffered reader.
+/// Both passes (dictionaries and addresses) use the same mapping.
+pub fn get_address_parser_2012_mmap<'a>(
+    mmap: &'a Mmap,
+    batch_size: &usize,
+) -> Result<AddressParser2012<Cursor<&'a [u8]>>, PrgError> {
+    get_address_parser_2012_from_bytes(mmap, batch_size)
 }
 
 pub fn get_address_parser_2012_zip<'a>(
     archive: &'a mut ZipArchive<File>,
     batch_size: &usize,
     zip_file_index: usize,
-) -> anyhow::Result<AddressParser2012<std::io::BufReader<ZipFile<'a, File>>>> {
-    let zip_file = archive
-        .by_index(zip_file_index)
-        .with_context(|| "Could not decompress file from ZIP archive.")?;
-    let buf_reader = BufReader::new(zip_file);
-    let mut reader = Reader::from_reader(buf_reader);
-    reader.config_mut().expand_empty_elements = true;
-    println!("Building dictionaries...");
-    let dict = model2012::build_dictionaries(reader);
+) -> Result<AddressParser2012<std::io::BufReader<ZipFile<'a, File>>>, PrgError> {
+    parser_2012_zip(archive, batch_size, zip_file_index, false)
+}
+
+/// With `skip_dictionary` the dictionary pass is skipped, see
+/// `AddressParser2012::with_skip_dictionary`.
+pub(crate) fn parser_2012_zip<'a>(
+    archive: &'a mut ZipArchive<File>,
+    batch_size: &usize,
+    zip_file_index: usize,
+    skip_dictionary: bool,
+) -> Result<AddressParser2012<std::io::BufReader<ZipFile<'a, File>>>, PrgError> {
+    let dict = dictionaries_2012(skip_dictionary, || {
+        let zip_file = archive
+            .by_index(zip_file_index)
+            .with_context(|| "Could not decompress file from ZIP archive.")?;
+        let buf_reader = BufReader::new(zip_file);
+        let mut reader = Reader::from_reader(buf_reader);
+        reader.config_mut().expand_empty_elements = true;
+        Ok(reader)
+    })?;
 
     let zip_file = archive
         .by_index(zip_file_index)
@@ -126,15 +668,74 @@ pub fn get_address_parser_2012_zip<'a>(
     let mut reader = Reader::from_reader(buf_reader);
     reader.config_mut().expand_empty_elements = true;
 
-    Ok(AddressParser2012::new(reader, *batch_size, dict))
+    Ok(AddressParser2012::new(reader, *batch_size, dict).with_skip_dictionary(skip_dictionary))
 }
 
+/// Experimental: parses the file on `num_threads` threads, see [`ParallelParser`].
+pub fn get_address_parser_2012_parallel(
+    file_path: &PathBuf,
+    batch_size: &usize,
+    num_threads: usize,
+) -> Result<ParallelParser, PrgError> {
+    parallel_parser_2012(
+        file_path,
+        *batch_size,
+        num_threads,
+        parallel::CHUNK_SIZE,
+        false,
+    )
+    .map_err(PrgError::from)
+}
+
+/// With `skip_dictionary` the dictionary pass is skipped, see
+/// `AddressParser2012::with_skip_dictionary`.
+pub(crate) fn parallel_parser_2012(
+    file_path: &PathBuf,
+    batch_size: usize,
+    num_threads: usize,
+    chunk_size: u64,
+    skip_dictionary: bool,
+) -> anyhow::Result<ParallelParser> {
+    let dict = Arc::new(dictionaries_2012(skip_dictionary, || {
+        get_xml_reader_from_uncompressed_file(file_path)
+    })?);
+    let ranges = parallel::split_at_tag(file_path, model2012::ADDRESS_TAG, chunk_size)?;
+    Ok(ParallelParser::new(
+        file_path,
+        ranges,
+        num_threads,
+        Arc::new(move |reader, offset, settings| {
+            Box::new(
+                AddressParser2012::new(reader, batch_size, dict.clone())
+                    .with_offset_base(offset)
+                    .with_error_sink(settings.errors)
+                    .with_filter(settings.filter)
+                    .with_validation_report(settings.report)
+                    .with_id_prefix(settings.id_prefix)
+                    .with_status_raw(settings.status_raw)
+                    .with_skip_dictionary(skip_dictionary)
+                    .with_reprojection(!settings.skip_reprojection)
+                    .with_input_crs(settings.input_crs)
+                    .with_verbose_unknown_tags(settings.verbose_unknown_tags)
+                    .with_fail_on_unknown_tag(settings.fail_on_unknown_tag)
+                    .with_normalize_whitespace(settings.normalize_whitespace)
+                    .with_untrimmed_columns(settings.untrimmed),
+            )
+        }),
+    ))
+}
+
+/// TERC dictionary downloaded from the TERYT API or read from
+/// `teryt_file_path`, a file or a directory of TERC files (see
+/// `terc::get_terc_mapping_of_date`). `teryt_date` pins the catalog date of
+/// the file.
 pub fn get_teryt_mapping(
     download_teryt: bool,
     teryt_api_username: &Option<String>,
     teryt_api_password: &Option<String>,
     teryt_file_path: &Option<PathBuf>,
-) -> anyhow::Result<HashMap<String, Terc>> {
+    teryt_date: &Option<String>,
+) -> Result<TercDictionary, PrgError> {
     if download_teryt {
         #[cfg(feature = "download")]
         {
@@ -142,16 +743,17 @@ pub fn get_teryt_mapping(
                 teryt_api_username.as_deref().unwrap(),
                 teryt_api_password.as_deref().unwrap(),
             )
+            .map_err(PrgError::from)
         }
         #[cfg(not(feature = "download"))]
         {
             let _ = (teryt_api_username, teryt_api_password);
-            anyhow::bail!(
+            Err(PrgError::Other(anyhow::anyhow!(
                 "This build was compiled without the `download` feature; downloading TERYT is unavailable. Provide a TERYT file via --teryt-path."
-            )
+            )))
         }
     } else {
-        get_terc_mapping(teryt_file_path.as_ref().unwrap())
+        get_terc_mapping_of_date(teryt_file_path.as_ref().unwrap(), teryt_date.as_deref())
     }
 }
 
@@ -159,10 +761,10 @@ pub fn get_address_parser_2021_uncompressed(
     file_path: &PathBuf,
     batch_size: &usize,
     teryt_mapping: &Arc<HashMap<String, Terc>>,
-) -> anyhow::Result<AddressParser2021<std::io::BufReader<File>>> {
+) -> Result<AddressParser2021<std::io::BufReader<File>>, PrgError> {
     let reader = get_xml_reader_from_uncompressed_file(file_path)?;
-    println!("Building dictionaries...");
-    let dict = model2021::build_dictionaries(reader);
+    progress!("Building dictionaries...");
+    let dict = model2021::build_dictionaries(reader)?;
     let reader = get_xml_reader_from_uncompressed_file(file_path)?;
     Ok(AddressParser2021::new(
         reader,
@@ -172,20 +774,124 @@ pub fn get_address_parser_2021_uncompressed(
     ))
 }
 
+/// Parses XML that is already in memory. Both passes (dictionaries and addresses) read the same bytes.
+pub fn get_address_parser_2021_from_bytes<'a>(
+    bytes: &'a [u8],
+    batch_size: &usize,
+    teryt_mapping: &Arc<HashMap<String, Terc>>,
+) -> Result<AddressParser2021<Cursor<&'a [u8]>>, PrgError> {
+    progress!("Building dictionaries...");
+    let dict = model2021::build_dictionaries(get_xml_reader_from_bytes(bytes))?;
+    let reader = get_xml_reader_from_bytes(bytes);
+    Ok(AddressParser2021::new(
+        reader,
+        *batch_size,
+        dict,
+        teryt_mapping.clone(),
+    ))
+}
+
+/// Reads the file from a memory map (see [`map_file`]) instead of through a buffered reader.
+/// Both passes (dictionaries and addresses) use the same mapping.
+pub fn get_address_parser_2021_mmap<'a>(
+    mmap: &'a Mmap,
+    batch_size: &usize,
+    teryt_mapping: &Arc<HashMap<String, Terc>>,
+) -> Result<AddressParser2021<Cursor<&'a [u8]>>, PrgError> {
+    get_address_parser_2021_from_bytes(mmap, batch_size, teryt_mapping)
+}
+
+/// Parses XML that is already in memory with the parser of `schema_version`.
+/// The TERYT mapping is required for the 2021 schema, see [`get_teryt_mapping`].
+pub fn get_address_parser_from_bytes<'a>(
+    bytes: &'a [u8],
+    schema_version: SchemaVersion,
+    batch_size: &usize,
+    teryt_mapping: Option<&Arc<HashMap<String, Terc>>>,
+) -> Result<Box<dyn Iterator<Item = Result<RecordBatch, ArrowError>> + 'a>, PrgError> {
+    match schema_version {
+        SchemaVersion::Model2012 => Ok(Box::new(get_address_parser_2012_from_bytes(
+            bytes, batch_size,
+        )?)),
+        SchemaVersion::Model2021 => {
+            let teryt_mapping = teryt_mapping
+                .context("TERYT mapping is required to parse addresses of the 2021 schema.")?;
+            Ok(Box::new(get_address_parser_2021_from_bytes(
+                bytes,
+                batch_size,
+                teryt_mapping,
+            )?))
+        }
+    }
+}
+
+/// Experimental: parses the file on `num_threads` threads, see [`ParallelParser`].
+pub fn get_address_parser_2021_parallel(
+    file_path: &PathBuf,
+    batch_size: &usize,
+    teryt_mapping: &Arc<HashMap<String, Terc>>,
+    num_threads: usize,
+) -> Result<ParallelParser, PrgError> {
+    parallel_parser_2021(
+        file_path,
+        *batch_size,
+        teryt_mapping,
+        num_threads,
+        parallel::CHUNK_SIZE,
+    )
+    .map_err(PrgError::from)
+}
+
+fn parallel_parser_2021(
+    file_path: &PathBuf,
+    batch_size: usize,
+    teryt_mapping: &Arc<HashMap<String, Terc>>,
+    num_threads: usize,
+    chunk_size: u64,
+) -> anyhow::Result<ParallelParser> {
+    let reader = get_xml_reader_from_uncompressed_file(file_path)?;
+    progress!("Building dictionaries...");
+    let dict = Arc::new(model2021::build_dictionaries(reader)?);
+    let ranges = parallel::split_at_tag(file_path, model2021::ADDRESS_TAG, chunk_size)?;
+    let teryt_mapping = teryt_mapping.clone();
+    Ok(ParallelParser::new(
+        file_path,
+        ranges,
+        num_threads,
+        Arc::new(move |reader, offset, settings| {
+            Box::new(
+                AddressParser2021::new(reader, batch_size, dict.clone(), teryt_mapping.clone())
+                    .with_offset_base(offset)
+                    .with_error_sink(settings.errors)
+                    .with_filter(settings.filter)
+                    .with_validation_report(settings.report)
+                    .with_reprojection(!settings.skip_reprojection)
+                    .with_input_crs(settings.input_crs)
+                    .with_verbose_unknown_tags(settings.verbose_unknown_tags)
+                    .with_fail_on_unknown_tag(settings.fail_on_unknown_tag)
+                    .with_normalize_whitespace(settings.normalize_whitespace)
+                    .with_untrimmed_columns(settings.untrimmed)
+                    .with_street_types(settings.street_types)
+                    .with_join_audit(settings.join_audit),
+            )
+        }),
+    ))
+}
+
 pub fn get_address_parser_2021_zip<'a>(
     archive: &'a mut ZipArchive<File>,
     batch_size: &usize,
     teryt_mapping: &Arc<HashMap<String, Terc>>,
     zip_file_index: usize,
-) -> anyhow::Result<AddressParser2021<std::io::BufReader<ZipFile<'a, File>>>> {
+) -> Result<AddressParser2021<std::io::BufReader<ZipFile<'a, File>>>, PrgError> {
     let zip_file = archive
         .by_index(zip_file_index)
         .with_context(|| "Could not decompress file from ZIP archive.")?;
     let buf_reader = BufReader::new(zip_file);
     let mut reader = Reader::from_reader(buf_reader);
     reader.config_mut().expand_empty_elements = true;
-    println!("Building dictionaries...");
-    let dict = model2021::build_dictionaries(reader);
+    progress!("Building dictionaries...");
+    let dict = model2021::build_dictionaries(reader)?;
 
     let zip_file = archive
         .by_index(zip_file_index)
@@ -221,11 +927,12 @@ mod tests {
         let batches: Vec<arrow::array::RecordBatch> = parser
             .expect("Something wrong while creating parser object.")
             .into_iter()
-            .collect();
+            .collect::<Result<_, _>>()
+            .expect("Failed to parse addresses.");
         let arrow_batch = concat_batches(&crate::common::SCHEMA_CSV.clone(), &batches)
             .expect("Error in concatenating batches");
         assert_eq!(arrow_batch.num_rows(), 2);
-        assert_eq!(arrow_batch.num_columns(), 24);
+        assert_eq!(arrow_batch.num_columns(), 25);
         let expected_przestrzen_nazw = &StringArray::from(vec!["PL.PZGIK.200", "PL.PZGIK.200"]);
         let przestrzen_nazw: &StringArray = &arrow_batch
             .column_by_name("przestrzen_nazw")
@@ -442,7 +1149,15 @@ mod tests {
         let sample_file_path = "fixtures/PRG-punkty_adresowe.zip";
         let teryt_file_path = "fixtures/TERC_Urzedowy_2025-11-18.zip";
         let teryt_mapping = Arc::new(
-            get_teryt_mapping(false, &None, &None, &Some(PathBuf::from(teryt_file_path))).unwrap(),
+            get_teryt_mapping(
+                false,
+                &None,
+                &None,
+                &Some(PathBuf::from(teryt_file_path)),
+                &None,
+            )
+            .unwrap()
+            .mapping,
         );
         let f = std::fs::File::open(&sample_file_path)
             .expect(format!("Failed to open file: `{}`.", &sample_file_path).as_str());
@@ -452,11 +1167,12 @@ mod tests {
         let batches: Vec<arrow::array::RecordBatch> = parser
             .expect("Something wrong while creating parser object.")
             .into_iter()
-            .collect();
+            .collect::<Result<_, _>>()
+            .expect("Failed to parse addresses.");
         let arrow_batch = concat_batches(&crate::common::SCHEMA_CSV.clone(), &batches)
             .expect("Error in concatenating batches");
         assert_eq!(arrow_batch.num_rows(), 3);
-        assert_eq!(arrow_batch.num_columns(), 24);
+        assert_eq!(arrow_batch.num_columns(), 25);
         let expected_przestrzen_nazw =
             &StringArray::from(vec!["PL.PZGIK.200", "PL.PZGIK.200", "PL.PZGIK.200"]);
         let przestrzen_nazw: &StringArray = &arrow_batch
@@ -677,6 +1393,98 @@ mod tests {
         assert_eq!(&szerokosc_geograficzna, &expected_szerokosc_geograficzna);
     }
 
+    #[test]
+    fn test_address_parser_from_bytes() {
+        let bytes = std::fs::read("fixtures/sample_model2012.xml").unwrap();
+        let batches: Vec<RecordBatch> =
+            get_address_parser_from_bytes(&bytes, SchemaVersion::Model2012, &1, None)
+                .unwrap()
+                .collect::<Result<_, _>>()
+                .unwrap();
+        assert_eq!(batches.len(), 2);
+        let expected = get_address_parser_2012_uncompressed(
+            &PathBuf::from("fixtures/sample_model2012.xml"),
+            &1,
+        )
+        .unwrap()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+        assert_eq!(batches, expected);
+
+        let bytes = std::fs::read("fixtures/sample_model2021.xml").unwrap();
+        assert!(get_address_parser_from_bytes(&bytes, SchemaVersion::Model2021, &1, None).is_err());
+        let teryt_mapping = Arc::new(
+            get_teryt_mapping(
+                false,
+                &None,
+                &None,
+                &Some(PathBuf::from("fixtures/TERC_Urzedowy_2025-11-18.zip")),
+                &None,
+            )
+            .unwrap()
+            .mapping,
+        );
+        let batches: Vec<RecordBatch> = get_address_parser_from_bytes(
+            &bytes,
+            SchemaVersion::Model2021,
+            &100,
+            Some(&teryt_mapping),
+        )
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+        assert_eq!(batches.iter().map(|b| b.num_rows()).sum::<usize>(), 3);
+    }
+
+    #[test]
+    fn test_dictionary_accessors() {
+        let parser = get_address_parser_2012_uncompressed(
+            &PathBuf::from("fixtures/sample_model2012.xml"),
+            &10,
+        )
+        .unwrap();
+        assert_eq!(parser.component_count(), 6);
+        let voivodeship = parser.lookup_component("PL.PZGIK.200_366267").unwrap();
+        assert_eq!(voivodeship.typ(), &KomponentType::Voivodeship);
+        assert_eq!(voivodeship.name(), "lubuskie");
+        assert_eq!(voivodeship.teryt_id(), Some("08"));
+        assert!(parser.lookup_component("missing").is_none());
+
+        let teryt_mapping = Arc::new(
+            get_teryt_mapping(
+                false,
+                &None,
+                &None,
+                &Some(PathBuf::from("fixtures/TERC_Urzedowy_2025-11-18.zip")),
+                &None,
+            )
+            .unwrap()
+            .mapping,
+        );
+        let parser = get_address_parser_2021_uncompressed(
+            &PathBuf::from("fixtures/sample_model2021.xml"),
+            &10,
+            &teryt_mapping,
+        )
+        .unwrap();
+        assert_eq!(parser.mappings().city_count(), 3);
+        let city = parser
+            .city("PL.ZIPIN.2418.EMUiA_0188009_2025-10-14T14_04_04_02_00")
+            .unwrap();
+        assert_eq!(city.name(), "Żubrów");
+        assert_eq!(city.kind(), "wieś");
+        assert_eq!(city.city_teryt_id(), Some("0188009"));
+        assert_eq!(city.municipality_teryt_id(), "0807043");
+        let street = parser
+            .street("PL.ZIPIN.3122.EMUiA_24ee6a48-8011-42d4-af8e-b048691e2c8c_2017-04-13T15_30_04_02_00")
+            .unwrap();
+        assert_eq!(street.name(), "Plac Kasztanowy");
+        assert_eq!(street.kind(), "plac");
+        assert_eq!(street.code(), "3");
+        assert_eq!(street.teryt_id(), Some("08173"));
+        assert!(parser.street("missing").is_none());
+    }
+
     #[test]
     fn test_address_parser_2012_xml_csv() {
         let file_path = PathBuf::from("fixtures/sample_model2012.xml");
@@ -684,11 +1492,12 @@ mod tests {
         let batches: Vec<arrow::array::RecordBatch> = parser
             .expect("Something wrong while creating parser object.")
             .into_iter()
-            .collect();
+            .collect::<Result<_, _>>()
+            .expect("Failed to parse addresses.");
         let arrow_batch = concat_batches(&crate::common::SCHEMA_CSV.clone(), &batches)
             .expect("Error in concatenating batches");
         assert_eq!(arrow_batch.num_rows(), 2);
-        assert_eq!(arrow_batch.num_columns(), 24);
+        assert_eq!(arrow_batch.num_columns(), 25);
         let expected_lokalny_id = &StringArray::from(vec![
             "fd9c9319-0a6a-44b4-972a-1e6c4ec0d4ca",
             "5baa8bef-75ef-4241-a2fe-9d4137845693",
@@ -715,17 +1524,26 @@ mod tests {
         let file_path = PathBuf::from("fixtures/sample_model2021.xml");
         let teryt_file_path = "fixtures/TERC_Urzedowy_2025-11-18.zip";
         let teryt_mapping = Arc::new(
-            get_teryt_mapping(false, &None, &None, &Some(PathBuf::from(teryt_file_path))).unwrap(),
+            get_teryt_mapping(
+                false,
+                &None,
+                &None,
+                &Some(PathBuf::from(teryt_file_path)),
+                &None,
+            )
+            .unwrap()
+            .mapping,
         );
         let parser = get_address_parser_2021_uncompressed(&file_path, &100_000, &teryt_mapping);
         let batches: Vec<arrow::array::RecordBatch> = parser
             .expect("Something wrong while creating parser object.")
             .into_iter()
-            .collect();
+            .collect::<Result<_, _>>()
+            .expect("Failed to parse addresses.");
         let arrow_batch = concat_batches(&crate::common::SCHEMA_CSV.clone(), &batches)
             .expect("Error in concatenating batches");
         assert_eq!(arrow_batch.num_rows(), 3);
-        assert_eq!(arrow_batch.num_columns(), 24);
+        assert_eq!(arrow_batch.num_columns(), 25);
         let expected_teryt_gmina = &StringArray::from(vec!["0807043", "0805043", "0807023"]);
         let teryt_gmina: &StringArray = &arrow_batch
             .column_by_name("teryt_gmina")
@@ -747,11 +1565,12 @@ mod tests {
         let batches: Vec<arrow::array::RecordBatch> = parser
             .expect("Something wrong while creating parser object.")
             .into_iter()
-            .collect();
+            .collect::<Result<_, _>>()
+            .expect("Failed to parse addresses.");
         let arrow_batch = concat_batches(&crate::common::SCHEMA_CSV.clone(), &batches)
             .expect("Error in concatenating batches");
         assert_eq!(arrow_batch.num_rows(), 2);
-        assert_eq!(arrow_batch.num_columns(), 24);
+        assert_eq!(arrow_batch.num_columns(), 25);
         let x = arrow_batch
             .column_by_name("x_epsg_2180")
             .expect("Expected x_epsg_2180 column");
@@ -763,7 +1582,15 @@ mod tests {
         let sample_file_path = "fixtures/PRG-punkty_adresowe.zip";
         let teryt_file_path = "fixtures/TERC_Urzedowy_2025-11-18.zip";
         let teryt_mapping = Arc::new(
-            get_teryt_mapping(false, &None, &None, &Some(PathBuf::from(teryt_file_path))).unwrap(),
+            get_teryt_mapping(
+                false,
+                &None,
+                &None,
+                &Some(PathBuf::from(teryt_file_path)),
+                &None,
+            )
+            .unwrap()
+            .mapping,
         );
         let f = std::fs::File::open(&sample_file_path)
             .expect(format!("Failed to open file: `{}`.", &sample_file_path).as_str());
@@ -773,11 +1600,12 @@ mod tests {
         let batches: Vec<arrow::array::RecordBatch> = parser
             .expect("Something wrong while creating parser object.")
             .into_iter()
-            .collect();
+            .collect::<Result<_, _>>()
+            .expect("Failed to parse addresses.");
         let arrow_batch = concat_batches(&crate::common::SCHEMA_CSV.clone(), &batches)
             .expect("Error in concatenating batches");
         assert_eq!(arrow_batch.num_rows(), 3);
-        assert_eq!(arrow_batch.num_columns(), 24);
+        assert_eq!(arrow_batch.num_columns(), 25);
         let x = arrow_batch
             .column_by_name("x_epsg_2180")
             .expect("Expected x_epsg_2180 column");
@@ -795,9 +1623,337 @@ mod tests {
         let batches: Vec<arrow::array::RecordBatch> = parser
             .expect("Something wrong while creating parser object.")
             .into_iter()
-            .collect();
+            .collect::<Result<_, _>>()
+            .expect("Failed to parse addresses.");
         assert_eq!(batches.len(), 2);
         assert_eq!(batches[0].num_rows(), 1);
         assert_eq!(batches[1].num_rows(), 1);
     }
+
+    #[test]
+    fn test_parallel_parser_2012_matches_sequential() {
+        let file_path = PathBuf::from("fixtures/sample_model2012.xml");
+        let sequential: Vec<arrow::array::RecordBatch> =
+            get_address_parser_2012_uncompressed(&file_path, &100_000)
+                .unwrap()
+                .collect::<Result<_, _>>()
+                .expect("Failed to parse addresses.");
+        // every address in its own range
+        let report = crate::common::ValidationReport::default();
+        let parallel: Vec<arrow::array::RecordBatch> =
+            parallel_parser_2012(&file_path, 100_000, 2, 1, false)
+                .unwrap()
+                .with_validation_report(Some(report.clone()))
+                .collect::<Result<_, _>>()
+                .expect("Failed to parse addresses.");
+        assert_eq!(parallel.len(), 2);
+        assert_eq!(report.counts().addresses, 2);
+        let schema = crate::common::SCHEMA_CSV.clone();
+        assert_eq!(
+            concat_batches(&schema, &parallel).unwrap(),
+            concat_batches(&schema, &sequential).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_parser_2012_skip_dictionary() {
+        use arrow::array::AsArray;
+        let file_path = PathBuf::from("fixtures/sample_model2012.xml");
+        let batches: Vec<arrow::array::RecordBatch> =
+            parser_2012_uncompressed(&file_path, &10, true)
+                .unwrap()
+                .collect::<Result<_, _>>()
+                .expect("Failed to parse addresses.");
+        assert_eq!(batches.len(), 1);
+        let batch = &batches[0];
+        assert_eq!(batch.num_rows(), 2);
+        // names come from the address, codes only from the dictionaries
+        let column = |name: &str| batch.column_by_name(name).unwrap().clone();
+        assert_eq!(column("miejscowosc").as_string::<i32>().value(0), "Konotop");
+        assert_eq!(
+            column("wojewodztwo").as_string::<i32>().value(0),
+            "lubuskie"
+        );
+        for name in [
+            "teryt_wojewodztwo",
+            "teryt_powiat",
+            "teryt_gmina",
+            "teryt_miejscowosc",
+        ] {
+            assert_eq!(column(name).null_count(), 2, "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_parser_2012_street_name_format() {
+        use arrow::array::AsArray;
+        // street names only in the dictionary, split into a prefix and the main part
+        let xml = std::fs::read_to_string("fixtures/sample_model2012.xml")
+            .unwrap()
+            .replace("<prg-ad:ulica>Podgórna</prg-ad:ulica>", "")
+            .replace(
+                "<mua:nazwaGlownaCzesc>Podgórna</mua:nazwaGlownaCzesc>",
+                "<mua:przedrostek1Czesc>al.</mua:przedrostek1Czesc>\
+                 <mua:nazwaGlownaCzesc>Podgórna</mua:nazwaGlownaCzesc>",
+            );
+        let streets = |format: Option<StreetNameFormat>| {
+            let mut parser = get_address_parser_2012_from_bytes(xml.as_bytes(), &10).unwrap();
+            if let Some(format) = format {
+                parser = parser.with_street_name_format(&format);
+            }
+            let batches: Vec<arrow::array::RecordBatch> = parser
+                .collect::<Result<_, _>>()
+                .expect("Failed to parse addresses.");
+            let column = batches[0].column_by_name("ulica").unwrap().clone();
+            column.as_string::<i32>().value(0).to_string()
+        };
+        assert_eq!(streets(None), "al. Podgórna");
+        let hyphenated = StreetNameFormat {
+            separator: "-",
+            trim_parts: true,
+        };
+        assert_eq!(streets(Some(hyphenated.clone())), "al.-Podgórna");
+        assert_eq!(
+            construct_full_name_from_parts(
+                "al.".to_string(),
+                String::new(),
+                String::new(),
+                "Podgórna".to_string(),
+                &hyphenated,
+            ),
+            "al.-Podgórna"
+        );
+    }
+
+    #[test]
+    fn test_mmap_parser_2012_matches_sequential() {
+        let file_path = PathBuf::from("fixtures/sample_model2012.xml");
+        let sequential: Vec<arrow::array::RecordBatch> =
+            get_address_parser_2012_uncompressed(&file_path, &100_000)
+                .unwrap()
+                .collect::<Result<_, _>>()
+                .expect("Failed to parse addresses.");
+        let mmap = map_file(&file_path).unwrap();
+        let mapped: Vec<arrow::array::RecordBatch> = get_address_parser_2012_mmap(&mmap, &100_000)
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .expect("Failed to parse addresses.");
+        assert_eq!(mapped, sequential);
+    }
+
+    #[test]
+    fn test_parallel_parser_2021_matches_sequential() {
+        let file_path = PathBuf::from("fixtures/sample_model2021.xml");
+        let teryt_file_path = "fixtures/TERC_Urzedowy_2025-11-18.zip";
+        let teryt_mapping = Arc::new(
+            get_teryt_mapping(
+                false,
+                &None,
+                &None,
+                &Some(PathBuf::from(teryt_file_path)),
+                &None,
+            )
+            .unwrap()
+            .mapping,
+        );
+        let sequential: Vec<arrow::array::RecordBatch> =
+            get_address_parser_2021_uncompressed(&file_path, &100_000, &teryt_mapping)
+                .unwrap()
+                .collect::<Result<_, _>>()
+                .expect("Failed to parse addresses.");
+        // every address in its own range
+        let parallel: Vec<arrow::array::RecordBatch> =
+            parallel_parser_2021(&file_path, 100_000, &teryt_mapping, 2, 1)
+                .unwrap()
+                .collect::<Result<_, _>>()
+                .expect("Failed to parse addresses.");
+        assert_eq!(parallel.len(), 3);
+        let schema = crate::common::SCHEMA_CSV.clone();
+        assert_eq!(
+            concat_batches(&schema, &parallel).unwrap(),
+            concat_batches(&schema, &sequential).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_row_order_independent_of_batch_size() {
+        let file_path_2012 = PathBuf::from("fixtures/sample_model2012.xml");
+        let file_path_2021 = PathBuf::from("fixtures/sample_model2021.xml");
+        let teryt_file_path = "fixtures/TERC_Urzedowy_2025-11-18.zip";
+        let teryt_mapping = Arc::new(
+            get_teryt_mapping(
+                false,
+                &None,
+                &None,
+                &Some(PathBuf::from(teryt_file_path)),
+                &None,
+            )
+            .unwrap()
+            .mapping,
+        );
+        let schema = crate::common::SCHEMA_CSV.clone();
+        let convert = |batch_size: usize| {
+            let batches_2012: Vec<arrow::array::RecordBatch> =
+                get_address_parser_2012_uncompressed(&file_path_2012, &batch_size)
+                    .unwrap()
+                    .collect::<Result<_, _>>()
+                    .expect("Failed to parse addresses.");
+            let batches_2021: Vec<arrow::array::RecordBatch> =
+                get_address_parser_2021_uncompressed(&file_path_2021, &batch_size, &teryt_mapping)
+                    .unwrap()
+                    .collect::<Result<_, _>>()
+                    .expect("Failed to parse addresses.");
+            // no empty batch at the end, also when the last batch is full
+            assert!(batches_2012.iter().all(|b| b.num_rows() > 0));
+            assert!(batches_2021.iter().all(|b| b.num_rows() > 0));
+            (
+                concat_batches(&schema, &batches_2012).unwrap(),
+                concat_batches(&schema, &batches_2021).unwrap(),
+            )
+        };
+        let expected = convert(100);
+        assert_eq!(convert(1), expected);
+        assert_eq!(convert(3), expected);
+    }
+
+    #[test]
+    fn test_enums_try_from_str() {
+        assert_eq!(OutputFormat::try_from("csv").unwrap(), OutputFormat::CSV);
+        #[cfg(feature = "geoparquet")]
+        assert_eq!(
+            OutputFormat::try_from("GeoParquet").unwrap(),
+            OutputFormat::GeoParquet
+        );
+        #[cfg(feature = "geoparquet")]
+        assert_eq!(
+            OutputFormat::try_from("parquet").unwrap(),
+            OutputFormat::Parquet
+        );
+        #[cfg(not(feature = "geoparquet"))]
+        assert!(OutputFormat::try_from("geoparquet").is_err());
+        #[cfg(feature = "protobuf")]
+        assert_eq!(
+            OutputFormat::try_from("protobuf").unwrap(),
+            OutputFormat::Protobuf
+        );
+        #[cfg(not(feature = "protobuf"))]
+        assert!(OutputFormat::try_from("pbf").is_err());
+        assert_eq!(
+            SchemaVersion::try_from("2021").unwrap(),
+            SchemaVersion::Model2021
+        );
+        assert_eq!(CRS::try_from("4326").unwrap(), CRS::Epsg4326);
+        assert_eq!(
+            ParquetCompression::try_from("BROTLI").unwrap(),
+            ParquetCompression::Brotli
+        );
+        let err = OutputFormat::try_from("excel").unwrap_err().to_string();
+        assert_eq!(
+            err,
+            format!(
+                "Unknown output format `excel`, expected one of: {}.",
+                OutputFormat::NAMES.join(", ")
+            )
+        );
+        assert!(SchemaVersion::try_from("9999").is_err());
+        assert!(CRS::try_from("3857").is_err());
+        assert!(ParquetCompression::try_from("lz4").is_err());
+        // every listed name is accepted
+        for &name in OutputFormat::NAMES {
+            assert_eq!(OutputFormat::try_from(name).unwrap().to_string(), name);
+        }
+        for name in SchemaVersion::NAMES {
+            assert_eq!(SchemaVersion::try_from(name).unwrap().to_string(), name);
+        }
+        for name in CRS::NAMES {
+            assert!(CRS::try_from(name).is_ok());
+        }
+        for name in ParquetCompression::NAMES {
+            assert!(ParquetCompression::try_from(name).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_enum_display_round_trip() {
+        fn round_trip<T>(variants: &[T])
+        where
+            T: std::fmt::Display
+                + std::str::FromStr<Err = anyhow::Error>
+                + std::fmt::Debug
+                + PartialEq,
+        {
+            for variant in variants {
+                let displayed = variant.to_string();
+                assert_eq!(&displayed.parse::<T>().unwrap(), variant, "{}", displayed);
+            }
+        }
+        round_trip(OutputFormat::all_variants());
+        round_trip(FileType::all_variants());
+        round_trip(SchemaVersion::all_variants());
+        round_trip(CRS::all_variants());
+        round_trip(TimestampTimezone::all_variants());
+        round_trip(ParquetCompression::all_variants());
+        assert_eq!(
+            OutputFormat::all_variants().len(),
+            OutputFormat::NAMES.len()
+        );
+        assert_eq!("epsg:2180".parse::<CRS>().unwrap(), CRS::Epsg2180);
+        assert!("EPSG:3857".parse::<CRS>().is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "geoparquet")]
+    fn test_parquet_compression_default_level() {
+        use parquet::basic::{BrotliLevel, Compression, ZstdLevel};
+        assert_eq!(
+            ParquetCompression::Zstd.to_parquet(None).unwrap(),
+            Compression::ZSTD(ZstdLevel::try_new(11).unwrap())
+        );
+        assert_eq!(
+            ParquetCompression::Brotli.to_parquet(Some(3)).unwrap(),
+            Compression::BROTLI(BrotliLevel::try_new(3).unwrap())
+        );
+        assert_eq!(
+            ParquetCompression::Snappy.to_parquet(Some(3)).unwrap(),
+            Compression::SNAPPY
+        );
+        assert!(ParquetCompression::Zstd.to_parquet(Some(100)).is_err());
+    }
+
+    #[test]
+    fn test_schema_version_detect_from_fixtures() {
+        assert_eq!(
+            SchemaVersion::detect_from_path(Path::new("fixtures/sample_model2012.xml")).unwrap(),
+            SchemaVersion::Model2012
+        );
+        assert_eq!(
+            SchemaVersion::detect_from_path(Path::new("fixtures/sample_model2021.xml")).unwrap(),
+            SchemaVersion::Model2021
+        );
+        // first XML/GML entry of the archive is the 2012 file
+        assert_eq!(
+            SchemaVersion::detect_from_path(Path::new("fixtures/PRG-punkty_adresowe.zip")).unwrap(),
+            SchemaVersion::Model2012
+        );
+        assert_eq!(
+            SchemaVersion::detect_from_path(Path::new("fixtures/sample_model2012_split.zip"))
+                .unwrap(),
+            SchemaVersion::Model2012
+        );
+    }
+
+    #[test]
+    fn test_schema_version_detect_unknown_input() {
+        let err = SchemaVersion::detect(&b"<root><a>1</a></root>"[..]).unwrap_err();
+        assert!(err.to_string().contains("--schema-version"));
+        assert!(matches!(err, PrgError::UnknownSchema(_)));
+        let err =
+            SchemaVersion::detect_from_path(Path::new("fixtures/TERC_Urzedowy_2025-11-18.xml"))
+                .unwrap_err();
+        assert!(format!("{:#}", err).contains("neither"));
+        assert!(matches!(err, PrgError::UnknownSchema(_)));
+        let err = SchemaVersion::detect_from_path(Path::new("fixtures/definitely_nonexistent.xml"))
+            .unwrap_err();
+        assert!(matches!(err, PrgError::Io(_)));
+    }
 }
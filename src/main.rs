@@ -1,353 +1,302 @@
 use std::sync::Arc;
-use std::{collections::HashMap, path::PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 
 use anyhow::{Context, Result};
-use arrow::array::{Array, ArrayRef, Float64Array, RecordBatch};
-use arrow::csv::writer::WriterBuilder;
-use arrow::datatypes::Schema;
 use clap::Parser;
-use geoarrow::array::{GeoArrowArray, PointBuilder};
-use geoarrow::datatypes::{CoordType, Dimension, Metadata, PointType};
-use geoparquet::writer::{GeoParquetRecordBatchEncoder, GeoParquetWriterOptions};
-use parquet::{arrow::arrow_writer::ArrowWriter, file::properties::WriterProperties};
-use prg_convert::CRS;
-use prg_convert::common::{CRS_2180, CRS_4326, get_geoparquet_schema};
+use prg_convert::common::{ValidationCounts, Verbosity, set_verbosity, verbosity};
+use prg_convert::convert::{convert, output_schema};
+use prg_convert::progress;
+use prg_convert::self_test;
+use prg_convert::transform_bench::{benchmark_transform, read_input_positions};
 
 mod cli;
-use prg_convert::{
-    FileType, OutputFormat, SchemaVersion, get_address_parser_2012_uncompressed,
-    get_address_parser_2012_zip, get_address_parser_2021_uncompressed, get_address_parser_2021_zip,
-    get_teryt_mapping, terc::Terc,
-};
-use zip::ZipArchive;
+mod expected_counts;
+mod manifest;
+#[cfg(feature = "http")]
+mod serve;
 
-use crate::cli::CompressedFile;
-
-enum OutputWriter {
-    Csv(arrow::csv::writer::Writer<std::fs::File>),
-    GeoParquet {
-        writer: ArrowWriter<std::fs::File>,
-        encoder: GeoParquetRecordBatchEncoder,
-        crs: CRS,
-        geom_type: PointType,
-        geoparquet_schema: Arc<Schema>,
-    },
-}
-
-impl OutputWriter {
-    fn write_batch(&mut self, batch: &RecordBatch) -> anyhow::Result<()> {
-        match self {
-            OutputWriter::Csv(w) => {
-                w.write(batch).context("Failed to write CSV batch.")?;
-            }
-            OutputWriter::GeoParquet {
-                writer,
-                encoder,
-                crs,
-                geom_type,
-                geoparquet_schema,
-            } => {
-                let geo_batch =
-                    canonical_to_geoparquet_batch(batch, crs, geom_type, geoparquet_schema)?;
-                let encoded = encoder
-                    .encode_record_batch(&geo_batch)
-                    .context("Failed to encode GeoParquet batch.")?;
-                writer
-                    .write(&encoded)
-                    .context("Failed to write GeoParquet batch.")?;
-            }
-        }
-        Ok(())
-    }
-
-    fn finish(self) -> anyhow::Result<()> {
-        match self {
-            OutputWriter::Csv(_) => Ok(()),
-            OutputWriter::GeoParquet {
-                mut writer,
-                encoder,
-                ..
-            } => {
-                let kv_metadata = encoder
-                    .into_keyvalue()
-                    .context("Could not create GeoParquet K/V metadata.")?;
-                writer.append_key_value_metadata(kv_metadata);
-                writer
-                    .finish()
-                    .context("Failed to write GeoParquet metadata.")?;
-                Ok(())
-            }
+fn print_validation_report(counts: &ValidationCounts, error_count: usize) {
+    println!("🔎 Validation report:");
+    println!("  Addresses: {}", counts.addresses);
+    println!("  Addresses with errors: {}", error_count);
+    println!(
+        "  Component references: {} (unresolved: {})",
+        counts.components, counts.unresolved_references
+    );
+    if counts.unknown_tags.is_empty() {
+        println!("  Unknown tags: none");
+    } else {
+        println!("  Unknown tags:");
+        for (name, count) in &counts.unknown_tags {
+            println!("    {}: {}", name, count);
         }
     }
 }
 
-/// Convert a canonical (SCHEMA_CSV-shaped) batch into a GeoParquet batch:
-/// build a `geometry` point column from the coordinate columns selected by `crs`,
-/// drop `x_epsg_2180`/`y_epsg_2180`, and reorder to match `geoparquet_schema`.
-fn canonical_to_geoparquet_batch(
-    batch: &RecordBatch,
-    crs: &CRS,
-    geom_type: &PointType,
-    geoparquet_schema: &Arc<Schema>,
-) -> anyhow::Result<RecordBatch> {
-    let (x_name, y_name) = match crs {
-        CRS::Epsg2180 => ("x_epsg_2180", "y_epsg_2180"),
-        CRS::Epsg4326 => ("dlugosc_geograficzna", "szerokosc_geograficzna"),
-    };
-    let xs = batch
-        .column_by_name(x_name)
-        .context("canonical batch missing x column")?
-        .as_any()
-        .downcast_ref::<Float64Array>()
-        .context("x column is not Float64")?;
-    let ys = batch
-        .column_by_name(y_name)
-        .context("canonical batch missing y column")?
-        .as_any()
-        .downcast_ref::<Float64Array>()
-        .context("y column is not Float64")?;
-    let points: Vec<Option<geo_types::Point>> = (0..batch.num_rows())
-        .map(|i| {
-            if xs.is_null(i) || ys.is_null(i) {
-                None
-            } else {
-                Some(geo_types::point!(x: xs.value(i), y: ys.value(i)))
-            }
-        })
-        .collect();
-    let geometry =
-        PointBuilder::from_nullable_points(points.iter().map(Option::as_ref), geom_type.clone())
-            .finish();
-
-    let mut columns: Vec<ArrayRef> = Vec::with_capacity(geoparquet_schema.fields().len());
-    for field in geoparquet_schema.fields() {
-        if field.name() == "geometry" {
-            columns.push(geometry.to_array_ref());
-        } else {
-            let col = batch
-                .column_by_name(field.name())
-                .with_context(|| format!("canonical batch missing column `{}`", field.name()))?;
-            columns.push(col.clone());
+/// Installs a Ctrl-C handler: the first interrupt sets the returned flag so the
+/// conversion stops after the current batch and finalizes the output file,
+/// the second one exits immediately.
+fn install_interrupt_handler() -> Result<Arc<AtomicBool>> {
+    let interrupt = Arc::new(AtomicBool::new(false));
+    let flag = interrupt.clone();
+    ctrlc::set_handler(move || {
+        if flag.swap(true, Ordering::SeqCst) {
+            println!("Interrupted again, exiting without finalizing the output file.");
+            std::process::exit(130);
         }
-    }
-    Ok(RecordBatch::try_new(geoparquet_schema.clone(), columns)?)
+        println!(
+            "Interrupted, stopping after the current batch and finalizing the output file (press Ctrl-C again to abort)..."
+        );
+    })
+    .context("Could not set Ctrl-C handler.")?;
+    Ok(interrupt)
 }
 
-fn parse_file(
-    file_type: &FileType,
-    parsed_args: &cli::ParsedArgs,
-    file_path: &PathBuf,
-    output_writer: &mut OutputWriter,
-    zip_file_index: &Option<usize>,
-    teryt_mapping: &Option<std::sync::Arc<HashMap<String, Terc>>>,
-) -> anyhow::Result<usize> {
-    let mut processed_rows = 0;
-    match (&file_type, &parsed_args.schema_version) {
-        (FileType::XML, SchemaVersion::Model2012) => {
-            for batch in get_address_parser_2012_uncompressed(&file_path, &parsed_args.batch_size)?
-            {
-                processed_rows += batch.num_rows();
-                println!("Read batch of {} addresses.", batch.num_rows());
-                output_writer.write_batch(&batch)?;
-            }
-        }
-        (FileType::ZIP, SchemaVersion::Model2012) => {
-            let f = std::fs::File::open(&file_path)
-                .with_context(|| format!("Failed to open file: `{}`.", &file_path.display()))?;
-            let mut archive = ZipArchive::new(f).with_context(|| {
-                format!("Failed to decompress ZIP file: `{}`.", &file_path.display())
-            })?;
-            for batch in get_address_parser_2012_zip(
-                &mut archive,
-                &parsed_args.batch_size,
-                zip_file_index.unwrap(),
-            )? {
-                processed_rows += batch.num_rows();
-                println!("Read batch of {} addresses.", batch.num_rows());
-                output_writer.write_batch(&batch)?;
-            }
-        }
-        (FileType::XML, SchemaVersion::Model2021) => {
-            for batch in get_address_parser_2021_uncompressed(
-                &file_path,
-                &parsed_args.batch_size,
-                teryt_mapping.as_ref().unwrap(),
-            )? {
-                processed_rows += batch.num_rows();
-                println!("Read batch of {} addresses.", batch.num_rows());
-                output_writer.write_batch(&batch)?;
+fn main() -> Result<()> {
+    let start_time = std::time::Instant::now();
+    let args = cli::RawArgs::parse();
+    set_verbosity(args.verbosity());
+    if let Some(address) = args.serve_address() {
+        #[cfg(feature = "http")]
+        return serve::serve(address);
+        #[cfg(not(feature = "http"))]
+        anyhow::bail!(
+            "Cannot serve on `{}`, the program was built without the `http` feature.",
+            address
+        );
+    }
+    if let Some(listing) = args.list_zip()? {
+        print!("{}", listing);
+        return Ok(());
+    }
+    if args.self_test() {
+        let checks = self_test::run_self_test();
+        for check in &checks {
+            match &check.error {
+                None => println!("✅ {}", check.name),
+                Some(error) => println!("❌ {}: {}", check.name, error),
             }
         }
-        (FileType::ZIP, SchemaVersion::Model2021) => {
-            let f = std::fs::File::open(&file_path)
-                .with_context(|| format!("Failed to open file: `{}`.", &file_path.display()))?;
-            let mut archive = ZipArchive::new(f).with_context(|| {
-                format!("Failed to decompress ZIP file: `{}`.", &file_path.display())
-            })?;
-            for batch in get_address_parser_2021_zip(
-                &mut archive,
-                &parsed_args.batch_size,
-                teryt_mapping.as_ref().unwrap(),
-                zip_file_index.unwrap(),
-            )? {
-                processed_rows += batch.num_rows();
-                println!("Read batch of {} addresses.", batch.num_rows());
-                output_writer.write_batch(&batch)?;
-            }
+        let failed = checks.iter().filter(|c| c.error.is_some()).count();
+        if failed > 0 {
+            anyhow::bail!("{} of {} self-test checks failed.", failed, checks.len());
         }
+        println!("All {} self-test checks passed.", checks.len());
+        return Ok(());
     }
-    Ok(processed_rows)
-}
+    let mut parsed_args: cli::ParsedArgs = args
+        .with_env_fallbacks(|name| std::env::var(name).ok())?
+        .try_into()
+        .expect("Could not parse args.");
 
-fn main() -> Result<()> {
-    let start_time = std::time::Instant::now();
-    let args = cli::RawArgs::parse();
-    let mut parsed_args: cli::ParsedArgs = args.try_into().expect("Could not parse args.");
+    if parsed_args.print_schema {
+        let schema =
+            output_schema(&parsed_args.convert_options(vec![])).map_err(cli::with_option_flags)?;
+        println!(
+            "{}",
+            serde_json::to_string_pretty(schema.as_ref())
+                .context("Could not serialize output schema.")?
+        );
+        return Ok(());
+    }
 
-    cli::print_parsed_args(&parsed_args);
+    if verbosity() >= Verbosity::Normal {
+        cli::print_parsed_args(&parsed_args);
+    }
+    if !parsed_args.column_renames.is_empty() {
+        // fail before reading any input if a renamed column does not exist
+        output_schema(&parsed_args.convert_options(vec![])).map_err(cli::with_option_flags)?;
+    }
 
     // Download data if requested, keeping the temp file alive for the duration of processing
     let _temp_file;
-    let files_to_process: Vec<cli::FileRecord>;
+    let files_to_process: Vec<prg_convert::convert::FileRecord>;
     if parsed_args.download_data {
-        println!("⬇️  Downloading PRG data...");
+        progress!("⬇️  Downloading PRG data...");
         let temp = cli::download_prg_data(parsed_args.download_data_path.as_deref())?;
         let download_path = match (&parsed_args.download_data_path, &temp) {
             (Some(path), _) => path.to_string_lossy().to_string(),
             (None, Some(t)) => t.path().to_string_lossy().to_string(),
             _ => unreachable!(),
         };
-        files_to_process =
-            cli::parse_input_paths(&vec![download_path], &parsed_args.schema_version)?;
+        files_to_process = cli::parse_input_paths(
+            &vec![download_path],
+            &parsed_args.schema_version,
+            &parsed_args.zip_entry_filter,
+            parsed_args.skip_bad_entries,
+        )?;
         _temp_file = temp;
     } else {
         files_to_process = std::mem::take(&mut parsed_args.parsed_paths);
         _temp_file = None;
     }
 
-    let mut file_counter = 1;
-    let mut total_row_count = 0;
-    let mut total_file_size = 0;
-
-    let output_file = std::fs::File::create(&parsed_args.output_path).with_context(|| {
-        format!(
-            "could not create output file `{}`",
-            &parsed_args.output_path.to_string_lossy()
-        )
-    })?;
-    let mut output_writer = match &parsed_args.output_format {
-        OutputFormat::CSV => {
-            OutputWriter::Csv(WriterBuilder::new().with_header(true).build(output_file))
-        }
-        OutputFormat::GeoParquet => {
-            let geoarrow_crs = match parsed_args.crs {
-                CRS::Epsg2180 => CRS_2180.clone(),
-                CRS::Epsg4326 => CRS_4326.clone(),
-            };
-            let geom_type =
-                PointType::new(Dimension::XY, Arc::new(Metadata::new(geoarrow_crs, None)))
-                    .with_coord_type(CoordType::Separated);
-            let geoparquet_schema = get_geoparquet_schema(geom_type.clone());
-            let props = WriterProperties::builder()
-                .set_max_row_group_row_count(Some(parsed_args.parquet_row_group_size))
-                .set_writer_version(parsed_args.parquet_version)
-                .set_compression(parsed_args.parquet_compression)
-                .build();
-            let encoder = GeoParquetRecordBatchEncoder::try_new(
-                &geoparquet_schema,
-                &GeoParquetWriterOptions::default(),
-            )
-            .expect("Could not create GeoParquet encoder.");
-            let writer = ArrowWriter::try_new(output_file, encoder.target_schema(), Some(props))
-                .expect("Could not create GeoParquet writer.");
-            OutputWriter::GeoParquet {
-                writer,
-                encoder,
-                crs: parsed_args.crs,
-                geom_type,
-                geoparquet_schema,
-            }
-        }
-    };
-
-    let num_files_to_process = &files_to_process.len();
-    let teryt_mapping: Option<std::sync::Arc<HashMap<String, Terc>>> =
-        match &parsed_args.schema_version {
-            SchemaVersion::Model2012 => None,
-            SchemaVersion::Model2021 => Some(std::sync::Arc::new(get_teryt_mapping(
-                parsed_args.download_teryt,
-                &parsed_args.teryt_api_username,
-                &parsed_args.teryt_api_password,
-                &parsed_args.teryt_path,
-            )?)),
-        };
-    for file in &files_to_process {
-        total_file_size += &file.size_in_bytes;
-
+    if let Some(max_points) = parsed_args.transform_only {
+        let points =
+            read_input_positions(&files_to_process, parsed_args.schema_version, max_points)?;
+        let benchmark = benchmark_transform(&points)?;
         println!(
-            "🪓 Processing file ({}/{})({}): `{}`, size: {:.2}MB.",
-            &file_counter,
-            &num_files_to_process,
-            &file.file_type,
-            &file.path.display(),
-            (file.size_in_bytes as f64 / 1024.0 / 1024.0)
+            "⏱️  Transformed {} points from EPSG:2180 to EPSG:4326:",
+            benchmark.points
         );
-        println!("Parsing data...");
-        match file.file_type {
-            FileType::XML => {
-                let processed_rows = parse_file(
-                    &file.file_type,
-                    &parsed_args,
-                    &file.path,
-                    &mut output_writer,
-                    &None,
-                    &teryt_mapping,
-                )?;
-                total_row_count += processed_rows;
-            }
-            FileType::ZIP => {
-                let files_to_parse: Vec<&CompressedFile> = file
-                    .compressed_files
-                    .as_ref()
-                    .unwrap()
-                    .iter()
-                    .filter(|f| f.to_be_parsed)
-                    .collect();
-                for compressed_file in files_to_parse {
-                    println!("Decompressing file: {}", compressed_file.name);
-                    let processed_rows = parse_file(
-                        &file.file_type,
-                        &parsed_args,
-                        &file.path,
-                        &mut output_writer,
-                        &Some(compressed_file.index),
-                        &teryt_mapping,
-                    )?;
-                    total_row_count += processed_rows;
-                }
-            }
-        }
-
-        file_counter += 1;
+        println!(
+            "  Point by point: {:.3}s ({:.0} points/s)",
+            benchmark.per_point.as_secs_f64(),
+            benchmark.per_point_throughput()
+        );
+        println!(
+            "  In one call: {:.3}s ({:.0} points/s)",
+            benchmark.batched.as_secs_f64(),
+            benchmark.batched_throughput()
+        );
+        return Ok(());
     }
-    output_writer.finish()?;
+
+    let total_file_size: u64 = files_to_process.iter().map(|f| f.size_in_bytes).sum();
+    let mut options = parsed_args.convert_options(files_to_process.clone());
+    options.interrupt = Some(install_interrupt_handler()?);
+    let report = convert(options).map_err(cli::with_option_flags)?;
+    let total_row_count = report.total_rows;
     let duration = start_time.elapsed();
-    println!("----------------------------------------");
-    println!(
+    progress!("----------------------------------------");
+    progress!(
         "📊 Total addresses read {}. Duration: {:.1}s. Input data size: {:.2}MB.",
         total_row_count,
         duration.as_secs_f64(),
         (total_file_size as f64 / 1024.0 / 1024.0)
     );
+    if report.interrupted {
+        println!("⚠️  Interrupted, the output contains only the addresses read until then.");
+    }
+    if let Some(tail) = parsed_args.tail {
+        progress!(
+            "✂️  Written only the last {} addresses.",
+            tail.min(total_row_count)
+        );
+    }
+    if let Some(rate) = parsed_args.sample_rate {
+        progress!(
+            "🎲 Sampled {} addresses (sample rate {}, seed {}).",
+            total_row_count,
+            rate,
+            parsed_args.sample_seed
+        );
+    }
+    if let Some(changes) = &report.changes {
+        progress!(
+            "🔀 Differences from --compare: {} added, {} removed, {} modified ({} unchanged).",
+            changes.added,
+            changes.removed,
+            changes.modified,
+            changes.unchanged
+        );
+    }
+    if parsed_args.drop_null_geometry {
+        progress!(
+            "🗺️  Addresses dropped because they had no coordinates: {}.",
+            report.null_geometry_rows
+        );
+    }
+    if report.skipped_rows > 0 {
+        println!(
+            "⚠️  Addresses skipped because of errors: {}.",
+            report.skipped_rows
+        );
+        if let Some(path) = &parsed_args.errors_to {
+            println!("   Details written to: {}", path.to_string_lossy());
+        }
+    }
+    if let (Some(records), Some(path)) = (report.join_audit_records, &parsed_args.join_audit) {
+        progress!(
+            "🔎 Join problems (municipalities and cities): {}, written to: {}",
+            records,
+            path.display()
+        );
+    }
+    if let Some(phase_durations) = &report.phase_durations {
+        println!("⏱️  Time by phase:");
+        for (phase, duration) in phase_durations {
+            println!("  {}: {:.3}s", phase, duration.as_secs_f64());
+        }
+    }
 
-    let _ = &parsed_args.output_path.metadata().inspect(|f| {
-        let output_file_size_mb = f.len() as f64 / 1024.0 / 1024.0;
+    if !report.skipped_entries.is_empty() {
         println!(
-            "💾 Output file: {} size: {:.2}MB",
-            &parsed_args.output_path.to_string_lossy(),
-            output_file_size_mb
+            "⚠️  Files inside ZIP archives skipped because of errors: {}.",
+            report.skipped_entries.len()
+        );
+        for skipped in &report.skipped_entries {
+            println!(
+                "   `{}` in `{}`: {}",
+                skipped.entry,
+                skipped.path.display(),
+                skipped.error
+            );
+        }
+    }
+
+    if let Some(path) = &parsed_args.manifest_path {
+        manifest::RunManifest::new(
+            &parsed_args,
+            &files_to_process,
+            report.terc_catalog_date.clone(),
+            total_row_count,
+            report.skipped_rows,
+            report.interrupted,
+            duration,
+        )
+        .with_output_files(&report.output_files)
+        .with_terc_file(report.terc_path.as_deref())
+        .write(path)?;
+        progress!("📝 Manifest: {}", path.to_string_lossy());
+    }
+
+    if let (Some(expected), Some(actual)) =
+        (&parsed_args.expected_counts, &report.voivodeship_counts)
+    {
+        let mismatches = expected_counts::count_mismatches(
+            expected,
+            actual,
+            parsed_args.expect_counts_tolerance,
+        );
+        if !mismatches.is_empty() {
+            println!("❌ Address counts differ from --expect-counts:");
+            for mismatch in &mismatches {
+                println!("   {}", mismatch);
+            }
+            anyhow::bail!(
+                "Address counts differ from --expect-counts for {} of {} voivodeships.",
+                mismatches.len(),
+                expected.len()
+            );
+        }
+        progress!(
+            "✅ Address counts match --expect-counts ({} voivodeships).",
+            expected.len()
         );
-    });
+    }
+
+    if let Some(counts) = &report.validation {
+        print_validation_report(counts, report.skipped_rows);
+        if report.skipped_rows > 0 {
+            anyhow::bail!(
+                "Validation failed: {} addresses could not be parsed.",
+                report.skipped_rows
+            );
+        }
+        return Ok(());
+    }
+
+    for output_file in &report.output_files {
+        let _ = output_file.metadata().inspect(|f| {
+            let output_file_size_mb = f.len() as f64 / 1024.0 / 1024.0;
+            progress!(
+                "💾 Output file: {} size: {:.2}MB",
+                &output_file.to_string_lossy(),
+                output_file_size_mb
+            );
+        });
+    }
 
     Ok(())
 }
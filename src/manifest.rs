@@ -0,0 +1,142 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use prg_convert::FileType;
+#[cfg(feature = "geoparquet")]
+use prg_convert::OutputFormat;
+use prg_convert::common::StreetFilter;
+use prg_convert::convert::FileRecord;
+use serde::Serialize;
+
+use crate::cli::ParsedArgs;
+
+#[derive(Serialize)]
+pub struct InputFile {
+    pub path: String,
+    pub file_type: String,
+    pub size_in_bytes: u64,
+    /// Names of the files read from inside a ZIP archive.
+    pub parsed_entries: Option<Vec<String>>,
+}
+
+impl From<&FileRecord> for InputFile {
+    fn from(file: &FileRecord) -> Self {
+        let parsed_entries = match file.file_type {
+            FileType::XML => None,
+            FileType::ZIP => file.compressed_files.as_ref().map(|entries| {
+                entries
+                    .iter()
+                    .filter(|e| e.to_be_parsed)
+                    .map(|e| e.name.clone())
+                    .collect()
+            }),
+        };
+        Self {
+            path: file.path.to_string_lossy().to_string(),
+            file_type: file.file_type.to_string(),
+            size_in_bytes: file.size_in_bytes,
+            parsed_entries,
+        }
+    }
+}
+
+/// Machine-readable summary of a conversion, written with `--manifest`.
+#[derive(Serialize)]
+pub struct RunManifest {
+    pub prg_convert_version: &'static str,
+    pub inputs: Vec<InputFile>,
+    pub schema_version: String,
+    /// Date of the TERC catalog used for administrative unit names (schema 2021 only).
+    pub terc_catalog_date: Option<String>,
+    /// TERC file read, e.g. the one chosen from a directory given as `--teryt-path`.
+    pub terc_file: Option<String>,
+    pub output_path: String,
+    /// Files written, several with `--rows-per-file`.
+    pub output_files: Vec<String>,
+    pub output_format: String,
+    /// CRS of the geometry column (GeoParquet only, CSV and plain parquet have coordinates in both).
+    pub crs: Option<String>,
+    pub parquet_compression: Option<String>,
+    pub start_date: Option<String>,
+    pub end_date: Option<String>,
+    pub sample_rate: Option<f64>,
+    pub sample_seed: Option<u64>,
+    /// `with street` or `without street` when addresses were filtered on having a street.
+    pub street_filter: Option<String>,
+    pub total_rows: usize,
+    pub skipped_rows: usize,
+    /// The run was interrupted with Ctrl-C and the output is incomplete.
+    pub interrupted: bool,
+    pub duration_seconds: f64,
+}
+
+impl RunManifest {
+    pub fn new(
+        parsed_args: &ParsedArgs,
+        files: &[FileRecord],
+        terc_catalog_date: Option<String>,
+        total_rows: usize,
+        skipped_rows: usize,
+        interrupted: bool,
+        duration: std::time::Duration,
+    ) -> Self {
+        #[cfg(feature = "geoparquet")]
+        let (crs, parquet_compression) = (
+            matches!(parsed_args.output_format, OutputFormat::GeoParquet)
+                .then(|| parsed_args.crs.to_string()),
+            parsed_args
+                .output_format
+                .is_parquet()
+                .then(|| parsed_args.parquet_compression.to_string()),
+        );
+        #[cfg(not(feature = "geoparquet"))]
+        let (crs, parquet_compression) = (None, None);
+        Self {
+            prg_convert_version: env!("CARGO_PKG_VERSION"),
+            inputs: files.iter().map(InputFile::from).collect(),
+            schema_version: parsed_args.schema_version.to_string(),
+            terc_catalog_date,
+            terc_file: None,
+            output_path: parsed_args.output_path.to_string_lossy().to_string(),
+            output_files: Vec::new(),
+            output_format: parsed_args.output_format.to_string(),
+            crs,
+            parquet_compression,
+            start_date: parsed_args.start_date.map(|d| d.to_string()),
+            end_date: parsed_args.end_date.map(|d| d.to_string()),
+            sample_rate: parsed_args.sample_rate,
+            sample_seed: parsed_args.sample_rate.map(|_| parsed_args.sample_seed),
+            street_filter: (parsed_args.street_filter != StreetFilter::Any)
+                .then(|| parsed_args.street_filter.to_string()),
+            total_rows,
+            skipped_rows,
+            interrupted,
+            duration_seconds: duration.as_secs_f64(),
+        }
+    }
+
+    /// Files written, reported by `convert`.
+    pub fn with_output_files(mut self, output_files: &[PathBuf]) -> Self {
+        self.output_files = output_files
+            .iter()
+            .map(|p| p.to_string_lossy().to_string())
+            .collect();
+        self
+    }
+
+    /// TERC file read, reported by `convert`.
+    pub fn with_terc_file(mut self, terc_file: Option<&Path>) -> Self {
+        self.terc_file = terc_file.map(|p| p.to_string_lossy().to_string());
+        self
+    }
+
+    pub fn write(&self, path: &Path) -> anyhow::Result<()> {
+        let file = std::fs::File::create(path).with_context(|| {
+            format!(
+                "Could not create manifest file `{}`.",
+                path.to_string_lossy()
+            )
+        })?;
+        serde_json::to_writer_pretty(file, self).context("Could not write manifest file.")
+    }
+}
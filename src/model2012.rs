@@ -1,24 +1,68 @@
 use std::collections::HashMap;
+use std::collections::HashSet;
 use std::io::BufRead;
+use std::sync::Arc;
+use std::sync::LazyLock;
 
-use chrono::DateTime;
-use chrono::NaiveDate;
+use arrow::array::ArrayBuilder;
+use arrow::array::RecordBatch;
+use arrow::array::RecordBatchReader;
+use arrow::datatypes::SchemaRef;
+use arrow::error::ArrowError;
 use quick_xml::Reader;
 use quick_xml::events::Event;
 
+use crate::CRS;
 use crate::CoordOrder;
+use crate::PrgError;
 use crate::common::CanonicalBuilders;
-use crate::common::EPOCH_DATE;
+use crate::common::ErrorSink;
+use crate::common::RecordFilter;
+use crate::common::RecordState;
+use crate::common::SCHEMA_CSV;
+use crate::common::UntrimmedColumns;
+use crate::common::ValidationCounts;
+use crate::common::ValidationReport;
+use crate::common::check_srs_name;
 use crate::common::get_attribute;
+use crate::common::normalize_name;
 use crate::common::option_append_value_or_null;
+use crate::common::parse_date_days;
+use crate::common::parse_datetime_millis;
 use crate::common::parse_gml_pos;
 use crate::common::str_append_value_or_null;
+use crate::common::trim_unless;
+use crate::common::try_get_attribute;
+use crate::progress;
+use crate::timings::{self, Phase};
 
-const ADDRESS_TAG: &[u8] = b"prg-ad:PRG_PunktAdresowy";
+pub(crate) const ADDRESS_TAG: &[u8] = b"prg-ad:PRG_PunktAdresowy";
 const ADMINISTRATIVE_UNIT_TAG: &[u8] = b"prg-ad:PRG_JednostkaAdministracyjnaNazwa";
 const CITY_TAG: &[u8] = b"prg-ad:PRG_MiejscowoscNazwa";
 const STREET_TAG: &[u8] = b"prg-ad:PRG_UlicaNazwa";
 
+/// Canonical values of the `status` column: `istniejacy`, `prognozowany`,
+/// `w budowie` and `nieistniejacy`. Keys are `prg-ad:status` values after
+/// `status_key` (lowercase, without Polish diacritics and whitespace).
+static STATUS: LazyLock<HashMap<&str, &str>> = LazyLock::new(|| {
+    let mut mapping = HashMap::new();
+    mapping.insert("istniejacy", "istniejacy");
+    mapping.insert("prognozowany", "prognozowany");
+    mapping.insert("wbudowie", "w budowie");
+    mapping.insert("wtrakciebudowy", "w budowie");
+    mapping.insert("nieistniejacy", "nieistniejacy");
+    mapping
+});
+
+fn status_key(status: &str) -> String {
+    normalize_name(status).replace(' ', "")
+}
+
+/// Canonical value of a `prg-ad:status`, `None` if it is not known.
+fn canonical_status(status: &str) -> Option<&'static str> {
+    STATUS.get(status_key(status).as_str()).copied()
+}
+
 #[derive(Clone, PartialEq, Debug)]
 pub enum KomponentType {
     Country,
@@ -30,12 +74,30 @@ pub enum KomponentType {
     Unknown,
 }
 
-#[allow(dead_code)]
-#[derive(Clone)]
+/// Administrative unit, city or street referenced by `prg-ad:komponent`,
+/// read by `build_dictionaries`.
+#[derive(Clone, Debug)]
 pub struct AdditionalInfo {
     typ: KomponentType,
     name: String,
     teryt_id: Option<String>,
+    /// Parts of a street name, kept so the name can be rebuilt with another
+    /// `StreetNameFormat`.
+    street_name_parts: Option<[String; 4]>,
+}
+
+impl AdditionalInfo {
+    pub fn typ(&self) -> &KomponentType {
+        &self.typ
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn teryt_id(&self) -> Option<&str> {
+        self.teryt_id.as_deref()
+    }
 }
 
 impl Default for AdditionalInfo {
@@ -44,6 +106,27 @@ impl Default for AdditionalInfo {
             typ: KomponentType::Unknown,
             name: String::new(),
             teryt_id: None,
+            street_name_parts: None,
+        }
+    }
+}
+
+/// How `construct_full_name_from_parts` joins the parts of a street name.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StreetNameFormat<'a> {
+    /// Put between the non-empty parts.
+    pub separator: &'a str,
+    /// Whether to trim whitespace around each part first (parts with only
+    /// whitespace are then left out).
+    pub trim_parts: bool,
+}
+
+impl Default for StreetNameFormat<'_> {
+    /// Parts joined with a single space as they are, like in the output.
+    fn default() -> Self {
+        Self {
+            separator: " ",
+            trim_parts: false,
         }
     }
 }
@@ -54,13 +137,32 @@ pub fn construct_full_name_from_parts(
     name_part_2: String,
     name_part_3: String,
     name_part_4: String,
+    format: &StreetNameFormat,
 ) -> String {
     let name_parts = [name_part_1, name_part_2, name_part_3, name_part_4];
-    let non_empty_parts: Vec<String> = name_parts.into_iter().filter(|s| !s.is_empty()).collect();
-    non_empty_parts.join(" ")
+    let non_empty_parts: Vec<&str> = name_parts
+        .iter()
+        .map(|s| {
+            if format.trim_parts {
+                s.trim()
+            } else {
+                s.as_str()
+            }
+        })
+        .filter(|s| !s.is_empty())
+        .collect();
+    non_empty_parts.join(format.separator)
+}
+
+fn street_name(parts: &[String; 4], format: &StreetNameFormat) -> String {
+    let [part_1, part_2, part_3, part_4] = parts.clone();
+    construct_full_name_from_parts(part_1, part_2, part_3, part_4, format)
 }
 
-fn parse_additional_info<R: BufRead>(reader: &mut Reader<R>, tag: &[u8]) -> AdditionalInfo {
+fn parse_additional_info<R: BufRead>(
+    reader: &mut Reader<R>,
+    tag: &[u8],
+) -> anyhow::Result<AdditionalInfo> {
     let mut buffer = Vec::new();
     let mut last_tag = Vec::new();
     let mut typ: Option<KomponentType> = None;
@@ -70,6 +172,7 @@ fn parse_additional_info<R: BufRead>(reader: &mut Reader<R>, tag: &[u8]) -> Addi
     let mut name_part_3 = String::new();
     let mut name_part_4 = String::new();
     let mut teryt_id: Option<String> = None;
+    let mut street_name_parts: Option<[String; 4]> = None;
     loop {
         match reader.read_event_into(&mut buffer) {
             Ok(Event::Start(ref e)) => {
@@ -139,35 +242,61 @@ fn parse_additional_info<R: BufRead>(reader: &mut Reader<R>, tag: &[u8]) -> Addi
                     }
                     STREET_TAG => {
                         typ = Some(KomponentType::Street);
-                        name = Some(construct_full_name_from_parts(
-                            name_part_1,
-                            name_part_2,
-                            name_part_3,
-                            name_part_4,
-                        ));
+                        let parts = [name_part_1, name_part_2, name_part_3, name_part_4];
+                        name = Some(street_name(&parts, &StreetNameFormat::default()));
+                        street_name_parts = Some(parts);
                     }
                     _ => (),
                 }
                 break;
             }
             Ok(Event::Eof) => {
-                panic!("Error: reached end of file before end of address entry");
+                return Err(PrgError::Xml(anyhow::anyhow!(
+                    "Error: reached end of file before end of address entry"
+                ))
+                .into());
             }
             Err(e) => {
-                panic!("Error at position {}: {:?}", reader.error_position(), e);
+                return Err(PrgError::Xml(anyhow::anyhow!(
+                    "Error at position {}: {}",
+                    reader.error_position(),
+                    e
+                ))
+                .into());
             }
             _ => (), // we do not care about other events here
         }
         buffer.clear();
     }
-    AdditionalInfo {
+    Ok(AdditionalInfo {
         typ: typ.unwrap(),
         name: name.unwrap(),
         teryt_id: teryt_id,
+        street_name_parts,
+    })
+}
+
+/// Fraction of `prg-ad:komponent` references not found in the dictionaries
+/// above which a warning about the id prefix is printed.
+const MISSING_COMPONENTS_WARNING_RATIO: f64 = 0.5;
+
+/// Returns the `gml:id` referenced by a `prg-ad:komponent` link. Without
+/// `prefix` the namespace part of the link (up to the last `/` or `#`) is
+/// dropped, whatever it is; with `prefix` the link must start with it.
+fn component_id<'a>(href: &'a str, prefix: Option<&str>) -> Option<&'a str> {
+    match prefix {
+        Some(prefix) => href.strip_prefix(prefix),
+        None => href.rsplit(['/', '#']).next(),
     }
 }
 
-pub fn build_dictionaries<R: BufRead>(mut reader: Reader<R>) -> HashMap<String, AdditionalInfo> {
+/// Builds dictionaries of administrative units, cities and streets keyed by
+/// their `gml:id`. Returns an error if the input cannot be read (e.g. a
+/// corrupted file inside a ZIP archive) or is not well-formed XML.
+pub fn build_dictionaries<R: BufRead>(
+    mut reader: Reader<R>,
+) -> anyhow::Result<HashMap<String, AdditionalInfo>> {
+    let _timer = timings::start(Phase::Dictionaries);
     let mut dict = HashMap::<String, AdditionalInfo>::new();
     let mut buffer = Vec::new();
     // main loop that catches events when new object starts
@@ -175,61 +304,313 @@ pub fn build_dictionaries<R: BufRead>(mut reader: Reader<R>) -> HashMap<String,
         match reader.read_event_into(&mut buffer) {
             Ok(Event::Start(ref e)) => match e.name().as_ref() {
                 ADMINISTRATIVE_UNIT_TAG => {
-                    let id = "http://geoportal.gov.pl/PZGIK/dane/".to_string()
-                        + &get_attribute(e, b"gml:id");
-                    let info = parse_additional_info(&mut reader, ADMINISTRATIVE_UNIT_TAG);
+                    let id = get_attribute(e, b"gml:id").into_owned();
+                    let info = parse_additional_info(&mut reader, ADMINISTRATIVE_UNIT_TAG)?;
                     dict.insert(id, info);
                 }
                 CITY_TAG => {
-                    let id = "http://geoportal.gov.pl/PZGIK/dane/".to_string()
-                        + &get_attribute(e, b"gml:id");
-                    let info = parse_additional_info(&mut reader, CITY_TAG);
+                    let id = get_attribute(e, b"gml:id").into_owned();
+                    let info = parse_additional_info(&mut reader, CITY_TAG)?;
                     dict.insert(id, info);
                 }
                 STREET_TAG => {
-                    let id = "http://geoportal.gov.pl/PZGIK/dane/".to_string()
-                        + &get_attribute(e, b"gml:id");
-                    let info = parse_additional_info(&mut reader, STREET_TAG);
+                    let id = get_attribute(e, b"gml:id").into_owned();
+                    let info = parse_additional_info(&mut reader, STREET_TAG)?;
                     dict.insert(id, info);
                 }
                 _ => (),
             },
             Ok(Event::Eof) => break, // exits the loop when reaching end of file
-            Err(e) => panic!("Error at position {}: {:?}", reader.error_position(), e),
+            Err(e) => {
+                return Err(PrgError::Xml(anyhow::anyhow!(
+                    "Error at position {}: {}",
+                    reader.error_position(),
+                    e
+                ))
+                .into());
+            }
             _ => (), // we do not care about other events here
         }
         buffer.clear();
     }
-    dict
+    if dict.is_empty() {
+        println!(
+            "Warning: no administrative units, cities or streets were found in the input, names and TERYT ids of addresses will be empty."
+        );
+    } else {
+        progress!(
+            "Loaded {} components: {}.",
+            dict.len(),
+            component_counts(&dict)
+        );
+    }
+    Ok(dict)
+}
+
+/// Number of dictionary entries of every component type, e.g.
+/// `1 voivodeship, 2 counties` (types without entries are left out).
+fn component_counts(dict: &HashMap<String, AdditionalInfo>) -> String {
+    let types = [
+        (KomponentType::Country, "country", "countries"),
+        (KomponentType::Voivodeship, "voivodeship", "voivodeships"),
+        (KomponentType::County, "county", "counties"),
+        (
+            KomponentType::Municipality,
+            "municipality",
+            "municipalities",
+        ),
+        (KomponentType::City, "city", "cities"),
+        (KomponentType::Street, "street", "streets"),
+        (KomponentType::Unknown, "unknown", "unknown"),
+    ];
+    types
+        .iter()
+        .filter_map(|(typ, singular, plural)| {
+            let count = dict.values().filter(|info| &info.typ == typ).count();
+            match count {
+                0 => None,
+                1 => Some(format!("1 {singular}")),
+                _ => Some(format!("{count} {plural}")),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Names of the administrative units, city and street referenced by the
+/// `prg-ad:komponent` links of one address. Used as a fallback when the
+/// address itself does not carry the name inline.
+#[derive(Default)]
+struct ComponentNames<'a> {
+    voivodeship: Option<&'a str>,
+    county: Option<&'a str>,
+    municipality: Option<&'a str>,
+    city: Option<&'a str>,
+    street: Option<&'a str>,
+}
+
+impl<'a> ComponentNames<'a> {
+    fn set(&mut self, typ: &KomponentType, name: &'a str) {
+        match typ {
+            KomponentType::Voivodeship => self.voivodeship = Some(name),
+            // dictionary has e.g. "powiat nowosolski" while addresses use "nowosolski"
+            KomponentType::County => self.county = Some(name.trim_start_matches("powiat ")),
+            KomponentType::Municipality => self.municipality = Some(name),
+            KomponentType::City => self.city = Some(name),
+            KomponentType::Street => self.street = Some(name),
+            KomponentType::Country | KomponentType::Unknown => {}
+        }
+    }
+
+    /// Appends dictionary names for the columns that did not get a value for
+    /// the current row.
+    fn fill_missing(&self, builders: &mut CanonicalBuilders) {
+        let row_count = builders.row_count();
+        for (builder, name) in [
+            (&mut builders.voivodeship, self.voivodeship),
+            (&mut builders.county, self.county),
+            (&mut builders.municipality, self.municipality),
+            (&mut builders.city, self.city),
+            (&mut builders.street, self.street),
+        ] {
+            if let Some(name) = name
+                && builder.len() == row_count
+                && !name.is_empty()
+            {
+                builder.append_value(name);
+            }
+        }
+    }
 }
 
 pub struct AddressParser2012<R: BufRead> {
     reader: Reader<R>,
     batch_size: usize,
-    additional_info: HashMap<String, AdditionalInfo>,
+    additional_info: Arc<HashMap<String, AdditionalInfo>>,
     builders: CanonicalBuilders,
+    errors: ErrorSink,
+    filter: RecordFilter,
+    /// Set once the end of input or an XML error was reached.
+    finished: bool,
+    /// Position of the start of `reader` in the file, added to reported offsets.
+    offset_base: u64,
+    counts: ValidationCounts,
+    report: Option<ValidationReport>,
+    /// Print unknown tags after every batch instead of at the end of input.
+    verbose_unknown_tags: bool,
+    /// Return an error on the first unknown tag instead of counting it.
+    fail_on_unknown_tag: bool,
+    /// Prefix of `prg-ad:komponent` links before the `gml:id`, stripped when
+    /// set. Without it anything up to the last `/` or `#` is ignored.
+    id_prefix: Option<String>,
+    /// Component references seen and not found in the dictionaries, for the
+    /// warning printed at the end of input.
+    components_seen: usize,
+    components_missed: usize,
+    /// Write `prg-ad:status` as is instead of its canonical value.
+    status_raw: bool,
+    /// Ignore `prg-ad:komponent` links, the dictionaries were not built.
+    skip_dictionary: bool,
+    /// Unknown statuses already warned about.
+    unknown_statuses: HashSet<String>,
 }
 
 impl<R: BufRead> AddressParser2012<R> {
     pub fn new(
         reader: Reader<R>,
         batch_size: usize,
-        additional_info: HashMap<String, AdditionalInfo>,
+        additional_info: impl Into<Arc<HashMap<String, AdditionalInfo>>>,
     ) -> Self {
         Self {
             reader,
             batch_size,
-            additional_info,
+            additional_info: additional_info.into(),
             builders: CanonicalBuilders::with_capacity(batch_size),
+            errors: ErrorSink::default(),
+            filter: RecordFilter::default(),
+            finished: false,
+            offset_base: 0,
+            counts: ValidationCounts::default(),
+            report: None,
+            verbose_unknown_tags: false,
+            fail_on_unknown_tag: false,
+            id_prefix: None,
+            components_seen: 0,
+            components_missed: 0,
+            status_raw: false,
+            skip_dictionary: false,
+            unknown_statuses: HashSet::new(),
         }
     }
 
-    fn parse_address(&mut self) {
+    /// Join the parts of street names from the dictionaries with `format`
+    /// instead of `StreetNameFormat::default()`. Street names given inline in
+    /// `prg-ad:ulica` are not changed.
+    pub fn with_street_name_format(mut self, format: &StreetNameFormat) -> Self {
+        for info in Arc::make_mut(&mut self.additional_info).values_mut() {
+            if let Some(parts) = &info.street_name_parts {
+                info.name = street_name(parts, format);
+            }
+        }
+        self
+    }
+
+    /// Write `prg-ad:status` as is. By default known statuses are written as
+    /// their canonical value (see `STATUS`) and unknown ones as is with a warning.
+    pub fn with_status_raw(mut self, status_raw: bool) -> Self {
+        self.status_raw = status_raw;
+        self
+    }
+
+    /// Compute `dlugosc_geograficzna`/`szerokosc_geograficzna` from the EPSG:2180
+    /// coordinates (default). Without it they are null and no reprojection is done.
+    pub fn with_reprojection(mut self, reproject: bool) -> Self {
+        self.builders.reproject = reproject;
+        self
+    }
+
+    /// Read `gml:pos` (and points without `srsName`) in `input_crs` instead
+    /// of EPSG:2180, see `parse_gml_pos`.
+    pub fn with_input_crs(mut self, input_crs: CRS) -> Self {
+        self.builders.input_crs = input_crs;
+        self
+    }
+
+    /// Collapse runs of whitespace (including non-breaking spaces) to a single
+    /// space in the names, house numbers, postcodes and statuses. By default
+    /// they are written as in the input, only trimmed.
+    pub fn with_normalize_whitespace(mut self, normalize: bool) -> Self {
+        self.builders.normalize_whitespace = normalize;
+        self
+    }
+
+    /// Write the text of `untrimmed` columns with its leading and trailing
+    /// whitespace. By default all text is trimmed.
+    pub fn with_untrimmed_columns(mut self, untrimmed: UntrimmedColumns) -> Self {
+        self.builders.untrimmed = untrimmed;
+        self
+    }
+
+    /// Ignore `prg-ad:komponent` links instead of looking them up in the
+    /// dictionaries (built without them, see `--skip-dictionary`): names are
+    /// only read from the address and the TERYT id columns are null.
+    pub fn with_skip_dictionary(mut self, skip_dictionary: bool) -> Self {
+        self.skip_dictionary = skip_dictionary;
+        self
+    }
+
+    /// Expect `prg-ad:komponent` links to be `prefix` followed by the `gml:id`
+    /// of the component (e.g. `http://geoportal.gov.pl/PZGIK/dane/`).
+    /// By default anything up to the last `/` or `#` of the link is ignored.
+    pub fn with_id_prefix(mut self, prefix: Option<String>) -> Self {
+        self.id_prefix = prefix;
+        self
+    }
+
+    /// Number of components (administrative units, cities and streets) in the dictionaries.
+    pub fn component_count(&self) -> usize {
+        self.additional_info.len()
+    }
+
+    /// Component with the given id (the part of a `prg-ad:komponent` link after the prefix).
+    pub fn lookup_component(&self, id: &str) -> Option<&AdditionalInfo> {
+        self.additional_info.get(id)
+    }
+
+    /// Report addresses that could not be parsed to `errors`.
+    /// By default they are printed as warnings.
+    pub fn with_error_sink(mut self, errors: ErrorSink) -> Self {
+        self.errors = errors;
+        self
+    }
+
+    /// Only output addresses accepted by `filter`.
+    pub fn with_filter(mut self, filter: RecordFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// Add counts of addresses, component references and unknown tags to
+    /// `report` if one is given (unknown tags are then not printed).
+    pub fn with_validation_report(mut self, report: Option<ValidationReport>) -> Self {
+        self.report = report;
+        self
+    }
+
+    /// Without a validation report, unknown tags inside addresses are counted
+    /// and printed once at the end of input; with `verbose` after every batch.
+    pub fn with_verbose_unknown_tags(mut self, verbose: bool) -> Self {
+        self.verbose_unknown_tags = verbose;
+        self
+    }
+
+    /// Stop with an error naming the tag and the byte offset of the address
+    /// at the first unknown tag inside an address, e.g. to notice new fields
+    /// of the schema. By default unknown tags are counted and ignored.
+    pub fn with_fail_on_unknown_tag(mut self, fail: bool) -> Self {
+        self.fail_on_unknown_tag = fail;
+        self
+    }
+
+    /// Treat `reader` as starting at byte `offset` of the file (when parsing a
+    /// part of it), so that reported offsets are positions in the whole file.
+    pub(crate) fn with_offset_base(mut self, offset: u64) -> Self {
+        self.offset_base = offset;
+        self
+    }
+
+    /// Parses one address into the builders. Returns `false` if the address
+    /// was skipped because of an error (already reported to the error sink)
+    /// or rejected by the filter. Malformed XML is returned as an error.
+    fn parse_address(&mut self, offset: u64) -> Result<bool, ArrowError> {
+        let mut record = RecordState::new(offset);
+        self.counts.addresses += 1;
         let mut buffer = Vec::new();
         let mut last_tag = Vec::new();
         let mut nested_tag = false; // informs if we're processing a nested tag
         let mut tag_ignore_text = false; // informs if we're processing a tag that won't have any text content
         let mut admin_unit_counter: u8 = 0;
+        let mut component_names = ComponentNames::default();
         // inside loop to process the content of the current address
         loop {
             match self.reader.read_event_into(&mut buffer) {
@@ -241,19 +622,45 @@ impl<R: BufRead> AddressParser2012<R> {
                         | b"bt:BT_Identyfikator"
                         | b"prg-ad:cyklZycia"
                         | b"bt:BT_CyklZyciaInfo"
-                        | b"prg-ad:pozycja"
-                        | b"gml:Point" => {
+                        | b"prg-ad:pozycja" => {
+                            nested_tag = true;
+                            tag_ignore_text = false;
+                        }
+                        b"gml:Point" => {
+                            // points without `srsName` are assumed to be in `input_crs`
+                            if let Ok(srs_name) = try_get_attribute(e, b"srsName")
+                                && let Err(err) = check_srs_name(&srs_name, self.builders.input_crs)
+                            {
+                                record.fail(err);
+                            }
                             nested_tag = true;
                             tag_ignore_text = false;
                         }
                         b"prg-ad:komponent" => {
-                            let attr = get_attribute(e, b"xlink:href");
                             // Look up by &str (no key allocation) and copy out only what we use.
-                            let info = self
-                                .additional_info
-                                .get(attr.as_ref())
-                                .map(|i| (i.typ.clone(), i.teryt_id.clone()));
-                            if let Some((typ, teryt_id)) = info {
+                            let info = match try_get_attribute(e, b"xlink:href") {
+                                Ok(_) if self.skip_dictionary => None,
+                                Ok(attr) => {
+                                    self.counts.components += 1;
+                                    self.components_seen += 1;
+                                    let info = component_id(&attr, self.id_prefix.as_deref())
+                                        .and_then(|id| self.additional_info.get(id))
+                                        .map(|i| {
+                                            (i.typ.clone(), i.teryt_id.clone(), i.name.as_str())
+                                        });
+                                    if info.is_none() {
+                                        self.counts.unresolved_references += 1;
+                                        self.components_missed += 1;
+                                    }
+                                    info
+                                }
+                                Err(err) => {
+                                    record.fail(err);
+                                    None
+                                }
+                            };
+                            if let Some((typ, teryt_id, name)) = info {
+                                component_names.set(&typ, name);
                                 match typ {
                                     KomponentType::Voivodeship => option_append_value_or_null(
                                         &mut self.builders.voivodeship_teryt_id,
@@ -285,6 +692,19 @@ impl<R: BufRead> AddressParser2012<R> {
                             nested_tag = false;
                             tag_ignore_text = true;
                         }
+                        b"prg-ad:budynek" => {
+                            // building reference, either as a link or as an identifier in text
+                            match try_get_attribute(e, b"xlink:href") {
+                                Ok(href) => {
+                                    self.builders
+                                        .building_id
+                                        .append_value(href.trim_start_matches('#'));
+                                    tag_ignore_text = true;
+                                }
+                                Err(_) => tag_ignore_text = false,
+                            }
+                            nested_tag = false;
+                        }
                         _ => {
                             nested_tag = false;
                             tag_ignore_text = false;
@@ -297,66 +717,77 @@ impl<R: BufRead> AddressParser2012<R> {
                         // if nested_tag is true, we are inside a nested tag that we want to skip (only read innermost text not the whole tree branch)
                         continue;
                     }
-                    let text_decoded = e.decode().expect("Failed to decode text.");
+                    let text_decoded = match e.decode() {
+                        Ok(text) => text,
+                        Err(err) => {
+                            record.fail(anyhow::Error::new(err).context("Failed to decode text"));
+                            last_tag.clear();
+                            continue;
+                        }
+                    };
                     let text_trimmed = text_decoded.trim();
                     match last_tag.as_slice() {
                         b"gml:identifier" => {}
                         b"bt:lokalnyId" => {
+                            record.uuid = Some(text_trimmed.to_string());
                             self.builders.uuid.append_value(text_trimmed);
                         }
                         b"bt:przestrzenNazw" => {
                             self.builders.id_namespace.append_value(text_trimmed);
                         }
-                        b"bt:wersjaId" => {
-                            let dt = DateTime::parse_from_rfc3339(text_trimmed)
-                                .expect("Failed to parse datetime")
-                                .to_utc();
-                            self.builders.version.append_value(dt.timestamp() * 1000);
-                        }
+                        b"bt:wersjaId" => match parse_datetime_millis(text_trimmed) {
+                            Ok(millis) => self.builders.version.append_value(millis),
+                            Err(err) => record.fail(err),
+                        },
                         b"bt:poczatekWersjiObiektu" => {
                             if text_trimmed.is_empty() {
                                 self.builders.lifecycle_start_date.append_null();
                             } else {
-                                let dt = DateTime::parse_from_rfc3339(text_trimmed)
-                                    .expect("Failed to parse datetime")
-                                    .to_utc();
-                                self.builders
-                                    .lifecycle_start_date
-                                    .append_value(dt.timestamp() * 1000);
+                                match parse_datetime_millis(text_trimmed) {
+                                    Ok(millis) => {
+                                        self.builders.lifecycle_start_date.append_value(millis)
+                                    }
+                                    Err(err) => record.fail(err),
+                                }
                             }
                         }
                         b"prg-ad:waznyOd" => {
                             if text_trimmed.is_empty() {
                                 self.builders.valid_since_date.append_null();
                             } else {
-                                let date = NaiveDate::parse_from_str(text_trimmed, "%Y-%m-%d")
-                                    .expect("Failed to parse date");
-                                self.builders.valid_since_date.append_value(
-                                    date.signed_duration_since(EPOCH_DATE).num_days() as i32,
-                                );
+                                match parse_date_days(text_trimmed) {
+                                    Ok(days) => {
+                                        record.valid_since = Some(days);
+                                        self.builders.valid_since_date.append_value(days)
+                                    }
+                                    Err(err) => record.fail(err),
+                                }
                             }
                         }
                         b"prg-ad:waznyDo" => {
                             if text_trimmed.is_empty() {
                                 self.builders.valid_to_date.append_null();
                             } else {
-                                let date = NaiveDate::parse_from_str(text_trimmed, "%Y-%m-%d")
-                                    .expect("Failed to parse date");
-                                self.builders.valid_to_date.append_value(
-                                    date.signed_duration_since(EPOCH_DATE).num_days() as i32,
-                                );
+                                match parse_date_days(text_trimmed) {
+                                    Ok(days) => self.builders.valid_to_date.append_value(days),
+                                    Err(err) => record.fail(err),
+                                }
                             }
                         }
                         b"prg-ad:jednostkaAdmnistracyjna" => {
                             // sic!
+                            // empty names are left for `ComponentNames::fill_missing`
                             match admin_unit_counter {
                                 0 => {}
+                                1 if text_trimmed.is_empty() => {}
                                 1 => {
                                     self.builders.voivodeship.append_value(text_trimmed);
                                 }
+                                2 if text_trimmed.is_empty() => {}
                                 2 => {
                                     self.builders.county.append_value(text_trimmed);
                                 }
+                                3 if text_trimmed.is_empty() => {}
                                 3 => {
                                     self.builders.municipality.append_value(text_trimmed);
                                 }
@@ -369,111 +800,198 @@ impl<R: BufRead> AddressParser2012<R> {
                             admin_unit_counter += 1;
                         }
                         b"prg-ad:miejscowosc" => {
-                            self.builders.city.append_value(text_trimmed);
+                            if !text_trimmed.is_empty() {
+                                self.builders.city.append_value(text_trimmed);
+                            }
                         }
                         b"prg-ad:czescMiejscowosci" => {
                             str_append_value_or_null(&mut self.builders.city_part, text_trimmed);
                         }
                         b"prg-ad:ulica" => {
-                            str_append_value_or_null(&mut self.builders.street, text_trimmed);
+                            if !text_trimmed.is_empty() {
+                                self.builders.street.append_value(text_trimmed);
+                                record.has_street = true;
+                            }
                         }
                         b"prg-ad:numerPorzadkowy" => {
-                            self.builders.house_number.append_value(text_trimmed);
+                            let text =
+                                trim_unless(self.builders.untrimmed.house_number, &text_decoded);
+                            str_append_value_or_null(&mut self.builders.house_number, text);
                         }
                         b"prg-ad:kodPocztowy" => {
-                            str_append_value_or_null(&mut self.builders.postcode, text_trimmed);
+                            let text = trim_unless(self.builders.untrimmed.postcode, &text_decoded);
+                            str_append_value_or_null(&mut self.builders.postcode, text);
                         }
                         b"prg-ad:status" => {
-                            self.builders.status.append_value(text_trimmed);
-                        }
-                        b"gml:pos" => {
-                            let coords = parse_gml_pos(text_trimmed, CoordOrder::YX)
-                                .expect("Could not parse coordinates.");
-                            match coords {
-                                None => {
-                                    self.builders.longitude.append_null();
-                                    self.builders.latitude.append_null();
+                            let text = trim_unless(self.builders.untrimmed.status, &text_decoded);
+                            match canonical_status(text_trimmed) {
+                                Some(status) if !self.status_raw => {
+                                    self.builders.status.append_value(status);
+                                }
+                                None if !self.status_raw
+                                    && self.unknown_statuses.insert(text_trimmed.to_string()) =>
+                                {
+                                    println!(
+                                        "Warning: unknown `prg-ad:status` value `{}`, written as is.",
+                                        text_trimmed
+                                    );
+                                    self.builders.status.append_value(text);
+                                }
+                                _ => self.builders.status.append_value(text),
+                            }
+                        }
+                        b"prg-ad:budynek" => {
+                            let text =
+                                trim_unless(self.builders.untrimmed.building_id, &text_decoded);
+                            str_append_value_or_null(&mut self.builders.building_id, text);
+                        }
+                        b"gml:pos" if record.first_position() => {
+                            match parse_gml_pos(
+                                text_trimmed,
+                                CoordOrder::YX,
+                                self.builders.input_crs.proj(),
+                            ) {
+                                Ok(None) => {
                                     self.builders.x_epsg_2180.append_null();
                                     self.builders.y_epsg_2180.append_null();
                                 }
-                                Some(coords) => {
-                                    self.builders.longitude.append_value(coords.x4326);
-                                    self.builders.latitude.append_value(coords.y4326);
-                                    self.builders.x_epsg_2180.append_value(coords.x2180);
-                                    self.builders.y_epsg_2180.append_value(coords.y2180);
+                                Ok(Some(coords)) => {
+                                    record.has_coordinates = true;
+                                    self.builders.append_point(&coords);
+                                }
+                                Err(err) => {
+                                    self.builders.x_epsg_2180.append_null();
+                                    self.builders.y_epsg_2180.append_null();
+                                    record.warn(format!("{:#}, writing null coordinates.", err));
                                 }
                             }
                         }
+                        b"gml:pos" => {} // later positions are ignored, see `first_position`
                         _ => {
-                            println!(
-                                "Unknown tag `{:?}` for parse_address function.",
-                                std::str::from_utf8(&last_tag)
-                            );
+                            if self.fail_on_unknown_tag {
+                                return Err(ArrowError::ParseError(format!(
+                                    "Unknown tag `{}` inside address at byte offset {} (uuid: {}).",
+                                    String::from_utf8_lossy(&last_tag),
+                                    record.offset,
+                                    record.uuid.as_deref().unwrap_or("unknown")
+                                )));
+                            }
+                            self.counts.unknown_tag(&last_tag);
                         }
                     }
                     last_tag.clear();
                 }
                 Ok(Event::End(ref e)) if e.name().as_ref() == ADDRESS_TAG => {
-                    // ensure all builders have the same length
-                    self.builders.pad_short_columns();
                     // end of the current address entry
-                    break;
+                    // the street name may also be filled from the dictionary
+                    if component_names.street.is_some_and(|name| !name.is_empty()) {
+                        record.has_street = true;
+                    }
+                    let keep = record
+                        .finish(&self.filter, &self.errors)
+                        .map_err(|e| ArrowError::ExternalError(e.into()))?;
+                    component_names.fill_missing(&mut self.builders);
+                    // ensure all builders have the same length
+                    self.builders.end_row(keep);
+                    return Ok(keep);
                 }
                 Ok(Event::Eof) => {
-                    panic!("Error: reached end of file before end of address entry");
+                    return Err(ArrowError::ParseError(
+                        "Reached end of file before end of address entry.".to_string(),
+                    ));
                 }
                 Err(e) => {
-                    panic!(
+                    return Err(ArrowError::ParseError(format!(
                         "Error at position {}: {:?}",
                         self.reader.error_position(),
                         e
-                    );
+                    )));
                 }
                 _ => (), // we do not care about other events here
             }
             buffer.clear();
         }
     }
-}
-
-impl<R: BufRead> Iterator for AddressParser2012<R> {
-    type Item = arrow::array::RecordBatch;
 
-    fn next(&mut self) -> Option<Self::Item> {
+    /// Parses addresses until a batch is full or the input ends.
+    fn next_batch(&mut self) -> Option<Result<RecordBatch, ArrowError>> {
+        if self.finished {
+            return None;
+        }
         let mut buffer = Vec::new();
         let mut row_count: usize = 0;
         // main loop that catches events when new object starts
         loop {
+            let offset = self.offset_base + self.reader.buffer_position();
             match self.reader.read_event_into(&mut buffer) {
-                Ok(Event::Start(ref e)) => {
-                    if e.name().as_ref() == ADDRESS_TAG {
-                        row_count += 1;
-                        self.parse_address();
-                        if row_count == self.batch_size {
-                            let record_batch = self.builders.build_record_batch();
-                            return Some(record_batch);
+                Ok(Event::Start(ref e)) if e.name().as_ref() == ADDRESS_TAG => {
+                    match self.parse_address(offset) {
+                        Ok(true) => {
+                            row_count += 1;
+                            if row_count == self.batch_size {
+                                return Some(self.builders.build_record_batch());
+                            }
+                        }
+                        Ok(false) => (),
+                        Err(e) => {
+                            self.finished = true;
+                            return Some(Err(e));
                         }
                     }
                 }
                 Ok(Event::Eof) => break, // exits the loop when reaching end of file
-                Err(e) => panic!(
-                    "Error at position {}: {:?}",
-                    self.reader.error_position(),
-                    e
-                ),
+                Err(e) => {
+                    self.finished = true;
+                    return Some(Err(ArrowError::ParseError(format!(
+                        "Error at position {}: {:?}",
+                        self.reader.error_position(),
+                        e
+                    ))));
+                }
                 _ => (), // we do not care about other events here
             }
             buffer.clear();
         }
-        let record_batch = self.builders.build_record_batch();
-        if record_batch.num_rows() > 0 {
-            Some(record_batch)
-        } else {
-            None
+        self.finished = true;
+        self.warn_about_missing_components();
+        self.builders.build_final_record_batch()
+    }
+
+    fn warn_about_missing_components(&self) {
+        if self.components_seen > 0
+            && self.components_missed as f64
+                > self.components_seen as f64 * MISSING_COMPONENTS_WARNING_RATIO
+        {
+            println!(
+                "Warning: {} of {} `prg-ad:komponent` references were not found in the dictionaries, so names and TERYT ids may be missing. If the links use an unexpected id prefix, set it with --id-prefix.",
+                self.components_missed, self.components_seen
+            );
         }
     }
 }
 
+impl<R: BufRead> Iterator for AddressParser2012<R> {
+    type Item = Result<RecordBatch, ArrowError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let batch = self.next_batch();
+        match &self.report {
+            Some(report) => report.add(std::mem::take(&mut self.counts)),
+            None if self.verbose_unknown_tags || self.finished => {
+                self.counts.warn_about_unknown_tags()
+            }
+            None => {}
+        }
+        batch
+    }
+}
+
+impl<R: BufRead> RecordBatchReader for AddressParser2012<R> {
+    fn schema(&self) -> SchemaRef {
+        SCHEMA_CSV.clone()
+    }
+}
+
 #[test]
 fn name_from_part1() {
     let name_part_1 = "Test".to_string();
@@ -481,7 +999,13 @@ fn name_from_part1() {
     let name_part_3 = String::new();
     let name_part_4 = String::new();
     let expected_name = "Test".to_string();
-    let name = construct_full_name_from_parts(name_part_1, name_part_2, name_part_3, name_part_4);
+    let name = construct_full_name_from_parts(
+        name_part_1,
+        name_part_2,
+        name_part_3,
+        name_part_4,
+        &StreetNameFormat::default(),
+    );
     assert_eq!(name, expected_name);
 }
 
@@ -492,7 +1016,13 @@ fn name_from_part1_part2() {
     let name_part_3 = String::new();
     let name_part_4 = String::new();
     let expected_name = "Test Test2".to_string();
-    let name = construct_full_name_from_parts(name_part_1, name_part_2, name_part_3, name_part_4);
+    let name = construct_full_name_from_parts(
+        name_part_1,
+        name_part_2,
+        name_part_3,
+        name_part_4,
+        &StreetNameFormat::default(),
+    );
     assert_eq!(name, expected_name);
 }
 
@@ -503,7 +1033,13 @@ fn name_from_part1_part2_part3() {
     let name_part_3 = "Test3".to_string();
     let name_part_4 = String::new();
     let expected_name = "Test Test2 Test3".to_string();
-    let name = construct_full_name_from_parts(name_part_1, name_part_2, name_part_3, name_part_4);
+    let name = construct_full_name_from_parts(
+        name_part_1,
+        name_part_2,
+        name_part_3,
+        name_part_4,
+        &StreetNameFormat::default(),
+    );
     assert_eq!(name, expected_name);
 }
 
@@ -514,37 +1050,658 @@ fn name_from_part1_part2_part3_part4() {
     let name_part_3 = "Test3".to_string();
     let name_part_4 = "Test4".to_string();
     let expected_name = "Test Test2 Test3 Test4".to_string();
-    let name = construct_full_name_from_parts(name_part_1, name_part_2, name_part_3, name_part_4);
+    let name = construct_full_name_from_parts(
+        name_part_1,
+        name_part_2,
+        name_part_3,
+        name_part_4,
+        &StreetNameFormat::default(),
+    );
     assert_eq!(name, expected_name);
 }
 
+#[test]
+fn name_with_separator_and_trimmed_parts() {
+    let parts = || {
+        (
+            "al.".to_string(),
+            " ".to_string(),
+            " Jana ".to_string(),
+            "Pawła II".to_string(),
+        )
+    };
+    let (part_1, part_2, part_3, part_4) = parts();
+    let name = construct_full_name_from_parts(
+        part_1,
+        part_2,
+        part_3,
+        part_4,
+        &StreetNameFormat {
+            separator: "-",
+            trim_parts: true,
+        },
+    );
+    assert_eq!(name, "al.-Jana-Pawła II");
+    // without trimming the parts are joined as they are
+    let (part_1, part_2, part_3, part_4) = parts();
+    let name = construct_full_name_from_parts(
+        part_1,
+        part_2,
+        part_3,
+        part_4,
+        &StreetNameFormat::default(),
+    );
+    assert_eq!(name, "al.    Jana  Pawła II");
+}
+
 #[test]
 fn test_build_dictionaries() {
     let sample_file_path = "fixtures/sample_model2012.xml";
     let mut reader = Reader::from_file(sample_file_path).unwrap();
     reader.config_mut().expand_empty_elements = true;
-    let dict = build_dictionaries(reader);
-    let country = &dict["http://geoportal.gov.pl/PZGIK/dane/PL.PZGIK.200_366263"];
+    let dict = build_dictionaries(reader).unwrap();
+    assert_eq!(
+        component_counts(&dict),
+        "1 country, 1 voivodeship, 1 county, 1 municipality, 1 city, 1 street"
+    );
+    let country = &dict["PL.PZGIK.200_366263"];
     assert_eq!(country.typ, KomponentType::Country);
     assert_eq!(country.name, "POLSKA");
-    let voivodeship = &dict["http://geoportal.gov.pl/PZGIK/dane/PL.PZGIK.200_366267"];
+    let voivodeship = &dict["PL.PZGIK.200_366267"];
     assert_eq!(voivodeship.typ, KomponentType::Voivodeship);
     assert_eq!(voivodeship.name, "lubuskie");
     assert_eq!(voivodeship.teryt_id, Some("08".to_string()));
-    let county = &dict["http://geoportal.gov.pl/PZGIK/dane/PL.PZGIK.200_366439"];
+    let county = &dict["PL.PZGIK.200_366439"];
     assert_eq!(county.typ, KomponentType::County);
     assert_eq!(county.name, "powiat nowosolski");
     assert_eq!(county.teryt_id, Some("0804".to_string()));
-    let municipality = &dict["http://geoportal.gov.pl/PZGIK/dane/PL.PZGIK.200_370095"];
+    let municipality = &dict["PL.PZGIK.200_370095"];
     assert_eq!(municipality.typ, KomponentType::Municipality);
     assert_eq!(municipality.name, "Kolsko");
     assert_eq!(municipality.teryt_id, Some("0804032".to_string()));
-    let city = &dict["http://geoportal.gov.pl/PZGIK/dane/PL.ZIPIN.4404.EMUiA_0910140"];
+    let city = &dict["PL.ZIPIN.4404.EMUiA_0910140"];
     assert_eq!(city.typ, KomponentType::City);
     assert_eq!(city.name, "Konotop");
     assert_eq!(city.teryt_id, Some("0910140".to_string()));
-    let street = &dict["http://geoportal.gov.pl/PZGIK/dane/PL.ZIPIN.4404.EMUiA_95d1f98c-7a1e-4726-a17d-a3c7bdaec79e"];
+    let street = &dict["PL.ZIPIN.4404.EMUiA_95d1f98c-7a1e-4726-a17d-a3c7bdaec79e"];
     assert_eq!(street.typ, KomponentType::Street);
     assert_eq!(street.name, "Podgórna");
     assert_eq!(street.teryt_id, Some("16742".to_string()));
 }
+
+#[cfg(test)]
+fn parse_2012_str(xml: &str) -> arrow::array::RecordBatch {
+    let mut reader = Reader::from_reader(xml.as_bytes());
+    reader.config_mut().expand_empty_elements = true;
+    let dict = build_dictionaries(reader).unwrap();
+    let mut reader = Reader::from_reader(xml.as_bytes());
+    reader.config_mut().expand_empty_elements = true;
+    let batches: Vec<arrow::array::RecordBatch> = AddressParser2012::new(reader, 100, dict)
+        .collect::<Result<_, _>>()
+        .expect("Failed to parse addresses.");
+    assert_eq!(batches.len(), 1);
+    batches.into_iter().next().unwrap()
+}
+
+#[test]
+fn test_names_filled_from_dictionary_when_inline_missing() {
+    use arrow::array::AsArray;
+
+    let xml = std::fs::read_to_string("fixtures/sample_model2012.xml").unwrap();
+    let stripped: String = xml
+        .lines()
+        .filter(|l| {
+            !l.contains("<prg-ad:jednostkaAdmnistracyjna>")
+                && !l.contains("<prg-ad:miejscowosc>")
+                && !l.contains("<prg-ad:ulica>")
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    let expected = parse_2012_str(&xml);
+    let batch = parse_2012_str(&stripped);
+    assert_eq!(batch.num_rows(), 2);
+    for column in ["wojewodztwo", "powiat", "gmina", "miejscowosc", "ulica"] {
+        assert_eq!(
+            batch.column_by_name(column).unwrap(),
+            expected.column_by_name(column).unwrap(),
+            "column `{}` differs",
+            column
+        );
+    }
+    let county = batch.column_by_name("powiat").unwrap().as_string::<i32>();
+    assert_eq!(county.value(0), "nowosolski");
+    let street = batch.column_by_name("ulica").unwrap().as_string::<i32>();
+    assert_eq!(street.value(1), "Podgórna");
+}
+
+#[test]
+fn test_inline_names_take_precedence_over_dictionary() {
+    use arrow::array::AsArray;
+
+    let xml = std::fs::read_to_string("fixtures/sample_model2012.xml")
+        .unwrap()
+        .replacen(
+            "<prg-ad:miejscowosc>Konotop</prg-ad:miejscowosc>",
+            "<prg-ad:miejscowosc>Konotop Inline</prg-ad:miejscowosc>",
+            1,
+        );
+    let batch = parse_2012_str(&xml);
+    let city = batch
+        .column_by_name("miejscowosc")
+        .unwrap()
+        .as_string::<i32>();
+    assert_eq!(city.value(0), "Konotop Inline");
+    assert_eq!(city.value(1), "Konotop");
+}
+
+#[test]
+fn test_parser_is_record_batch_reader() {
+    fn num_rows(reader: impl RecordBatchReader) -> usize {
+        assert_eq!(reader.schema(), SCHEMA_CSV.clone());
+        reader.map(|batch| batch.unwrap().num_rows()).sum()
+    }
+
+    let xml = std::fs::read_to_string("fixtures/sample_model2012.xml").unwrap();
+    let mut reader = Reader::from_reader(xml.as_bytes());
+    reader.config_mut().expand_empty_elements = true;
+    let dict = build_dictionaries(reader).unwrap();
+    let mut reader = Reader::from_reader(xml.as_bytes());
+    reader.config_mut().expand_empty_elements = true;
+    assert_eq!(num_rows(AddressParser2012::new(reader, 1, dict)), 2);
+}
+
+#[test]
+fn test_malformed_xml_returns_error() {
+    let xml = "<root><prg-ad:PRG_PunktAdresowy></root>";
+    let reader = Reader::from_reader(xml.as_bytes());
+    let mut parser = AddressParser2012::new(reader, 100, HashMap::new());
+    assert!(matches!(
+        parser.next(),
+        Some(Err(ArrowError::ParseError(_)))
+    ));
+    assert!(parser.next().is_none());
+}
+
+#[test]
+fn test_malformed_gml_pos_writes_null_coordinates() {
+    use arrow::array::Array;
+    use arrow::array::AsArray;
+    use arrow::datatypes::Float64Type;
+
+    let xml = std::fs::read_to_string("fixtures/sample_model2012.xml")
+        .unwrap()
+        .replacen(
+            "<gml:pos>456005.140000001 287772.37</gml:pos>",
+            "<gml:pos>5300000.0</gml:pos>",
+            1,
+        );
+    let batch = parse_2012_str(&xml);
+    assert_eq!(batch.num_rows(), 2);
+    let x = batch
+        .column_by_name("x_epsg_2180")
+        .unwrap()
+        .as_primitive::<Float64Type>();
+    assert!(x.is_null(0));
+    assert!(!x.is_null(1));
+    let lon = batch
+        .column_by_name("dlugosc_geograficzna")
+        .unwrap()
+        .as_primitive::<Float64Type>();
+    assert!(lon.is_null(0));
+}
+
+#[test]
+fn test_building_id() {
+    use arrow::array::Array;
+    use arrow::array::AsArray;
+
+    let xml = std::fs::read_to_string("fixtures/sample_model2012.xml")
+        .unwrap()
+        .replacen(
+            "<prg-ad:status>istniejacy</prg-ad:status>",
+            "<prg-ad:status>istniejacy</prg-ad:status>\n<prg-ad:budynek xlink:href=\"#PL.PZGIK.EGiB_0208.1_12\" />",
+            1,
+        );
+    let batch = parse_2012_str(&xml);
+    let building_id = batch
+        .column_by_name("id_budynku")
+        .unwrap()
+        .as_string::<i32>();
+    assert_eq!(building_id.value(0), "PL.PZGIK.EGiB_0208.1_12");
+    assert!(building_id.is_null(1));
+}
+
+#[test]
+fn test_component_id() {
+    let href = "http://geoportal.gov.pl/PZGIK/dane/PL.PZGIK.200_366267";
+    assert_eq!(component_id(href, None), Some("PL.PZGIK.200_366267"));
+    assert_eq!(
+        component_id("#PL.PZGIK.200_366267", None),
+        Some("PL.PZGIK.200_366267")
+    );
+    assert_eq!(
+        component_id(href, Some("http://geoportal.gov.pl/PZGIK/dane/")),
+        Some("PL.PZGIK.200_366267")
+    );
+    assert_eq!(component_id(href, Some("http://example.org/")), None);
+}
+
+#[test]
+fn test_components_with_other_id_prefix() {
+    use arrow::array::Array;
+    use arrow::array::AsArray;
+
+    let xml = std::fs::read_to_string("fixtures/sample_model2012.xml")
+        .unwrap()
+        .replace(
+            "http://geoportal.gov.pl/PZGIK/dane/",
+            "https://example.org/prg/dane/",
+        );
+    // detected from the links
+    let batch = parse_2012_str(&xml);
+    let county_teryt_id = batch
+        .column_by_name("teryt_powiat")
+        .unwrap()
+        .as_string::<i32>();
+    assert_eq!(county_teryt_id.value(0), "0804");
+
+    // the given prefix does not match the links
+    let mut reader = Reader::from_reader(xml.as_bytes());
+    reader.config_mut().expand_empty_elements = true;
+    let dict = build_dictionaries(reader).unwrap();
+    let mut reader = Reader::from_reader(xml.as_bytes());
+    reader.config_mut().expand_empty_elements = true;
+    let batches: Vec<arrow::array::RecordBatch> = AddressParser2012::new(reader, 100, dict)
+        .with_id_prefix(Some("http://geoportal.gov.pl/PZGIK/dane/".to_string()))
+        .collect::<Result<_, _>>()
+        .expect("Failed to parse addresses.");
+    let county_teryt_id = batches[0]
+        .column_by_name("teryt_powiat")
+        .unwrap()
+        .as_string::<i32>();
+    assert!(county_teryt_id.is_null(0));
+}
+
+#[test]
+fn test_second_position_is_ignored() {
+    use arrow::array::AsArray;
+    use arrow::datatypes::Float64Type;
+
+    let xml = std::fs::read_to_string("fixtures/sample_model2012.xml")
+        .unwrap()
+        .replacen(
+            "<gml:pos>456005.140000001 287772.37</gml:pos>",
+            "<gml:pos>456005.140000001 287772.37</gml:pos>\n<gml:pos>1.0 2.0</gml:pos>",
+            1,
+        );
+    let batch = parse_2012_str(&xml);
+    assert_eq!(batch.num_rows(), 2);
+    let x = batch
+        .column_by_name("x_epsg_2180")
+        .unwrap()
+        .as_primitive::<Float64Type>();
+    let y = batch
+        .column_by_name("y_epsg_2180")
+        .unwrap()
+        .as_primitive::<Float64Type>();
+    assert_eq!(x.len(), 2);
+    assert_eq!(x.value(0), 287772.37);
+    assert_eq!(y.value(0), 456005.140000001);
+    assert_eq!(x.value(1), 287751.0102);
+}
+
+#[test]
+fn test_canonical_status() {
+    assert_eq!(canonical_status("istniejacy"), Some("istniejacy"));
+    assert_eq!(canonical_status("Istniejący"), Some("istniejacy"));
+    assert_eq!(canonical_status("prognozowany"), Some("prognozowany"));
+    assert_eq!(canonical_status("wBudowie"), Some("w budowie"));
+    assert_eq!(canonical_status("w trakcie budowy"), Some("w budowie"));
+    assert_eq!(canonical_status("zlikwidowany"), None);
+}
+
+#[test]
+fn test_status_normalized_unless_raw() {
+    use arrow::array::AsArray;
+
+    let xml = std::fs::read_to_string("fixtures/sample_model2012.xml")
+        .unwrap()
+        .replacen(
+            "<prg-ad:status>istniejacy</prg-ad:status>",
+            "<prg-ad:status>Istniejący</prg-ad:status>",
+            1,
+        )
+        .replacen(
+            "<prg-ad:status>istniejacy</prg-ad:status>",
+            "<prg-ad:status>zlikwidowany</prg-ad:status>",
+            1,
+        );
+    let batch = parse_2012_str(&xml);
+    let status = batch.column_by_name("status").unwrap().as_string::<i32>();
+    assert_eq!(status.value(0), "istniejacy");
+    assert_eq!(status.value(1), "zlikwidowany");
+
+    let mut reader = Reader::from_reader(xml.as_bytes());
+    reader.config_mut().expand_empty_elements = true;
+    let dict = build_dictionaries(reader).unwrap();
+    let mut reader = Reader::from_reader(xml.as_bytes());
+    reader.config_mut().expand_empty_elements = true;
+    let batches: Vec<arrow::array::RecordBatch> = AddressParser2012::new(reader, 100, dict)
+        .with_status_raw(true)
+        .collect::<Result<_, _>>()
+        .expect("Failed to parse addresses.");
+    let status = batches[0]
+        .column_by_name("status")
+        .unwrap()
+        .as_string::<i32>();
+    assert_eq!(status.value(0), "Istniejący");
+}
+
+#[test]
+fn test_validation_report() {
+    let xml = std::fs::read_to_string("fixtures/sample_model2012.xml")
+        .unwrap()
+        .replacen(
+            "<prg-ad:status>istniejacy</prg-ad:status>",
+            "<prg-ad:status>istniejacy</prg-ad:status>\n<prg-ad:nowyTag>x</prg-ad:nowyTag>\n<prg-ad:komponent xlink:href=\"http://geoportal.gov.pl/PZGIK/dane/missing\" />",
+            1,
+        );
+    let mut reader = Reader::from_reader(xml.as_bytes());
+    reader.config_mut().expand_empty_elements = true;
+    let dict = build_dictionaries(reader).unwrap();
+    let mut reader = Reader::from_reader(xml.as_bytes());
+    reader.config_mut().expand_empty_elements = true;
+    let report = ValidationReport::default();
+    let batches: Vec<arrow::array::RecordBatch> = AddressParser2012::new(reader, 1, dict)
+        .with_validation_report(Some(report.clone()))
+        .collect::<Result<_, _>>()
+        .expect("Failed to parse addresses.");
+    assert_eq!(batches.len(), 2);
+    let counts = report.counts();
+    assert_eq!(counts.addresses, 2);
+    assert_eq!(counts.components, 13);
+    assert_eq!(counts.unresolved_references, 1);
+    assert_eq!(
+        counts.unknown_tags.into_iter().collect::<Vec<_>>(),
+        vec![("prg-ad:nowyTag".to_string(), 1)]
+    );
+}
+
+#[test]
+fn test_without_reprojection_lonlat_are_null() {
+    let xml = std::fs::read_to_string("fixtures/sample_model2012.xml").unwrap();
+    let mut reader = Reader::from_reader(xml.as_bytes());
+    reader.config_mut().expand_empty_elements = true;
+    let dict = build_dictionaries(reader).unwrap();
+    let mut reader = Reader::from_reader(xml.as_bytes());
+    reader.config_mut().expand_empty_elements = true;
+    let batches: Vec<arrow::array::RecordBatch> = AddressParser2012::new(reader, 100, dict)
+        .with_reprojection(false)
+        .collect::<Result<_, _>>()
+        .expect("Failed to parse addresses.");
+    let batch = &batches[0];
+    assert_eq!(batch.num_rows(), 2);
+    assert_eq!(batch.column_by_name("x_epsg_2180").unwrap().null_count(), 0);
+    assert_eq!(
+        batch
+            .column_by_name("dlugosc_geograficzna")
+            .unwrap()
+            .null_count(),
+        2
+    );
+    assert_eq!(
+        batch
+            .column_by_name("szerokosc_geograficzna")
+            .unwrap()
+            .null_count(),
+        2
+    );
+}
+
+#[test]
+fn test_batch_columns_in_schema_order() {
+    let path = std::path::PathBuf::from("fixtures/sample_model2012.xml");
+    let mut parser = crate::get_address_parser_2012_uncompressed(&path, &10).unwrap();
+    let batch = parser.next().unwrap().unwrap();
+    crate::common::assert_row_values(
+        &batch,
+        0,
+        &[
+            ("przestrzen_nazw", "PL.PZGIK.200"),
+            ("lokalny_id", "fd9c9319-0a6a-44b4-972a-1e6c4ec0d4ca"),
+            ("wersja_id", "2022-09-09T16:18:16Z"),
+            ("poczatek_wersji_obiektu", "2022-09-09T18:18:16Z"),
+            ("wazny_od_lub_data_nadania", "2022-09-09"),
+            ("wazny_do", ""),
+            ("teryt_wojewodztwo", "08"),
+            ("wojewodztwo", "lubuskie"),
+            ("teryt_powiat", "0804"),
+            ("powiat", "nowosolski"),
+            ("teryt_gmina", "0804032"),
+            ("gmina", "Kolsko"),
+            ("teryt_miejscowosc", "0910140"),
+            ("miejscowosc", "Konotop"),
+            ("czesc_miejscowosci", ""),
+            ("teryt_ulica", "16742"),
+            ("ulica", "Podgórna"),
+            ("numer_porzadkowy", "2"),
+            ("kod_pocztowy", "67-416"),
+            ("status", "istniejacy"),
+            ("id_budynku", ""),
+            ("x_epsg_2180", "287772.37"),
+            ("y_epsg_2180", "456005.140000001"),
+            ("dlugosc_geograficzna", "15.9121240698886"),
+            ("szerokosc_geograficzna", "51.92977532639213"),
+        ],
+    );
+}
+
+#[test]
+fn test_point_in_other_crs_is_skipped() {
+    let xml = std::fs::read_to_string("fixtures/sample_model2012.xml")
+        .unwrap()
+        .replacen(
+            r#"<gml:Point srsName="urn:ogc:def:crs:EPSG::2180""#,
+            r#"<gml:Point srsName="urn:ogc:def:crs:EPSG::4326""#,
+            1,
+        );
+    let batch = parse_2012_str(&xml);
+    assert_eq!(batch.num_rows(), 1);
+}
+
+#[test]
+fn test_input_crs_4326() {
+    use arrow::array::AsArray;
+    use arrow::datatypes::Float64Type;
+    // the first point as latitude/longitude, the second one is left in EPSG:2180
+    let xml = std::fs::read_to_string("fixtures/sample_model2012.xml")
+        .unwrap()
+        .replacen(
+            r#"<gml:Point srsName="urn:ogc:def:crs:EPSG::2180""#,
+            r#"<gml:Point srsName="urn:ogc:def:crs:EPSG::4326""#,
+            1,
+        )
+        .replacen(
+            "<gml:pos>456005.140000001 287772.37</gml:pos>",
+            "<gml:pos>51.92977532639213 15.9121240698886</gml:pos>",
+            1,
+        );
+    let parse = |input_crs| {
+        let mut reader = Reader::from_reader(xml.as_bytes());
+        reader.config_mut().expand_empty_elements = true;
+        let dict = build_dictionaries(reader).unwrap();
+        let mut reader = Reader::from_reader(xml.as_bytes());
+        reader.config_mut().expand_empty_elements = true;
+        AddressParser2012::new(reader, 100, dict)
+            .with_input_crs(input_crs)
+            .next()
+            .unwrap()
+            .unwrap()
+    };
+    let float = |batch: &arrow::array::RecordBatch, name| {
+        batch
+            .column_by_name(name)
+            .unwrap()
+            .as_primitive::<Float64Type>()
+            .value(0)
+    };
+
+    // the point in EPSG:4326 is skipped by default, the other one is read as is
+    let batch = parse(CRS::Epsg2180);
+    assert_eq!(batch.num_rows(), 1);
+    assert_eq!(float(&batch, "x_epsg_2180"), 287751.0102);
+
+    // longitude/latitude are written as read, EPSG:2180 is reprojected from them
+    let batch = parse(CRS::Epsg4326);
+    assert_eq!(batch.num_rows(), 1);
+    assert_eq!(float(&batch, "dlugosc_geograficzna"), 15.9121240698886);
+    assert_eq!(float(&batch, "szerokosc_geograficzna"), 51.92977532639213);
+    assert!((float(&batch, "x_epsg_2180") - 287772.37).abs() <= 0.01);
+    assert!((float(&batch, "y_epsg_2180") - 456005.14).abs() <= 0.01);
+}
+
+#[test]
+fn test_address_without_house_number() {
+    use arrow::array::{Array, AsArray};
+    let xml = std::fs::read_to_string("fixtures/sample_model2012_no_number.xml").unwrap();
+    let batch = parse_2012_str(&xml);
+    assert_eq!(batch.num_rows(), 2);
+    let house_number = batch
+        .column_by_name("numer_porzadkowy")
+        .unwrap()
+        .as_string::<i32>();
+    assert_eq!(house_number.value(0), "2");
+    assert!(house_number.is_null(1));
+}
+
+#[test]
+fn test_fail_on_unknown_tag() {
+    let xml = std::fs::read_to_string("fixtures/sample_model2012.xml")
+        .unwrap()
+        .replacen(
+            "<prg-ad:status>istniejacy</prg-ad:status>",
+            "<prg-ad:status>istniejacy</prg-ad:status><prg-ad:nowyTag>x</prg-ad:nowyTag>",
+            1,
+        );
+    let parse = |fail| {
+        let mut reader = Reader::from_reader(xml.as_bytes());
+        reader.config_mut().expand_empty_elements = true;
+        let dict = build_dictionaries(reader).unwrap();
+        let mut reader = Reader::from_reader(xml.as_bytes());
+        reader.config_mut().expand_empty_elements = true;
+        AddressParser2012::new(reader, 100, dict)
+            .with_fail_on_unknown_tag(fail)
+            .collect::<Result<Vec<_>, _>>()
+    };
+    assert_eq!(parse(false).unwrap()[0].num_rows(), 2);
+    let err = parse(true).unwrap_err().to_string();
+    assert!(
+        err.contains("Unknown tag `prg-ad:nowyTag` inside address at byte offset"),
+        "{}",
+        err
+    );
+    assert!(
+        err.contains("fd9c9319-0a6a-44b4-972a-1e6c4ec0d4ca"),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn test_normalize_whitespace() {
+    use arrow::array::AsArray;
+
+    let xml = std::fs::read_to_string("fixtures/sample_model2012.xml")
+        .unwrap()
+        .replacen(
+            "<prg-ad:miejscowosc>Konotop</prg-ad:miejscowosc>",
+            "<prg-ad:miejscowosc>Konotop\u{a0}Mały  Dolny</prg-ad:miejscowosc>",
+            1,
+        )
+        .replacen(
+            "<prg-ad:ulica>Podgórna</prg-ad:ulica>",
+            "<prg-ad:ulica>Plac  Podgórna\u{a0}</prg-ad:ulica>",
+            1,
+        );
+    let parse = |normalize| {
+        let mut reader = Reader::from_reader(xml.as_bytes());
+        reader.config_mut().expand_empty_elements = true;
+        let dict = build_dictionaries(reader).unwrap();
+        let mut reader = Reader::from_reader(xml.as_bytes());
+        reader.config_mut().expand_empty_elements = true;
+        let batches = AddressParser2012::new(reader, 100, dict)
+            .with_normalize_whitespace(normalize)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let value = |name: &str| {
+            batches[0]
+                .column_by_name(name)
+                .unwrap()
+                .as_string::<i32>()
+                .value(0)
+                .to_string()
+        };
+        (value("miejscowosc"), value("ulica"))
+    };
+    assert_eq!(
+        parse(false),
+        (
+            "Konotop\u{a0}Mały  Dolny".to_string(),
+            "Plac  Podgórna".to_string()
+        )
+    );
+    assert_eq!(
+        parse(true),
+        (
+            "Konotop Mały Dolny".to_string(),
+            "Plac Podgórna".to_string()
+        )
+    );
+}
+
+#[test]
+fn test_untrimmed_columns() {
+    use arrow::array::AsArray;
+
+    let xml = std::fs::read_to_string("fixtures/sample_model2012.xml")
+        .unwrap()
+        .replacen(
+            "<prg-ad:numerPorzadkowy>2</prg-ad:numerPorzadkowy>",
+            "<prg-ad:numerPorzadkowy> 12 </prg-ad:numerPorzadkowy>",
+            1,
+        )
+        .replacen(
+            "<prg-ad:kodPocztowy>67-416</prg-ad:kodPocztowy>",
+            "<prg-ad:kodPocztowy>67-416 </prg-ad:kodPocztowy>",
+            1,
+        );
+    let parse = |untrimmed| {
+        let mut reader = Reader::from_reader(xml.as_bytes());
+        reader.config_mut().expand_empty_elements = true;
+        let dict = build_dictionaries(reader).unwrap();
+        let mut reader = Reader::from_reader(xml.as_bytes());
+        reader.config_mut().expand_empty_elements = true;
+        let batches = AddressParser2012::new(reader, 100, dict)
+            .with_untrimmed_columns(untrimmed)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let value = |name: &str| {
+            batches[0]
+                .column_by_name(name)
+                .unwrap()
+                .as_string::<i32>()
+                .value(0)
+                .to_string()
+        };
+        (value("numer_porzadkowy"), value("kod_pocztowy"))
+    };
+    assert_eq!(
+        parse(UntrimmedColumns::default()),
+        ("12".to_string(), "67-416".to_string())
+    );
+    let house_number = UntrimmedColumns::from_names(&["numer_porzadkowy"]).unwrap();
+    assert_eq!(
+        parse(house_number),
+        (" 12 ".to_string(), "67-416".to_string())
+    );
+}
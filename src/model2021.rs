@@ -1,47 +1,274 @@
+use std::collections::BTreeMap;
 use std::collections::HashMap;
 use std::io::BufRead;
+use std::io::BufWriter;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::sync::Mutex;
 
-use chrono::DateTime;
-use chrono::NaiveDate;
+use anyhow::Context;
+
+use arrow::array::RecordBatch;
+use arrow::array::RecordBatchReader;
+use arrow::datatypes::SchemaRef;
+use arrow::error::ArrowError;
 use chrono::NaiveDateTime;
 use quick_xml::Reader;
 use quick_xml::events::Event;
+use serde::Serialize;
 use std::sync::LazyLock;
 
+use crate::CRS;
 use crate::CoordOrder;
+use crate::PrgError;
 use crate::common::CanonicalBuilders;
-use crate::common::EPOCH_DATE;
+use crate::common::ErrorSink;
+use crate::common::RecordFilter;
+use crate::common::RecordState;
+use crate::common::SCHEMA_CSV;
+use crate::common::UntrimmedColumns;
+use crate::common::ValidationCounts;
+use crate::common::ValidationReport;
+use crate::common::check_srs_name;
 use crate::common::get_attribute;
 use crate::common::option_append_value_or_null;
+use crate::common::parse_date_days;
+use crate::common::parse_datetime_millis;
 use crate::common::parse_gml_pos;
 use crate::common::str_append_value_or_null;
+use crate::common::trim_unless;
+use crate::common::try_get_attribute;
+use crate::progress;
 use crate::terc::Terc;
+use crate::timings::{self, Phase};
 
 const CITY_TAG: &[u8] = b"prgad:AD_Miejscowosc";
 const STREET_TAG: &[u8] = b"prgad:AD_UlicaPlac";
-const ADDRESS_TAG: &[u8] = b"prgad:AD_PunktAdresowy";
+pub(crate) const ADDRESS_TAG: &[u8] = b"prgad:AD_PunktAdresowy";
 
-#[allow(dead_code)]
-struct City {
+/// City read by `build_dictionaries`.
+#[derive(Debug)]
+pub struct City {
     name: String,
     kind: String,
+    /// `prgad:rodzaj` code the kind is read from.
+    kind_code: String,
     city_teryt_id: Option<String>,
     municipality_teryt_id: String,
 }
 
-#[allow(dead_code)]
-struct Street {
+impl City {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Kind of the city, e.g. `miasto` or `wieś`. Empty if the code of the kind
+    /// is not a known SIMC code.
+    pub fn kind(&self) -> &str {
+        &self.kind
+    }
+
+    /// `prgad:rodzaj` code of the kind (SIMC `RM`), e.g. `96` for `miasto`.
+    pub fn kind_code(&self) -> &str {
+        &self.kind_code
+    }
+
+    /// SIMC id.
+    pub fn city_teryt_id(&self) -> Option<&str> {
+        self.city_teryt_id.as_deref()
+    }
+
+    pub fn municipality_teryt_id(&self) -> &str {
+        &self.municipality_teryt_id
+    }
+}
+
+/// Problem with joining an address to the dictionaries, see `JoinAudit`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JoinProblem {
+    /// `prgad:TERYTGminy` of the city is not a municipality of the TERC
+    /// catalog, so its addresses are skipped.
+    MunicipalityNotInTerc,
+    /// `prgad:rodzaj` of the city is not a known SIMC code, so its kind is empty.
+    UnknownCityKind,
+}
+
+/// One line of the join audit file: a municipality missing from TERC (for all
+/// its cities) or a city with an unknown kind.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct JoinAuditRecord {
+    pub problem: JoinProblem,
+    pub municipality_teryt_id: String,
+    /// SIMC id of the city, only with `UnknownCityKind`.
+    pub city_teryt_id: Option<String>,
+    /// Name of the city, only with `UnknownCityKind`.
+    pub city: Option<String>,
+    /// `prgad:rodzaj` code of the city, only with `UnknownCityKind`.
+    pub city_kind_code: Option<String>,
+    /// Addresses in the municipality (all skipped) or city.
+    pub addresses: usize,
+}
+
+/// Records of a `JoinAudit` by problem, municipality and city.
+type JoinAuditRecords = BTreeMap<(JoinProblem, String, String), JoinAuditRecord>;
+
+/// Problems found when joining addresses with the TERC catalog and the city
+/// dictionary, collected by `AddressParser2021::with_join_audit` and written
+/// to `path` as JSON lines by `write`. Clones share the records.
+#[derive(Clone, Debug)]
+pub struct JoinAudit {
+    path: PathBuf,
+    records: Arc<Mutex<JoinAuditRecords>>,
+}
+
+impl JoinAudit {
+    /// Audit written to `path` (the file is only created by `write`).
+    pub fn to_file(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            records: Arc::default(),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn add(&self, problem: JoinProblem, city: &City) {
+        let key = match problem {
+            JoinProblem::MunicipalityNotInTerc => String::new(),
+            JoinProblem::UnknownCityKind => city.city_teryt_id.clone().unwrap_or(city.name.clone()),
+        };
+        let city_detail =
+            |value: &str| (problem == JoinProblem::UnknownCityKind).then(|| value.to_string());
+        self.records
+            .lock()
+            .unwrap()
+            .entry((problem, city.municipality_teryt_id.clone(), key))
+            .or_insert_with(|| JoinAuditRecord {
+                problem,
+                municipality_teryt_id: city.municipality_teryt_id.clone(),
+                city_teryt_id: city.city_teryt_id.as_deref().and_then(city_detail),
+                city: city_detail(&city.name),
+                city_kind_code: city_detail(&city.kind_code),
+                addresses: 0,
+            })
+            .addresses += 1;
+    }
+
+    /// Records collected so far, by problem and municipality.
+    pub fn records(&self) -> Vec<JoinAuditRecord> {
+        self.records.lock().unwrap().values().cloned().collect()
+    }
+
+    /// Writes the records to `path`, one JSON object per line. Returns the
+    /// number of records.
+    pub fn write(&self) -> anyhow::Result<usize> {
+        let records = self.records();
+        let file = std::fs::File::create(&self.path).with_context(|| {
+            format!(
+                "Could not create join audit file `{}`.",
+                self.path.display()
+            )
+        })?;
+        let mut writer = BufWriter::new(file);
+        for record in &records {
+            serde_json::to_writer(&mut writer, record)?;
+            writer.write_all(b"\n")?;
+        }
+        writer.flush().with_context(|| {
+            format!("Could not write join audit file `{}`.", self.path.display())
+        })?;
+        Ok(records.len())
+    }
+}
+
+/// Street read by `build_dictionaries`.
+#[derive(Debug)]
+pub struct Street {
     name: String,
     kind: String,
     teryt_id: Option<String>,
+    /// Parts of the name and `prgad:rodzaj` code, to construct the name with
+    /// other street types, see `AddressParser2021::with_street_types`.
+    part1: String,
+    part2: Option<String>,
+    code: String,
 }
 
+impl Street {
+    /// Full name, with the street type unless it is `ulica`.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Street type, e.g. `plac`; empty for `ulica` and other types.
+    pub fn kind(&self) -> &str {
+        &self.kind
+    }
+
+    /// ULIC id.
+    pub fn teryt_id(&self) -> Option<&str> {
+        self.teryt_id.as_deref()
+    }
+
+    /// `prgad:rodzaj` code of the street type.
+    pub fn code(&self) -> &str {
+        &self.code
+    }
+}
+
+/// Cities and streets read by `build_dictionaries`.
 pub struct Mappings {
     city: HashMap<String, City>,
     street: HashMap<String, Street>,
 }
 
+impl Mappings {
+    /// Number of cities loaded from the input.
+    pub fn city_count(&self) -> usize {
+        self.city.len()
+    }
+
+    /// City with the given `gml:id`.
+    pub fn city(&self, id: &str) -> Option<&City> {
+        self.city.get(id)
+    }
+
+    /// Street with the given `gml:id`.
+    pub fn street(&self, id: &str) -> Option<&Street> {
+        self.street.get(id)
+    }
+
+    /// Number of streets loaded from the input.
+    pub fn street_count(&self) -> usize {
+        self.street.len()
+    }
+
+    /// Prints the number of loaded cities and streets, with a warning for
+    /// an empty dictionary (e.g. a truncated input file).
+    fn print_counts(&self) {
+        progress!(
+            "Loaded {} cities and {} streets.",
+            self.city_count(),
+            self.street_count()
+        );
+        if self.city.is_empty() {
+            println!(
+                "Warning: no cities were found in the input, city names and TERYT ids of addresses will be empty."
+            );
+        }
+        if self.street.is_empty() {
+            println!(
+                "Warning: no streets were found in the input, street names and TERYT ids of addresses will be empty."
+            );
+        }
+    }
+}
+
 static CITY_TYPE: LazyLock<HashMap<&str, &str>> = LazyLock::new(|| {
     let mut mapping = HashMap::new();
     mapping.insert("02", "kolonia");
@@ -80,63 +307,117 @@ static STREET_TYPE: LazyLock<HashMap<&str, &str>> = LazyLock::new(|| {
     mapping
 });
 
-/// Concatenates parts of the name and the street type.
-/// If name contains shorthand type then we don't replace it with the full type name.
-pub fn construct_full_name_from_parts(
-    part1: &String,
-    part2: &Option<String>,
-    kind: &str,
-) -> String {
-    let prefix = match kind {
-        "plac" => {
-            if part1.to_lowercase().starts_with(kind) || part1.to_lowercase().starts_with("pl.") {
-                ""
-            } else {
-                kind
-            }
-        }
-        "osiedle" => {
-            if part1.to_lowercase().starts_with(kind) || part1.to_lowercase().starts_with("os.") {
-                ""
-            } else {
-                kind
-            }
-        }
-        "rondo" => {
-            if part1.to_lowercase().starts_with(kind) || part1.to_lowercase().starts_with("rondo") {
-                ""
-            } else {
-                kind
-            }
+/// Abbreviations used with `StreetTypes::abbreviated`, other types are written in full.
+static STREET_TYPE_ABBREVIATIONS: LazyLock<HashMap<&str, &str>> = LazyLock::new(|| {
+    let mut mapping = HashMap::new();
+    mapping.insert("3", "pl.");
+    mapping.insert("11", "os.");
+    mapping.insert("2", "al.");
+    mapping.insert("5", "bulw.");
+    mapping.insert("14", "wyb.");
+    mapping
+});
+
+/// Street types written before street names, by `prgad:rodzaj` code.
+/// The default are the full names used by the parser (none for `ulica`).
+#[derive(Clone, Debug, PartialEq)]
+pub struct StreetTypes {
+    names: BTreeMap<String, String>,
+}
+
+impl Default for StreetTypes {
+    fn default() -> Self {
+        Self {
+            names: STREET_TYPE
+                .iter()
+                .map(|(code, name)| (code.to_string(), name.to_string()))
+                .collect(),
         }
-        "aleja" => {
-            if part1.to_lowercase().starts_with(kind) || part1.to_lowercase().starts_with("al.") {
-                ""
-            } else {
-                kind
-            }
+    }
+}
+
+impl StreetTypes {
+    /// Common abbreviations (e.g. `pl.` instead of `plac`).
+    pub fn abbreviated() -> Self {
+        let mut street_types = Self::default();
+        for (code, abbreviation) in STREET_TYPE_ABBREVIATIONS.iter() {
+            street_types
+                .names
+                .insert(code.to_string(), abbreviation.to_string());
         }
-        _ => {
-            if part1.to_lowercase().starts_with(kind) {
-                ""
-            } else {
-                kind
+        street_types
+    }
+
+    /// Writes `name` (may be empty) before names of streets of type `code`.
+    /// Returns an error for codes that are not `prgad:rodzaj` codes.
+    pub fn set(&mut self, code: &str, name: &str) -> anyhow::Result<()> {
+        match self.names.get_mut(code) {
+            Some(current) => {
+                *current = name.to_string();
+                Ok(())
             }
+            None => anyhow::bail!(
+                "Unknown street type code `{}`, expected one of: 1-16.",
+                code
+            ),
         }
+    }
+
+    fn get(&self, code: &str) -> &str {
+        self.names.get(code).map_or("", |name| name.as_str())
+    }
+}
+
+/// Whether `part1` already starts with the street type `kind` (or with its usual shorthand).
+fn starts_with_street_type(part1: &str, kind: &str) -> bool {
+    let part1 = part1.to_lowercase();
+    let shorthand = match kind {
+        "plac" => "pl.",
+        "osiedle" => "os.",
+        "aleja" => "al.",
+        _ => kind,
     };
-    let name_parts = [
-        prefix.to_string(),
-        part2.clone().unwrap_or_default(),
-        part1.to_string(),
-    ];
-    let non_empty_parts: Vec<String> = name_parts.into_iter().filter(|s| !s.is_empty()).collect();
+    part1.starts_with(&kind.to_lowercase()) || part1.starts_with(shorthand)
+}
+
+fn join_name_parts(prefix: &str, part1: &str, part2: &Option<String>) -> String {
+    let name_parts = [prefix, part2.as_deref().unwrap_or_default(), part1];
+    let non_empty_parts: Vec<&str> = name_parts.into_iter().filter(|s| !s.is_empty()).collect();
     non_empty_parts.join(" ")
 }
 
-fn parse_city<R: BufRead>(reader: &mut Reader<R>) -> City {
+/// Concatenates parts of the name and the street type.
+/// If name contains shorthand type then we don't replace it with the full type name.
+pub fn construct_full_name_from_parts(part1: &str, part2: &Option<String>, kind: &str) -> String {
+    let prefix = if starts_with_street_type(part1, kind) {
+        ""
+    } else {
+        kind
+    };
+    join_name_parts(prefix, part1, part2)
+}
+
+/// Like `construct_full_name_from_parts`, with the type of `code` taken from
+/// `street_types`. Names that already start with the default type of `code`
+/// or its shorthand are left as they are.
+fn construct_name_with_street_types(
+    part1: &str,
+    part2: &Option<String>,
+    code: &str,
+    street_types: &StreetTypes,
+) -> String {
+    let kind = street_types.get(code);
+    let default_kind = STREET_TYPE.get(code).copied().unwrap_or("");
+    let has_type = (!default_kind.is_empty() && starts_with_street_type(part1, default_kind))
+        || starts_with_street_type(part1, kind);
+    join_name_parts(if has_type { "" } else { kind }, part1, part2)
+}
+
+fn parse_city<R: BufRead>(reader: &mut Reader<R>) -> anyhow::Result<City> {
     let mut buffer = Vec::new();
     let mut last_tag = Vec::new();
     let mut kind = String::new();
+    let mut kind_code = String::new();
     let mut name = String::new();
     let mut city_teryt_id = None;
     let mut municipality_teryt_id = String::new();
@@ -163,6 +444,7 @@ fn parse_city<R: BufRead>(reader: &mut Reader<R>) -> City {
                             .cloned()
                             .unwrap_or("")
                             .to_string();
+                        kind_code = text_trimmed.to_string();
                     }
                     b"prgad:identyfikatorSIMC" => {
                         city_teryt_id = Some(text_trimmed.to_string());
@@ -178,27 +460,37 @@ fn parse_city<R: BufRead>(reader: &mut Reader<R>) -> City {
                 break;
             }
             Ok(Event::Eof) => {
-                panic!("Error: reached end of file before end of address entry");
+                return Err(PrgError::Xml(anyhow::anyhow!(
+                    "Error: reached end of file before end of address entry"
+                ))
+                .into());
             }
             Err(e) => {
-                panic!("Error at position {}: {:?}", reader.error_position(), e);
+                return Err(PrgError::Xml(anyhow::anyhow!(
+                    "Error at position {}: {}",
+                    reader.error_position(),
+                    e
+                ))
+                .into());
             }
             _ => (), // we do not care about other events here
         }
         buffer.clear();
     }
-    City {
+    Ok(City {
         kind: kind,
+        kind_code,
         name: name,
         city_teryt_id: city_teryt_id,
         municipality_teryt_id: municipality_teryt_id,
-    }
+    })
 }
 
-fn parse_street<R: BufRead>(reader: &mut Reader<R>) -> Street {
+fn parse_street<R: BufRead>(reader: &mut Reader<R>) -> anyhow::Result<Street> {
     let mut buffer = Vec::new();
     let mut last_tag = Vec::new();
     let mut kind = String::new();
+    let mut code = String::new();
     let name;
     let mut teryt_id = None;
     let mut part1 = String::new();
@@ -218,6 +510,7 @@ fn parse_street<R: BufRead>(reader: &mut Reader<R>) -> Street {
                 let text_trimmed = text_decoded.trim();
                 match last_tag.as_slice() {
                     b"prgad:rodzaj" => {
+                        code = text_trimmed.to_string();
                         kind = STREET_TYPE
                             .get(text_trimmed)
                             .cloned()
@@ -242,10 +535,18 @@ fn parse_street<R: BufRead>(reader: &mut Reader<R>) -> Street {
                 break;
             }
             Ok(Event::Eof) => {
-                panic!("Error: reached end of file before end of address entry");
+                return Err(PrgError::Xml(anyhow::anyhow!(
+                    "Error: reached end of file before end of address entry"
+                ))
+                .into());
             }
             Err(e) => {
-                panic!("Error at position {}: {:?}", reader.error_position(), e);
+                return Err(PrgError::Xml(anyhow::anyhow!(
+                    "Error at position {}: {}",
+                    reader.error_position(),
+                    e
+                ))
+                .into());
             }
             _ => (), // we do not care about other events here
         }
@@ -259,14 +560,20 @@ fn parse_street<R: BufRead>(reader: &mut Reader<R>) -> Street {
             part2.unwrap_or_default()
         );
     }
-    Street {
+    Ok(Street {
         kind: kind.to_string(),
         name: name,
         teryt_id: teryt_id,
-    }
+        part1,
+        part2,
+        code,
+    })
 }
 
-pub fn build_dictionaries<R: BufRead>(mut reader: Reader<R>) -> Mappings {
+/// Returns an error if the input cannot be read (e.g. a corrupted file inside
+/// a ZIP archive) or is not well-formed XML.
+pub fn build_dictionaries<R: BufRead>(mut reader: Reader<R>) -> anyhow::Result<Mappings> {
+    let _timer = timings::start(Phase::Dictionaries);
     let mut city_dict = HashMap::<String, City>::new();
     let mut street_dict = HashMap::<String, Street>::new();
     let mut buffer = Vec::new();
@@ -276,53 +583,194 @@ pub fn build_dictionaries<R: BufRead>(mut reader: Reader<R>) -> Mappings {
             Ok(Event::Start(ref e)) => match e.name().as_ref() {
                 CITY_TAG => {
                     let id = get_attribute(e, b"gml:id").to_string();
-                    let info = parse_city(&mut reader);
+                    let info = parse_city(&mut reader)?;
                     city_dict.insert(id, info);
                 }
                 STREET_TAG => {
                     let id = get_attribute(e, b"gml:id").to_string();
-                    let info = parse_street(&mut reader);
+                    let info = parse_street(&mut reader)?;
                     street_dict.insert(id, info);
                 }
                 _ => (),
             },
             Ok(Event::Eof) => break, // exits the loop when reaching end of file
-            Err(e) => panic!("Error at position {}: {:?}", reader.error_position(), e),
+            Err(e) => {
+                return Err(PrgError::Xml(anyhow::anyhow!(
+                    "Error at position {}: {}",
+                    reader.error_position(),
+                    e
+                ))
+                .into());
+            }
             _ => (), // we do not care about other events here
         }
         buffer.clear();
     }
-    Mappings {
+    let mappings = Mappings {
         city: city_dict,
         street: street_dict,
-    }
+    };
+    mappings.print_counts();
+    Ok(mappings)
 }
 
 pub struct AddressParser2021<R: BufRead> {
     reader: Reader<R>,
     batch_size: usize,
-    mappings: Mappings,
+    mappings: Arc<Mappings>,
     teryt_names: Arc<HashMap<String, Terc>>,
     builders: CanonicalBuilders,
+    errors: ErrorSink,
+    filter: RecordFilter,
+    /// Set once the end of input or an XML error was reached.
+    finished: bool,
+    /// Position of the start of `reader` in the file, added to reported offsets.
+    offset_base: u64,
+    counts: ValidationCounts,
+    report: Option<ValidationReport>,
+    /// Print unknown tags after every batch instead of at the end of input.
+    verbose_unknown_tags: bool,
+    /// Return an error on the first unknown tag instead of counting it.
+    fail_on_unknown_tag: bool,
+    /// Street types replacing the default ones in street names.
+    street_types: Option<Arc<StreetTypes>>,
+    /// Collects failed joins instead of printing them.
+    join_audit: Option<JoinAudit>,
 }
 
 impl<R: BufRead> AddressParser2021<R> {
     pub fn new(
         reader: Reader<R>,
         batch_size: usize,
-        additional_info: Mappings,
+        additional_info: impl Into<Arc<Mappings>>,
         teryt_names: Arc<HashMap<String, Terc>>,
     ) -> Self {
         Self {
             reader,
             batch_size,
-            mappings: additional_info,
+            mappings: additional_info.into(),
             teryt_names,
             builders: CanonicalBuilders::with_capacity(batch_size),
+            errors: ErrorSink::default(),
+            filter: RecordFilter::default(),
+            finished: false,
+            offset_base: 0,
+            counts: ValidationCounts::default(),
+            report: None,
+            verbose_unknown_tags: false,
+            fail_on_unknown_tag: false,
+            street_types: None,
+            join_audit: None,
         }
     }
 
-    fn parse_address(&mut self) {
+    /// Dictionaries of cities and streets the addresses refer to.
+    pub fn mappings(&self) -> &Mappings {
+        &self.mappings
+    }
+
+    /// City with the given `gml:id`, see `Mappings::city`.
+    pub fn city(&self, id: &str) -> Option<&City> {
+        self.mappings.city(id)
+    }
+
+    /// Street with the given `gml:id`, see `Mappings::street`.
+    pub fn street(&self, id: &str) -> Option<&Street> {
+        self.mappings.street(id)
+    }
+
+    /// Report addresses that could not be parsed to `errors`.
+    /// By default they are printed as warnings.
+    pub fn with_error_sink(mut self, errors: ErrorSink) -> Self {
+        self.errors = errors;
+        self
+    }
+
+    /// Only output addresses accepted by `filter`.
+    pub fn with_filter(mut self, filter: RecordFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// Add counts of addresses, component references and unknown tags to
+    /// `report` if one is given (unknown tags are then not printed).
+    pub fn with_validation_report(mut self, report: Option<ValidationReport>) -> Self {
+        self.report = report;
+        self
+    }
+
+    /// Without a validation report, unknown tags inside addresses are counted
+    /// and printed once at the end of input; with `verbose` after every batch.
+    pub fn with_verbose_unknown_tags(mut self, verbose: bool) -> Self {
+        self.verbose_unknown_tags = verbose;
+        self
+    }
+
+    /// Stop with an error naming the tag and the byte offset of the address
+    /// at the first unknown tag inside an address, e.g. to notice new fields
+    /// of the schema. By default unknown tags are counted and ignored.
+    pub fn with_fail_on_unknown_tag(mut self, fail: bool) -> Self {
+        self.fail_on_unknown_tag = fail;
+        self
+    }
+
+    /// Construct street names with `street_types` instead of the default
+    /// full names of street types.
+    pub fn with_street_types(mut self, street_types: Option<Arc<StreetTypes>>) -> Self {
+        self.street_types = street_types;
+        self
+    }
+
+    /// Add the municipalities missing from the TERC catalog and the cities
+    /// with an unknown kind to `audit` if one is given. Addresses in missing
+    /// municipalities are skipped and reported to the error sink either way.
+    pub fn with_join_audit(mut self, audit: Option<JoinAudit>) -> Self {
+        self.join_audit = audit;
+        self
+    }
+
+    /// Compute `dlugosc_geograficzna`/`szerokosc_geograficzna` from the EPSG:2180
+    /// coordinates (default). Without it they are null and no reprojection is done.
+    pub fn with_reprojection(mut self, reproject: bool) -> Self {
+        self.builders.reproject = reproject;
+        self
+    }
+
+    /// Read `gml:pos` (and points without `srsName`) in `input_crs` instead
+    /// of EPSG:2180, see `parse_gml_pos`.
+    pub fn with_input_crs(mut self, input_crs: CRS) -> Self {
+        self.builders.input_crs = input_crs;
+        self
+    }
+
+    /// Collapse runs of whitespace (including non-breaking spaces) to a single
+    /// space in the names, house numbers, postcodes and statuses. By default
+    /// they are written as in the input, only trimmed.
+    pub fn with_normalize_whitespace(mut self, normalize: bool) -> Self {
+        self.builders.normalize_whitespace = normalize;
+        self
+    }
+
+    /// Write the text of `untrimmed` columns with its leading and trailing
+    /// whitespace. By default all text is trimmed.
+    pub fn with_untrimmed_columns(mut self, untrimmed: UntrimmedColumns) -> Self {
+        self.builders.untrimmed = untrimmed;
+        self
+    }
+
+    /// Treat `reader` as starting at byte `offset` of the file (when parsing a
+    /// part of it), so that reported offsets are positions in the whole file.
+    pub(crate) fn with_offset_base(mut self, offset: u64) -> Self {
+        self.offset_base = offset;
+        self
+    }
+
+    /// Parses one address into the builders. Returns `false` if the address
+    /// was skipped because of an error (already reported to the error sink)
+    /// or rejected by the filter. Malformed XML is returned as an error.
+    fn parse_address(&mut self, offset: u64) -> Result<bool, ArrowError> {
+        let mut record = RecordState::new(offset);
+        self.counts.addresses += 1;
         let mut buffer = Vec::new();
         let mut last_tag = Vec::new();
         let mut nested_tag = false; // informs if we're processing a nested tag
@@ -334,56 +782,89 @@ impl<R: BufRead> AddressParser2021<R> {
                     last_tag.clear();
                     last_tag.extend_from_slice(e.name().as_ref());
                     match e.name().as_ref() {
-                        b"prgad:idIIP"
-                        | b"prgad:AD_IdentyfikatorIIP"
-                        | b"prgad:georeferencja"
-                        | b"gml:Point" => {
+                        b"prgad:idIIP" | b"prgad:AD_IdentyfikatorIIP" | b"prgad:georeferencja" => {
+                            nested_tag = true;
+                            tag_ignore_text = false;
+                        }
+                        b"gml:Point" => {
+                            // points without `srsName` are assumed to be in `input_crs`
+                            if let Ok(srs_name) = try_get_attribute(e, b"srsName")
+                                && let Err(err) = check_srs_name(&srs_name, self.builders.input_crs)
+                            {
+                                record.fail(err);
+                            }
                             nested_tag = true;
                             tag_ignore_text = false;
                         }
                         b"prgad:miejscowosc" => {
-                            let id = &get_attribute(e, b"xlink:href")[1..];
-                            let city = self.mappings.city.get(id);
-                            match city {
-                                None => {
-                                    println!(
-                                        "Warning: Could not find information about city with id: {} in GML.",
-                                        &id
-                                    );
-                                }
-                                Some(c) => {
-                                    self.builders.city.append_value(&c.name);
-                                    self.builders
-                                        .municipality_teryt_id
-                                        .append_value(&c.municipality_teryt_id);
-                                    option_append_value_or_null(
-                                        &mut self.builders.city_teryt_id,
-                                        c.city_teryt_id.clone(),
-                                    );
-                                    let terc_info = self.teryt_names.get(&c.municipality_teryt_id);
-                                    match terc_info {
+                            match try_get_attribute(e, b"xlink:href") {
+                                Err(err) => record.fail(err),
+                                Ok(href) => {
+                                    let id = &href[1..];
+                                    let city = self.mappings.city.get(id);
+                                    self.counts.components += 1;
+                                    match city {
                                         None => {
+                                            self.counts.unresolved_references += 1;
                                             println!(
-                                                "Could not find info for municipality with teryt id: {}",
-                                                &c.municipality_teryt_id
+                                                "Warning: Could not find information about city with id: {} in GML.",
+                                                &id
                                             );
                                         }
-                                        Some(t) => {
-                                            self.builders
-                                                .voivodeship_teryt_id
-                                                .append_value(t.voivodeship_teryt_id.clone());
-                                            self.builders
-                                                .voivodeship
-                                                .append_value(t.voivodeship_name.clone());
+                                        Some(c) => {
+                                            if c.kind.is_empty()
+                                                && let Some(audit) = &self.join_audit
+                                            {
+                                                audit.add(JoinProblem::UnknownCityKind, c);
+                                            }
+                                            self.builders.city.append_value(&c.name);
                                             self.builders
-                                                .county_teryt_id
-                                                .append_value(t.county_teryt_id.clone());
-                                            self.builders
-                                                .county
-                                                .append_value(t.county_name.clone());
-                                            self.builders
-                                                .municipality
-                                                .append_value(t.municipality_name.clone());
+                                                .municipality_teryt_id
+                                                .append_value(&c.municipality_teryt_id);
+                                            option_append_value_or_null(
+                                                &mut self.builders.city_teryt_id,
+                                                c.city_teryt_id.clone(),
+                                            );
+                                            let terc_info =
+                                                self.teryt_names.get(&c.municipality_teryt_id);
+                                            match terc_info {
+                                                None => {
+                                                    self.counts.unresolved_references += 1;
+                                                    if let Some(audit) = &self.join_audit {
+                                                        audit.add(
+                                                            JoinProblem::MunicipalityNotInTerc,
+                                                            c,
+                                                        );
+                                                    }
+                                                    // the administrative unit columns are not nullable
+                                                    record.fail(
+                                                        PrgError::UnresolvedReference(anyhow::anyhow!(
+                                                            "Municipality with teryt id {} is not in the TERC catalog",
+                                                            &c.municipality_teryt_id
+                                                        ))
+                                                        .into(),
+                                                    );
+                                                }
+                                                Some(t) => {
+                                                    self.builders
+                                                        .voivodeship_teryt_id
+                                                        .append_value(
+                                                            t.voivodeship_teryt_id.clone(),
+                                                        );
+                                                    self.builders
+                                                        .voivodeship
+                                                        .append_value(t.voivodeship_name.clone());
+                                                    self.builders
+                                                        .county_teryt_id
+                                                        .append_value(t.county_teryt_id.clone());
+                                                    self.builders
+                                                        .county
+                                                        .append_value(t.county_name.clone());
+                                                    self.builders
+                                                        .municipality
+                                                        .append_value(t.municipality_name.clone());
+                                                }
+                                            }
                                         }
                                     }
                                 }
@@ -391,22 +872,55 @@ impl<R: BufRead> AddressParser2021<R> {
                             nested_tag = false;
                             tag_ignore_text = true;
                         }
-                        b"prgad:ulica2" => {
-                            let id = &get_attribute(e, b"xlink:href")[1..];
-                            let street = self.mappings.street.get(id);
-                            match street {
-                                None => {
-                                    println!(
-                                        "Warning: Could not find information about street with id: {} in GML.",
-                                        &id
-                                    );
+                        b"prgad:budynek" => {
+                            // building reference, either as a link or as an identifier in text
+                            match try_get_attribute(e, b"xlink:href") {
+                                Ok(href) => {
+                                    self.builders
+                                        .building_id
+                                        .append_value(href.trim_start_matches('#'));
+                                    tag_ignore_text = true;
                                 }
-                                Some(s) => {
-                                    self.builders.street.append_value(&s.name);
-                                    option_append_value_or_null(
-                                        &mut self.builders.street_teryt_id,
-                                        s.teryt_id.clone(),
-                                    );
+                                Err(_) => tag_ignore_text = false,
+                            }
+                            nested_tag = false;
+                        }
+                        b"prgad:ulica2" => {
+                            match try_get_attribute(e, b"xlink:href") {
+                                Err(err) => record.fail(err),
+                                Ok(href) => {
+                                    let id = &href[1..];
+                                    let street = self.mappings.street.get(id);
+                                    self.counts.components += 1;
+                                    match street {
+                                        None => {
+                                            self.counts.unresolved_references += 1;
+                                            println!(
+                                                "Warning: Could not find information about street with id: {} in GML.",
+                                                &id
+                                            );
+                                        }
+                                        Some(s) => {
+                                            match &self.street_types {
+                                                None => self.builders.street.append_value(&s.name),
+                                                Some(street_types) => {
+                                                    self.builders.street.append_value(
+                                                        construct_name_with_street_types(
+                                                            &s.part1,
+                                                            &s.part2,
+                                                            &s.code,
+                                                            street_types,
+                                                        ),
+                                                    )
+                                                }
+                                            }
+                                            record.has_street = true;
+                                            option_append_value_or_null(
+                                                &mut self.builders.street_teryt_id,
+                                                s.teryt_id.clone(),
+                                            );
+                                        }
+                                    }
                                 }
                             }
                             nested_tag = false;
@@ -424,41 +938,61 @@ impl<R: BufRead> AddressParser2021<R> {
                         // if nested_tag is true, we are inside a nested tag that we want to skip (only read innermost text not the whole tree branch)
                         continue;
                     }
-                    let text_decoded = e.decode().expect("Failed to decode text.");
+                    let text_decoded = match e.decode() {
+                        Ok(text) => text,
+                        Err(err) => {
+                            record.fail(anyhow::Error::new(err).context("Failed to decode text"));
+                            last_tag.clear();
+                            continue;
+                        }
+                    };
                     let text_trimmed = text_decoded.trim();
                     match last_tag.as_slice() {
                         b"prgad:lokalnyId" => {
+                            record.uuid = Some(text_trimmed.to_string());
                             self.builders.uuid.append_value(text_trimmed);
                         }
                         b"prgad:przestrzenNazw" => {
                             self.builders.id_namespace.append_value(text_trimmed);
                         }
-                        b"prgad:wersjaId" => {
-                            let dt = DateTime::parse_from_rfc3339(text_trimmed)
-                                .expect("Failed to parse datetime")
-                                .to_utc();
-                            self.builders.version.append_value(dt.timestamp() * 1000);
-                        }
+                        b"prgad:wersjaId" => match parse_datetime_millis(text_trimmed) {
+                            Ok(millis) => self.builders.version.append_value(millis),
+                            Err(err) => record.fail(err),
+                        },
                         b"prgad:poczatekWersjiObiektu" => {
                             if text_trimmed.is_empty() {
                                 self.builders.lifecycle_start_date.append_null();
+                            } else if let Ok(dt) =
+                                chrono::DateTime::parse_from_rfc3339(text_trimmed)
+                            {
+                                // an explicit offset takes precedence over the Europe/Warsaw assumption
+                                self.builders
+                                    .lifecycle_start_date
+                                    .append_value(dt.timestamp_millis());
                             } else {
-                                let naive = NaiveDateTime::parse_from_str(
+                                match NaiveDateTime::parse_from_str(
                                     text_trimmed,
                                     "%Y-%m-%dT%H:%M:%S",
-                                )
-                                .expect("Failed to parse datetime");
-                                match crate::common::warsaw_naive_to_utc_millis(naive) {
-                                    Ok(millis) => {
-                                        self.builders.lifecycle_start_date.append_value(millis);
-                                    }
-                                    Err(e) => {
-                                        println!(
-                                            "Warning: could not convert poczatekWersjiObiektu `{}` to UTC: {}. Writing null.",
-                                            text_trimmed, e
-                                        );
-                                        self.builders.lifecycle_start_date.append_null();
+                                ) {
+                                    Ok(naive) => {
+                                        match crate::common::warsaw_naive_to_utc_millis(naive) {
+                                            Ok(millis) => {
+                                                self.builders
+                                                    .lifecycle_start_date
+                                                    .append_value(millis);
+                                            }
+                                            Err(e) => {
+                                                println!(
+                                                    "Warning: could not convert poczatekWersjiObiektu `{}` to UTC: {}. Writing null.",
+                                                    text_trimmed, e
+                                                );
+                                                self.builders.lifecycle_start_date.append_null();
+                                            }
+                                        }
                                     }
+                                    Err(err) => record.fail(anyhow::Error::new(err).context(
+                                        format!("Failed to parse datetime `{}`", text_trimmed),
+                                    )),
                                 }
                             }
                         }
@@ -466,104 +1000,155 @@ impl<R: BufRead> AddressParser2021<R> {
                             if text_trimmed.is_empty() {
                                 self.builders.valid_since_date.append_null();
                             } else {
-                                let date = NaiveDate::parse_from_str(text_trimmed, "%Y-%m-%d")
-                                    .expect("Failed to parse date");
-                                self.builders.valid_since_date.append_value(
-                                    date.signed_duration_since(EPOCH_DATE).num_days() as i32,
-                                );
+                                match parse_date_days(text_trimmed) {
+                                    Ok(days) => {
+                                        record.valid_since = Some(days);
+                                        self.builders.valid_since_date.append_value(days)
+                                    }
+                                    Err(err) => record.fail(err),
+                                }
                             }
                         }
                         b"prgad:numerPorzadkowy" => {
-                            self.builders.house_number.append_value(text_trimmed);
+                            let text =
+                                trim_unless(self.builders.untrimmed.house_number, &text_decoded);
+                            str_append_value_or_null(&mut self.builders.house_number, text);
                         }
                         b"prgad:kodPocztowy" => {
-                            str_append_value_or_null(&mut self.builders.postcode, text_trimmed);
+                            let text = trim_unless(self.builders.untrimmed.postcode, &text_decoded);
+                            str_append_value_or_null(&mut self.builders.postcode, text);
+                        }
+                        b"prgad:budynek" => {
+                            let text =
+                                trim_unless(self.builders.untrimmed.building_id, &text_decoded);
+                            str_append_value_or_null(&mut self.builders.building_id, text);
                         }
-                        b"gml:pos" => {
-                            let coords = parse_gml_pos(text_trimmed, CoordOrder::XY)
-                                .expect("Could not parse coordinates.");
-                            match coords {
-                                None => {
-                                    self.builders.longitude.append_null();
-                                    self.builders.latitude.append_null();
+                        b"gml:pos" if record.first_position() => {
+                            match parse_gml_pos(
+                                text_trimmed,
+                                CoordOrder::XY,
+                                self.builders.input_crs.proj(),
+                            ) {
+                                Ok(None) => {
                                     self.builders.x_epsg_2180.append_null();
                                     self.builders.y_epsg_2180.append_null();
                                 }
-                                Some(coords) => {
-                                    self.builders.longitude.append_value(coords.x4326);
-                                    self.builders.latitude.append_value(coords.y4326);
-                                    self.builders.x_epsg_2180.append_value(coords.x2180);
-                                    self.builders.y_epsg_2180.append_value(coords.y2180);
+                                Ok(Some(coords)) => {
+                                    record.has_coordinates = true;
+                                    self.builders.append_point(&coords);
+                                }
+                                Err(err) => {
+                                    self.builders.x_epsg_2180.append_null();
+                                    self.builders.y_epsg_2180.append_null();
+                                    record.warn(format!("{:#}, writing null coordinates.", err));
                                 }
                             }
                         }
+                        b"gml:pos" => {} // later positions are ignored, see `first_position`
                         _ => {
-                            println!(
-                                "Unknown tag `{:?}` for parse_address function.",
-                                std::str::from_utf8(&last_tag)
-                            );
+                            if self.fail_on_unknown_tag {
+                                return Err(ArrowError::ParseError(format!(
+                                    "Unknown tag `{}` inside address at byte offset {} (uuid: {}).",
+                                    String::from_utf8_lossy(&last_tag),
+                                    record.offset,
+                                    record.uuid.as_deref().unwrap_or("unknown")
+                                )));
+                            }
+                            self.counts.unknown_tag(&last_tag);
                         }
                     }
                     last_tag.clear();
                 }
                 Ok(Event::End(ref e)) if e.name().as_ref() == ADDRESS_TAG => {
-                    // ensure all builders have the same length
-                    self.builders.pad_short_columns();
                     // end of the current address entry
-                    break;
+                    let keep = record
+                        .finish(&self.filter, &self.errors)
+                        .map_err(|e| ArrowError::ExternalError(e.into()))?;
+                    // ensure all builders have the same length
+                    self.builders.end_row(keep);
+                    return Ok(keep);
                 }
                 Ok(Event::Eof) => {
-                    panic!("Error: reached end of file before end of address entry");
+                    return Err(ArrowError::ParseError(
+                        "Reached end of file before end of address entry.".to_string(),
+                    ));
                 }
                 Err(e) => {
-                    panic!(
+                    return Err(ArrowError::ParseError(format!(
                         "Error at position {}: {:?}",
                         self.reader.error_position(),
                         e
-                    );
+                    )));
                 }
                 _ => (), // we do not care about other events here
             }
             buffer.clear();
         }
     }
-}
-
-impl<R: BufRead> Iterator for AddressParser2021<R> {
-    type Item = arrow::array::RecordBatch;
 
-    fn next(&mut self) -> Option<Self::Item> {
+    /// Parses addresses until a batch is full or the input ends.
+    fn next_batch(&mut self) -> Option<Result<RecordBatch, ArrowError>> {
+        if self.finished {
+            return None;
+        }
         let mut buffer = Vec::new();
         let mut row_count: usize = 0;
         // main loop that catches events when new object starts
         loop {
+            let offset = self.offset_base + self.reader.buffer_position();
             match self.reader.read_event_into(&mut buffer) {
-                Ok(Event::Start(ref e)) => {
-                    if e.name().as_ref() == ADDRESS_TAG {
-                        row_count += 1;
-                        self.parse_address();
-                        if row_count == self.batch_size {
-                            let record_batch = self.builders.build_record_batch();
-                            return Some(record_batch);
+                Ok(Event::Start(ref e)) if e.name().as_ref() == ADDRESS_TAG => {
+                    match self.parse_address(offset) {
+                        Ok(true) => {
+                            row_count += 1;
+                            if row_count == self.batch_size {
+                                return Some(self.builders.build_record_batch());
+                            }
+                        }
+                        Ok(false) => (),
+                        Err(e) => {
+                            self.finished = true;
+                            return Some(Err(e));
                         }
                     }
                 }
                 Ok(Event::Eof) => break, // exits the loop when reaching end of file
-                Err(e) => panic!(
-                    "Error at position {}: {:?}",
-                    self.reader.error_position(),
-                    e
-                ),
+                Err(e) => {
+                    self.finished = true;
+                    return Some(Err(ArrowError::ParseError(format!(
+                        "Error at position {}: {:?}",
+                        self.reader.error_position(),
+                        e
+                    ))));
+                }
                 _ => (), // we do not care about other events here
             }
             buffer.clear();
         }
-        let record_batch = self.builders.build_record_batch();
-        if record_batch.num_rows() > 0 {
-            Some(record_batch)
-        } else {
-            None
+        self.finished = true;
+        self.builders.build_final_record_batch()
+    }
+}
+
+impl<R: BufRead> Iterator for AddressParser2021<R> {
+    type Item = Result<RecordBatch, ArrowError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let batch = self.next_batch();
+        match &self.report {
+            Some(report) => report.add(std::mem::take(&mut self.counts)),
+            None if self.verbose_unknown_tags || self.finished => {
+                self.counts.warn_about_unknown_tags()
+            }
+            None => {}
         }
+        batch
+    }
+}
+
+impl<R: BufRead> RecordBatchReader for AddressParser2021<R> {
+    fn schema(&self) -> SchemaRef {
+        SCHEMA_CSV.clone()
     }
 }
 
@@ -642,7 +1227,9 @@ fn test_build_dictionaries() {
     let sample_file_path = "fixtures/sample_model2021.xml";
     let mut reader = Reader::from_file(sample_file_path).unwrap();
     reader.config_mut().expand_empty_elements = true;
-    let dict = build_dictionaries(reader);
+    let dict = build_dictionaries(reader).unwrap();
+    assert_eq!(dict.city_count(), 3);
+    assert_eq!(dict.street_count(), 2);
     let city_zubrow = &dict.city["PL.ZIPIN.2418.EMUiA_0188009_2025-10-14T14_04_04_02_00"];
     let city_rzepin = &dict.city["PL.ZIPIN.4877.EMUiA_0935682_2025-11-06T15_01_26_02_00"];
     assert_eq!(city_zubrow.municipality_teryt_id, "0807043");
@@ -681,6 +1268,7 @@ fn test_parse_address_dst_gap_timestamp_does_not_panic() {
         City {
             name: "Sulęcin".to_string(),
             kind: "miasto".to_string(),
+            kind_code: "96".to_string(),
             city_teryt_id: Some("0188009".to_string()),
             municipality_teryt_id: "0807043".to_string(),
         },
@@ -707,7 +1295,9 @@ fn test_parse_address_dst_gap_timestamp_does_not_panic() {
         },
         Arc::new(teryt),
     );
-    let batches: Vec<arrow::array::RecordBatch> = parser.collect();
+    let batches: Vec<arrow::array::RecordBatch> = parser
+        .collect::<Result<_, _>>()
+        .expect("Failed to parse addresses.");
     assert_eq!(batches.len(), 1);
     assert_eq!(batches[0].num_rows(), 1);
     let expected = chrono::DateTime::parse_from_rfc3339("2025-03-30T01:30:00Z")
@@ -722,6 +1312,83 @@ fn test_parse_address_dst_gap_timestamp_does_not_panic() {
     assert_eq!(column.value(0), expected);
 }
 
+#[test]
+fn test_parse_address_with_bad_date_is_skipped_and_reported() {
+    let xml = r##"
+<prgad:AD_PunktAdresowy>
+  <prgad:lokalnyId>bad-uuid</prgad:lokalnyId>
+  <prgad:wersjaId>2025-03-30T02:30:00+02:00</prgad:wersjaId>
+  <prgad:dataNadania>2025-13-45</prgad:dataNadania>
+  <prgad:numerPorzadkowy>1</prgad:numerPorzadkowy>
+</prgad:AD_PunktAdresowy>
+<prgad:AD_PunktAdresowy>
+  <prgad:lokalnyId>good-uuid</prgad:lokalnyId>
+  <prgad:przestrzenNazw>PL.TEST</prgad:przestrzenNazw>
+  <prgad:wersjaId>2025-03-30T02:30:00+02:00</prgad:wersjaId>
+  <prgad:dataNadania>2025-01-15</prgad:dataNadania>
+  <prgad:miejscowosc xlink:href="#city1"/>
+  <prgad:numerPorzadkowy>2</prgad:numerPorzadkowy>
+</prgad:AD_PunktAdresowy>
+"##;
+    let mut city = HashMap::new();
+    city.insert(
+        "city1".to_string(),
+        City {
+            name: "Sulęcin".to_string(),
+            kind: "miasto".to_string(),
+            kind_code: "96".to_string(),
+            city_teryt_id: Some("0188009".to_string()),
+            municipality_teryt_id: "0807043".to_string(),
+        },
+    );
+    let mut teryt = HashMap::new();
+    teryt.insert(
+        "0807043".to_string(),
+        Terc {
+            voivodeship_teryt_id: "08".to_string(),
+            voivodeship_name: "lubuskie".to_string(),
+            county_teryt_id: "0807".to_string(),
+            county_name: "sulęciński".to_string(),
+            municipality_name: "Sulęcin".to_string(),
+        },
+    );
+    let errors_file = tempfile::NamedTempFile::new().unwrap();
+    let errors = ErrorSink::to_file(errors_file.path()).unwrap();
+    let mut reader = Reader::from_reader(xml.as_bytes());
+    reader.config_mut().expand_empty_elements = true;
+    let parser = AddressParser2021::new(
+        reader,
+        100,
+        Mappings {
+            city,
+            street: HashMap::new(),
+        },
+        Arc::new(teryt),
+    )
+    .with_error_sink(errors.clone());
+    let batches: Vec<arrow::array::RecordBatch> = parser
+        .collect::<Result<_, _>>()
+        .expect("Failed to parse addresses.");
+    assert_eq!(batches.len(), 1);
+    assert_eq!(batches[0].num_rows(), 1);
+    let uuid = batches[0]
+        .column_by_name("lokalny_id")
+        .unwrap()
+        .as_any()
+        .downcast_ref::<arrow::array::StringArray>()
+        .unwrap()
+        .value(0);
+    assert_eq!(uuid, "good-uuid");
+
+    assert_eq!(errors.count(), 1);
+    errors.flush().unwrap();
+    let content = std::fs::read_to_string(errors_file.path()).unwrap();
+    let line: serde_json::Value = serde_json::from_str(content.trim()).unwrap();
+    assert_eq!(line["offset"], 1);
+    assert_eq!(line["uuid"], "bad-uuid");
+    assert!(line["message"].as_str().unwrap().contains("2025-13-45"));
+}
+
 #[test]
 fn test_construct_full_name_rondo_with_prefix() {
     let typ = "rondo";
@@ -761,3 +1428,454 @@ fn test_construct_full_name_aleja_without_prefix() {
     let name = construct_full_name_from_parts(&part1, &part2, &typ);
     assert_eq!(name, expected_name);
 }
+
+#[test]
+fn test_building_id() {
+    use arrow::array::Array;
+    use arrow::array::AsArray;
+
+    let xml = std::fs::read_to_string("fixtures/sample_model2021.xml")
+        .unwrap()
+        .replacen(
+            "<prgad:kodPocztowy>69-110</prgad:kodPocztowy>",
+            "<prgad:kodPocztowy>69-110</prgad:kodPocztowy>\n<prgad:budynek>PL.PZGIK.EGiB_0805043.1_77</prgad:budynek>",
+            1,
+        );
+    let mut reader = Reader::from_reader(xml.as_bytes());
+    reader.config_mut().expand_empty_elements = true;
+    let dict = build_dictionaries(reader).unwrap();
+    let teryt = crate::terc::get_terc_mapping(&std::path::PathBuf::from(
+        "fixtures/TERC_Urzedowy_2025-11-18.xml",
+    ))
+    .unwrap();
+    let mut reader = Reader::from_reader(xml.as_bytes());
+    reader.config_mut().expand_empty_elements = true;
+    let batches: Vec<arrow::array::RecordBatch> =
+        AddressParser2021::new(reader, 100, dict, Arc::new(teryt.mapping))
+            .collect::<Result<_, _>>()
+            .expect("Failed to parse addresses.");
+    let building_id = batches[0]
+        .column_by_name("id_budynku")
+        .unwrap()
+        .as_string::<i32>();
+    assert!(building_id.is_null(0));
+    assert_eq!(building_id.value(1), "PL.PZGIK.EGiB_0805043.1_77");
+}
+
+#[cfg(test)]
+fn parse_lifecycle_start_millis(value: &str) -> Option<i64> {
+    use arrow::array::Array;
+    use arrow::array::AsArray;
+    use arrow::datatypes::TimestampMillisecondType;
+
+    let xml = format!(
+        r##"
+<prgad:AD_Miejscowosc gml:id="city1">
+  <prgad:nazwa>Sulęcin</prgad:nazwa>
+  <prgad:rodzaj>96</prgad:rodzaj>
+  <prgad:identyfikatorSIMC>0188009</prgad:identyfikatorSIMC>
+  <prgad:TERYTGminy>0807043</prgad:TERYTGminy>
+</prgad:AD_Miejscowosc>
+<prgad:AD_PunktAdresowy>
+  <prgad:lokalnyId>test-uuid-1</prgad:lokalnyId>
+  <prgad:przestrzenNazw>PL.TEST</prgad:przestrzenNazw>
+  <prgad:wersjaId>2025-01-01T00:00:00+01:00</prgad:wersjaId>
+  <prgad:poczatekWersjiObiektu>{}</prgad:poczatekWersjiObiektu>
+  <prgad:miejscowosc xlink:href="#city1"/>
+  <prgad:numerPorzadkowy>1</prgad:numerPorzadkowy>
+</prgad:AD_PunktAdresowy>
+"##,
+        value
+    );
+    let teryt = crate::terc::get_terc_mapping(&std::path::PathBuf::from(
+        "fixtures/TERC_Urzedowy_2025-11-18.xml",
+    ))
+    .unwrap();
+    let mut reader = Reader::from_reader(xml.as_bytes());
+    reader.config_mut().expand_empty_elements = true;
+    let dict = build_dictionaries(reader).unwrap();
+    let mut reader = Reader::from_reader(xml.as_bytes());
+    reader.config_mut().expand_empty_elements = true;
+    let batches: Vec<arrow::array::RecordBatch> =
+        AddressParser2021::new(reader, 100, dict, Arc::new(teryt.mapping))
+            .collect::<Result<_, _>>()
+            .expect("Failed to parse addresses.");
+    let column = batches[0]
+        .column_by_name("poczatek_wersji_obiektu")
+        .unwrap()
+        .as_primitive::<TimestampMillisecondType>();
+    (!column.is_null(0)).then(|| column.value(0))
+}
+
+#[test]
+fn test_lifecycle_start_summer_is_cest() {
+    let expected = chrono::DateTime::parse_from_rfc3339("2025-07-01T10:00:00Z")
+        .unwrap()
+        .timestamp_millis();
+    assert_eq!(
+        parse_lifecycle_start_millis("2025-07-01T12:00:00"),
+        Some(expected)
+    );
+}
+
+#[test]
+fn test_lifecycle_start_winter_is_cet() {
+    let expected = chrono::DateTime::parse_from_rfc3339("2025-01-15T11:00:00Z")
+        .unwrap()
+        .timestamp_millis();
+    assert_eq!(
+        parse_lifecycle_start_millis("2025-01-15T12:00:00"),
+        Some(expected)
+    );
+}
+
+#[test]
+fn test_lifecycle_start_explicit_offset_is_respected() {
+    let expected = chrono::DateTime::parse_from_rfc3339("2025-07-01T12:00:00Z")
+        .unwrap()
+        .timestamp_millis();
+    assert_eq!(
+        parse_lifecycle_start_millis("2025-07-01T12:00:00Z"),
+        Some(expected)
+    );
+    assert_eq!(
+        parse_lifecycle_start_millis("2025-07-01T13:00:00+01:00"),
+        Some(expected)
+    );
+}
+
+#[test]
+fn test_batch_columns_in_schema_order() {
+    let terc = crate::terc::get_terc_mapping(&std::path::PathBuf::from(
+        "fixtures/TERC_Urzedowy_2025-11-18.xml",
+    ))
+    .unwrap();
+    let teryt_mapping = Arc::new(terc.mapping);
+    let path = std::path::PathBuf::from("fixtures/sample_model2021.xml");
+    let mut parser =
+        crate::get_address_parser_2021_uncompressed(&path, &10, &teryt_mapping).unwrap();
+    let batch = parser.next().unwrap().unwrap();
+    crate::common::assert_row_values(
+        &batch,
+        0,
+        &[
+            ("przestrzen_nazw", "PL.PZGIK.200"),
+            ("lokalny_id", "7343b2d2-c2ac-4951-ae9a-fe1932ffecfb"),
+            ("wersja_id", "2025-10-14T12:05:46Z"),
+            ("poczatek_wersji_obiektu", "2025-10-14T12:05:46Z"),
+            ("wazny_od_lub_data_nadania", "2012-04-27"),
+            ("wazny_do", ""),
+            ("teryt_wojewodztwo", "08"),
+            ("wojewodztwo", "lubuskie"),
+            ("teryt_powiat", "0807"),
+            ("powiat", "sulęciński"),
+            ("teryt_gmina", "0807043"),
+            ("gmina", "Sulęcin"),
+            ("teryt_miejscowosc", "0188009"),
+            ("miejscowosc", "Żubrów"),
+            ("czesc_miejscowosci", ""),
+            ("teryt_ulica", ""),
+            ("ulica", ""),
+            ("numer_porzadkowy", "21A"),
+            ("kod_pocztowy", "69-200"),
+            ("status", ""),
+            ("id_budynku", ""),
+            ("x_epsg_2180", "238651.83"),
+            ("y_epsg_2180", "519741.27"),
+            ("dlugosc_geograficzna", "15.149797186509767"),
+            ("szerokosc_geograficzna", "52.48080576032958"),
+        ],
+    );
+}
+
+#[test]
+fn test_construct_name_with_street_types() {
+    let mut street_types = StreetTypes::default();
+    street_types.set("3", "pl.").unwrap();
+    street_types.set("1", "ul.").unwrap();
+    let name =
+        |part1: &str, code| construct_name_with_street_types(part1, &None, code, &street_types);
+    assert_eq!(name("Kasztanowy", "3"), "pl. Kasztanowy");
+    // names that already have the type (full or abbreviated) are not changed
+    assert_eq!(name("Plac Kasztanowy", "3"), "Plac Kasztanowy");
+    assert_eq!(name("pl. Kasztanowy", "3"), "pl. Kasztanowy");
+    assert_eq!(name("Długa", "1"), "ul. Długa");
+    assert_eq!(name("Grunwaldzka", "2"), "aleja Grunwaldzka");
+    assert!(street_types.set("17", "x").is_err());
+    let abbreviated = StreetTypes::abbreviated();
+    assert_eq!(
+        construct_name_with_street_types("Grunwaldzka", &None, "2", &abbreviated),
+        "al. Grunwaldzka"
+    );
+    assert_eq!(
+        construct_name_with_street_types("Grunwaldzkie", &None, "6", &abbreviated),
+        "rondo Grunwaldzkie"
+    );
+}
+
+#[test]
+fn test_parser_with_street_types() {
+    use arrow::array::AsArray;
+
+    let xml = std::fs::read_to_string("fixtures/sample_model2021.xml")
+        .unwrap()
+        .replace(">Plac Kasztanowy<", ">Kasztanowy<");
+    let teryt = Arc::new(
+        crate::terc::get_terc_mapping(&std::path::PathBuf::from(
+            "fixtures/TERC_Urzedowy_2025-11-18.xml",
+        ))
+        .unwrap()
+        .mapping,
+    );
+    let streets = |street_types: Option<Arc<StreetTypes>>| {
+        let mut reader = Reader::from_reader(xml.as_bytes());
+        reader.config_mut().expand_empty_elements = true;
+        let dict = build_dictionaries(reader).unwrap();
+        let mut reader = Reader::from_reader(xml.as_bytes());
+        reader.config_mut().expand_empty_elements = true;
+        let batches: Vec<RecordBatch> = AddressParser2021::new(reader, 100, dict, teryt.clone())
+            .with_street_types(street_types)
+            .collect::<Result<_, _>>()
+            .unwrap();
+        let mut names: Vec<String> = batches[0]
+            .column_by_name("ulica")
+            .unwrap()
+            .as_string::<i32>()
+            .iter()
+            .flatten()
+            .map(str::to_string)
+            .collect();
+        names.sort();
+        names
+    };
+    assert_eq!(
+        streets(None),
+        vec!["Inwalidów Wojennych", "plac Kasztanowy"]
+    );
+    let mut street_types = StreetTypes::default();
+    street_types.set("3", "pl.").unwrap();
+    street_types.set("1", "ul.").unwrap();
+    assert_eq!(
+        streets(Some(Arc::new(street_types))),
+        vec!["pl. Kasztanowy", "ul. Inwalidów Wojennych"]
+    );
+}
+
+#[test]
+fn test_point_in_other_crs_is_reported() {
+    let xml = std::fs::read_to_string("fixtures/sample_model2021.xml")
+        .unwrap()
+        .replace(
+            r#"<gml:Point srsName="EPSG:2180""#,
+            r#"<gml:Point srsName="EPSG:4326""#,
+        );
+    let teryt = crate::terc::get_terc_mapping(&std::path::PathBuf::from(
+        "fixtures/TERC_Urzedowy_2025-11-18.xml",
+    ))
+    .unwrap();
+    let errors_file = tempfile::NamedTempFile::new().unwrap();
+    let errors = ErrorSink::to_file(errors_file.path()).unwrap();
+    let mut reader = Reader::from_reader(xml.as_bytes());
+    reader.config_mut().expand_empty_elements = true;
+    let dict = build_dictionaries(reader).unwrap();
+    let mut reader = Reader::from_reader(xml.as_bytes());
+    reader.config_mut().expand_empty_elements = true;
+    let rows: usize = AddressParser2021::new(reader, 100, dict, Arc::new(teryt.mapping))
+        .with_error_sink(errors.clone())
+        .map(|batch| batch.unwrap().num_rows())
+        .sum();
+    assert_eq!(rows, 0);
+    assert_eq!(errors.count(), 3);
+    errors.flush().unwrap();
+    let content = std::fs::read_to_string(errors_file.path()).unwrap();
+    assert!(content.contains("EPSG:4326"));
+}
+
+#[test]
+fn test_address_without_house_number() {
+    use arrow::array::{Array, AsArray};
+    let xml = std::fs::read_to_string("fixtures/sample_model2021.xml")
+        .unwrap()
+        .replacen(
+            "<prgad:numerPorzadkowy>21A</prgad:numerPorzadkowy>",
+            "<prgad:numerPorzadkowy/>",
+            1,
+        );
+    let teryt = crate::terc::get_terc_mapping(&std::path::PathBuf::from(
+        "fixtures/TERC_Urzedowy_2025-11-18.xml",
+    ))
+    .unwrap();
+    let mut reader = Reader::from_reader(xml.as_bytes());
+    reader.config_mut().expand_empty_elements = true;
+    let dict = build_dictionaries(reader).unwrap();
+    let mut reader = Reader::from_reader(xml.as_bytes());
+    reader.config_mut().expand_empty_elements = true;
+    let batch = AddressParser2021::new(reader, 100, dict, Arc::new(teryt.mapping))
+        .next()
+        .unwrap()
+        .unwrap();
+    assert_eq!(batch.num_rows(), 3);
+    let house_number = batch
+        .column_by_name("numer_porzadkowy")
+        .unwrap()
+        .as_string::<i32>();
+    assert!(house_number.is_null(0));
+    assert_eq!(house_number.value(1), "1A");
+}
+
+#[test]
+fn test_second_position_is_ignored() {
+    use arrow::array::AsArray;
+    use arrow::datatypes::Float64Type;
+
+    let xml = std::fs::read_to_string("fixtures/sample_model2021.xml")
+        .unwrap()
+        .replacen(
+            "<gml:pos>238651.83 519741.27</gml:pos>",
+            "<gml:pos>238651.83 519741.27</gml:pos>\n<gml:pos>1.0 2.0</gml:pos>",
+            1,
+        );
+    let teryt = crate::terc::get_terc_mapping(&std::path::PathBuf::from(
+        "fixtures/TERC_Urzedowy_2025-11-18.xml",
+    ))
+    .unwrap();
+    let mut reader = Reader::from_reader(xml.as_bytes());
+    reader.config_mut().expand_empty_elements = true;
+    let dict = build_dictionaries(reader).unwrap();
+    let mut reader = Reader::from_reader(xml.as_bytes());
+    reader.config_mut().expand_empty_elements = true;
+    let batch = AddressParser2021::new(reader, 100, dict, Arc::new(teryt.mapping))
+        .next()
+        .unwrap()
+        .unwrap();
+    assert_eq!(batch.num_rows(), 3);
+    let x = batch
+        .column_by_name("x_epsg_2180")
+        .unwrap()
+        .as_primitive::<Float64Type>();
+    let y = batch
+        .column_by_name("y_epsg_2180")
+        .unwrap()
+        .as_primitive::<Float64Type>();
+    assert_eq!(x.len(), 3);
+    assert_eq!(x.value(0), 238651.83);
+    assert_eq!(y.value(0), 519741.27);
+    assert_eq!(x.value(1), 216691.39);
+}
+
+#[test]
+fn test_normalize_whitespace() {
+    use arrow::array::AsArray;
+
+    // city from the dictionary and house number from the address
+    let xml = std::fs::read_to_string("fixtures/sample_model2021.xml")
+        .unwrap()
+        .replacen(
+            "<prgad:nazwa>Żubrów</prgad:nazwa>",
+            "<prgad:nazwa>Żubrów\u{a0} Dolny</prgad:nazwa>",
+            1,
+        )
+        .replacen(
+            "<prgad:numerPorzadkowy>21A</prgad:numerPorzadkowy>",
+            "<prgad:numerPorzadkowy>21  A</prgad:numerPorzadkowy>",
+            1,
+        );
+    let teryt = crate::terc::get_terc_mapping(&std::path::PathBuf::from(
+        "fixtures/TERC_Urzedowy_2025-11-18.xml",
+    ))
+    .unwrap();
+    let teryt = Arc::new(teryt.mapping);
+    let parse = |normalize| {
+        let mut reader = Reader::from_reader(xml.as_bytes());
+        reader.config_mut().expand_empty_elements = true;
+        let dict = build_dictionaries(reader).unwrap();
+        let mut reader = Reader::from_reader(xml.as_bytes());
+        reader.config_mut().expand_empty_elements = true;
+        let batches: Vec<arrow::array::RecordBatch> =
+            AddressParser2021::new(reader, 100, dict, teryt.clone())
+                .with_normalize_whitespace(normalize)
+                .collect::<Result<_, _>>()
+                .expect("Failed to parse addresses.");
+        let values = |name: &str| {
+            batches[0]
+                .column_by_name(name)
+                .unwrap()
+                .as_string::<i32>()
+                .iter()
+                .flatten()
+                .map(str::to_string)
+                .collect::<Vec<_>>()
+        };
+        (values("miejscowosc"), values("numer_porzadkowy"))
+    };
+    let (cities, house_numbers) = parse(false);
+    assert!(cities.contains(&"Żubrów\u{a0} Dolny".to_string()));
+    assert!(house_numbers.contains(&"21  A".to_string()));
+    let (cities, house_numbers) = parse(true);
+    assert!(cities.contains(&"Żubrów Dolny".to_string()));
+    assert!(!cities.iter().any(|c| c.contains('\u{a0}')));
+    assert!(house_numbers.contains(&"21 A".to_string()));
+}
+
+#[test]
+fn test_join_audit() {
+    let xml = std::fs::read_to_string("fixtures/sample_model2021.xml")
+        .unwrap()
+        .replacen(
+            "<prgad:TERYTGminy>0807043</prgad:TERYTGminy>",
+            "<prgad:TERYTGminy>0899997</prgad:TERYTGminy>",
+            1,
+        )
+        .replacen(
+            "<prgad:rodzaj>96</prgad:rodzaj>",
+            "<prgad:rodzaj>42</prgad:rodzaj>",
+            1,
+        );
+    let mut reader = Reader::from_reader(xml.as_bytes());
+    reader.config_mut().expand_empty_elements = true;
+    let dict = build_dictionaries(reader).unwrap();
+    let teryt = crate::terc::get_terc_mapping(&std::path::PathBuf::from(
+        "fixtures/TERC_Urzedowy_2025-11-18.xml",
+    ))
+    .unwrap();
+    let output = tempfile::NamedTempFile::new().unwrap();
+    let audit = JoinAudit::to_file(output.path());
+    let mut reader = Reader::from_reader(xml.as_bytes());
+    reader.config_mut().expand_empty_elements = true;
+    let batches: Vec<RecordBatch> =
+        AddressParser2021::new(reader, 100, dict, Arc::new(teryt.mapping))
+            .with_join_audit(Some(audit.clone()))
+            .collect::<Result<_, _>>()
+            .expect("Failed to parse addresses.");
+    // the address in the missing municipality is skipped
+    assert_eq!(batches[0].num_rows(), 2);
+    assert_eq!(
+        audit.records(),
+        vec![
+            JoinAuditRecord {
+                problem: JoinProblem::MunicipalityNotInTerc,
+                municipality_teryt_id: "0899997".to_string(),
+                city_teryt_id: None,
+                city: None,
+                city_kind_code: None,
+                addresses: 1,
+            },
+            JoinAuditRecord {
+                problem: JoinProblem::UnknownCityKind,
+                municipality_teryt_id: "0805043".to_string(),
+                city_teryt_id: Some("0935682".to_string()),
+                city: Some("Rzepin".to_string()),
+                city_kind_code: Some("42".to_string()),
+                addresses: 1,
+            },
+        ]
+    );
+    assert_eq!(audit.write().unwrap(), 2);
+    let written = std::fs::read_to_string(output.path()).unwrap();
+    let lines: Vec<&str> = written.lines().collect();
+    assert_eq!(
+        lines[0],
+        r#"{"problem":"municipality_not_in_terc","municipality_teryt_id":"0899997","city_teryt_id":null,"city":null,"city_kind_code":null,"addresses":1}"#
+    );
+    assert!(lines[1].starts_with(r#"{"problem":"unknown_city_kind","#));
+}
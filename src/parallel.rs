@@ -0,0 +1,351 @@
+//! Experimental parsing of one uncompressed file on multiple threads.
+//!
+//! The file is split into byte ranges that start at an opening address tag.
+//! Each range is parsed by its own parser (sharing the dictionaries built up
+//! front) and the batches are returned in file order.
+
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom, Take};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::mpsc::{Receiver, channel};
+use std::thread::JoinHandle;
+
+use anyhow::Context;
+use arrow::array::{RecordBatch, RecordBatchReader};
+use arrow::datatypes::SchemaRef;
+use arrow::error::ArrowError;
+use quick_xml::Reader;
+
+use crate::CRS;
+use crate::JoinAudit;
+use crate::StreetTypes;
+use crate::common::{ErrorSink, RecordFilter, SCHEMA_CSV, UntrimmedColumns, ValidationReport};
+
+/// Approximate size of the byte range parsed by one thread at a time.
+pub(crate) const CHUNK_SIZE: u64 = 64 * 1024 * 1024;
+
+/// XML reader over one byte range of a file.
+pub(crate) type ChunkReader = Reader<BufReader<Take<File>>>;
+
+type BatchIterator = Box<dyn Iterator<Item = Result<RecordBatch, ArrowError>>>;
+
+/// Settings given to the parser of every range.
+#[derive(Clone, Default)]
+pub(crate) struct ParserSettings {
+    pub(crate) errors: ErrorSink,
+    pub(crate) filter: RecordFilter,
+    pub(crate) report: Option<ValidationReport>,
+    /// Prefix of component links (schema 2012 only).
+    pub(crate) id_prefix: Option<String>,
+    /// Write statuses as is (schema 2012 only).
+    pub(crate) status_raw: bool,
+    /// Leave longitude/latitude null.
+    pub(crate) skip_reprojection: bool,
+    /// CRS of the input coordinates.
+    pub(crate) input_crs: CRS,
+    /// Print unknown tags after every batch.
+    pub(crate) verbose_unknown_tags: bool,
+    /// Return an error at the first unknown tag.
+    pub(crate) fail_on_unknown_tag: bool,
+    /// Collapse whitespace in text columns.
+    pub(crate) normalize_whitespace: bool,
+    /// Columns written without trimming.
+    pub(crate) untrimmed: UntrimmedColumns,
+    /// Street types in street names (schema 2021 only).
+    pub(crate) street_types: Option<Arc<StreetTypes>>,
+    /// Audit of joins with the dictionaries (schema 2021 only).
+    pub(crate) join_audit: Option<JoinAudit>,
+}
+
+type MakeParser = dyn Fn(ChunkReader, u64, ParserSettings) -> BatchIterator + Send + Sync;
+
+/// Returns position of the first `<tag` in `data` that is followed by
+/// whitespace, `>` or `/` (so that longer tag names do not match).
+fn find_start_tag(data: &[u8], tag: &[u8]) -> Option<usize> {
+    let pattern_len = tag.len() + 1;
+    data.windows(pattern_len + 1).position(|w| {
+        w[0] == b'<'
+            && &w[1..pattern_len] == tag
+            && matches!(w[pattern_len], b' ' | b'\t' | b'\r' | b'\n' | b'>' | b'/')
+    })
+}
+
+/// Returns position of the first opening `tag` at or after byte `from`.
+fn find_start_tag_from(file: &mut File, from: u64, tag: &[u8]) -> std::io::Result<Option<u64>> {
+    file.seek(SeekFrom::Start(from))?;
+    let mut window: Vec<u8> = Vec::new();
+    let mut window_start = from;
+    let mut block = vec![0; 1024 * 1024];
+    loop {
+        let n = file.read(&mut block)?;
+        if n == 0 {
+            return Ok(None);
+        }
+        window.extend_from_slice(&block[..n]);
+        if let Some(pos) = find_start_tag(&window, tag) {
+            return Ok(Some(window_start + pos as u64));
+        }
+        // keep the tail that could be the beginning of a match
+        let keep = (tag.len() + 1).min(window.len());
+        let consumed = window.len() - keep;
+        window.drain(..consumed);
+        window_start += consumed as u64;
+    }
+}
+
+/// Splits the file into byte ranges of roughly `chunk_size` bytes. Every range
+/// except the first one starts at an opening `tag`, so no element with that
+/// tag is split between ranges.
+pub(crate) fn split_at_tag(
+    path: &Path,
+    tag: &[u8],
+    chunk_size: u64,
+) -> anyhow::Result<Vec<Range<u64>>> {
+    let mut file =
+        File::open(path).with_context(|| format!("Failed to open file: `{}`.", path.display()))?;
+    let file_size = file.metadata()?.len();
+    let mut boundaries = vec![0];
+    let mut target = chunk_size.max(1);
+    while target < file_size {
+        match find_start_tag_from(&mut file, target, tag)
+            .with_context(|| format!("Failed to read file: `{}`.", path.display()))?
+        {
+            Some(pos) => {
+                if pos > *boundaries.last().unwrap() {
+                    boundaries.push(pos);
+                }
+                target = pos + chunk_size.max(1);
+            }
+            None => break,
+        }
+    }
+    boundaries.push(file_size);
+    Ok(boundaries.windows(2).map(|w| w[0]..w[1]).collect())
+}
+
+struct Chunk {
+    receiver: Receiver<Result<RecordBatch, ArrowError>>,
+    handle: JoinHandle<()>,
+}
+
+/// Parses byte ranges of a file on up to `num_threads` threads at a time and
+/// returns their batches in file order.
+pub struct ParallelParser {
+    path: PathBuf,
+    pending: VecDeque<Range<u64>>,
+    running: VecDeque<Chunk>,
+    num_threads: usize,
+    make_parser: Arc<MakeParser>,
+    settings: ParserSettings,
+    /// Unknown tags of all ranges when no report is given, printed once at
+    /// the end instead of at the end of every range.
+    unknown_tags: ValidationReport,
+    /// Set once all ranges were parsed or an error was returned.
+    finished: bool,
+}
+
+impl ParallelParser {
+    /// `make_parser` builds a parser for the reader of one range, given the
+    /// position of its start in the file and the settings of this parser.
+    pub(crate) fn new(
+        path: &Path,
+        ranges: Vec<Range<u64>>,
+        num_threads: usize,
+        make_parser: Arc<MakeParser>,
+    ) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            pending: ranges.into(),
+            running: VecDeque::new(),
+            num_threads: num_threads.max(1),
+            make_parser,
+            settings: ParserSettings::default(),
+            unknown_tags: ValidationReport::default(),
+            finished: false,
+        }
+    }
+
+    /// Report addresses that could not be parsed to `errors`.
+    /// By default they are printed as warnings.
+    pub fn with_error_sink(mut self, errors: ErrorSink) -> Self {
+        self.settings.errors = errors;
+        self
+    }
+
+    /// Only output addresses accepted by `filter`.
+    pub fn with_filter(mut self, filter: RecordFilter) -> Self {
+        self.settings.filter = filter;
+        self
+    }
+
+    /// Add counts of addresses, component references and unknown tags to
+    /// `report` if one is given (unknown tags are then not printed).
+    pub fn with_validation_report(mut self, report: Option<ValidationReport>) -> Self {
+        self.settings.report = report;
+        self
+    }
+
+    /// Prefix of `prg-ad:komponent` links (schema 2012 only), see
+    /// `AddressParser2012::with_id_prefix`.
+    pub fn with_id_prefix(mut self, prefix: Option<String>) -> Self {
+        self.settings.id_prefix = prefix;
+        self
+    }
+
+    /// Write `prg-ad:status` as is (schema 2012 only), see
+    /// `AddressParser2012::with_status_raw`.
+    pub fn with_status_raw(mut self, status_raw: bool) -> Self {
+        self.settings.status_raw = status_raw;
+        self
+    }
+
+    /// Compute longitude/latitude (default), see
+    /// `AddressParser2012::with_reprojection`.
+    pub fn with_reprojection(mut self, reproject: bool) -> Self {
+        self.settings.skip_reprojection = !reproject;
+        self
+    }
+
+    /// CRS of the input coordinates, see `AddressParser2012::with_input_crs`.
+    pub fn with_input_crs(mut self, input_crs: CRS) -> Self {
+        self.settings.input_crs = input_crs;
+        self
+    }
+
+    /// Print unknown tags after every batch of a range, see
+    /// `AddressParser2012::with_verbose_unknown_tags`. By default they are
+    /// printed once, after the last range.
+    pub fn with_verbose_unknown_tags(mut self, verbose: bool) -> Self {
+        self.settings.verbose_unknown_tags = verbose;
+        self
+    }
+
+    /// Return an error at the first unknown tag, see
+    /// `AddressParser2012::with_fail_on_unknown_tag`.
+    pub fn with_fail_on_unknown_tag(mut self, fail: bool) -> Self {
+        self.settings.fail_on_unknown_tag = fail;
+        self
+    }
+
+    /// Collapse whitespace in text columns, see
+    /// `AddressParser2012::with_normalize_whitespace`.
+    pub fn with_normalize_whitespace(mut self, normalize: bool) -> Self {
+        self.settings.normalize_whitespace = normalize;
+        self
+    }
+
+    /// Columns written without trimming, see
+    /// `AddressParser2012::with_untrimmed_columns`.
+    pub fn with_untrimmed_columns(mut self, untrimmed: UntrimmedColumns) -> Self {
+        self.settings.untrimmed = untrimmed;
+        self
+    }
+
+    /// Street types in street names (schema 2021 only), see
+    /// `AddressParser2021::with_street_types`.
+    pub fn with_street_types(mut self, street_types: Option<Arc<StreetTypes>>) -> Self {
+        self.settings.street_types = street_types;
+        self
+    }
+
+    /// Audit of joins with the dictionaries (schema 2021 only), see
+    /// `AddressParser2021::with_join_audit`.
+    pub fn with_join_audit(mut self, audit: Option<JoinAudit>) -> Self {
+        self.settings.join_audit = audit;
+        self
+    }
+
+    /// Whether unknown tags of the ranges are collected into `unknown_tags`.
+    fn collects_unknown_tags(&self) -> bool {
+        self.settings.report.is_none() && !self.settings.verbose_unknown_tags
+    }
+
+    fn spawn_chunks(&mut self) -> Result<(), ArrowError> {
+        while self.running.len() < self.num_threads {
+            let Some(range) = self.pending.pop_front() else {
+                break;
+            };
+            let mut file = File::open(&self.path)?;
+            file.seek(SeekFrom::Start(range.start))?;
+            let mut reader =
+                Reader::from_reader(BufReader::new(file.take(range.end - range.start)));
+            reader.config_mut().expand_empty_elements = true;
+            // parents of the first address were opened in the previous range
+            reader.config_mut().allow_unmatched_ends = true;
+            let make_parser = self.make_parser.clone();
+            let mut settings = self.settings.clone();
+            if self.collects_unknown_tags() {
+                settings.report = Some(self.unknown_tags.clone());
+            }
+            let (sender, receiver) = channel();
+            let handle = std::thread::spawn(move || {
+                for batch in make_parser(reader, range.start, settings) {
+                    if sender.send(batch).is_err() {
+                        break; // the parser was dropped
+                    }
+                }
+            });
+            self.running.push_back(Chunk { receiver, handle });
+        }
+        Ok(())
+    }
+}
+
+impl Iterator for ParallelParser {
+    type Item = Result<RecordBatch, ArrowError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+        loop {
+            if let Err(e) = self.spawn_chunks() {
+                self.finished = true;
+                return Some(Err(e));
+            }
+            let Some(chunk) = self.running.front() else {
+                self.finished = true;
+                if self.collects_unknown_tags() {
+                    self.unknown_tags.warn_about_unknown_tags();
+                }
+                return None;
+            };
+            match chunk.receiver.recv() {
+                Ok(Ok(batch)) if batch.num_rows() == 0 => (),
+                Ok(Ok(batch)) => return Some(Ok(batch)),
+                Ok(Err(e)) => {
+                    self.finished = true;
+                    return Some(Err(e));
+                }
+                // the range was parsed completely
+                Err(_) => {
+                    let chunk = self.running.pop_front().unwrap();
+                    if chunk.handle.join().is_err() {
+                        self.finished = true;
+                        return Some(Err(ArrowError::ComputeError(
+                            "Parsing thread panicked.".to_string(),
+                        )));
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl RecordBatchReader for ParallelParser {
+    fn schema(&self) -> SchemaRef {
+        SCHEMA_CSV.clone()
+    }
+}
+
+#[test]
+fn test_find_start_tag() {
+    let tag = b"prg-ad:PRG_PunktAdresowy";
+    let data = b"<a></prg-ad:PRG_PunktAdresowy><prg-ad:PRG_PunktAdresowyX/><prg-ad:PRG_PunktAdresowy gml:id=\"1\">";
+    assert_eq!(find_start_tag(data, tag), Some(58));
+    assert_eq!(find_start_tag(b"<prg-ad:PRG_PunktAdresowy", tag), None);
+    assert_eq!(find_start_tag(b"<prg-ad:PRG_PunktAdresowy>", tag), Some(0));
+}
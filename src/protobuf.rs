@@ -0,0 +1,223 @@
+//! Protobuf output (`--output-format pbf`, `protobuf` feature): one
+//! [`Address`] message per address, each preceded by its length as a varint,
+//! for services that read length-delimited messages instead of arrow or text.
+//! The message is defined in `proto/address.proto`; only the columns of the
+//! base schema are written, columns added by other options are left out.
+
+use std::io::Write;
+
+use anyhow::Context;
+use arrow::array::{Array, AsArray, PrimitiveArray, RecordBatch, StringArray};
+use arrow::datatypes::{ArrowPrimitiveType, Date32Type, Float64Type, TimestampMillisecondType};
+use prost::Message;
+
+/// Address as defined in `proto/address.proto`.
+#[derive(Clone, PartialEq, Message)]
+pub struct Address {
+    #[prost(string, tag = "1")]
+    pub przestrzen_nazw: String,
+    #[prost(string, tag = "2")]
+    pub lokalny_id: String,
+    /// Milliseconds since 1970-01-01T00:00:00Z.
+    #[prost(int64, tag = "3")]
+    pub wersja_id: i64,
+    #[prost(int64, optional, tag = "4")]
+    pub poczatek_wersji_obiektu: Option<i64>,
+    /// Days since 1970-01-01.
+    #[prost(int32, optional, tag = "5")]
+    pub wazny_od_lub_data_nadania: Option<i32>,
+    #[prost(int32, optional, tag = "6")]
+    pub wazny_do: Option<i32>,
+    #[prost(string, optional, tag = "7")]
+    pub teryt_wojewodztwo: Option<String>,
+    #[prost(string, tag = "8")]
+    pub wojewodztwo: String,
+    #[prost(string, optional, tag = "9")]
+    pub teryt_powiat: Option<String>,
+    #[prost(string, tag = "10")]
+    pub powiat: String,
+    #[prost(string, optional, tag = "11")]
+    pub teryt_gmina: Option<String>,
+    #[prost(string, tag = "12")]
+    pub gmina: String,
+    #[prost(string, optional, tag = "13")]
+    pub teryt_miejscowosc: Option<String>,
+    #[prost(string, tag = "14")]
+    pub miejscowosc: String,
+    #[prost(string, optional, tag = "15")]
+    pub czesc_miejscowosci: Option<String>,
+    #[prost(string, optional, tag = "16")]
+    pub teryt_ulica: Option<String>,
+    #[prost(string, optional, tag = "17")]
+    pub ulica: Option<String>,
+    #[prost(string, optional, tag = "18")]
+    pub numer_porzadkowy: Option<String>,
+    #[prost(string, optional, tag = "19")]
+    pub kod_pocztowy: Option<String>,
+    #[prost(string, optional, tag = "20")]
+    pub status: Option<String>,
+    #[prost(string, optional, tag = "21")]
+    pub id_budynku: Option<String>,
+    #[prost(double, optional, tag = "22")]
+    pub dlugosc_geograficzna: Option<f64>,
+    #[prost(double, optional, tag = "23")]
+    pub szerokosc_geograficzna: Option<f64>,
+    #[prost(double, optional, tag = "24")]
+    pub x_epsg_2180: Option<f64>,
+    #[prost(double, optional, tag = "25")]
+    pub y_epsg_2180: Option<f64>,
+}
+
+/// Columns of a batch with the canonical columns, read by name.
+struct Columns<'a> {
+    batch: &'a RecordBatch,
+}
+
+impl<'a> Columns<'a> {
+    fn column(&self, name: &str) -> anyhow::Result<&'a dyn Array> {
+        self.batch
+            .column_by_name(name)
+            .map(|c| c.as_ref())
+            .with_context(|| format!("Batch is missing column `{}`.", name))
+    }
+
+    fn string(&self, name: &str) -> anyhow::Result<&'a StringArray> {
+        self.column(name)?
+            .as_string_opt::<i32>()
+            .with_context(|| format!("Column `{}` is not a string.", name))
+    }
+
+    fn primitive<T: ArrowPrimitiveType>(
+        &self,
+        name: &str,
+    ) -> anyhow::Result<&'a PrimitiveArray<T>> {
+        self.column(name)?
+            .as_primitive_opt::<T>()
+            .with_context(|| format!("Column `{}` is not of type {}.", name, T::DATA_TYPE))
+    }
+}
+
+fn optional_string(array: &StringArray, row: usize) -> Option<String> {
+    array.is_valid(row).then(|| array.value(row).to_string())
+}
+
+fn optional<T: ArrowPrimitiveType>(array: &PrimitiveArray<T>, row: usize) -> Option<T::Native> {
+    array.is_valid(row).then(|| array.value(row))
+}
+
+/// Addresses of a batch with the canonical columns as messages.
+pub fn batch_to_addresses(batch: &RecordBatch) -> anyhow::Result<Vec<Address>> {
+    let columns = Columns { batch };
+    let string = |name| columns.string(name);
+    let timestamp = |name| columns.primitive::<TimestampMillisecondType>(name);
+    let date = |name| columns.primitive::<Date32Type>(name);
+    let float = |name| columns.primitive::<Float64Type>(name);
+    let (przestrzen_nazw, lokalny_id) = (string("przestrzen_nazw")?, string("lokalny_id")?);
+    let (wersja_id, poczatek_wersji_obiektu) = (
+        timestamp("wersja_id")?,
+        timestamp("poczatek_wersji_obiektu")?,
+    );
+    let (wazny_od, wazny_do) = (date("wazny_od_lub_data_nadania")?, date("wazny_do")?);
+    let (teryt_wojewodztwo, wojewodztwo) = (string("teryt_wojewodztwo")?, string("wojewodztwo")?);
+    let (teryt_powiat, powiat) = (string("teryt_powiat")?, string("powiat")?);
+    let (teryt_gmina, gmina) = (string("teryt_gmina")?, string("gmina")?);
+    let (teryt_miejscowosc, miejscowosc) = (string("teryt_miejscowosc")?, string("miejscowosc")?);
+    let czesc_miejscowosci = string("czesc_miejscowosci")?;
+    let (teryt_ulica, ulica) = (string("teryt_ulica")?, string("ulica")?);
+    let (numer_porzadkowy, kod_pocztowy) = (string("numer_porzadkowy")?, string("kod_pocztowy")?);
+    let (status, id_budynku) = (string("status")?, string("id_budynku")?);
+    let (dlugosc, szerokosc) = (
+        float("dlugosc_geograficzna")?,
+        float("szerokosc_geograficzna")?,
+    );
+    let (x, y) = (float("x_epsg_2180")?, float("y_epsg_2180")?);
+
+    Ok((0..batch.num_rows())
+        .map(|row| Address {
+            przestrzen_nazw: przestrzen_nazw.value(row).to_string(),
+            lokalny_id: lokalny_id.value(row).to_string(),
+            wersja_id: wersja_id.value(row),
+            poczatek_wersji_obiektu: optional(poczatek_wersji_obiektu, row),
+            wazny_od_lub_data_nadania: optional(wazny_od, row),
+            wazny_do: optional(wazny_do, row),
+            teryt_wojewodztwo: optional_string(teryt_wojewodztwo, row),
+            wojewodztwo: wojewodztwo.value(row).to_string(),
+            teryt_powiat: optional_string(teryt_powiat, row),
+            powiat: powiat.value(row).to_string(),
+            teryt_gmina: optional_string(teryt_gmina, row),
+            gmina: gmina.value(row).to_string(),
+            teryt_miejscowosc: optional_string(teryt_miejscowosc, row),
+            miejscowosc: miejscowosc.value(row).to_string(),
+            czesc_miejscowosci: optional_string(czesc_miejscowosci, row),
+            teryt_ulica: optional_string(teryt_ulica, row),
+            ulica: optional_string(ulica, row),
+            numer_porzadkowy: optional_string(numer_porzadkowy, row),
+            kod_pocztowy: optional_string(kod_pocztowy, row),
+            status: optional_string(status, row),
+            id_budynku: optional_string(id_budynku, row),
+            dlugosc_geograficzna: optional(dlugosc, row),
+            szerokosc_geograficzna: optional(szerokosc, row),
+            x_epsg_2180: optional(x, row),
+            y_epsg_2180: optional(y, row),
+        })
+        .collect())
+}
+
+pub struct ProtobufWriter<W: Write> {
+    inner: W,
+    /// Encoded messages of the current batch.
+    buffer: Vec<u8>,
+}
+
+impl<W: Write> ProtobufWriter<W> {
+    pub fn new(inner: W) -> Self {
+        Self {
+            inner,
+            buffer: Vec::new(),
+        }
+    }
+
+    /// Writes the addresses of a batch with the canonical columns.
+    pub fn write(&mut self, batch: &RecordBatch) -> anyhow::Result<()> {
+        self.buffer.clear();
+        for address in batch_to_addresses(batch)? {
+            address.encode_length_delimited(&mut self.buffer)?;
+        }
+        self.inner.write_all(&self.buffer)?;
+        Ok(())
+    }
+
+    /// Flushes and returns the inner writer.
+    pub fn finish(mut self) -> anyhow::Result<W> {
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
+
+#[test]
+fn test_protobuf_writer() {
+    let path = std::path::PathBuf::from("fixtures/sample_model2012.xml");
+    let mut parser = crate::get_address_parser_2012_uncompressed(&path, &10).unwrap();
+    let batch = parser.next().unwrap().unwrap();
+    let mut writer = ProtobufWriter::new(Vec::new());
+    writer.write(&batch).unwrap();
+    let bytes = writer.finish().unwrap();
+
+    // the output can be read back message by message
+    let mut remaining = bytes.as_slice();
+    let mut addresses = Vec::new();
+    while !remaining.is_empty() {
+        addresses.push(Address::decode_length_delimited(&mut remaining).unwrap());
+    }
+    assert_eq!(addresses, batch_to_addresses(&batch).unwrap());
+    assert_eq!(addresses.len(), 2);
+    let address = &addresses[0];
+    assert_eq!(address.przestrzen_nazw, "PL.PZGIK.200");
+    assert_eq!(address.lokalny_id, "fd9c9319-0a6a-44b4-972a-1e6c4ec0d4ca");
+    assert_eq!(address.miejscowosc, "Konotop");
+    assert_eq!(address.numer_porzadkowy.as_deref(), Some("2"));
+    assert_eq!(address.x_epsg_2180, Some(287772.37));
+    assert_eq!(address.y_epsg_2180, Some(456005.140000001));
+    assert!(address.dlugosc_geograficzna.is_some());
+    assert_eq!(address.wazny_do, None);
+}
@@ -0,0 +1,300 @@
+//! Smoke test of a build (`--self-test`), for installations without the test
+//! suite. The samples of both schemas and the TERC catalog of `fixtures/` are
+//! embedded in the program, parsed as in a conversion and checked against
+//! known values.
+
+use std::fmt::Debug;
+use std::io::Cursor;
+use std::sync::Arc;
+
+use anyhow::Context;
+use arrow::array::{AsArray, RecordBatch};
+use arrow::compute::concat_batches;
+use arrow::datatypes::Float64Type;
+use quick_xml::Reader;
+
+use crate::KomponentType;
+use crate::common::SCHEMA_CSV;
+use crate::model2012::{self, AddressParser2012};
+use crate::model2021::{self, AddressParser2021};
+use crate::terc::{TercDictionary, get_terc_mapping_from_zip};
+
+const SAMPLE_2012: &str = include_str!("../fixtures/sample_model2012.xml");
+const SAMPLE_2021: &str = include_str!("../fixtures/sample_model2021.xml");
+const TERC_ZIP: &[u8] = include_bytes!("../fixtures/TERC_Urzedowy_2025-11-18.zip");
+
+/// Outcome of one check of `run_self_test`.
+#[derive(Debug)]
+pub struct SelfTestCheck {
+    pub name: &'static str,
+    /// Why the check failed, `None` if it passed.
+    pub error: Option<String>,
+}
+
+/// Known values of an address of the samples.
+struct ExpectedAddress {
+    lokalny_id: &'static str,
+    numer_porzadkowy: &'static str,
+    miejscowosc: &'static str,
+    gmina: &'static str,
+    x_epsg_2180: f64,
+    y_epsg_2180: f64,
+    lon: f64,
+    lat: f64,
+}
+
+const EXPECTED_2012: [ExpectedAddress; 2] = [
+    ExpectedAddress {
+        lokalny_id: "fd9c9319-0a6a-44b4-972a-1e6c4ec0d4ca",
+        numer_porzadkowy: "2",
+        miejscowosc: "Konotop",
+        gmina: "Kolsko",
+        x_epsg_2180: 287772.37,
+        y_epsg_2180: 456005.140000001,
+        lon: 15.9121240698886,
+        lat: 51.92977532639213,
+    },
+    ExpectedAddress {
+        lokalny_id: "5baa8bef-75ef-4241-a2fe-9d4137845693",
+        numer_porzadkowy: "1",
+        miejscowosc: "Konotop",
+        gmina: "Kolsko",
+        x_epsg_2180: 287751.0102,
+        y_epsg_2180: 456027.7794,
+        lon: 15.911799807186908,
+        lat: 51.92997049675426,
+    },
+];
+
+const EXPECTED_2021: [ExpectedAddress; 3] = [
+    ExpectedAddress {
+        lokalny_id: "7343b2d2-c2ac-4951-ae9a-fe1932ffecfb",
+        numer_porzadkowy: "21A",
+        miejscowosc: "Żubrów",
+        gmina: "Sulęcin",
+        x_epsg_2180: 238651.83,
+        y_epsg_2180: 519741.27,
+        lon: 15.149797186509767,
+        lat: 52.48080576032958,
+    },
+    ExpectedAddress {
+        lokalny_id: "07bcb481-4975-4c77-ab58-c8e4b9e05362",
+        numer_porzadkowy: "1A",
+        miejscowosc: "Rzepin",
+        gmina: "Rzepin",
+        x_epsg_2180: 216691.39,
+        y_epsg_2180: 505645.69,
+        lon: 14.839103470789498,
+        lat: 52.3434219342925,
+    },
+    ExpectedAddress {
+        lokalny_id: "e4ed4971-15f6-473d-b9a4-e9e12e602f6e",
+        numer_porzadkowy: "2A",
+        miejscowosc: "Lubniewice",
+        gmina: "Lubniewice",
+        x_epsg_2180: 245250.11,
+        y_epsg_2180: 522957.46,
+        lon: 15.24431221852159,
+        lat: 52.51278706040695,
+    },
+];
+
+type Check = fn() -> anyhow::Result<()>;
+
+/// Runs all checks, also the ones after a failed check.
+pub fn run_self_test() -> Vec<SelfTestCheck> {
+    let checks: [(&'static str, Check); 4] = [
+        ("schema 2012 dictionaries", check_dictionaries_2012),
+        ("schema 2012 addresses", check_addresses_2012),
+        ("TERC catalog", check_terc),
+        ("schema 2021 addresses", check_addresses_2021),
+    ];
+    checks
+        .into_iter()
+        .map(|(name, check)| SelfTestCheck {
+            name,
+            error: check().err().map(|e| format!("{:#}", e)),
+        })
+        .collect()
+}
+
+fn expect_eq<T: PartialEq + Debug>(what: &str, actual: T, expected: T) -> anyhow::Result<()> {
+    if actual != expected {
+        anyhow::bail!("{}: expected {:?}, got {:?}", what, expected, actual);
+    }
+    Ok(())
+}
+
+fn xml_reader(xml: &str) -> Reader<&[u8]> {
+    let mut reader = Reader::from_reader(xml.as_bytes());
+    reader.config_mut().expand_empty_elements = true;
+    reader
+}
+
+fn terc() -> anyhow::Result<TercDictionary> {
+    get_terc_mapping_from_zip(Cursor::new(TERC_ZIP)).context("Could not read the TERC catalog")
+}
+
+fn check_dictionaries_2012() -> anyhow::Result<()> {
+    let dict = model2012::build_dictionaries(xml_reader(SAMPLE_2012))?;
+    expect_eq("number of components", dict.len(), 6)?;
+    let components = [
+        (
+            "PL.PZGIK.200_366267",
+            KomponentType::Voivodeship,
+            "lubuskie",
+            "08",
+        ),
+        (
+            "PL.PZGIK.200_366439",
+            KomponentType::County,
+            "powiat nowosolski",
+            "0804",
+        ),
+        (
+            "PL.PZGIK.200_370095",
+            KomponentType::Municipality,
+            "Kolsko",
+            "0804032",
+        ),
+        (
+            "PL.ZIPIN.4404.EMUiA_0910140",
+            KomponentType::City,
+            "Konotop",
+            "0910140",
+        ),
+        (
+            "PL.ZIPIN.4404.EMUiA_95d1f98c-7a1e-4726-a17d-a3c7bdaec79e",
+            KomponentType::Street,
+            "Podgórna",
+            "16742",
+        ),
+    ];
+    for (id, typ, name, teryt_id) in components {
+        let component = dict
+            .get(id)
+            .with_context(|| format!("component `{}` is missing", id))?;
+        expect_eq(&format!("type of `{}`", id), component.typ(), &typ)?;
+        expect_eq(&format!("name of `{}`", id), component.name(), name)?;
+        expect_eq(
+            &format!("TERYT id of `{}`", id),
+            component.teryt_id(),
+            Some(teryt_id),
+        )?;
+    }
+    Ok(())
+}
+
+/// Checks the addresses of `batches` against `expected`, in order.
+fn check_addresses(batches: &[RecordBatch], expected: &[ExpectedAddress]) -> anyhow::Result<()> {
+    let batch = concat_batches(&SCHEMA_CSV, batches)?;
+    expect_eq("number of addresses", batch.num_rows(), expected.len())?;
+    let string = |name: &str| batch.column_by_name(name).unwrap().as_string::<i32>();
+    let float = |name: &str| {
+        batch
+            .column_by_name(name)
+            .unwrap()
+            .as_primitive::<Float64Type>()
+    };
+    for (row, address) in expected.iter().enumerate() {
+        let what = |name: &str| format!("`{}` of address {}", name, row + 1);
+        expect_eq(
+            &what("lokalny_id"),
+            string("lokalny_id").value(row),
+            address.lokalny_id,
+        )?;
+        expect_eq(
+            &what("numer_porzadkowy"),
+            string("numer_porzadkowy").value(row),
+            address.numer_porzadkowy,
+        )?;
+        expect_eq(
+            &what("miejscowosc"),
+            string("miejscowosc").value(row),
+            address.miejscowosc,
+        )?;
+        expect_eq(&what("gmina"), string("gmina").value(row), address.gmina)?;
+        expect_eq(
+            &what("x_epsg_2180"),
+            float("x_epsg_2180").value(row),
+            address.x_epsg_2180,
+        )?;
+        expect_eq(
+            &what("y_epsg_2180"),
+            float("y_epsg_2180").value(row),
+            address.y_epsg_2180,
+        )?;
+        // the reprojection may differ in the last digits between platforms
+        let lon = float("dlugosc_geograficzna").value(row);
+        let lat = float("szerokosc_geograficzna").value(row);
+        if (lon - address.lon).abs() > 1e-9 || (lat - address.lat).abs() > 1e-9 {
+            anyhow::bail!(
+                "longitude/latitude of address {}: expected {} {}, got {} {}",
+                row + 1,
+                address.lon,
+                address.lat,
+                lon,
+                lat
+            );
+        }
+    }
+    Ok(())
+}
+
+fn check_addresses_2012() -> anyhow::Result<()> {
+    let dict = model2012::build_dictionaries(xml_reader(SAMPLE_2012))?;
+    let batches = AddressParser2012::new(xml_reader(SAMPLE_2012), 100, dict)
+        .collect::<Result<Vec<_>, _>>()?;
+    check_addresses(&batches, &EXPECTED_2012)
+}
+
+fn check_terc() -> anyhow::Result<()> {
+    let terc = terc()?;
+    expect_eq("catalog date", terc.catalog_date.as_str(), "2025-01-01")?;
+    let municipality = terc
+        .mapping
+        .get("0201011")
+        .context("municipality `0201011` is missing")?;
+    expect_eq(
+        "name of municipality `0201011`",
+        municipality.municipality_name.as_str(),
+        "Bolesławiec",
+    )?;
+    expect_eq(
+        "county of municipality `0201011`",
+        (
+            municipality.county_teryt_id.as_str(),
+            municipality.county_name.as_str(),
+        ),
+        ("0201", "bolesławiecki"),
+    )?;
+    expect_eq(
+        "voivodeship of municipality `0201011`",
+        (
+            municipality.voivodeship_teryt_id.as_str(),
+            municipality.voivodeship_name.as_str(),
+        ),
+        ("02", "dolnośląskie"),
+    )
+}
+
+fn check_addresses_2021() -> anyhow::Result<()> {
+    let dict = model2021::build_dictionaries(xml_reader(SAMPLE_2021))?;
+    let batches = AddressParser2021::new(
+        xml_reader(SAMPLE_2021),
+        100,
+        dict,
+        Arc::new(terc()?.mapping),
+    )
+    .collect::<Result<Vec<_>, _>>()?;
+    check_addresses(&batches, &EXPECTED_2021)
+}
+
+#[test]
+fn test_run_self_test() {
+    let checks = run_self_test();
+    assert_eq!(checks.len(), 4);
+    for check in checks {
+        assert_eq!(check.error, None, "{}", check.name);
+    }
+}
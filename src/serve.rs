@@ -0,0 +1,292 @@
+//! `--serve` (`http` feature): a long-running mode converting files on
+//! request over HTTP with the library `convert`, one request at a time.
+//! `POST /convert` takes the command line options as a JSON array and
+//! responds with the output file, the summary of the conversion is sent as
+//! JSON in the `X-Prg-Convert-Report` header (and as the body with
+//! `--validate-only`).
+
+use std::fs::File;
+use std::path::Path;
+
+use anyhow::Context;
+use clap::Parser;
+use serde::Serialize;
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use prg_convert::OutputFormat;
+use prg_convert::convert::{ConvertReport, convert};
+use prg_convert::progress;
+
+use crate::cli;
+
+/// Path of the conversion endpoint.
+const CONVERT_PATH: &str = "/convert";
+/// Header with the summary of the conversion as JSON.
+const REPORT_HEADER: &str = "X-Prg-Convert-Report";
+
+/// Summary of a conversion sent with the response.
+#[derive(Serialize)]
+struct ReportSummary {
+    total_rows: usize,
+    skipped_rows: usize,
+    null_geometry_rows: usize,
+    duration_seconds: f64,
+    terc_catalog_date: Option<String>,
+}
+
+impl From<&ConvertReport> for ReportSummary {
+    fn from(report: &ConvertReport) -> Self {
+        Self {
+            total_rows: report.total_rows,
+            skipped_rows: report.skipped_rows,
+            null_geometry_rows: report.null_geometry_rows,
+            duration_seconds: report.duration.as_secs_f64(),
+            terc_catalog_date: report.terc_catalog_date.clone(),
+        }
+    }
+}
+
+/// A request that was not converted: status code and message of the response.
+struct Failure {
+    status: u16,
+    message: String,
+}
+
+impl Failure {
+    fn new(status: u16, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            message: message.into(),
+        }
+    }
+}
+
+/// Listens on `address` and handles requests until the program is stopped.
+pub fn serve(address: &str) -> anyhow::Result<()> {
+    let server = Server::http(address)
+        .map_err(|e| anyhow::anyhow!(e))
+        .with_context(|| format!("Could not listen on `{}`.", address))?;
+    progress!(
+        "🌐 Listening on http://{}{} (POST a JSON array of options).",
+        server.server_addr(),
+        CONVERT_PATH
+    );
+    for request in server.incoming_requests() {
+        handle(request);
+    }
+    Ok(())
+}
+
+fn header(name: &str, value: &str) -> Option<Header> {
+    Header::from_bytes(name.as_bytes(), value.as_bytes()).ok()
+}
+
+fn content_type(output_format: OutputFormat) -> &'static str {
+    match output_format {
+        OutputFormat::CSV => "text/csv; charset=utf-8",
+        #[cfg(feature = "geoparquet")]
+        OutputFormat::GeoParquet | OutputFormat::Parquet => "application/vnd.apache.parquet",
+        OutputFormat::Gml => "application/gml+xml; charset=utf-8",
+        #[cfg(feature = "protobuf")]
+        OutputFormat::Protobuf => "application/x-protobuf",
+    }
+}
+
+/// Converts the request and responds with the output file, or with an error
+/// message as plain text.
+fn handle(mut request: Request) {
+    let method = request.method().clone();
+    let url = request.url().to_string();
+    let output_dir = match tempfile::tempdir() {
+        Ok(dir) => dir,
+        Err(e) => {
+            println!("Warning: could not create a temporary directory: {}", e);
+            return;
+        }
+    };
+    let output_path = output_dir.path().join("output");
+    let result = match convert_request(&mut request, &output_path) {
+        Ok((parsed_args, report)) => {
+            progress!(
+                "📨 {} {}: {} addresses in {:.1}s.",
+                method,
+                url,
+                report.total_rows,
+                report.duration.as_secs_f64()
+            );
+            let summary = serde_json::to_string(&ReportSummary::from(&report))
+                .expect("Could not serialize report.");
+            if parsed_args.validate_only {
+                let response = Response::from_string(summary.as_str())
+                    .with_header(header("Content-Type", "application/json").unwrap());
+                request.respond(response)
+            } else {
+                match File::open(&output_path) {
+                    Ok(file) => {
+                        let mut response = Response::from_file(file).with_header(
+                            header("Content-Type", content_type(parsed_args.output_format))
+                                .unwrap(),
+                        );
+                        if let Some(report_header) = header(REPORT_HEADER, &summary) {
+                            response.add_header(report_header);
+                        }
+                        request.respond(response)
+                    }
+                    Err(e) => request.respond(
+                        Response::from_string(format!("Could not read the output: {}", e))
+                            .with_status_code(500),
+                    ),
+                }
+            }
+        }
+        Err(failure) => {
+            println!(
+                "Warning: {} {} failed ({}): {}",
+                method,
+                url,
+                failure.status,
+                failure.message.trim_end()
+            );
+            request.respond(Response::from_string(failure.message).with_status_code(failure.status))
+        }
+    };
+    if let Err(e) = result {
+        println!("Warning: could not send the response: {}", e);
+    }
+}
+
+/// Parses the options in the body of `request` like the command line and
+/// converts the inputs to `output_path`.
+fn convert_request(
+    request: &mut Request,
+    output_path: &Path,
+) -> Result<(cli::ParsedArgs, ConvertReport), Failure> {
+    if request.url().split('?').next() != Some(CONVERT_PATH) {
+        return Err(Failure::new(
+            404,
+            format!("Not found, use `POST {}`.", CONVERT_PATH),
+        ));
+    }
+    if *request.method() != Method::Post {
+        return Err(Failure::new(
+            405,
+            format!("Method not allowed, use `POST {}`.", CONVERT_PATH),
+        ));
+    }
+    let options: Vec<String> = serde_json::from_reader(request.as_reader()).map_err(|e| {
+        Failure::new(
+            400,
+            format!(
+                "The body must be a JSON array of command line options: {}",
+                e
+            ),
+        )
+    })?;
+    let args = std::iter::once("prg_convert".into())
+        .chain(options)
+        .chain(["--output-path".into(), output_path.to_string_lossy().into()]);
+    let raw_args: cli::RawArgs = cli::RawArgs::try_parse_from(args)
+        .map_err(|e| Failure::new(400, e.render().to_string()))?;
+    let mut parsed_args: cli::ParsedArgs = raw_args
+        .with_env_fallbacks(|name| std::env::var(name).ok())
+        .and_then(TryInto::try_into)
+        .map_err(|e| Failure::new(400, format!("{:#}", e)))?;
+    let unsupported = [
+        ("--print-schema", parsed_args.print_schema),
+        ("--transform-only", parsed_args.transform_only.is_some()),
+        ("--download-data", parsed_args.download_data),
+        ("--rows-per-file", parsed_args.rows_per_file.is_some()),
+        ("--join-audit", parsed_args.join_audit.is_some()),
+        ("--errors-to", parsed_args.errors_to.is_some()),
+        ("--manifest", parsed_args.manifest_path.is_some()),
+        ("--checkpoint", parsed_args.checkpoint.is_some()),
+        ("--resume", parsed_args.resume),
+        ("--benchmark", parsed_args.benchmark),
+    ];
+    if let Some((option, _)) = unsupported.iter().find(|(_, used)| *used) {
+        return Err(Failure::new(
+            400,
+            format!("`{}` cannot be used with --serve.", option),
+        ));
+    }
+    let inputs = std::mem::take(&mut parsed_args.parsed_paths);
+    let report = convert(parsed_args.convert_options(inputs)).map_err(|e| {
+        let e = cli::with_option_flags(e);
+        Failure::new(500, format!("Conversion failed: {:#}", e))
+    })?;
+    Ok((parsed_args, report))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+    use std::net::{SocketAddr, TcpStream};
+
+    use super::*;
+
+    /// Sends a request and returns the whole response.
+    fn send(address: SocketAddr, method: &str, path: &str, body: &str) -> String {
+        let mut stream = TcpStream::connect(address).unwrap();
+        write!(
+            stream,
+            "{} {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\nContent-Length: {}\r\n\r\n{}",
+            method,
+            path,
+            body.len(),
+            body
+        )
+        .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    #[test]
+    fn test_serve_convert() {
+        let server = Server::http("127.0.0.1:0").unwrap();
+        let address = server.server_addr().to_ip().unwrap();
+        let requests = 5;
+        let server_thread = std::thread::spawn(move || {
+            for _ in 0..requests {
+                handle(server.recv().unwrap());
+            }
+        });
+
+        let response = send(
+            address,
+            "POST",
+            CONVERT_PATH,
+            r#"["--input-paths", "fixtures/sample_model2012.xml", "--schema-version", "2012", "--output-format", "csv"]"#,
+        );
+        assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+        assert!(response.contains("text/csv"), "{}", response);
+        assert!(
+            response.contains(r#"X-Prg-Convert-Report: {"total_rows":2,"skipped_rows":0"#),
+            "{}",
+            response
+        );
+        let (_, body) = response.split_once("\r\n\r\n").unwrap();
+        assert_eq!(body.lines().count(), 3);
+        assert!(body.starts_with("przestrzen_nazw,"));
+
+        let response = send(address, "POST", CONVERT_PATH, r#"["--no-such-option"]"#);
+        assert!(response.starts_with("HTTP/1.1 400"), "{}", response);
+        let response = send(
+            address,
+            "POST",
+            CONVERT_PATH,
+            r#"["--input-paths", "fixtures/sample_model2012.xml", "--schema-version", "2012", "--output-format", "csv", "--errors-to", "errors.txt"]"#,
+        );
+        assert!(response.starts_with("HTTP/1.1 400"), "{}", response);
+        assert!(
+            response.contains("`--errors-to` cannot be used"),
+            "{}",
+            response
+        );
+        let response = send(address, "POST", CONVERT_PATH, "not json");
+        assert!(response.starts_with("HTTP/1.1 400"), "{}", response);
+        let response = send(address, "GET", CONVERT_PATH, "");
+        assert!(response.starts_with("HTTP/1.1 405"), "{}", response);
+        server_thread.join().unwrap();
+    }
+}
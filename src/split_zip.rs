@@ -0,0 +1,475 @@
+//! Reading of ZIP archives split into volumes (`archive.z01`, `archive.z02`,
+//! ..., `archive.zip`), which the `zip` crate does not support.
+//!
+//! The volumes are copied one after another into a temporary file and the
+//! central directory is rewritten, so that offsets that were relative to the
+//! start of a volume point into the joined file and all entries are on disk 0.
+//! When only the first entries are needed, the volumes can also be read in
+//! order without joining them (`read_split_volumes`).
+
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use tempfile::TempPath;
+
+use crate::progress;
+
+const SPLIT_SIGNATURE: u32 = 0x08074b50;
+const EOCD_SIGNATURE: u32 = 0x06054b50;
+const ZIP64_EOCD_SIGNATURE: u32 = 0x06064b50;
+const ZIP64_LOCATOR_SIGNATURE: u32 = 0x07064b50;
+const CENTRAL_HEADER_SIGNATURE: u32 = 0x02014b50;
+const ZIP64_EXTRA_ID: u16 = 0x0001;
+const EOCD_SIZE: usize = 22;
+const ZIP64_LOCATOR_SIZE: usize = 20;
+const CENTRAL_HEADER_SIZE: usize = 46;
+
+/// End of central directory of the last volume. Disk numbers and the central
+/// directory offset are taken from the ZIP64 record when there is one.
+struct EndOfCentralDirectory {
+    /// Number of volumes.
+    disks: u32,
+    cd_disk: u32,
+    entries: u64,
+    cd_size: u64,
+    /// Offset of the central directory from the start of volume `cd_disk`.
+    cd_offset: u64,
+    comment: Vec<u8>,
+}
+
+fn u16_at(data: &[u8], pos: usize) -> u16 {
+    u16::from_le_bytes(data[pos..pos + 2].try_into().unwrap())
+}
+
+fn u32_at(data: &[u8], pos: usize) -> u32 {
+    u32::from_le_bytes(data[pos..pos + 4].try_into().unwrap())
+}
+
+fn u64_at(data: &[u8], pos: usize) -> u64 {
+    u64::from_le_bytes(data[pos..pos + 8].try_into().unwrap())
+}
+
+/// Reads the end of central directory record of the last volume, `file`.
+/// ZIP64 record that is not in the last volume is read from `volume_path`.
+fn read_end_of_central_directory(
+    file: &mut File,
+    volume_path: impl Fn(u32) -> PathBuf,
+) -> anyhow::Result<EndOfCentralDirectory> {
+    let file_size = file.metadata()?.len();
+    let tail_size = file_size.min((EOCD_SIZE + u16::MAX as usize) as u64);
+    file.seek(SeekFrom::Start(file_size - tail_size))?;
+    let mut tail = Vec::with_capacity(tail_size as usize);
+    file.read_to_end(&mut tail)?;
+    // the whole record has to fit after the signature
+    let last_pos = tail
+        .len()
+        .checked_sub(EOCD_SIZE)
+        .context("End of central directory not found.")?;
+    let eocd_pos = (0..=last_pos)
+        .rev()
+        .find(|&pos| u32_at(&tail, pos) == EOCD_SIGNATURE)
+        .context("End of central directory not found.")?;
+    let eocd = &tail[eocd_pos..];
+    let comment_len = u16_at(eocd, 20) as usize;
+    let comment = eocd[EOCD_SIZE..(EOCD_SIZE + comment_len).min(eocd.len())].to_vec();
+    let mut record = EndOfCentralDirectory {
+        disks: u16_at(eocd, 4) as u32 + 1,
+        cd_disk: u16_at(eocd, 6) as u32,
+        entries: u16_at(eocd, 10) as u64,
+        cd_size: u32_at(eocd, 12) as u64,
+        cd_offset: u32_at(eocd, 16) as u64,
+        comment,
+    };
+    let has_locator = eocd_pos >= ZIP64_LOCATOR_SIZE
+        && u32_at(&tail, eocd_pos - ZIP64_LOCATOR_SIZE) == ZIP64_LOCATOR_SIGNATURE;
+    if has_locator {
+        let locator = &tail[eocd_pos - ZIP64_LOCATOR_SIZE..eocd_pos];
+        let zip64_disk = u32_at(locator, 4);
+        let zip64_offset = u64_at(locator, 8);
+        record.disks = u32_at(locator, 16);
+        let mut zip64_file = if zip64_disk + 1 == record.disks {
+            file.try_clone()?
+        } else {
+            File::open(volume_path(zip64_disk))?
+        };
+        zip64_file.seek(SeekFrom::Start(zip64_offset))?;
+        let mut zip64 = [0; 56];
+        zip64_file.read_exact(&mut zip64)?;
+        if u32_at(&zip64, 0) != ZIP64_EOCD_SIGNATURE {
+            anyhow::bail!("ZIP64 end of central directory not found.");
+        }
+        record.cd_disk = u32_at(&zip64, 20);
+        record.entries = u64_at(&zip64, 32);
+        record.cd_size = u64_at(&zip64, 40);
+        record.cd_offset = u64_at(&zip64, 48);
+    }
+    Ok(record)
+}
+
+/// Path of volume `disk` (counted from 0) of a split archive whose last
+/// volume is `zip_path`: `archive.z01` for disk 0 and so on. The extension
+/// is upper case if the one of `zip_path` is.
+fn volume_path(zip_path: &Path, disk: u32) -> PathBuf {
+    let upper_case = zip_path
+        .extension()
+        .is_some_and(|e| e.to_string_lossy() == "ZIP");
+    let extension = format!("{}{:02}", if upper_case { "Z" } else { "z" }, disk + 1);
+    zip_path.with_extension(extension)
+}
+
+/// Whether `path` is a volume of a split archive (`.z01`, `.z02`, ...) with
+/// the last volume (`.zip`) next to it.
+pub fn is_split_volume(path: &Path) -> bool {
+    let is_volume_extension = path.extension().is_some_and(|e| {
+        let e = e.to_string_lossy();
+        e.len() >= 3
+            && e[..1].eq_ignore_ascii_case("z")
+            && e[1..].bytes().all(|b| b.is_ascii_digit())
+    });
+    is_volume_extension
+        && (path.with_extension("zip").is_file() || path.with_extension("ZIP").is_file())
+}
+
+/// Volumes of the split archive whose last volume is `zip_path`, in order,
+/// and its end of central directory. `None` for a regular archive.
+fn split_volumes(zip_path: &Path) -> anyhow::Result<Option<(Vec<PathBuf>, EndOfCentralDirectory)>> {
+    let mut last_volume = File::open(zip_path)
+        .with_context(|| format!("Failed to open ZIP file: `{}`.", zip_path.display()))?;
+    let Ok(eocd) =
+        read_end_of_central_directory(&mut last_volume, |disk| volume_path(zip_path, disk))
+    else {
+        // not a valid archive, let the zip crate report it
+        return Ok(None);
+    };
+    if eocd.disks <= 1 {
+        return Ok(None);
+    }
+    let volumes: Vec<PathBuf> = (0..eocd.disks - 1)
+        .map(|disk| volume_path(zip_path, disk))
+        .chain([zip_path.to_path_buf()])
+        .collect();
+    if let Some(missing) = volumes.iter().find(|v| !v.is_file()) {
+        anyhow::bail!(
+            "ZIP archive `{}` is split into {} volumes but volume `{}` is missing.",
+            zip_path.display(),
+            eocd.disks,
+            missing.display()
+        );
+    }
+    Ok(Some((volumes, eocd)))
+}
+
+/// If `zip_path` is the last volume of a split archive, returns a reader of
+/// its volumes one after another, starting at the first local header, so
+/// the entries can be read in order with `zip::read::read_zipfile_from_stream`
+/// without joining the volumes. Returns `None` for a regular archive. Fails
+/// if any of the volumes is missing.
+pub fn read_split_volumes(zip_path: &Path) -> anyhow::Result<Option<Box<dyn Read>>> {
+    let Some((volumes, _)) = split_volumes(zip_path)? else {
+        return Ok(None);
+    };
+    let mut reader: Box<dyn Read> = Box::new(std::io::empty());
+    for volume in &volumes {
+        let file = File::open(volume)
+            .with_context(|| format!("Failed to open volume `{}`.", volume.display()))?;
+        reader = Box::new(reader.chain(file));
+    }
+    let mut signature = [0; 4];
+    reader.read_exact(&mut signature)?;
+    if u32::from_le_bytes(signature) != SPLIT_SIGNATURE {
+        // the signature of split archives is optional
+        reader = Box::new(std::io::Cursor::new(signature).chain(reader));
+    }
+    Ok(Some(reader))
+}
+
+/// If `zip_path` is the last volume of a split archive, joins all volumes into
+/// a temporary ZIP file readable by `ZipArchive` and returns its path (the
+/// file is removed when the path is dropped). Returns `None` for a regular
+/// archive. Fails if any of the volumes is missing.
+pub fn join_split_archive(zip_path: &Path) -> anyhow::Result<Option<TempPath>> {
+    let Some((volumes, eocd)) = split_volumes(zip_path)? else {
+        return Ok(None);
+    };
+    progress!(
+        "Joining {} volumes of split ZIP archive `{}`...",
+        volumes.len(),
+        zip_path.display()
+    );
+    join_volumes(&volumes, &eocd)
+        .with_context(|| format!("Failed to join split ZIP archive `{}`.", zip_path.display()))
+        .map(Some)
+}
+
+fn join_volumes(volumes: &[PathBuf], eocd: &EndOfCentralDirectory) -> anyhow::Result<TempPath> {
+    let mut joined = tempfile::Builder::new().suffix(".zip").tempfile()?;
+    let mut volume_starts = Vec::with_capacity(volumes.len());
+    let mut position = 0;
+    for volume in volumes {
+        volume_starts.push(position);
+        let mut file = File::open(volume)
+            .with_context(|| format!("Failed to open volume `{}`.", volume.display()))?;
+        position += std::io::copy(&mut file, joined.as_file_mut())?;
+    }
+    let volume_start = |disk: u32| {
+        volume_starts
+            .get(disk as usize)
+            .copied()
+            .with_context(|| format!("Reference to volume {} which does not exist.", disk + 1))
+    };
+
+    let cd_start = volume_start(eocd.cd_disk)? + eocd.cd_offset;
+    let file = joined.as_file_mut();
+    file.seek(SeekFrom::Start(cd_start))?;
+    let mut central_directory = vec![0; eocd.cd_size as usize];
+    file.read_exact(&mut central_directory)
+        .context("Central directory is truncated.")?;
+
+    let mut new_central_directory = Vec::with_capacity(central_directory.len());
+    let mut pos = 0;
+    for _ in 0..eocd.entries {
+        let header = central_directory
+            .get(pos..pos + CENTRAL_HEADER_SIZE)
+            .context("Central directory is truncated.")?;
+        if u32_at(header, 0) != CENTRAL_HEADER_SIGNATURE {
+            anyhow::bail!("Invalid central directory entry at offset {}.", pos);
+        }
+        let name_len = u16_at(header, 28) as usize;
+        let extra_len = u16_at(header, 30) as usize;
+        let comment_len = u16_at(header, 32) as usize;
+        let entry_len = CENTRAL_HEADER_SIZE + name_len + extra_len + comment_len;
+        let entry = central_directory
+            .get(pos..pos + entry_len)
+            .context("Central directory is truncated.")?;
+        new_central_directory.extend(rewrite_central_header(entry, &volume_start)?);
+        pos += entry_len;
+    }
+
+    file.set_len(cd_start)?;
+    file.seek(SeekFrom::Start(cd_start))?;
+    file.write_all(&new_central_directory)?;
+    write_end_of_central_directory(
+        file,
+        eocd.entries,
+        new_central_directory.len() as u64,
+        cd_start,
+        &eocd.comment,
+    )?;
+    file.flush()?;
+    Ok(joined.into_temp_path())
+}
+
+/// Returns central directory `entry` with its local header offset made
+/// relative to the start of the joined file and its disk set to 0. Values
+/// that do not fit into the header are stored in the ZIP64 extra field.
+fn rewrite_central_header(
+    entry: &[u8],
+    volume_start: &impl Fn(u32) -> anyhow::Result<u64>,
+) -> anyhow::Result<Vec<u8>> {
+    let name_len = u16_at(entry, 28) as usize;
+    let extra_len = u16_at(entry, 30) as usize;
+    let extra_start = CENTRAL_HEADER_SIZE + name_len;
+    let extra = &entry[extra_start..extra_start + extra_len];
+
+    let mut uncompressed_size = u32_at(entry, 24) as u64;
+    let mut compressed_size = u32_at(entry, 20) as u64;
+    let mut offset = u32_at(entry, 42) as u64;
+    let mut disk = u16_at(entry, 34) as u32;
+    // extra fields other than ZIP64 are kept as they are
+    let mut other_extra = Vec::new();
+    let mut extra_pos = 0;
+    while extra_pos + 4 <= extra.len() {
+        let id = u16_at(extra, extra_pos);
+        let len = u16_at(extra, extra_pos + 2) as usize;
+        let data = extra
+            .get(extra_pos + 4..extra_pos + 4 + len)
+            .context("Invalid extra field in central directory.")?;
+        if id == ZIP64_EXTRA_ID {
+            let mut field_pos = 0;
+            let mut next_u64 = || {
+                let value = data
+                    .get(field_pos..field_pos + 8)
+                    .map(|_| u64_at(data, field_pos));
+                field_pos += 8;
+                value.context("Invalid ZIP64 extra field in central directory.")
+            };
+            if uncompressed_size == 0xFFFFFFFF {
+                uncompressed_size = next_u64()?;
+            }
+            if compressed_size == 0xFFFFFFFF {
+                compressed_size = next_u64()?;
+            }
+            if offset == 0xFFFFFFFF {
+                offset = next_u64()?;
+            }
+            if disk == 0xFFFF {
+                disk = data
+                    .get(field_pos..field_pos + 4)
+                    .map(|_| u32_at(data, field_pos))
+                    .context("Invalid ZIP64 extra field in central directory.")?;
+            }
+        } else {
+            other_extra.extend_from_slice(&extra[extra_pos..extra_pos + 4 + len]);
+        }
+        extra_pos += 4 + len;
+    }
+    let offset = volume_start(disk)? + offset;
+
+    let mut zip64_extra = Vec::new();
+    let mut header = entry[..CENTRAL_HEADER_SIZE].to_vec();
+    for (value, header_pos) in [(uncompressed_size, 24), (compressed_size, 20), (offset, 42)] {
+        let header_value = if value >= 0xFFFFFFFF {
+            zip64_extra.extend_from_slice(&value.to_le_bytes());
+            0xFFFFFFFF
+        } else {
+            value as u32
+        };
+        header[header_pos..header_pos + 4].copy_from_slice(&header_value.to_le_bytes());
+    }
+    header[34..36].copy_from_slice(&0u16.to_le_bytes());
+    if !zip64_extra.is_empty() {
+        other_extra.extend_from_slice(&ZIP64_EXTRA_ID.to_le_bytes());
+        other_extra.extend_from_slice(&(zip64_extra.len() as u16).to_le_bytes());
+        other_extra.extend_from_slice(&zip64_extra);
+    }
+    let new_extra_len =
+        u16::try_from(other_extra.len()).context("Extra field in central directory too long.")?;
+    header[30..32].copy_from_slice(&new_extra_len.to_le_bytes());
+
+    let comment = &entry[extra_start + extra_len..];
+    let mut rewritten = header;
+    rewritten.extend_from_slice(&entry[CENTRAL_HEADER_SIZE..extra_start]);
+    rewritten.extend_from_slice(&other_extra);
+    rewritten.extend_from_slice(comment);
+    Ok(rewritten)
+}
+
+/// Writes the end of central directory of a single volume archive, with a
+/// ZIP64 record and locator when the counts or offsets do not fit.
+fn write_end_of_central_directory(
+    file: &mut File,
+    entries: u64,
+    cd_size: u64,
+    cd_offset: u64,
+    comment: &[u8],
+) -> anyhow::Result<()> {
+    let needs_zip64 = entries >= 0xFFFF || cd_size >= 0xFFFFFFFF || cd_offset >= 0xFFFFFFFF;
+    let mut record = Vec::new();
+    if needs_zip64 {
+        let zip64_offset = cd_offset + cd_size;
+        record.extend_from_slice(&ZIP64_EOCD_SIGNATURE.to_le_bytes());
+        record.extend_from_slice(&44u64.to_le_bytes()); // size of the rest of the record
+        record.extend_from_slice(&45u16.to_le_bytes()); // version made by
+        record.extend_from_slice(&45u16.to_le_bytes()); // version needed
+        record.extend_from_slice(&0u32.to_le_bytes()); // this disk
+        record.extend_from_slice(&0u32.to_le_bytes()); // disk with central directory
+        record.extend_from_slice(&entries.to_le_bytes());
+        record.extend_from_slice(&entries.to_le_bytes());
+        record.extend_from_slice(&cd_size.to_le_bytes());
+        record.extend_from_slice(&cd_offset.to_le_bytes());
+        record.extend_from_slice(&ZIP64_LOCATOR_SIGNATURE.to_le_bytes());
+        record.extend_from_slice(&0u32.to_le_bytes());
+        record.extend_from_slice(&zip64_offset.to_le_bytes());
+        record.extend_from_slice(&1u32.to_le_bytes()); // total number of disks
+    }
+    let entries = u16::try_from(entries).unwrap_or(0xFFFF);
+    record.extend_from_slice(&EOCD_SIGNATURE.to_le_bytes());
+    record.extend_from_slice(&0u16.to_le_bytes()); // this disk
+    record.extend_from_slice(&0u16.to_le_bytes()); // disk with central directory
+    record.extend_from_slice(&entries.to_le_bytes());
+    record.extend_from_slice(&entries.to_le_bytes());
+    record.extend_from_slice(&u32::try_from(cd_size).unwrap_or(0xFFFFFFFF).to_le_bytes());
+    record.extend_from_slice(&u32::try_from(cd_offset).unwrap_or(0xFFFFFFFF).to_le_bytes());
+    record.extend_from_slice(&(comment.len() as u16).to_le_bytes());
+    record.extend_from_slice(comment);
+    file.write_all(&record)?;
+    Ok(())
+}
+
+#[test]
+fn test_join_split_archive() {
+    let joined = join_split_archive(Path::new("fixtures/sample_model2012_split.zip"))
+        .unwrap()
+        .expect("Expected a split archive");
+    let mut archive = zip::ZipArchive::new(File::open(&joined).unwrap()).unwrap();
+    assert_eq!(archive.len(), 2);
+    let mut names = Vec::new();
+    for idx in 0..archive.len() {
+        let mut entry = archive.by_index(idx).unwrap();
+        names.push(entry.name().to_string());
+        let mut content = String::new();
+        entry.read_to_string(&mut content).unwrap();
+        assert_eq!(content.len() as u64, entry.size());
+        assert!(content.starts_with("<?xml"));
+    }
+    assert_eq!(
+        names,
+        ["sample_model2012_split.xml", "sample_model2012_small.xml"]
+    );
+
+    assert!(
+        join_split_archive(Path::new("fixtures/PRG-punkty_adresowe.zip"))
+            .unwrap()
+            .is_none()
+    );
+}
+
+#[test]
+fn test_read_split_volumes() {
+    let mut volumes = read_split_volumes(Path::new("fixtures/sample_model2012_split.zip"))
+        .unwrap()
+        .expect("Expected a split archive");
+    let mut names = Vec::new();
+    while let Some(mut entry) = zip::read::read_zipfile_from_stream(&mut volumes).unwrap() {
+        names.push(entry.name().to_string());
+        let mut content = String::new();
+        entry.read_to_string(&mut content).unwrap();
+        assert!(content.starts_with("<?xml"));
+    }
+    assert_eq!(
+        names,
+        ["sample_model2012_split.xml", "sample_model2012_small.xml"]
+    );
+
+    assert!(
+        read_split_volumes(Path::new("fixtures/PRG-punkty_adresowe.zip"))
+            .unwrap()
+            .is_none()
+    );
+}
+
+#[test]
+fn test_join_split_archive_missing_volume() {
+    let dir = tempfile::tempdir().unwrap();
+    let zip_path = dir.path().join("archive.zip");
+    std::fs::copy("fixtures/sample_model2012_split.zip", &zip_path).unwrap();
+    let err = join_split_archive(&zip_path).unwrap_err();
+    assert!(format!("{}", err).contains("archive.z01"), "{}", err);
+}
+
+#[test]
+fn test_too_short_zip_is_not_split() {
+    let dir = tempfile::tempdir().unwrap();
+    let zip_path = dir.path().join("archive.zip");
+    // a signature without the rest of the record after it
+    let mut truncated_record = EOCD_SIGNATURE.to_le_bytes().to_vec();
+    truncated_record.resize(EOCD_SIZE - 1, 0);
+    for content in [&[][..], &[0x50, 0x4b], &truncated_record] {
+        std::fs::write(&zip_path, content).unwrap();
+        assert!(join_split_archive(&zip_path).unwrap().is_none());
+        assert!(read_split_volumes(&zip_path).unwrap().is_none());
+    }
+}
+
+#[test]
+fn test_is_split_volume() {
+    assert!(is_split_volume(Path::new(
+        "fixtures/sample_model2012_split.z01"
+    )));
+    assert!(!is_split_volume(Path::new(
+        "fixtures/sample_model2012_split.zip"
+    )));
+    assert!(!is_split_volume(Path::new("fixtures/missing.z01")));
+}
@@ -1,5 +1,7 @@
 #[cfg(feature = "download")]
-use std::io::{Seek, Write};
+use std::io::Write;
+use std::io::{BufRead, Read, Seek};
+use std::path::Path;
 use std::{collections::HashMap, io::BufReader, path::PathBuf};
 
 use anyhow::Context;
@@ -7,6 +9,7 @@ use anyhow::Context;
 use base64::{Engine as _, engine::general_purpose};
 #[cfg(feature = "download")]
 use chrono::Local;
+use chrono::NaiveDate;
 use quick_xml::de::Deserializer;
 use serde::Deserialize;
 #[cfg(feature = "download")]
@@ -15,6 +18,9 @@ use tempfile::tempfile;
 use uuid::Uuid;
 use zip::ZipArchive;
 
+use crate::PrgError;
+use crate::progress;
+
 #[derive(Deserialize)]
 struct Teryt {
     pub catalog: Catalog,
@@ -88,10 +94,50 @@ pub struct Terc {
     pub municipality_name: String,
 }
 
-fn parse_terc_zip_file(teryt_file: std::fs::File) -> anyhow::Result<Teryt> {
-    let mut archive =
-        ZipArchive::new(teryt_file).with_context(|| "Failed to decompress TERC ZIP file.")?;
-    let mut idx_to_read: isize = -1;
+/// TERC dictionary keyed by the 7-digit municipality TERYT id, together with
+/// the date of the catalog it was built from.
+pub struct TercDictionary {
+    /// `date` attribute of the TERC catalog (`YYYY-MM-DD`).
+    pub catalog_date: String,
+    /// File the catalog was read from, `None` when it was downloaded.
+    pub source_path: Option<PathBuf>,
+    pub mapping: HashMap<String, Terc>,
+}
+
+impl TercDictionary {
+    fn from_teryt(teryt: Teryt) -> anyhow::Result<TercDictionary> {
+        let catalog_date = teryt.catalog.date.clone();
+        let (mapping, skipped) = prepare_mapping_from_teryt(teryt)?;
+        if !skipped.is_empty() {
+            println!(
+                "Warning: skipped {} TERC rows that are not a voivodeship, county or municipality, or whose voivodeship or county is missing: {}{}.",
+                skipped.len(),
+                skipped[..skipped.len().min(SKIPPED_ROWS_SHOWN)].join(", "),
+                if skipped.len() > SKIPPED_ROWS_SHOWN {
+                    ", ..."
+                } else {
+                    ""
+                }
+            );
+        }
+        if mapping.is_empty() {
+            anyhow::bail!("After parsing TERYT file mapping dict is empty.")
+        }
+        Ok(TercDictionary {
+            catalog_date,
+            source_path: None,
+            mapping,
+        })
+    }
+}
+
+/// Index of the XML file with TERC data inside a ZIP archive. XML files named
+/// like `TERC*.xml` (e.g. `TERC_Urzedowy_2025-11-18.xml`) are preferred, other
+/// XML files are only considered when there is none. It is an error if more
+/// than one file is left to choose from, instead of picking one of them.
+fn find_terc_entry<R: Read + std::io::Seek>(archive: &mut ZipArchive<R>) -> anyhow::Result<usize> {
+    let mut terc_files = Vec::new();
+    let mut other_xml_files = Vec::new();
     for idx in 0..archive.len() {
         let entry = archive
             .by_index(idx)
@@ -99,27 +145,226 @@ fn parse_terc_zip_file(teryt_file: std::fs::File) -> anyhow::Result<Teryt> {
         let name = entry
             .enclosed_name()
             .with_context(|| "Could not read file name inside ZIP archive.")?;
-        // for now we'll determine if the file inside zip should be processed based on extension
-        let file_extension = &name
+        let is_xml = name
             .extension()
-            .expect("Could not read file extension.")
-            .to_string_lossy()
-            .to_lowercase();
-        if file_extension == "xml" {
-            idx_to_read = idx as isize;
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("xml"));
+        if !entry.is_file() || !is_xml {
+            continue;
+        }
+        let file_name = name.file_name().unwrap_or_default().to_string_lossy();
+        if is_terc_name(&file_name) {
+            terc_files.push((idx, entry.name().to_string()));
+        } else {
+            other_xml_files.push((idx, entry.name().to_string()));
         }
     }
-    if idx_to_read == -1 {
-        anyhow::bail!("Did not find XML file in ZIP archive with TERYT data.")
+    let candidates = if terc_files.is_empty() {
+        other_xml_files
+    } else {
+        terc_files
+    };
+    match candidates.as_slice() {
+        [] => anyhow::bail!("Did not find XML file in ZIP archive with TERYT data."),
+        [(idx, _)] => Ok(*idx),
+        _ => anyhow::bail!(
+            "Found more than one XML file in ZIP archive with TERYT data ({}), extract the TERC file and pass it with --teryt-path.",
+            candidates
+                .iter()
+                .map(|(_, name)| format!("`{}`", name))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
     }
+}
+
+fn is_terc_name(file_name: &str) -> bool {
+    file_name.to_uppercase().starts_with("TERC")
+}
+
+/// Whether `name` of a file inside a ZIP archive looks like a TERC file that
+/// `--teryt-path` can read: `TERC*.xml` or `TERC*.csv` (case-insensitive).
+pub fn is_terc_entry_name(name: &str) -> bool {
+    let path = Path::new(name);
+    path.file_name()
+        .is_some_and(|file_name| is_terc_name(&file_name.to_string_lossy()))
+        && path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("xml") || ext.eq_ignore_ascii_case("csv"))
+}
+
+/// Separator of a ZIP archive and the path of a file inside it in a TERYT
+/// path, e.g. `adresy.zip::TERC_Urzedowy_2025-11-18.xml`.
+pub const ZIP_ENTRY_SEPARATOR: &str = "::";
+
+/// TERYT path of the file `entry` inside the ZIP archive `archive`.
+pub fn zip_entry_path(archive: &Path, entry: &str) -> PathBuf {
+    let mut path = archive.as_os_str().to_owned();
+    path.push(ZIP_ENTRY_SEPARATOR);
+    path.push(entry);
+    PathBuf::from(path)
+}
+
+/// Splits a TERYT path `archive.zip::entry` into the archive and the path of
+/// the file inside it, `None` for other paths.
+fn split_zip_entry_path(path: &Path) -> Option<(PathBuf, &str)> {
+    let (archive, entry) = path.to_str()?.split_once(ZIP_ENTRY_SEPARATOR)?;
+    let is_zip = Path::new(archive)
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("zip"));
+    (is_zip && !entry.is_empty()).then(|| (PathBuf::from(archive), entry))
+}
+
+fn parse_terc_xml<R: BufRead>(reader: R) -> anyhow::Result<Teryt> {
+    let mut deserializer = Deserializer::from_reader(reader);
+    Teryt::deserialize(&mut deserializer).with_context(|| "Could not deserialize TERC XML file.")
+}
+
+/// Reads the TERC file `entry` (`.xml` or `.csv`) inside the ZIP archive
+/// `archive_path`.
+fn read_teryt_zip_entry(archive_path: &Path, entry: &str) -> anyhow::Result<Teryt> {
+    let file = std::fs::File::open(archive_path)
+        .with_context(|| format!("could not open file `{}`", archive_path.display()))?;
+    let mut archive = ZipArchive::new(file).with_context(|| {
+        format!(
+            "Failed to decompress ZIP file: `{}`.",
+            archive_path.display()
+        )
+    })?;
+    let zip_file = archive.by_name(entry).with_context(|| {
+        format!(
+            "Could not find `{}` inside ZIP archive `{}`.",
+            entry,
+            archive_path.display()
+        )
+    })?;
+    let reader = BufReader::new(zip_file);
+    match Path::new(entry)
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .as_deref()
+    {
+        Some("xml") => parse_terc_xml(reader),
+        Some("csv") => parse_terc_csv(reader).with_context(|| "Could not parse TERC CSV file."),
+        _ => anyhow::bail!(
+            "Unsupported TERYT file `{}` inside ZIP archive `{}`. Expected `.xml` or `.csv`.",
+            entry,
+            archive_path.display()
+        ),
+    }
+}
+
+fn parse_terc_zip_file<R: Read + Seek>(teryt_file: R) -> anyhow::Result<Teryt> {
+    let mut archive =
+        ZipArchive::new(teryt_file).with_context(|| "Failed to decompress TERC ZIP file.")?;
+    let idx_to_read = find_terc_entry(&mut archive)?;
     let f = archive
-        .by_index(idx_to_read as usize)
+        .by_index(idx_to_read)
         .with_context(|| "Could not access file inside ZIP archive")?;
-    let reader = BufReader::new(f);
-    let mut deserializer = Deserializer::from_reader(reader);
-    let teryt = Teryt::deserialize(&mut deserializer)
-        .with_context(|| "Could not deserialize TERC data from file.")?;
-    Ok(teryt)
+    parse_terc_xml(BufReader::new(f)).with_context(|| "Could not deserialize TERC data from file.")
+}
+
+/// Columns of the TERC catalog, in the order of the CSV file downloaded from eTERYT.
+const CSV_COLUMNS: [&str; 7] = ["WOJ", "POW", "GMI", "RODZ", "NAZWA", "NAZWA_DOD", "STAN_NA"];
+
+/// Splits a CSV line on `delimiter`, removing the quotes around quoted values.
+fn split_csv_line(line: &str, delimiter: char) -> Vec<String> {
+    let mut values = Vec::new();
+    let mut value = String::new();
+    let mut in_quotes = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if in_quotes && chars.peek() == Some(&'"') => {
+                value.push('"');
+                chars.next();
+            }
+            '"' => in_quotes = !in_quotes,
+            c if c == delimiter && !in_quotes => values.push(std::mem::take(&mut value)),
+            c => value.push(c),
+        }
+    }
+    values.push(value);
+    values.iter().map(|v| v.trim().to_string()).collect()
+}
+
+/// Parses the TERC catalog in the CSV layout (`WOJ;POW;GMI;RODZ;NAZWA;NAZWA_DOD;STAN_NA`).
+/// The delimiter (`;`, `,` or tab) is the one found most often in the first
+/// line, and the first line is a header if one of its values is `WOJ`; columns
+/// are then found by name, otherwise the order above is assumed. The catalog
+/// date is the latest `STAN_NA`.
+fn parse_terc_csv<R: BufRead>(reader: R) -> anyhow::Result<Teryt> {
+    let mut lines = reader.lines();
+    let first_line = match lines.next() {
+        Some(line) => line.with_context(|| "Could not read TERC CSV file (expected UTF-8).")?,
+        None => anyhow::bail!("TERC CSV file is empty."),
+    };
+    let first_line = first_line.trim_start_matches('\u{feff}').to_string();
+    let delimiter = [';', ',', '\t']
+        .into_iter()
+        .max_by_key(|d| first_line.matches(*d).count())
+        .unwrap_or(';');
+    let first_values = split_csv_line(&first_line, delimiter);
+    let has_header = first_values
+        .iter()
+        .any(|v| v.eq_ignore_ascii_case(CSV_COLUMNS[0]));
+    let indexes = if has_header {
+        CSV_COLUMNS
+            .iter()
+            .map(|column| {
+                first_values
+                    .iter()
+                    .position(|v| v.eq_ignore_ascii_case(column))
+                    .with_context(|| format!("TERC CSV file is missing column `{}`.", column))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?
+    } else {
+        (0..CSV_COLUMNS.len()).collect()
+    };
+    let data_lines = (!has_header)
+        .then_some(Ok(first_line.clone()))
+        .into_iter()
+        .chain(lines);
+    let mut rows = Vec::new();
+    for (line_number, line) in data_lines.enumerate() {
+        let line = line.with_context(|| "Could not read TERC CSV file (expected UTF-8).")?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let values = split_csv_line(&line, delimiter);
+        let value = |column: usize| -> anyhow::Result<String> {
+            values.get(indexes[column]).cloned().with_context(|| {
+                format!(
+                    "TERC CSV line {} has no column `{}`.",
+                    line_number + 1 + has_header as usize,
+                    CSV_COLUMNS[column]
+                )
+            })
+        };
+        let optional = |column: usize| value(column).map(|v| Some(v).filter(|v| !v.is_empty()));
+        rows.push(Row {
+            woj: value(0)?,
+            pow: optional(1)?,
+            gmi: optional(2)?,
+            rodz: optional(3)?,
+            nazwa: value(4)?,
+            nazwa_dod: value(5)?,
+            stan_na: value(6)?,
+        });
+    }
+    let date = rows
+        .iter()
+        .map(|row| row.stan_na.as_str())
+        .max()
+        .unwrap_or_default()
+        .to_string();
+    Ok(Teryt {
+        catalog: Catalog {
+            name: "TERC".to_string(),
+            catalog_type: "CSV".to_string(),
+            date,
+            row: rows,
+        },
+    })
 }
 
 #[cfg(feature = "download")]
@@ -184,19 +429,19 @@ fn test_build_terc_soap_payload_escapes_credentials() {
 pub fn download_terc_mapping(
     api_username: &str,
     api_password: &str,
-) -> anyhow::Result<HashMap<String, Terc>> {
+) -> anyhow::Result<TercDictionary> {
     let url = "https://uslugaterytws1.stat.gov.pl/TerytWs1.svc";
     let uuid = Uuid::new_v4();
     let todays_date = Local::now().format("%Y-%m-%d").to_string();
     let payload = build_terc_soap_payload(&uuid, url, api_username, api_password, &todays_date);
     let client = reqwest::blocking::Client::new();
-    println!("Sending request to TERYT API...");
+    progress!("Sending request to TERYT API...");
     let res = client
         .post(url)
         .header("Content-Type", "text/xml;charset=UTF-8")
         .body(payload)
         .send()?;
-    println!("Response received.");
+    progress!("Response received.");
     let xml_string = &res.text()?;
     let bytes = get_file_content_from_response(xml_string)?;
     let mut file = tempfile()?;
@@ -204,12 +449,7 @@ pub fn download_terc_mapping(
         .with_context(|| "Could not write downloaded TERC file to temp storage.")?;
     file.seek(std::io::SeekFrom::Start(0))?;
     let teryt = parse_terc_zip_file(file)?;
-    let mapping = prepare_mapping_from_teryt(teryt)?;
-    if mapping.is_empty() {
-        anyhow::bail!("After parsing TERYT file mapping dict is empty.")
-    } else {
-        Ok(mapping)
-    }
+    TercDictionary::from_teryt(teryt)
 }
 
 #[cfg(feature = "download")]
@@ -225,37 +465,217 @@ fn get_file_content_from_response(xml_string: &str) -> anyhow::Result<Vec<u8>> {
     Ok(bytes)
 }
 
-pub fn get_terc_mapping(file_path: &PathBuf) -> anyhow::Result<HashMap<String, Terc>> {
-    let teryt_file = std::fs::File::open(&file_path)
+/// Extensions of the files `read_teryt` can parse.
+const TERC_FILE_EXTENSIONS: [&str; 3] = ["xml", "zip", "csv"];
+
+/// Reads a TERC file, or a file inside a ZIP archive with the
+/// `archive.zip::entry` syntax (see `ZIP_ENTRY_SEPARATOR`).
+fn read_teryt(file_path: &Path) -> anyhow::Result<Teryt> {
+    if let Some((archive, entry)) = split_zip_entry_path(file_path) {
+        return read_teryt_zip_entry(&archive, entry)
+            .with_context(|| "Could not deserialize teryt dictionary from file.");
+    }
+    let teryt_file = std::fs::File::open(file_path)
         .with_context(|| format!("could not open file `{}`", &file_path.to_string_lossy()))?;
-    let teryt = match file_path
+    match file_path
         .extension()
-        .expect("Could not read file extension from teryt file.")
+        .with_context(|| format!("TERYT file `{}` has no extension.", file_path.display()))?
         .to_string_lossy()
         .to_lowercase()
         .as_str()
     {
-        "xml" => {
-            let reader = BufReader::new(teryt_file);
-            let mut deserializer = Deserializer::from_reader(reader);
-            Teryt::deserialize(&mut deserializer)
-                .with_context(|| "Could not deserialize TERC XML file.")
-        }
+        "xml" => parse_terc_xml(BufReader::new(teryt_file)),
         "zip" => parse_terc_zip_file(teryt_file),
+        "csv" => parse_terc_csv(BufReader::new(teryt_file))
+            .with_context(|| "Could not parse TERC CSV file."),
         other => {
             anyhow::bail!(
-                "Unsupported TERYT file extension `{}` for `{}`. Expected `.xml` or `.zip`.",
+                "Unsupported TERYT file extension `{}` for `{}`. Expected `.xml`, `.zip` or `.csv`.",
                 other,
                 file_path.display()
             )
         }
     }
-    .with_context(|| "Could not deserialize teryt dictionary from XML file.")?;
-    let mapping = prepare_mapping_from_teryt(teryt)?;
-    if mapping.is_empty() {
-        anyhow::bail!("After parsing TERYT file mapping dict is empty.")
+    .with_context(|| "Could not deserialize teryt dictionary from file.")
+}
+
+/// Date of the TERC catalog of `teryt` read from `file_path`: its `date`
+/// attribute (the latest `STAN_NA` for CSV), or else the first `YYYY-MM-DD`
+/// in the file name (e.g. `TERC_Urzedowy_2025-11-18.xml`).
+fn catalog_date(teryt: &Teryt, file_path: &Path) -> Option<String> {
+    let date = teryt.catalog.date.trim();
+    if !date.is_empty() {
+        return Some(date.to_string());
+    }
+    let file_name = file_path.file_name()?.to_string_lossy();
+    file_name
+        .char_indices()
+        .filter_map(|(i, _)| file_name.get(i..i + 10))
+        .find(|s| NaiveDate::parse_from_str(s, "%Y-%m-%d").is_ok())
+        .map(str::to_string)
+}
+
+/// Reads the TERC files (`.xml`, `.zip`, `.csv`) directly in `dir` and
+/// returns the newest by `catalog_date`, or the one of `date`. Files that
+/// cannot be read or have no date are skipped with a warning.
+fn find_terc_file(dir: &Path, date: Option<&str>) -> anyhow::Result<(PathBuf, Teryt)> {
+    let mut paths = std::fs::read_dir(dir)
+        .with_context(|| format!("Could not list TERYT directory `{}`.", dir.display()))?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<Result<Vec<_>, _>>()
+        .with_context(|| format!("Could not list TERYT directory `{}`.", dir.display()))?;
+    paths.retain(|path| {
+        path.is_file()
+            && path.extension().is_some_and(|ext| {
+                TERC_FILE_EXTENSIONS.contains(&ext.to_string_lossy().to_lowercase().as_str())
+            })
+    });
+    // sorted so that of files with the same date the last by name is chosen
+    paths.sort();
+    let mut newest: Option<(String, PathBuf, Teryt)> = None;
+    let mut dates = Vec::new();
+    for path in paths {
+        let mut teryt = match read_teryt(&path) {
+            Ok(teryt) => teryt,
+            Err(e) => {
+                println!(
+                    "Warning: skipped `{}` in the TERYT directory, it is not a TERC file: {:#}",
+                    path.display(),
+                    e
+                );
+                continue;
+            }
+        };
+        let Some(file_date) = catalog_date(&teryt, &path) else {
+            println!(
+                "Warning: skipped `{}` in the TERYT directory, its catalog date is unknown.",
+                path.display()
+            );
+            continue;
+        };
+        teryt.catalog.date = file_date.clone();
+        dates.push(file_date.clone());
+        let chosen = match (date, &newest) {
+            (Some(date), _) => file_date == date,
+            (None, Some((newest_date, _, _))) => file_date >= *newest_date,
+            (None, None) => true,
+        };
+        if chosen {
+            newest = Some((file_date, path, teryt));
+        }
+    }
+    match (newest, date) {
+        (Some((_, path, teryt)), _) => Ok((path, teryt)),
+        (None, _) if dates.is_empty() => anyhow::bail!(
+            "TERYT directory `{}` has no valid TERC file (`.xml`, `.zip` or `.csv`).",
+            dir.display()
+        ),
+        (None, date) => {
+            dates.sort();
+            dates.dedup();
+            anyhow::bail!(
+                "TERYT directory `{}` has no TERC file of {}, available: {}.",
+                dir.display(),
+                date.unwrap_or_default(),
+                dates.join(", ")
+            )
+        }
+    }
+}
+
+pub fn get_terc_mapping(file_path: &Path) -> Result<TercDictionary, PrgError> {
+    get_terc_mapping_of_date(file_path, None)
+}
+
+/// TERC dictionary of a file, of a file inside a ZIP archive
+/// (`archive.zip::TERC_Urzedowy_2025-11-18.xml`), or of the newest TERC file
+/// of a directory (see `find_terc_file`). With `date` (`YYYY-MM-DD`) the catalog must be of
+/// that date: the file of the directory with it is used, and a single file of
+/// another date is an error.
+pub fn get_terc_mapping_of_date(
+    file_path: &Path,
+    date: Option<&str>,
+) -> Result<TercDictionary, PrgError> {
+    read_terc_mapping(file_path, date).map_err(|e| PrgError::classify(e, PrgError::TercParse))
+}
+
+fn read_terc_mapping(file_path: &Path, date: Option<&str>) -> anyhow::Result<TercDictionary> {
+    let (path, teryt) = if file_path.is_dir() {
+        let (path, teryt) = find_terc_file(file_path, date)?;
+        progress!(
+            "Using TERC catalog of {} from `{}`.",
+            teryt.catalog.date,
+            path.display()
+        );
+        (path, teryt)
     } else {
-        Ok(mapping)
+        let teryt = read_teryt(file_path)?;
+        if let Some(date) = date {
+            let file_date = catalog_date(&teryt, file_path);
+            if file_date.as_deref() != Some(date) {
+                anyhow::bail!(
+                    "TERC file `{}` is of {}, not of the requested date {}.",
+                    file_path.display(),
+                    file_date.as_deref().unwrap_or("an unknown date"),
+                    date
+                );
+            }
+        }
+        (file_path.to_path_buf(), teryt)
+    };
+    let mut dictionary = TercDictionary::from_teryt(teryt)?;
+    dictionary.source_path = Some(path);
+    Ok(dictionary)
+}
+
+/// Dictionary from a ZIP archive with the TERC catalog as XML, like
+/// `get_terc_mapping` with a `.zip` file.
+#[cfg(feature = "cli")]
+pub(crate) fn get_terc_mapping_from_zip<R: Read + Seek>(
+    reader: R,
+) -> anyhow::Result<TercDictionary> {
+    let teryt = parse_terc_zip_file(reader)
+        .with_context(|| "Could not deserialize teryt dictionary from file.")?;
+    TercDictionary::from_teryt(teryt)
+}
+
+/// Value of an optional WOJ/POW/GMI/RODZ component, `None` if it is missing
+/// or empty (`<GMI />`).
+fn component(value: &Option<String>) -> Option<&str> {
+    value.as_deref().map(str::trim).filter(|v| !v.is_empty())
+}
+
+/// Kind of a TERC row, decided by which of its components are present.
+#[derive(Debug, PartialEq)]
+enum RowKind {
+    /// WOJ only.
+    Voivodeship,
+    /// WOJ and POW, also cities with powiat rights (e.g. `0264` Wrocław).
+    County,
+    /// WOJ, POW, GMI and RODZ: urban, rural and urban-rural municipalities
+    /// with their parts (RODZ 1-5), districts of Warsaw (8) and delegations
+    /// of cities with powiat rights (9).
+    Municipality,
+    /// Any other combination, e.g. GMI without POW or a RODZ without GMI.
+    Unknown,
+}
+
+fn row_kind(row: &Row) -> RowKind {
+    let is_digits = |v: &str, len: usize| v.len() == len && v.bytes().all(|b| b.is_ascii_digit());
+    match (
+        is_digits(row.woj.trim(), 2),
+        component(&row.pow),
+        component(&row.gmi),
+        component(&row.rodz),
+    ) {
+        (true, None, None, None) => RowKind::Voivodeship,
+        (true, Some(pow), None, None) if is_digits(pow, 2) => RowKind::County,
+        (true, Some(pow), Some(gmi), Some(rodz))
+            if is_digits(pow, 2) && is_digits(gmi, 2) && is_digits(rodz, 1) =>
+        {
+            RowKind::Municipality
+        }
+        _ => RowKind::Unknown,
     }
 }
 
@@ -263,70 +683,77 @@ pub fn get_terc_mapping(file_path: &PathBuf) -> anyhow::Result<HashMap<String, T
 /// (2 chars for a voivodeship, 4 for a county, 7 for a municipality).
 fn teryt_code(row: &Row) -> String {
     [
-        row.woj.clone(),
-        row.pow.clone().unwrap_or_default(),
-        row.gmi.clone().unwrap_or_default(),
-        row.rodz.clone().unwrap_or_default(),
+        Some(row.woj.trim()),
+        component(&row.pow),
+        component(&row.gmi),
+        component(&row.rodz),
     ]
-    .join("")
+    .into_iter()
+    .flatten()
+    .collect()
 }
 
-fn prepare_mapping_from_teryt(teryt: Teryt) -> anyhow::Result<HashMap<String, Terc>> {
+/// Maximum number of skipped TERC rows listed in the warning.
+const SKIPPED_ROWS_SHOWN: usize = 5;
+
+/// Builds the mapping of municipalities. Rows of unknown kind and
+/// municipalities whose voivodeship or county is missing from the catalog are
+/// skipped; they are returned as `code (name)` for a warning.
+fn prepare_mapping_from_teryt(
+    teryt: Teryt,
+) -> anyhow::Result<(HashMap<String, Terc>, Vec<String>)> {
     let mut woj = HashMap::new();
     let mut pow = HashMap::new();
+    let mut skipped = Vec::new();
     // First pass: collect voivodeship (2-digit) and county (4-digit) names.
     for row in &teryt.catalog.row {
-        let teryt_id = teryt_code(row);
-        match teryt_id.len() {
-            2 => {
+        match row_kind(row) {
+            RowKind::Voivodeship => {
                 // teryt dictionary stores these uppercase; previous PRG schema used lowercase
-                woj.insert(teryt_id, row.nazwa.to_lowercase());
+                woj.insert(teryt_code(row), row.nazwa.to_lowercase());
             }
-            4 => {
-                pow.insert(teryt_id, row.nazwa.clone());
+            RowKind::County => {
+                pow.insert(teryt_code(row), row.nazwa.clone());
             }
-            7 => {} // handled in the second pass
-            other => anyhow::bail!(
-                "Unrecognized teryt code length {} for code `{}`.",
-                other,
-                teryt_id
-            ),
+            RowKind::Municipality => {} // handled in the second pass
+            RowKind::Unknown => skipped.push(format!("{} ({})", teryt_code(row), row.nazwa)),
         }
     }
     // Second pass: build municipality entries, now that woj/pow are fully populated.
     let mut mapping = HashMap::new();
     for row in &teryt.catalog.row {
-        let teryt_id = teryt_code(row);
-        if teryt_id.len() != 7 {
+        if row_kind(row) != RowKind::Municipality {
             continue;
         }
-        let voivodeship_name = woj
-            .get(&row.woj)
-            .with_context(|| format!("No voivodeship name found for code `{}`.", row.woj))?
-            .clone();
+        let teryt_id = teryt_code(row);
+        let voivodeship_id = teryt_id[..2].to_string();
         let county_id = teryt_id[..4].to_string();
-        let county_name = pow
-            .get(&county_id)
-            .with_context(|| format!("No county name found for code `{}`.", county_id))?
-            .clone();
+        let (Some(voivodeship_name), Some(county_name)) =
+            (woj.get(&voivodeship_id), pow.get(&county_id))
+        else {
+            skipped.push(format!("{} ({})", teryt_id, row.nazwa));
+            continue;
+        };
         mapping.insert(
             teryt_id,
             Terc {
-                voivodeship_teryt_id: row.woj.clone(),
-                voivodeship_name,
+                voivodeship_teryt_id: voivodeship_id,
+                voivodeship_name: voivodeship_name.clone(),
                 county_teryt_id: county_id,
-                county_name,
+                county_name: county_name.clone(),
                 municipality_name: row.nazwa.clone(),
             },
         );
     }
-    Ok(mapping)
+    Ok((mapping, skipped))
 }
 
 #[test]
 fn get_terc_mapping_xml() {
     let teryt_file_path = PathBuf::from("fixtures/TERC_Urzedowy_2025-11-18.xml");
-    let teryt_mapping = crate::terc::get_terc_mapping(&teryt_file_path).unwrap();
+    let teryt_mapping = crate::terc::get_terc_mapping(&teryt_file_path)
+        .unwrap()
+        .mapping;
     let k0201011 = &teryt_mapping["0201011"];
     assert_eq!(k0201011.municipality_name, "Bolesławiec");
     assert_eq!(k0201011.county_teryt_id, "0201");
@@ -338,7 +765,9 @@ fn get_terc_mapping_xml() {
 #[test]
 fn get_terc_mapping_zip() {
     let teryt_file_path = PathBuf::from("fixtures/TERC_Urzedowy_2025-11-18.zip");
-    let teryt_mapping = crate::terc::get_terc_mapping(&teryt_file_path).unwrap();
+    let teryt_mapping = crate::terc::get_terc_mapping(&teryt_file_path)
+        .unwrap()
+        .mapping;
     let k0201011 = &teryt_mapping["0201011"];
     assert_eq!(k0201011.municipality_name, "Bolesławiec");
     assert_eq!(k0201011.county_teryt_id, "0201");
@@ -347,6 +776,161 @@ fn get_terc_mapping_zip() {
     assert_eq!(k0201011.voivodeship_name, "dolnośląskie");
 }
 
+#[test]
+fn get_terc_mapping_zip_entry() {
+    use std::io::Write;
+    let dir = tempfile::tempdir().unwrap();
+    let archive_path = dir.path().join("adresy.zip");
+    let mut writer = zip::ZipWriter::new(std::fs::File::create(&archive_path).unwrap());
+    let options = zip::write::SimpleFileOptions::default();
+    writer.start_file("adresy.gml", options).unwrap();
+    writer
+        .start_file("teryt/TERC_Urzedowy_2025-11-18.csv", options)
+        .unwrap();
+    writer
+        .write_all(&std::fs::read("fixtures/TERC_Urzedowy_2025-11-18.csv").unwrap())
+        .unwrap();
+    writer.finish().unwrap();
+
+    let path = zip_entry_path(&archive_path, "teryt/TERC_Urzedowy_2025-11-18.csv");
+    assert_eq!(
+        split_zip_entry_path(&path),
+        Some((archive_path.clone(), "teryt/TERC_Urzedowy_2025-11-18.csv"))
+    );
+    let dictionary = get_terc_mapping(&path).unwrap();
+    let expected = get_terc_mapping(Path::new("fixtures/TERC_Urzedowy_2025-11-18.csv")).unwrap();
+    assert_eq!(dictionary.mapping.len(), expected.mapping.len());
+    assert_eq!(dictionary.catalog_date, expected.catalog_date);
+    assert_eq!(dictionary.source_path, Some(path));
+
+    let missing = zip_entry_path(&archive_path, "TERC.xml");
+    let Err(err) = get_terc_mapping(&missing) else {
+        panic!("TERC.xml is not in the archive");
+    };
+    assert!(format!("{:#}", err).contains("Could not find `TERC.xml`"));
+    // only `.zip::` is split
+    assert_eq!(split_zip_entry_path(Path::new("dir::TERC.xml")), None);
+    assert_eq!(split_zip_entry_path(Path::new("adresy.zip::")), None);
+}
+
+#[test]
+fn test_is_terc_entry_name() {
+    assert!(is_terc_entry_name("TERC_Urzedowy_2025-11-18.xml"));
+    assert!(is_terc_entry_name("teryt/terc.CSV"));
+    assert!(!is_terc_entry_name("TERC_Urzedowy_2025-11-18.zip"));
+    assert!(!is_terc_entry_name("PRG_punkty_adresowe.gml"));
+    assert!(!is_terc_entry_name("teryt_TERC.xml"));
+}
+
+#[test]
+fn get_terc_mapping_keeps_catalog_date() {
+    let teryt_file_path = PathBuf::from("fixtures/TERC_Urzedowy_2025-11-18.zip");
+    let dictionary = crate::terc::get_terc_mapping(&teryt_file_path).unwrap();
+    assert_eq!(dictionary.catalog_date, "2025-01-01");
+}
+
+#[test]
+fn get_terc_mapping_of_date_from_directory() {
+    let dir = tempfile::tempdir().unwrap();
+    let xml = std::fs::read_to_string("fixtures/TERC_Urzedowy_2025-11-18.xml").unwrap();
+    std::fs::write(dir.path().join("TERC_old.xml"), &xml).unwrap();
+    std::fs::write(
+        dir.path().join("TERC_new.xml"),
+        xml.replace(r#"date="2025-01-01""#, r#"date="2025-06-01""#),
+    )
+    .unwrap();
+    std::fs::write(dir.path().join("broken.xml"), "<teryt>").unwrap();
+    std::fs::write(dir.path().join("notes.txt"), "not a TERC file").unwrap();
+
+    let dictionary = get_terc_mapping_of_date(dir.path(), None).unwrap();
+    assert_eq!(dictionary.catalog_date, "2025-06-01");
+    assert_eq!(
+        dictionary.source_path,
+        Some(dir.path().join("TERC_new.xml"))
+    );
+    assert_eq!(
+        dictionary.mapping["0201011"].municipality_name,
+        "Bolesławiec"
+    );
+
+    let dictionary = get_terc_mapping_of_date(dir.path(), Some("2025-01-01")).unwrap();
+    assert_eq!(
+        dictionary.source_path,
+        Some(dir.path().join("TERC_old.xml"))
+    );
+    let error = get_terc_mapping_of_date(dir.path(), Some("2024-01-01")).err();
+    assert!(
+        format!("{:#}", error.unwrap()).contains("available: 2025-01-01, 2025-06-01"),
+        "expected the dates of the directory"
+    );
+
+    // a single file must be of the requested date
+    let file = dir.path().join("TERC_old.xml");
+    assert!(get_terc_mapping_of_date(&file, Some("2025-01-01")).is_ok());
+    assert!(matches!(
+        get_terc_mapping_of_date(&file, Some("2025-06-01")),
+        Err(PrgError::TercParse(_))
+    ));
+
+    let empty = tempfile::tempdir().unwrap();
+    std::fs::write(empty.path().join("broken.xml"), "<teryt>").unwrap();
+    assert!(get_terc_mapping_of_date(empty.path(), None).is_err());
+}
+
+#[test]
+fn test_catalog_date_from_file_name() {
+    let teryt = parse_terc_csv("WOJ;POW;GMI;RODZ;NAZWA;NAZWA_DOD;STAN_NA\n".as_bytes()).unwrap();
+    assert_eq!(
+        catalog_date(&teryt, Path::new("dir/TERC_Urzedowy_2025-11-18.csv")),
+        Some("2025-11-18".to_string())
+    );
+    assert_eq!(catalog_date(&teryt, Path::new("TERC.csv")), None);
+}
+
+#[test]
+fn get_terc_mapping_csv() {
+    let teryt_file_path = PathBuf::from("fixtures/TERC_Urzedowy_2025-11-18.csv");
+    let dictionary = crate::terc::get_terc_mapping(&teryt_file_path).unwrap();
+    assert_eq!(dictionary.catalog_date, "2025-01-01");
+    let k0201011 = &dictionary.mapping["0201011"];
+    assert_eq!(k0201011.municipality_name, "Bolesławiec");
+    assert_eq!(k0201011.county_teryt_id, "0201");
+    assert_eq!(k0201011.county_name, "bolesławiecki");
+    assert_eq!(k0201011.voivodeship_teryt_id, "02");
+    assert_eq!(k0201011.voivodeship_name, "dolnośląskie");
+    // same mapping as from the XML file
+    let xml_mapping = get_terc_mapping(&PathBuf::from("fixtures/TERC_Urzedowy_2025-11-18.xml"))
+        .unwrap()
+        .mapping;
+    assert_eq!(dictionary.mapping.len(), xml_mapping.len());
+}
+
+#[test]
+fn test_parse_terc_csv_delimiter_and_header() {
+    let rows = |csv: &str| {
+        let teryt = parse_terc_csv(csv.as_bytes()).unwrap();
+        let (mapping, skipped) = prepare_mapping_from_teryt(teryt).unwrap();
+        assert!(skipped.is_empty(), "{:?}", skipped);
+        mapping
+    };
+    let with_header = "\u{feff}NAZWA,WOJ,POW,GMI,RODZ,NAZWA_DOD,STAN_NA\n\
+        DOLNOŚLĄSKIE,02,,,,województwo,2025-01-01\n\
+        bolesławiecki,02,01,,,powiat,2025-01-01\n\
+        \"Bolesławiec\",02,01,01,1,gmina miejska,2025-01-01\n";
+    let mapping = rows(with_header);
+    assert_eq!(mapping["0201011"].municipality_name, "Bolesławiec");
+    assert_eq!(mapping["0201011"].voivodeship_name, "dolnośląskie");
+
+    let without_header = "02;;;;DOLNOŚLĄSKIE;województwo;2025-01-01\n\
+        02;01;;;bolesławiecki;powiat;2025-01-01\n\
+        02;01;01;1;Bolesławiec;gmina miejska;2025-01-01\n";
+    assert_eq!(rows(without_header)["0201011"].county_name, "bolesławiecki");
+
+    let missing_column = "WOJ;POW;GMI;RODZ;NAZWA\n02;;;;DOLNOŚLĄSKIE\n";
+    let err = parse_terc_csv(missing_column.as_bytes()).err().unwrap();
+    assert!(format!("{}", err).contains("NAZWA_DOD"), "{}", err);
+}
+
 #[cfg(feature = "download")]
 #[test]
 fn test_parse_api_response() {
@@ -356,7 +940,7 @@ fn test_parse_api_response() {
     file.write_all(&bytes).unwrap();
     file.seek(std::io::SeekFrom::Start(0)).unwrap();
     let teryt = parse_terc_zip_file(file).unwrap();
-    let teryt_mapping = prepare_mapping_from_teryt(teryt).unwrap();
+    let (teryt_mapping, _) = prepare_mapping_from_teryt(teryt).unwrap();
     let k0201011 = &teryt_mapping["0201011"];
     assert_eq!(k0201011.municipality_name, "Bolesławiec");
     assert_eq!(k0201011.county_teryt_id, "0201");
@@ -368,20 +952,20 @@ fn test_parse_api_response() {
 #[test]
 fn test_get_terc_mapping_file_not_found() {
     let result = get_terc_mapping(&PathBuf::from("fixtures/definitely_nonexistent.xml"));
-    assert!(result.is_err());
+    assert!(matches!(result, Err(PrgError::Io(_))));
 }
 
 #[test]
 fn test_get_terc_mapping_unsupported_extension() {
     let temp_file = tempfile::Builder::new()
-        .suffix(".csv")
+        .suffix(".txt")
         .tempfile()
         .expect("Failed to create temp file");
-    let result = get_terc_mapping(&temp_file.path().to_path_buf());
-    assert!(result.is_err());
+    let result = get_terc_mapping(temp_file.path());
+    assert!(matches!(result, Err(PrgError::TercParse(_))));
     let err = format!("{}", result.err().unwrap());
     assert!(
-        err.contains("extension") && err.contains("csv"),
+        err.contains("extension") && err.contains("txt"),
         "error message was: {}",
         err
     );
@@ -419,7 +1003,9 @@ fn test_prepare_mapping_handles_out_of_order_rows() {
             ],
         },
     };
-    let mapping = prepare_mapping_from_teryt(teryt).expect("should not panic on out-of-order rows");
+    let (mapping, skipped) =
+        prepare_mapping_from_teryt(teryt).expect("should not panic on out-of-order rows");
+    assert!(skipped.is_empty());
     let m = &mapping["0201011"];
     assert_eq!(m.municipality_name, "Bolesławiec");
     assert_eq!(m.county_teryt_id, "0201");
@@ -427,3 +1013,97 @@ fn test_prepare_mapping_handles_out_of_order_rows() {
     assert_eq!(m.voivodeship_teryt_id, "02");
     assert_eq!(m.voivodeship_name, "dolnośląskie"); // lowercased
 }
+
+#[test]
+fn get_terc_mapping_special_rows() {
+    let dictionary =
+        crate::terc::get_terc_mapping(&PathBuf::from("fixtures/TERC_special_rows.xml")).unwrap();
+    let mapping = dictionary.mapping;
+    // city with powiat rights: the county row has the name of the city
+    let wroclaw = &mapping["0264011"];
+    assert_eq!(wroclaw.municipality_name, "Wrocław");
+    assert_eq!(wroclaw.county_teryt_id, "0264");
+    assert_eq!(wroclaw.county_name, "Wrocław");
+    assert_eq!(wroclaw.voivodeship_name, "dolnośląskie");
+    // delegation of a city with powiat rights (RODZ 9)
+    assert_eq!(mapping["0264029"].municipality_name, "Wrocław-Fabryczna");
+    assert_eq!(mapping["0264029"].county_name, "Wrocław");
+    // district of Warsaw (RODZ 8)
+    assert_eq!(mapping["1465028"].municipality_name, "Bemowo");
+    assert_eq!(mapping["1465028"].county_name, "Warszawa");
+    assert_eq!(mapping["1465028"].voivodeship_teryt_id, "14");
+    assert_eq!(mapping.len(), 5);
+}
+
+#[test]
+fn test_row_kind() {
+    fn row(woj: &str, pow: Option<&str>, gmi: Option<&str>, rodz: Option<&str>) -> Row {
+        Row {
+            woj: woj.to_string(),
+            pow: pow.map(str::to_string),
+            gmi: gmi.map(str::to_string),
+            rodz: rodz.map(str::to_string),
+            nazwa: String::new(),
+            nazwa_dod: String::new(),
+            stan_na: "2026-01-01".to_string(),
+        }
+    }
+    assert_eq!(row_kind(&row("02", None, None, None)), RowKind::Voivodeship);
+    assert_eq!(
+        row_kind(&row("02", Some(""), Some(""), Some(""))),
+        RowKind::Voivodeship
+    );
+    assert_eq!(
+        row_kind(&row("02", Some("64"), None, None)),
+        RowKind::County
+    );
+    assert_eq!(
+        row_kind(&row("02", Some("64"), Some("02"), Some("9"))),
+        RowKind::Municipality
+    );
+    assert_eq!(
+        row_kind(&row("02", None, Some("01"), Some("1"))),
+        RowKind::Unknown
+    );
+    assert_eq!(
+        row_kind(&row("02", Some("64"), Some("01"), None)),
+        RowKind::Unknown
+    );
+    assert_eq!(
+        row_kind(&row("02", Some("6"), None, None)),
+        RowKind::Unknown
+    );
+}
+
+#[cfg(test)]
+fn zip_with_files(names: &[&str]) -> ZipArchive<std::io::Cursor<Vec<u8>>> {
+    use std::io::Write;
+    let mut writer = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    for name in names {
+        writer
+            .start_file(*name, zip::write::SimpleFileOptions::default())
+            .unwrap();
+        writer.write_all(b"<teryt/>").unwrap();
+    }
+    ZipArchive::new(writer.finish().unwrap()).unwrap()
+}
+
+#[test]
+fn test_find_terc_entry() {
+    // the TERC file is preferred over other XML files, whatever their order
+    let mut archive = zip_with_files(&["opis.xml", "TERC_Urzedowy_2025-11-18.xml"]);
+    assert_eq!(find_terc_entry(&mut archive).unwrap(), 1);
+    let mut archive = zip_with_files(&["TERC_Urzedowy_2025-11-18.xml", "opis.xml"]);
+    assert_eq!(find_terc_entry(&mut archive).unwrap(), 0);
+    // a single XML file is used whatever its name
+    let mut archive = zip_with_files(&["dane.csv", "dane.xml"]);
+    assert_eq!(find_terc_entry(&mut archive).unwrap(), 1);
+
+    let mut archive = zip_with_files(&["TERC_Urzedowy_2025-11-18.xml", "TERC_Adresowy.xml"]);
+    let err = find_terc_entry(&mut archive).unwrap_err().to_string();
+    assert!(err.contains("`TERC_Adresowy.xml`"), "{}", err);
+    let mut archive = zip_with_files(&["a.xml", "b.xml"]);
+    assert!(find_terc_entry(&mut archive).is_err());
+    let mut archive = zip_with_files(&["TERC_Urzedowy_2025-11-18.csv"]);
+    assert!(find_terc_entry(&mut archive).is_err());
+}
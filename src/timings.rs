@@ -0,0 +1,175 @@
+//! Time spent in the phases of a conversion (`--benchmark`), accumulated for
+//! the whole process like the verbosity. Until enabled, timers only load a
+//! flag, so they can stay in the parsers and writers.
+//!
+//! The timings are global: conversions running at the same time add to (and
+//! reset) the same totals, so only one of them should be measured at a time.
+
+use std::cell::Cell;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Phase {
+    /// Building the dictionaries of the input before its addresses are read.
+    Dictionaries,
+    /// Reading the TERC dictionary (schema 2021).
+    Terc,
+    /// Reading addresses from XML, without `ArrowBuilding`.
+    Parsing,
+    /// Transforming the EPSG:2180 coordinates of a batch to longitude and
+    /// latitude (input in EPSG:4326 is transformed while parsing).
+    Reprojection,
+    /// Building record batches from the parsed addresses, without
+    /// `Reprojection`.
+    ArrowBuilding,
+    /// Encoding, compressing and writing the output files.
+    Writing,
+}
+
+impl Phase {
+    pub const ALL: [Phase; 6] = [
+        Phase::Dictionaries,
+        Phase::Terc,
+        Phase::Parsing,
+        Phase::Reprojection,
+        Phase::ArrowBuilding,
+        Phase::Writing,
+    ];
+}
+
+impl std::fmt::Display for Phase {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Phase::Dictionaries => write!(f, "Dictionaries"),
+            Phase::Terc => write!(f, "TERC"),
+            Phase::Parsing => write!(f, "XML parsing"),
+            Phase::Reprojection => write!(f, "Reprojection"),
+            Phase::ArrowBuilding => write!(f, "Arrow building"),
+            Phase::Writing => write!(f, "Writing/compression"),
+        }
+    }
+}
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+/// Nanoseconds per phase in the order of `Phase::ALL`, including the phases
+/// nested in them (see `phase_durations`).
+static NANOS: [AtomicU64; 6] = [const { AtomicU64::new(0) }; 6];
+/// Part of `Phase::ArrowBuilding` run inside `Phase::Parsing` on the same
+/// thread (with `parallel_parse` batches are built on the worker threads
+/// while the parsing thread waits for them).
+static ARROW_BUILDING_IN_PARSING_NANOS: AtomicU64 = AtomicU64::new(0);
+
+thread_local! {
+    /// Whether a `Phase::Parsing` timer is running on this thread.
+    static PARSING: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Starts measuring the phases of the whole process.
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Sets the time of all phases to zero.
+pub fn reset() {
+    for nanos in &NANOS {
+        nanos.store(0, Ordering::Relaxed);
+    }
+    ARROW_BUILDING_IN_PARSING_NANOS.store(0, Ordering::Relaxed);
+}
+
+/// Restores the previous state of the timings when dropped, see `enable`.
+#[must_use]
+pub struct EnabledGuard {
+    previous: bool,
+}
+
+impl Drop for EnabledGuard {
+    fn drop(&mut self) {
+        set_enabled(self.previous);
+    }
+}
+
+/// Resets and enables the timings until the returned guard is dropped.
+pub fn enable() -> EnabledGuard {
+    reset();
+    EnabledGuard {
+        previous: ENABLED.swap(true, Ordering::Relaxed),
+    }
+}
+
+/// Adds the time until it is dropped to its phase.
+#[must_use]
+pub struct Timer {
+    start: Option<(Phase, Instant)>,
+    /// Whether a parsing timer was already running on this thread.
+    was_parsing: bool,
+}
+
+impl Drop for Timer {
+    fn drop(&mut self) {
+        if let Some((phase, start)) = self.start {
+            let nanos = u64::try_from(start.elapsed().as_nanos()).unwrap_or(u64::MAX);
+            NANOS[phase as usize].fetch_add(nanos, Ordering::Relaxed);
+            match phase {
+                Phase::Parsing => PARSING.set(self.was_parsing),
+                Phase::ArrowBuilding if self.was_parsing => {
+                    ARROW_BUILDING_IN_PARSING_NANOS.fetch_add(nanos, Ordering::Relaxed);
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Timer of `phase`, which does nothing unless timings are enabled.
+pub fn start(phase: Phase) -> Timer {
+    if !enabled() {
+        return Timer {
+            start: None,
+            was_parsing: false,
+        };
+    }
+    let was_parsing = match phase {
+        Phase::Parsing => PARSING.replace(true),
+        _ => PARSING.get(),
+    };
+    Timer {
+        start: Some((phase, Instant::now())),
+        was_parsing,
+    }
+}
+
+/// Time of every phase since the last `reset`. Nested phases are taken off
+/// the phases they run in (reprojection off arrow building, arrow building
+/// off parsing when it runs on the parsing thread). Times of phases running
+/// on several threads (writing with `workers_per_file` 2, arrow building
+/// with `parallel_parse`) are summed, so together they can be longer than
+/// the run.
+pub fn phase_durations() -> Vec<(Phase, Duration)> {
+    let total = |phase: Phase| NANOS[phase as usize].load(Ordering::Relaxed);
+    let reprojection = total(Phase::Reprojection);
+    let arrow_building = total(Phase::ArrowBuilding);
+    let arrow_building_in_parsing = ARROW_BUILDING_IN_PARSING_NANOS.load(Ordering::Relaxed);
+    Phase::ALL
+        .iter()
+        .map(|&phase| {
+            let nanos = match phase {
+                Phase::Parsing => total(phase).saturating_sub(arrow_building_in_parsing),
+                Phase::ArrowBuilding => arrow_building.saturating_sub(reprojection),
+                _ => total(phase),
+            };
+            (phase, Duration::from_nanos(nanos))
+        })
+        .collect()
+}
+
+/// Runs `f`, adding its time to `phase`.
+pub fn time<T>(phase: Phase, f: impl FnOnce() -> T) -> T {
+    let _timer = start(phase);
+    f()
+}
@@ -0,0 +1,165 @@
+//! Micro-benchmark of the reprojection from EPSG:2180 to EPSG:4326
+//! (`--transform-only`). Coordinates are read from the `gml:pos` elements of
+//! the input and transformed, without parsing addresses or building any
+//! output, once point by point and once in a single call over all of them.
+
+use std::fs::File;
+use std::hint::black_box;
+use std::io::{BufRead, BufReader};
+use std::time::{Duration, Instant};
+
+use anyhow::Context;
+use quick_xml::Reader;
+use quick_xml::events::Event;
+use zip::ZipArchive;
+
+use crate::common::EPSG_2180;
+use crate::common::transform_2180_to_4326;
+use crate::convert::FileRecord;
+use crate::{CoordOrder, FileType, SchemaVersion};
+
+/// Times of transforming the same points.
+#[derive(Debug)]
+pub struct TransformBenchmark {
+    pub points: usize,
+    /// One `proj4rs` call per point.
+    pub per_point: Duration,
+    /// One `proj4rs` call over all points.
+    pub batched: Duration,
+}
+
+impl TransformBenchmark {
+    pub fn per_point_throughput(&self) -> f64 {
+        self.points as f64 / self.per_point.as_secs_f64()
+    }
+
+    pub fn batched_throughput(&self) -> f64 {
+        self.points as f64 / self.batched.as_secs_f64()
+    }
+}
+
+fn coordinate_order(schema_version: SchemaVersion) -> CoordOrder {
+    match schema_version {
+        SchemaVersion::Model2012 => CoordOrder::YX,
+        SchemaVersion::Model2021 => CoordOrder::XY,
+    }
+}
+
+/// Appends the EPSG:2180 `(x, y)` coordinates of the `gml:pos` elements read
+/// by `reader` to `points`, until it holds `max_points` of them.
+fn read_positions<R: BufRead>(
+    mut reader: Reader<R>,
+    coordinate_order: CoordOrder,
+    max_points: usize,
+    points: &mut Vec<(f64, f64)>,
+) -> anyhow::Result<()> {
+    let mut buffer = Vec::new();
+    let mut in_pos = false;
+    while points.len() < max_points {
+        match reader.read_event_into(&mut buffer) {
+            Ok(Event::Start(e)) if e.name().as_ref() == b"gml:pos" => in_pos = true,
+            Ok(Event::End(e)) if e.name().as_ref() == b"gml:pos" => in_pos = false,
+            Ok(Event::Text(e)) if in_pos => {
+                let text = e.decode().context("Could not decode gml:pos.")?;
+                let parsed =
+                    crate::common::parse_gml_pos(text.trim(), coordinate_order, &EPSG_2180)?;
+                if let Some(coords) = parsed {
+                    points.push((coords.x2180, coords.y2180));
+                }
+            }
+            Ok(Event::Eof) => break,
+            Err(e) => anyhow::bail!("Error at position {}: {}", reader.error_position(), e),
+            _ => (),
+        }
+        buffer.clear();
+    }
+    Ok(())
+}
+
+/// Reads up to `max_points` coordinates from `inputs` (uncompressed files and
+/// the entries of ZIP archives that are to be parsed).
+pub fn read_input_positions(
+    inputs: &[FileRecord],
+    schema_version: SchemaVersion,
+    max_points: usize,
+) -> anyhow::Result<Vec<(f64, f64)>> {
+    let coordinate_order = coordinate_order(schema_version);
+    let mut points = Vec::new();
+    for file in inputs {
+        if points.len() >= max_points {
+            break;
+        }
+        match file.file_type {
+            FileType::XML => {
+                let reader = Reader::from_file(&file.path)
+                    .with_context(|| format!("Failed to open file: `{}`.", &file.path.display()))?;
+                read_positions(reader, coordinate_order, max_points, &mut points)?;
+            }
+            FileType::ZIP => {
+                let f = File::open(file.read_path())
+                    .with_context(|| format!("Failed to open file: `{}`.", &file.path.display()))?;
+                let mut archive = ZipArchive::new(f).with_context(|| {
+                    format!("Failed to decompress ZIP file: `{}`.", &file.path.display())
+                })?;
+                let entries = file.compressed_files.iter().flatten();
+                for entry in entries.filter(|f| f.to_be_parsed) {
+                    if points.len() >= max_points {
+                        break;
+                    }
+                    let zip_file = archive
+                        .by_index(entry.index)
+                        .with_context(|| "Could not decompress file from ZIP archive.")?;
+                    let reader = Reader::from_reader(BufReader::new(zip_file));
+                    read_positions(reader, coordinate_order, max_points, &mut points)?;
+                }
+            }
+        }
+    }
+    Ok(points)
+}
+
+/// Transforms `points` point by point and then in one call, discarding the
+/// results.
+pub fn benchmark_transform(points: &[(f64, f64)]) -> anyhow::Result<TransformBenchmark> {
+    let mut per_point_points = points.to_vec();
+    let start = Instant::now();
+    for point in per_point_points.iter_mut() {
+        transform_2180_to_4326(std::slice::from_mut(point))?;
+    }
+    let per_point = start.elapsed();
+    black_box(&per_point_points);
+
+    let mut batched_points = points.to_vec();
+    let start = Instant::now();
+    transform_2180_to_4326(&mut batched_points)?;
+    let batched = start.elapsed();
+    black_box(&batched_points);
+
+    Ok(TransformBenchmark {
+        points: points.len(),
+        per_point,
+        batched,
+    })
+}
+
+#[test]
+fn test_benchmark_transform_sample() {
+    let path = std::path::PathBuf::from("fixtures/sample_model2012.xml");
+    let file = FileRecord::from_path(path, &SchemaVersion::Model2012, false).unwrap();
+    // two addresses and one dictionary component
+    let points =
+        read_input_positions(std::slice::from_ref(&file), SchemaVersion::Model2012, 10).unwrap();
+    assert_eq!(
+        points,
+        vec![
+            (287772.37, 456005.140000001),
+            (287751.0102, 456027.7794),
+            (287516.2157, 457017.4738)
+        ]
+    );
+    let points = read_input_positions(&[file], SchemaVersion::Model2012, 1).unwrap();
+    assert_eq!(points.len(), 1);
+
+    let benchmark = benchmark_transform(&points).unwrap();
+    assert_eq!(benchmark.points, 1);
+}
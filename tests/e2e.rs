@@ -2,7 +2,7 @@ use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 use std::process::Command;
 
-use arrow::array::{Float64Array, StringArray};
+use arrow::array::{Array, AsArray, Float64Array, StringArray};
 use arrow::compute::concat_batches;
 use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
 
@@ -126,19 +126,22 @@ fn validate_csv(path: &Path, expected: &[ExpectedRow]) {
     let mut lines = content.lines().filter(|l| !l.is_empty());
 
     let header = lines.next().expect("CSV must have a header row");
+    // `--csv-delimiter ;` (with or without `--decimal-comma`)
+    let delimiter = if header.contains(';') { ';' } else { ',' };
     let col_idx: HashMap<&str, usize> = header
-        .split(',')
+        .split(delimiter)
         .enumerate()
         .map(|(i, name)| (name, i))
         .collect();
 
-    let data_rows: Vec<Vec<&str>> = lines.map(|l| l.split(',').collect()).collect();
+    let data_rows: Vec<Vec<&str>> = lines.map(|l| l.split(delimiter).collect()).collect();
     assert_eq!(data_rows.len(), expected.len(), "CSV row count mismatch");
 
     for (i, (row, exp)) in data_rows.iter().zip(expected).enumerate() {
         let get = |col: &str| row[col_idx[col]];
         let parse_f64 = |col: &str| -> f64 {
             get(col)
+                .replace(',', ".")
                 .parse()
                 .unwrap_or_else(|_| panic!("row {i}: failed to parse {col} as f64: '{}'", get(col)))
         };
@@ -295,6 +298,27 @@ fn run(
     input_path: &str,
     expected: &[ExpectedRow],
     teryt_path: Option<&str>,
+) {
+    run_with_args(
+        schema_version,
+        crs_epsg,
+        output_format,
+        input_path,
+        expected,
+        teryt_path,
+        &[],
+    );
+}
+
+/// Like `run`, with additional command line arguments passed to the binary.
+fn run_with_args(
+    schema_version: &str,
+    crs_epsg: &str,
+    output_format: &str,
+    input_path: &str,
+    expected: &[ExpectedRow],
+    teryt_path: Option<&str>,
+    extra_args: &[&str],
 ) {
     let ext = if output_format == "csv" {
         "csv"
@@ -322,6 +346,7 @@ fn run(
     if let Some(teryt) = teryt_path {
         cmd.arg("--teryt-path").arg(teryt);
     }
+    cmd.args(extra_args);
 
     let result = cmd.output().expect("Failed to execute binary");
 
@@ -384,6 +409,124 @@ fn test_e2e_schema2012_xml_geoparquet_2180() {
     );
 }
 
+/// Decode a 2D WKB point into its `(x, y)` coordinates.
+fn decode_wkb_point(wkb: &[u8]) -> (f64, f64) {
+    assert_eq!(
+        wkb.len(),
+        21,
+        "Expected a 2D WKB point, got {} bytes",
+        wkb.len()
+    );
+    let little_endian = match wkb[0] {
+        0 => false,
+        1 => true,
+        order => panic!("Invalid WKB byte order {order}"),
+    };
+    let read_u32 = |b: &[u8]| {
+        let b: [u8; 4] = b.try_into().unwrap();
+        if little_endian {
+            u32::from_le_bytes(b)
+        } else {
+            u32::from_be_bytes(b)
+        }
+    };
+    let read_f64 = |b: &[u8]| {
+        let b: [u8; 8] = b.try_into().unwrap();
+        if little_endian {
+            f64::from_le_bytes(b)
+        } else {
+            f64::from_be_bytes(b)
+        }
+    };
+    assert_eq!(read_u32(&wkb[1..5]), 1, "Expected WKB geometry type Point");
+    (read_f64(&wkb[5..13]), read_f64(&wkb[13..21]))
+}
+
+/// Convert the 2012 sample to GeoParquet in `crs_epsg`, read it back and check
+/// the row count, attributes, decoded points and CRS of the geometry column.
+fn round_trip_geoparquet_2012(crs_epsg: &str) {
+    let output_file = tempfile::Builder::new()
+        .suffix(".parquet")
+        .tempfile()
+        .expect("Failed to create temp output file");
+    let result = Command::new(bin())
+        .current_dir(manifest_dir())
+        .args(["--schema-version", "2012", "--output-format", "geoparquet"])
+        .args(["--crs-epsg", crs_epsg, "--input-paths", MODEL_2012_XML])
+        .arg("--output-path")
+        .arg(output_file.path())
+        .output()
+        .expect("Failed to execute binary");
+    assert!(
+        result.status.success(),
+        "stderr: {}",
+        String::from_utf8_lossy(&result.stderr)
+    );
+    validate_geoparquet(output_file.path(), EXPECTED_2012);
+
+    let geo = read_geo_metadata(output_file.path());
+    let column = &geo["columns"]["geometry"];
+    assert_eq!(geo["primary_column"], "geometry");
+    assert_eq!(column["encoding"], "WKB");
+    assert_eq!(column["geometry_types"], serde_json::json!(["Point"]));
+    assert_eq!(column["crs"]["id"]["authority"], "EPSG");
+    assert_eq!(column["crs"]["id"]["code"].to_string(), crs_epsg);
+
+    let file = std::fs::File::open(output_file.path()).expect("Failed to open GeoParquet file");
+    let batches: Vec<_> = ParquetRecordBatchReaderBuilder::try_new(file)
+        .expect("Failed to create parquet reader builder")
+        .build()
+        .expect("Failed to build parquet reader")
+        .collect::<Result<_, _>>()
+        .expect("Failed to read parquet record batches");
+    let batch = concat_batches(&batches[0].schema(), &batches).expect("Failed to concat batches");
+    let geometry = batch
+        .column_by_name("geometry")
+        .expect("Expected geometry column")
+        .as_binary_opt::<i32>()
+        .expect("Expected WKB geometry column");
+    let teryt_wojewodztwo = batch
+        .column_by_name("teryt_wojewodztwo")
+        .expect("Expected teryt_wojewodztwo column")
+        .as_string::<i32>();
+
+    let (tolerance, expected_points): (f64, Vec<(f64, f64)>) = match crs_epsg {
+        "2180" => (
+            COORD_TOLERANCE_2180,
+            EXPECTED_2012
+                .iter()
+                .map(|exp| (exp.x_epsg_2180, exp.y_epsg_2180))
+                .collect(),
+        ),
+        _ => (
+            COORD_TOLERANCE_4326,
+            EXPECTED_2012.iter().map(|exp| (exp.lon, exp.lat)).collect(),
+        ),
+    };
+    for (i, (x, y)) in expected_points.into_iter().enumerate() {
+        assert_eq!(
+            teryt_wojewodztwo.value(i),
+            "08",
+            "row {i} teryt_wojewodztwo"
+        );
+        let (actual_x, actual_y) = decode_wkb_point(geometry.value(i));
+        assert!(
+            (actual_x - x).abs() < tolerance && (actual_y - y).abs() < tolerance,
+            "row {i} geometry: ({actual_x}, {actual_y}) vs ({x}, {y})"
+        );
+    }
+}
+
+#[test]
+fn test_e2e_schema2012_xml_geoparquet_round_trip_2180() {
+    round_trip_geoparquet_2012("2180");
+}
+
+#[test]
+fn test_e2e_schema2012_xml_geoparquet_round_trip_4326() {
+    round_trip_geoparquet_2012("4326");
+}
+
 // --- Schema 2012, compressed ZIP ---
 
 #[test]
@@ -605,3 +748,1185 @@ fn test_e2e_schema2021_zip_teryt_zip_geoparquet_2180() {
         Some(TERYT_ZIP),
     );
 }
+
+// --- Schema 2021, compressed ZIP with the TERYT XML inside ---
+
+/// Copy of `PRG_ZIP` with the TERC XML added as `TERC_Urzedowy_2025-11-18.xml`.
+fn prg_zip_with_terc() -> tempfile::NamedTempFile {
+    use std::io::Write;
+
+    let zip_file = tempfile::Builder::new()
+        .suffix(".zip")
+        .tempfile()
+        .expect("Failed to create temp zip file");
+    let mut archive =
+        zip::ZipArchive::new(std::fs::File::open(manifest_dir().join(PRG_ZIP)).unwrap()).unwrap();
+    let mut writer = zip::ZipWriter::new(zip_file.reopen().unwrap());
+    for idx in 0..archive.len() {
+        writer
+            .raw_copy_file(archive.by_index_raw(idx).unwrap())
+            .unwrap();
+    }
+    writer
+        .start_file(
+            "TERC_Urzedowy_2025-11-18.xml",
+            zip::write::SimpleFileOptions::default(),
+        )
+        .unwrap();
+    writer
+        .write_all(&std::fs::read(manifest_dir().join(TERYT_XML)).unwrap())
+        .unwrap();
+    writer.finish().unwrap();
+    zip_file
+}
+
+#[test]
+fn test_e2e_schema2021_zip_teryt_zip_entry_csv_4326() {
+    let zip_file = prg_zip_with_terc();
+    let input = zip_file.path().to_str().unwrap();
+    let teryt = format!("{}::TERC_Urzedowy_2025-11-18.xml", input);
+    run("2021", "4326", "csv", input, EXPECTED_2021, Some(&teryt));
+}
+
+#[test]
+fn test_e2e_schema2021_zip_teryt_detected_csv_4326() {
+    let zip_file = prg_zip_with_terc();
+    let input = zip_file.path().to_str().unwrap();
+    run("2021", "4326", "csv", input, EXPECTED_2021, None);
+}
+
+// --- Filters ---
+
+#[test]
+fn test_e2e_schema2012_xml_csv_start_date() {
+    run_with_args(
+        "2012",
+        "2180",
+        "csv",
+        MODEL_2012_XML,
+        &EXPECTED_2012[..1],
+        None,
+        &["--start-date", "2020-01-01"],
+    );
+}
+
+#[test]
+fn test_e2e_schema2021_xml_geoparquet_date_window() {
+    run_with_args(
+        "2021",
+        "4326",
+        "geoparquet",
+        MODEL_2021_XML,
+        &EXPECTED_2021[1..2],
+        Some(TERYT_XML),
+        &["--start-date", "2021-03-09", "--end-date", "2021-03-09"],
+    );
+}
+
+#[test]
+fn test_e2e_schema2021_xml_csv_require_street() {
+    run_with_args(
+        "2021",
+        "2180",
+        "csv",
+        MODEL_2021_XML,
+        &EXPECTED_2021[1..],
+        Some(TERYT_XML),
+        &["--require-street"],
+    );
+}
+
+#[test]
+fn test_e2e_schema2021_xml_csv_no_street() {
+    run_with_args(
+        "2021",
+        "2180",
+        "csv",
+        MODEL_2021_XML,
+        &EXPECTED_2021[..1],
+        Some(TERYT_XML),
+        &["--no-street"],
+    );
+}
+
+#[test]
+fn test_e2e_schema2021_xml_csv_tail() {
+    // all addresses are read, only the last two are written
+    let output_file = write_csv(
+        &["--schema-version", "2021", "--input-paths", MODEL_2021_XML],
+        &[
+            "--teryt-path",
+            TERYT_XML,
+            "--tail",
+            "2",
+            "--batch-size",
+            "1",
+        ],
+    );
+    validate_csv(output_file.path(), &EXPECTED_2021[1..]);
+}
+
+#[test]
+fn test_e2e_schema2012_xml_geoparquet_tail_larger_than_input() {
+    run_with_args(
+        "2012",
+        "4326",
+        "geoparquet",
+        MODEL_2012_XML,
+        EXPECTED_2012,
+        None,
+        &["--tail", "50"],
+    );
+}
+
+// --- Parallel parsing ---
+
+#[test]
+fn test_e2e_schema2012_xml_csv_parallel_parse() {
+    run_with_args(
+        "2012",
+        "2180",
+        "csv",
+        MODEL_2012_XML,
+        EXPECTED_2012,
+        None,
+        &["--parallel-parse"],
+    );
+}
+
+#[test]
+fn test_e2e_schema2021_xml_geoparquet_parallel_parse() {
+    run_with_args(
+        "2021",
+        "4326",
+        "geoparquet",
+        MODEL_2021_XML,
+        EXPECTED_2021,
+        Some(TERYT_XML),
+        &["--parallel-parse"],
+    );
+}
+
+// --- Memory-mapped input ---
+
+#[test]
+fn test_e2e_schema2012_xml_csv_mmap() {
+    run_with_args(
+        "2012",
+        "2180",
+        "csv",
+        MODEL_2012_XML,
+        EXPECTED_2012,
+        None,
+        &["--mmap"],
+    );
+}
+
+#[test]
+fn test_e2e_schema2021_xml_geoparquet_mmap() {
+    run_with_args(
+        "2021",
+        "4326",
+        "geoparquet",
+        MODEL_2021_XML,
+        EXPECTED_2021,
+        Some(TERYT_XML),
+        &["--mmap"],
+    );
+}
+
+// --- Validation ---
+
+fn validate_only(input_path: &Path) -> std::process::Output {
+    Command::new(bin())
+        .current_dir(manifest_dir())
+        .args(["--validate-only", "--schema-version", "2012"])
+        .arg("--input-paths")
+        .arg(input_path)
+        .output()
+        .expect("Failed to execute binary")
+}
+
+#[test]
+fn test_e2e_validate_only() {
+    let result = validate_only(Path::new(MODEL_2012_XML));
+    let stdout = String::from_utf8_lossy(&result.stdout);
+    assert!(result.status.success(), "stdout: {stdout}");
+    assert!(stdout.contains("Addresses: 2"), "stdout: {stdout}");
+    assert!(
+        stdout.contains("Addresses with errors: 0"),
+        "stdout: {stdout}"
+    );
+    assert!(stdout.contains("Unknown tags: none"), "stdout: {stdout}");
+}
+
+#[test]
+fn test_e2e_quiet() {
+    let output_file = tempfile::Builder::new()
+        .suffix(".csv")
+        .tempfile()
+        .expect("Failed to create temp output file");
+    let result = Command::new(bin())
+        .current_dir(manifest_dir())
+        .args(["--schema-version", "2012", "--output-format", "csv"])
+        .args(["--input-paths", MODEL_2012_XML, "--quiet"])
+        .arg("--output-path")
+        .arg(output_file.path())
+        .output()
+        .expect("Failed to execute binary");
+    let stdout = String::from_utf8_lossy(&result.stdout);
+    assert!(result.status.success(), "stdout: {stdout}");
+    assert!(stdout.is_empty(), "stdout: {stdout}");
+    validate_csv(output_file.path(), EXPECTED_2012);
+}
+
+#[test]
+fn test_e2e_validate_only_fails_on_errors() {
+    let xml = std::fs::read_to_string(manifest_dir().join(MODEL_2012_XML))
+        .unwrap()
+        .replacen("2022-09-09", "not a date", 1);
+    let input_file = tempfile::Builder::new().suffix(".xml").tempfile().unwrap();
+    std::fs::write(input_file.path(), xml).unwrap();
+    let result = validate_only(input_file.path());
+    let stdout = String::from_utf8_lossy(&result.stdout);
+    assert!(!result.status.success(), "stdout: {stdout}");
+    assert!(stdout.contains("Addresses: 2"), "stdout: {stdout}");
+    assert!(
+        stdout.contains("Addresses with errors: 1"),
+        "stdout: {stdout}"
+    );
+}
+
+/// Runs the conversion of the 2012 sample with an unknown tag in both addresses.
+fn run_with_unknown_tags(extra_args: &[&str]) -> std::process::Output {
+    let xml = std::fs::read_to_string(manifest_dir().join(MODEL_2012_XML))
+        .unwrap()
+        .replace(
+            "<prg-ad:status>istniejacy</prg-ad:status>",
+            "<prg-ad:status>istniejacy</prg-ad:status><prg-ad:nowyTag>x</prg-ad:nowyTag>",
+        );
+    let input_file = tempfile::Builder::new().suffix(".xml").tempfile().unwrap();
+    std::fs::write(input_file.path(), xml).unwrap();
+    let output_file = tempfile::Builder::new().suffix(".csv").tempfile().unwrap();
+    Command::new(bin())
+        .current_dir(manifest_dir())
+        .args(["--output-format", "csv", "--schema-version", "2012"])
+        .args(["--batch-size", "1"])
+        .arg("--input-paths")
+        .arg(input_file.path())
+        .arg("--output-path")
+        .arg(output_file.path())
+        .args(extra_args)
+        .output()
+        .expect("Failed to execute binary")
+}
+
+/// Output of converting the 2012 sample with an unknown tag in both addresses.
+fn convert_with_unknown_tags(extra_args: &[&str]) -> String {
+    let result = run_with_unknown_tags(extra_args);
+    assert!(
+        result.status.success(),
+        "stderr: {}",
+        String::from_utf8_lossy(&result.stderr)
+    );
+    String::from_utf8_lossy(&result.stdout).into_owned()
+}
+
+#[test]
+fn test_e2e_unknown_tags_summary() {
+    let expected = "ignored unknown tags inside addresses (occurrences): `prg-ad:nowyTag`";
+    for extra_args in [&[][..], &["--parallel-parse"]] {
+        let stdout = convert_with_unknown_tags(extra_args);
+        let summaries: Vec<&str> = stdout.lines().filter(|l| l.contains(expected)).collect();
+        assert_eq!(summaries.len(), 1, "stdout: {stdout}");
+        assert!(summaries[0].ends_with("(2)."), "stdout: {stdout}");
+    }
+    let stdout = convert_with_unknown_tags(&["--verbose-unknown-tags"]);
+    let summaries: Vec<&str> = stdout.lines().filter(|l| l.contains(expected)).collect();
+    assert_eq!(summaries, vec![format!("Warning: {expected} (1)."); 2]);
+}
+
+#[test]
+fn test_e2e_fail_on_unknown_tag() {
+    for extra_args in [
+        &["--fail-on-unknown-tag"][..],
+        &["--fail-on-unknown-tag", "--parallel-parse"],
+    ] {
+        let result = run_with_unknown_tags(extra_args);
+        let stderr = String::from_utf8_lossy(&result.stderr);
+        assert!(!result.status.success(), "stderr: {stderr}");
+        assert!(
+            stderr.contains("Unknown tag `prg-ad:nowyTag` inside address at byte offset"),
+            "stderr: {stderr}"
+        );
+    }
+}
+
+// --- CSV formatting ---
+
+/// Run the binary writing CSV to a temp file with `input_args` and `extra_args`.
+fn write_csv(input_args: &[&str], extra_args: &[&str]) -> tempfile::NamedTempFile {
+    let output_file = tempfile::Builder::new()
+        .suffix(".csv")
+        .tempfile()
+        .expect("Failed to create temp output file");
+    let result = Command::new(bin())
+        .current_dir(manifest_dir())
+        .args(["--output-format", "csv"])
+        .args(input_args)
+        .arg("--output-path")
+        .arg(output_file.path())
+        .args(extra_args)
+        .output()
+        .expect("Failed to execute binary");
+    assert!(
+        result.status.success(),
+        "stderr: {}",
+        String::from_utf8_lossy(&result.stderr)
+    );
+    output_file
+}
+
+#[test]
+fn test_e2e_schema2012_xml_csv_decimal_comma() {
+    let output_file = write_csv(
+        &["--schema-version", "2012", "--input-paths", MODEL_2012_XML],
+        &["--csv-delimiter", ";", "--decimal-comma"],
+    );
+    validate_csv(output_file.path(), EXPECTED_2012);
+    let content = std::fs::read_to_string(output_file.path()).unwrap();
+    let header: Vec<&str> = content.lines().next().unwrap().split(';').collect();
+    let x_idx = header.iter().position(|c| *c == "x_epsg_2180").unwrap();
+    for line in content.lines().skip(1) {
+        let x = line.split(';').nth(x_idx).unwrap();
+        assert!(x.contains(',') && !x.contains('.'), "x_epsg_2180: {x}");
+    }
+}
+
+#[test]
+fn test_e2e_decimal_comma_requires_other_delimiter() {
+    let result = Command::new(bin())
+        .current_dir(manifest_dir())
+        .args(["--schema-version", "2012", "--output-format", "csv"])
+        .args(["--input-paths", MODEL_2012_XML, "--decimal-comma"])
+        .args(["--output-path", "/tmp/prg_convert_decimal_comma.csv"])
+        .output()
+        .expect("Failed to execute binary");
+    assert!(!result.status.success());
+}
+
+/// Values of the first data row of the 2012 sample in CSV written with `extra_args`:
+/// `wersja_id`, `poczatek_wersji_obiektu` and `wazny_od_lub_data_nadania`.
+fn first_row_dates(extra_args: &[&str]) -> Vec<String> {
+    let output_file = write_csv(
+        &["--schema-version", "2012", "--input-paths", MODEL_2012_XML],
+        extra_args,
+    );
+    let content = std::fs::read_to_string(output_file.path()).unwrap();
+    content
+        .lines()
+        .nth(1)
+        .unwrap()
+        .split(',')
+        .skip(2)
+        .take(3)
+        .map(String::from)
+        .collect()
+}
+
+#[test]
+fn test_e2e_schema2012_xml_csv_date_formats() {
+    assert_eq!(
+        first_row_dates(&[]),
+        ["2022-09-09T16:18:16Z", "2022-09-09T18:18:16Z", "2022-09-09"]
+    );
+    assert_eq!(
+        first_row_dates(&[
+            "--date-format",
+            "%d.%m.%Y",
+            "--timestamp-format",
+            "%Y-%m-%d %H:%M:%S%:z"
+        ]),
+        [
+            "2022-09-09 16:18:16+00:00",
+            "2022-09-09 18:18:16+00:00",
+            "09.09.2022"
+        ]
+    );
+}
+
+#[test]
+fn test_e2e_schema2012_xml_csv_timestamp_tz_warsaw() {
+    assert_eq!(
+        first_row_dates(&["--timestamp-tz", "warsaw"]),
+        [
+            "2022-09-09T18:18:16+02:00",
+            "2022-09-09T20:18:16+02:00",
+            "2022-09-09"
+        ]
+    );
+}
+
+// --- Derived columns ---
+
+#[test]
+fn test_e2e_schema2021_xml_csv_normalize_names() {
+    run_with_args(
+        "2021",
+        "2180",
+        "csv",
+        MODEL_2021_XML,
+        EXPECTED_2021,
+        Some(TERYT_XML),
+        &["--normalize-names"],
+    );
+}
+
+#[test]
+fn test_e2e_schema2021_xml_geoparquet_normalize_names() {
+    let output_file = write_geoparquet_2021(&["--normalize-names"]);
+    validate_geoparquet(output_file.path(), EXPECTED_2021);
+
+    let file = std::fs::File::open(output_file.path()).unwrap();
+    let batches: Vec<_> = ParquetRecordBatchReaderBuilder::try_new(file)
+        .unwrap()
+        .build()
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    let batch = concat_batches(&batches[0].schema(), &batches).unwrap();
+    let city: &StringArray = batch
+        .column_by_name("miejscowosc_norm")
+        .expect("Expected miejscowosc_norm column")
+        .as_any()
+        .downcast_ref()
+        .unwrap();
+    let street: &StringArray = batch
+        .column_by_name("ulica_norm")
+        .expect("Expected ulica_norm column")
+        .as_any()
+        .downcast_ref()
+        .unwrap();
+    assert_eq!(city.value(0), "zubrow");
+    assert!(street.is_null(0));
+    assert_eq!(street.value(1), "inwalidow wojennych");
+}
+
+#[test]
+fn test_e2e_split_house_number() {
+    let output_file = write_csv(
+        &["--schema-version", "2012", "--input-paths", MODEL_2012_XML],
+        &["--split-house-number"],
+    );
+    validate_csv(output_file.path(), EXPECTED_2012);
+    let content = std::fs::read_to_string(output_file.path()).unwrap();
+    let mut lines = content.lines();
+    assert!(lines.next().unwrap().ends_with(",numer,numer_suffix"));
+    assert!(lines.next().unwrap().ends_with(",2,"));
+    assert!(lines.next().unwrap().ends_with(",1,"));
+}
+
+#[test]
+fn test_e2e_split_valid_from() {
+    let output_2012 = write_csv(
+        &["--schema-version", "2012", "--input-paths", MODEL_2012_XML],
+        &["--split-valid-from"],
+    );
+    let output_2021 = write_csv(
+        &["--schema-version", "2021", "--input-paths", MODEL_2021_XML],
+        &["--split-valid-from", "--teryt-path", TERYT_XML],
+    );
+    validate_csv(output_2012.path(), EXPECTED_2012);
+    validate_csv(output_2021.path(), EXPECTED_2021);
+    let content = std::fs::read_to_string(output_2012.path()).unwrap();
+    let mut lines = content.lines();
+    assert!(lines.next().unwrap().ends_with(",wazny_od,data_nadania"));
+    assert!(lines.next().unwrap().ends_with(",2022-09-09,"));
+    let content = std::fs::read_to_string(output_2021.path()).unwrap();
+    let mut lines = content.lines();
+    assert!(lines.next().unwrap().ends_with(",wazny_od,data_nadania"));
+    assert!(lines.next().unwrap().ends_with(",,2012-04-27"));
+}
+
+#[test]
+fn test_e2e_add_source_column() {
+    for (input, expected) in [
+        (MODEL_2012_XML, "sample_model2012.xml"),
+        (
+            PRG_ZIP,
+            "PRG-punkty_adresowe.zip/06.11.2025_20_06_55__08_lubuskie.xml",
+        ),
+    ] {
+        let output_file = write_csv(
+            &["--schema-version", "2012", "--input-paths", input],
+            &["--add-source-column"],
+        );
+        validate_csv(output_file.path(), EXPECTED_2012);
+        let content = std::fs::read_to_string(output_file.path()).unwrap();
+        let mut lines = content.lines();
+        assert!(lines.next().unwrap().ends_with(",zrodlo"));
+        for line in lines {
+            assert!(line.ends_with(&format!(",{}", expected)), "line: {line}");
+        }
+    }
+}
+
+#[test]
+fn test_e2e_add_full_address() {
+    let output_file = write_csv(
+        &["--schema-version", "2012", "--input-paths", MODEL_2012_XML],
+        &["--add-full-address"],
+    );
+    validate_csv(output_file.path(), EXPECTED_2012);
+    let content = std::fs::read_to_string(output_file.path()).unwrap();
+    let mut lines = content.lines();
+    assert!(lines.next().unwrap().ends_with(",pelny_adres"));
+    assert!(lines.next().unwrap().ends_with(
+        r#","ul. Podgórna 2, 67-416 Konotop, gmina Kolsko, powiat nowosolski, woj. lubuskie""#
+    ));
+}
+
+// --- Split ZIP archives ---
+
+#[test]
+fn test_e2e_split_zip() {
+    // the glob matches both volumes, `.z01` is read together with `.zip`;
+    // the schema version is detected from the joined archive
+    let output_file = write_csv(
+        &["--input-paths", "fixtures/sample_model2012_split.*"],
+        &["--add-source-column"],
+    );
+    let content = std::fs::read_to_string(output_file.path()).unwrap();
+    let rows: Vec<&str> = content.lines().skip(1).collect();
+    assert_eq!(rows.len(), 24);
+    assert_eq!(
+        rows.iter()
+            .filter(|l| l.ends_with(",sample_model2012_split.zip/sample_model2012_small.xml"))
+            .count(),
+        2
+    );
+}
+
+// --- Manifest ---
+
+/// Run the binary on the 2012 sample (2 addresses in voivodeship 08) with
+/// `--expect-counts` given as `expected` and return the output.
+fn expect_counts(expected: &str, extra_args: &[&str]) -> std::process::Output {
+    let mut counts_file = tempfile::Builder::new()
+        .suffix(".csv")
+        .tempfile()
+        .expect("Failed to create temp counts file");
+    std::io::Write::write_all(&mut counts_file, expected.as_bytes()).unwrap();
+    Command::new(bin())
+        .current_dir(manifest_dir())
+        .args(["--schema-version", "2012", "--input-paths", MODEL_2012_XML])
+        .arg("--validate-only")
+        .arg("--expect-counts")
+        .arg(counts_file.path())
+        .args(extra_args)
+        .output()
+        .expect("Failed to execute binary")
+}
+
+#[test]
+fn test_e2e_expect_counts() {
+    let result = expect_counts("teryt_wojewodztwo,count\n08,2\n", &[]);
+    assert!(
+        result.status.success(),
+        "stderr: {}",
+        String::from_utf8_lossy(&result.stderr)
+    );
+    let stdout = String::from_utf8_lossy(&result.stdout);
+    assert!(stdout.contains("Address counts match --expect-counts (1 voivodeships)."));
+
+    let result = expect_counts("08,3\n02,0\n", &[]);
+    assert!(!result.status.success());
+    let stdout = String::from_utf8_lossy(&result.stdout);
+    assert!(stdout.contains("voivodeship 08: expected 3, got 2"));
+    assert!(!stdout.contains("voivodeship 02"));
+    let stderr = String::from_utf8_lossy(&result.stderr);
+    assert!(
+        stderr.contains("for 1 of 2 voivodeships"),
+        "stderr: {}",
+        stderr
+    );
+
+    let result = expect_counts("08,3\n", &["--expect-counts-tolerance", "50"]);
+    assert!(
+        result.status.success(),
+        "stderr: {}",
+        String::from_utf8_lossy(&result.stderr)
+    );
+}
+
+// --- Comparing snapshots ---
+
+#[test]
+fn test_e2e_compare() {
+    // no differences from itself
+    let output_file = write_csv(
+        &["--schema-version", "2012", "--input-paths", MODEL_2012_XML],
+        &["--compare", MODEL_2012_XML],
+    );
+    // nothing is written to the CSV writer, not even the header
+    let content = std::fs::read_to_string(output_file.path()).unwrap();
+    assert!(content.is_empty());
+
+    // the first address gets another number, the second one another id
+    let xml = std::fs::read_to_string(manifest_dir().join(MODEL_2012_XML))
+        .unwrap()
+        .replace(
+            "<prg-ad:numerPorzadkowy>2</prg-ad:numerPorzadkowy>",
+            "<prg-ad:numerPorzadkowy>2a</prg-ad:numerPorzadkowy>",
+        )
+        .replace(
+            "5baa8bef-75ef-4241-a2fe-9d4137845693",
+            "00000000-0000-0000-0000-000000000001",
+        );
+    let new_snapshot = tempfile::Builder::new().suffix(".xml").tempfile().unwrap();
+    std::fs::write(new_snapshot.path(), xml).unwrap();
+    let new_path = new_snapshot.path().to_str().unwrap();
+    let output_file = write_csv(
+        &["--schema-version", "2012", "--input-paths", new_path],
+        &["--compare", MODEL_2012_XML],
+    );
+    let content = std::fs::read_to_string(output_file.path()).unwrap();
+    let mut changes: Vec<(&str, &str)> = content
+        .lines()
+        .skip(1)
+        .map(|line| {
+            let fields: Vec<&str> = line.split(',').collect();
+            (fields[1], *fields.last().unwrap())
+        })
+        .collect();
+    changes.sort();
+    assert_eq!(
+        changes,
+        [
+            ("00000000-0000-0000-0000-000000000001", "added"),
+            ("5baa8bef-75ef-4241-a2fe-9d4137845693", "removed"),
+            ("fd9c9319-0a6a-44b4-972a-1e6c4ec0d4ca", "modified"),
+        ]
+    );
+}
+
+#[test]
+fn test_e2e_schema2021_manifest() {
+    let output_file = tempfile::Builder::new()
+        .suffix(".parquet")
+        .tempfile()
+        .expect("Failed to create temp output file");
+    let manifest_file = tempfile::Builder::new()
+        .suffix(".json")
+        .tempfile()
+        .expect("Failed to create temp manifest file");
+    let result = Command::new(bin())
+        .current_dir(manifest_dir())
+        .args(["--schema-version", "2021", "--output-format", "geoparquet"])
+        .args(["--input-paths", MODEL_2021_XML, "--teryt-path", TERYT_ZIP])
+        .arg("--output-path")
+        .arg(output_file.path())
+        .arg("--manifest")
+        .arg(manifest_file.path())
+        .output()
+        .expect("Failed to execute binary");
+    assert!(
+        result.status.success(),
+        "stderr: {}",
+        String::from_utf8_lossy(&result.stderr)
+    );
+
+    let manifest: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(manifest_file.path()).unwrap()).unwrap();
+    assert_eq!(manifest["prg_convert_version"], env!("CARGO_PKG_VERSION"));
+    assert_eq!(manifest["schema_version"], "2021");
+    assert_eq!(manifest["terc_catalog_date"], "2025-01-01");
+    assert_eq!(manifest["output_format"], "geoparquet");
+    assert_eq!(manifest["crs"], "EPSG:2180");
+    assert_eq!(manifest["total_rows"], EXPECTED_2021.len());
+    assert_eq!(manifest["skipped_rows"], 0);
+    assert_eq!(manifest["inputs"][0]["path"], MODEL_2021_XML);
+    assert_eq!(manifest["inputs"][0]["file_type"], "XML");
+}
+
+// --- GeoParquet metadata ---
+
+/// Run the binary on the 2021 sample writing GeoParquet and return the output file.
+fn write_geoparquet_2021(extra_args: &[&str]) -> tempfile::NamedTempFile {
+    let output_file = tempfile::Builder::new()
+        .suffix(".parquet")
+        .tempfile()
+        .expect("Failed to create temp output file");
+    let result = Command::new(bin())
+        .current_dir(manifest_dir())
+        .args(["--schema-version", "2021", "--output-format", "geoparquet"])
+        .args(["--input-paths", MODEL_2021_XML, "--teryt-path", TERYT_XML])
+        .arg("--output-path")
+        .arg(output_file.path())
+        .args(extra_args)
+        .output()
+        .expect("Failed to execute binary");
+    assert!(
+        result.status.success(),
+        "stderr: {}",
+        String::from_utf8_lossy(&result.stderr)
+    );
+    output_file
+}
+
+/// Parse the `geo` key/value metadata of a GeoParquet file.
+fn read_geo_metadata(path: &Path) -> serde_json::Value {
+    let file = std::fs::File::open(path).expect("Failed to open GeoParquet file");
+    let builder =
+        ParquetRecordBatchReaderBuilder::try_new(file).expect("Failed to read parquet metadata");
+    let geo = builder
+        .metadata()
+        .file_metadata()
+        .key_value_metadata()
+        .and_then(|kv| kv.iter().find(|kv| kv.key == "geo"))
+        .and_then(|kv| kv.value.clone())
+        .expect("Expected `geo` key/value metadata");
+    serde_json::from_str(&geo).expect("`geo` metadata is not valid JSON")
+}
+
+#[test]
+fn test_e2e_geoparquet_bbox_covering() {
+    let output_file = write_geoparquet_2021(&[]);
+    let geo = read_geo_metadata(output_file.path());
+
+    let bbox = geo["columns"]["geometry"]["bbox"]
+        .as_array()
+        .expect("Expected dataset-level bbox");
+    assert_eq!(bbox.len(), 4);
+    let (xmin, ymin, xmax, ymax) = (
+        bbox[0].as_f64().unwrap(),
+        bbox[1].as_f64().unwrap(),
+        bbox[2].as_f64().unwrap(),
+        bbox[3].as_f64().unwrap(),
+    );
+    for exp in EXPECTED_2021 {
+        assert!(xmin <= exp.x_epsg_2180 && exp.x_epsg_2180 <= xmax);
+        assert!(ymin <= exp.y_epsg_2180 && exp.y_epsg_2180 <= ymax);
+    }
+    assert_eq!(
+        geo["columns"]["geometry"]["covering"]["bbox"]["xmin"],
+        serde_json::json!(["bbox", "xmin"])
+    );
+
+    // The covering column has min/max statistics in every row group.
+    let file = std::fs::File::open(output_file.path()).unwrap();
+    let metadata = ParquetRecordBatchReaderBuilder::try_new(file)
+        .unwrap()
+        .metadata()
+        .clone();
+    for row_group in metadata.row_groups() {
+        let xmin_column = row_group
+            .columns()
+            .iter()
+            .find(|c| c.column_path().string() == "bbox.xmin")
+            .expect("Expected bbox.xmin covering column");
+        assert!(xmin_column.statistics().is_some());
+    }
+}
+
+#[test]
+fn test_e2e_geoparquet_flush_every() {
+    let output_file = write_geoparquet_2021(&[
+        "--batch-size",
+        "1",
+        "--parquet-row-group-size",
+        "100",
+        "--flush-every",
+        "2",
+    ]);
+    validate_geoparquet(output_file.path(), EXPECTED_2021);
+    let file = std::fs::File::open(output_file.path()).unwrap();
+    let metadata = ParquetRecordBatchReaderBuilder::try_new(file)
+        .unwrap()
+        .metadata()
+        .clone();
+    let row_group_sizes: Vec<i64> = metadata
+        .row_groups()
+        .iter()
+        .map(|row_group| row_group.num_rows())
+        .collect();
+    assert_eq!(row_group_sizes, vec![2, 1]);
+}
+
+#[test]
+fn test_e2e_geoparquet_geometry_encoding() {
+    let output_file = write_geoparquet_2021(&[]);
+    let geo = read_geo_metadata(output_file.path());
+    assert_eq!(geo["columns"]["geometry"]["encoding"], "WKB");
+
+    let output_file = write_geoparquet_2021(&["--geometry-encoding", "native"]);
+    let geo = read_geo_metadata(output_file.path());
+    assert_eq!(geo["columns"]["geometry"]["encoding"], "point");
+    validate_geoparquet(output_file.path(), EXPECTED_2021);
+}
+
+// --- GML ---
+
+#[test]
+fn test_e2e_schema2021_xml_gml() {
+    let output_file = tempfile::Builder::new()
+        .suffix(".gml")
+        .tempfile()
+        .expect("Failed to create temp output file");
+    let result = Command::new(bin())
+        .current_dir(manifest_dir())
+        .args(["--schema-version", "2021", "--output-format", "gml"])
+        .args(["--input-paths", MODEL_2021_XML, "--teryt-path", TERYT_XML])
+        .arg("--output-path")
+        .arg(output_file.path())
+        .output()
+        .expect("Failed to execute binary");
+    assert!(
+        result.status.success(),
+        "stderr: {}",
+        String::from_utf8_lossy(&result.stderr)
+    );
+    let gml = std::fs::read_to_string(output_file.path()).unwrap();
+    assert_eq!(
+        gml.matches("<gml:featureMember>").count(),
+        EXPECTED_2021.len()
+    );
+    for exp in EXPECTED_2021 {
+        assert!(
+            gml.contains(&format!(
+                "<prg:lokalny_id>{}</prg:lokalny_id>",
+                exp.lokalny_id
+            )),
+            "{gml}"
+        );
+        // y x (northing, easting), the axis order of EPSG:2180 used by PRG
+        let pos = gml
+            .split(&format!("{}.geometria", exp.lokalny_id))
+            .nth(1)
+            .and_then(|rest| rest.split("<gml:pos>").nth(1))
+            .and_then(|rest| rest.split("</gml:pos>").next())
+            .expect("Expected gml:pos of the address");
+        let coords: Vec<f64> = pos.split(' ').map(|c| c.parse().unwrap()).collect();
+        assert!((coords[0] - exp.y_epsg_2180).abs() < COORD_TOLERANCE_2180);
+        assert!((coords[1] - exp.x_epsg_2180).abs() < COORD_TOLERANCE_2180);
+    }
+}
+
+// --- Plain parquet ---
+
+#[test]
+fn test_e2e_schema2012_xml_parquet() {
+    let output_file = tempfile::Builder::new()
+        .suffix(".parquet")
+        .tempfile()
+        .expect("Failed to create temp output file");
+    let result = Command::new(bin())
+        .current_dir(manifest_dir())
+        .args(["--schema-version", "2012", "--output-format", "parquet"])
+        .args(["--input-paths", MODEL_2012_XML])
+        .args(["--batch-size", "1", "--flush-every", "1"])
+        .arg("--output-path")
+        .arg(output_file.path())
+        .output()
+        .expect("Failed to execute binary");
+    assert!(
+        result.status.success(),
+        "stderr: {}",
+        String::from_utf8_lossy(&result.stderr)
+    );
+
+    let file = std::fs::File::open(output_file.path()).expect("Failed to open parquet file");
+    let builder =
+        ParquetRecordBatchReaderBuilder::try_new(file).expect("Failed to read parquet metadata");
+    let has_geo_metadata = builder
+        .metadata()
+        .file_metadata()
+        .key_value_metadata()
+        .is_some_and(|kv| kv.iter().any(|kv| kv.key == "geo"));
+    assert!(!has_geo_metadata);
+    assert_eq!(builder.metadata().num_row_groups(), EXPECTED_2012.len());
+    let csv_schema = print_schema(&["--schema-version", "2012", "--output-format", "csv"]);
+    let written = builder.schema().clone();
+    assert_eq!(written.fields(), csv_schema.fields());
+
+    let batches: Vec<_> = builder
+        .build()
+        .expect("Failed to build parquet reader")
+        .collect::<Result<_, _>>()
+        .expect("Failed to read parquet batches");
+    let batch = concat_batches(&written, &batches).unwrap();
+    assert_eq!(batch.num_rows(), EXPECTED_2012.len());
+    let x = batch
+        .column_by_name("x_epsg_2180")
+        .unwrap()
+        .as_any()
+        .downcast_ref::<Float64Array>()
+        .unwrap();
+    let lon = batch
+        .column_by_name("dlugosc_geograficzna")
+        .unwrap()
+        .as_any()
+        .downcast_ref::<Float64Array>()
+        .unwrap();
+    for (i, exp) in EXPECTED_2012.iter().enumerate() {
+        assert!((x.value(i) - exp.x_epsg_2180).abs() < 1e-6);
+        assert!((lon.value(i) - exp.lon).abs() < 1e-6);
+    }
+}
+
+#[test]
+fn test_e2e_list_zip_json() {
+    let result = Command::new(bin())
+        .current_dir(manifest_dir())
+        .args(["--list-zip", PRG_ZIP, "--format", "json"])
+        .output()
+        .expect("Failed to execute binary");
+    assert!(
+        result.status.success(),
+        "stderr: {}",
+        String::from_utf8_lossy(&result.stderr)
+    );
+    // nothing but the listing is printed
+    let entries: serde_json::Value =
+        serde_json::from_slice(&result.stdout).expect("Listing is not valid JSON");
+    let entries = entries.as_array().unwrap();
+    assert_eq!(entries.len(), 3);
+    assert_eq!(entries[0]["path"], PRG_ZIP);
+    assert_eq!(entries[0]["index"], 0);
+    assert_eq!(entries[0]["name"], "06.11.2025_20_06_55__08_lubuskie.xml");
+    assert_eq!(entries[0]["compressed_size"], 2138);
+    assert_eq!(entries[0]["uncompressed_size"], 15398);
+    // schema 2012 detected from the archive
+    assert_eq!(entries[0]["to_be_parsed"], true);
+    assert_eq!(entries[1]["to_be_parsed"], false);
+}
+
+/// Run the binary with `--print-schema` and parse the printed arrow schema.
+fn print_schema(args: &[&str]) -> arrow_schema::Schema {
+    let result = Command::new(bin())
+        .current_dir(manifest_dir())
+        .arg("--print-schema")
+        .args(args)
+        .output()
+        .expect("Failed to execute binary");
+    assert!(
+        result.status.success(),
+        "stderr: {}",
+        String::from_utf8_lossy(&result.stderr)
+    );
+    serde_json::from_slice(&result.stdout).expect("Printed schema is not valid JSON")
+}
+
+#[test]
+fn test_e2e_print_schema_matches_geoparquet_output() {
+    let extra_args = ["--crs-epsg", "4326", "--normalize-names"];
+    let output_file = write_geoparquet_2021(&extra_args);
+    let file = std::fs::File::open(output_file.path()).expect("Failed to open GeoParquet file");
+    let builder =
+        ParquetRecordBatchReaderBuilder::try_new(file).expect("Failed to read parquet metadata");
+
+    let mut args = vec!["--schema-version", "2021", "--output-format", "geoparquet"];
+    args.extend(extra_args);
+    let printed = print_schema(&args);
+    let written = builder.schema();
+    assert_eq!(printed.fields().len(), written.fields().len());
+    for (printed, written) in printed.fields().iter().zip(written.fields()) {
+        assert_eq!(printed.name(), written.name());
+        assert_eq!(printed.data_type(), written.data_type());
+        assert_eq!(printed.is_nullable(), written.is_nullable());
+    }
+}
+
+#[test]
+fn test_e2e_print_schema_csv() {
+    let schema = print_schema(&["--schema-version", "2012", "--output-format", "csv"]);
+    assert_eq!(schema.fields().len(), 25);
+    assert!(schema.field_with_name("x_epsg_2180").is_ok());
+    assert!(schema.field_with_name("miejscowosc_norm").is_err());
+
+    let schema = print_schema(&[
+        "--schema-version",
+        "2012",
+        "--output-format",
+        "csv",
+        "--normalize-names",
+    ]);
+    assert_eq!(schema.fields().len(), 27);
+    assert!(schema.field_with_name("miejscowosc_norm").is_ok());
+}
+
+// --- Renamed columns ---
+
+#[test]
+fn test_e2e_csv_rename_columns() {
+    let output_file = write_csv(
+        &["--schema-version", "2012", "--input-paths", MODEL_2012_XML],
+        &["--rename-columns", "przestrzen_nazw=namespace,ulica=street"],
+    );
+    let content = std::fs::read_to_string(output_file.path()).unwrap();
+    let header: Vec<&str> = content.lines().next().unwrap().split(',').collect();
+    assert_eq!(header[0], "namespace");
+    assert!(header.contains(&"street"));
+    assert!(!header.contains(&"ulica"));
+    assert_eq!(content.lines().count(), EXPECTED_2012.len() + 1);
+}
+
+#[test]
+fn test_e2e_geoparquet_rename_geometry_column() {
+    let output_file = write_geoparquet_2021(&["--rename-columns", "geometry=geom,ulica=street"]);
+    let geo = read_geo_metadata(output_file.path());
+    assert_eq!(geo["primary_column"], "geom");
+    assert_eq!(geo["columns"]["geom"]["encoding"], "WKB");
+    let file = std::fs::File::open(output_file.path()).unwrap();
+    let schema = ParquetRecordBatchReaderBuilder::try_new(file)
+        .unwrap()
+        .schema()
+        .clone();
+    assert!(schema.field_with_name("geom").is_ok());
+    assert!(schema.field_with_name("street").is_ok());
+    assert!(schema.field_with_name("geometry").is_err());
+}
+
+#[test]
+fn test_e2e_geoparquet_timestamp_tz_warsaw() {
+    let output_file = write_geoparquet_2021(&["--timestamp-tz", "warsaw"]);
+    let file = std::fs::File::open(output_file.path()).unwrap();
+    let batches: Vec<_> = ParquetRecordBatchReaderBuilder::try_new(file)
+        .unwrap()
+        .build()
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    let column = batches[0].column_by_name("wersja_id").unwrap();
+    assert_eq!(
+        column.data_type(),
+        &arrow::datatypes::DataType::Timestamp(
+            arrow::datatypes::TimeUnit::Millisecond,
+            Some("Europe/Warsaw".into())
+        )
+    );
+    assert_eq!(
+        arrow::util::display::array_value_to_string(column, 0).unwrap(),
+        "2025-10-14T14:05:46+02:00"
+    );
+}
+
+#[test]
+fn test_e2e_rename_unknown_column_fails() {
+    let result = Command::new(bin())
+        .current_dir(manifest_dir())
+        .args(["--schema-version", "2012", "--output-format", "csv"])
+        .args(["--input-paths", MODEL_2012_XML])
+        .args(["--output-path", "/tmp/prg_convert_rename_unknown.csv"])
+        .args(["--rename-columns", "street=ulica"])
+        .output()
+        .expect("Failed to execute binary");
+    assert!(!result.status.success());
+    assert!(
+        String::from_utf8_lossy(&result.stderr).contains("Unknown column(s) to rename: street.")
+    );
+}
+
+// --- Bad ZIP entries ---
+
+/// ZIP archive with two copies of the 2012 sample, `a.xml` intact and
+/// `b.xml` with corrupted content (so its checksum does not match).
+fn zip_with_bad_entry() -> tempfile::NamedTempFile {
+    use std::io::Write;
+
+    let zip_file = tempfile::Builder::new()
+        .suffix(".zip")
+        .tempfile()
+        .expect("Failed to create temp zip file");
+    let content = std::fs::read(manifest_dir().join(MODEL_2012_XML)).unwrap();
+    let options =
+        zip::write::SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored);
+    let mut writer = zip::ZipWriter::new(zip_file.reopen().unwrap());
+    for name in ["a.xml", "b.xml"] {
+        writer.start_file(name, options).unwrap();
+        writer.write_all(&content).unwrap();
+    }
+    writer.finish().unwrap();
+
+    let mut bytes = std::fs::read(zip_file.path()).unwrap();
+    let needle = b"<gml:pos>";
+    let pos = bytes
+        .windows(needle.len())
+        .rposition(|w| w == needle)
+        .unwrap();
+    bytes[pos + 5] = b'X';
+    std::fs::write(zip_file.path(), bytes).unwrap();
+    zip_file
+}
+
+#[test]
+fn test_e2e_bad_zip_entry_fails_by_default() {
+    let zip_file = zip_with_bad_entry();
+    let result = Command::new(bin())
+        .current_dir(manifest_dir())
+        .args(["--schema-version", "2012", "--output-format", "csv"])
+        .arg("--input-paths")
+        .arg(zip_file.path())
+        .args(["--output-path", "/tmp/prg_convert_bad_zip_entry.csv"])
+        .output()
+        .expect("Failed to execute binary");
+    assert!(!result.status.success());
+}
+
+#[test]
+fn test_e2e_skip_bad_zip_entries() {
+    let zip_file = zip_with_bad_entry();
+    let output_file = tempfile::Builder::new()
+        .suffix(".csv")
+        .tempfile()
+        .expect("Failed to create temp output file");
+    let result = Command::new(bin())
+        .current_dir(manifest_dir())
+        .args(["--schema-version", "2012", "--output-format", "csv"])
+        .arg("--input-paths")
+        .arg(zip_file.path())
+        .arg("--output-path")
+        .arg(output_file.path())
+        .arg("--skip-bad-entries")
+        .output()
+        .expect("Failed to execute binary");
+    let stdout = String::from_utf8_lossy(&result.stdout);
+    assert!(
+        result.status.success(),
+        "stderr: {}",
+        String::from_utf8_lossy(&result.stderr)
+    );
+    assert_eq!(parse_row_count(&stdout), EXPECTED_2012.len());
+    assert!(stdout.contains("Files inside ZIP archives skipped because of errors: 1."));
+    assert!(stdout.contains("`b.xml` in"));
+    validate_csv(output_file.path(), EXPECTED_2012);
+}
+
+#[test]
+fn test_e2e_rows_per_file() {
+    let output_dir = tempfile::tempdir().expect("Failed to create temp output dir");
+    let result = Command::new(bin())
+        .current_dir(manifest_dir())
+        .args(["--schema-version", "2012", "--output-format", "csv"])
+        .args(["--input-paths", MODEL_2012_XML])
+        .arg("--output-path")
+        .arg(output_dir.path().join("part-{n}.csv"))
+        .args(["--rows-per-file", "1"])
+        .output()
+        .expect("Failed to execute binary");
+    let stdout = String::from_utf8_lossy(&result.stdout);
+    assert!(
+        result.status.success(),
+        "stderr: {}",
+        String::from_utf8_lossy(&result.stderr)
+    );
+    for (i, expected) in EXPECTED_2012.chunks(1).enumerate() {
+        let path = output_dir.path().join(format!("part-{:05}.csv", i + 1));
+        assert!(stdout.contains(path.to_string_lossy().as_ref()));
+        validate_csv(&path, expected);
+    }
+    assert_eq!(
+        std::fs::read_dir(output_dir.path()).unwrap().count(),
+        EXPECTED_2012.len()
+    );
+}